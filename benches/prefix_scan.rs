@@ -0,0 +1,58 @@
+// Measures `get_relationships_by_source`, a prefix scan over the 'indices' cabinet, with and
+// without `StorageOptions::prefix_extractors`. Run with `cargo bench --bench prefix_scan`.
+//
+// With the extractor and a bloom filter configured, RocksDB skips SST files that hold no keys
+// for the scanned concept. The gap only shows once data has been flushed to several files,
+// which is why the setup writes well past one memtable.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use mnemonic_core::storage::{ColumnFamilyTuning, RocksBackend, StorageOptions};
+use mnemonic_core::types::{concept::Concept, relationship::Relationship};
+use serde_json::json;
+use std::hint::black_box;
+use tempfile::tempdir;
+
+const CONCEPTS: usize = 2_000;
+const EDGES_PER_CONCEPT: usize = 5;
+
+fn populated(options: &StorageOptions) -> (tempfile::TempDir, RocksBackend, Vec<Concept>) {
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::with_options(dir.path(), options).unwrap();
+    let concepts: Vec<Concept> = (0..CONCEPTS).map(|n| Concept::new(json!({ "n": n }))).collect();
+    for concept in &concepts {
+        backend.store_concept(concept).unwrap();
+    }
+    for (n, source) in concepts.iter().enumerate() {
+        for k in 1..=EDGES_PER_CONCEPT {
+            let target = &concepts[(n + k) % CONCEPTS];
+            let relationship = Relationship::new(source.id, "links".to_string(), target.id);
+            backend.store_relationship(&relationship).unwrap();
+        }
+    }
+    (dir, backend, concepts)
+}
+
+fn bench_prefix_scans(c: &mut Criterion) {
+    let tuned = StorageOptions {
+        prefix_extractors: true,
+        indices: ColumnFamilyTuning {
+            bloom_filter_bits: Some(10.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    for (name, options) in [("default", StorageOptions::default()), ("prefix_extractor", tuned)] {
+        let (_dir, backend, concepts) = populated(&options);
+        let mut next = 0;
+        c.bench_function(&format!("relationships_by_source/{}", name), |b| {
+            b.iter(|| {
+                next = (next + 1) % CONCEPTS;
+                black_box(backend.get_relationships_by_source(&concepts[next].id).unwrap())
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_prefix_scans);
+criterion_main!(benches);
@@ -25,7 +25,7 @@ const AddConceptForm: React.FC<AddConceptFormProps> = ({ onConceptAdded }) => {
     setStatusMessage('Creating concept...');
 
     try {
-      const backendUrl = import.meta.env.VITE_BACKEND_URL || 'http://localhost:8080';
+      const backendUrl = import.meta.env.VITE_BACKEND_URL || '/api/v1';
       const apiClient = axios.create({ baseURL: backendUrl });
 
       // Send the POST request to our Rust backend.
@@ -106,7 +106,7 @@ const GraphCanvas: React.FC<GraphCanvasProps> = ({ onNodeClick }) => {
     useEffect(() => {
         const fetchGraphData = async () => {
             try {
-                const backendUrl = import.meta.env.VITE_BACKEND_URL || 'http://localhost:8080';
+                const backendUrl = import.meta.env.VITE_BACKEND_URL || '/api/v1';
                 const response = await axios.get<GraphData>(`${backendUrl}/graph`);
                 setGraphData(response.data);
                 setStatus('Data loaded successfully.');
@@ -22,7 +22,7 @@ const InspectorSidebar: React.FC<InspectorSidebarProps> = ({ conceptId }) => {
         const fetchConcept = async () => {
             setIsLoading(true);
             try {
-                const backendUrl = import.meta.env.VITE_BACKEND_URL || 'http://localhost:8080';
+                const backendUrl = import.meta.env.VITE_BACKEND_URL || '/api/v1';
                 const response = await axios.get(`${backendUrl}/concepts/${conceptId}`);
                 setConcept(response.data);
             } catch (error) {
@@ -4,4 +4,11 @@ import react from '@vitejs/plugin-react-swc'
 // https://vite.dev/config/
 export default defineConfig({
   plugins: [react()],
+  // The dev server hands API calls to a local mre, so the UI sees one origin as it does when
+  // mre serves it (the `ui` feature).
+  server: {
+    proxy: {
+      '/api': 'http://localhost:8080',
+    },
+  },
 })
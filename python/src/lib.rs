@@ -0,0 +1,395 @@
+// Python bindings for an embedded Mnemonic graph.
+//
+// `Mnemonic` wraps a `GraphEngine` and the Tokio runtime it runs its work on. Each method
+// converts its arguments on the calling thread, then lets go of the GIL while the engine
+// works, so Python threads using the same graph (or anything else) aren't held up by it.
+// Concept data crosses the boundary as plain dicts and lists; IDs as `uuid.UUID`; timestamps
+// as timezone-aware `datetime`s in UTC.
+
+use chrono::{DateTime, FixedOffset, Utc};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDateTime, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple};
+use serde_json::{Map, Number, Value};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+use mnemonic_core::graph::GraphEngine;
+use mnemonic_core::types::concept::{Concept, ConceptVersion};
+use mnemonic_core::types::query::parse_data;
+use mnemonic_core::types::relationship::Relationship;
+
+create_exception!(mnemonic, MnemonicError, PyException, "An error reported by the Mnemonic engine.");
+
+/// An embedded graph stored in a directory on disk.
+#[pyclass(module = "mnemonic", frozen)]
+struct Mnemonic {
+    engine: Arc<GraphEngine>,
+    runtime: Runtime,
+}
+
+#[pymethods]
+impl Mnemonic {
+    /// Opens the graph at `path`, creating it if there's nothing there yet.
+    #[staticmethod]
+    fn open(py: Python<'_>, path: PathBuf) -> PyResult<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| MnemonicError::new_err(e.to_string()))?;
+        let engine = py.allow_threads(|| GraphEngine::new(&path)).map_err(engine_error)?;
+        Ok(Self { engine: Arc::new(engine), runtime })
+    }
+
+    /// Stores `data` as a new concept and returns its ID.
+    fn store<'py>(&self, py: Python<'py>, data: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyAny>> {
+        let data = py_to_json(data.as_any())?;
+        let id = self.run(py, self.engine.store(data))?;
+        uuid_to_py(py, id)
+    }
+
+    /// The data of the concept `id`, or `None` if there's no such concept.
+    fn get<'py>(&self, py: Python<'py>, id: &Bound<'py, PyAny>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let id = uuid_from_py(id)?;
+        self.run(py, self.engine.get_concept(id))?
+            .map(|concept| json_to_py(py, &parse_data(&concept.data)))
+            .transpose()
+    }
+
+    /// Relates `source` to `target` and returns the new relationship's ID.
+    fn relate<'py>(
+        &self,
+        py: Python<'py>,
+        source: &Bound<'py, PyAny>,
+        r#type: String,
+        target: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let (source, target) = (uuid_from_py(source)?, uuid_from_py(target)?);
+        let id = self.run(py, self.engine.relate(source, r#type, target))?;
+        uuid_to_py(py, id)
+    }
+
+    /// The live relationships matching every filter given. An undirected relationship
+    /// matches with its ends either way round.
+    #[pyo3(signature = (source=None, r#type=None, target=None))]
+    fn retrieve<'py>(
+        &self,
+        py: Python<'py>,
+        source: Option<&Bound<'py, PyAny>>,
+        r#type: Option<String>,
+        target: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let source = source.map(uuid_from_py).transpose()?;
+        let target = target.map(uuid_from_py).transpose()?;
+        let engine = &self.engine;
+        let relationship_type = r#type.clone();
+        let relationships = self.run(py, async move {
+            // Starting from an end's index when there is one, rather than the whole graph.
+            let mut relationships = match (source, relationship_type, target) {
+                // The commonest question, "the `member_of` edges leaving this node", has its own index.
+                (Some(source), Some(relationship_type), _) => {
+                    engine.retrieve_by_source_and_type(source, relationship_type).await?
+                }
+                (Some(source), None, _) => {
+                    let mut found = engine.retrieve_by_source(source).await?;
+                    found.extend(engine.retrieve_by_target(source).await?);
+                    found
+                }
+                (None, _, Some(target)) => {
+                    let mut found = engine.retrieve_by_target(target).await?;
+                    found.extend(engine.retrieve_by_source(target).await?);
+                    found
+                }
+                (None, _, None) => engine.snapshot().await?.relationships,
+            };
+            relationships.sort_by_key(|relationship| relationship.id);
+            relationships.dedup_by_key(|relationship| relationship.id);
+            Ok(relationships)
+        })?;
+        relationships
+            .iter()
+            .filter(|relationship| {
+                r#type.as_deref().is_none_or(|t| relationship.relationship_type.as_str() == t)
+                    && ends_match(relationship, source, target)
+            })
+            .map(|relationship| relationship_to_py(py, relationship))
+            .collect()
+    }
+
+    /// Every version of the concept `id` still held, oldest first, deletions included.
+    fn history<'py>(&self, py: Python<'py>, id: &Bound<'py, PyAny>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let id = uuid_from_py(id)?;
+        self.run(py, self.engine.concept_history(id))?
+            .iter()
+            .map(|version| version_to_py(py, version))
+            .collect()
+    }
+
+    /// The graph as it stood at `timestamp`, as `{"concepts": [...], "relationships": [...]}`.
+    /// A naive `datetime` is rejected rather than guessed at.
+    fn graph_at<'py>(&self, py: Python<'py>, timestamp: DateTime<FixedOffset>) -> PyResult<Bound<'py, PyDict>> {
+        let snapshot = self.run(py, self.engine.snapshot_at(timestamp.with_timezone(&Utc)))?;
+        let concepts = snapshot
+            .concepts
+            .iter()
+            .map(|concept| concept_to_py(py, concept))
+            .collect::<PyResult<Vec<_>>>()?;
+        let relationships = snapshot
+            .relationships
+            .iter()
+            .map(|relationship| relationship_to_py(py, relationship))
+            .collect::<PyResult<Vec<_>>>()?;
+        let graph = PyDict::new(py);
+        graph.set_item("concepts", concepts)?;
+        graph.set_item("relationships", relationships)?;
+        Ok(graph)
+    }
+}
+
+impl Mnemonic {
+    /// Runs an engine call to completion with the GIL released.
+    fn run<T: Send>(
+        &self,
+        py: Python<'_>,
+        work: impl Future<Output = mnemonic_core::Result<T>> + Send,
+    ) -> PyResult<T> {
+        py.allow_threads(|| self.runtime.block_on(work)).map_err(engine_error)
+    }
+}
+
+fn engine_error(error: mnemonic_core::MnemonicError) -> PyErr {
+    MnemonicError::new_err(error.to_string())
+}
+
+fn ends_match(relationship: &Relationship, source: Option<Uuid>, target: Option<Uuid>) -> bool {
+    let matches = |from: Uuid, to: Uuid| source.is_none_or(|s| s == from) && target.is_none_or(|t| t == to);
+    matches(relationship.source, relationship.target)
+        || (!relationship.directed && matches(relationship.target, relationship.source))
+}
+
+/// An ID as a `uuid.UUID`.
+fn uuid_to_py(py: Python<'_>, id: Uuid) -> PyResult<Bound<'_, PyAny>> {
+    py.import("uuid")?.getattr("UUID")?.call1((id.to_string(),))
+}
+
+/// An ID from a `uuid.UUID` or its string form.
+fn uuid_from_py(value: &Bound<'_, PyAny>) -> PyResult<Uuid> {
+    let text = value.str()?;
+    let text = text.to_cow()?;
+    Uuid::parse_str(&text).map_err(|_| PyValueError::new_err(format!("'{text}' is not a UUID")))
+}
+
+fn concept_to_py<'py>(py: Python<'py>, concept: &Concept) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", uuid_to_py(py, concept.id)?)?;
+    dict.set_item("data", json_to_py(py, &parse_data(&concept.data))?)?;
+    dict.set_item("version", concept.metadata.version)?;
+    dict.set_item("created_at", concept.metadata.created_at)?;
+    dict.set_item("updated_at", concept.metadata.updated_at)?;
+    dict.set_item("expires_at", concept.expires_at)?;
+    Ok(dict)
+}
+
+fn relationship_to_py<'py>(py: Python<'py>, relationship: &Relationship) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", uuid_to_py(py, relationship.id)?)?;
+    dict.set_item("source", uuid_to_py(py, relationship.source)?)?;
+    dict.set_item("type", relationship.relationship_type.as_str())?;
+    dict.set_item("target", uuid_to_py(py, relationship.target)?)?;
+    dict.set_item("directed", relationship.directed)?;
+    dict.set_item("created_at", relationship.metadata.created_at)?;
+    Ok(dict)
+}
+
+fn version_to_py<'py>(py: Python<'py>, version: &ConceptVersion) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("version", version.version)?;
+    dict.set_item("data", json_to_py(py, &parse_data(&version.data))?)?;
+    dict.set_item("created_at", version.created_at)?;
+    dict.set_item("deleted_at", version.deleted_at)?;
+    dict.set_item("expires_at", version.expires_at)?;
+    Ok(dict)
+}
+
+/// A JSON value as the Python object `json.loads` would give.
+fn json_to_py<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+            (None, None) => n.as_f64().unwrap_or_default().into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any(),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(json_to_py(py, item)?)?;
+            }
+            list.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, field) in fields {
+                dict.set_item(key, json_to_py(py, field)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+/// A Python object as JSON: dicts with string keys, lists and tuples, strings, numbers,
+/// booleans and `None`. A timezone-aware `datetime` is stored as its RFC 3339 string.
+fn py_to_json(value: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if value.is_none() {
+        return Ok(Value::Null);
+    }
+    // Before `int`, which `bool` is a subclass of.
+    if let Ok(b) = value.downcast::<PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if let Ok(i) = value.downcast::<PyInt>() {
+        return match i.extract::<i64>() {
+            Ok(i) => Ok(i.into()),
+            Err(_) => Ok(i.extract::<u64>()?.into()),
+        };
+    }
+    if let Ok(f) = value.downcast::<PyFloat>() {
+        return Number::from_f64(f.value())
+            .map(Value::Number)
+            .ok_or_else(|| PyValueError::new_err(format!("{} can't be stored as JSON", f.value())));
+    }
+    if let Ok(s) = value.downcast::<PyString>() {
+        return Ok(Value::String(s.to_cow()?.into_owned()));
+    }
+    if let Ok(dict) = value.downcast::<PyDict>() {
+        let mut fields = Map::new();
+        for (key, field) in dict.iter() {
+            let key = key
+                .downcast::<PyString>()
+                .map_err(|_| PyTypeError::new_err(format!("dict keys must be strings, not {}", key.get_type())))?;
+            fields.insert(key.to_cow()?.into_owned(), py_to_json(&field)?);
+        }
+        return Ok(Value::Object(fields));
+    }
+    if let Ok(list) = value.downcast::<PyList>() {
+        return list.iter().map(|item| py_to_json(&item)).collect::<PyResult<_>>().map(Value::Array);
+    }
+    if let Ok(tuple) = value.downcast::<PyTuple>() {
+        return tuple.iter().map(|item| py_to_json(&item)).collect::<PyResult<_>>().map(Value::Array);
+    }
+    if value.is_instance_of::<PyDateTime>() {
+        let timestamp: DateTime<FixedOffset> = value.extract()?;
+        return Ok(Value::String(timestamp.with_timezone(&Utc).to_rfc3339()));
+    }
+    Err(PyTypeError::new_err(format!("{} can't be stored as JSON", value.get_type())))
+}
+
+#[pymodule]
+fn mnemonic(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Mnemonic>()?;
+    m.add("MnemonicError", m.py().get_type::<MnemonicError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn with_python(test: impl FnOnce(Python<'_>)) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(test);
+    }
+
+    #[test]
+    fn test_json_round_trips_through_python_objects() {
+        with_python(|py| {
+            let value = json!({
+                "name": "Ada",
+                "born": 1815,
+                "big": u64::MAX,
+                "height": 1.65,
+                "alive": false,
+                "spouse": null,
+                "languages": ["English", {"notation": true}],
+            });
+            let object = json_to_py(py, &value).unwrap();
+            assert!(object.downcast::<PyDict>().unwrap().get_item("alive").unwrap().unwrap().is_instance_of::<PyBool>());
+            assert_eq!(py_to_json(&object).unwrap(), value);
+        });
+    }
+
+    #[test]
+    fn test_python_only_values_are_converted_or_refused() {
+        with_python(|py| {
+            let tuple = PyTuple::new(py, [1, 2]).unwrap();
+            assert_eq!(py_to_json(tuple.as_any()).unwrap(), json!([1, 2]));
+
+            let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+            let datetime = at.into_pyobject(py).unwrap();
+            assert_eq!(py_to_json(datetime.as_any()).unwrap(), json!(at.to_rfc3339()));
+
+            let nan = f64::NAN.into_pyobject(py).unwrap();
+            assert!(py_to_json(nan.as_any()).unwrap_err().is_instance_of::<PyValueError>(py));
+            let keyed = PyDict::new(py);
+            keyed.set_item(1, "one").unwrap();
+            assert!(py_to_json(keyed.as_any()).unwrap_err().is_instance_of::<PyTypeError>(py));
+            let set = py.eval(c"{1, 2}", None, None).unwrap();
+            assert!(py_to_json(&set).unwrap_err().is_instance_of::<PyTypeError>(py));
+        });
+    }
+
+    #[test]
+    fn test_uuids_are_accepted_as_uuid_objects_or_strings() {
+        with_python(|py| {
+            let id = Uuid::new_v4();
+            let object = uuid_to_py(py, id).unwrap();
+            assert_eq!(object.get_type().name().unwrap().to_cow().unwrap(), "UUID");
+            assert_eq!(uuid_from_py(&object).unwrap(), id);
+            assert_eq!(uuid_from_py(PyString::new(py, &id.to_string()).as_any()).unwrap(), id);
+            assert!(uuid_from_py(PyString::new(py, "ada").as_any()).is_err());
+        });
+    }
+
+    #[test]
+    fn test_undirected_relationships_match_their_ends_either_way_round() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut relationship = Relationship::new(a, "sibling_of", b);
+        assert!(ends_match(&relationship, Some(a), Some(b)));
+        assert!(!ends_match(&relationship, Some(b), Some(a)));
+        relationship.directed = false;
+        assert!(ends_match(&relationship, Some(b), Some(a)));
+        assert!(ends_match(&relationship, None, Some(a)));
+    }
+
+    #[test]
+    fn test_a_graph_is_stored_and_read_back_through_the_class() {
+        with_python(|py| {
+            let dir = tempfile::tempdir().unwrap();
+            let graph = Mnemonic::open(py, dir.path().to_path_buf()).unwrap();
+            let data = json_to_py(py, &json!({"name": "Ada"})).unwrap();
+            let ada = graph.store(py, data.downcast().unwrap()).unwrap();
+            let data = json_to_py(py, &json!({"name": "Charles"})).unwrap();
+            let charles = graph.store(py, data.downcast().unwrap()).unwrap();
+            graph.relate(py, &ada, "knows".to_string(), &charles).unwrap();
+
+            let read = graph.get(py, &ada).unwrap().unwrap();
+            assert_eq!(py_to_json(&read).unwrap(), json!({"name": "Ada"}));
+            assert_eq!(graph.retrieve(py, Some(&ada), None, None).unwrap().len(), 1);
+            assert!(graph.retrieve(py, None, Some("owns".to_string()), None).unwrap().is_empty());
+            assert_eq!(graph.history(py, &ada).unwrap().len(), 1);
+
+            let now = Utc::now().fixed_offset();
+            let snapshot = graph.graph_at(py, now).unwrap();
+            let concepts = snapshot.get_item("concepts").unwrap().unwrap();
+            assert_eq!(concepts.len().unwrap(), 2);
+        });
+    }
+}
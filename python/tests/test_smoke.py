@@ -0,0 +1,64 @@
+import time
+import uuid
+from datetime import datetime, timezone
+
+import pytest
+
+import mnemonic
+
+
+@pytest.fixture
+def graph(tmp_path):
+    return mnemonic.Mnemonic.open(str(tmp_path / "graph"))
+
+
+def test_store_and_get_round_trip_the_data(graph):
+    data = {"name": "Ada", "born": 1815, "height": 1.65, "alive": False, "languages": ["English"], "spouse": None}
+    ada = graph.store(data)
+    assert isinstance(ada, uuid.UUID)
+    assert graph.get(ada) == data
+    assert graph.get(str(ada)) == data
+    assert graph.get(uuid.uuid4()) is None
+
+
+def test_retrieve_filters_by_source_type_and_target(graph):
+    ada = graph.store({"name": "Ada"})
+    charles = graph.store({"name": "Charles"})
+    lovelace = graph.store({"name": "Lovelace"})
+    knows = graph.relate(ada, "knows", charles)
+    graph.relate(ada, "married", lovelace)
+
+    assert [r["id"] for r in graph.retrieve(source=ada, type="knows")] == [knows]
+    assert [r["type"] for r in graph.retrieve(target=lovelace)] == ["married"]
+    assert len(graph.retrieve(source=ada)) == 2
+    assert graph.retrieve(source=charles) == []
+    assert len(graph.retrieve()) == 2
+
+
+def test_history_and_graph_at_travel_back_in_time(graph):
+    ada = graph.store({"name": "Ada"})
+    time.sleep(0.01)
+    before = datetime.now(timezone.utc)
+    time.sleep(0.01)
+    graph.store({"name": "Charles"})
+
+    [version] = graph.history(ada)
+    assert version["version"] == 1
+    assert version["data"] == {"name": "Ada"}
+    assert version["created_at"].tzinfo is not None
+    assert version["deleted_at"] is None
+
+    then = graph.graph_at(before)
+    assert [c["data"] for c in then["concepts"]] == [{"name": "Ada"}]
+    assert len(graph.graph_at(datetime.now(timezone.utc))["concepts"]) == 2
+    with pytest.raises(TypeError):
+        graph.graph_at(datetime.now())
+
+
+def test_values_json_cannot_hold_are_refused(graph):
+    with pytest.raises(ValueError):
+        graph.store({"score": float("nan")})
+    with pytest.raises(TypeError):
+        graph.store({"tags": {"a", "b"}})
+    with pytest.raises(mnemonic.MnemonicError):
+        graph.relate(uuid.uuid4(), "knows", uuid.uuid4())
@@ -0,0 +1,75 @@
+use axum::{extract::FromRequestParts, http::StatusCode, http::request::Parts};
+use std::collections::HashMap;
+
+use crate::api::{ApiError, routes::AppState};
+use crate::types::transaction::TransactionMetadata;
+
+/// The header clients send their API key in.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// An API key the server accepts, and who it belongs to.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub actor: String,
+    pub admin: bool,
+}
+
+/// All API keys the server accepts, by key. When empty, authentication is disabled.
+pub type ApiKeys = HashMap<String, ApiKey>;
+
+/// The authenticated caller of a request, resolved from the API key header.
+/// When the server has no API keys configured, every caller is anonymous and trusted.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub actor: Option<String>,
+    pub admin: bool,
+}
+
+impl Caller {
+    /// Transaction metadata attributing a change to this caller.
+    pub fn metadata(&self) -> TransactionMetadata {
+        TransactionMetadata {
+            actor: self.actor.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Refuses callers whose API key isn't an admin key.
+    pub fn require_admin(&self) -> Result<(), ApiError> {
+        if self.admin {
+            Ok(())
+        } else {
+            Err(ApiError::new(StatusCode::FORBIDDEN, "This endpoint requires an admin API key"))
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for Caller {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        if state.api_keys.is_empty() {
+            return Ok(Self {
+                actor: None,
+                admin: true,
+            });
+        }
+
+        let key = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                ApiError::new(StatusCode::UNAUTHORIZED, format!("Missing {} header", API_KEY_HEADER))
+            })?;
+
+        state
+            .api_keys
+            .get(key)
+            .map(|api_key| Self {
+                actor: Some(api_key.actor.clone()),
+                admin: api_key.admin,
+            })
+            .ok_or_else(|| ApiError::new(StatusCode::UNAUTHORIZED, "Unknown API key"))
+    }
+}
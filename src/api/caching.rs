@@ -0,0 +1,84 @@
+// Conditional GETs, so polling clients don't download what they already have. A response
+// carries an `ETag` (and `Last-Modified`, where there is one); a request repeating that tag in
+// `If-None-Match` gets 304 Not Modified with no body.
+
+use axum::{
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ETAG, IF_NONE_MATCH, LAST_MODIFIED},
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+/// What identifies one state of a resource, for conditional requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// Validators for a resource whose state `tag` names, quoted as an entity tag.
+    pub fn new(tag: impl std::fmt::Display, last_modified: Option<DateTime<Utc>>) -> Self {
+        Self { etag: format!("\"{}\"", tag), last_modified }
+    }
+
+    /// The quoted entity tag.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Whether the request's `If-None-Match` names this state, so the client has it already.
+    /// Weak tags match too: they only mean the bodies are equivalent.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        headers.get_all(IF_NONE_MATCH).iter().filter_map(|value| value.to_str().ok()).any(|value| {
+            value.split(',').map(str::trim).any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.etag)
+        })
+    }
+
+    /// 304 Not Modified, with no body, if the client has this state already.
+    pub fn not_modified(&self, headers: &HeaderMap) -> Option<Response> {
+        self.matches(headers).then(|| self.attach(StatusCode::NOT_MODIFIED.into_response()))
+    }
+
+    /// `response`, carrying the validators.
+    pub fn attach(&self, mut response: Response) -> Response {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            response.headers_mut().insert(ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified.and_then(|at| HeaderValue::from_str(&http_date(at)).ok()) {
+            response.headers_mut().insert(LAST_MODIFIED, last_modified);
+        }
+        response
+    }
+}
+
+/// `timestamp` as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_none_match_accepts_lists_weak_tags_and_wildcards() {
+        let validators = Validators::new("7", None);
+        assert_eq!(validators.etag(), "\"7\"");
+        let request = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(validators.matches(&request("\"7\"")));
+        assert!(validators.matches(&request("\"3\", W/\"7\"")));
+        assert!(validators.matches(&request("*")));
+        assert!(!validators.matches(&request("\"8\"")));
+        assert!(!validators.matches(&HeaderMap::new()));
+
+        let at = DateTime::parse_from_rfc3339("1994-11-06T08:49:37Z").unwrap().with_timezone(&Utc);
+        assert_eq!(http_date(at), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+}
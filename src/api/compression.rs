@@ -0,0 +1,165 @@
+// Gzip for JSON and MessagePack responses, when the request's `Accept-Encoding` allows it.
+//
+// Bodies are buffered and deflated whole, so only the buffered formats are touched: event
+// streams and anything else pass through as they are. Bodies under `MIN_BYTES` aren't worth
+// the header and trailer. A compressed response's strong `ETag` turns weak, since its bytes
+// no longer match the identity body's, and If-None-Match compares weak tags anyway.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode,
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG, VARY},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::format::MSGPACK_CONTENT_TYPE;
+
+/// Bodies smaller than this go out as they are.
+pub const MIN_BYTES: usize = 256;
+
+/// The deflate level, from 0 to 10: zlib's default trade of speed for size.
+const LEVEL: u8 = 6;
+
+/// Gzips the response if the client accepts it and the body is JSON or MessagePack.
+pub async fn gzip(request: Request, next: Next) -> Response {
+    let accepts_gzip = accepts_gzip(request.headers());
+    let head = request.method() == Method::HEAD;
+    let response = next.run(request).await;
+    if !compressible(response.headers()) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+    let skip = !accepts_gzip
+        || head
+        || parts.headers.contains_key(CONTENT_ENCODING)
+        || matches!(parts.status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED);
+    if skip {
+        return Response::from_parts(parts, body);
+    }
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if bytes.len() < MIN_BYTES {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    parts.headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts.headers.remove(CONTENT_LENGTH);
+    if let Some(weak) = parts
+        .headers
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok())
+    {
+        parts.headers.insert(ETAG, weak);
+    }
+    Response::from_parts(parts, Body::from(encode(&bytes)))
+}
+
+/// Whether `Accept-Encoding` gives gzip, by name or through `*`, a non-zero quality.
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    let mut gzip = None;
+    let mut any = None;
+    let codings = headers.get_all(ACCEPT_ENCODING).iter().filter_map(|value| value.to_str().ok());
+    for coding in codings.flat_map(|value| value.split(',')) {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = params
+            .find_map(|param| param.strip_prefix("q=").or_else(|| param.strip_prefix("Q=")))
+            .map_or(1.0, |q| q.parse::<f32>().unwrap_or(0.0));
+        match name.as_str() {
+            "gzip" | "x-gzip" => gzip = Some(quality),
+            "*" => any = Some(quality),
+            _ => {}
+        }
+    }
+    gzip.or(any).is_some_and(|quality| quality > 0.0)
+}
+
+/// Whether the response is one of the buffered formats worth compressing.
+fn compressible(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|essence| {
+            let essence = essence.trim();
+            essence.eq_ignore_ascii_case("application/json") || essence.eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+        })
+}
+
+/// `data` as a gzip member (RFC 1952): a bare header, the deflated data, then its CRC-32 and
+/// length.
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate, no flags, no mtime, no extra flags, unknown OS.
+    let mut out = vec![0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff];
+    out.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(data, LEVEL));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// The CRC-32 gzip uses: reflected, polynomial 0xedb88320.
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &byte| TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_crc32_matches_the_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_encode_writes_a_gzip_member_that_inflates_back() {
+        let data = "mnemonic ".repeat(100);
+        let gzipped = encode(data.as_bytes());
+        assert_eq!(gzipped[..3], [0x1f, 0x8b, 0x08]);
+        let (deflated, trailer) = gzipped[10..].split_at(gzipped.len() - 18);
+        assert_eq!(miniz_oxide::inflate::decompress_to_vec(deflated).unwrap(), data.as_bytes());
+        assert_eq!(trailer[..4], crc32(data.as_bytes()).to_le_bytes());
+        assert_eq!(trailer[4..], (data.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn test_accept_encoding_honours_qualities_and_wildcards() {
+        assert!(accepts_gzip(&accept("gzip")));
+        assert!(accepts_gzip(&accept("deflate, gzip;q=0.5")));
+        assert!(accepts_gzip(&accept("br, *")));
+        assert!(!accepts_gzip(&accept("gzip;q=0")));
+        assert!(!accepts_gzip(&accept("gzip;q=0, *")));
+        assert!(!accepts_gzip(&accept("br, identity")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+}
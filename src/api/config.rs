@@ -0,0 +1,419 @@
+// Configuration for the mre server: where it listens, where its database lives, which
+// browser origins may call it, what a fresh database is seeded with, and how long and how far
+// a request may work.
+//
+// Settings come from the TOML file named by `--config`, if any, and then from `MNEMONIC_*`
+// environment variables, which override the file. Each value is checked when it is read, so
+// a bad deployment fails at startup naming the setting, not later with a confusing symptom.
+
+use axum::http::{header, HeaderValue, Uri};
+use clap::{Arg, ArgAction, Command, value_parser};
+use serde::Deserialize;
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::api::routes::DEFAULT_REQUEST_TIMEOUT;
+use crate::error::MnemonicError;
+use crate::graph::{ScanBudget, SeedFixture, DEFAULT_SCAN_BUDGET};
+
+pub const ADDR_ENV: &str = "MNEMONIC_ADDR";
+pub const DATA_DIR_ENV: &str = "MNEMONIC_DATA_DIR";
+/// A comma-separated list of origins, or `*` for any.
+pub const CORS_ORIGINS_ENV: &str = "MNEMONIC_CORS_ORIGINS";
+/// `off`, `demo`, or the path of a JSON seed fixture.
+pub const SEED_ENV: &str = "MNEMONIC_SEED";
+/// A whole number of seconds, or `off`.
+pub const REQUEST_TIMEOUT_ENV: &str = "MNEMONIC_REQUEST_TIMEOUT";
+/// A whole number of items above zero.
+pub const SCAN_MAX_ITEMS_ENV: &str = "MNEMONIC_SCAN_MAX_ITEMS";
+/// A whole number of seconds above zero.
+pub const SCAN_MAX_DURATION_ENV: &str = "MNEMONIC_SCAN_MAX_DURATION";
+
+/// Why the server's configuration could not be loaded.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// Bad arguments, or a request for help; `clap::Error::exit` prints it appropriately.
+    #[error(transparent)]
+    Usage(#[from] clap::Error),
+    #[error("Reading {}: {source}", path.display())]
+    Read { path: PathBuf, source: std::io::Error },
+    #[error("{}: {message}", path.display())]
+    File { path: PathBuf, message: String },
+    /// A value that doesn't parse, named the way it was given: `MNEMONIC_ADDR`, or
+    /// `addr in mre.toml`.
+    #[error("{setting}: {message}")]
+    Invalid { setting: String, message: String },
+    #[error("Loading the seed fixture {}: {source}", path.display())]
+    Seed { path: PathBuf, source: MnemonicError },
+}
+
+/// Which origins a browser may call the API from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum CorsOrigins {
+    /// Same-origin requests only: no cross-origin response headers are sent.
+    #[default]
+    None,
+    /// Any origin; only when `*` is configured.
+    Any,
+    /// Exactly these origins, e.g. `https://explorer.example.com`.
+    List(Vec<HeaderValue>),
+}
+
+/// What a database with no concepts is seeded with when the server starts.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Seed {
+    Off,
+    /// `SeedFixture::demo`.
+    #[default]
+    Demo,
+    /// A JSON fixture, as `SeedFixture::from_json_file` reads.
+    File(PathBuf),
+}
+
+/// The mre server's settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MnemonicConfig {
+    /// Defaults to `0.0.0.0:8080`, which containers and Codespaces need to reach the server.
+    pub addr: SocketAddr,
+    /// Defaults to `./mre_data`.
+    pub data_dir: PathBuf,
+    /// Defaults to none.
+    pub cors_origins: CorsOrigins,
+    pub seed: Seed,
+    /// Serve the graph explorer at `/`, in builds with the `ui` feature; `--no-ui` turns it off.
+    pub ui: bool,
+    /// How long a traversal, /graph or listing request may work before it is answered with 504.
+    /// Defaults to `DEFAULT_REQUEST_TIMEOUT`; `off` lifts the limit.
+    pub request_timeout: Option<Duration>,
+    /// How many items, and for how long, one scanning request may look at. Defaults to
+    /// `DEFAULT_SCAN_BUDGET`; requests may ask for less.
+    pub scan_budget: ScanBudget,
+}
+
+impl Default for MnemonicConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([0, 0, 0, 0], 8080)),
+            data_dir: PathBuf::from("./mre_data"),
+            cors_origins: CorsOrigins::default(),
+            seed: Seed::default(),
+            ui: true,
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            scan_budget: DEFAULT_SCAN_BUDGET,
+        }
+    }
+}
+
+/// The settings as a file or the environment spells them, before they are checked.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Settings {
+    addr: Option<String>,
+    data_dir: Option<String>,
+    cors_origins: Option<Vec<String>>,
+    seed: Option<String>,
+    request_timeout: Option<String>,
+    scan_max_items: Option<String>,
+    scan_max_duration: Option<String>,
+}
+
+/// The mre binary's argument parser.
+pub fn command() -> Command {
+    Command::new("mre")
+        .about("Serve a mnemonic database over HTTP")
+        .after_help(
+            "Environment variables override the config file: MNEMONIC_ADDR, MNEMONIC_DATA_DIR, \
+             MNEMONIC_CORS_ORIGINS (comma-separated, or *), MNEMONIC_SEED (off, demo or a fixture path) \
+             MNEMONIC_REQUEST_TIMEOUT (seconds, or off), MNEMONIC_SCAN_MAX_ITEMS and \
+             MNEMONIC_SCAN_MAX_DURATION (seconds).",
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "A TOML file with addr, data_dir, cors_origins, seed, request_timeout, \
+                     scan_max_items and scan_max_duration",
+                ),
+        )
+        .arg(
+            Arg::new("no-ui")
+                .long("no-ui")
+                .action(ArgAction::SetTrue)
+                .help("Serve only the API, not the graph explorer (built in by the ui feature)"),
+        )
+}
+
+impl MnemonicConfig {
+    /// The configuration for these command-line arguments and the process environment.
+    pub fn load<I, T>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = command().try_get_matches_from(args)?;
+        let config = match matches.get_one::<PathBuf>("config") {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        let config = config.with_env(|name| std::env::var(name).ok())?;
+        Ok(Self { ui: !matches.get_flag("no-ui"), ..config })
+    }
+
+    /// The defaults, overridden by a TOML file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|source| ConfigError::Read { path: path.to_path_buf(), source })?;
+        let settings: Settings = toml::from_str(&text)
+            .map_err(|e| ConfigError::File { path: path.to_path_buf(), message: e.to_string() })?;
+        let file_name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+        Self::default().apply(settings, |field| format!("{} in {}", field, file_name))
+    }
+
+    /// This configuration, overridden by whichever `MNEMONIC_*` variables `var` returns.
+    pub fn with_env(self, var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let settings = Settings {
+            addr: var(ADDR_ENV),
+            data_dir: var(DATA_DIR_ENV),
+            cors_origins: var(CORS_ORIGINS_ENV).map(|list| list.split(',').map(str::to_string).collect()),
+            seed: var(SEED_ENV),
+            request_timeout: var(REQUEST_TIMEOUT_ENV),
+            scan_max_items: var(SCAN_MAX_ITEMS_ENV),
+            scan_max_duration: var(SCAN_MAX_DURATION_ENV),
+        };
+        self.apply(settings, |field| {
+            match field {
+                "addr" => ADDR_ENV,
+                "data_dir" => DATA_DIR_ENV,
+                "cors_origins" => CORS_ORIGINS_ENV,
+                "request_timeout" => REQUEST_TIMEOUT_ENV,
+                "scan_max_items" => SCAN_MAX_ITEMS_ENV,
+                "scan_max_duration" => SCAN_MAX_DURATION_ENV,
+                _ => SEED_ENV,
+            }
+            .to_string()
+        })
+    }
+
+    fn apply(mut self, settings: Settings, setting: impl Fn(&str) -> String) -> Result<Self, ConfigError> {
+        let invalid = |field: &str, message: String| ConfigError::Invalid { setting: setting(field), message };
+        if let Some(addr) = settings.addr {
+            self.addr = addr.trim().parse().map_err(|_| {
+                invalid("addr", format!("{:?} is not an address and port, e.g. 0.0.0.0:8080 or [::1]:8080", addr))
+            })?;
+        }
+        if let Some(data_dir) = settings.data_dir {
+            if data_dir.trim().is_empty() {
+                return Err(invalid("data_dir", "must name a directory".to_string()));
+            }
+            self.data_dir = PathBuf::from(data_dir.trim());
+        }
+        if let Some(origins) = settings.cors_origins {
+            self.cors_origins = parse_origins(&origins).map_err(|message| invalid("cors_origins", message))?;
+        }
+        if let Some(seed) = settings.seed {
+            self.seed = match seed.trim() {
+                "off" => Seed::Off,
+                "demo" => Seed::Demo,
+                "" => return Err(invalid("seed", "must be off, demo, or the path of a seed fixture".to_string())),
+                path if !Path::new(path).is_file() => {
+                    return Err(invalid("seed", format!("{} is not off, demo, or an existing fixture file", path)));
+                }
+                path => Seed::File(PathBuf::from(path)),
+            };
+        }
+        if let Some(timeout) = settings.request_timeout {
+            self.request_timeout = match timeout.trim() {
+                "off" => None,
+                seconds => match seconds.parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => Some(Duration::from_secs(seconds)),
+                    _ => {
+                        return Err(invalid(
+                            "request_timeout",
+                            format!("{:?} is not a whole number of seconds above zero, or off", timeout),
+                        ));
+                    }
+                },
+            };
+        }
+        if let Some(items) = settings.scan_max_items {
+            self.scan_budget.max_items = match items.trim().parse::<usize>() {
+                Ok(items) if items > 0 => items,
+                _ => return Err(invalid("scan_max_items", format!("{:?} is not a whole number above zero", items))),
+            };
+        }
+        if let Some(duration) = settings.scan_max_duration {
+            self.scan_budget.max_duration = match duration.trim().parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+                _ => {
+                    return Err(invalid(
+                        "scan_max_duration",
+                        format!("{:?} is not a whole number of seconds above zero", duration),
+                    ));
+                }
+            };
+        }
+        Ok(self)
+    }
+
+    /// The CORS middleware for `cors_origins`. Any method and header is allowed; only the
+    /// origin is restricted. Scripts may read the validators, to send them back.
+    pub fn cors_layer(&self) -> CorsLayer {
+        let layer = CorsLayer::new()
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers([header::ETAG, header::LAST_MODIFIED]);
+        match &self.cors_origins {
+            CorsOrigins::None => layer,
+            CorsOrigins::Any => layer.allow_origin(Any),
+            CorsOrigins::List(origins) => layer.allow_origin(AllowOrigin::list(origins.iter().cloned())),
+        }
+    }
+
+    /// The fixture a fresh database gets, or `None` when seeding is off.
+    pub fn seed_fixture(&self) -> Result<Option<SeedFixture>, ConfigError> {
+        match &self.seed {
+            Seed::Off => Ok(None),
+            Seed::Demo => Ok(Some(SeedFixture::demo())),
+            Seed::File(path) => SeedFixture::from_json_file(path)
+                .map(Some)
+                .map_err(|source| ConfigError::Seed { path: path.clone(), source }),
+        }
+    }
+}
+
+/// `*` on its own, or origins as a browser sends them: a scheme and host, with an optional
+/// port and nothing else. An empty list allows none.
+fn parse_origins(origins: &[String]) -> Result<CorsOrigins, String> {
+    let origins: Vec<&str> = origins.iter().map(|origin| origin.trim()).filter(|origin| !origin.is_empty()).collect();
+    if origins == ["*"] {
+        return Ok(CorsOrigins::Any);
+    }
+    if origins.is_empty() {
+        return Ok(CorsOrigins::None);
+    }
+    origins
+        .into_iter()
+        .map(|origin| {
+            if origin == "*" {
+                return Err("* allows every origin, so it can't be combined with others".to_string());
+            }
+            let uri: Uri = origin.parse().map_err(|_| not_an_origin(origin))?;
+            match (uri.scheme_str(), uri.authority()) {
+                (Some(scheme @ ("http" | "https")), Some(authority))
+                    if format!("{}://{}", scheme, authority) == origin =>
+                {
+                    HeaderValue::from_str(origin).map_err(|_| not_an_origin(origin))
+                }
+                _ => Err(not_an_origin(origin)),
+            }
+        })
+        .collect::<Result<_, _>>()
+        .map(CorsOrigins::List)
+}
+
+fn not_an_origin(origin: &str) -> String {
+    format!(
+        "{:?} is not an origin; write it as a browser sends it, like https://example.com or http://localhost:3000, \
+         with no path or trailing slash",
+        origin
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes::{AppState, create_app};
+    use crate::graph::GraphEngine;
+    use axum_test::TestServer;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mre.toml")
+    }
+
+    #[tokio::test]
+    async fn test_the_fixture_config_only_lets_its_origins_through_cors() {
+        let config = MnemonicConfig::from_file(fixture()).unwrap();
+        assert_eq!(config.addr, "127.0.0.1:9090".parse().unwrap());
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/mnemonic"));
+        assert_eq!(config.seed, Seed::Off);
+        assert_eq!(config.request_timeout, Some(Duration::from_secs(10)));
+
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let server = TestServer::new(create_app(AppState::new(engine), false).layer(config.cors_layer())).unwrap();
+
+        let allowed = server.get("/api/v1/ping").add_header("origin", "https://explorer.example.com").await;
+        allowed.assert_status_ok();
+        assert_eq!(allowed.header("access-control-allow-origin"), "https://explorer.example.com");
+        assert_eq!(allowed.header("access-control-expose-headers"), "etag,last-modified");
+
+        let preflight = server
+            .method(axum::http::Method::OPTIONS, "/api/v1/concepts")
+            .add_header("origin", "https://evil.example.net")
+            .add_header("access-control-request-method", "POST")
+            .await;
+        assert!(preflight.maybe_header("access-control-allow-origin").is_none());
+        let disallowed = server.get("/api/v1/ping").add_header("origin", "https://evil.example.net").await;
+        assert!(disallowed.maybe_header("access-control-allow-origin").is_none());
+    }
+
+    #[test]
+    fn test_environment_variables_override_the_file() {
+        let env = HashMap::from([
+            (ADDR_ENV, "[::1]:8181"),
+            (CORS_ORIGINS_ENV, "*"),
+            (SEED_ENV, "demo"),
+            (REQUEST_TIMEOUT_ENV, "off"),
+            (SCAN_MAX_ITEMS_ENV, "500"),
+        ]);
+        let config = MnemonicConfig::from_file(fixture())
+            .unwrap()
+            .with_env(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config.addr, "[::1]:8181".parse().unwrap());
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/mnemonic"));
+        assert_eq!(config.cors_origins, CorsOrigins::Any);
+        assert_eq!(config.seed_fixture().unwrap(), Some(SeedFixture::demo()));
+        assert_eq!(config.request_timeout, None);
+        assert_eq!(config.scan_budget, ScanBudget { max_items: 500, ..DEFAULT_SCAN_BUDGET });
+
+        assert_eq!(MnemonicConfig::default().cors_origins, CorsOrigins::None);
+        assert!(matches!(MnemonicConfig::load(["mre", "--port", "80"]), Err(ConfigError::Usage(_))));
+        assert!(!MnemonicConfig::load(["mre", "--no-ui"]).unwrap().ui);
+    }
+
+    #[test]
+    fn test_bad_values_are_rejected_naming_their_setting() {
+        let with = |name: &'static str, value: &'static str| {
+            MnemonicConfig::default().with_env(|var| (var == name).then(|| value.to_string())).unwrap_err().to_string()
+        };
+        assert!(with(ADDR_ENV, "8080").starts_with("MNEMONIC_ADDR: \"8080\" is not an address and port"));
+        assert!(with(CORS_ORIGINS_ENV, "https://a.example.com/").contains("no path or trailing slash"));
+        assert!(with(CORS_ORIGINS_ENV, "example.com").contains("is not an origin"));
+        assert!(with(CORS_ORIGINS_ENV, "*,https://a.example.com").contains("can't be combined"));
+        assert!(with(SEED_ENV, "/no/such/fixture.json").starts_with("MNEMONIC_SEED: /no/such/fixture.json is not off"));
+        assert!(with(DATA_DIR_ENV, " ").contains("must name a directory"));
+        assert!(with(REQUEST_TIMEOUT_ENV, "30s").starts_with("MNEMONIC_REQUEST_TIMEOUT: \"30s\" is not a whole number"));
+        assert!(with(REQUEST_TIMEOUT_ENV, "0").contains("above zero"));
+        assert!(with(SCAN_MAX_ITEMS_ENV, "0").starts_with("MNEMONIC_SCAN_MAX_ITEMS: \"0\" is not a whole number"));
+        assert!(with(SCAN_MAX_DURATION_ENV, "1.5").starts_with("MNEMONIC_SCAN_MAX_DURATION:"));
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("mre.toml");
+        std::fs::write(&path, "addr = \"0.0.0.0:80\"\nport = 80\n").unwrap();
+        assert!(matches!(MnemonicConfig::from_file(&path), Err(ConfigError::File { .. })));
+        std::fs::write(&path, "cors_origins = [\"ftp://files.example.com\"]\n").unwrap();
+        let err = MnemonicConfig::from_file(&path).unwrap_err().to_string();
+        assert!(err.starts_with("cors_origins in mre.toml: \"ftp://files.example.com\" is not an origin"), "{}", err);
+    }
+}
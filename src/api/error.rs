@@ -0,0 +1,169 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+use crate::MnemonicError;
+
+/// An error returned by an HTTP handler. It is rendered as a JSON body
+/// (e.g. {"error": "..."}) with a status code matching the failure.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: serde_json::Value,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+}
+
+impl From<MnemonicError> for ApiError {
+    fn from(err: MnemonicError) -> Self {
+        // Conflicts carry structured details that retry logic can act on.
+        if let MnemonicError::TransactionConflict(conflicts) = &err {
+            return Self {
+                status: StatusCode::CONFLICT,
+                body: json!({ "error": err.to_string(), "conflicts": conflicts }),
+            };
+        }
+
+        // Batches say exactly which items were wrong.
+        if let MnemonicError::InvalidBatch(items) = &err {
+            return Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                body: json!({ "error": err.to_string(), "items": items }),
+            };
+        }
+
+        // Pruning and purging name the transactions to wait for (or abort) before retrying.
+        if let MnemonicError::PruneBlocked(transactions) | MnemonicError::PurgeBlocked(transactions) = &err {
+            return Self {
+                status: StatusCode::CONFLICT,
+                body: json!({ "error": err.to_string(), "transactions": transactions }),
+            };
+        }
+
+        // A refused edge names the relationship already holding its slot.
+        if let MnemonicError::CardinalityViolation { existing, .. } = &err {
+            return Self {
+                status: StatusCode::CONFLICT,
+                body: json!({ "error": err.to_string(), "existing": existing }),
+            };
+        }
+
+        // A concept that can't be deleted names the relationships still holding on to it.
+        if let MnemonicError::ConceptReferenced { relationships, .. } = &err {
+            return Self {
+                status: StatusCode::CONFLICT,
+                body: json!({ "error": err.to_string(), "relationships": relationships }),
+            };
+        }
+
+        // A rejected edge lists the cycle it would have closed.
+        if let MnemonicError::CycleDetected { cycle, .. } = &err {
+            return Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                body: json!({ "error": err.to_string(), "cycle": cycle }),
+            };
+        }
+
+        // An unregistered relationship type comes with the registered ones it resembles.
+        if let MnemonicError::UnknownRelationshipType { suggestions, .. } = &err {
+            return Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                body: json!({ "error": err.to_string(), "suggestions": suggestions }),
+            };
+        }
+
+        // Schema violations point at each offending field.
+        if let MnemonicError::SchemaViolation(violations) = &err {
+            return Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                body: json!({ "error": err.to_string(), "violations": violations }),
+            };
+        }
+
+        // Strict writes name each concept they were wrong about.
+        if let MnemonicError::RejectedWrites(rejected) = &err {
+            return Self {
+                status: StatusCode::CONFLICT,
+                body: json!({ "error": err.to_string(), "rejected": rejected }),
+            };
+        }
+
+        // A rejected query points at the clause at fault.
+        if let MnemonicError::InvalidQuery { path, .. } = &err {
+            return Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                body: json!({ "error": err.to_string(), "path": path }),
+            };
+        }
+
+        let status = match &err {
+            MnemonicError::ConceptNotFound(_)
+            | MnemonicError::RelationshipNotFound(_)
+            | MnemonicError::SnapshotNotFound(_) => {
+                StatusCode::NOT_FOUND
+            }
+            MnemonicError::TransactionExpired(_) | MnemonicError::HistoryErased { .. } => StatusCode::GONE,
+            MnemonicError::DanglingRelationship { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            MnemonicError::InvalidSchema(_) => StatusCode::BAD_REQUEST,
+            MnemonicError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            MnemonicError::InvalidData(_) | MnemonicError::ScanBudgetExceeded { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            MnemonicError::Overloaded { .. } | MnemonicError::EngineClosed => StatusCode::SERVICE_UNAVAILABLE,
+            MnemonicError::ReadOnlyReplica => StatusCode::CONFLICT,
+            MnemonicError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            // Nobody is waiting for this one; 499 is what logs call a request the client gave up on.
+            MnemonicError::Cancelled => StatusCode::from_u16(499).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConflictInfo, ConflictItem};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_conflict_renders_structured_409() {
+        let concept_id = Uuid::new_v4();
+        let err = MnemonicError::TransactionConflict(vec![ConflictInfo {
+            item: ConflictItem::Concept(concept_id),
+            our_base_version: Some(1),
+            committed_version: 2,
+            committed_at: Utc::now(),
+            committed_by: Uuid::new_v4(),
+        }]);
+
+        let api_error = ApiError::from(err);
+        assert_eq!(api_error.status, StatusCode::CONFLICT);
+
+        let conflict = &api_error.body["conflicts"][0];
+        assert_eq!(conflict["item"]["kind"], "concept");
+        assert_eq!(conflict["item"]["id"], concept_id.to_string());
+        assert_eq!(conflict["committed_version"], 2);
+    }
+}
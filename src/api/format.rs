@@ -0,0 +1,73 @@
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{
+        StatusCode,
+        header::{ACCEPT, CONTENT_TYPE},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::convert::Infallible;
+
+use crate::api::{ApiError, msgpack};
+
+/// The content type of MessagePack responses.
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// The body format a client asked for, via `Accept: application/msgpack` or `?format=msgpack`.
+/// JSON unless the client asks otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        let in_query = parts
+            .uri
+            .query()
+            .is_some_and(|query| query.split('&').any(|pair| pair == "format=msgpack"));
+        let in_accept = parts
+            .headers
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .is_some_and(|accept| {
+                accept.split(',').any(|media_type| {
+                    let media_type = media_type.split(';').next().unwrap_or_default().trim();
+                    media_type == MSGPACK_CONTENT_TYPE || media_type == "application/x-msgpack"
+                })
+            });
+
+        Ok(if in_query || in_accept {
+            Self::MessagePack
+        } else {
+            Self::Json
+        })
+    }
+}
+
+/// A response body rendered in whichever format the client negotiated.
+pub struct Negotiated<T>(pub ResponseFormat, pub T);
+
+impl<T: Serialize> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Negotiated(format, body) = self;
+        match format {
+            ResponseFormat::Json => Json(body).into_response(),
+            ResponseFormat::MessagePack => match msgpack::to_vec(&body) {
+                Ok(bytes) => ([(CONTENT_TYPE, MSGPACK_CONTENT_TYPE)], bytes).into_response(),
+                Err(e) => ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to encode MessagePack: {}", e),
+                )
+                .into_response(),
+            },
+        }
+    }
+}
@@ -1 +1,16 @@
-pub mod routes;
\ No newline at end of file
+pub mod auth;
+pub mod caching;
+pub mod compression;
+pub mod config;
+pub mod error;
+pub mod format;
+pub mod msgpack;
+pub mod rate_limit;
+pub mod routes;
+pub mod types;
+#[cfg(feature = "ui")]
+pub mod ui;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
+
+pub use error::ApiError;
@@ -0,0 +1,859 @@
+// A small MessagePack codec.
+//
+// `Serializer` writes any `Serialize` value straight to MessagePack, with no intermediate
+// tree. It mirrors serde_json's data model so a body decodes to what the JSON body would:
+// it is human-readable (UUIDs and timestamps are strings), enums are externally tagged,
+// byte strings become `bin`, and map keys must be strings or integers, which are written
+// as strings. The decoder reads that data model back, through `serde_json::Value`.
+
+use serde::ser::{self, Serialize};
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Why a MessagePack body could not be decoded.
+#[derive(Debug, Error, PartialEq)]
+#[error("Invalid MessagePack: {0}")]
+pub struct DecodeError(String);
+
+/// Why a value could not be encoded as MessagePack.
+#[derive(Debug, Error, PartialEq)]
+#[error("Cannot encode as MessagePack: {0}")]
+pub struct EncodeError(String);
+
+impl ser::Error for EncodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        EncodeError(msg.to_string())
+    }
+}
+
+/// Serializes any value to MessagePack.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EncodeError> {
+    let mut out = Vec::new();
+    value.serialize(&mut Serializer { out: &mut out })?;
+    Ok(out)
+}
+
+/// Deserializes a MessagePack body into any value.
+pub fn from_slice<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut decoder = Decoder { bytes, pos: 0 };
+    let value = decoder.value()?;
+    if decoder.pos != bytes.len() {
+        return Err(DecodeError("trailing bytes after value".to_string()));
+    }
+    serde_json::from_value(value).map_err(|e| DecodeError(e.to_string()))
+}
+
+/// Writes MessagePack to `out`.
+pub struct Serializer<'a> {
+    out: &'a mut Vec<u8>,
+}
+
+impl<'a> Serializer<'a> {
+    pub fn new(out: &'a mut Vec<u8>) -> Self {
+        Self { out }
+    }
+
+    /// Starts an array or map of `len` items, or of however many turn up if `len` isn't
+    /// known up front; those are buffered so the header can count them.
+    fn compound(&mut self, len: Option<usize>, kind: Kind) -> Compound<'_> {
+        if let Some(len) = len {
+            kind.header(len, self.out);
+        }
+        Compound { out: self.out, kind, expected: len, count: 0, buffer: Vec::new() }
+    }
+
+    /// Writes the `{variant: ...}` wrapper an externally tagged enum variant's value goes in.
+    fn variant(&mut self, variant: &str) {
+        Kind::Map.header(1, self.out);
+        write_str(variant, self.out);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Array,
+    Map,
+}
+
+impl Kind {
+    fn header(self, len: usize, out: &mut Vec<u8>) {
+        match self {
+            // array8 and map8 don't exist, so 0x00 is never used as a marker.
+            Kind::Array => write_len(len, 0x90, 16, [0x00, 0xdc, 0xdd], out),
+            Kind::Map => write_len(len, 0x80, 16, [0x00, 0xde, 0xdf], out),
+        }
+    }
+}
+
+/// An array or map being written: its items go straight to `out` when the header already
+/// holds their count, or to `buffer` until it's known.
+pub struct Compound<'a> {
+    out: &'a mut Vec<u8>,
+    kind: Kind,
+    expected: Option<usize>,
+    count: usize,
+    buffer: Vec<u8>,
+}
+
+impl Compound<'_> {
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        let out = if self.expected.is_some() { &mut *self.out } else { &mut self.buffer };
+        value.serialize(&mut Serializer { out })
+    }
+
+    fn key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), EncodeError> {
+        self.count += 1;
+        let out = if self.expected.is_some() { &mut *self.out } else { &mut self.buffer };
+        key.serialize(KeySerializer { out })
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        match self.expected {
+            None => {
+                self.kind.header(self.count, self.out);
+                self.out.extend_from_slice(&self.buffer);
+                Ok(())
+            }
+            Some(expected) if expected == self.count => Ok(()),
+            Some(expected) => Err(EncodeError(format!("promised {} items, got {}", expected, self.count))),
+        }
+    }
+}
+
+impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), EncodeError> {
+        self.out.push(if v { 0xc3 } else { 0xc2 });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), EncodeError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), EncodeError> {
+        write_int(v, self.out);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), EncodeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), EncodeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), EncodeError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), EncodeError> {
+        write_uint(v, self.out);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), EncodeError> {
+        self.out.push(0xca);
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), EncodeError> {
+        self.out.push(0xcb);
+        self.out.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), EncodeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), EncodeError> {
+        write_str(v, self.out);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodeError> {
+        write_len(v.len(), 0x00, 0, [0xc4, 0xc5, 0xc6], self.out);
+        self.out.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), EncodeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodeError> {
+        self.out.push(0xc0);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), EncodeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        self.variant(variant);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a>, EncodeError> {
+        Ok(self.compound(len, Kind::Array))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a>, EncodeError> {
+        Ok(self.compound(Some(len), Kind::Array))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a>, EncodeError> {
+        Ok(self.compound(Some(len), Kind::Array))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, EncodeError> {
+        self.variant(variant);
+        Ok(self.compound(Some(len), Kind::Array))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a>, EncodeError> {
+        Ok(self.compound(len, Kind::Map))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a>, EncodeError> {
+        Ok(self.compound(Some(len), Kind::Map))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, EncodeError> {
+        self.variant(variant);
+        Ok(self.compound(Some(len), Kind::Map))
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.count += 1;
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), EncodeError> {
+        self.key(key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), EncodeError> {
+        self.key(key)?;
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), EncodeError> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        Compound::end(self)
+    }
+}
+
+/// Writes a map key: a string as it is, an integer as its decimal string, as serde_json does.
+struct KeySerializer<'a> {
+    out: &'a mut Vec<u8>,
+}
+
+impl KeySerializer<'_> {
+    fn unsupported(what: &str) -> EncodeError {
+        EncodeError(format!("map keys must be strings or integers, not {}", what))
+    }
+
+    fn number(self, n: impl Display) -> Result<(), EncodeError> {
+        write_str(&n.to_string(), self.out);
+        Ok(())
+    }
+}
+
+impl ser::Serializer for KeySerializer<'_> {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = ser::Impossible<(), EncodeError>;
+    type SerializeTuple = ser::Impossible<(), EncodeError>;
+    type SerializeTupleStruct = ser::Impossible<(), EncodeError>;
+    type SerializeTupleVariant = ser::Impossible<(), EncodeError>;
+    type SerializeMap = ser::Impossible<(), EncodeError>;
+    type SerializeStruct = ser::Impossible<(), EncodeError>;
+    type SerializeStructVariant = ser::Impossible<(), EncodeError>;
+
+    fn serialize_str(self, v: &str) -> Result<(), EncodeError> {
+        write_str(v, self.out);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), EncodeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), EncodeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), EncodeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), EncodeError> {
+        self.number(v)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), EncodeError> {
+        Err(Self::unsupported("a bool"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), EncodeError> {
+        Err(Self::unsupported("a float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), EncodeError> {
+        Err(Self::unsupported("a float"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), EncodeError> {
+        Err(Self::unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<(), EncodeError> {
+        Err(Self::unsupported("none"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), EncodeError> {
+        Err(Self::unsupported("an option"))
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodeError> {
+        Err(Self::unsupported("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
+        Err(Self::unsupported("a unit struct"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), EncodeError> {
+        Err(Self::unsupported("an enum variant with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, EncodeError> {
+        Err(Self::unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, EncodeError> {
+        Err(Self::unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, EncodeError> {
+        Err(Self::unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EncodeError> {
+        Err(Self::unsupported("an enum variant with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EncodeError> {
+        Err(Self::unsupported("a map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, EncodeError> {
+        Err(Self::unsupported("a struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, EncodeError> {
+        Err(Self::unsupported("an enum variant with data"))
+    }
+}
+
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_len(s.len(), 0xa0, 32, [0xd9, 0xda, 0xdb], out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Writes an integer in the smallest form that holds it; a non-negative one as unsigned.
+fn write_int(i: i64, out: &mut Vec<u8>) {
+    if i >= 0 {
+        write_uint(i as u64, out);
+    } else if i >= -32 {
+        out.push(i as u8);
+    } else if i >= i8::MIN as i64 {
+        out.extend_from_slice(&[0xd0, i as u8]);
+    } else if i >= i16::MIN as i64 {
+        out.push(0xd1);
+        out.extend_from_slice(&(i as i16).to_be_bytes());
+    } else if i >= i32::MIN as i64 {
+        out.push(0xd2);
+        out.extend_from_slice(&(i as i32).to_be_bytes());
+    } else {
+        out.push(0xd3);
+        out.extend_from_slice(&i.to_be_bytes());
+    }
+}
+
+fn write_uint(u: u64, out: &mut Vec<u8>) {
+    match u {
+        0..=0x7f => out.push(u as u8),
+        0x80..=0xff => out.extend_from_slice(&[0xcc, u as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(u as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(u as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xcf);
+            out.extend_from_slice(&u.to_be_bytes());
+        }
+    }
+}
+
+/// Writes a length header: the fix form if it fits, otherwise the 8/16/32-bit form. A form
+/// whose marker is 0x00 doesn't exist for that type.
+fn write_len(len: usize, fix: u8, fix_limit: usize, markers: [u8; 3], out: &mut Vec<u8>) {
+    if len < fix_limit {
+        out.push(fix | len as u8);
+    } else if len <= 0xff && markers[0] != 0x00 {
+        out.extend_from_slice(&[markers[0], len as u8]);
+    } else if len <= 0xffff {
+        out.push(markers[1]);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(markers[2]);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn value(&mut self) -> Result<Value, DecodeError> {
+        let marker = self.take(1)?[0];
+        Ok(match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize)?,
+            0x90..=0x9f => self.array((marker & 0x0f) as usize)?,
+            0xa0..=0xbf => self.string((marker & 0x1f) as usize)?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            // Byte strings decode as arrays of numbers, which is how serde_json holds them.
+            0xc4 => {
+                let len = self.take(1)?[0] as usize;
+                self.bin(len)?
+            }
+            0xc5 => {
+                let len = u16::from_be_bytes(self.array_of()?) as usize;
+                self.bin(len)?
+            }
+            0xc6 => {
+                let len = u32::from_be_bytes(self.array_of()?) as usize;
+                self.bin(len)?
+            }
+            0xca => Value::from(f32::from_be_bytes(self.array_of()?) as f64),
+            0xcb => Value::from(f64::from_be_bytes(self.array_of()?)),
+            0xcc => Value::from(self.take(1)?[0]),
+            0xcd => Value::from(u16::from_be_bytes(self.array_of()?)),
+            0xce => Value::from(u32::from_be_bytes(self.array_of()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.array_of()?)),
+            0xd0 => Value::from(self.take(1)?[0] as i8),
+            0xd1 => Value::from(i16::from_be_bytes(self.array_of()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.array_of()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.array_of()?)),
+            0xd9 => {
+                let len = self.take(1)?[0] as usize;
+                self.string(len)?
+            }
+            0xda => {
+                let len = u16::from_be_bytes(self.array_of()?) as usize;
+                self.string(len)?
+            }
+            0xdb => {
+                let len = u32::from_be_bytes(self.array_of()?) as usize;
+                self.string(len)?
+            }
+            0xdc => {
+                let len = u16::from_be_bytes(self.array_of()?) as usize;
+                self.array(len)?
+            }
+            0xdd => {
+                let len = u32::from_be_bytes(self.array_of()?) as usize;
+                self.array(len)?
+            }
+            0xde => {
+                let len = u16::from_be_bytes(self.array_of()?) as usize;
+                self.map(len)?
+            }
+            0xdf => {
+                let len = u32::from_be_bytes(self.array_of()?) as usize;
+                self.map(len)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+            other => return Err(DecodeError(format!("unsupported marker 0x{:02x}", other))),
+        })
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value, DecodeError> {
+        let bytes = self.take(len)?;
+        let s = std::str::from_utf8(bytes).map_err(|e| DecodeError(e.to_string()))?;
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn bin(&mut self, len: usize) -> Result<Value, DecodeError> {
+        Ok(Value::Array(self.take(len)?.iter().copied().map(Value::from).collect()))
+    }
+
+    fn array(&mut self, len: usize) -> Result<Value, DecodeError> {
+        let mut items = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            items.push(self.value()?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize) -> Result<Value, DecodeError> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let Value::String(key) = self.value()? else {
+                return Err(DecodeError("map keys must be strings".to_string()));
+            };
+            map.insert(key, self.value()?);
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], DecodeError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| DecodeError("unexpected end of input".to_string()))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array_of<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().expect("take returns exactly N bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trips_every_json_shape() {
+        let long_string = "x".repeat(300);
+        let many: Vec<u32> = (0..70_000).collect();
+        let value = json!({
+            "null": null,
+            "bools": [true, false],
+            "ints": [0, 127, 128, 255, 256, 65_535, 65_536, 4_294_967_296u64, u64::MAX],
+            "negatives": [-1, -32, -33, -128, -129, -32_768, -32_769, i64::MIN],
+            "floats": [1.5, -0.25],
+            "strings": ["", "short", long_string],
+            "nested": {"a": {"b": [1, {"c": "d"}]}},
+            "many": many,
+        });
+
+        let bytes = to_vec(&value).unwrap();
+        let decoded: Value = from_slice(&bytes).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn test_matches_the_spec_for_small_values() {
+        assert_eq!(
+            to_vec(&json!({"a": [1, -1, "b"]})).unwrap(),
+            vec![0x81, 0xa1, b'a', 0x93, 0x01, 0xff, 0xa1, b'b']
+        );
+    }
+
+    fn bytes_of<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+        to_vec(value).unwrap()
+    }
+
+    #[test]
+    fn test_encodes_scalars_as_the_spec_does() {
+        assert_eq!(bytes_of(&()), [0xc0]);
+        assert_eq!(bytes_of(&None::<u8>), [0xc0]);
+        assert_eq!(bytes_of(&false), [0xc2]);
+        assert_eq!(bytes_of(&true), [0xc3]);
+        assert_eq!(bytes_of(&0u8), [0x00]);
+        assert_eq!(bytes_of(&127u8), [0x7f]);
+        assert_eq!(bytes_of(&128u8), [0xcc, 0x80]);
+        assert_eq!(bytes_of(&256u16), [0xcd, 0x01, 0x00]);
+        assert_eq!(bytes_of(&65_536u32), [0xce, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(bytes_of(&(1u64 << 32)), [0xcf, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(bytes_of(&5i64), [0x05]);
+        assert_eq!(bytes_of(&-1i8), [0xff]);
+        assert_eq!(bytes_of(&-32i8), [0xe0]);
+        assert_eq!(bytes_of(&-33i8), [0xd0, 0xdf]);
+        assert_eq!(bytes_of(&-129i16), [0xd1, 0xff, 0x7f]);
+        assert_eq!(bytes_of(&-32_769i32), [0xd2, 0xff, 0xff, 0x7f, 0xff]);
+        assert_eq!(
+            bytes_of(&i64::MIN),
+            [0xd3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(bytes_of(&1.5f32), [0xca, 0x3f, 0xc0, 0x00, 0x00]);
+        assert_eq!(bytes_of(&1.5f64), [0xcb, 0x3f, 0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_encodes_headers_as_the_spec_does() {
+        assert_eq!(bytes_of(""), [0xa0]);
+        assert_eq!(bytes_of(&"x".repeat(31))[0], 0xbf);
+        assert_eq!(bytes_of(&"x".repeat(32))[..2], [0xd9, 0x20]);
+        assert_eq!(bytes_of(&"x".repeat(256))[..3], [0xda, 0x01, 0x00]);
+        assert_eq!(bytes_of(&[0u8; 15])[0], 0x9f);
+        assert_eq!(bytes_of(&vec![0u8; 16])[..3], [0xdc, 0x00, 0x10]);
+        assert_eq!(bytes_of(&vec![0u8; 65_536])[..5], [0xdd, 0x00, 0x01, 0x00, 0x00]);
+        let map: std::collections::BTreeMap<u8, u8> = (0..16).map(|i| (i, i)).collect();
+        assert_eq!(bytes_of(&map)[..3], [0xde, 0x00, 0x10]);
+        assert_eq!(bytes_of(&Bytes(b"ab")), [0xc4, 0x02, b'a', b'b']);
+    }
+
+    /// A byte string, serialized through `serialize_bytes` as `serde_bytes` would.
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn test_encodes_the_spec_example() {
+        #[derive(serde::Serialize)]
+        struct Example {
+            compact: bool,
+            schema: u8,
+        }
+
+        let mut expected = vec![0x82, 0xa7];
+        expected.extend_from_slice(b"compact");
+        expected.extend_from_slice(&[0xc3, 0xa6]);
+        expected.extend_from_slice(b"schema");
+        expected.push(0x00);
+        assert_eq!(bytes_of(&Example { compact: true, schema: 0 }), expected);
+        assert_eq!(bytes_of(&json!({"compact": true, "schema": 0})), expected);
+    }
+
+    #[test]
+    fn test_tags_enums_and_counts_unsized_maps() {
+        #[derive(serde::Serialize)]
+        enum Shape {
+            Point,
+            Circle(u8),
+            Line(u8, u8),
+            Rect { w: u8 },
+        }
+
+        #[derive(serde::Serialize)]
+        struct Flattened {
+            id: u8,
+            #[serde(flatten)]
+            rest: std::collections::BTreeMap<String, u8>,
+        }
+
+        assert_eq!(bytes_of(&Shape::Point), [0xa5, b'P', b'o', b'i', b'n', b't']);
+        assert_eq!(from_slice::<Value>(&bytes_of(&Shape::Circle(1))).unwrap(), json!({"Circle": 1}));
+        assert_eq!(from_slice::<Value>(&bytes_of(&Shape::Line(1, 2))).unwrap(), json!({"Line": [1, 2]}));
+        assert_eq!(from_slice::<Value>(&bytes_of(&Shape::Rect { w: 3 })).unwrap(), json!({"Rect": {"w": 3}}));
+
+        let flattened = Flattened { id: 1, rest: [("a".to_string(), 2)].into_iter().collect() };
+        assert_eq!(bytes_of(&flattened)[0], 0x82);
+        assert_eq!(from_slice::<Value>(&bytes_of(&flattened)).unwrap(), json!({"id": 1, "a": 2}));
+
+        let numbered: std::collections::BTreeMap<u8, bool> = [(7, true)].into_iter().collect();
+        assert_eq!(from_slice::<Value>(&bytes_of(&numbered)).unwrap(), json!({"7": true}));
+        let bad: std::collections::BTreeMap<(u8, u8), bool> = [((1, 2), true)].into_iter().collect();
+        assert!(to_vec(&bad).is_err());
+    }
+
+    #[test]
+    fn test_decodes_bin_as_a_byte_array() {
+        assert_eq!(from_slice::<Vec<u8>>(&[0xc4, 0x02, 0x01, 0x02]).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_rejects_truncated_input() {
+        let bytes = to_vec(&json!(["truncated"])).unwrap();
+        assert!(from_slice::<Value>(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
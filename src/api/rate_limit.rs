@@ -0,0 +1,300 @@
+// Per-caller rate limiting, so one client can't saturate the engine's worker pool.
+//
+// Every caller gets a token bucket for reads and another for writes. A caller is its API
+// key if the server knows the key, or else its IP address; the limiter runs before auth, so
+// a made-up key buys no bucket of its own. POST /query only reads, under /v1 or not, so it
+// counts as a read. Requests that find their bucket empty get 429 with Retry-After. Every
+// response carries the bucket's size and what is left in it.
+//
+// Buckets are spread over shards, each behind its own lock, so callers rarely wait on each
+// other. A shard holds a bounded number of buckets; a new caller in a full shard evicts the
+// one seen least recently, which has most likely refilled anyway.
+//
+// IPs come from axum's `ConnectInfo`, so serve the router with
+// `into_make_service_with_connect_info::<SocketAddr>()`. Without it, every caller without a
+// known API key shares one bucket.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, Method, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::{BTreeMap, HashMap, hash_map::RandomState};
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::api::ApiError;
+use crate::api::auth::{API_KEY_HEADER, ApiKeys};
+use crate::api::routes::API_VERSION;
+
+/// The size of the bucket a response was counted against.
+pub const LIMIT_HEADER: &str = "x-ratelimit-limit";
+/// How many requests the bucket would still allow right now.
+pub const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
+/// The most buckets kept at once, across every shard.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+/// How many independently locked shards the buckets are spread over.
+const SHARDS: usize = 16;
+
+/// A token bucket's shape: it holds `burst` requests and refills at `per_second`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+/// The budgets each caller gets, one for reads and one for writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    pub read: RateLimit,
+    pub write: RateLimit,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            read: RateLimit { per_second: 100.0, burst: 200 },
+            write: RateLimit { per_second: 20.0, burst: 40 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Budget {
+    Read,
+    Write,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    /// When the bucket was last used, as a tick of its shard's clock.
+    seen: u64,
+}
+
+type BucketKey = (String, Budget);
+
+/// Some of the buckets, and the order they were last used in.
+#[derive(Debug, Default)]
+struct Shard {
+    buckets: HashMap<BucketKey, Bucket>,
+    /// Each bucket's key by its `seen` tick, least recently used first.
+    by_seen: BTreeMap<u64, BucketKey>,
+    clock: u64,
+}
+
+impl Shard {
+    /// The bucket for `key`, marked as just used. A new bucket starts full, first evicting the
+    /// least recently used one if the shard already holds `capacity`.
+    fn touch(&mut self, key: BucketKey, capacity: usize, full: f64, now: Instant) -> &mut Bucket {
+        self.clock += 1;
+        let seen = self.clock;
+        if let Some(bucket) = self.buckets.get(&key) {
+            self.by_seen.remove(&bucket.seen);
+        } else if self.buckets.len() >= capacity
+            && let Some((_, oldest)) = self.by_seen.pop_first()
+        {
+            self.buckets.remove(&oldest);
+        }
+        self.by_seen.insert(seen, key.clone());
+        let bucket = self.buckets.entry(key).or_insert(Bucket { tokens: full, refilled_at: now, seen });
+        bucket.seen = seen;
+        bucket
+    }
+}
+
+/// What a request found in its bucket.
+enum Admission {
+    Allowed { remaining: u32 },
+    Limited { retry_after: Duration },
+}
+
+/// Every caller's buckets.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    shards: Vec<Mutex<Shard>>,
+    /// The most buckets one shard keeps.
+    shard_capacity: usize,
+    /// Picks a bucket's shard; seeded randomly, so callers can't aim at one.
+    hasher: RandomState,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self::with_capacity(limits, MAX_TRACKED_BUCKETS)
+    }
+
+    /// A limiter keeping at most `capacity` buckets, rounded up to a multiple of `SHARDS`.
+    fn with_capacity(limits: RateLimits, capacity: usize) -> Self {
+        Self {
+            limits,
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            shard_capacity: capacity.div_ceil(SHARDS).max(1),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    fn limit(&self, budget: Budget) -> RateLimit {
+        match budget {
+            Budget::Read => self.limits.read,
+            Budget::Write => self.limits.write,
+        }
+    }
+
+    /// How many tokens `bucket` holds at `now`, counting what has refilled since.
+    fn tokens_at(&self, bucket: &Bucket, budget: Budget, now: Instant) -> f64 {
+        let limit = self.limit(budget);
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        (bucket.tokens + elapsed * limit.per_second).min(limit.burst as f64)
+    }
+
+    fn admit(&self, caller: String, budget: Budget, now: Instant) -> Admission {
+        let limit = self.limit(budget);
+        let key = (caller, budget);
+        let shard = &self.shards[self.hasher.hash_one(&key) as usize % SHARDS];
+        let mut shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+        let bucket = shard.touch(key, self.shard_capacity, limit.burst as f64, now);
+        bucket.tokens = self.tokens_at(bucket, budget, now);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Admission::Allowed { remaining: bucket.tokens as u32 }
+        } else {
+            let wait = (1.0 - bucket.tokens) / limit.per_second;
+            Admission::Limited {
+                retry_after: Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX),
+            }
+        }
+    }
+}
+
+/// Which budget a request spends. The limiter sits outside the /v1 nest, so it sees both
+/// the versioned and the deprecated unprefixed paths.
+fn budget_for(method: &Method, path: &str) -> Budget {
+    let path = path.strip_prefix(&format!("/{}", API_VERSION)).filter(|rest| rest.starts_with('/')).unwrap_or(path);
+    match (method, path) {
+        (&Method::GET | &Method::HEAD, _) | (&Method::POST, "/query") => Budget::Read,
+        _ => Budget::Write,
+    }
+}
+
+/// The middleware `create_router` installs when the state carries a `RateLimiter`.
+pub(crate) async fn enforce(
+    State((limiter, api_keys)): State<(Arc<RateLimiter>, Arc<ApiKeys>)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = request.headers().get(API_KEY_HEADER).and_then(|key| key.to_str().ok());
+    let caller = match key.filter(|key| api_keys.contains_key(*key)) {
+        Some(key) => format!("key:{}", key),
+        None => match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
+        },
+    };
+    let budget = budget_for(request.method(), request.uri().path());
+    let limit = limiter.limit(budget);
+
+    let (mut response, remaining) = match limiter.admit(caller, budget, Instant::now()) {
+        Admission::Allowed { remaining } => (next.run(request).await, remaining),
+        Admission::Limited { retry_after } => {
+            // Retry-After is in whole seconds; round up so a retry never comes too soon.
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            let mut response = ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Rate limit exceeded; retry in {} second(s)", seconds),
+            )
+            .into_response();
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(seconds));
+            (response, 0)
+        }
+    };
+    let headers = response.headers_mut();
+    headers.insert(LIMIT_HEADER, HeaderValue::from(limit.burst));
+    headers.insert(REMAINING_HEADER, HeaderValue::from(remaining));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_refill_at_their_rate() {
+        let limiter = RateLimiter::new(RateLimits {
+            read: RateLimit { per_second: 2.0, burst: 2 },
+            write: RateLimit { per_second: 1.0, burst: 1 },
+        });
+        let start = Instant::now();
+        let admit = |budget, at: Duration| limiter.admit("key:a".to_string(), budget, start + at);
+
+        assert!(matches!(admit(Budget::Read, Duration::ZERO), Admission::Allowed { remaining: 1 }));
+        assert!(matches!(admit(Budget::Read, Duration::ZERO), Admission::Allowed { remaining: 0 }));
+        match admit(Budget::Read, Duration::ZERO) {
+            Admission::Limited { retry_after } => assert_eq!(retry_after, Duration::from_millis(500)),
+            Admission::Allowed { .. } => panic!("the read bucket should be empty"),
+        }
+        // Writes have their own bucket.
+        assert!(matches!(admit(Budget::Write, Duration::ZERO), Admission::Allowed { remaining: 0 }));
+        // Half a second brings back one read.
+        assert!(matches!(admit(Budget::Read, Duration::from_millis(500)), Admission::Allowed { remaining: 0 }));
+    }
+
+    #[test]
+    fn test_a_full_shard_forgets_its_least_recently_seen_bucket() {
+        let limiter = RateLimiter::with_capacity(
+            RateLimits { read: RateLimit { per_second: 0.001, burst: 1 }, ..RateLimits::default() },
+            SHARDS,
+        );
+        let now = Instant::now();
+        let admit = |caller: &str| limiter.admit(caller.to_string(), Budget::Read, now);
+        let shard_of = |caller: &str| limiter.hasher.hash_one((caller.to_string(), Budget::Read)) as usize % SHARDS;
+
+        // Two callers that share a shard holding one bucket.
+        let callers: Vec<String> =
+            (0..).map(|n| format!("ip:{}", n)).filter(|c| shard_of(c) == shard_of("ip:0")).take(2).collect();
+        assert!(matches!(admit(&callers[0]), Admission::Allowed { .. }));
+        assert!(matches!(admit(&callers[0]), Admission::Limited { .. }));
+        // A second caller takes the only slot, so the first comes back to a full bucket.
+        assert!(matches!(admit(&callers[1]), Admission::Allowed { .. }));
+        assert!(matches!(admit(&callers[0]), Admission::Allowed { .. }));
+        assert!(matches!(admit(&callers[0]), Admission::Limited { .. }));
+
+        let shard = limiter.shards[shard_of(&callers[0])].lock().unwrap();
+        assert_eq!((shard.buckets.len(), shard.by_seen.len()), (1, 1));
+        drop(shard);
+        // Bounded however many callers turn up.
+        for n in 0..1000 {
+            admit(&format!("ip:{}", n));
+        }
+        let tracked: usize = limiter.shards.iter().map(|shard| shard.lock().unwrap().buckets.len()).sum();
+        assert!(tracked <= SHARDS);
+    }
+
+    #[test]
+    fn test_recently_seen_buckets_outlive_idle_ones() {
+        let mut shard = Shard::default();
+        let now = Instant::now();
+        let key = |n: u32| (format!("ip:{}", n), Budget::Read);
+        for n in 0..3 {
+            shard.touch(key(n), 3, 1.0, now);
+        }
+        shard.touch(key(0), 3, 1.0, now).tokens = 0.0;
+        // The new caller evicts 1, the least recently seen, not 0.
+        shard.touch(key(3), 3, 1.0, now);
+        assert!(!shard.buckets.contains_key(&key(1)));
+        assert_eq!(shard.buckets[&key(0)].tokens, 0.0);
+        assert_eq!(shard.by_seen.values().cloned().collect::<Vec<_>>(), vec![key(2), key(0), key(3)]);
+    }
+}
@@ -1,79 +1,273 @@
-use axum::{extract::{State, Path}, routing::{get, post}, Json, Router};
-use tokio::task;
+// The HTTP API. Each resource's handlers live in a submodule with its routes; this module
+// holds the shared state, assembles the routers and keeps the helpers more than one uses.
+
+mod admin;
+mod concepts;
+mod graph;
+mod relationships;
+
+use axum::{body::Body, extract::{DefaultBodyLimit, OriginalUri, State, Request}, http::{HeaderValue, StatusCode, header}, middleware::Next, response::{IntoResponse, Redirect, Response}, routing::get, Json, Router};
 use std::sync::Arc;
-use crate::{graph::GraphEngine, types::concept::{ConceptData, ConceptId, Concept}, MnemonicError};
-use crate::types::relationship::{RelationshipId, RelationType};
-use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use std::time::Duration;
+use crate::{graph::GraphEngine, BatchItemError, MnemonicError};
+use crate::api::ApiError;
+use crate::api::auth::ApiKeys;
+use crate::api::compression;
+use crate::api::rate_limit::{self, RateLimiter, RateLimits};
+#[cfg(feature = "webhooks")]
+use crate::api::webhooks::Webhooks;
+use crate::api::types::{BatchPayload, HistoryOptions, MetaResponse, ReadinessResponse, ScanOptions, SnapshotOptions};
+use crate::graph::{Deadline, ScanBudget, TraversalLimits, DEFAULT_SCAN_BUDGET};
+use crate::types::query::{DEFAULT_LABEL_FIELDS, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+
+/// How many items a batch request may carry unless configured otherwise.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1000;
+
+/// Where `create_app` serves the API.
+pub const API_PREFIX: &str = "/api";
 
+/// The API version `create_router` serves under `/v1`.
+pub const API_VERSION: &str = "v1";
 
+/// The first segments of the API's paths, which it was served at before it moved under
+/// `API_PREFIX`; `create_app` redirects them.
+const LEGACY_ROOTS: [&str; 13] = [
+    "ping", "concepts", "path", "query", "graph", "snapshots", "stats", "relationship-types", "audit", "changes",
+    "relationships", "admin", "v1",
+];
+
+/// How large a request body may be unless configured otherwise.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// How long /graph, /path, /concepts/:id/neighborhood and GET /concepts may work on one
+/// request unless configured otherwise, before answering 504.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How far /path and /concepts/:id/neighborhood may walk unless configured otherwise.
+pub const DEFAULT_TRAVERSAL_LIMITS: TraversalLimits = TraversalLimits { max_depth: 6, max_concepts: 1000 };
 
 // This struct will hold all shared state for our application
 #[derive(Clone)]
 pub struct AppState {
     pub engine: Arc<GraphEngine>,
+    /// The API keys callers may authenticate with. Empty means authentication is off.
+    pub api_keys: Arc<ApiKeys>,
+    /// The most items a single batch request may carry.
+    pub max_batch_size: usize,
+    /// The most bytes a request body may carry.
+    pub max_body_bytes: usize,
+    /// The data fields tried, in order, for a node's label in /graph.
+    pub label_fields: Arc<Vec<String>>,
+    /// How far a traversal request may walk, whatever it asks for.
+    pub traversal_limits: TraversalLimits,
+    /// How much of the store one /graph, listing, aggregate, /query, traversal or delete-where
+    /// dry run may scan. Requests may ask for less with `ScanOptions`, never for more.
+    pub scan_budget: ScanBudget,
+    /// Where committed changes are delivered. Without it, /admin/webhooks answers 404.
+    #[cfg(feature = "webhooks")]
+    pub webhooks: Option<Arc<Webhooks>>,
+    /// Each caller's read and write budgets. Without it, requests aren't limited.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Whether DELETE /concepts/{id} without `cascade` refuses a concept that live
+    /// relationships still touch, rather than leaving them behind. On by default.
+    pub strict_deletes: bool,
+    /// How long a traversal, /graph or listing request may work before it is stopped and
+    /// answered with 504. `None` means no limit. Work for a client that disconnects is
+    /// stopped either way.
+    pub request_timeout: Option<Duration>,
 }
 
-// This defines the shape of the JSON we expect for creating a concept.
-// e.g {"data": {"name": "Alice"}}
-#[derive(Deserialize)]
-pub struct CreateConceptPayload {
-    data: serde_json::Value,
-}
+impl AppState {
+    /// State for an open server that doesn't require API keys.
+    pub fn new(engine: Arc<GraphEngine>) -> Self {
+        Self {
+            engine,
+            api_keys: Arc::new(ApiKeys::new()),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            label_fields: Arc::new(DEFAULT_LABEL_FIELDS.iter().map(|f| f.to_string()).collect()),
+            traversal_limits: DEFAULT_TRAVERSAL_LIMITS,
+            scan_budget: DEFAULT_SCAN_BUDGET,
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
+            rate_limiter: None,
+            strict_deletes: true,
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+        }
+    }
 
-// This defines the shape of the JSON we will send back.
-// e.g {"concept_id": "..."}
-#[derive(Serialize, Deserialize)]
-pub struct CreateConceptResponse {
-    concept_id: Uuid,
-}
+    /// The deadline for one request's work, `request_timeout` from now.
+    pub fn deadline(&self) -> Deadline {
+        Deadline::within(self.request_timeout)
+    }
+
+    /// The scan budget for one request: `scan_budget`, narrowed to what it asked for.
+    pub fn scan_budget_for(&self, options: &ScanOptions) -> ScanBudget {
+        self.scan_budget
+            .narrowed(options.max_items, options.max_duration_ms.map(Duration::from_millis))
+    }
+
+    /// Change which data fields, in order, name a node in /graph.
+    pub fn with_label_fields(mut self, label_fields: Vec<String>) -> Self {
+        self.label_fields = Arc::new(label_fields);
+        self
+    }
 
-// These structs are simplified for the UI. It doesn't need all the metadata.
-#[derive(Serialize, Deserialize)]
+    /// Change how many items a single batch request may carry.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    /// Change how many bytes a request body may carry.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// Change how far /path and /concepts/:id/neighborhood may walk.
+    pub fn with_traversal_limits(mut self, traversal_limits: TraversalLimits) -> Self {
+        self.traversal_limits = traversal_limits;
+        self
+    }
+
+    /// Change how much of the store one scanning request may look at.
+    pub fn with_scan_budget(mut self, scan_budget: ScanBudget) -> Self {
+        self.scan_budget = scan_budget;
+        self
+    }
+
+    /// Serve /admin/webhooks from these webhooks.
+    #[cfg(feature = "webhooks")]
+    pub fn with_webhooks(mut self, webhooks: Arc<Webhooks>) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Limit each caller, by API key or else by IP, to these budgets.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(limits)));
+        self
+    }
+
+    /// Change whether DELETE /concepts/{id} without `cascade` refuses a concept that live
+    /// relationships still touch.
+    pub fn with_strict_deletes(mut self, strict_deletes: bool) -> Self {
+        self.strict_deletes = strict_deletes;
+        self
+    }
+
+    /// Change how long a traversal, /graph or listing request may work, or lift the limit.
+    pub fn with_request_timeout(mut self, request_timeout: Option<Duration>) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
 
-struct GraphNode {
-    id: String,
-    label: String,
+    /// Require callers to present one of these API keys.
+    pub fn with_api_keys(mut self, api_keys: ApiKeys) -> Self {
+        self.api_keys = Arc::new(api_keys);
+        self
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+// This is our main router function. It will define all the `buttons` on our API vending machine.
+// Everything is served under /v1. The unprefixed paths came first and still answer, marked
+// deprecated, until clients have moved; new routes only go under /v1.
+pub fn create_router(app_state: AppState) -> Router {
+    let routes = Router::new()
+        .route("/ping", get(ping))
+        .merge(concepts::router())
+        .merge(relationships::router())
+        .merge(graph::router())
+        .merge(admin::router());
+    #[cfg(feature = "webhooks")]
+    let routes = routes.merge(crate::api::webhooks::router());
+    let router = Router::new()
+        .nest(
+            &format!("/{}", API_VERSION),
+            routes
+                .clone()
+                .route("/meta", get(meta))
+                .route("/readyz", get(readyz))
+                .route("/metrics", get(admin::get_metrics)),
+        )
+        .merge(routes.layer(axum::middleware::from_fn(deprecated)));
+    let router = router
+        .layer(axum::middleware::from_fn_with_state(app_state.max_body_bytes, enforce_body_limit))
+        .layer(DefaultBodyLimit::disable());
+    let router = match app_state.rate_limiter.clone() {
+        Some(limiter) => router.layer(axum::middleware::from_fn_with_state(
+            (limiter, Arc::clone(&app_state.api_keys)),
+            rate_limit::enforce,
+        )),
+        None => router,
+    };
+    router.layer(axum::middleware::from_fn(compression::gzip)).with_state(app_state)
+}
 
-struct GraphEdge {
-    id: String,
-    source: String,
-    target: String,
-    label: String,
+/// Flags a response to an unprefixed path as deprecated, with a `Link` to the same path
+/// under /v1. The link keeps whatever prefix the router is nested under, such as /api.
+async fn deprecated(OriginalUri(original): OriginalUri, request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    let mount = original.path().strip_suffix(path).unwrap_or_default();
+    let successor = format!("<{}/{}{}>; rel=\"successor-version\"", mount, API_VERSION, path);
+    let mut response = next.run(request).await;
+    response.headers_mut().insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        response.headers_mut().insert(header::LINK, link);
+    }
+    response
 }
 
-#[derive(Serialize, Deserialize)]
-struct GraphData {
-    nodes: Vec<GraphNode>,
-    edges: Vec<GraphEdge>,
+/// What the mre binary serves: `create_router` under `/api`, permanent redirects to /api/v1
+/// from the paths the API used to have at the root, and, with the `ui` feature and `ui` set, the
+/// graph explorer at `/`.
+pub fn create_app(app_state: AppState, ui: bool) -> Router {
+    Router::new()
+        .nest(API_PREFIX, create_router(app_state))
+        .fallback(move |request: Request| app_fallback(request, ui))
 }
 
-// Request: { "source": "...", "type": "...", "target": "..." }
-#[derive(Deserialize)]
-struct RelatePayload {
-    source: ConceptId,
-    #[serde(rename = "type")]
-    relationship_type: RelationType,
-    target: ConceptId,
+async fn app_fallback(request: Request, ui: bool) -> Response {
+    let uri = request.uri();
+    let root = uri.path().trim_start_matches('/').split('/').next().unwrap_or_default();
+    if LEGACY_ROOTS.contains(&root) {
+        // 308 rather than 301, so clients repeat a POST or DELETE, body and all, at the new path.
+        let location = match uri.query() {
+            Some(query) => format!("{}/{}{}?{}", API_PREFIX, API_VERSION, versioned(uri.path()), query),
+            None => format!("{}/{}{}", API_PREFIX, API_VERSION, versioned(uri.path())),
+        };
+        return Redirect::permanent(&location).into_response();
+    }
+    // Anything else is the UI's, except unknown API paths, which get the API's JSON 404.
+    #[cfg(feature = "ui")]
+    if ui && root != API_PREFIX.trim_start_matches('/') {
+        return crate::api::ui::serve(request.method(), uri);
+    }
+    #[cfg(not(feature = "ui"))]
+    let _ = ui;
+    ApiError::new(StatusCode::NOT_FOUND, format!("No route for {}", uri.path())).into_response()
 }
 
-#[derive(Serialize)]
-struct RelateResponse {
-    relationship_id: RelationshipId,
+/// Reads the body up to the limit, refusing the request with the limit named if there is
+/// more. Handlers then get the buffered body, so extractors never meet axum's own limit.
+async fn enforce_body_limit(State(limit): State<usize>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    match axum::body::to_bytes(body, limit).await {
+        Ok(bytes) => next.run(Request::from_parts(parts, Body::from(bytes))).await,
+        Err(_) => ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body is over the limit of {} bytes", limit),
+        )
+        .into_response(),
+    }
 }
 
-// This is our main router function. It will define all the `buttons` on our API vending machine.
-pub fn create_router(app_state: AppState) -> Router {
-    Router::new()
-    .route("/ping", get(ping))
-    .route("/concepts", post(create_concept))
-    .route("/concepts/{id}", get(get_concept_details))
-    .route("/graph", get(get_graph_data))
-    .route("/relationships", post(relate_concepts))
-    .with_state(app_state)
+/// A root path without the `/v1` it may already start with.
+fn versioned(path: &str) -> &str {
+    path.strip_prefix("/v1").filter(|rest| rest.is_empty() || rest.starts_with('/')).unwrap_or(path)
 }
 
 // This is an `handler function`. It's the logic that runs when someone requests `/ping`.
@@ -81,103 +275,247 @@ async fn ping() -> &'static str {
     "pong"
 }
 
-async fn create_concept(
-    State(state): State<AppState>,
-    Json(payload): Json<CreateConceptPayload>,
-) -> Result<Json<CreateConceptResponse>, String> {
-    print!("Received request to create concept with data: {:?}", payload.data);
+/// Whether the server has finished warming up. It answers requests either way.
+async fn readyz(State(state): State<AppState>) -> Json<ReadinessResponse> {
+    Json(ReadinessResponse { warmed: state.engine.warmed() })
+}
 
-    // This is where we finally call the engine we built!
-    match state.engine.store(payload.data).await {
-        Ok(concept_id) => Ok(Json(CreateConceptResponse { concept_id })),
-        Err(e) => Err(format!("Failed to store concept: {}", e)),
-    }
+/// What this server is: `GET /v1/meta` gives the crate and API versions and the cargo
+/// features it was built with.
+async fn meta() -> Json<MetaResponse> {
+    let features = [
+        ("client", cfg!(feature = "client")),
+        ("webhooks", cfg!(feature = "webhooks")),
+        ("ui", cfg!(feature = "ui")),
+        ("testing", cfg!(feature = "testing")),
+    ];
+    Json(MetaResponse {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: API_VERSION.to_string(),
+        features: std::iter::once("server")
+            .chain(features.into_iter().filter_map(|(name, enabled)| enabled.then_some(name)))
+            .map(str::to_string)
+            .collect(),
+    })
 }
 
- async fn relate_concepts(
-        State(state): State<AppState>,
-        Json(payload): Json<RelatePayload>,
-    ) -> Result<Json<RelateResponse>, String> {
-        
-        match state.engine.relate(payload.source, payload.relationship_type, payload.target).await {
-            Ok(relationship_id) => Ok(Json(RelateResponse { relationship_id })),
-            Err(e) => Err(format!("Failed to relate concepts: {}", e)),
-        }
+/// Checks the batch size and parses every item, reporting all malformed items at once.
+fn parse_batch<T: DeserializeOwned>(state: &AppState, payload: BatchPayload) -> Result<Vec<T>, ApiError> {
+    if payload.items.len() > state.max_batch_size {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Batch of {} items exceeds the limit of {}",
+                payload.items.len(),
+                state.max_batch_size
+            ),
+        ));
     }
 
-    async fn get_graph_data(
-    State(state): State<AppState>,
-) -> Result<Json<GraphData>, String> {
-    
-    // We get the Transaction Manager...
-    let tm = state.engine.transaction_manager();
-    // ...and from it, the already-hydrated Version Store.
-    let vs = tm.version_store();
-
-    // Spawn a blocking task because RwLock is synchronous.
-    let graph_data_result = task::spawn_blocking(move || {
-        // Fetch nodes from the IN-MEMORY, hydrated Version Store.
-        let nodes: Vec<GraphNode> = vs.get_all_active_concepts().unwrap_or_default()
-            .iter()
-            .map(|version| GraphNode {
-                id: version.concept_id.to_string(),
-                label: match &version.data {
-                    ConceptData::Structured(s) => {
-                        let json: serde_json::Value = serde_json::from_str(s).unwrap_or_default();
-                        json.get("name").and_then(|v| v.as_str()).unwrap_or("Concept").to_string()
-                    },
-                    _ => "Concept".to_string(),
-                }
-            })
-            .collect();
-    
-        // Fetch edges from the IN-MEMORY, hydrated Version Store.
-        let edges: Vec<GraphEdge> = vs.get_all_active_relationships().unwrap_or_default()
-            .iter()
-            .map(|version| GraphEdge {
-                id: version.relationship_id.to_string(),
-                source: version.source.to_string(),
-                target: version.target.to_string(),
-                label: version.relationship_type.clone(),
-            })
-            .collect();
-            
-        Ok(GraphData { nodes, edges })
-    }).await.map_err(|e| format!("Task error: {}", e))?;
+    let mut items = Vec::with_capacity(payload.items.len());
+    let mut errors = Vec::new();
+    for (index, item) in payload.items.into_iter().enumerate() {
+        match serde_json::from_value(item) {
+            Ok(item) => items.push(item),
+            Err(e) => errors.push(BatchItemError {
+                index,
+                error: e.to_string(),
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(MnemonicError::InvalidBatch(errors).into())
+    }
+}
 
-    let graph_data: GraphData = graph_data_result.map_err(|e: MnemonicError| e.to_string())?;
+/// The timestamp `?snapshot=<token>` names, if given; 404 once the snapshot is gone.
+async fn snapshot_timestamp(state: &AppState, options: &SnapshotOptions) -> Result<Option<DateTime<Utc>>, ApiError> {
+    match options.snapshot {
+        Some(token) => Ok(Some(state.engine.snapshot_timestamp(token).await?)),
+        None => Ok(None),
+    }
+}
 
-    tracing::info!("Returning {} nodes and {} edges", graph_data.nodes.len(), graph_data.edges.len());
-    Ok(Json(graph_data))
+/// The version a commit receipt records for `id`, or 0 if it wrote none.
+fn version_of<I: PartialEq>(versions: &[(I, u64)], id: I) -> u64 {
+    versions.iter().find(|(written, _)| *written == id).map_or(0, |(_, version)| *version)
 }
 
-/// This handler will be called for requests to `/concepts/:id`
-async fn get_concept_details(
-    State(state): State<AppState>,
-    Path(id): Path<Uuid>, //Axum extracts the ID from the URL path
-) -> Result<Json<Concept>, String> {
-    match state.engine.get_concept(id).await {
-        Ok(Some(concept)) => Ok(Json(concept)),
-        Ok(None) => Err(format!("Concept with ID {} not found", id)),
-        Err(e) => Err(format!("Failed to retrieve concept: {}", e)),
+/// Picks the page of an oldest-first version chain that `options` asks for, newest first,
+/// and the `before_version` of the next page. `stamps` reads a version's number, creation
+/// and deletion times.
+fn select_history<V>(
+    history: Vec<V>,
+    options: &HistoryOptions,
+    stamps: impl Fn(&V) -> (u64, DateTime<Utc>, Option<DateTime<Utc>>),
+) -> (Vec<V>, Option<u64>) {
+    if let Some(at) = options.at {
+        // The newest version created by then decides; if it was already deleted, nothing was live.
+        let live = history
+            .into_iter()
+            .rev()
+            .find(|v| stamps(v).1 <= at)
+            .filter(|v| stamps(v).2.is_none_or(|deleted| deleted > at));
+        return (live.into_iter().collect(), None);
+    }
+    let limit = options.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let mut page: Vec<V> = history
+        .into_iter()
+        .rev()
+        .filter(|v| options.before_version.is_none_or(|before| stamps(v).0 < before))
+        .take(limit + 1)
+        .collect();
+    if page.len() <= limit {
+        return (page, None);
     }
+    page.truncate(limit);
+    let next = page.last().map(|v| stamps(v).0);
+    (page, next)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*; // Import everything from the parent module (routes.rs)
-    use crate::graph::GraphEngine;
+    use crate::api::auth::{API_KEY_HEADER, ApiKey};
+    use crate::api::types::{
+        BatchResponse, ConceptHistoryEntry, CreateConceptResponse, DegreeResponse, GraphData, GraphDeltaResponse, GraphEdge,
+        EndpointConcept, GraphNode, HistoryPage, RelateResponse, RelationshipHistoryEntry, RelationshipResponse,
+        TraversalResponse,
+    };
+    use crate::graph::{
+        ActivityBucket, Archive, AuditPage, BulkDeleteReport, CheckReport, CommitEvent, CommitFeed, GraphEngine, GraphSnapshot, GraphStats, IsolationLevel,
+        PruneReport, QueryResult, ReadSnapshot, RebuildReport, RelateOptions, ScanBudget, StoreOptions,
+        TransactionSummary,
+    };
+    use crate::storage::StorageStats;
+    use crate::types::concept::{Concept, ConceptData, ConceptId, ConceptKind, ConceptVersion};
+    use crate::types::query::ConceptPage;
+    use crate::types::relationship::{Relationship, RelationshipTypeUsage, RelationshipVersion};
     use axum_test::TestServer; 
     use serde_json::json;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use uuid::Uuid;
     use tempfile::tempdir;
 
     /// Helper function to quickly create a testable server.
     fn setup_test_server() -> TestServer {
+        setup_test_server_with_engine().0
+    }
+
+    /// Like `setup_test_server`, but also hands back the engine so tests can act on it directly.
+    fn setup_test_server_with_engine() -> (TestServer, Arc<GraphEngine>) {
         let dir = tempdir().unwrap();
         let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
-        let app_state = AppState { engine };
+        let app_state = AppState::new(Arc::clone(&engine));
         let app = create_router(app_state);
-        TestServer::new(app).unwrap()
+        (TestServer::new(app).unwrap(), engine)
+    }
+
+    #[tokio::test]
+    async fn test_the_app_serves_the_api_under_its_prefix_and_redirects_the_old_paths() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let server = TestServer::new(create_app(AppState::new(engine), false)).unwrap();
+
+        let response = server.get("/api/v1/ping").await;
+        response.assert_status_ok();
+        response.assert_text("pong");
+        server.post("/api/v1/concepts").json(&json!({"data": {"name": "Ada"}})).await.assert_status_ok();
+
+        let redirect = server.get("/concepts").add_query_param("limit", 5).await;
+        redirect.assert_status(StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(redirect.header("location"), "/api/v1/concepts?limit=5");
+        let redirect = server.post("/admin/prune").await;
+        redirect.assert_status(StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(redirect.header("location"), "/api/v1/admin/prune");
+        assert_eq!(server.get("/v1/stats").await.header("location"), "/api/v1/stats");
+
+        // Under /api, the unversioned paths are deprecated aliases; their successors keep /api.
+        let legacy = server.get("/api/concepts").await;
+        legacy.assert_status_ok();
+        assert_eq!(legacy.header("link"), "</api/v1/concepts>; rel=\"successor-version\"");
+
+        server.get("/api/nowhere").await.assert_status(StatusCode::NOT_FOUND);
+        server.get("/").await.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_routes_answer_under_v1_and_deprecated_at_their_old_paths() {
+        let server = setup_test_server();
+
+        let created: CreateConceptResponse =
+            server.post("/v1/concepts").json(&json!({"data": {"name": "Ada"}})).await.json();
+        let current = server.get(&format!("/v1/concepts/{}", created.concept_id)).await;
+        current.assert_status_ok();
+        assert!(current.maybe_header("deprecation").is_none());
+
+        let legacy = server.get(&format!("/concepts/{}", created.concept_id)).await;
+        legacy.assert_status_ok();
+        assert_eq!(legacy.json::<Concept>(), current.json::<Concept>());
+        assert_eq!(legacy.header("deprecation"), "true");
+        assert_eq!(
+            legacy.header("link"),
+            format!("</v1/concepts/{}>; rel=\"successor-version\"", created.concept_id).as_str()
+        );
+        // Errors from an old path are flagged too, and new routes have no unversioned alias.
+        let missing = server.get(&format!("/concepts/{}", Uuid::new_v4())).await;
+        missing.assert_status(StatusCode::NOT_FOUND);
+        assert_eq!(missing.header("deprecation"), "true");
+        server.get("/meta").await.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_meta_names_the_versions_and_features() {
+        let server = setup_test_server();
+        let meta: MetaResponse = server.get("/v1/meta").await.json();
+        assert_eq!(meta.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(meta.api_version, "v1");
+        assert!(meta.features.contains(&"server".to_string()));
+        assert_eq!(meta.features.contains(&"webhooks".to_string()), cfg!(feature = "webhooks"));
+    }
+
+    #[tokio::test]
+    async fn test_readyz_says_whether_the_store_is_warm() {
+        let server = setup_test_server();
+        let ready: ReadinessResponse = server.get("/v1/readyz").await.json();
+        assert!(ready.warmed);
+        let stats: GraphStats = server.get("/v1/stats").await.json();
+        assert!(stats.warmed);
+    }
+
+    #[tokio::test]
+    async fn test_a_request_out_of_time_is_answered_with_504() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let source = engine.store(json!({"name": "A"})).await.unwrap();
+        let target = engine.store(json!({"name": "B"})).await.unwrap();
+        engine.relate(source, "knows", target).await.unwrap();
+        let state = AppState::new(Arc::clone(&engine)).with_request_timeout(Some(Duration::ZERO));
+        let server = TestServer::new(create_router(state)).unwrap();
+
+        let path = server.get(&format!("/path?from={}&to={}", source, target)).await;
+        path.assert_status(StatusCode::GATEWAY_TIMEOUT);
+        server.get("/graph").await.assert_status(StatusCode::GATEWAY_TIMEOUT);
+        server.get("/concepts").await.assert_status(StatusCode::GATEWAY_TIMEOUT);
+
+        let server = TestServer::new(create_router(AppState::new(engine).with_request_timeout(None))).unwrap();
+        server.get(&format!("/path?from={}&to={}", source, target)).await.assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_served_as_prometheus_text() {
+        let (server, engine) = setup_test_server_with_engine();
+        engine.store(json!({"name": "Ada"})).await.unwrap();
+        let response = server.get("/v1/metrics").await;
+        response.assert_status_ok();
+        assert!(response.header("content-type").to_str().unwrap().starts_with("text/plain"));
+        let body = response.text();
+        assert!(body.contains("# TYPE mnemonic_transactions_committed_total counter\nmnemonic_transactions_committed_total 1\n"));
+        assert!(body.contains("mnemonic_storage_slow_ops_total 0\n"));
+        // Statistics are off by default, so there is no sample to show.
+        assert!(!body.contains("mnemonic_rocksdb_"));
     }
 
     #[tokio::test]
@@ -258,4 +596,1866 @@ mod tests {
         assert_eq!(edge.target, project_id.to_string());
         assert_eq!(edge.label, "works_on");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_full_api_lifecycle_for_deleting_a_concept() {
+        let server = setup_test_server();
+        let create = async |name: &str| -> Uuid {
+            let created: CreateConceptResponse =
+                server.post("/concepts").json(&json!({"data": {"name": name}})).await.json();
+            created.concept_id
+        };
+        let person_id = create("API Alice").await;
+        let project_id = create("API Project").await;
+        let rel: RelateResponse = server
+            .post("/relationships")
+            .json(&json!({"source": person_id, "type": "works_on", "target": project_id}))
+            .await
+            .json();
+
+        // Still referenced, so a plain delete is refused and names the edge in the way.
+        let refused = server.delete(&format!("/concepts/{}", project_id)).await;
+        refused.assert_status(StatusCode::CONFLICT);
+        assert_eq!(refused.json::<serde_json::Value>()["relationships"], json!([rel.relationship_id]));
+
+        let report: crate::graph::DeleteReport =
+            server.delete(&format!("/concepts/{}?cascade=true", project_id)).await.json();
+        assert_eq!(report.concept_id, project_id);
+        assert_eq!(report.relationships_deleted, vec![rel.relationship_id]);
+
+        let graph: GraphData = server.get("/graph").await.json();
+        assert_eq!(graph.nodes.len(), 1);
+        assert_eq!(graph.nodes[0].id, person_id.to_string());
+        assert!(graph.edges.is_empty());
+
+        // Gone already, and never there.
+        server.delete(&format!("/concepts/{}", project_id)).await.assert_status(StatusCode::NOT_FOUND);
+        server.delete(&format!("/concepts/{}", Uuid::new_v4())).await.assert_status(StatusCode::NOT_FOUND);
+        server.delete(&format!("/concepts/{}", person_id)).await.assert_status(StatusCode::NO_CONTENT);
+        server.get(&format!("/concepts/{}", person_id)).await.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_deletes_leave_relationships_behind() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let server =
+            TestServer::new(create_router(AppState::new(Arc::clone(&engine)).with_strict_deletes(false))).unwrap();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let charles = engine.store(json!({"name": "Charles"})).await.unwrap();
+        engine.relate(ada, "knows".to_string(), charles).await.unwrap();
+
+        server.delete(&format!("/concepts/{}", charles)).await.assert_status(StatusCode::NO_CONTENT);
+        assert_eq!(engine.retrieve_by_source(ada).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_admin_transactions_listing() {
+        let (server, engine) = setup_test_server_with_engine();
+
+        let first = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let second = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+
+        let listed: Vec<TransactionSummary> = server.get("/admin/transactions").await.json();
+        assert_eq!(listed.len(), 2);
+        assert!(listed.iter().any(|summary| summary.id == first.id));
+        assert!(listed.iter().any(|summary| summary.id == second.id));
+
+        let single = server.get(&format!("/admin/transactions/{}", first.id)).await;
+        single.assert_status_ok();
+        assert_eq!(single.json::<TransactionSummary>().id, first.id);
+
+        engine.commit_transaction(first.clone()).await.unwrap();
+        engine.abort_transaction(second.id).await.unwrap();
+
+        let listed: Vec<TransactionSummary> = server.get("/admin/transactions").await.json();
+        assert!(listed.is_empty());
+
+        server
+            .get(&format!("/admin/transactions/{}", first.id))
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_api_key_actor_is_recorded_on_commit() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let api_keys = ApiKeys::from([(
+            "alice-key".to_string(),
+            ApiKey {
+                actor: "alice".to_string(),
+                admin: false,
+            },
+        )]);
+        let app = create_router(AppState::new(Arc::clone(&engine)).with_api_keys(api_keys));
+        let server = TestServer::new(app).unwrap();
+
+        // Without a valid key, writes are refused.
+        server
+            .post("/concepts")
+            .json(&json!({"data": {"name": "Anonymous"}}))
+            .await
+            .assert_status_unauthorized();
+
+        let created: CreateConceptResponse = server
+            .post("/concepts")
+            .add_header(API_KEY_HEADER, "alice-key")
+            .json(&json!({"data": {"name": "Alice's note"}}))
+            .await
+            .json();
+
+        let version = engine
+            .transaction_manager()
+            .version_store()
+            .get_latest_concept_version(&created.concept_id)
+            .unwrap()
+            .unwrap();
+        let metadata = engine
+            .transaction_metadata(version.created_by)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.actor.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_engine_panic_becomes_json_500() {
+        let (server, engine) = setup_test_server_with_engine();
+
+        engine.transaction_manager().set_panic_on_commit(true);
+        let response = server
+            .post("/concepts")
+            .json(&json!({"data": {"name": "Doomed"}}))
+            .await;
+        engine.transaction_manager().set_panic_on_commit(false);
+
+        response.assert_status_internal_server_error();
+        let body: serde_json::Value = response.json();
+        assert!(body["error"].as_str().unwrap().contains("panicked"));
+
+        // The server keeps working afterwards, and unknown concepts are a clean 404.
+        server
+            .post("/concepts")
+            .json(&json!({"data": {"name": "Fine"}}))
+            .await
+            .assert_status_ok();
+        server
+            .get(&format!("/concepts/{}", Uuid::new_v4()))
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_list_concepts_pages_without_duplicates_or_gaps() {
+        let (server, engine) = setup_test_server_with_engine();
+        let mut expected = std::collections::BTreeSet::new();
+        for i in 0..250 {
+            expected.insert(engine.store(json!({ "n": i })).await.unwrap());
+        }
+        // Deleted concepts never show up.
+        let deleted = engine.store(json!({"name": "Gone"})).await.unwrap();
+        engine.delete_concept(deleted).await.unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<Uuid> = None;
+        let mut pages = 0;
+        loop {
+            let url = match cursor {
+                Some(cursor) => format!("/concepts?limit=100&cursor={}", cursor),
+                None => "/concepts?limit=100".to_string(),
+            };
+            let page: ConceptPage = server.get(&url).await.json();
+            pages += 1;
+            seen.extend(page.concepts.iter().map(|concept| concept.id));
+
+            // A concept created mid-listing must not disturb the remaining pages.
+            if pages == 1 {
+                engine.store(json!({"name": "Latecomer"})).await.unwrap();
+            }
+
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]), "pages must be in id order");
+        let seen_set: std::collections::BTreeSet<Uuid> = seen.iter().copied().collect();
+        assert_eq!(seen_set.len(), seen.len(), "no duplicates");
+        assert!(expected.is_subset(&seen_set), "no gaps");
+        assert!(!seen_set.contains(&deleted));
+        // 250 concepts (plus possibly the latecomer) fit in three pages of 100.
+        assert_eq!(pages, 3);
+    }
+
+    #[tokio::test]
+    async fn test_list_concepts_filters_by_label() {
+        let (server, engine) = setup_test_server_with_engine();
+        let person = engine
+            .store(json!({"name": "Ada", "labels": ["Person"]}))
+            .await
+            .unwrap();
+        engine
+            .store(json!({"name": "Engine", "labels": ["Project"]}))
+            .await
+            .unwrap();
+
+        let page: ConceptPage = server.get("/concepts?label=Person").await.json();
+        assert_eq!(page.concepts.len(), 1);
+        assert_eq!(page.concepts[0].id, person);
+        assert_eq!(page.concepts[0].labels, vec!["Person".to_string()]);
+        assert_eq!(page.concepts[0].data["name"], "Ada");
+        assert!(page.next_cursor.is_none());
+
+        let page: ConceptPage = server
+            .get("/concepts")
+            .add_query_param("predicate", json!({"not": {"label": "Person"}}).to_string())
+            .await
+            .json();
+        assert_eq!(page.concepts.len(), 1);
+        assert_eq!(page.concepts[0].data["name"], "Engine");
+        server.get("/concepts").add_query_param("predicate", "{\"gte\": 1}").await.assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_list_and_graph_filter_by_tag() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let bob = engine.store(json!({"name": "Bob"})).await.unwrap();
+        let eve = engine.store(json!({"name": "Eve"})).await.unwrap();
+        engine.relate(ada, "knows".to_string(), bob).await.unwrap();
+        engine.relate(bob, "knows".to_string(), eve).await.unwrap();
+        engine.add_tags(ada, ["starred"]).await.unwrap();
+        engine.add_tags(bob, ["starred", "needs-review"]).await.unwrap();
+
+        let page: ConceptPage = server.get("/concepts?tag=needs-review").await.json();
+        assert_eq!(page.concepts.iter().map(|c| c.id).collect::<Vec<_>>(), vec![bob]);
+        assert!(page.concepts[0].tags.contains("starred"));
+
+        // Only the tagged concepts, and the edges with both ends among them.
+        let graph: GraphData = server.get("/graph?tag=starred").await.json();
+        let mut nodes: Vec<String> = graph.nodes.iter().map(|node| node.id.clone()).collect();
+        nodes.sort();
+        let mut expected = vec![ada.to_string(), bob.to_string()];
+        expected.sort();
+        assert_eq!(nodes, expected);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!((graph.edges[0].source.clone(), graph.edges[0].target.clone()), (ada.to_string(), bob.to_string()));
+        assert!(graph.nodes.iter().all(|node| node.tags.contains(&"starred".to_string())));
+
+        let everything: GraphData = server.get("/graph?shape=minimal").await.json();
+        assert_eq!((everything.nodes.len(), everything.edges.len()), (3, 2));
+        assert!(everything.nodes.iter().all(|node| node.tags.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_batch_create_concepts_and_relationships() {
+        let (server, engine) = setup_test_server_with_engine();
+
+        let concepts: BatchResponse = server
+            .post("/concepts/batch")
+            .json(&json!({"items": [{"data": {"name": "Ada"}}, {"data": {"name": "Engine"}}]}))
+            .await
+            .json();
+        assert_eq!(concepts.ids.len(), 2);
+        let ada = engine.get_concept(concepts.ids[0]).await.unwrap().unwrap();
+        assert_eq!(ada.data, ConceptData::Structured(r#"{"name":"Ada"}"#.to_string().into()));
+
+        let relationships: BatchResponse = server
+            .post("/relationships/batch")
+            .json(&json!({"items": [
+                {"source": concepts.ids[0], "type": "works_on", "target": concepts.ids[1]},
+                {"source": concepts.ids[1], "type": "built_by", "target": concepts.ids[0]},
+            ]}))
+            .await
+            .json();
+        assert_eq!(relationships.ids.len(), 2);
+        assert_eq!(engine.retrieve_by_source(concepts.ids[0]).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_bad_item_commits_nothing() {
+        let (server, engine) = setup_test_server_with_engine();
+
+        // A malformed concept item rejects the whole batch.
+        let response = server
+            .post("/concepts/batch")
+            .json(&json!({"items": [{"data": {"name": "Fine"}}, {"oops": true}]}))
+            .await;
+        response.assert_status_unprocessable_entity();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["items"][0]["index"], 1);
+        assert!(engine.list_concepts(Default::default()).await.unwrap().concepts.is_empty());
+
+        // So does a relationship pointing at a concept that doesn't exist.
+        let existing = engine.store(json!({"name": "Real"})).await.unwrap();
+        let missing = Uuid::new_v4();
+        let response = server
+            .post("/relationships/batch")
+            .json(&json!({"items": [
+                {"source": existing, "type": "knows", "target": existing},
+                {"source": existing, "type": "knows", "target": missing},
+            ]}))
+            .await;
+        response.assert_status_unprocessable_entity();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["items"].as_array().unwrap().len(), 1);
+        assert_eq!(body["items"][0]["index"], 1);
+        assert!(body["items"][0]["error"].as_str().unwrap().contains(&missing.to_string()));
+        assert!(engine.retrieve_by_source(existing).await.unwrap().is_empty());
+        assert!(engine.active_transactions().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_concept_violating_schema_is_unprocessable() {
+        let (server, engine) = setup_test_server_with_engine();
+        engine
+            .register_schema("person", json!({"required": ["name"], "properties": {"name": {"type": "string"}}}))
+            .await
+            .unwrap();
+
+        let response = server
+            .post("/concepts")
+            .json(&json!({"data": {"labels": ["person"], "name": 7}}))
+            .await;
+        response.assert_status_unprocessable_entity();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["violations"][0]["path"], "/name");
+        assert_eq!(body["violations"][0]["label"], "person");
+        assert_eq!(engine.count_concepts().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_size_limit() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let app = create_router(AppState::new(Arc::clone(&engine)).with_max_batch_size(2));
+        let server = TestServer::new(app).unwrap();
+
+        let items: Vec<_> = (0..3).map(|i| json!({"data": {"n": i}})).collect();
+        server
+            .post("/concepts/batch")
+            .json(&json!({ "items": items }))
+            .await
+            .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(engine.list_concepts(Default::default()).await.unwrap().concepts.is_empty());
+
+        server
+            .post("/concepts/batch")
+            .json(&json!({ "items": items[..2] }))
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_graph_can_be_fetched_as_msgpack() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let engine_id = engine.store(json!({"name": "Engine"})).await.unwrap();
+        engine.relate(ada, "works_on".to_string(), engine_id).await.unwrap();
+
+        let as_json = server.get("/graph").await;
+        as_json.assert_header("content-type", "application/json");
+        let from_json: GraphData = as_json.json();
+
+        let as_msgpack = server
+            .get("/graph")
+            .add_header("accept", "application/msgpack")
+            .await;
+        as_msgpack.assert_header("content-type", "application/msgpack");
+        let mut from_msgpack: GraphData =
+            crate::api::msgpack::from_slice(as_msgpack.as_bytes()).unwrap();
+
+        // Each fetch hands out its own watermark.
+        assert!(from_msgpack.as_of > from_json.as_of);
+        from_msgpack.as_of = from_json.as_of;
+        assert_eq!(
+            serde_json::to_value(&from_msgpack).unwrap(),
+            serde_json::to_value(&from_json).unwrap()
+        );
+        assert_eq!(from_msgpack.nodes.len(), 2);
+
+        // The query parameter works too, and leaves the listing's own parameters alone.
+        let page = server.get("/concepts?format=msgpack&limit=1").await;
+        page.assert_header("content-type", "application/msgpack");
+        let page: ConceptPage = crate::api::msgpack::from_slice(page.as_bytes()).unwrap();
+        assert_eq!(page.concepts.len(), 1);
+        assert!(page.next_cursor.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_export_serves_the_same_graph_in_either_format() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada", "born": 1815})).await.unwrap();
+        let engine_id = engine.store(json!({"name": "Engine"})).await.unwrap();
+        engine.relate(ada, "works_on".to_string(), engine_id).await.unwrap();
+        let snapshot: ReadSnapshot = server.post("/snapshots").await.json();
+        engine.delete_concept(engine_id).await.unwrap();
+
+        let as_json = server.get("/export").await;
+        as_json.assert_header("content-type", "application/json");
+        let from_json: GraphSnapshot = as_json.json();
+        let as_msgpack = server.get("/export").add_header("accept", "application/msgpack").await;
+        as_msgpack.assert_header("content-type", "application/msgpack");
+        let from_msgpack: GraphSnapshot = crate::api::msgpack::from_slice(as_msgpack.as_bytes()).unwrap();
+        assert_eq!(from_msgpack, from_json);
+        assert_eq!(from_json, engine.snapshot().await.unwrap());
+        assert!(from_json.relationships.is_empty());
+
+        // A snapshot exports what it saw, in either format.
+        let pinned = server
+            .get("/export?format=msgpack")
+            .add_query_param("snapshot", snapshot.token)
+            .await;
+        let pinned: GraphSnapshot = crate::api::msgpack::from_slice(pinned.as_bytes()).unwrap();
+        let ids: HashSet<ConceptId> = pinned.concepts.iter().map(|c| c.id).collect();
+        assert!(ids.contains(&ada) && ids.contains(&engine_id));
+        assert_eq!(pinned.relationships.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_gzipped_when_the_client_accepts_it() {
+        let (server, engine) = setup_test_server_with_engine();
+        for i in 0..20 {
+            engine.store(json!({"name": format!("concept {}", i)})).await.unwrap();
+        }
+        let inflate = |body: &[u8]| miniz_oxide::inflate::decompress_to_vec(&body[10..body.len() - 8]).unwrap();
+
+        let plain = server.get("/export").await;
+        assert!(plain.maybe_header("content-encoding").is_none());
+        plain.assert_header("vary", "accept-encoding");
+
+        let gzipped = server.get("/export").add_header("accept-encoding", "br;q=1, gzip;q=0.8").await;
+        gzipped.assert_header("content-encoding", "gzip");
+        gzipped.assert_header("content-type", "application/json");
+        assert!(gzipped.as_bytes().len() < plain.as_bytes().len());
+        assert_eq!(inflate(gzipped.as_bytes()), plain.as_bytes().to_vec());
+
+        let packed = server
+            .get("/export?format=msgpack")
+            .add_header("accept-encoding", "gzip")
+            .await;
+        packed.assert_header("content-encoding", "gzip");
+        let packed: GraphSnapshot = crate::api::msgpack::from_slice(&inflate(packed.as_bytes())).unwrap();
+        assert_eq!(packed, plain.json::<GraphSnapshot>());
+
+        // Refused, too small to bother with, or not a buffered format: sent as it is.
+        let refused = server.get("/export").add_header("accept-encoding", "gzip;q=0").await;
+        assert!(refused.maybe_header("content-encoding").is_none());
+        let small = server.get("/ping").add_header("accept-encoding", "gzip").await;
+        assert!(small.maybe_header("content-encoding").is_none());
+        assert_eq!(small.text(), "pong");
+    }
+
+    #[tokio::test]
+    async fn test_gzipped_responses_keep_conditional_gets_working() {
+        let (server, engine) = setup_test_server_with_engine();
+        for i in 0..20 {
+            engine.store(json!({"name": format!("concept {}", i)})).await.unwrap();
+        }
+        let first = server.get("/graph").add_header("accept-encoding", "gzip").await;
+        first.assert_header("content-encoding", "gzip");
+        let etag = first.header("etag");
+        assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+        server
+            .get("/graph")
+            .add_header("accept-encoding", "gzip")
+            .add_header("if-none-match", etag)
+            .await
+            .assert_status(StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn test_graph_labels_follow_the_configured_fields() {
+        let (_, engine) = setup_test_server_with_engine();
+        let server = TestServer::new(create_router(
+            AppState::new(Arc::clone(&engine))
+                .with_label_fields(vec!["title".to_string(), "handle".to_string()]),
+        ))
+        .unwrap();
+        let titled = engine.store(json!({"title": "Dune", "handle": "dune"})).await.unwrap();
+        let handled = engine.store(json!({"name": "ignored", "handle": "ada"})).await.unwrap();
+        let bare = engine.store(json!({"name": "ignored too"})).await.unwrap();
+
+        let graph: GraphData = server.get("/graph").await.json();
+        let label_of = |id: Uuid| {
+            graph.nodes.iter().find(|n| n.id == id.to_string()).unwrap().label.clone()
+        };
+        assert_eq!(label_of(titled), "Dune");
+        assert_eq!(label_of(handled), "ada");
+        assert_eq!(label_of(bare), bare.simple().to_string()[..8]);
+        assert!(graph.nodes.iter().all(|n| n.data.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_graph_includes_data_on_request() {
+        let (server, engine) = setup_test_server_with_engine();
+        let id = engine.store(json!({"name": "Ada", "born": 1815})).await.unwrap();
+
+        let graph: GraphData = server.get("/graph?include_data=true").await.json();
+        let node = graph.nodes.iter().find(|n| n.id == id.to_string()).unwrap();
+        assert_eq!(node.label, "Ada");
+        assert_eq!(node.data, Some(json!({"name": "Ada", "born": 1815})));
+    }
+
+    #[tokio::test]
+    async fn test_graph_carries_metadata_unless_asked_for_the_minimal_shape() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada", "labels": ["Person", "Author"]})).await.unwrap();
+        let notes = engine.store(json!({"name": "Notes"})).await.unwrap();
+        engine.relate(ada, "wrote".to_string(), notes).await.unwrap();
+        rename(&engine, notes, "Notes, revised").await;
+
+        let full: serde_json::Value = server.get("/graph").await.json();
+        let node = |graph: &serde_json::Value, id: ConceptId| {
+            graph["nodes"].as_array().unwrap().iter().find(|n| n["id"] == id.to_string()).unwrap().clone()
+        };
+        assert_eq!(node(&full, ada)["labels"], json!(["Person", "Author"]));
+        assert_eq!(node(&full, notes)["labels"], json!([]));
+        assert_eq!(node(&full, notes)["version"], 2);
+        assert!(node(&full, ada)["created_at"].is_string());
+        assert!(node(&full, ada).get("data").is_none());
+        assert_eq!(full["edges"][0]["version"], 1);
+        assert!(full["edges"][0]["created_at"].is_string());
+
+        let minimal: serde_json::Value = server.get("/graph?shape=minimal").await.json();
+        let keys = |value: &serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&node(&minimal, ada)), ["id", "label"]);
+        assert_eq!(keys(&minimal["edges"][0]), ["id", "label", "source", "target"]);
+        let with_data: serde_json::Value = server.get("/graph?shape=minimal&include_data=true").await.json();
+        assert_eq!(keys(&node(&with_data, ada)), ["data", "id", "label"]);
+    }
+
+    /// Commits a new name for an existing concept.
+    async fn rename(engine: &GraphEngine, id: ConceptId, name: &str) {
+        let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let mut concept = Concept::new(json!({ "name": name }));
+        concept.id = id;
+        txn.write_set.insert(id);
+        txn.pending_writes.insert(id, concept);
+        engine.commit_transaction(txn).await.unwrap();
+    }
+
+    /// Stores a concept and renames it until it has `versions` versions.
+    async fn concept_with_history(engine: &GraphEngine, versions: u64) -> ConceptId {
+        let id = engine.store(json!({"name": "v1"})).await.unwrap();
+        for n in 2..=versions {
+            rename(engine, id, &format!("v{}", n)).await;
+        }
+        id
+    }
+
+    #[tokio::test]
+    async fn test_prune_removes_old_versions_from_memory_and_disk() {
+        let (server, engine) = setup_test_server_with_engine();
+        let id = concept_with_history(&engine, 5).await;
+
+        // Nothing was superseded an hour ago yet.
+        let report: PruneReport = server
+            .post("/admin/prune")
+            .json(&json!({"min_age_secs": 3600}))
+            .await
+            .json();
+        assert_eq!(report, PruneReport::default());
+
+        let report: PruneReport = server
+            .post("/admin/prune")
+            .json(&json!({"keep_latest": 2}))
+            .await
+            .json();
+        assert_eq!(report.concept_versions_pruned, 3);
+        assert_eq!(report.items_pruned, 1);
+
+        let version_store = engine.transaction_manager().version_store();
+        assert_eq!(version_store.concept_version_numbers().unwrap()[&id], vec![4, 5]);
+        let mut on_disk: Vec<u64> = engine
+            .transaction_manager()
+            .backend()
+            .load_all_concept_versions()
+            .unwrap()
+            .iter()
+            .filter(|v| v.concept_id == id)
+            .map(|v| v.version)
+            .collect();
+        on_disk.sort_unstable();
+        assert_eq!(on_disk, vec![4, 5]);
+        assert!(engine.verify_consistency().await.unwrap().is_consistent());
+
+        server.post("/admin/compact").await.assert_status(StatusCode::NO_CONTENT);
+
+        // Pruned version numbers are never handed out again.
+        rename(&engine, id, "v6").await;
+        let latest = version_store.get_latest_concept_version(&id).unwrap().unwrap();
+        assert_eq!(latest.version, 6);
+    }
+
+    #[tokio::test]
+    async fn test_prune_refuses_while_a_snapshot_reads_old_versions() {
+        let (server, engine) = setup_test_server_with_engine();
+        let id = engine.store(json!({"name": "v1"})).await.unwrap();
+        let reader = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        rename(&engine, id, "v2").await;
+        rename(&engine, id, "v3").await;
+
+        // `reader` still sees v1.
+        let response = server.post("/admin/prune").json(&json!({})).await;
+        response.assert_status(StatusCode::CONFLICT);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["transactions"], json!([reader.id]));
+        let version_store = engine.transaction_manager().version_store();
+        assert_eq!(version_store.concept_version_numbers().unwrap()[&id], vec![1, 2, 3]);
+
+        engine.abort_transaction(reader.id).await.unwrap();
+        let report: PruneReport = server.post("/admin/prune").json(&json!({})).await.json();
+        assert_eq!(report.concept_versions_pruned, 2);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_reads_stay_put_while_commits_land() {
+        let (server, engine) = setup_test_server_with_engine();
+        let first = engine.store(json!({"name": "v1"})).await.unwrap();
+        let second = engine.store(json!({"name": "second"})).await.unwrap();
+        let snapshot: ReadSnapshot = server.post("/snapshots").await.json();
+
+        rename(&engine, first, "v2").await;
+        engine.delete_concept(second).await.unwrap();
+        let added = engine.store(json!({"name": "added"})).await.unwrap();
+
+        // Unpinned reads see every commit...
+        let concept: Concept = server.get(&format!("/concepts/{}", first)).await.json();
+        assert_eq!(concept.data, ConceptData::Structured(json!({"name": "v2"}).to_string().into()));
+        let page: ConceptPage = server.get("/concepts").await.json();
+        let live: HashSet<ConceptId> = page.concepts.iter().map(|c| c.id).collect();
+        assert_eq!(live, HashSet::from([first, added]));
+
+        // ...while the snapshot's pages hold what was there when it was taken.
+        let in_snapshot = |path: String| server.get(&path).add_query_param("snapshot", snapshot.token);
+        let concept: Concept = in_snapshot(format!("/concepts/{}", first)).await.json();
+        assert_eq!(concept.data, ConceptData::Structured(json!({"name": "v1"}).to_string().into()));
+        in_snapshot(format!("/concepts/{}", added)).await.assert_status_not_found();
+        let page_one: ConceptPage = in_snapshot("/concepts?limit=1".to_string()).await.json();
+        let cursor = page_one.next_cursor.expect("the snapshot holds two concepts");
+        let page_two: ConceptPage = in_snapshot(format!("/concepts?limit=1&cursor={}", cursor)).await.json();
+        let seen: HashSet<ConceptId> = page_one.concepts.iter().chain(&page_two.concepts).map(|c| c.id).collect();
+        assert_eq!(seen, HashSet::from([first, second]));
+        assert_eq!(page_two.next_cursor, None);
+
+        // Pruning must keep v1 while the snapshot can read it.
+        let response = server.post("/admin/prune").json(&json!({})).await;
+        response.assert_status(StatusCode::CONFLICT);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["transactions"], json!([snapshot.token]));
+
+        server.delete(&format!("/snapshots/{}", snapshot.token)).await.assert_status(StatusCode::NO_CONTENT);
+        server.delete(&format!("/snapshots/{}", snapshot.token)).await.assert_status_not_found();
+        in_snapshot("/concepts".to_string()).await.assert_status_not_found();
+        let report: PruneReport = server.post("/admin/prune").json(&json!({})).await.json();
+        assert_eq!(report.concept_versions_pruned, 2);
+    }
+
+    #[tokio::test]
+    async fn test_history_pages_newest_first_and_survives_deletion() {
+        let (server, engine) = setup_test_server_with_engine();
+        let id = engine.store(json!({"name": "v1"})).await.unwrap();
+        rename(&engine, id, "v2").await;
+        engine.delete_concept(id).await.unwrap();
+        server.get(&format!("/concepts/{}", id)).await.assert_status(StatusCode::NOT_FOUND);
+
+        // The deleted concept still serves all three versions, with parsed data.
+        let page: HistoryPage<ConceptHistoryEntry> = server.get(&format!("/concepts/{}/history", id)).await.json();
+        assert_eq!(page.versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert_eq!(page.versions[1].data, json!({"name": "v2"}));
+        assert!(page.versions[0].deleted_at.is_some());
+        assert_eq!(page.next_before_version, None);
+
+        let first: HistoryPage<ConceptHistoryEntry> =
+            server.get(&format!("/concepts/{}/history?limit=2", id)).await.json();
+        assert_eq!(first.versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(first.next_before_version, Some(2));
+        let rest: HistoryPage<ConceptHistoryEntry> = server
+            .get(&format!("/concepts/{}/history?limit=2&before_version=2", id))
+            .await
+            .json();
+        assert_eq!(rest.versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(rest.next_before_version, None);
+
+        // `at` picks the version live at that moment, and nothing once it was deleted.
+        let at = |timestamp: chrono::DateTime<Utc>| {
+            server
+                .get(&format!("/concepts/{}/history", id))
+                .add_query_param("at", timestamp.to_rfc3339())
+        };
+        let v2: HistoryPage<ConceptHistoryEntry> = at(page.versions[1].created_at).await.json();
+        assert_eq!(v2.versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![2]);
+        let v1: HistoryPage<ConceptHistoryEntry> =
+            at(page.versions[1].created_at - chrono::Duration::nanoseconds(1)).await.json();
+        assert_eq!(v1.versions[0].data, json!({"name": "v1"}));
+        let gone: HistoryPage<ConceptHistoryEntry> = at(page.versions[0].created_at).await.json();
+        assert!(gone.versions.is_empty());
+
+        // Relationships keep theirs once unrelated, too.
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        let rel = engine.relate(a, "knows".to_string(), b).await.unwrap();
+        engine.unrelate(rel).await.unwrap();
+        let page: HistoryPage<RelationshipHistoryEntry> =
+            server.get(&format!("/relationships/{}/history", rel)).await.json();
+        assert_eq!(page.versions.len(), 2);
+        assert!(page.versions[0].deleted_at.is_some());
+        assert_eq!((page.versions[1].source, page.versions[1].target), (a, b));
+
+        server.get(&format!("/concepts/{}/history", Uuid::new_v4())).await.assert_status(StatusCode::NOT_FOUND);
+        server
+            .get(&format!("/relationships/{}/history", Uuid::new_v4()))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_a_relationship_expands_to_its_endpoints_and_marks_deleted_ones() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada", "labels": ["Person"]})).await.unwrap();
+        let engine_id = engine.store(json!({"name": "Analytical Engine"})).await.unwrap();
+        let rel = engine.relate(ada, "designed".to_string(), engine_id).await.unwrap();
+
+        let plain: serde_json::Value = server.get(&format!("/relationships/{}", rel)).await.json();
+        assert_eq!(plain["type"], "designed");
+        assert!(plain.get("source_concept").is_none());
+
+        let expanded: RelationshipResponse =
+            server.get(&format!("/relationships/{}", rel)).add_query_param("expand", "endpoints").await.json();
+        assert_eq!((expanded.source, expanded.target, expanded.version), (ada, engine_id, 1));
+        assert_eq!(
+            expanded.source_concept,
+            Some(EndpointConcept::Concept {
+                id: ada,
+                labels: vec!["Person".to_string()],
+                data: json!({"name": "Ada", "labels": ["Person"]}),
+                version: 1,
+            })
+        );
+
+        // The relationship outlives its target when deletes leave relationships behind.
+        engine.delete_concept(engine_id).await.unwrap();
+        let response = server.get(&format!("/relationships/{}", rel)).add_query_param("expand", "endpoints").await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["target_concept"], json!({"deleted": true}));
+        assert_eq!(body["source_concept"]["id"], json!(ada));
+        assert_eq!(response.json::<RelationshipResponse>().target_concept, Some(EndpointConcept::Deleted { deleted: true }));
+
+        server
+            .get(&format!("/relationships/{}", rel))
+            .add_query_param("expand", "everything")
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+        engine.unrelate(rel).await.unwrap();
+        server.get(&format!("/relationships/{}", rel)).await.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_activity_counts_changes_per_utc_bucket_with_empty_buckets_as_zeros() {
+        let (server, engine) = setup_test_server_with_engine();
+        let at = |rfc3339: &str| DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc);
+        let txn = Uuid::new_v4();
+        let concept_version = |concept: &Concept, version, created_at, deleted_at: Option<DateTime<Utc>>| ConceptVersion {
+            created_at,
+            deleted_at,
+            deleted_by: deleted_at.map(|_| txn),
+            ..ConceptVersion::from_concept(concept, txn, version)
+        };
+
+        // Day one: two concepts and a relationship. Day two: an update a moment before
+        // midnight. Day three: nothing. Day four, on the stroke of midnight: two deletions.
+        let (ada, charles) = (Concept::new(json!({"name": "Ada"})), Concept::new(json!({"name": "Charles"})));
+        let rel = Relationship::new(ada.id, "knows", charles.id);
+        let midnight = at("2026-03-04T00:00:00Z");
+        let rel_v1 = RelationshipVersion { created_at: at("2026-03-01T11:15:00Z"), ..RelationshipVersion::from_relationship(&rel, txn) };
+        let archive = Archive {
+            concept_versions: vec![
+                concept_version(&ada, 1, at("2026-03-01T10:00:00Z"), None),
+                concept_version(&ada, 2, at("2026-03-02T23:59:59.999Z"), None),
+                concept_version(&charles, 1, at("2026-03-01T10:30:00Z"), None),
+                concept_version(&charles, 2, at("2026-03-01T10:30:00Z"), Some(midnight)),
+            ],
+            relationship_versions: vec![
+                rel_v1.clone(),
+                RelationshipVersion { version: 2, deleted_at: Some(midnight), deleted_by: Some(txn), ..rel_v1 },
+            ],
+            concept_heads: vec![(ada.id, 2), (charles.id, 2)],
+            relationship_heads: vec![(rel.id, 2)],
+            ..Archive::default()
+        };
+        let mut bytes = Vec::new();
+        archive.write(&mut bytes).unwrap();
+        engine.import_archive(bytes.as_slice()).await.unwrap();
+
+        let days: Vec<ActivityBucket> = server
+            .get("/stats/activity")
+            .add_query_param("from", "2026-03-01T12:00:00Z")
+            .add_query_param("to", "2026-03-04T00:00:01Z")
+            .await
+            .json();
+        let counts = |bucket: &ActivityBucket| {
+            (
+                bucket.concepts_created,
+                bucket.concepts_updated,
+                bucket.concepts_deleted,
+                bucket.relationships_created,
+                bucket.relationships_deleted,
+            )
+        };
+        assert_eq!(
+            days.iter().map(|bucket| bucket.start).collect::<Vec<_>>(),
+            ["2026-03-01", "2026-03-02", "2026-03-03", "2026-03-04"].map(|day| at(&format!("{}T00:00:00Z", day)))
+        );
+        assert_eq!(days.iter().map(counts).collect::<Vec<_>>(), vec![
+            (2, 0, 0, 1, 0),
+            (0, 1, 0, 0, 0),
+            (0, 0, 0, 0, 0),
+            (0, 0, 1, 0, 1),
+        ]);
+
+        let hours: Vec<ActivityBucket> = server
+            .get("/stats/activity")
+            .add_query_param("from", "2026-03-01T10:00:00Z")
+            .add_query_param("to", "2026-03-01T12:00:00Z")
+            .add_query_param("bucket", "hour")
+            .await
+            .json();
+        assert_eq!(hours.iter().map(|bucket| bucket.start).collect::<Vec<_>>(), vec![
+            at("2026-03-01T10:00:00Z"),
+            at("2026-03-01T11:00:00Z"),
+        ]);
+        assert_eq!(hours.iter().map(counts).collect::<Vec<_>>(), vec![(2, 0, 0, 0, 0), (0, 0, 0, 1, 0)]);
+
+        let activity = |from: &str, to: &str, bucket: &str| {
+            server
+                .get("/stats/activity")
+                .add_query_param("from", from.to_string())
+                .add_query_param("to", to.to_string())
+                .add_query_param("bucket", bucket.to_string())
+        };
+        activity("2026-03-02T00:00:00Z", "2026-03-01T00:00:00Z", "day").await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        activity("2000-01-01T00:00:00Z", "2026-03-01T00:00:00Z", "hour").await.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        activity("2026-03-01T00:00:00Z", "2026-03-02T00:00:00Z", "week").await.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_gets_answer_304_until_something_changes() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let concept_path = format!("/concepts/{}", ada);
+
+        for path in [concept_path.as_str(), "/graph"] {
+            let first = server.get(path).await;
+            first.assert_status_ok();
+            let etag = first.header("etag").to_str().unwrap().to_string();
+            assert!(first.maybe_header("last-modified").is_some());
+
+            let cached = server.get(path).add_header("if-none-match", etag.clone()).await;
+            cached.assert_status(StatusCode::NOT_MODIFIED);
+            assert!(cached.as_bytes().is_empty());
+            assert_eq!(cached.header("etag"), etag.as_str());
+
+            engine.add_tags(ada, [path]).await.unwrap();
+            let changed = server.get(path).add_header("if-none-match", etag.clone()).await;
+            changed.assert_status_ok();
+            assert_ne!(changed.header("etag"), etag.as_str());
+        }
+        assert_eq!(engine.latest_commit_sequence(), 3);
+
+        // Each body format has its own tag.
+        let json_tag = server.get("/graph").await.header("etag");
+        let msgpack = server.get("/graph").add_header("accept", "application/msgpack").add_header("if-none-match", json_tag.clone()).await;
+        msgpack.assert_status_ok();
+
+        // A purge erases without committing, and still retires the tag.
+        engine.purge_concept(ada).await.unwrap();
+        assert_eq!(engine.latest_commit_sequence(), 3);
+        let purged = server.get("/graph").add_header("if-none-match", json_tag.clone()).await;
+        purged.assert_status_ok();
+        assert_ne!(purged.header("etag"), json_tag);
+        assert!(purged.json::<serde_json::Value>()["nodes"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_graph_tags_name_the_parameters_that_shape_the_body() {
+        let (server, engine) = setup_test_server_with_engine();
+        for name in ["Ada", "Charles", "Engine"] {
+            engine.store(json!({"name": name})).await.unwrap();
+        }
+        let first = server.get("/graph").await;
+        first.assert_header("vary", "accept");
+        let etag = first.header("etag");
+
+        // Defaults spelled out, or budgets over the server's own, read the same as leaving them out.
+        for path in ["/graph?shape=full&include_data=false&include_system=false", "/graph?max_items=1000000000"] {
+            server
+                .get(path)
+                .add_header("if-none-match", etag.clone())
+                .await
+                .assert_status(StatusCode::NOT_MODIFIED);
+        }
+
+        for path in [
+            "/graph?include_data=true",
+            "/graph?shape=minimal",
+            "/graph?include_system=true",
+            "/graph?tag=starred",
+            "/graph?max_items=100",
+            "/graph?max_duration_ms=1000",
+        ] {
+            let other = server.get(path).add_header("if-none-match", etag.clone()).await;
+            other.assert_status_ok();
+            assert_ne!(other.header("etag"), etag, "{}", path);
+        }
+
+        // A body cut short by the budget never comes back as 304.
+        let partial = server.get("/graph?max_items=1").await;
+        assert!(partial.json::<GraphData>().truncated);
+        server
+            .get("/graph?max_items=1")
+            .add_header("if-none-match", partial.header("etag"))
+            .await
+            .assert_status_ok();
+
+        let not_modified = server.get("/graph").add_header("if-none-match", etag).await;
+        not_modified.assert_status(StatusCode::NOT_MODIFIED);
+        not_modified.assert_header("vary", "accept");
+    }
+
+    #[tokio::test]
+    async fn test_system_concepts_only_show_up_when_asked_for() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        engine.put_system("schema:person", json!({"type": "object"})).await.unwrap();
+        let system_id = crate::types::system::system_id("schema:person");
+        engine.relate(ada, "described_by".to_string(), system_id).await.unwrap();
+
+        let listed = |body: &serde_json::Value, key: &str| -> Vec<String> {
+            body[key].as_array().unwrap().iter().map(|item| item["id"].as_str().unwrap().to_string()).collect()
+        };
+        for (query, expected) in [("", vec![ada]), ("?include_system=true", vec![ada, system_id])] {
+            let mut expected: Vec<String> = expected.iter().map(Uuid::to_string).collect();
+            expected.sort();
+            let mut concepts = listed(&server.get(&format!("/concepts{}", query)).await.json::<serde_json::Value>(), "concepts");
+            concepts.sort();
+            assert_eq!(concepts, expected);
+
+            let graph = server.get(&format!("/graph{}", query)).await.json::<serde_json::Value>();
+            let mut nodes = listed(&graph, "nodes");
+            nodes.sort();
+            assert_eq!(nodes, expected);
+            // An edge to a hidden concept is hidden with it.
+            assert_eq!(graph["edges"].as_array().unwrap().len(), expected.len() - 1);
+
+            let matched = server.post(&format!("/query{}", query)).json(&json!({"match": {"source": {}}})).await;
+            matched.assert_status_ok();
+            assert_eq!(matched.json::<serde_json::Value>()["rows"].as_array().unwrap().len(), expected.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_path_and_neighborhood_over_a_chain() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let limits = TraversalLimits { max_depth: 3, max_concepts: 1000 };
+        let server =
+            TestServer::new(create_router(AppState::new(Arc::clone(&engine)).with_traversal_limits(limits))).unwrap();
+        // c0 -> c1 -> c2 -> c3 -> c4, all "next".
+        let mut c = Vec::new();
+        for n in 0..5 {
+            c.push(engine.store(json!({ "name": format!("c{}", n) })).await.unwrap());
+        }
+        for pair in c.windows(2) {
+            engine.relate(pair[0], "next".to_string(), pair[1]).await.unwrap();
+        }
+        let ids = |graph: &GraphData| graph.nodes.iter().map(|n| n.id.clone()).collect::<Vec<_>>();
+        let names = |ids: &[ConceptId]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        let path: TraversalResponse = server.get(&format!("/path?from={}&to={}", c[0], c[3])).await.json();
+        assert_eq!(ids(&path.graph), names(&c[0..4]));
+        assert_eq!(path.graph.nodes[1].label, "c1");
+        assert_eq!(path.graph.edges.len(), 3);
+        assert!(!path.truncated);
+
+        // c4 is four hops away, beyond the server's limit: not found, and flagged truncated.
+        let response = server.get(&format!("/path?from={}&to={}&max_depth=10", c[0], c[4])).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+        let missed: TraversalResponse = response.json();
+        assert!(missed.graph.nodes.is_empty());
+        assert!(missed.truncated);
+        // Against the direction of the edges there's no path, and nothing was cut short.
+        let response = server.get(&format!("/path?from={}&to={}&types=next,other", c[3], c[0])).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+        assert!(!response.json::<TraversalResponse>().truncated);
+        server.get(&format!("/path?from=nope&to={}", c[0])).await.assert_status(StatusCode::BAD_REQUEST);
+
+        // Two hops either way from the middle covers the whole chain.
+        let around: TraversalResponse = server.get(&format!("/concepts/{}/neighborhood", c[2])).await.json();
+        assert_eq!(ids(&around.graph)[0], c[2].to_string());
+        assert_eq!(around.graph.nodes.len(), 5);
+        assert_eq!(around.graph.edges.len(), 4);
+        assert!(!around.truncated);
+        let near: TraversalResponse =
+            server.get(&format!("/concepts/{}/neighborhood?depth=1", c[0])).await.json();
+        assert_eq!(ids(&near.graph), names(&c[0..2]));
+        let other: TraversalResponse =
+            server.get(&format!("/concepts/{}/neighborhood?types=other", c[0])).await.json();
+        assert_eq!(ids(&other.graph), names(&c[0..1]));
+
+        // Running out of concepts truncates too.
+        let server = TestServer::new(create_router(
+            AppState::new(Arc::clone(&engine)).with_traversal_limits(TraversalLimits { max_concepts: 2, ..limits }),
+        ))
+        .unwrap();
+        let capped: TraversalResponse = server.get(&format!("/concepts/{}/neighborhood", c[2])).await.json();
+        assert_eq!(capped.graph.nodes.len(), 2);
+        assert_eq!(capped.graph.edges.len(), 1);
+        assert!(capped.truncated);
+        server
+            .get(&format!("/concepts/{}/neighborhood", Uuid::new_v4()))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_query_matches_a_pattern_and_rejects_bad_clauses() {
+        let (server, engine) = setup_test_server_with_engine();
+        let ada = engine.store(json!({"labels": ["person"], "name": "Ada"})).await.unwrap();
+        let acme = engine.store(json!({"labels": ["company"], "name": "Acme"})).await.unwrap();
+        engine.relate(ada, "works_for".to_string(), acme).await.unwrap();
+        // A relabelled concept leaves the label index.
+        rename(&engine, acme, "Acme").await;
+
+        let query = |label: &str| {
+            json!({
+                "match": {
+                    "source": {"label": "person"},
+                    "relationship": {"type": "works_for"},
+                    "target": {label: {"name": "Acme"}}
+                },
+                "return": ["source"]
+            })
+        };
+        let result: QueryResult = server.post("/query").json(&query("property")).await.json();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0].source.as_ref().unwrap().id, ada);
+        assert!(result.rows[0].target.is_none());
+        let companies: QueryResult = server.post("/query").json(&json!({"match": {"source": {"label": "company"}}})).await.json();
+        assert!(companies.rows.is_empty());
+
+        let response = server.post("/query").json(&query("props")).await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["path"], "/match/target/props");
+    }
+
+    #[tokio::test]
+    async fn test_active_transactions_require_an_admin_key() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let api_keys = ApiKeys::from([
+            ("user-key".to_string(), ApiKey { actor: "user".to_string(), admin: false }),
+            ("admin-key".to_string(), ApiKey { actor: "ops".to_string(), admin: true }),
+        ]);
+        let server =
+            TestServer::new(create_router(AppState::new(engine).with_api_keys(api_keys))).unwrap();
+
+        for path in ["/admin/transactions".to_string(), format!("/admin/transactions/{}", txn.id)] {
+            server.get(&path).expect_failure().await.assert_status(StatusCode::UNAUTHORIZED);
+            server
+                .get(&path)
+                .add_header(API_KEY_HEADER, "user-key")
+                .expect_failure()
+                .await
+                .assert_status(StatusCode::FORBIDDEN);
+            server.get(&path).add_header(API_KEY_HEADER, "admin-key").await.assert_status_ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prune_and_compact_require_an_admin_key() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let api_keys = ApiKeys::from([
+            ("user-key".to_string(), ApiKey { actor: "user".to_string(), admin: false }),
+            ("admin-key".to_string(), ApiKey { actor: "ops".to_string(), admin: true }),
+        ]);
+        let server =
+            TestServer::new(create_router(AppState::new(engine).with_api_keys(api_keys))).unwrap();
+
+        server
+            .post("/admin/prune")
+            .add_header(API_KEY_HEADER, "user-key")
+            .json(&json!({}))
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        server
+            .post("/admin/compact")
+            .add_header(API_KEY_HEADER, "user-key")
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        server
+            .post("/admin/rebuild")
+            .add_header(API_KEY_HEADER, "user-key")
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        server
+            .get("/admin/check?depth=deep")
+            .add_header(API_KEY_HEADER, "user-key")
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        let checked: CheckReport = server
+            .get("/admin/check?depth=deep")
+            .add_header(API_KEY_HEADER, "admin-key")
+            .await
+            .json();
+        assert_eq!(checked.depth, crate::graph::CheckDepth::Deep);
+        assert!(checked.is_clean());
+        let rebuilt: RebuildReport = server
+            .post("/admin/rebuild")
+            .add_header(API_KEY_HEADER, "admin-key")
+            .await
+            .json();
+        assert_eq!(rebuilt.repaired(), 0);
+        server
+            .post("/admin/prune")
+            .add_header(API_KEY_HEADER, "admin-key")
+            .json(&json!({}))
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_delete_where_is_a_dry_run_unless_told_otherwise() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let api_keys = ApiKeys::from([
+            ("user-key".to_string(), ApiKey { actor: "user".to_string(), admin: false }),
+            ("admin-key".to_string(), ApiKey { actor: "ops".to_string(), admin: true }),
+        ]);
+        let server =
+            TestServer::new(create_router(AppState::new(Arc::clone(&engine)).with_api_keys(api_keys))).unwrap();
+        let bad = engine.store(json!({"name": "a", "source": "import-2024-06-01"})).await.unwrap();
+        let good = engine.store(json!({"name": "b", "source": "manual"})).await.unwrap();
+        engine.relate(good, "cites", bad).await.unwrap();
+        let body = json!({"filter": {"equals": {"source": "import-2024-06-01"}}, "cascade": true});
+
+        server
+            .post("/admin/delete-where")
+            .add_header(API_KEY_HEADER, "user-key")
+            .json(&body)
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        let dry_run: BulkDeleteReport =
+            server.post("/admin/delete-where").add_header(API_KEY_HEADER, "admin-key").json(&body).await.json();
+        assert!(dry_run.dry_run);
+        assert_eq!(dry_run.matched, vec![bad]);
+        assert!(engine.get_concept(bad).await.unwrap().is_some());
+
+        let mut body = body;
+        body["dry_run"] = json!(false);
+        let report: BulkDeleteReport =
+            server.post("/admin/delete-where").add_header(API_KEY_HEADER, "admin-key").json(&body).await.json();
+        assert_eq!((report.concepts_deleted, report.relationships_deleted, report.batches_committed), (1, 1, 1));
+        assert!(engine.get_concept(bad).await.unwrap().is_none());
+        assert!(engine.get_concept(good).await.unwrap().is_some());
+        assert_eq!(engine.count_relationships().await.unwrap(), 0);
+
+        // A structured predicate narrows the filter further.
+        let old = engine.store(json!({"name": "c", "score": 1})).await.unwrap();
+        engine.store(json!({"name": "d", "score": 9})).await.unwrap();
+        let body = json!({"filter": {"predicate": {"lt": {"path": "score", "value": 5}}}});
+        let dry_run: BulkDeleteReport =
+            server.post("/admin/delete-where").add_header(API_KEY_HEADER, "admin-key").json(&body).await.json();
+        assert_eq!(dry_run.matched, vec![old]);
+    }
+
+    #[tokio::test]
+    async fn test_scans_stop_at_the_budget_and_say_so() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let server =
+            TestServer::new(create_router(AppState::new(Arc::clone(&engine)).with_scan_budget(ScanBudget::items(3))))
+                .unwrap();
+        let mut ids = Vec::new();
+        for n in 0..10 {
+            ids.push(engine.store(json!({"labels": ["person"], "name": format!("p{}", n)})).await.unwrap());
+        }
+        for pair in ids.windows(2) {
+            engine.relate(pair[0], "knows".to_string(), pair[1]).await.unwrap();
+        }
+
+        let graph: GraphData = server.get("/graph").await.json();
+        assert!(graph.truncated);
+        assert_eq!((graph.nodes.len(), graph.edges.len()), (3, 0));
+        // A request may ask for less than the server allows, never more.
+        let narrowed: GraphData = server.get("/graph?max_items=2").await.json();
+        assert_eq!(narrowed.nodes.len(), 2);
+        let widened: GraphData = server.get("/graph?max_items=50").await.json();
+        assert_eq!(widened.nodes.len(), 3);
+
+        let page: ConceptPage = server.get("/concepts?label=person&limit=5").await.json();
+        assert!(page.truncated);
+        assert_eq!(page.concepts.len(), 3);
+        assert!(page.next_cursor.is_some());
+
+        let response = server.get("/concepts/aggregate?group_by=name").await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+        let result: QueryResult = server.post("/query").json(&json!({"match": {"source": {"label": "person"}}})).await.json();
+        assert!(result.truncated);
+
+        let neighborhood: TraversalResponse = server.get(&format!("/concepts/{}/neighborhood?depth=5", ids[0])).await.json();
+        assert!(neighborhood.truncated);
+        assert!(neighborhood.graph.nodes.len() < ids.len());
+
+        let dry_run: BulkDeleteReport =
+            server.post("/admin/delete-where").json(&json!({"filter": {"equals": {}}})).await.json();
+        assert!(dry_run.truncated);
+        assert_eq!(dry_run.matched.len(), 3);
+
+        // Out of time before the first item: an empty answer, straight away.
+        let started = std::time::Instant::now();
+        let timed: GraphData = server.get("/graph?max_duration_ms=0").await.json();
+        assert!(timed.truncated && timed.nodes.is_empty());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_filters_by_actor_and_pages_with_a_cursor() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let api_keys = ApiKeys::from([
+            ("alice-key".to_string(), ApiKey { actor: "alice".to_string(), admin: false }),
+            ("bob-key".to_string(), ApiKey { actor: "bob".to_string(), admin: false }),
+            ("admin-key".to_string(), ApiKey { actor: "ops".to_string(), admin: true }),
+        ]);
+        let server = TestServer::new(create_router(AppState::new(engine).with_api_keys(api_keys))).unwrap();
+
+        let mut alice_ids = Vec::new();
+        for (key, name) in [("alice-key", "A1"), ("bob-key", "B1"), ("alice-key", "A2"), ("alice-key", "A3")] {
+            let created: CreateConceptResponse = server
+                .post("/concepts")
+                .add_header(API_KEY_HEADER, key)
+                .json(&json!({"data": {"name": name}}))
+                .await
+                .json();
+            if key == "alice-key" {
+                alice_ids.push(created.concept_id);
+            }
+        }
+
+        server
+            .get("/audit")
+            .add_header(API_KEY_HEADER, "alice-key")
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+
+        // Two pages of alice's changes, in commit order.
+        let first: AuditPage = server
+            .get("/audit?actor=alice&limit=2")
+            .add_header(API_KEY_HEADER, "admin-key")
+            .await
+            .json();
+        assert_eq!(first.records.len(), 2);
+        let cursor = first.next_cursor.expect("a second page");
+        let second: AuditPage = server
+            .get("/audit")
+            .add_query_param("actor", "alice")
+            .add_query_param("limit", 2)
+            .add_query_param("cursor", cursor.to_string())
+            .add_header(API_KEY_HEADER, "admin-key")
+            .await
+            .json();
+        assert!(second.next_cursor.is_none());
+
+        let records: Vec<_> = first.records.iter().chain(&second.records).collect();
+        let ids: Vec<Uuid> = records.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids, alice_ids);
+        assert!(records.iter().all(|r| r.actor.as_deref() == Some("alice")));
+        assert!(records.windows(2).all(|pair| pair[0].timestamp < pair[1].timestamp));
+
+        let everyone: AuditPage = server.get("/audit").add_header(API_KEY_HEADER, "admin-key").await.json();
+        assert_eq!(everyone.records.len(), 4);
+        server
+            .get("/audit?cursor=nonsense")
+            .add_header(API_KEY_HEADER, "admin-key")
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_storage_stats_cover_every_column_family() {
+        let (server, engine) = setup_test_server_with_engine();
+        concept_with_history(&engine, 3).await;
+
+        let stats: StorageStats = server.get("/admin/storage?exact=true").await.json();
+        let names: Vec<&str> = stats.column_families.iter().map(|cf| cf.name.as_str()).collect();
+        assert_eq!(names, vec!["concepts", "relationships", "indices", "versions", "blobs"]);
+        let versions = stats.column_family("versions").unwrap();
+        assert!(versions.exact_keys.unwrap() >= 3);
+
+        let estimated: StorageStats = server.get("/admin/storage").await.json();
+        assert!(estimated.column_families.iter().all(|cf| cf.exact_keys.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_stats_and_degree_follow_deletes() {
+        let (server, engine) = setup_test_server_with_engine();
+        let hub = engine.store(json!({"name": "hub"})).await.unwrap();
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        let loner = engine.store(json!({"name": "loner"})).await.unwrap();
+        engine.relate(hub, "knows".to_string(), a).await.unwrap();
+        let doomed = engine.relate(hub, "knows".to_string(), b).await.unwrap();
+        engine.relate(b, "likes".to_string(), hub).await.unwrap();
+
+        let stats: GraphStats = server.get("/stats").await.json();
+        assert_eq!((stats.concepts, stats.relationships), (4, 3));
+        assert_eq!(stats.relationships_by_type["knows"], 2);
+        assert_eq!(stats.relationships_by_type["likes"], 1);
+        assert_eq!(stats.versions.concept_versions, 4);
+
+        let degree: DegreeResponse = server.get(&format!("/concepts/{}/degree", hub)).await.json();
+        assert_eq!(degree.degree, 3);
+        let degree: DegreeResponse = server
+            .get(&format!("/concepts/{}/degree?direction=outgoing", hub))
+            .await
+            .json();
+        assert_eq!(degree.degree, 2);
+
+        engine.unrelate(doomed).await.unwrap();
+        engine.delete_concept(loner).await.unwrap();
+
+        let stats: GraphStats = server.get("/stats").await.json();
+        assert_eq!((stats.concepts, stats.relationships), (3, 2));
+        assert_eq!(stats.relationships_by_type["knows"], 1);
+        // Tombstones are history too.
+        assert_eq!(stats.versions.concept_versions, 5);
+        assert_eq!(stats.versions.relationship_versions, 4);
+        let degree: DegreeResponse = server
+            .get(&format!("/concepts/{}/degree?direction=outgoing", hub))
+            .await
+            .json();
+        assert_eq!(degree.degree, 1);
+
+        server
+            .get(&format!("/concepts/{}/degree", loner))
+            .await
+            .assert_status_not_found();
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_buckets_mixed_data() {
+        let (server, engine) = setup_test_server_with_engine();
+        for data in [
+            json!({"category": "books", "status": "open"}),
+            json!({"category": "books", "status": "closed"}),
+            json!({"category": "music", "status": "open"}),
+            json!({"category": 7, "status": "open"}),
+            json!({"category": null, "status": "open"}),
+            json!({"status": "open"}),
+        ] {
+            engine.store(data).await.unwrap();
+        }
+        let deleted = engine.store(json!({"category": "books"})).await.unwrap();
+        engine.delete_concept(deleted).await.unwrap();
+
+        let groups: HashMap<String, u64> =
+            server.get("/concepts/aggregate?group_by=category").await.json();
+        let expected: HashMap<String, u64> = [
+            ("books".to_string(), 2),
+            ("music".to_string(), 1),
+            ("7".to_string(), 1),
+            (crate::types::query::MISSING_GROUP.to_string(), 2),
+        ]
+        .into();
+        assert_eq!(groups, expected);
+
+        let open: HashMap<String, u64> = server
+            .get("/concepts/aggregate?group_by=category&where.status=open")
+            .await
+            .json();
+        assert_eq!(open["books"], 1);
+        assert_eq!(open.values().sum::<u64>(), 5);
+
+        let strings: HashMap<String, u64> = server
+            .get("/concepts/aggregate?group_by=category&where.status=open")
+            .add_query_param("predicate", json!({"gt": {"path": "category", "value": ""}}).to_string())
+            .await
+            .json();
+        assert_eq!(strings, HashMap::from([("books".to_string(), 1), ("music".to_string(), 1)]));
+        server
+            .get("/concepts/aggregate?group_by=category&predicate=nope")
+            .await
+            .assert_status_bad_request();
+
+        server
+            .get("/concepts/aggregate")
+            .expect_failure()
+            .await
+            .assert_status_bad_request();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_return_429_until_the_bucket_refills() {
+        use crate::api::rate_limit::{LIMIT_HEADER, REMAINING_HEADER, RateLimit, RateLimits};
+
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let state = AppState::new(engine)
+            .with_api_keys(ApiKeys::from([
+                ("alice-key".to_string(), ApiKey { actor: "alice".to_string(), admin: false }),
+                ("bob-key".to_string(), ApiKey { actor: "bob".to_string(), admin: false }),
+            ]))
+            .with_rate_limits(RateLimits {
+                read: RateLimit { per_second: 20.0, burst: 2 },
+                write: RateLimit { per_second: 20.0, burst: 1 },
+            });
+        let server = TestServer::new(create_router(state)).unwrap();
+        let ping = |key: &'static str| server.get("/ping").add_header(API_KEY_HEADER, key);
+
+        let first = ping("alice-key").await;
+        first.assert_status_ok();
+        assert_eq!(first.header(LIMIT_HEADER), "2");
+        assert_eq!(first.header(REMAINING_HEADER), "1");
+        assert_eq!(ping("alice-key").await.header(REMAINING_HEADER), "0");
+        let limited = ping("alice-key").expect_failure().await;
+        limited.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.header("retry-after"), "1");
+        assert_eq!(limited.header(REMAINING_HEADER), "0");
+
+        // Other keys, and alice's writes, have budgets of their own.
+        ping("bob-key").await.assert_status_ok();
+        // A key the server doesn't know is no one's: it shares the caller's IP bucket, so
+        // making keys up doesn't buy fresh budgets.
+        assert_eq!(ping("made-up-1").await.header(REMAINING_HEADER), "1");
+        assert_eq!(ping("made-up-2").await.header(REMAINING_HEADER), "0");
+        ping("made-up-3").expect_failure().await.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        let write = server
+            .post("/concepts")
+            .add_header(API_KEY_HEADER, "alice-key")
+            .json(&json!({"data": {"name": "Limited"}}))
+            .await;
+        write.assert_status_ok();
+        assert_eq!(write.header(LIMIT_HEADER), "1");
+
+        // At 20 per second, a token is back within 50ms.
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        ping("alice-key").await.assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_versioned_queries_spend_the_read_budget() {
+        use crate::api::rate_limit::{LIMIT_HEADER, RateLimit, RateLimits};
+
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let state = AppState::new(engine).with_rate_limits(RateLimits {
+            read: RateLimit { per_second: 1.0, burst: 100 },
+            write: RateLimit { per_second: 1.0, burst: 1 },
+        });
+        let server = TestServer::new(create_router(state)).unwrap();
+        let query = json!({"match": {"source": {}}});
+        for path in ["/v1/query", "/v1/query", "/query"] {
+            let response = server.post(path).json(&query).await;
+            response.assert_status_ok();
+            assert_eq!(response.header(LIMIT_HEADER), "100");
+        }
+        let write = server.post("/v1/concepts").json(&json!({"data": {}})).await;
+        assert_eq!(write.header(LIMIT_HEADER), "1");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_malformed_data_is_refused_before_a_transaction_begins() {
+        use crate::graph::EngineConfig;
+        use crate::types::concept::DataLimits;
+
+        let dir = tempdir().unwrap();
+        let config = EngineConfig {
+            data_limits: DataLimits { max_bytes: 64, max_depth: 3, require_object: true },
+            ..Default::default()
+        };
+        let engine = Arc::new(GraphEngine::with_config(dir.path(), config).unwrap());
+        let server = TestServer::new(create_router(
+            AppState::new(Arc::clone(&engine)).with_max_body_bytes(1024),
+        ))
+        .unwrap();
+
+        let huge = server
+            .post("/concepts")
+            .json(&json!({"data": {"blob": "x".repeat(2000)}}))
+            .await;
+        huge.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(huge.json::<serde_json::Value>()["error"].as_str().unwrap().contains("limit of 1024"));
+
+        let large = server
+            .post("/concepts")
+            .json(&json!({"data": {"blob": "x".repeat(100)}}))
+            .await;
+        large.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(large.json::<serde_json::Value>()["error"].as_str().unwrap().contains("limit of 64"));
+
+        for data in [json!({"a": {"b": {"c": {"d": 1}}}}), json!("not an object")] {
+            server
+                .post("/concepts")
+                .json(&json!({ "data": data }))
+                .await
+                .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        server
+            .post("/concepts/batch")
+            .json(&json!({"items": [{"data": {"ok": true}}, {"data": [1]}]}))
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+        let metrics = engine.transaction_manager().metrics();
+        assert_eq!((metrics.committed.get(), metrics.aborted.get()), (0, 0));
+        assert!(engine.active_transactions().await.unwrap().is_empty());
+
+        server
+            .post("/concepts")
+            .json(&json!({"data": {"name": "Small"}}))
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_expiring_concept_leaves_the_graph_without_a_restart() {
+        let (server, engine) = setup_test_server_with_engine();
+        let expires_at = Utc::now() + chrono::Duration::milliseconds(300);
+        let created: CreateConceptResponse = server
+            .post("/concepts")
+            .json(&json!({"data": {"name": "Session"}, "expires_at": expires_at}))
+            .await
+            .json();
+        assert_eq!(created.version, 1);
+        assert!(created.committed_at.is_some());
+        let user = engine.store(json!({"name": "User"})).await.unwrap();
+        let related: RelateResponse = server
+            .post("/relationships")
+            .json(&json!({"source": created.concept_id, "type": "belongs_to", "target": user}))
+            .await
+            .json();
+        assert_eq!(related.version, 1);
+        assert!(related.committed_at.is_some());
+
+        let graph: GraphData = server.get("/graph").await.json();
+        assert_eq!((graph.nodes.len(), graph.edges.len()), (2, 1));
+        let concept: Concept = server.get(&format!("/concepts/{}", created.concept_id)).await.json();
+        assert_eq!(concept.expires_at, Some(expires_at));
+
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        let graph: GraphData = server.get("/graph").await.json();
+        assert_eq!(graph.nodes.iter().map(|n| n.id.clone()).collect::<Vec<_>>(), vec![user.to_string()]);
+        assert!(graph.edges.is_empty());
+        server
+            .get(&format!("/concepts/{}", created.concept_id))
+            .await
+            .assert_status_not_found();
+
+        // Batches can't carry an expiry, and one already past is refused.
+        server
+            .post("/concepts/batch")
+            .json(&json!({"items": [{"data": {"n": 1}, "expires_at": expires_at}]}))
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        server
+            .post("/concepts")
+            .json(&json!({"data": {"n": 2}, "expires_at": expires_at}))
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_relationship_types_list_live_usage_counts() {
+        let (server, engine) = setup_test_server_with_engine();
+        engine.register_relationship_type("knows", "Acquaintance").await.unwrap();
+        engine.register_relationship_type("blocks", "Dependency").await.unwrap();
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        let first = engine.relate(a, "knows".to_string(), b).await.unwrap();
+        engine.relate(b, "knows".to_string(), a).await.unwrap();
+        // Unregistered types are accepted outside strict mode, but not listed.
+        engine.relate(a, "likes".to_string(), b).await.unwrap();
+
+        let counts = |types: Vec<RelationshipTypeUsage>| {
+            types.into_iter().map(|t| (t.name.to_string(), t.active_relationships)).collect::<Vec<_>>()
+        };
+        let listed: Vec<RelationshipTypeUsage> = server.get("/relationship-types").await.json();
+        assert_eq!(listed[1].description, "Acquaintance");
+        assert_eq!(counts(listed), vec![("blocks".to_string(), 0), ("knows".to_string(), 2)]);
+
+        engine.unrelate(first).await.unwrap();
+        engine.relate(a, "blocks".to_string(), b).await.unwrap();
+        let listed: Vec<RelationshipTypeUsage> = server.get("/relationship-types").await.json();
+        assert_eq!(counts(listed), vec![("blocks".to_string(), 1), ("knows".to_string(), 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_undirected_relationship_is_one_edge_seen_from_both_ends() {
+        let (server, engine) = setup_test_server_with_engine();
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        let created: RelateResponse = server
+            .post("/relationships")
+            .json(&json!({"source": a, "type": "collaborates_with", "target": b, "directed": false}))
+            .await
+            .json();
+
+        let graph: GraphData = server.get("/graph").await.json();
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.edges[0].id, created.relationship_id.to_string());
+        assert!(!graph.edges[0].directed);
+        for (from, to) in [(a, b), (b, a)] {
+            let around: TraversalResponse =
+                server.get(&format!("/concepts/{}/neighborhood?depth=1", from)).await.json();
+            assert!(around.graph.nodes.iter().any(|n| n.id == to.to_string()));
+            assert_eq!(around.graph.edges.len(), 1);
+        }
+
+        server
+            .post("/relationships/batch")
+            .json(&json!({"items": [{"source": b, "type": "collaborates_with", "target": a, "directed": false}]}))
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_empty_concepts_are_created_rendered_and_related() {
+        let (server, engine) = setup_test_server_with_engine();
+        let mut empties = Vec::new();
+        for body in [json!({"data": null}), json!({"kind": "empty"})] {
+            let created: CreateConceptResponse = server.post("/concepts").json(&body).await.json();
+            let concept: Concept = server.get(&format!("/concepts/{}", created.concept_id)).await.json();
+            assert_eq!(concept.data, ConceptData::Empty);
+            empties.push(created.concept_id);
+        }
+        server
+            .post("/concepts")
+            .json(&json!({"kind": "empty", "data": {"name": "x"}}))
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+        let group = engine.store_empty().await.unwrap();
+        let member = engine.store(json!({"name": "Member"})).await.unwrap();
+        server
+            .post("/relationships")
+            .json(&json!({"source": member, "type": "member_of", "target": group}))
+            .await
+            .assert_status_ok();
+
+        let graph: GraphData = server.get("/graph").await.json();
+        let node = |id: ConceptId| graph.nodes.iter().find(|n| n.id == id.to_string()).unwrap();
+        assert_eq!((node(member).kind, node(member).label.as_str()), (Some(ConceptKind::Structured), "Member"));
+        assert_eq!(node(group).kind, Some(ConceptKind::Empty));
+        assert_eq!(node(group).label, group.simple().to_string()[..8]);
+        assert!(empties.iter().all(|id| node(*id).kind == Some(ConceptKind::Empty)));
+        assert_eq!(graph.edges.len(), 1);
+
+        let around: TraversalResponse =
+            server.get(&format!("/concepts/{}/neighborhood?depth=1", member)).await.json();
+        let reached = around.graph.nodes.iter().find(|n| n.id == group.to_string()).unwrap();
+        assert_eq!(reached.kind, Some(ConceptKind::Empty));
+    }
+
+    /// A client's copy of the graph: every node and edge by id.
+    #[derive(Debug, Default, PartialEq)]
+    struct GraphCopy {
+        nodes: BTreeMap<String, serde_json::Value>,
+        edges: BTreeMap<String, serde_json::Value>,
+    }
+
+    impl GraphCopy {
+        fn of(graph: &GraphData) -> Self {
+            let mut copy = Self::default();
+            copy.apply(&graph.nodes, &graph.edges);
+            copy
+        }
+
+        fn apply(&mut self, nodes: &[GraphNode], edges: &[GraphEdge]) {
+            for node in nodes {
+                self.nodes.insert(node.id.clone(), serde_json::to_value(node).unwrap());
+            }
+            for edge in edges {
+                self.edges.insert(edge.id.clone(), serde_json::to_value(edge).unwrap());
+            }
+        }
+
+        fn apply_delta(&mut self, delta: &GraphDeltaResponse) {
+            for id in &delta.removed_node_ids {
+                self.nodes.remove(id);
+            }
+            for id in &delta.removed_edge_ids {
+                self.edges.remove(id);
+            }
+            self.apply(&delta.added_nodes, &delta.added_edges);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_plus_deltas_equals_the_current_graph() {
+        let (server, engine) = setup_test_server_with_engine();
+        let delta_since = |since: DateTime<Utc>| {
+            server.get("/graph/delta").add_query_param("since", since.to_rfc3339()).add_query_param("include_data", true)
+        };
+        let fresh = || async {
+            let graph: GraphData = server.get("/graph?include_data=true").await.json();
+            GraphCopy::of(&graph)
+        };
+
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        let a_to_b = engine.relate(a, "knows", b).await.unwrap();
+        let snapshot: GraphData = server.get("/graph?include_data=true").await.json();
+        let mut copy = GraphCopy::of(&snapshot);
+        let mut since = snapshot.as_of.expect("/graph hands out a watermark");
+
+        let c = engine.store(json!({"name": "c"})).await.unwrap();
+        let mut renamed = Concept::new(json!({"name": "a2"}));
+        renamed.id = a;
+        let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        txn.write_set.insert(a);
+        txn.pending_writes.insert(a, renamed);
+        engine.commit_transaction(txn).await.unwrap();
+        engine
+            .relate_with_options(b, "knows", c, RelateOptions { directed: false })
+            .await
+            .unwrap();
+        let expires_at = Utc::now() + chrono::Duration::milliseconds(300);
+        let d = engine
+            .store_with_options(json!({"name": "d"}), StoreOptions { expires_at: Some(expires_at), ..Default::default() })
+            .await
+            .unwrap()
+            .concept_id;
+        engine.relate(c, "knows", d).await.unwrap();
+
+        let delta: GraphDeltaResponse = delta_since(since).await.json();
+        assert_eq!(delta.added_nodes.len(), 3);
+        assert!(delta.removed_node_ids.is_empty());
+        copy.apply_delta(&delta);
+        assert_eq!(copy, fresh().await);
+        since = delta.as_of;
+
+        // Nothing new makes an empty delta, and the watermark still moves on.
+        let quiet: GraphDeltaResponse = delta_since(since).await.json();
+        assert!(quiet.added_nodes.is_empty() && quiet.removed_edge_ids.is_empty());
+        assert!(quiet.as_of > since);
+        since = quiet.as_of;
+
+        engine.unrelate(a_to_b).await.unwrap();
+        engine.delete_concept(a).await.unwrap();
+        let delta: GraphDeltaResponse = delta_since(since).await.json();
+        assert_eq!(delta.removed_node_ids, vec![a.to_string()]);
+        assert_eq!(delta.removed_edge_ids, vec![a_to_b.to_string()]);
+        copy.apply_delta(&delta);
+        assert_eq!(copy, fresh().await);
+        since = delta.as_of;
+
+        // Expiring commits nothing, but its node and edge still leave with the next delta.
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        let delta: GraphDeltaResponse = delta_since(since).await.json();
+        assert_eq!(delta.removed_node_ids, vec![d.to_string()]);
+        assert_eq!(delta.removed_edge_ids.len(), 1);
+        copy.apply_delta(&delta);
+        assert_eq!(copy, fresh().await);
+        assert_eq!(copy.nodes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_graph_delta_from_before_a_purge_is_gone() {
+        let (server, engine) = setup_test_server_with_engine();
+        let before: GraphData = server.get("/graph").await.json();
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        engine.delete_concept(a).await.unwrap();
+        engine.purge_concept(a).await.unwrap();
+
+        server
+            .get("/graph/delta")
+            .add_query_param("since", before.as_of.unwrap().to_rfc3339())
+            .await
+            .assert_status(StatusCode::GONE);
+        let after: GraphData = server.get("/graph").await.json();
+        let delta: GraphDeltaResponse =
+            server.get("/graph/delta").add_query_param("since", after.as_of.unwrap().to_rfc3339()).await.json();
+        assert!(delta.removed_node_ids.is_empty());
+    }
+
+    async fn graph_copy(server: &TestServer) -> GraphCopy {
+        GraphCopy::of(&server.get("/graph?include_data=true").await.json::<GraphData>())
+    }
+
+    /// Waits for the follower's /graph to match the leader's.
+    async fn converge(leader: &TestServer, follower: &TestServer) {
+        let expected = graph_copy(leader).await;
+        for _ in 0..100 {
+            if graph_copy(follower).await == expected {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(graph_copy(follower).await, expected, "the follower never caught up");
+    }
+
+    #[tokio::test]
+    async fn test_a_follower_replicates_the_leaders_graph() {
+        let (leader_server, leader) = setup_test_server_with_engine();
+        let (follower_server, follower) = setup_test_server_with_engine();
+
+        // Some history before the follower starts, which it catches up on.
+        let a = leader.store(json!({"name": "a"})).await.unwrap();
+        let b = leader.store(json!({"name": "b"})).await.unwrap();
+        let a_to_b = leader.relate(a, "knows", b).await.unwrap();
+        let replication = tokio::spawn({
+            let (leader, follower) = (Arc::clone(&leader), Arc::clone(&follower));
+            async move { follower.replicate_from(CommitFeed::new(leader)).await }
+        });
+        converge(&leader_server, &follower_server).await;
+
+        // Then commits as they land, deletions included.
+        let c = leader.store(json!({"name": "c"})).await.unwrap();
+        leader.relate_with_options(b, "knows", c, RelateOptions { directed: false }).await.unwrap();
+        leader.unrelate(a_to_b).await.unwrap();
+        let mut renamed = Concept::new(json!({"name": "b2"}));
+        renamed.id = b;
+        let mut txn = leader.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        txn.write_set.insert(b);
+        txn.pending_writes.insert(b, renamed);
+        leader.commit_transaction(txn).await.unwrap();
+        leader.delete_concept(a).await.unwrap();
+        converge(&leader_server, &follower_server).await;
+        assert_eq!(follower.concept_history(b).await.unwrap(), leader.concept_history(b).await.unwrap());
+        assert_eq!(
+            follower.relationship_history(a_to_b).await.unwrap(),
+            leader.relationship_history(a_to_b).await.unwrap()
+        );
+
+        // The follower is read-only while it replicates, and replays are no-ops.
+        assert!(matches!(follower.store(json!({"name": "local"})).await, Err(MnemonicError::ReadOnlyReplica)));
+        follower_server
+            .post("/concepts")
+            .json(&json!({"data": {"name": "local"}}))
+            .await
+            .assert_status(StatusCode::CONFLICT);
+        let first = leader.changes(None, 1).await.unwrap().remove(0);
+        assert!(!follower.transaction_manager().apply_replicated_commit(&first).unwrap());
+
+        let changes: Vec<CommitEvent> =
+            leader_server.get("/changes").add_query_param("after", first.committed_at.to_rfc3339()).await.json();
+        assert_eq!(changes, leader.changes(Some(first.committed_at), MAX_PAGE_SIZE).await.unwrap());
+        assert_eq!(changes.len(), 7);
+
+        replication.abort();
+        let _ = replication.await;
+        follower.store(json!({"name": "local"})).await.unwrap();
+    }
+}
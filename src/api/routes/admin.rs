@@ -0,0 +1,177 @@
+// Operator routes: the audit log and change feed, active transactions, and storage upkeep.
+// Everything that changes or reveals more than the graph itself needs an admin key.
+
+use axum::{extract::{Path, Query, State}, http::{header, StatusCode}, routing::{get, post}, Json, Router};
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::AppState;
+use crate::api::ApiError;
+use crate::api::auth::Caller;
+use crate::api::types::{ChangesOptions, CheckOptions, DeleteWherePayload, ScanOptions, StorageStatsOptions};
+use crate::graph::{
+    AuditPage, AuditQuery, BulkDeleteReport, CheckReport, CommitEvent, PruneReport, RebuildReport, RetentionPolicy,
+    TransactionSummary,
+};
+use crate::storage::StorageStats;
+use crate::types::query::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+pub(super) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/audit", get(get_audit_log))
+        .route("/changes", get(get_changes))
+        .route("/admin/transactions", get(list_active_transactions))
+        .route("/admin/transactions/{id}", get(get_active_transaction))
+        .route("/admin/prune", post(prune_versions))
+        .route("/admin/compact", post(compact_storage))
+        .route("/admin/delete-where", post(delete_where))
+        .route("/admin/check", get(check_integrity))
+        .route("/admin/rebuild", post(rebuild_derived_state))
+        .route("/admin/storage", get(get_storage_stats))
+}
+
+/// Lists committed changes oldest first: `GET /audit?since=&until=&actor=&limit=&cursor=`.
+/// It names who changed what, so it needs an admin key.
+async fn get_audit_log(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(state.engine.audit_log(query).await?))
+}
+
+/// The commits after `after`, oldest first, with every version each wrote, for a follower to
+/// replicate; needs an admin key. 410 Gone if a purge erased history since `after`.
+async fn get_changes(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(options): Query<ChangesOptions>,
+) -> Result<Json<Vec<CommitEvent>>, ApiError> {
+    caller.require_admin()?;
+    let limit = options.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    Ok(Json(state.engine.changes(options.after, limit).await?))
+}
+
+/// Lists every transaction that has begun but not yet committed or aborted; needs an admin key.
+async fn list_active_transactions(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<TransactionSummary>>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(state.engine.active_transactions().await?))
+}
+
+/// Shows a single active transaction, or 404 if it is unknown or already finished; needs an
+/// admin key.
+async fn get_active_transaction(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<Uuid>,
+) -> Result<Json<TransactionSummary>, ApiError> {
+    caller.require_admin()?;
+    state
+        .engine
+        .active_transaction(id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("Transaction {} is not active", id)))
+}
+
+/// Prunes old versions under the retention policy in the body.
+/// Answers 409 with the blocking transaction IDs if an active transaction still reads them.
+async fn prune_versions(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(policy): Json<RetentionPolicy>,
+) -> Result<Json<PruneReport>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(state.engine.prune_versions(policy).await?))
+}
+
+/// Deletes every active concept matching the filter in the body, in batches. Only lists the
+/// matches unless the body sets `"dry_run": false`; a dry run is held to the scan budget.
+async fn delete_where(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(scan): Query<ScanOptions>,
+    Json(mut payload): Json<DeleteWherePayload>,
+) -> Result<Json<BulkDeleteReport>, ApiError> {
+    caller.require_admin()?;
+    payload.options.scan_budget = state.scan_budget_for(&scan);
+    Ok(Json(state.engine.delete_where_with_meta(payload.filter, payload.options, caller.metadata()).await?))
+}
+
+/// Compacts every column family, reclaiming the space of pruned versions.
+async fn compact_storage(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<StatusCode, ApiError> {
+    caller.require_admin()?;
+    state.engine.compact_storage().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Checks disk, memory and the indexes against each other; `?depth=` picks how thoroughly.
+async fn check_integrity(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(options): Query<CheckOptions>,
+) -> Result<Json<CheckReport>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(state.engine.check(options.depth).await?))
+}
+
+/// Regenerates every index from the version history.
+async fn rebuild_derived_state(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<RebuildReport>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(state.engine.rebuild_derived_state().await?))
+}
+
+/// The engine's counters and the latest RocksDB statistics sample, in the Prometheus text
+/// format: `GET /v1/metrics`. Needs an admin key.
+pub(super) async fn get_metrics(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<([(header::HeaderName, &'static str); 1], String), ApiError> {
+    caller.require_admin()?;
+    let transactions = state.engine.transaction_manager();
+    let transactions = transactions.metrics();
+    let storage = state.engine.storage_metrics();
+    let mut figures: Vec<(&str, &str, f64)> = vec![
+        ("mnemonic_transactions_committed_total", "counter", transactions.committed.get() as f64),
+        ("mnemonic_transactions_aborted_total", "counter", transactions.aborted.get() as f64),
+        ("mnemonic_transactions_expired_total", "counter", transactions.expired.get() as f64),
+        ("mnemonic_storage_slow_ops_total", "counter", storage.slow_ops.get() as f64),
+    ];
+    if let Some(sample) = storage.latest_sample() {
+        figures.extend([
+            ("mnemonic_rocksdb_stall_micros_total", "counter", sample.stall_micros as f64),
+            ("mnemonic_rocksdb_pending_compaction_bytes", "gauge", sample.pending_compaction_bytes as f64),
+            ("mnemonic_rocksdb_memtable_bytes", "gauge", sample.memtable_bytes as f64),
+            ("mnemonic_rocksdb_block_cache_hits_total", "counter", sample.block_cache_hits as f64),
+            ("mnemonic_rocksdb_block_cache_misses_total", "counter", sample.block_cache_misses as f64),
+        ]);
+        if let Some(rate) = sample.block_cache_hit_rate {
+            figures.push(("mnemonic_rocksdb_block_cache_hit_rate", "gauge", rate));
+        }
+    }
+    let mut body = String::new();
+    for (name, kind, value) in figures {
+        let _ = writeln!(body, "# TYPE {} {}\n{} {}", name, kind, name, value);
+    }
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
+
+/// Reports disk usage per column family; `?exact=true` also counts every key.
+async fn get_storage_stats(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(options): Query<StorageStatsOptions>,
+) -> Result<Json<StorageStats>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(state.engine.storage_stats(options.exact).await?))
+}
@@ -0,0 +1,205 @@
+// The /concepts routes: creating, listing, reading and deleting concepts, and their history.
+
+use axum::{extract::{Path, Query, State}, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, routing::{get, post}, Json, Router};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::{parse_batch, select_history, snapshot_timestamp, version_of, AppState};
+use crate::api::ApiError;
+use crate::api::auth::Caller;
+use crate::api::caching::Validators;
+use crate::api::format::{Negotiated, ResponseFormat};
+use crate::api::types::{
+    BatchPayload, BatchResponse, ConceptHistoryEntry, CreateConceptPayload, CreateConceptResponse, DeleteConceptOptions,
+    DegreeOptions, DegreeResponse, HistoryOptions, HistoryPage, ScanOptions, SnapshotOptions,
+};
+use crate::graph::{DeleteOptions, StoreOptions};
+use crate::types::concept::ConceptKind;
+use crate::types::query::{parse_data, ConceptListOptions, ConceptPage, Filter};
+use crate::{BatchItemError, MnemonicError};
+
+pub(super) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/concepts", post(create_concept).get(list_concepts))
+        .route("/concepts/{id}", get(get_concept_details).delete(delete_concept))
+        .route("/concepts/{id}/degree", get(get_concept_degree))
+        .route("/concepts/{id}/history", get(get_concept_history))
+        .route("/concepts/batch", post(create_concepts_batch))
+        .route("/concepts/aggregate", get(aggregate_concepts))
+}
+
+async fn create_concept(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(payload): Json<CreateConceptPayload>,
+) -> Result<Json<CreateConceptResponse>, ApiError> {
+    print!("Received request to create concept with data: {:?}", payload.data);
+
+    // This is where we finally call the engine we built!
+    let options = StoreOptions { expires_at: payload.expires_at, ..Default::default() };
+    let report = match (payload.kind, payload.data) {
+        (Some(ConceptKind::Empty), Some(_)) => {
+            return Err(MnemonicError::InvalidData("an empty concept can't have data".to_string()).into());
+        }
+        (Some(ConceptKind::Empty), None) | (None, None) => {
+            state.engine.store_empty_with_options_and_meta(options, caller.metadata()).await?
+        }
+        (_, data) => {
+            let data = data.unwrap_or(serde_json::Value::Null);
+            state.engine.store_with_options_and_meta(data, options, caller.metadata()).await?
+        }
+    };
+    let (version, committed_at) = match &report.receipt {
+        Some(receipt) => (version_of(&receipt.concept_versions, report.concept_id), Some(receipt.committed_at)),
+        None => (0, None),
+    };
+    Ok(Json(CreateConceptResponse { concept_id: report.concept_id, version, committed_at }))
+}
+
+/// Creates every concept in the batch atomically: `POST /concepts/batch`.
+async fn create_concepts_batch(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(payload): Json<BatchPayload>,
+) -> Result<Json<BatchResponse>, ApiError> {
+    let items: Vec<CreateConceptPayload> = parse_batch(&state, payload)?;
+    if items.iter().any(|item| item.expires_at.is_some()) {
+        return Err(MnemonicError::InvalidData("expires_at is not supported in batches".to_string()).into());
+    }
+    if items.iter().any(|item| item.kind.is_some()) {
+        return Err(MnemonicError::InvalidData("kind is not supported in batches".to_string()).into());
+    }
+    let missing: Vec<BatchItemError> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.data.is_none())
+        .map(|(index, _)| BatchItemError { index, error: "missing field `data`".to_string() })
+        .collect();
+    if !missing.is_empty() {
+        return Err(MnemonicError::InvalidBatch(missing).into());
+    }
+    let data = items.into_iter().filter_map(|item| item.data).collect();
+    let ids = state.engine.store_batch(data, caller.metadata()).await?;
+    Ok(Json(BatchResponse { ids }))
+}
+
+/// Lists active concepts a page at a time: `GET /concepts?limit=&cursor=&label=&tag=`.
+/// With `?snapshot=<token>`, every page comes from the snapshot's moment. System concepts are
+/// left out unless `?include_system=true`.
+async fn list_concepts(
+    State(state): State<AppState>,
+    format: ResponseFormat,
+    Query(options): Query<ConceptListOptions>,
+    Query(snapshot): Query<SnapshotOptions>,
+    Query(scan): Query<ScanOptions>,
+) -> Result<Negotiated<ConceptPage>, ApiError> {
+    let at = snapshot_timestamp(&state, &snapshot).await?;
+    let budget = state.scan_budget_for(&scan);
+    let page = state.engine.list_concepts_budgeted(options, at, state.deadline(), budget).await?;
+    Ok(Negotiated(format, page))
+}
+
+/// Query parameters that name a filter field rather than an option.
+const FILTER_PARAM_PREFIX: &str = "where.";
+
+/// Counts active concepts per value of a data field:
+/// `GET /concepts/aggregate?group_by=category&where.status=open`, optionally only those passing
+/// `?predicate=<json>` (see `query::filter`). A count over part of the store would mislead, so
+/// running out of scan budget is a 422.
+async fn aggregate_concepts(
+    State(state): State<AppState>,
+    Query(scan): Query<ScanOptions>,
+    Query(mut params): Query<HashMap<String, String>>,
+) -> Result<Json<HashMap<String, u64>>, ApiError> {
+    let group_by = params
+        .remove("group_by")
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "group_by is required"))?;
+    let predicate = params
+        .remove("predicate")
+        .map(|text| serde_json::from_str(&text))
+        .transpose()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("predicate: {}", e)))?;
+    let equals: HashMap<String, String> = params
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix(FILTER_PARAM_PREFIX)?.to_string(), value)))
+        .collect();
+    let filter = (!equals.is_empty() || predicate.is_some()).then_some(Filter { equals, predicate });
+    Ok(Json(state.engine.aggregate_budgeted(&group_by, filter, state.scan_budget_for(&scan)).await?))
+}
+
+/// A concept: `GET /concepts/{id}`, or with `?snapshot=<token>` the version active at the
+/// snapshot's moment. Tagged with its version number, so a client sending the tag back in
+/// `If-None-Match` gets 304 until the concept changes.
+async fn get_concept_details(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>, //Axum extracts the ID from the URL path
+    Query(snapshot): Query<SnapshotOptions>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let concept = match snapshot_timestamp(&state, &snapshot).await? {
+        Some(timestamp) => state.engine.get_concept_at(id, timestamp).await?,
+        None => state.engine.get_concept(id).await?,
+    };
+    let concept = concept.ok_or_else(|| ApiError::not_found(format!("Concept with ID {} not found", id)))?;
+    let validators = Validators::new(concept.metadata.version, Some(concept.metadata.updated_at));
+    Ok(validators.not_modified(&headers).unwrap_or_else(|| validators.attach(Json(concept).into_response())))
+}
+
+/// Tombstones a concept: `DELETE /concepts/{id}`, answering 204. With `?cascade=true` the live
+/// relationships touching it go in the same transaction, and the report of what was deleted
+/// comes back. 404 if it isn't an active concept; 409 on a conflict, or when relationships
+/// still touch it and deletes are strict (see `AppState::with_strict_deletes`).
+async fn delete_concept(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<Uuid>,
+    Query(options): Query<DeleteConceptOptions>,
+) -> Result<Response, ApiError> {
+    let delete = DeleteOptions { cascade: options.cascade, require_unreferenced: state.strict_deletes };
+    let report = state.engine.delete_concept_with_options_and_meta(id, delete, caller.metadata()).await?;
+    Ok(if options.cascade {
+        Json(report).into_response()
+    } else {
+        StatusCode::NO_CONTENT.into_response()
+    })
+}
+
+/// Counts a concept's active relationships; `?direction=outgoing|incoming|both` (default both).
+async fn get_concept_degree(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<DegreeOptions>,
+) -> Result<Json<DegreeResponse>, ApiError> {
+    let degree = state.engine.degree(id, options.direction).await?;
+    Ok(Json(DegreeResponse {
+        id,
+        direction: options.direction,
+        degree,
+    }))
+}
+
+/// Lists every version of a concept, newest first, even once it has been deleted:
+/// `GET /concepts/:id/history?limit=&before_version=`, or `?at=` for the version active then.
+async fn get_concept_history(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<HistoryOptions>,
+) -> Result<Json<HistoryPage<ConceptHistoryEntry>>, ApiError> {
+    let history = state.engine.concept_history(id).await?;
+    let (versions, next_before_version) =
+        select_history(history, &options, |v| (v.version, v.created_at, v.deleted_at));
+    let mut entries = Vec::with_capacity(versions.len());
+    for v in versions {
+        let transaction = state.engine.transaction_metadata(v.deleted_by.unwrap_or(v.created_by)).await?;
+        entries.push(ConceptHistoryEntry {
+            version: v.version,
+            data: parse_data(&v.data),
+            created_at: v.created_at,
+            created_by: v.created_by,
+            deleted_at: v.deleted_at,
+            deleted_by: v.deleted_by,
+            transaction,
+        });
+    }
+    Ok(Json(HistoryPage { versions: entries, next_before_version }))
+}
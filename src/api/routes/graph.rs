@@ -0,0 +1,377 @@
+// Reading the graph as a whole: /graph and its deltas, traversals, /query, snapshots, /stats
+// and activity over time.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::VARY, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{snapshot_timestamp, AppState};
+use crate::api::ApiError;
+use crate::api::caching::Validators;
+use crate::api::format::{Negotiated, ResponseFormat};
+use crate::api::types::{
+    ActivityOptions, GraphData, GraphDeltaOptions, GraphDeltaResponse, GraphEdge, GraphNode, GraphOptions, GraphShape, NeighborhoodOptions,
+    PathOptions, QueryOptions, ScanOptions, SnapshotOptions, TraversalResponse,
+};
+use crate::graph::{ActivityBucket, GraphQuery, GraphSnapshot, GraphStats, QueryResult, ReadSnapshot, ScanBudget, SnapshotToken, Traversal, TraversalLimits};
+use crate::types::concept::{ConceptId, ConceptVersion};
+use crate::types::query::{label_for, labels_of, parse_data};
+use crate::types::relationship::{RelationType, RelationshipVersion};
+use crate::types::system;
+use crate::MnemonicError;
+
+/// The neighbourhood depth used when the request doesn't name one.
+const DEFAULT_NEIGHBORHOOD_DEPTH: u32 = 2;
+
+pub(super) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/concepts/{id}/neighborhood", get(get_neighborhood))
+        .route("/path", get(get_path))
+        .route("/query", post(run_query))
+        .route("/graph", get(get_graph_data))
+        .route("/graph/delta", get(get_graph_delta))
+        .route("/export", get(export_graph))
+        .route("/snapshots", post(open_snapshot))
+        .route("/snapshots/{token}", delete(release_snapshot))
+        .route("/stats", get(get_stats))
+        .route("/stats/activity", get(get_activity))
+}
+
+/// Every live concept and the relationships among them, in JSON or MessagePack: `GET /export`.
+/// `?snapshot=<token>` exports the graph as that snapshot saw it.
+async fn export_graph(
+    State(state): State<AppState>,
+    format: ResponseFormat,
+    Query(snapshot): Query<SnapshotOptions>,
+) -> Result<Negotiated<GraphSnapshot>, ApiError> {
+    let graph = match snapshot_timestamp(&state, &snapshot).await? {
+        Some(at) => state.engine.snapshot_at_within(at, state.deadline()).await?,
+        None => state.engine.snapshot().await?,
+    };
+    Ok(Negotiated(format, graph))
+}
+
+/// The whole live graph: `GET /graph`. Tagged with the latest commit sequence, so a client
+/// sending the tag back in `If-None-Match` gets 304, without the graph being walked, until the
+/// next commit or purge. (A concept that expires drops out at the sweep that tombstones it.) The
+/// tag also names the parameters that shape the body, so one URL's tag never answers for another's.
+/// A body cut short by the scan budget gets a tag of its own that never matches, so its client
+/// fetches again rather than keeping a partial graph. System concepts, and edges touching them,
+/// are left out unless `?include_system=true`.
+async fn get_graph_data(
+    State(state): State<AppState>,
+    format: ResponseFormat,
+    Query(options): Query<GraphOptions>,
+    Query(scan): Query<ScanOptions>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    // Taken before the walk, so the tag never names a newer graph than the body holds. A
+    // purge erases without a commit, so its time goes into the tag too.
+    let sequence = state.engine.latest_commit_sequence();
+    let erased_at = state.engine.history_erased_at();
+    let mut tag = format!("graph-{}", sequence);
+    if let Some(erased_at) = erased_at {
+        write!(tag, "-{}", erased_at.timestamp_micros()).ok();
+    }
+    let budget = state.scan_budget_for(&scan);
+    write!(tag, "-{}", graph_params(&options, budget)).ok();
+    if format == ResponseFormat::MessagePack {
+        tag.push_str("-msgpack");
+    }
+    let last_modified = state.engine.last_changed_at().max(erased_at);
+    let validators = Validators::new(&tag, last_modified);
+    if let Some(not_modified) = validators.not_modified(&headers) {
+        return Ok(vary_on_accept(not_modified));
+    }
+
+    // We get the Transaction Manager...
+    let tm = state.engine.transaction_manager();
+    // ...and from it, the already-hydrated Version Store.
+    let vs = tm.version_store();
+    let label_fields = Arc::clone(&state.label_fields);
+
+    // Anything committed after this is left to /graph/delta, even if the walk sees it.
+    let as_of = tm.watermark();
+
+    // Stop rendering once the request runs out of time, or the client goes away.
+    let deadline = state.deadline();
+    let guard = deadline.cancel_on_drop();
+
+    // Run on the engine's worker pool because RwLock is synchronous.
+    let graph_data: Result<GraphData, MnemonicError> = state.engine.worker_pool().run(move || {
+        // Walk the IN-MEMORY, hydrated Version Store a chunk at a time, so commits
+        // aren't held up for the whole walk. Nodes and edges share one budget.
+        let meter = budget.start();
+        let full = options.shape == GraphShape::Full;
+        let mut stopped = Ok(());
+        let mut nodes: Vec<GraphNode> = Vec::new();
+        let mut tagged: HashSet<ConceptId> = HashSet::new();
+        let mut hidden: HashSet<ConceptId> = HashSet::new();
+        vs.scan_active_concepts(&meter, |version, _| {
+            stopped = deadline.check();
+            if !options.include_system && system::is_system(&version.tags) {
+                hidden.insert(version.concept_id);
+            } else if options.tag.as_ref().is_none_or(|tag| version.tags.contains(tag)) {
+                if options.tag.is_some() {
+                    tagged.insert(version.concept_id);
+                }
+                nodes.push(graph_node(version, full, options.include_data, &label_fields));
+            }
+            if stopped.is_err() { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        })?;
+        std::mem::replace(&mut stopped, Ok(()))?;
+
+        let mut edges: Vec<GraphEdge> = Vec::new();
+        vs.scan_active_relationships(&meter, |version| {
+            stopped = deadline.check();
+            let shown = !hidden.contains(&version.source) && !hidden.contains(&version.target);
+            if shown && (options.tag.is_none() || (tagged.contains(&version.source) && tagged.contains(&version.target))) {
+                edges.push(graph_edge(version, full));
+            }
+            if stopped.is_err() { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        })?;
+        stopped?;
+
+        Ok(GraphData { nodes, edges, as_of: Some(as_of), truncated: meter.is_spent() })
+    }).await;
+    guard.disarm();
+    let graph_data = graph_data?;
+
+    tracing::info!("Returning {} nodes and {} edges", graph_data.nodes.len(), graph_data.edges.len());
+    let validators =
+        if graph_data.truncated { Validators::new(format!("{}-truncated", tag), last_modified) } else { validators };
+    Ok(vary_on_accept(validators.attach(Negotiated(format, graph_data).into_response())))
+}
+
+/// The /graph parameters that change its body, normalized: defaults and budgets the server
+/// caps anyway read the same as leaving them out. The tag filter is hex, to stay a valid ETag.
+fn graph_params(options: &GraphOptions, budget: ScanBudget) -> String {
+    let mut params = String::from(match options.shape {
+        GraphShape::Full => "full",
+        GraphShape::Minimal => "minimal",
+    });
+    if options.include_data {
+        params.push_str(".data");
+    }
+    if options.include_system {
+        params.push_str(".system");
+    }
+    if let Some(tag) = &options.tag {
+        params.push_str(".t");
+        for byte in tag.bytes() {
+            write!(params, "{:02x}", byte).ok();
+        }
+    }
+    if budget.max_items != usize::MAX {
+        write!(params, ".i{}", budget.max_items).ok();
+    }
+    if budget.max_duration != Duration::MAX {
+        write!(params, ".ms{}", budget.max_duration.as_millis()).ok();
+    }
+    params
+}
+
+/// `response`, marked as differing by `Accept`, which picks between JSON and MessagePack.
+fn vary_on_accept(mut response: Response) -> Response {
+    response.headers_mut().append(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// What changed in the graph since an earlier `as_of`: `GET /graph/delta?since=<as_of>`, with
+/// the same `include_data` and `shape` as /graph. 410 if a purge has erased the history since then;
+/// fetch /graph again.
+async fn get_graph_delta(
+    State(state): State<AppState>,
+    format: ResponseFormat,
+    Query(options): Query<GraphDeltaOptions>,
+) -> Result<Negotiated<GraphDeltaResponse>, ApiError> {
+    let delta = state.engine.graph_delta(options.since).await?;
+    let full = options.shape == GraphShape::Full;
+    let label_fields = &state.label_fields;
+    Ok(Negotiated(
+        format,
+        GraphDeltaResponse {
+            added_nodes: delta
+                .added_concepts
+                .iter()
+                .map(|version| graph_node(version, full, options.include_data, label_fields))
+                .collect(),
+            removed_node_ids: delta.removed_concept_ids.iter().map(Uuid::to_string).collect(),
+            added_edges: delta.added_relationships.iter().map(|version| graph_edge(version, full)).collect(),
+            removed_edge_ids: delta.removed_relationship_ids.iter().map(Uuid::to_string).collect(),
+            as_of: delta.as_of,
+        },
+    ))
+}
+
+/// A concept as /graph and /graph/delta show it.
+fn graph_node(version: &ConceptVersion, full: bool, include_data: bool, label_fields: &[String]) -> GraphNode {
+    let parsed = (full || include_data).then(|| parse_data(&version.data));
+    GraphNode {
+        id: version.concept_id.to_string(),
+        label: label_for(&version.concept_id, &version.data, label_fields),
+        kind: full.then(|| version.data.kind()),
+        labels: parsed.as_ref().filter(|_| full).map(labels_of),
+        tags: if full { version.tags.iter().cloned().collect() } else { Vec::new() },
+        created_at: full.then_some(version.created_at),
+        version: full.then_some(version.version),
+        data: parsed.filter(|_| include_data),
+    }
+}
+
+/// A relationship as /graph and /graph/delta show it.
+fn graph_edge(version: &RelationshipVersion, full: bool) -> GraphEdge {
+    GraphEdge {
+        id: version.relationship_id.to_string(),
+        source: version.source.to_string(),
+        target: version.target.to_string(),
+        label: version.relationship_type.to_string(),
+        directed: version.directed,
+        created_at: full.then_some(version.created_at),
+        version: full.then_some(version.version),
+    }
+}
+
+/// Opens a read snapshot: `POST /snapshots` returns `{token, timestamp}`. Pass the token as
+/// `?snapshot=` to `GET /concepts` and `GET /concepts/{id}` to read as of its timestamp.
+async fn open_snapshot(State(state): State<AppState>) -> Result<(StatusCode, Json<ReadSnapshot>), ApiError> {
+    Ok((StatusCode::CREATED, Json(state.engine.open_snapshot().await?)))
+}
+
+/// Releases a read snapshot: `DELETE /snapshots/{token}`; 404 if it wasn't open.
+async fn release_snapshot(State(state): State<AppState>, Path(token): Path<SnapshotToken>) -> Result<StatusCode, ApiError> {
+    if state.engine.release_snapshot(token).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(MnemonicError::SnapshotNotFound(token).into())
+    }
+}
+
+/// Runs a declarative query: `POST /query` with `{"match": {...}, "return": [...], "limit": n}`.
+/// A malformed query is a 422 naming the offending path. System concepts only match with
+/// `?include_system=true`.
+async fn run_query(
+    State(state): State<AppState>,
+    Query(options): Query<QueryOptions>,
+    Query(scan): Query<ScanOptions>,
+    Json(query): Json<serde_json::Value>,
+) -> Result<Json<QueryResult>, ApiError> {
+    let query = GraphQuery { include_system: options.include_system, ..GraphQuery::parse(&query)? };
+    Ok(Json(state.engine.query(query, state.scan_budget_for(&scan)).await?))
+}
+
+/// The shortest path along outgoing relationships: `GET /path?from=&to=&max_depth=&types=a,b`.
+/// Answers 404 with an empty graph when there is none within the limits.
+async fn get_path(
+    State(state): State<AppState>,
+    Query(options): Query<PathOptions>,
+    Query(scan): Query<ScanOptions>,
+) -> Result<(StatusCode, Json<TraversalResponse>), ApiError> {
+    let max = state.traversal_limits;
+    let requested = options.max_depth.unwrap_or(max.max_depth);
+    let limits = TraversalLimits { max_depth: requested.min(max.max_depth), ..max };
+    let path = state
+        .engine
+        .shortest_path_budgeted(
+            options.from,
+            options.to,
+            parse_types(options.types),
+            limits,
+            state.deadline(),
+            state.scan_budget_for(&scan),
+        )
+        .await?;
+    let status = if path.concepts.is_empty() { StatusCode::NOT_FOUND } else { StatusCode::OK };
+    Ok((status, Json(traversal_response(&state, path, requested > max.max_depth))))
+}
+
+/// The concepts around one concept, either way along relationships, as a renderable graph:
+/// `GET /concepts/:id/neighborhood?depth=2&types=a,b`.
+async fn get_neighborhood(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<NeighborhoodOptions>,
+    Query(scan): Query<ScanOptions>,
+) -> Result<Json<TraversalResponse>, ApiError> {
+    let max = state.traversal_limits;
+    let requested = options.depth.unwrap_or(DEFAULT_NEIGHBORHOOD_DEPTH);
+    let limits = TraversalLimits { max_depth: requested.min(max.max_depth), ..max };
+    let budget = state.scan_budget_for(&scan);
+    let neighborhood =
+        state.engine.neighborhood_budgeted(id, parse_types(options.types), limits, state.deadline(), budget).await?;
+    Ok(Json(traversal_response(&state, neighborhood, requested > max.max_depth)))
+}
+
+/// `a,b` as the relationship types `a` and `b`; absent or empty means any type.
+fn parse_types(types: Option<String>) -> Option<Vec<RelationType>> {
+    let types: Vec<RelationType> = types?
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(RelationType::new)
+        .collect();
+    (!types.is_empty()).then_some(types)
+}
+
+/// A traversal in the shape of /graph. `depth_capped` marks a walk the server kept
+/// shallower than asked, which is as much a truncation as running out of concepts.
+fn traversal_response(state: &AppState, traversal: Traversal, depth_capped: bool) -> TraversalResponse {
+    let nodes = traversal
+        .concepts
+        .iter()
+        .map(|concept| GraphNode {
+            id: concept.id.to_string(),
+            label: label_for(&concept.id, &concept.data, &state.label_fields),
+            kind: Some(concept.data.kind()),
+            labels: None,
+            tags: Vec::new(),
+            created_at: None,
+            version: None,
+            data: None,
+        })
+        .collect();
+    let edges = traversal
+        .relationships
+        .into_iter()
+        .map(|rel| GraphEdge {
+            id: rel.id.to_string(),
+            source: rel.source.to_string(),
+            target: rel.target.to_string(),
+            label: rel.relationship_type.into(),
+            directed: rel.directed,
+            created_at: None,
+            version: None,
+        })
+        .collect();
+    TraversalResponse {
+        graph: GraphData { nodes, edges, as_of: None, truncated: false },
+        truncated: traversal.truncated || depth_capped,
+    }
+}
+
+/// Counts active concepts and relationships, and the history held behind them.
+async fn get_stats(State(state): State<AppState>) -> Result<Json<GraphStats>, ApiError> {
+    Ok(Json(state.engine.stats().await?))
+}
+
+/// What was committed in each hour or day, for charting growth:
+/// `GET /stats/activity?from=&to=&bucket=hour|day`. Buckets are whole UTC hours or days, with
+/// zeros where nothing happened. 422 for an empty window or one of too many buckets.
+async fn get_activity(
+    State(state): State<AppState>,
+    Query(options): Query<ActivityOptions>,
+) -> Result<Json<Vec<ActivityBucket>>, ApiError> {
+    let to = options.to.unwrap_or_else(Utc::now);
+    Ok(Json(state.engine.activity(options.from, to, options.bucket).await?))
+}
@@ -0,0 +1,136 @@
+// The /relationships routes: relating concepts, reading a relationship and its history, and
+// the types in use.
+
+use axum::{extract::{Path, Query, State}, routing::{get, post}, Json, Router};
+use uuid::Uuid;
+
+use super::{parse_batch, select_history, version_of, AppState};
+use crate::api::ApiError;
+use crate::api::auth::Caller;
+use crate::api::types::{
+    BatchPayload, BatchResponse, EndpointConcept, HistoryOptions, HistoryPage, RelatePayload, RelateResponse,
+    RelationshipExpand, RelationshipHistoryEntry, RelationshipOptions, RelationshipResponse,
+};
+use crate::graph::RelateOptions;
+use crate::types::concept::ConceptVersion;
+use crate::types::query::{labels_of, parse_data};
+use crate::types::relationship::RelationshipTypeUsage;
+use crate::MnemonicError;
+
+pub(super) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/relationships", post(relate_concepts))
+        .route("/relationships/batch", post(relate_concepts_batch))
+        .route("/relationships/{id}", get(get_relationship))
+        .route("/relationships/{id}/history", get(get_relationship_history))
+        .route("/relationship-types", get(list_relationship_types))
+}
+
+async fn relate_concepts(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(payload): Json<RelatePayload>,
+) -> Result<Json<RelateResponse>, ApiError> {
+    
+    let options = RelateOptions { directed: payload.directed };
+    let (relationship_id, receipt) = state
+        .engine
+        .relate_with_receipt(
+            payload.source,
+            payload.relationship_type,
+            payload.target,
+            options,
+            caller.metadata(),
+        )
+        .await?;
+    Ok(Json(RelateResponse {
+        relationship_id,
+        version: version_of(&receipt.relationship_versions, relationship_id),
+        committed_at: Some(receipt.committed_at),
+    }))
+}
+
+/// Creates every relationship in the batch atomically: `POST /relationships/batch`.
+async fn relate_concepts_batch(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(payload): Json<BatchPayload>,
+) -> Result<Json<BatchResponse>, ApiError> {
+    let items: Vec<RelatePayload> = parse_batch(&state, payload)?;
+    if items.iter().any(|item| !item.directed) {
+        return Err(MnemonicError::InvalidData("undirected relationships are not supported in batches".to_string()).into());
+    }
+    let relationships = items
+        .into_iter()
+        .map(|item| (item.source, item.relationship_type, item.target))
+        .collect();
+    let ids = state.engine.relate_batch(relationships, caller.metadata()).await?;
+    Ok(Json(BatchResponse { ids }))
+}
+
+/// An active relationship: `GET /relationships/:id`. With `?expand=endpoints` it embeds the
+/// concepts at both ends, read at the same moment as the relationship. 404 if it isn't active.
+async fn get_relationship(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<RelationshipOptions>,
+) -> Result<Json<RelationshipResponse>, ApiError> {
+    let read = state.engine.relationship_with_endpoints(id).await?;
+    let expand = options.expand == Some(RelationshipExpand::Endpoints);
+    let rel = read.relationship;
+    Ok(Json(RelationshipResponse {
+        id: rel.relationship_id,
+        source: rel.source,
+        relationship_type: rel.relationship_type,
+        target: rel.target,
+        directed: rel.directed,
+        version: rel.version,
+        created_at: rel.created_at,
+        source_concept: expand.then(|| endpoint_concept(read.source)),
+        target_concept: expand.then(|| endpoint_concept(read.target)),
+    }))
+}
+
+fn endpoint_concept(version: Option<ConceptVersion>) -> EndpointConcept {
+    match version {
+        Some(version) => {
+            let data = parse_data(&version.data);
+            EndpointConcept::Concept { id: version.concept_id, labels: labels_of(&data), data, version: version.version }
+        }
+        None => EndpointConcept::Deleted { deleted: true },
+    }
+}
+
+/// Lists every version of a relationship, newest first, with the same parameters as
+/// `GET /concepts/:id/history`.
+async fn get_relationship_history(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<HistoryOptions>,
+) -> Result<Json<HistoryPage<RelationshipHistoryEntry>>, ApiError> {
+    let history = state.engine.relationship_history(id).await?;
+    let (versions, next_before_version) =
+        select_history(history, &options, |v| (v.version, v.created_at, v.deleted_at));
+    let mut entries = Vec::with_capacity(versions.len());
+    for v in versions {
+        let transaction = state.engine.transaction_metadata(v.deleted_by.unwrap_or(v.created_by)).await?;
+        entries.push(RelationshipHistoryEntry {
+            version: v.version,
+            source: v.source,
+            relationship_type: v.relationship_type,
+            target: v.target,
+            created_at: v.created_at,
+            created_by: v.created_by,
+            deleted_at: v.deleted_at,
+            deleted_by: v.deleted_by,
+            transaction,
+        });
+    }
+    Ok(Json(HistoryPage { versions: entries, next_before_version }))
+}
+
+/// Lists the registered relationship types with how many active relationships use each:
+/// `GET /relationship-types`.
+async fn list_relationship_types(State(state): State<AppState>) -> Result<Json<Vec<RelationshipTypeUsage>>, ApiError> {
+    Ok(Json(state.engine.relationship_types().await?))
+}
@@ -0,0 +1,405 @@
+// The request and response bodies of the HTTP API, shared by the server's handlers and
+// `MnemonicClient` so the two can't drift apart.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::graph::{BucketWidth, BulkDeleteOptions, CheckDepth, SnapshotToken};
+use crate::types::concept::{ConceptId, ConceptKind, TransactionId};
+use crate::types::query::{Direction, Filter};
+use crate::types::relationship::{RelationType, RelationshipId};
+use crate::types::transaction::TransactionMetadata;
+
+// This defines the shape of the JSON we expect for creating a concept.
+// e.g {"data": {"name": "Alice"}}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateConceptPayload {
+    // Null or missing data, or `"kind": "empty"`, makes an empty (structural) concept.
+    // Batch items must have data.
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ConceptKind>,
+    // When the concept stops being active. Single creates only; batches refuse it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// This defines the shape of the JSON we will send back.
+// e.g {"concept_id": "..."}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateConceptResponse {
+    pub concept_id: Uuid,
+    /// The version the concept was stored as.
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub committed_at: Option<DateTime<Utc>>,
+}
+
+// These structs are simplified for the UI. It doesn't need all the metadata.
+// With ?shape=minimal a node is just its id and label (and data, if asked for).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    // Whether the concept holds data or is empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ConceptKind>,
+    // Every label in the concept's `labels` array.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    // The concept's tags, when it has any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // When the concept's current version was written, and its number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    // The concept's full data, only sent with ?include_data=true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+// How much /graph says about each node and edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphShape {
+    #[default]
+    Full,
+    // Only the id and label, as /graph returned before nodes carried metadata.
+    Minimal,
+}
+
+// Query: ?include_data=true&shape=minimal&tag=starred&include_system=true
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GraphOptions {
+    #[serde(default)]
+    pub include_data: bool,
+    #[serde(default)]
+    pub shape: GraphShape,
+    // Only the concepts carrying this tag, and the edges between them.
+    #[serde(default)]
+    pub tag: Option<String>,
+    // System concepts too, which are left out otherwise.
+    #[serde(default)]
+    pub include_system: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub id: String,
+    pub source: String,
+    pub target: String,
+    pub label: String,
+    // Only sent for an undirected relationship.
+    #[serde(default = "crate::types::relationship::directed_by_default", skip_serializing_if = "is_directed")]
+    pub directed: bool,
+    // When the relationship's current version was written, and its number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+}
+
+fn is_directed(directed: &bool) -> bool {
+    *directed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphData {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    // From /graph, the watermark to ask /graph/delta for later changes from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<DateTime<Utc>>,
+    // From /graph, set when the scan budget ran out, so some nodes or edges are missing.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub truncated: bool,
+}
+
+fn is_false(flag: &bool) -> bool {
+    !*flag
+}
+
+// The body of POST /admin/delete-where, e.g.
+// {"filter": {"equals": {"source": "import-2024-06-01"}}, "dry_run": false, "cascade": true}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteWherePayload {
+    pub filter: Filter,
+    #[serde(flatten)]
+    pub options: BulkDeleteOptions,
+}
+
+// Query: ?cascade=true, on DELETE /concepts/{id}.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeleteConceptOptions {
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+// Query: ?max_items=1000&max_duration_ms=500, on the routes that scan, to spend less than
+// the server's scan budget. Asking for more than the budget gets the budget.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ScanOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration_ms: Option<u64>,
+}
+
+// Query: ?include_system=true, on POST /query, to match system concepts too.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct QueryOptions {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub include_system: bool,
+}
+
+// Query: ?snapshot=<token>, on the GET routes that can read as of a snapshot.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotToken>,
+}
+
+// Query: ?after=<committed_at>&limit=100. Without `after`, from the first commit held.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangesOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+// Query: ?since=<as_of>&include_data=true&shape=minimal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphDeltaOptions {
+    pub since: DateTime<Utc>,
+    #[serde(default)]
+    pub include_data: bool,
+    #[serde(default)]
+    pub shape: GraphShape,
+}
+
+// What /graph/delta sends back: apply it on top of what /graph (or the last delta) gave,
+// replacing nodes and edges with the same id, then ask again from `as_of`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphDeltaResponse {
+    pub added_nodes: Vec<GraphNode>,
+    pub removed_node_ids: Vec<String>,
+    pub added_edges: Vec<GraphEdge>,
+    pub removed_edge_ids: Vec<String>,
+    pub as_of: DateTime<Utc>,
+}
+
+// Query: ?from=&to=&max_depth=3&types=knows,cites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathOptions {
+    pub from: ConceptId,
+    pub to: ConceptId,
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    // Comma-separated relationship types to follow; any type when absent.
+    #[serde(default)]
+    pub types: Option<String>,
+}
+
+// Query: ?depth=2&types=knows,cites
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NeighborhoodOptions {
+    pub depth: Option<u32>,
+    pub types: Option<String>,
+}
+
+// A GraphData the frontend can render as is, plus whether a server-side limit cut it short.
+// For /path the nodes and edges are in path order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraversalResponse {
+    #[serde(flatten)]
+    pub graph: GraphData,
+    pub truncated: bool,
+}
+
+// Request: { "source": "...", "type": "...", "target": "...", "directed": false }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatePayload {
+    pub source: ConceptId,
+    #[serde(rename = "type")]
+    pub relationship_type: RelationType,
+    pub target: ConceptId,
+    /// Absent means directed.
+    #[serde(default = "crate::types::relationship::directed_by_default")]
+    pub directed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelateResponse {
+    pub relationship_id: RelationshipId,
+    /// The version the relationship was stored as.
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub committed_at: Option<DateTime<Utc>>,
+}
+
+// Request: { "items": [ ... ] }, where each item has the shape of the single-item endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchPayload {
+    pub items: Vec<serde_json::Value>,
+}
+
+// Response: { "ids": [...] }, in the same order as the request items.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub ids: Vec<Uuid>,
+}
+
+// Query: ?direction=outgoing|incoming|both
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DegreeOptions {
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DegreeResponse {
+    pub id: ConceptId,
+    pub direction: Direction,
+    pub degree: u64,
+}
+
+/// What `GET /v1/meta` says about the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetaResponse {
+    /// The mnemonic-core release, e.g. `0.1.0`.
+    pub crate_version: String,
+    /// The API version its paths are prefixed with, e.g. `v1`.
+    pub api_version: String,
+    /// The cargo features the server was built with.
+    pub features: Vec<String>,
+}
+
+/// What `GET /v1/readyz` says about the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    /// False while the version history is still being loaded in the background; reads
+    /// are served meanwhile, from disk where need be.
+    pub warmed: bool,
+}
+
+// Query: ?exact=true
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageStatsOptions {
+    #[serde(default)]
+    pub exact: bool,
+}
+
+// Query: ?depth=quick|standard|deep
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CheckOptions {
+    #[serde(default)]
+    pub depth: CheckDepth,
+}
+
+// Query: ?from=&to=&bucket=hour|day. `to` defaults to now, `bucket` to day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityOptions {
+    pub from: DateTime<Utc>,
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub bucket: BucketWidth,
+}
+
+// Query: ?limit=&before_version=, or ?at= for the one version active at that moment.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryOptions {
+    pub limit: Option<usize>,
+    pub before_version: Option<u64>,
+    pub at: Option<DateTime<Utc>>,
+}
+
+// Response: { "versions": [...], "next_before_version": 3 }, newest version first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage<T> {
+    pub versions: Vec<T>,
+    // Pass this as `before_version` to get the next page. `None` on the last page.
+    pub next_before_version: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConceptHistoryEntry {
+    pub version: u64,
+    pub data: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub created_by: TransactionId,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<TransactionId>,
+    // The metadata of the transaction that wrote this version, if it recorded any.
+    pub transaction: Option<TransactionMetadata>,
+}
+
+// Query: ?expand=endpoints
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelationshipOptions {
+    pub expand: Option<RelationshipExpand>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationshipExpand {
+    // Embed the concepts at both ends.
+    Endpoints,
+}
+
+// The body of GET /relationships/:id. The endpoint concepts are only sent with ?expand=endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationshipResponse {
+    pub id: RelationshipId,
+    pub source: ConceptId,
+    #[serde(rename = "type")]
+    pub relationship_type: RelationType,
+    pub target: ConceptId,
+    #[serde(default = "crate::types::relationship::directed_by_default")]
+    pub directed: bool,
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_concept: Option<EndpointConcept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_concept: Option<EndpointConcept>,
+}
+
+// An endpoint as ?expand=endpoints embeds it, or `{"deleted": true}` once it is gone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EndpointConcept {
+    Concept {
+        id: ConceptId,
+        labels: Vec<String>,
+        data: serde_json::Value,
+        version: u64,
+    },
+    Deleted {
+        deleted: bool,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationshipHistoryEntry {
+    pub version: u64,
+    pub source: ConceptId,
+    #[serde(rename = "type")]
+    pub relationship_type: RelationType,
+    pub target: ConceptId,
+    pub created_at: DateTime<Utc>,
+    pub created_by: TransactionId,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<TransactionId>,
+    pub transaction: Option<TransactionMetadata>,
+}
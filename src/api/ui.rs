@@ -0,0 +1,119 @@
+// The graph explorer, built into the binary by the `ui` feature. explorer-ui/dist, as
+// `npm run build` leaves it, is embedded at compile time and served at `/`, so the UI and the
+// API share an origin and need no CORS configuration.
+
+use axum::body::Body;
+use axum::http::{HeaderValue, Method, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use include_dir::{Dir, File, include_dir};
+use std::path::Path;
+
+use crate::api::ApiError;
+
+static ASSETS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/explorer-ui/dist");
+
+/// Vite puts a content hash in the name of everything under assets/, so a browser may keep
+/// those for good.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// Everything else, index.html above all, names the current hashes and must be revalidated.
+const REVALIDATE: &str = "no-cache";
+
+/// The embedded file at `uri`, or index.html for paths the UI routes itself. A path with an
+/// extension names a file, so a missing one is a 404 rather than a page of HTML.
+pub(crate) fn serve(method: &Method, uri: &Uri) -> Response {
+    if method != Method::GET && method != Method::HEAD {
+        return ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "The UI only answers GET and HEAD").into_response();
+    }
+    let path = uri.path().trim_start_matches('/');
+    match ASSETS.get_file(path) {
+        Some(file) if !path.is_empty() => asset(file, method),
+        _ if Path::new(path).extension().is_some() => {
+            ApiError::new(StatusCode::NOT_FOUND, format!("No such file: /{}", path)).into_response()
+        }
+        _ => match ASSETS.get_file("index.html") {
+            Some(index) => asset(index, method),
+            None => ApiError::new(StatusCode::NOT_FOUND, "The embedded UI has no index.html").into_response(),
+        },
+    }
+}
+
+fn asset(file: &File<'static>, method: &Method) -> Response {
+    let path = file.path();
+    let cache = if path.starts_with("assets") { IMMUTABLE } else { REVALIDATE };
+    let body = if method == Method::HEAD { Body::empty() } else { Body::from(file.contents()) };
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type(path))),
+            (header::CACHE_CONTROL, HeaderValue::from_static(cache)),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("json" | "map") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes::{AppState, create_app};
+    use crate::graph::GraphEngine;
+    use axum_test::TestServer;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    fn server(ui: bool) -> TestServer {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        TestServer::new(create_app(AppState::new(engine), ui)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_the_root_serves_index_html_beside_the_api() {
+        let server = server(true);
+        let index = ASSETS.get_file("index.html").unwrap().contents();
+
+        let response = server.get("/").await;
+        response.assert_status_ok();
+        assert_eq!(response.as_bytes().as_ref(), index);
+        assert_eq!(response.header("content-type"), "text/html; charset=utf-8");
+        assert_eq!(response.header("cache-control"), REVALIDATE);
+
+        // Client-side routes get the page too; missing files and API paths don't.
+        assert_eq!(server.get("/explore/some-concept").await.as_bytes().as_ref(), index);
+        server.get("/assets/missing.js").await.assert_status(StatusCode::NOT_FOUND);
+        server.get("/api/nowhere").await.assert_status(StatusCode::NOT_FOUND);
+        server.get("/api/v1/ping").await.assert_text("pong");
+
+        let without_ui = self::server(false);
+        without_ui.get("/").await.assert_status(StatusCode::NOT_FOUND);
+        without_ui.get("/api/v1/ping").await.assert_text("pong");
+    }
+
+    #[tokio::test]
+    async fn test_hashed_assets_are_cached_for_good() {
+        let server = server(true);
+        let Some(asset) = ASSETS.get_dir("assets").and_then(|assets| assets.files().next()) else {
+            return;
+        };
+        let response = server.get(&format!("/{}", asset.path().display())).await;
+        response.assert_status_ok();
+        assert_eq!(response.as_bytes().as_ref(), asset.contents());
+        assert_eq!(response.header("cache-control"), IMMUTABLE);
+    }
+}
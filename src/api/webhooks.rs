@@ -0,0 +1,634 @@
+// Webhooks: POSTing committed changes to URLs registered under /admin/webhooks.
+//
+// Registrations are persisted next to the graph and reloaded when `Webhooks::start` runs.
+// A background task listens on the engine's commit channel. For each commit and webhook it
+// sends one delivery carrying the events that pass that webhook's filter. Deliveries are
+// signed with the webhook's secret and retried with exponential backoff. A delivery that
+// never succeeds is dropped and counted as dead-lettered. Retries mean deliveries can
+// arrive out of order, so receivers should order them by `committed_at`.
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+};
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Method, Request, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::api::ApiError;
+use crate::api::auth::Caller;
+use crate::api::routes::AppState;
+use crate::error::Result;
+use crate::graph::{CommitEvent, GraphEngine};
+use crate::storage::RocksBackend;
+use crate::types::concept::{ConceptId, TransactionId};
+use crate::types::query::{labels_of, parse_data};
+use crate::types::relationship::RelationType;
+use crate::types::transaction::TransactionMetadata;
+use crate::utils::hmac::{hmac_sha256, to_hex};
+use crate::utils::metrics::Counter;
+
+/// The header carrying `sha256=<hex HMAC-SHA256 of the body>`, keyed by the webhook's secret.
+pub const SIGNATURE_HEADER: &str = "x-mnemonic-signature";
+/// The header naming the webhook a delivery is for.
+pub const WEBHOOK_ID_HEADER: &str = "x-mnemonic-webhook";
+
+/// What kind of item an event is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Concept,
+    Relationship,
+}
+
+/// What happened to the item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Which events a webhook receives. Every field narrows the filter; an empty list or a
+/// missing value lets everything through.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookFilter {
+    pub entities: Vec<EntityKind>,
+    pub changes: Vec<ChangeKind>,
+    /// Only concepts carrying this label. Doesn't affect relationship events.
+    pub label: Option<String>,
+    /// Only relationships of this type. Doesn't affect concept events.
+    pub relationship_type: Option<RelationType>,
+}
+
+impl WebhookFilter {
+    pub fn matches(&self, event: &WebhookEvent) -> bool {
+        if !self.entities.is_empty() && !self.entities.contains(&event.entity) {
+            return false;
+        }
+        if !self.changes.is_empty() && !self.changes.contains(&event.change) {
+            return false;
+        }
+        match event.entity {
+            EntityKind::Concept => self.label.as_ref().is_none_or(|label| {
+                event.data.as_ref().is_some_and(|data| labels_of(data).contains(label))
+            }),
+            EntityKind::Relationship => self
+                .relationship_type
+                .as_ref()
+                .is_none_or(|wanted| event.relationship_type.as_ref() == Some(wanted)),
+        }
+    }
+}
+
+/// One changed item in a delivery.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub entity: EntityKind,
+    pub change: ChangeKind,
+    pub id: Uuid,
+    pub version: u64,
+    /// A concept's data. For a deletion, the data it had last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ConceptId>,
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub relationship_type: Option<RelationType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<ConceptId>,
+}
+
+impl WebhookEvent {
+    /// Every event in a commit, concepts first.
+    pub fn from_commit(commit: &CommitEvent) -> Vec<Self> {
+        let change = |version: u64, deleted: bool| match (deleted, version) {
+            (true, _) => ChangeKind::Deleted,
+            (false, 1) => ChangeKind::Created,
+            (false, _) => ChangeKind::Updated,
+        };
+        let concepts = commit.concept_versions.iter().map(|version| Self {
+            entity: EntityKind::Concept,
+            change: change(version.version, version.deleted_at.is_some()),
+            id: version.concept_id,
+            version: version.version,
+            data: Some(parse_data(&version.data)),
+            source: None,
+            relationship_type: None,
+            target: None,
+        });
+        let relationships = commit.relationship_versions.iter().map(|version| Self {
+            entity: EntityKind::Relationship,
+            change: change(version.version, version.deleted_at.is_some()),
+            id: version.relationship_id,
+            version: version.version,
+            data: None,
+            source: Some(version.source),
+            relationship_type: Some(version.relationship_type.clone()),
+            target: Some(version.target),
+        });
+        concepts.chain(relationships).collect()
+    }
+}
+
+/// The JSON body POSTed to a webhook: the events from one commit that passed its filter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub webhook_id: Uuid,
+    pub transaction_id: TransactionId,
+    pub committed_at: DateTime<Utc>,
+    pub metadata: TransactionMetadata,
+    pub events: Vec<WebhookEvent>,
+}
+
+/// A registered webhook, as persisted. Its secret is only shown when it is registered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub filter: WebhookFilter,
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The body of `POST /admin/webhooks`. Without a secret, one is generated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterWebhookPayload {
+    pub url: String,
+    #[serde(default)]
+    pub filter: WebhookFilter,
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// How a webhook's deliveries have gone since the server started.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryStats {
+    pub delivered: u64,
+    /// Attempts that failed, including ones a later retry made up for.
+    pub failed_attempts: u64,
+    /// Deliveries given up on after every attempt failed.
+    pub dead_lettered: u64,
+    pub last_error: Option<String>,
+}
+
+/// A webhook as listed by `GET /admin/webhooks`, without its secret.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookSummary {
+    pub id: Uuid,
+    pub url: String,
+    pub filter: WebhookFilter,
+    pub created_at: DateTime<Utc>,
+    pub stats: DeliveryStats,
+}
+
+/// How hard deliveries are tried.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Attempts per delivery, the first included.
+    pub max_attempts: u32,
+    /// The wait before the first retry. Each later retry waits twice as long as the last.
+    pub initial_backoff: Duration,
+    /// How long one attempt may take.
+    pub request_timeout: Duration,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+struct Registration {
+    webhook: Webhook,
+    stats: Mutex<DeliveryStats>,
+}
+
+impl Registration {
+    fn record(&self, update: impl FnOnce(&mut DeliveryStats)) {
+        update(&mut self.stats.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+}
+
+/// The registered webhooks and the task delivering commits to them.
+pub struct Webhooks {
+    backend: Arc<RocksBackend>,
+    config: WebhookConfig,
+    registrations: RwLock<HashMap<Uuid, Arc<Registration>>>,
+    http: Client<HttpConnector, Full<Bytes>>,
+    missed_commits: Counter,
+}
+
+impl Webhooks {
+    /// Loads the persisted registrations and starts delivering `engine`'s commits to them.
+    /// Must be called from within a tokio runtime. Delivery stops when the engine is dropped.
+    pub fn start(engine: &GraphEngine, config: WebhookConfig) -> Result<Arc<Self>> {
+        let backend = engine.backend();
+        let mut registrations = HashMap::new();
+        for (id, record) in backend.load_all_webhooks()? {
+            match serde_json::from_value::<Webhook>(record) {
+                Ok(webhook) => {
+                    let stats = Mutex::new(DeliveryStats::default());
+                    registrations.insert(id, Arc::new(Registration { webhook, stats }));
+                }
+                Err(e) => tracing::warn!("Skipping unreadable webhook {}: {}", id, e),
+            }
+        }
+
+        let webhooks = Arc::new(Self {
+            backend,
+            config,
+            registrations: RwLock::new(registrations),
+            http: Client::builder(TokioExecutor::new()).build_http(),
+            missed_commits: Counter::new(),
+        });
+
+        let mut commits = engine.subscribe_commits();
+        let dispatcher = Arc::clone(&webhooks);
+        tokio::spawn(async move {
+            loop {
+                match commits.recv().await {
+                    Ok(commit) => dispatcher.dispatch(&commit),
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("Webhook delivery fell behind and skipped {} commits", missed);
+                        dispatcher.missed_commits.add(missed);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(webhooks)
+    }
+
+    /// Registers and persists a webhook. Deliveries only go to `http://` URLs, which
+    /// `POST /admin/webhooks` checks before calling this.
+    pub fn register(&self, payload: RegisterWebhookPayload) -> Result<Webhook> {
+        let webhook = Webhook {
+            id: Uuid::new_v4(),
+            url: payload.url,
+            filter: payload.filter,
+            secret: payload.secret.unwrap_or_else(|| Uuid::new_v4().simple().to_string()),
+            created_at: Utc::now(),
+        };
+        let record = serde_json::to_value(&webhook).map_err(std::io::Error::from)?;
+        self.backend.store_webhook(&webhook.id, &record)?;
+        let stats = Mutex::new(DeliveryStats::default());
+        self.write()
+            .insert(webhook.id, Arc::new(Registration { webhook: webhook.clone(), stats }));
+        Ok(webhook)
+    }
+
+    /// Removes a webhook. Deliveries already under way are not retried again.
+    /// Returns false if there was no such webhook.
+    pub fn remove(&self, id: &Uuid) -> Result<bool> {
+        if self.write().remove(id).is_none() {
+            return Ok(false);
+        }
+        self.backend.delete_webhook(id)?;
+        Ok(true)
+    }
+
+    /// Every registered webhook with its delivery stats, oldest first.
+    pub fn list(&self) -> Vec<WebhookSummary> {
+        let mut summaries: Vec<WebhookSummary> = self
+            .read()
+            .values()
+            .map(|registration| WebhookSummary {
+                id: registration.webhook.id,
+                url: registration.webhook.url.clone(),
+                filter: registration.webhook.filter.clone(),
+                created_at: registration.webhook.created_at,
+                stats: registration.stats.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            })
+            .collect();
+        summaries.sort_by_key(|summary| (summary.created_at, summary.id));
+        summaries
+    }
+
+    /// Commits skipped because the delivery task fell too far behind the commit channel.
+    pub fn missed_commits(&self) -> u64 {
+        self.missed_commits.get()
+    }
+
+    fn dispatch(self: &Arc<Self>, commit: &CommitEvent) {
+        let events = WebhookEvent::from_commit(commit);
+        for registration in self.read().values() {
+            let matching: Vec<WebhookEvent> = events
+                .iter()
+                .filter(|event| registration.webhook.filter.matches(event))
+                .cloned()
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+
+            let delivery = WebhookDelivery {
+                webhook_id: registration.webhook.id,
+                transaction_id: commit.transaction_id,
+                committed_at: commit.committed_at,
+                metadata: commit.metadata.clone(),
+                events: matching,
+            };
+            match serde_json::to_vec(&delivery) {
+                Ok(body) => {
+                    tokio::spawn(Arc::clone(self).deliver(Arc::clone(registration), Bytes::from(body)));
+                }
+                Err(e) => tracing::warn!("Could not encode a delivery for webhook {}: {}", delivery.webhook_id, e),
+            }
+        }
+    }
+
+    async fn deliver(self: Arc<Self>, registration: Arc<Registration>, body: Bytes) {
+        let webhook = &registration.webhook;
+        let signature = format!("sha256={}", to_hex(&hmac_sha256(webhook.secret.as_bytes(), &body)));
+        let mut backoff = self.config.initial_backoff;
+
+        for attempt in 1..=self.config.max_attempts {
+            match self.post(webhook, body.clone(), &signature).await {
+                Ok(()) => {
+                    registration.record(|stats| stats.delivered += 1);
+                    return;
+                }
+                Err(e) => registration.record(|stats| {
+                    stats.failed_attempts += 1;
+                    stats.last_error = Some(e);
+                }),
+            }
+            if attempt == self.config.max_attempts {
+                break;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            if !self.read().contains_key(&webhook.id) {
+                return;
+            }
+        }
+
+        registration.record(|stats| stats.dead_lettered += 1);
+        tracing::warn!(
+            "Gave up on a delivery to webhook {} after {} attempts",
+            webhook.id,
+            self.config.max_attempts
+        );
+    }
+
+    async fn post(&self, webhook: &Webhook, body: Bytes, signature: &str) -> std::result::Result<(), String> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&webhook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .header(WEBHOOK_ID_HEADER, webhook.id.to_string())
+            .body(Full::new(body))
+            .map_err(|e| e.to_string())?;
+        let response = tokio::time::timeout(self.config.request_timeout, self.http.request(request))
+            .await
+            .map_err(|_| format!("Timed out after {:?}", self.config.request_timeout))?
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status()))
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<Uuid, Arc<Registration>>> {
+        self.registrations.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<Uuid, Arc<Registration>>> {
+        self.registrations.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The /admin/webhooks routes. They answer 404 unless the state carries `Webhooks`.
+pub(crate) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/webhooks", get(list_webhooks).post(register_webhook))
+        .route("/admin/webhooks/{id}", delete(remove_webhook))
+}
+
+fn enabled(state: &AppState) -> std::result::Result<Arc<Webhooks>, ApiError> {
+    state
+        .webhooks
+        .clone()
+        .ok_or_else(|| ApiError::not_found("Webhooks are not enabled on this server"))
+}
+
+/// Deliveries go out over plain HTTP, so only absolute `http://` URLs are accepted.
+fn check_url(url: &str) -> std::result::Result<(), ApiError> {
+    let invalid = |message: String| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, message);
+    let uri: Uri = url.parse().map_err(|e| invalid(format!("Invalid webhook URL: {}", e)))?;
+    if uri.scheme_str() != Some("http") || uri.host().is_none() {
+        return Err(invalid("Webhook URLs must be absolute http:// URLs".to_string()));
+    }
+    Ok(())
+}
+
+/// Registers a webhook: `POST /admin/webhooks`. The response is the only place its secret
+/// is shown.
+async fn register_webhook(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(payload): Json<RegisterWebhookPayload>,
+) -> std::result::Result<(StatusCode, Json<Webhook>), ApiError> {
+    caller.require_admin()?;
+    let webhooks = enabled(&state)?;
+    check_url(&payload.url)?;
+    let webhook = state.engine.worker_pool().run(move || webhooks.register(payload)).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+/// Lists webhooks and how their deliveries have gone: `GET /admin/webhooks`.
+async fn list_webhooks(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> std::result::Result<Json<Vec<WebhookSummary>>, ApiError> {
+    caller.require_admin()?;
+    Ok(Json(enabled(&state)?.list()))
+}
+
+/// Removes a webhook: `DELETE /admin/webhooks/{id}`.
+async fn remove_webhook(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<Uuid>,
+) -> std::result::Result<StatusCode, ApiError> {
+    caller.require_admin()?;
+    let webhooks = enabled(&state)?;
+    if state.engine.worker_pool().run(move || webhooks.remove(&id)).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found(format!("Webhook not found: {}", id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes::create_router;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum_test::TestServer;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+    use tokio::sync::mpsc;
+
+    fn quick_retries(max_attempts: u32) -> WebhookConfig {
+        WebhookConfig {
+            max_attempts,
+            initial_backoff: Duration::from_millis(10),
+            request_timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// An HTTP listener that refuses the first `failures` requests with a 500, then hands
+    /// every request's headers and body to the returned channel. Returns its URL.
+    async fn receiver(failures: usize) -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+        let (sender, received) = mpsc::unbounded_channel();
+        let refused = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                if refused.fetch_add(1, Ordering::SeqCst) < failures {
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+                sender.send((headers, body)).unwrap();
+                StatusCode::OK
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    /// Polls the listing until `done` holds for the first webhook's stats.
+    async fn wait_for_stats(server: &TestServer, done: impl Fn(&DeliveryStats) -> bool) -> DeliveryStats {
+        for _ in 0..200 {
+            let listed: Vec<WebhookSummary> = server.get("/admin/webhooks").await.json();
+            if done(&listed[0].stats) {
+                return listed[0].stats.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("webhook stats never settled");
+    }
+
+    #[tokio::test]
+    async fn test_matching_changes_are_delivered_signed_after_a_retry() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let webhooks = Webhooks::start(&engine, quick_retries(3)).unwrap();
+        let server = TestServer::new(create_router(
+            AppState::new(Arc::clone(&engine)).with_webhooks(webhooks),
+        ))
+        .unwrap();
+        let (url, mut received) = receiver(1).await;
+
+        let response = server
+            .post("/admin/webhooks")
+            .json(&json!({
+                "url": url,
+                "filter": {"entities": ["concept"], "label": "Person"},
+                "secret": "s3cret"
+            }))
+            .await;
+        response.assert_status(StatusCode::CREATED);
+        let webhook: Webhook = response.json();
+        assert_eq!(webhook.secret, "s3cret");
+
+        // Only the labelled concept passes the filter; the robot and the edge don't.
+        let robot = engine.store(json!({"labels": ["Robot"], "name": "R2"})).await.unwrap();
+        let ada = engine.store(json!({"labels": ["Person"], "name": "Ada"})).await.unwrap();
+        engine.relate(ada, "built".to_string(), robot).await.unwrap();
+
+        let (headers, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let expected = format!("sha256={}", to_hex(&hmac_sha256(b"s3cret", &body)));
+        assert_eq!(headers[SIGNATURE_HEADER], expected.as_str());
+        assert_eq!(headers[WEBHOOK_ID_HEADER], webhook.id.to_string().as_str());
+        let delivery: WebhookDelivery = serde_json::from_slice(&body).unwrap();
+        assert_eq!(delivery.webhook_id, webhook.id);
+        assert_eq!(delivery.events.len(), 1);
+        assert_eq!(delivery.events[0].id, ada);
+        assert_eq!(delivery.events[0].change, ChangeKind::Created);
+        assert_eq!(delivery.events[0].data.as_ref().unwrap()["name"], "Ada");
+
+        let stats = wait_for_stats(&server, |stats| stats.delivered == 1).await;
+        assert_eq!((stats.failed_attempts, stats.dead_lettered), (1, 0));
+        assert_eq!(stats.last_error.as_deref(), Some("HTTP 500 Internal Server Error"));
+        assert!(received.try_recv().is_err());
+
+        // Listings never show the secret.
+        let listed = server.get("/admin/webhooks").await.json::<Value>();
+        assert!(listed[0].get("secret").is_none());
+
+        server
+            .delete(&format!("/admin/webhooks/{}", webhook.id))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        assert!(server.get("/admin/webhooks").await.json::<Vec<WebhookSummary>>().is_empty());
+        server
+            .delete(&format!("/admin/webhooks/{}", webhook.id))
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_undeliverable_changes_are_dead_lettered_and_registrations_persist() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+        let server = TestServer::new(create_router(
+            AppState::new(Arc::clone(&engine)).with_webhooks(Webhooks::start(&engine, quick_retries(2)).unwrap()),
+        ))
+        .unwrap();
+
+        // Nothing listens on a port we bound and let go of.
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        server
+            .post("/admin/webhooks")
+            .json(&json!({"url": format!("http://{}/hook", closed)}))
+            .await
+            .assert_status(StatusCode::CREATED);
+        engine.store(json!({"name": "Lost"})).await.unwrap();
+
+        let stats = wait_for_stats(&server, |stats| stats.dead_lettered == 1).await;
+        assert_eq!((stats.delivered, stats.failed_attempts), (0, 2));
+
+        // Registrations come back when webhooks start again.
+        let restarted = Webhooks::start(&engine, WebhookConfig::default()).unwrap();
+        assert_eq!(restarted.list().len(), 1);
+
+        server
+            .post("/admin/webhooks")
+            .json(&json!({"url": "https://example.com/hook"}))
+            .await
+            .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        let disabled = TestServer::new(create_router(AppState::new(engine))).unwrap();
+        disabled.get("/admin/webhooks").await.assert_status(StatusCode::NOT_FOUND);
+    }
+}
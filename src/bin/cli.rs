@@ -0,0 +1,15 @@
+use mnemonic_core::cli::{self, CliError};
+use serde_json::json;
+
+#[tokio::main]
+async fn main() {
+    let mut stdout = std::io::stdout().lock();
+    match cli::run(std::env::args_os(), &mut stdout).await {
+        Ok(()) => {}
+        Err(CliError::Usage(err)) => err.exit(),
+        Err(err) => {
+            eprintln!("{}", json!({ "error": err.to_string() }));
+            std::process::exit(1);
+        }
+    }
+}
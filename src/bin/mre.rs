@@ -1,9 +1,8 @@
-use mnemonic_core::api::routes::{AppState, create_router};
-use mnemonic_core::graph::GraphEngine;
-use std::net::SocketAddr;
-use std::path::Path;
+use mnemonic_core::api::config::{ConfigError, MnemonicConfig};
+use mnemonic_core::api::routes::{AppState, create_app};
+use mnemonic_core::graph::{EngineConfig, GraphEngine};
+use mnemonic_core::storage::{EncryptionKey, StorageOptions};
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
 
 #[tokio::main]
 async fn main() {
@@ -12,27 +11,56 @@ async fn main() {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    // Read --config and the MNEMONIC_* variables, and stop before touching anything if the
+    // deployment is misconfigured.
+    let settings = match MnemonicConfig::load(std::env::args_os()) {
+        Ok(settings) => settings,
+        Err(ConfigError::Usage(err)) => err.exit(),
+        Err(err) => {
+            eprintln!("mre: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let fixture = settings.seed_fixture().unwrap_or_else(|err| {
+        eprintln!("mre: {}", err);
+        std::process::exit(2);
+    });
+
     // Initialize our GraphEngine (the heart of our application)
-    let db_path = Path::new("./mre_data");
-    let engine = Arc::new(GraphEngine::new(db_path).expect("Failed to create GraphEngine"));
-    // Await the seed function to ensure it completes before the server starts listening.
-engine.seed_if_empty().await.expect("Failed to seed the database");
+    // Concept data is encrypted at rest when MNEMONIC_ENCRYPTION_KEY holds a key.
+    let storage = StorageOptions {
+        encryption: EncryptionKey::from_env().expect("Invalid encryption key"),
+        ..Default::default()
+    };
+    let config = EngineConfig { storage, ..Default::default() };
+    let engine =
+        Arc::new(GraphEngine::with_config(&settings.data_dir, config).expect("Failed to create GraphEngine"));
+    // Seed a fresh database before the server starts listening, unless MNEMONIC_SEED=off.
+    if let Some(fixture) = fixture {
+        let report = engine.seed_if_empty(fixture).await.expect("Failed to seed the database");
+        tracing::info!(
+            "Seeded {} concepts and {} relationships",
+            report.concepts_created,
+            report.relationships_created
+        );
+    }
 
     // Create our application state
-    let app_state = AppState {
-        engine: Arc::clone(&engine),
-    };
-    // Create the router from our api module.
-    // Allow requests from any origin
-    let cors = CorsLayer::new()
-        .allow_origin(Any) // Allow any origin
-        .allow_methods(Any) // Allow any HTTP method (GET, POST, etc.)
-        .allow_headers(Any); // Allow any HTTP headers
-    let app = create_router(app_state).layer(cors);
+    let app_state = AppState::new(Arc::clone(&engine))
+        .with_request_timeout(settings.request_timeout)
+        .with_scan_budget(settings.scan_budget);
+    // Deliver committed changes to whatever /admin/webhooks registers.
+    #[cfg(feature = "webhooks")]
+    let app_state = app_state.with_webhooks(
+        mnemonic_core::api::webhooks::Webhooks::start(&engine, Default::default())
+            .expect("Failed to start webhook delivery"),
+    );
+    // Create the app from our api module: the API under /api and, unless --no-ui, the explorer
+    // at /. Browsers are only answered from the configured origins.
+    let app = create_app(app_state, settings.ui).layer(settings.cors_layer());
 
-    // Define the network address to run our server on.
-    // 0.0.0.0 is important for Docker/Codespaces. 8080 is the port.
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    // The network address to run our server on; 0.0.0.0:8080 unless configured otherwise.
+    let addr = settings.addr;
     tracing::info!("Server listening on {}", addr);
 
     // This is the magic line. It creates the server and tells it to
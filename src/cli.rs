@@ -0,0 +1,265 @@
+// mnemonic-cli: inspects and administers a database directly, without the HTTP server.
+// The binary in src/bin/cli.rs only hands its arguments and stdout to `run`.
+
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::error::MnemonicError;
+use crate::graph::{CheckDepth, EngineConfig, GraphEngine, RetentionPolicy};
+use crate::storage::{EncryptionKey, StorageOptions};
+use crate::types::relationship::Relationship;
+
+/// Where the database lives when neither `--db` nor `MRE_DATA_PATH` says otherwise.
+pub const DEFAULT_DB_PATH: &str = "./mre_data";
+const DB_PATH_ENV: &str = "MRE_DATA_PATH";
+
+/// Commands that change the database, and so need `--write`.
+const WRITING_COMMANDS: [&str; 5] = ["import", "import-neo4j", "prune", "rebuild", "migrate"];
+
+/// Why a CLI invocation failed.
+#[derive(Debug, Error)]
+pub enum CliError {
+    /// Bad arguments, or a request for help; `clap::Error::exit` prints it appropriately.
+    #[error(transparent)]
+    Usage(#[from] clap::Error),
+    #[error(transparent)]
+    Engine(#[from] MnemonicError),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// The argument parser, exposed so its help text can be checked or embedded.
+pub fn command() -> Command {
+    let id = || {
+        Arg::new("id")
+            .required(true)
+            .value_parser(value_parser!(Uuid))
+            .help("Concept ID")
+    };
+    Command::new("mnemonic-cli")
+        .about("Inspect and administer a mnemonic database without running the server")
+        .subcommand_required(true)
+        .arg(
+            Arg::new("db")
+                .long("db")
+                .value_name("PATH")
+                .global(true)
+                .help("Database directory [default: $MRE_DATA_PATH, then ./mre_data]"),
+        )
+        .arg(
+            Arg::new("write")
+                .long("write")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Open the database writable; import, import-neo4j, prune, rebuild and migrate need this"),
+        )
+        .arg(
+            Arg::new("pretty")
+                .long("pretty")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Pretty-print the JSON output"),
+        )
+        .subcommand(Command::new("stats").about("Count concepts, relationships and versions"))
+        .subcommand(Command::new("get-concept").about("Show a concept").arg(id()))
+        .subcommand(Command::new("history").about("List every version of a concept").arg(id()))
+        .subcommand(
+            Command::new("neighbors")
+                .about("List the active relationships of a concept")
+                .arg(id())
+                .arg(
+                    Arg::new("direction")
+                        .long("direction")
+                        .value_parser(["outgoing", "incoming", "both"])
+                        .default_value("both"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the active graph to a file")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["jsonl", "dot"])
+                        .default_value("jsonl"),
+                )
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("File to write, or - for standard output"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Load a JSONL export in one transaction")
+                .arg(Arg::new("path").required(true).value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("import-neo4j")
+                .about("Load a Neo4j CSV export in one transaction, reporting the rows left out")
+                .arg(
+                    Arg::new("nodes")
+                        .long("nodes")
+                        .value_name("FILE")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("A node file with an :ID column; repeat for more"),
+                )
+                .arg(
+                    Arg::new("relationships")
+                        .long("relationships")
+                        .value_name("FILE")
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("A relationship file with :START_ID, :END_ID and :TYPE columns; repeat for more"),
+                ),
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Drop versions superseded more than N days ago")
+                .arg(
+                    Arg::new("keep-days")
+                        .long("keep-days")
+                        .required(true)
+                        .value_parser(value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check disk, memory and the indexes against each other")
+                .arg(
+                    Arg::new("depth")
+                        .long("depth")
+                        .value_parser(["quick", "standard", "deep"])
+                        .default_value("standard"),
+                ),
+        )
+        .subcommand(Command::new("rebuild").about("Regenerate every index from the version history"))
+        .subcommand(Command::new("migrate").about("Rewrite records stored in older formats in the latest one"))
+}
+
+/// Parses `args` (program name first), runs the command against the database, and writes
+/// its result to `out` as JSON.
+pub async fn run<I, T>(args: I, out: &mut impl Write) -> Result<(), CliError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let matches = command().try_get_matches_from(args)?;
+    let Some((name, sub)) = matches.subcommand() else {
+        unreachable!("clap requires a subcommand");
+    };
+    let writable = matches.get_flag("write");
+    if WRITING_COMMANDS.contains(&name) && !writable {
+        return Err(CliError::Invalid(format!("{} changes the database; pass --write", name)));
+    }
+
+    let path = matches
+        .get_one::<String>("db")
+        .cloned()
+        .or_else(|| std::env::var(DB_PATH_ENV).ok())
+        .unwrap_or_else(|| DEFAULT_DB_PATH.to_string());
+    let config = EngineConfig {
+        storage: StorageOptions {
+            read_only: !writable,
+            // An encrypted database opens with the key in $MNEMONIC_ENCRYPTION_KEY.
+            encryption: EncryptionKey::from_env()?,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let engine = GraphEngine::with_config(path.as_ref(), config)?;
+    let pretty = matches.get_flag("pretty");
+
+    match name {
+        "stats" => emit(out, &engine.stats().await?, pretty),
+        "get-concept" => {
+            let id = concept_id(sub);
+            let concept = engine.get_concept(id).await?.ok_or(MnemonicError::ConceptNotFound(id))?;
+            emit(out, &concept, pretty)
+        }
+        "history" => emit(out, &engine.concept_history(concept_id(sub)).await?, pretty),
+        "neighbors" => {
+            let id = concept_id(sub);
+            let direction = sub.get_one::<String>("direction").map(String::as_str);
+            let mut relationships: Vec<Relationship> = Vec::new();
+            if direction != Some("incoming") {
+                relationships.extend(engine.retrieve_by_source(id).await?);
+            }
+            if direction != Some("outgoing") {
+                relationships.extend(engine.retrieve_by_target(id).await?);
+            }
+            emit(out, &relationships, pretty)
+        }
+        "export" => {
+            let snapshot = engine.snapshot().await?;
+            let dot = sub.get_one::<String>("format").is_some_and(|format| format == "dot");
+            let path = sub.get_one::<PathBuf>("path").unwrap();
+            let write = |writer: &mut dyn Write| {
+                if dot {
+                    snapshot.write_dot(writer)
+                } else {
+                    snapshot.write_jsonl(writer)
+                }
+            };
+            // On standard output the export is the output; there's no room for a report.
+            if path.as_os_str() == "-" {
+                write(out)?;
+                return Ok(());
+            }
+            let report = write(&mut BufWriter::new(File::create(path).map_err(MnemonicError::from)?))?;
+            emit(out, &report, pretty)
+        }
+        "import" => {
+            let path = sub.get_one::<PathBuf>("path").unwrap();
+            let reader = BufReader::new(File::open(path).map_err(MnemonicError::from)?);
+            emit(out, &engine.import_jsonl(reader).await?, pretty)
+        }
+        "import-neo4j" => {
+            let files = |name: &str| sub.get_many::<PathBuf>(name).into_iter().flatten().cloned().collect();
+            emit(out, &engine.import_neo4j_csv(files("nodes"), files("relationships")).await?, pretty)
+        }
+        "prune" => {
+            let days = *sub.get_one::<u64>("keep-days").unwrap();
+            let policy = RetentionPolicy {
+                min_age_secs: Some(days.saturating_mul(24 * 60 * 60)),
+                ..Default::default()
+            };
+            emit(out, &engine.prune_versions(policy).await?, pretty)
+        }
+        "verify" => {
+            let depth = match sub.get_one::<String>("depth").map(String::as_str) {
+                Some("quick") => CheckDepth::Quick,
+                Some("deep") => CheckDepth::Deep,
+                _ => CheckDepth::Standard,
+            };
+            emit(out, &engine.check(depth).await?, pretty)
+        }
+        "rebuild" => emit(out, &engine.rebuild_derived_state().await?, pretty),
+        "migrate" => emit(out, &engine.backend().migrate_records()?, pretty),
+        other => unreachable!("unknown subcommand {}", other),
+    }
+}
+
+fn concept_id(matches: &ArgMatches) -> Uuid {
+    *matches.get_one::<Uuid>("id").expect("id is required")
+}
+
+fn emit(out: &mut impl Write, value: &impl Serialize, pretty: bool) -> Result<(), CliError> {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+    .map_err(|err| MnemonicError::Io(err.into()))?;
+    writeln!(out, "{}", json).map_err(MnemonicError::from)?;
+    Ok(())
+}
@@ -0,0 +1,545 @@
+// A typed async client for the HTTP API. Bodies are the `api::types` structs the server's
+// handlers use, so a route change that breaks the client breaks the build.
+
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::{CONTENT_TYPE, HeaderValue};
+use hyper::{Method, Request, StatusCode, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::api::auth::API_KEY_HEADER;
+use crate::api::routes::{API_PREFIX, API_VERSION};
+use crate::api::types::{
+    ActivityOptions, BatchPayload, BatchResponse, ChangesOptions, CheckOptions, ConceptHistoryEntry, CreateConceptPayload, CreateConceptResponse,
+    DeleteConceptOptions, DeleteWherePayload, DegreeOptions, DegreeResponse, GraphData, GraphDeltaOptions, GraphDeltaResponse, GraphOptions, GraphShape, HistoryOptions,
+    HistoryPage, MetaResponse, ReadinessResponse, NeighborhoodOptions, PathOptions,
+    RelatePayload, RelateResponse, RelationshipHistoryEntry, RelationshipOptions, RelationshipResponse, SnapshotOptions, StorageStatsOptions, TraversalResponse,
+};
+use crate::error::{BatchItemError, ConflictInfo, MnemonicError};
+use crate::graph::{
+    ActivityBucket, AuditPage, AuditQuery, BulkDeleteOptions, BulkDeleteReport, ChangeSource, CheckDepth, CheckReport, CommitEvent, DeleteReport, GraphStats, PruneReport, QueryResult, ReadSnapshot, RebuildReport,
+    RetentionPolicy, SnapshotToken, TransactionSummary,
+};
+use crate::storage::StorageStats;
+use crate::types::concept::{Concept, ConceptId, ConceptKind};
+use crate::types::query::{ConceptListOptions, ConceptPage, Direction, Filter};
+use crate::types::relationship::{RelationType, RelationshipId, RelationshipTypeUsage};
+use crate::types::schema::SchemaViolation;
+
+/// A failed API call. Statuses the server gives structured bodies keep their details, so
+/// callers can retry conflicts or fix the named batch items.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Invalid request URL {0}")]
+    InvalidUrl(String),
+    #[error("Request failed: {0}")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Could not decode the response: {0}")]
+    Decode(#[from] serde_json::Error),
+    /// 401: the API key is missing or unknown.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    /// 403: the endpoint needs an admin API key.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    /// 409 from a commit: another transaction got there first.
+    #[error("{message}")]
+    Conflict { message: String, conflicts: Vec<ConflictInfo> },
+    /// 409 from pruning: these transactions still read the versions.
+    #[error("{message}")]
+    PruneBlocked { message: String, transactions: Vec<Uuid> },
+    /// 409 from a relationship type's cardinality: this relationship already holds the slot.
+    #[error("{message}")]
+    CardinalityViolation { message: String, existing: RelationshipId },
+    /// 409 from deleting a concept without cascade: these relationships still touch it.
+    #[error("{message}")]
+    ConceptReferenced { message: String, relationships: Vec<RelationshipId> },
+    /// 422 from a batch endpoint: these items were rejected.
+    #[error("{message}")]
+    InvalidBatch { message: String, items: Vec<BatchItemError> },
+    /// 422 from a write whose concepts break their labels' schemas.
+    #[error("{message}")]
+    SchemaViolation { message: String, violations: Vec<SchemaViolation> },
+    /// 422 from an acyclic relationship type: the edge would have closed this cycle.
+    #[error("{message}")]
+    CycleDetected { message: String, cycle: Vec<Uuid> },
+    /// 422 in strict mode: the relationship type isn't registered, but these are.
+    #[error("{message}")]
+    UnknownRelationshipType { message: String, suggestions: Vec<String> },
+    /// 422 from `/query`: the clause at this path is malformed.
+    #[error("{message}")]
+    InvalidQuery { message: String, path: String },
+    /// Any other unsuccessful status.
+    #[error("HTTP {status}: {message}")]
+    Status { status: u16, message: String },
+}
+
+impl ClientError {
+    /// Turns an unsuccessful response back into the error the server reported.
+    fn from_response(status: StatusCode, body: &[u8]) -> Self {
+        let body: Value = serde_json::from_slice(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()));
+        let message = body["error"]
+            .as_str()
+            .or(body.as_str())
+            .unwrap_or_default()
+            .to_string();
+        fn detail<T: DeserializeOwned>(body: &Value, field: &str) -> Option<T> {
+            serde_json::from_value(body.get(field)?.clone()).ok()
+        }
+
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized(message),
+            StatusCode::FORBIDDEN => Self::Forbidden(message),
+            StatusCode::NOT_FOUND => Self::NotFound(message),
+            StatusCode::CONFLICT => {
+                if let Some(conflicts) = detail(&body, "conflicts") {
+                    Self::Conflict { message, conflicts }
+                } else if let Some(transactions) = detail(&body, "transactions") {
+                    Self::PruneBlocked { message, transactions }
+                } else if let Some(existing) = detail(&body, "existing") {
+                    Self::CardinalityViolation { message, existing }
+                } else if let Some(relationships) = detail(&body, "relationships") {
+                    Self::ConceptReferenced { message, relationships }
+                } else {
+                    Self::Status { status: status.as_u16(), message }
+                }
+            }
+            StatusCode::UNPROCESSABLE_ENTITY => {
+                if let Some(items) = detail(&body, "items") {
+                    Self::InvalidBatch { message, items }
+                } else if let Some(violations) = detail(&body, "violations") {
+                    Self::SchemaViolation { message, violations }
+                } else if let Some(cycle) = detail(&body, "cycle") {
+                    Self::CycleDetected { message, cycle }
+                } else if let Some(suggestions) = detail(&body, "suggestions") {
+                    Self::UnknownRelationshipType { message, suggestions }
+                } else if let Some(path) = detail(&body, "path") {
+                    Self::InvalidQuery { message, path }
+                } else {
+                    Self::Status { status: status.as_u16(), message }
+                }
+            }
+            _ => Self::Status { status: status.as_u16(), message },
+        }
+    }
+}
+
+pub type ClientResult<T> = std::result::Result<T, ClientError>;
+
+/// How long a replicating `MnemonicClient` waits before polling `/changes` again.
+pub const CHANGES_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Talks to a mnemonic server over plain HTTP, reusing pooled connections.
+/// Cloning is cheap and clones share the pool.
+#[derive(Clone)]
+pub struct MnemonicClient {
+    base_url: String,
+    api_key: Option<HeaderValue>,
+    http: Client<HttpConnector, Full<Bytes>>,
+}
+
+impl MnemonicClient {
+    /// A client for the server at `base_url` (e.g. `http://localhost:3000`), sending
+    /// `api_key` with every request when the server requires one. Requests go to the current
+    /// API version under `/api`, as `create_app` serves it: `GET /ping` is `/api/v1/ping`.
+    pub fn new(base_url: impl Into<String>, api_key: Option<String>) -> ClientResult<Self> {
+        let api_key = api_key
+            .map(|key| HeaderValue::try_from(key).map_err(|e| ClientError::Transport(e.into())))
+            .transpose()?;
+        Ok(Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key,
+            http: Client::builder(TokioExecutor::new()).build_http(),
+        })
+    }
+
+    /// `GET /ping`.
+    pub async fn ping(&self) -> ClientResult<String> {
+        let body = self.send(Method::GET, "/ping", None).await?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// `GET /meta`: the server's crate and API versions and its features.
+    pub async fn meta(&self) -> ClientResult<MetaResponse> {
+        self.get("/meta").await
+    }
+
+    /// `GET /readyz`: whether the server has finished warming up.
+    pub async fn readyz(&self) -> ClientResult<ReadinessResponse> {
+        self.get("/readyz").await
+    }
+
+    /// `POST /concepts`.
+    pub async fn create_concept(&self, data: Value) -> ClientResult<ConceptId> {
+        let response: CreateConceptResponse =
+            self.post("/concepts", &CreateConceptPayload { data: Some(data), expires_at: None, kind: None }).await?;
+        Ok(response.concept_id)
+    }
+
+    /// `POST /concepts` for an empty concept, one with no data.
+    pub async fn create_empty_concept(&self) -> ClientResult<ConceptId> {
+        let payload = CreateConceptPayload { data: None, expires_at: None, kind: Some(ConceptKind::Empty) };
+        let response: CreateConceptResponse = self.post("/concepts", &payload).await?;
+        Ok(response.concept_id)
+    }
+
+    /// `POST /concepts` for a concept that stops being active at `expires_at`.
+    pub async fn create_expiring_concept(&self, data: Value, expires_at: DateTime<Utc>) -> ClientResult<ConceptId> {
+        let payload = CreateConceptPayload { data: Some(data), expires_at: Some(expires_at), kind: None };
+        let response: CreateConceptResponse = self.post("/concepts", &payload).await?;
+        Ok(response.concept_id)
+    }
+
+    /// `POST /concepts/batch`: creates every concept or none.
+    pub async fn create_concepts(&self, data: Vec<Value>) -> ClientResult<Vec<ConceptId>> {
+        let items = data
+            .into_iter()
+            .map(|data| serde_json::to_value(CreateConceptPayload { data: Some(data), expires_at: None, kind: None }))
+            .collect::<Result<_, _>>()?;
+        let response: BatchResponse = self.post("/concepts/batch", &BatchPayload { items }).await?;
+        Ok(response.ids)
+    }
+
+    /// `GET /concepts/{id}`.
+    pub async fn get_concept(&self, id: ConceptId) -> ClientResult<Concept> {
+        self.get(&format!("/concepts/{}", id)).await
+    }
+
+    /// `GET /concepts`: one page of active concepts.
+    pub async fn list_concepts(&self, options: &ConceptListOptions) -> ClientResult<ConceptPage> {
+        self.get(&with_query("/concepts", options)?).await
+    }
+
+    /// `POST /snapshots`: a snapshot to read through with `get_concept_in_snapshot` and
+    /// `list_concepts_in_snapshot`, until it is released or left idle.
+    pub async fn open_snapshot(&self) -> ClientResult<ReadSnapshot> {
+        let body = self.send(Method::POST, "/snapshots", None).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// `DELETE /snapshots/{token}`; `NotFound` if it wasn't open.
+    pub async fn release_snapshot(&self, token: SnapshotToken) -> ClientResult<()> {
+        self.send(Method::DELETE, &format!("/snapshots/{}", token), None).await?;
+        Ok(())
+    }
+
+    /// `DELETE /concepts/{id}`: refused while relationships still touch the concept, unless the
+    /// server is configured for lenient deletes.
+    pub async fn delete_concept(&self, id: ConceptId) -> ClientResult<()> {
+        self.send(Method::DELETE, &format!("/concepts/{}", id), None).await.map(drop)
+    }
+
+    /// `DELETE /concepts/{id}?cascade=true`: deletes the concept and every relationship touching it.
+    pub async fn delete_concept_cascading(&self, id: ConceptId) -> ClientResult<DeleteReport> {
+        let path = with_query(&format!("/concepts/{}", id), &DeleteConceptOptions { cascade: true })?;
+        let body = self.send(Method::DELETE, &path, None).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// `GET /concepts/{id}?snapshot=`: the concept as it was when the snapshot was taken.
+    pub async fn get_concept_in_snapshot(&self, token: SnapshotToken, id: ConceptId) -> ClientResult<Concept> {
+        self.get(&with_query(&format!("/concepts/{}", id), &SnapshotOptions { snapshot: Some(token) })?).await
+    }
+
+    /// `GET /concepts?snapshot=`: one page of the concepts active when the snapshot was taken.
+    pub async fn list_concepts_in_snapshot(
+        &self,
+        token: SnapshotToken,
+        options: &ConceptListOptions,
+    ) -> ClientResult<ConceptPage> {
+        let path = with_query("/concepts", options)?;
+        let separator = if path.contains('?') { '&' } else { '?' };
+        self.get(&format!("{}{}snapshot={}", path, separator, token)).await
+    }
+
+    /// `GET /concepts/{id}/degree`.
+    pub async fn degree(&self, id: ConceptId, direction: Direction) -> ClientResult<u64> {
+        let path = with_query(&format!("/concepts/{}/degree", id), &DegreeOptions { direction })?;
+        let response: DegreeResponse = self.get(&path).await?;
+        Ok(response.degree)
+    }
+
+    /// `GET /concepts/{id}/history`: a page of its versions, newest first.
+    pub async fn concept_history(
+        &self,
+        id: ConceptId,
+        options: &HistoryOptions,
+    ) -> ClientResult<HistoryPage<ConceptHistoryEntry>> {
+        self.get(&with_query(&format!("/concepts/{}/history", id), options)?).await
+    }
+
+    /// `GET /relationships/{id}`, with its endpoint concepts if `options` expands them.
+    pub async fn relationship(&self, id: RelationshipId, options: &RelationshipOptions) -> ClientResult<RelationshipResponse> {
+        self.get(&with_query(&format!("/relationships/{}", id), options)?).await
+    }
+
+    /// `GET /relationships/{id}/history`: a page of its versions, newest first.
+    pub async fn relationship_history(
+        &self,
+        id: RelationshipId,
+        options: &HistoryOptions,
+    ) -> ClientResult<HistoryPage<RelationshipHistoryEntry>> {
+        self.get(&with_query(&format!("/relationships/{}/history", id), options)?).await
+    }
+
+    /// `GET /concepts/{id}/neighborhood`.
+    pub async fn neighborhood(&self, id: ConceptId, options: &NeighborhoodOptions) -> ClientResult<TraversalResponse> {
+        self.get(&with_query(&format!("/concepts/{}/neighborhood", id), options)?).await
+    }
+
+    /// `POST /query` with a query as `GraphQuery::parse` reads it.
+    pub async fn query(&self, query: &Value) -> ClientResult<QueryResult> {
+        self.post("/query", query).await
+    }
+
+    /// `GET /path`; `None` when there is no path, or either end isn't an active concept.
+    pub async fn path(&self, options: &PathOptions) -> ClientResult<Option<TraversalResponse>> {
+        match self.get(&with_query("/path", options)?).await {
+            Ok(path) => Ok(Some(path)),
+            Err(ClientError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `GET /concepts/aggregate`: active concepts counted per value of `group_by`.
+    pub async fn aggregate(&self, group_by: &str, filter: Option<&Filter>) -> ClientResult<HashMap<String, u64>> {
+        let mut params = vec![("group_by".to_string(), group_by.to_string())];
+        if let Some(filter) = filter {
+            params.extend(filter.equals.iter().map(|(path, value)| (format!("where.{}", path), value.clone())));
+            if let Some(predicate) = &filter.predicate {
+                params.push(("predicate".to_string(), serde_json::to_string(predicate)?));
+            }
+        }
+        self.get(&with_query("/concepts/aggregate", &params)?).await
+    }
+
+    /// `POST /relationships`.
+    pub async fn relate(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+    ) -> ClientResult<RelationshipId> {
+        let payload = RelatePayload { source, relationship_type: relationship_type.into(), target, directed: true };
+        let response: RelateResponse = self.post("/relationships", &payload).await?;
+        Ok(response.relationship_id)
+    }
+
+    /// `POST /relationships` for a symmetric relationship, which reads the same from either end.
+    pub async fn relate_undirected(
+        &self,
+        a: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        b: ConceptId,
+    ) -> ClientResult<RelationshipId> {
+        let payload = RelatePayload { source: a, relationship_type: relationship_type.into(), target: b, directed: false };
+        let response: RelateResponse = self.post("/relationships", &payload).await?;
+        Ok(response.relationship_id)
+    }
+
+    /// `POST /relationships/batch`: creates every relationship or none.
+    pub async fn relate_batch(
+        &self,
+        relationships: Vec<(ConceptId, RelationType, ConceptId)>,
+    ) -> ClientResult<Vec<RelationshipId>> {
+        let items = relationships
+            .into_iter()
+            .map(|(source, relationship_type, target)| {
+                serde_json::to_value(RelatePayload { source, relationship_type, target, directed: true })
+            })
+            .collect::<Result<_, _>>()?;
+        let response: BatchResponse = self.post("/relationships/batch", &BatchPayload { items }).await?;
+        Ok(response.ids)
+    }
+
+    /// `GET /graph`: every active node and edge, with concept data if asked for.
+    pub async fn graph(&self, include_data: bool) -> ClientResult<GraphData> {
+        self.get(&with_query("/graph", &GraphOptions { include_data, ..Default::default() })?).await
+    }
+
+    /// `GET /graph/delta`: what changed since `since`, the `as_of` of an earlier graph or delta.
+    pub async fn graph_delta(&self, since: DateTime<Utc>, include_data: bool) -> ClientResult<GraphDeltaResponse> {
+        let options = GraphDeltaOptions { since, include_data, shape: GraphShape::default() };
+        self.get(&with_query("/graph/delta", &options)?).await
+    }
+
+    /// `GET /stats`.
+    pub async fn stats(&self) -> ClientResult<GraphStats> {
+        self.get("/stats").await
+    }
+
+    /// `GET /stats/activity`: what was committed in each bucket of the window, oldest first.
+    pub async fn activity(&self, options: &ActivityOptions) -> ClientResult<Vec<ActivityBucket>> {
+        self.get(&with_query("/stats/activity", options)?).await
+    }
+
+    /// `GET /relationship-types`: every registered type with its live usage count.
+    pub async fn relationship_types(&self) -> ClientResult<Vec<RelationshipTypeUsage>> {
+        self.get("/relationship-types").await
+    }
+
+    /// `GET /audit`: one page of committed changes; needs an admin key.
+    pub async fn audit_log(&self, query: &AuditQuery) -> ClientResult<AuditPage> {
+        self.get(&with_query("/audit", query)?).await
+    }
+
+    /// `GET /changes`: up to `limit` commits after `after`, oldest first; needs an admin key.
+    pub async fn changes(&self, after: Option<DateTime<Utc>>, limit: Option<usize>) -> ClientResult<Vec<CommitEvent>> {
+        self.get(&with_query("/changes", &ChangesOptions { after, limit })?).await
+    }
+
+    /// `GET /admin/transactions`.
+    pub async fn active_transactions(&self) -> ClientResult<Vec<TransactionSummary>> {
+        self.get("/admin/transactions").await
+    }
+
+    /// `GET /admin/transactions/{id}`; `NotFound` once it has finished.
+    pub async fn active_transaction(&self, id: Uuid) -> ClientResult<TransactionSummary> {
+        self.get(&format!("/admin/transactions/{}", id)).await
+    }
+
+    /// `POST /admin/prune`; needs an admin key.
+    pub async fn prune_versions(&self, policy: &RetentionPolicy) -> ClientResult<PruneReport> {
+        self.post("/admin/prune", policy).await
+    }
+
+    /// `POST /admin/delete-where`; needs an admin key. A dry run unless `options` says otherwise.
+    pub async fn delete_where(&self, filter: Filter, options: BulkDeleteOptions) -> ClientResult<BulkDeleteReport> {
+        self.post("/admin/delete-where", &DeleteWherePayload { filter, options }).await
+    }
+
+    /// `POST /admin/compact`; needs an admin key.
+    pub async fn compact_storage(&self) -> ClientResult<()> {
+        self.send(Method::POST, "/admin/compact", None).await.map(drop)
+    }
+
+    /// `GET /admin/check`; needs an admin key.
+    pub async fn check(&self, depth: CheckDepth) -> ClientResult<CheckReport> {
+        self.get(&with_query("/admin/check", &CheckOptions { depth })?).await
+    }
+
+    /// `POST /admin/rebuild`; needs an admin key.
+    pub async fn rebuild_derived_state(&self) -> ClientResult<RebuildReport> {
+        let body = self.send(Method::POST, "/admin/rebuild", None).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// `GET /admin/storage`; needs an admin key.
+    pub async fn storage_stats(&self, exact: bool) -> ClientResult<StorageStats> {
+        self.get(&with_query("/admin/storage", &StorageStatsOptions { exact })?).await
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> ClientResult<T> {
+        let body = self.send(Method::GET, path, None).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn post<T: DeserializeOwned>(&self, path: &str, payload: &impl Serialize) -> ClientResult<T> {
+        let body = self.send(Method::POST, path, Some(serde_json::to_vec(payload)?)).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<Vec<u8>>) -> ClientResult<Bytes> {
+        let url = format!("{}{}/{}{}", self.base_url, API_PREFIX, API_VERSION, path);
+        let uri: Uri = url.parse().map_err(|_| ClientError::InvalidUrl(url))?;
+        let mut request = Request::builder().method(method).uri(uri);
+        if body.is_some() {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        if let Some(key) = &self.api_key {
+            request = request.header(API_KEY_HEADER, key);
+        }
+        let request = request
+            .body(Full::new(body.map(Bytes::from).unwrap_or_default()))
+            .map_err(|e| ClientError::Transport(e.into()))?;
+
+        let response = self.http.request(request).await.map_err(|e| ClientError::Transport(e.into()))?;
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| ClientError::Transport(e.into()))?
+            .to_bytes();
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(ClientError::from_response(status, &body))
+        }
+    }
+}
+
+/// Polls `/changes`, waiting `CHANGES_POLL_INTERVAL` between polls that find nothing new, so
+/// a follower engine can replicate from a server.
+impl ChangeSource for MnemonicClient {
+    async fn next_commits(&mut self, after: Option<DateTime<Utc>>) -> crate::Result<Option<Vec<CommitEvent>>> {
+        loop {
+            let commits = self
+                .changes(after, None)
+                .await
+                .map_err(|e| MnemonicError::Replication(e.to_string()))?;
+            if !commits.is_empty() {
+                return Ok(Some(commits));
+            }
+            tokio::time::sleep(CHANGES_POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn with_query(path: &str, query: &impl Serialize) -> ClientResult<String> {
+    let query = serde_urlencoded::to_string(query).map_err(|e| ClientError::Transport(e.into()))?;
+    Ok(if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, query)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConflictItem;
+    use chrono::Utc;
+    use serde_json::json;
+
+    #[test]
+    fn test_structured_errors_survive_the_round_trip() {
+        let concept_id = Uuid::new_v4();
+        let conflict = ConflictInfo {
+            item: ConflictItem::Concept(concept_id),
+            our_base_version: Some(1),
+            committed_version: 2,
+            committed_at: Utc::now(),
+            committed_by: Uuid::new_v4(),
+        };
+        let body = json!({"error": "conflict", "conflicts": [conflict.clone()]}).to_string();
+        match ClientError::from_response(StatusCode::CONFLICT, body.as_bytes()) {
+            ClientError::Conflict { conflicts, .. } => assert_eq!(conflicts, vec![conflict]),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        let body = json!({"error": "cycle", "cycle": [concept_id, concept_id]}).to_string();
+        assert!(matches!(
+            ClientError::from_response(StatusCode::UNPROCESSABLE_ENTITY, body.as_bytes()),
+            ClientError::CycleDetected { cycle, .. } if cycle == vec![concept_id, concept_id]
+        ));
+
+        match ClientError::from_response(StatusCode::BAD_GATEWAY, b"upstream down") {
+            ClientError::Status { status, message } => assert_eq!((status, message.as_str()), (502, "upstream down")),
+            other => panic!("expected a plain status, got {:?}", other),
+        }
+    }
+}
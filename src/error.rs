@@ -1,11 +1,24 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::types::relationship::Cardinality;
+use crate::types::schema::SchemaViolation;
+use crate::types::transaction::RejectedWrite;
+
 #[derive(Error, Debug)]
 pub enum MnemonicError {
+    #[cfg(feature = "server")]
     #[error("Storage error: {0}")]
     Storage(#[from] rocksdb::Error),
 
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[cfg(feature = "server")]
     #[error("Serialization error: {0}")]
     Serialization(#[from] Box<bincode::ErrorKind>),
 
@@ -18,11 +31,226 @@ pub enum MnemonicError {
     #[error("Transaction error: {0}")]
     Transaction(String),
 
-    #[error("Transaction conflict: {0}")]
-    TransactionConflict(String),
+    #[error("Transaction expired: {0}")]
+    TransactionExpired(Uuid),
+
+    #[error("Transaction conflict: {}", describe_conflicts(.0))]
+    TransactionConflict(Vec<ConflictInfo>),
+
+    #[error("Relationship {relationship_id} references concept {concept_id}, which is not active")]
+    DanglingRelationship {
+        relationship_id: Uuid,
+        concept_id: Uuid,
+    },
+
+    #[error("Concept {concept_id} is still referenced by {} active relationship(s)", relationships.len())]
+    ConceptReferenced {
+        concept_id: Uuid,
+        relationships: Vec<Uuid>,
+    },
+
+    #[error("Version {version} of {id} does not follow its latest version {latest}")]
+    VersionOutOfSequence { id: Uuid, version: u64, latest: u64 },
 
     #[error("Index error: {0}")]
     Index(String),
+
+    #[error("Internal error: {0}")]
+    Internal(String),
+
+    #[error("Batch rejected: {} invalid item(s)", .0.len())]
+    InvalidBatch(Vec<BatchItemError>),
+
+    #[error("Engine overloaded: every worker is busy and {queue_limit} operations are already queued")]
+    Overloaded { queue_limit: usize },
+
+    /// An operation ran past its `Deadline`, which allowed it this long.
+    #[error("Operation timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    /// An operation's `Deadline` was cancelled, e.g. because its caller went away.
+    #[error("Operation was cancelled")]
+    Cancelled,
+
+    /// `GraphEngine::close` has begun, or finished, on this engine.
+    #[error("Engine is closed and accepts no more work")]
+    EngineClosed,
+
+    #[error("The database at {} is already open in another engine or process", .0.display())]
+    DatabaseLocked(PathBuf),
+
+    #[error("Relationship type {relationship_type:?} would form a cycle through {}", describe_cycle(.cycle))]
+    CycleDetected {
+        relationship_type: String,
+        cycle: Vec<Uuid>,
+    },
+
+    #[error(
+        "Relationship {relationship_id} breaks the {cardinality:?} constraint on {relationship_type:?}: relationship {existing} is already active"
+    )]
+    CardinalityViolation {
+        relationship_type: String,
+        cardinality: Cardinality,
+        relationship_id: Uuid,
+        /// The active relationship the new one clashes with.
+        existing: Uuid,
+    },
+
+    #[error("Unknown relationship type {relationship_type:?}{}", describe_suggestions(.suggestions))]
+    UnknownRelationshipType {
+        relationship_type: String,
+        /// Registered types close enough to be what was meant, closest first.
+        suggestions: Vec<String>,
+    },
+
+    #[error("Schema violated: {}", describe_violations(.0))]
+    SchemaViolation(Vec<SchemaViolation>),
+
+    #[error("Invalid schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Invalid query at {path}: {message}")]
+    InvalidQuery { path: String, message: String },
+
+    #[error("Pruning blocked: {} active transaction(s) or snapshot(s) still read versions it would remove", .0.len())]
+    PruneBlocked(Vec<Uuid>),
+
+    #[error("Purge blocked: {} active transaction(s) or snapshot(s) could still read the concept", .0.len())]
+    PurgeBlocked(Vec<Uuid>),
+
+    #[error("Concept data is {size} bytes, over the limit of {limit}")]
+    PayloadTooLarge { size: usize, limit: usize },
+
+    #[error("Invalid concept data: {0}")]
+    InvalidData(String),
+
+    #[error("Changes since {since} can't be replayed: a purge erased history at {erased_at}")]
+    HistoryErased { since: DateTime<Utc>, erased_at: DateTime<Utc> },
+
+    #[error("Snapshot {0} not found; it was released or left idle too long")]
+    SnapshotNotFound(Uuid),
+
+    #[error("This engine is replicating from a leader and doesn't accept writes")]
+    ReadOnlyReplica,
+
+    #[error("Replication error: {0}")]
+    Replication(String),
+
+    /// A scan ran out of its `ScanBudget` where a partial answer would be wrong.
+    #[error("Scan budget exceeded after {items} items; narrow the request or raise the budget")]
+    ScanBudgetExceeded { items: usize },
+
+    /// An archive that can't be read, or can't be restored here.
+    #[error("Archive error: {0}")]
+    Archive(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    /// A concept read as a `ConceptEntity` lacks its label, or its data doesn't fit the type.
+    #[error("Concept {concept_id} is not a {label}: {reason}")]
+    EntityMismatch { concept_id: Uuid, label: String, reason: String },
+
+    /// A `WriteMode::Strict` commit inserted over an existing ID or updated a missing concept.
+    #[error("Writes rejected: {}", describe_rejected_writes(.0))]
+    RejectedWrites(Vec<RejectedWrite>),
+}
+
+#[cfg(feature = "server")]
+impl From<tokio::task::JoinError> for MnemonicError {
+    /// A blocking task that panicked or was cancelled is reported instead of propagated.
+    fn from(err: tokio::task::JoinError) -> Self {
+        if !err.is_panic() {
+            return Self::Internal("Background task was cancelled".to_string());
+        }
+        let panic = err.into_panic();
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Self::Internal(format!("Background task panicked: {}", message))
+    }
+}
+
+/// The graph item a conflicting write touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum ConflictItem {
+    Concept(Uuid),
+    Relationship(Uuid),
+}
+
+/// Describes one write that lost the "first committer wins" race.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflictInfo {
+    pub item: ConflictItem,
+    /// The version our transaction saw when it started, if the item existed then.
+    pub our_base_version: Option<u64>,
+    /// The version some other transaction committed after we started.
+    pub committed_version: u64,
+    pub committed_at: DateTime<Utc>,
+    pub committed_by: Uuid,
+}
+
+impl fmt::Display for ConflictInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.item {
+            ConflictItem::Concept(id) => write!(f, "concept {}", id)?,
+            ConflictItem::Relationship(id) => write!(f, "relationship {}", id)?,
+        }
+        write!(
+            f,
+            " (committed version {} by transaction {} at {}",
+            self.committed_version, self.committed_by, self.committed_at
+        )?;
+        match self.our_base_version {
+            Some(base) => write!(f, "; we started from version {})", base),
+            None => write!(f, "; it did not exist when we started)"),
+        }
+    }
+}
+
+/// Why one item of a batch was rejected. `index` is its position in the request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchItemError {
+    pub index: usize,
+    pub error: String,
+}
+
+fn describe_conflicts(conflicts: &[ConflictInfo]) -> String {
+    conflicts
+        .iter()
+        .map(ConflictInfo::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_violations(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(|v| format!("{} {}: {}", v.label, if v.path.is_empty() { "/" } else { &v.path }, v.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_rejected_writes(rejected: &[RejectedWrite]) -> String {
+    rejected.iter().map(RejectedWrite::to_string).collect::<Vec<_>>().join(", ")
+}
+
+fn describe_suggestions(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let quoted: Vec<String> = suggestions.iter().map(|s| format!("{:?}", s)).collect();
+    format!("; did you mean {}?", quoted.join(" or "))
+}
+
+fn describe_cycle(cycle: &[Uuid]) -> String {
+    let mut steps: Vec<String> = cycle.iter().map(Uuid::to_string).collect();
+    // Show the cycle closing on itself.
+    steps.extend(cycle.first().map(Uuid::to_string));
+    steps.join(" -> ")
 }
 
 // This creates a handy shortcut for our functions.
@@ -0,0 +1,738 @@
+// Whole-graph analysis over the adjacency index: connected components, reachability,
+// neighbourhoods, and cycles and orderings along a single relationship type.
+// Every walk keeps its own stack or queue, so long chains can't overflow the call stack.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use super::budget::ScanMeter;
+use super::deadline::Deadline;
+use super::engine::TraversalLimits;
+use super::versioning::{NeighborView, VersionStore};
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::ConceptId;
+use crate::types::query::Direction;
+use crate::types::relationship::{RelationType, RelationshipId, RelationshipVersion};
+
+/// Groups active concepts into components, largest first, each sorted by ID.
+/// `Direction::Both` ignores edge direction and gives weak components; either other
+/// direction follows edges as drawn and gives strongly connected components.
+/// Concepts with no edges come back as components of one.
+pub(crate) fn connected_components(
+    version_store: &VersionStore,
+    direction: Direction,
+    types: Option<&HashSet<RelationType>>,
+) -> Result<Vec<Vec<ConceptId>>> {
+    let concepts = version_store.active_concept_ids()?;
+    let position: HashMap<ConceptId, usize> =
+        concepts.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    let labels = version_store.with_neighbor_view(|view| {
+        let out_edges = edge_lists(view, &concepts, &position, Direction::Outgoing, types);
+        match direction {
+            Direction::Both => weak_labels(&out_edges),
+            Direction::Outgoing | Direction::Incoming => {
+                let in_edges = edge_lists(view, &concepts, &position, Direction::Incoming, types);
+                strong_labels(&out_edges, &in_edges)
+            }
+        }
+    });
+
+    let mut grouped: HashMap<usize, Vec<ConceptId>> = HashMap::new();
+    for (i, label) in labels.into_iter().enumerate() {
+        grouped.entry(label).or_default().push(concepts[i]);
+    }
+    let mut components: Vec<Vec<ConceptId>> = grouped.into_values().collect();
+    for component in &mut components {
+        component.sort();
+    }
+    components.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+    Ok(components)
+}
+
+/// Every active concept reachable from `start` in the given direction, `start` included.
+pub(crate) fn reachable_from(
+    version_store: &VersionStore,
+    start: ConceptId,
+    direction: Direction,
+    types: Option<&HashSet<RelationType>>,
+) -> Result<HashSet<ConceptId>> {
+    let active: HashSet<ConceptId> = version_store.active_concept_ids()?.into_iter().collect();
+    if !active.contains(&start) {
+        return Err(MnemonicError::ConceptNotFound(start));
+    }
+
+    Ok(version_store.with_neighbor_view(|view| {
+        let mut reached = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(concept_id) = queue.pop_front() {
+            for neighbor in view.neighbors(&concept_id, direction, types) {
+                // Edges left pointing at deleted concepts lead nowhere.
+                if active.contains(&neighbor) && reached.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        reached
+    }))
+}
+
+/// The active concepts within `depth` hops of any root, following edges either way, and
+/// the roots that aren't active concepts. Each concept is listed once, nearest roots first,
+/// however many neighbourhoods it falls in.
+pub(crate) fn neighborhood(
+    version_store: &VersionStore,
+    roots: &[ConceptId],
+    depth: u32,
+    types: Option<&HashSet<RelationType>>,
+) -> Result<(Vec<ConceptId>, Vec<ConceptId>)> {
+    let (collected, missing, _) =
+        bounded_neighborhood(version_store, roots, depth, types, usize::MAX, &Deadline::none(), &ScanMeter::unlimited())?;
+    Ok((collected, missing))
+}
+
+/// Like `neighborhood`, but stops once `max_concepts` concepts are collected, or once
+/// expanding another concept would overspend `meter`, and says whether it had to. Fails
+/// once `deadline` passes.
+pub(crate) fn bounded_neighborhood(
+    version_store: &VersionStore,
+    roots: &[ConceptId],
+    depth: u32,
+    types: Option<&HashSet<RelationType>>,
+    max_concepts: usize,
+    deadline: &Deadline,
+    meter: &ScanMeter,
+) -> Result<(Vec<ConceptId>, Vec<ConceptId>, bool)> {
+    let active: HashSet<ConceptId> = version_store.active_concept_ids()?.into_iter().collect();
+    let mut collected = Vec::new();
+    let mut missing = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    let mut truncated = false;
+    for &root in roots {
+        if !active.contains(&root) {
+            if !missing.contains(&root) {
+                missing.push(root);
+            }
+        } else if collected.len() == max_concepts {
+            truncated = true;
+        } else if seen.insert(root) {
+            collected.push(root);
+            queue.push_back((root, 0));
+        }
+    }
+
+    version_store.with_neighbor_view(|view| -> Result<()> {
+        'walk: while let Some((concept_id, hops)) = queue.pop_front() {
+            deadline.check()?;
+            if hops == depth {
+                continue;
+            }
+            if !meter.take() {
+                truncated = true;
+                break;
+            }
+            for neighbor in view.neighbors(&concept_id, Direction::Both, types) {
+                if active.contains(&neighbor) && !seen.contains(&neighbor) {
+                    if collected.len() == max_concepts {
+                        truncated = true;
+                        break 'walk;
+                    }
+                    seen.insert(neighbor);
+                    collected.push(neighbor);
+                    queue.push_back((neighbor, hops + 1));
+                }
+            }
+        }
+        Ok(())
+    })?;
+    Ok((collected, missing, truncated))
+}
+
+/// A path's concepts, both ends included, and the relationships between them in order.
+pub(crate) type PathSteps = (Vec<ConceptId>, Vec<RelationshipId>);
+
+/// A shortest path along outgoing edges (of the given types, if named) from `from` to `to`,
+/// at most `limits.max_depth` edges long: its concepts, both ends included, and the
+/// relationships between them in order. `None` if there is no such path among the first
+/// `limits.max_concepts` concepts the search reaches, or among those it could expand without
+/// overspending `meter`; the flag says whether either cut the search short. Fails once
+/// `deadline` passes.
+pub(crate) fn shortest_path(
+    version_store: &VersionStore,
+    from: ConceptId,
+    to: ConceptId,
+    limits: TraversalLimits,
+    types: Option<&HashSet<RelationType>>,
+    deadline: &Deadline,
+    meter: &ScanMeter,
+) -> Result<(Option<PathSteps>, bool)> {
+    let active: HashSet<ConceptId> = version_store.active_concept_ids()?.into_iter().collect();
+    for endpoint in [from, to] {
+        if !active.contains(&endpoint) {
+            return Err(MnemonicError::ConceptNotFound(endpoint));
+        }
+    }
+
+    version_store.with_neighbor_view(|view| {
+        let mut came_from: HashMap<ConceptId, (ConceptId, RelationshipId)> = HashMap::new();
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([(from, 0)]);
+        while let Some((concept_id, hops)) = queue.pop_front() {
+            deadline.check()?;
+            if concept_id == to {
+                let mut concepts = vec![to];
+                let mut relationships = Vec::new();
+                while let Some((previous, rel_id)) = came_from.get(concepts.last().unwrap()) {
+                    concepts.push(*previous);
+                    relationships.push(*rel_id);
+                }
+                concepts.reverse();
+                relationships.reverse();
+                return Ok((Some((concepts, relationships)), false));
+            }
+            if hops == limits.max_depth {
+                continue;
+            }
+            if !meter.take() {
+                return Ok((None, true));
+            }
+            for (rel_id, neighbor) in view.edges(&concept_id, Direction::Outgoing, types) {
+                if active.contains(&neighbor) && !seen.contains(&neighbor) {
+                    if seen.len() == limits.max_concepts {
+                        return Ok((None, true));
+                    }
+                    seen.insert(neighbor);
+                    came_from.insert(neighbor, (concept_id, rel_id));
+                    queue.push_back((neighbor, hops + 1));
+                }
+            }
+        }
+        Ok((None, false))
+    })
+}
+
+/// The active relationships with both ends among `concepts`, of the given types if named.
+pub(crate) fn relationships_among(
+    version_store: &VersionStore,
+    concepts: &[ConceptId],
+    types: Option<&HashSet<RelationType>>,
+) -> Vec<RelationshipVersion> {
+    let members: HashSet<ConceptId> = concepts.iter().copied().collect();
+    let members = &members;
+    version_store.with_neighbor_view(|view| {
+        concepts
+            .iter()
+            .flat_map(|concept_id| {
+                view.edges(concept_id, Direction::Outgoing, types)
+                    .into_iter()
+                    .filter_map(|(rel_id, _)| view.relationship(&rel_id))
+                    // An undirected relationship leaves both ends; it's listed from its source.
+                    .filter(move |version| version.source == *concept_id && members.contains(&version.target))
+            })
+            .cloned()
+            .collect()
+    })
+}
+
+/// One cycle of `relationship_type` edges between active concepts, in edge order, or `None`
+/// if those edges form a DAG. The last concept in the cycle leads back to the first.
+pub(crate) fn find_cycle(
+    version_store: &VersionStore,
+    relationship_type: &RelationType,
+) -> Result<Option<Vec<ConceptId>>> {
+    let (concepts, out_edges) = typed_graph(version_store, relationship_type)?;
+    Ok(cycle_in(&out_edges).map(|cycle| cycle.into_iter().map(|i| concepts[i]).collect()))
+}
+
+/// Orders the active concepts joined by `relationship_type` edges so every edge's source
+/// comes before its target; among unordered concepts, smaller IDs go first. Concepts no
+/// such edge touches are left out. Fails with the offending cycle if there is one.
+pub(crate) fn topological_order(
+    version_store: &VersionStore,
+    relationship_type: &RelationType,
+) -> Result<Vec<ConceptId>> {
+    let (concepts, out_edges) = typed_graph(version_store, relationship_type)?;
+    let mut in_degree = vec![0usize; concepts.len()];
+    for &neighbor in out_edges.iter().flatten() {
+        in_degree[neighbor] += 1;
+    }
+    let touched: Vec<bool> = (0..concepts.len())
+        .map(|i| in_degree[i] > 0 || !out_edges[i].is_empty())
+        .collect();
+
+    // Kahn's algorithm, taking the smallest ready ID each time so the order is stable.
+    let mut ready: BTreeSet<(ConceptId, usize)> = (0..concepts.len())
+        .filter(|&i| touched[i] && in_degree[i] == 0)
+        .map(|i| (concepts[i], i))
+        .collect();
+    let mut order = Vec::new();
+    while let Some((concept_id, node)) = ready.pop_first() {
+        order.push(concept_id);
+        for &neighbor in &out_edges[node] {
+            in_degree[neighbor] -= 1;
+            if in_degree[neighbor] == 0 {
+                ready.insert((concepts[neighbor], neighbor));
+            }
+        }
+    }
+
+    if order.len() < touched.iter().filter(|&&t| t).count() {
+        let cycle = cycle_in(&out_edges).unwrap_or_default();
+        return Err(MnemonicError::CycleDetected {
+            relationship_type: relationship_type.to_string(),
+            cycle: cycle.into_iter().map(|i| concepts[i]).collect(),
+        });
+    }
+    Ok(order)
+}
+
+/// A shortest path of `relationship_type` edges from `from` to `to`, both ends included, as
+/// the graph would be with the `added` edges drawn and the `removed` relationships gone.
+pub(crate) fn find_path(
+    version_store: &VersionStore,
+    from: ConceptId,
+    to: ConceptId,
+    relationship_type: &RelationType,
+    added: &[(ConceptId, ConceptId)],
+    removed: &HashSet<RelationshipId>,
+) -> Option<Vec<ConceptId>> {
+    let types = HashSet::from([relationship_type.clone()]);
+    let mut came_from: HashMap<ConceptId, ConceptId> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    let mut seen = HashSet::from([from]);
+
+    version_store.with_neighbor_view(|view| {
+        while let Some(concept_id) = queue.pop_front() {
+            if concept_id == to {
+                let mut path = vec![to];
+                while let Some(previous) = came_from.get(path.last().unwrap()) {
+                    path.push(*previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let existing = view
+                .edges(&concept_id, Direction::Outgoing, Some(&types))
+                .into_iter()
+                .filter(|(rel_id, _)| !removed.contains(rel_id))
+                .map(|(_, neighbor)| neighbor);
+            let pending = added
+                .iter()
+                .filter(|(source, _)| *source == concept_id)
+                .map(|(_, target)| *target);
+            for neighbor in existing.chain(pending) {
+                if seen.insert(neighbor) {
+                    came_from.insert(neighbor, concept_id);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        None
+    })
+}
+
+/// The active concepts and, per concept, its `relationship_type` out-edges as positions.
+fn typed_graph(
+    version_store: &VersionStore,
+    relationship_type: &RelationType,
+) -> Result<(Vec<ConceptId>, Vec<Vec<usize>>)> {
+    let mut concepts = version_store.active_concept_ids()?;
+    concepts.sort();
+    let position: HashMap<ConceptId, usize> =
+        concepts.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let types = HashSet::from([relationship_type.clone()]);
+    let out_edges = version_store.with_neighbor_view(|view| {
+        edge_lists(view, &concepts, &position, Direction::Outgoing, Some(&types))
+    });
+    Ok((concepts, out_edges))
+}
+
+/// Depth-first search for a back edge; the nodes on the stack from its target up form a cycle.
+fn cycle_in(out_edges: &[Vec<usize>]) -> Option<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        OnStack,
+        Done,
+    }
+
+    let mut marks = vec![Mark::Unvisited; out_edges.len()];
+    for root in 0..out_edges.len() {
+        if marks[root] != Mark::Unvisited {
+            continue;
+        }
+        marks[root] = Mark::OnStack;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            match out_edges[node].get(*next) {
+                Some(&neighbor) => {
+                    *next += 1;
+                    match marks[neighbor] {
+                        Mark::Unvisited => {
+                            marks[neighbor] = Mark::OnStack;
+                            stack.push((neighbor, 0));
+                        }
+                        Mark::OnStack => {
+                            let start = stack.iter().position(|&(n, _)| n == neighbor).unwrap();
+                            return Some(stack[start..].iter().map(|&(n, _)| n).collect());
+                        }
+                        Mark::Done => {}
+                    }
+                }
+                None => {
+                    marks[node] = Mark::Done;
+                    stack.pop();
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Each concept's neighbours in `direction`, as positions in `concepts`.
+fn edge_lists(
+    view: &NeighborView<'_>,
+    concepts: &[ConceptId],
+    position: &HashMap<ConceptId, usize>,
+    direction: Direction,
+    types: Option<&HashSet<RelationType>>,
+) -> Vec<Vec<usize>> {
+    concepts
+        .iter()
+        .map(|concept_id| {
+            view.neighbors(concept_id, direction, types)
+                .iter()
+                .filter_map(|neighbor| position.get(neighbor).copied())
+                .collect()
+        })
+        .collect()
+}
+
+/// Union-find over every edge; a node's label is its set's root.
+fn weak_labels(edges: &[Vec<usize>]) -> Vec<usize> {
+    let mut parent: Vec<usize> = (0..edges.len()).collect();
+
+    fn find(parent: &mut [usize], mut node: usize) -> usize {
+        while parent[node] != node {
+            // Path halving keeps the trees shallow without recursion.
+            parent[node] = parent[parent[node]];
+            node = parent[node];
+        }
+        node
+    }
+
+    for (node, neighbors) in edges.iter().enumerate() {
+        for &neighbor in neighbors {
+            let (a, b) = (find(&mut parent, node), find(&mut parent, neighbor));
+            if a != b {
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    (0..edges.len()).map(|node| find(&mut parent, node)).collect()
+}
+
+/// Kosaraju: order nodes by when a depth-first walk over `out_edges` finishes them, then
+/// flood `in_edges` in reverse of that order. Each flood is one strongly connected component.
+fn strong_labels(out_edges: &[Vec<usize>], in_edges: &[Vec<usize>]) -> Vec<usize> {
+    let count = out_edges.len();
+    let mut visited = vec![false; count];
+    let mut finished = Vec::with_capacity(count);
+    for root in 0..count {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        // Each frame is a node and how many of its edges have been followed so far.
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            match out_edges[node].get(*next) {
+                Some(&neighbor) => {
+                    *next += 1;
+                    if !visited[neighbor] {
+                        visited[neighbor] = true;
+                        stack.push((neighbor, 0));
+                    }
+                }
+                None => {
+                    finished.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let mut labels = vec![usize::MAX; count];
+    for &root in finished.iter().rev() {
+        if labels[root] != usize::MAX {
+            continue;
+        }
+        labels[root] = root;
+        let mut queue = VecDeque::from([root]);
+        while let Some(node) = queue.pop_front() {
+            for &neighbor in &in_edges[node] {
+                if labels[neighbor] == usize::MAX {
+                    labels[neighbor] = root;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::budget::ScanBudget;
+    use crate::types::concept::{ConceptData, ConceptVersion};
+    use crate::types::relationship::{Relationship, RelationshipVersion};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn concepts(store: &VersionStore, count: usize) -> Vec<ConceptId> {
+        (0..count)
+            .map(|_| {
+                let concept_id = Uuid::new_v4();
+                store
+                    .add_concept_version(ConceptVersion {
+                        concept_id,
+                        version: 1,
+                        data: ConceptData::Structured("{}".to_string().into()),
+                        created_at: Utc::now(),
+                        created_by: Uuid::new_v4(),
+                        deleted_at: None,
+                        deleted_by: None,
+                        expires_at: None,
+                        tags: Default::default(),
+                    })
+                    .unwrap();
+                concept_id
+            })
+            .collect()
+    }
+
+    fn relate(store: &VersionStore, source: ConceptId, relationship_type: &str, target: ConceptId) {
+        let rel = Relationship::new(source, relationship_type.to_string(), target);
+        store
+            .add_relationship_version(RelationshipVersion::from_relationship(&rel, Uuid::new_v4()))
+            .unwrap();
+    }
+
+    fn sorted(mut ids: Vec<ConceptId>) -> Vec<ConceptId> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_three_components_plus_isolated_nodes() {
+        let store = VersionStore::new();
+        let c = concepts(&store, 11);
+        // A cycle of four, a chain of three, a pair, and two concepts on their own.
+        relate(&store, c[0], "next", c[1]);
+        relate(&store, c[1], "next", c[2]);
+        relate(&store, c[2], "next", c[3]);
+        relate(&store, c[3], "next", c[0]);
+        relate(&store, c[4], "next", c[5]);
+        relate(&store, c[6], "cites", c[5]);
+        relate(&store, c[7], "next", c[8]);
+
+        let weak = connected_components(&store, Direction::Both, None).unwrap();
+        assert_eq!(
+            weak,
+            vec![
+                sorted(c[0..4].to_vec()),
+                sorted(c[4..7].to_vec()),
+                sorted(c[7..9].to_vec()),
+            ]
+            .into_iter()
+            .chain(sorted(c[9..11].to_vec()).into_iter().map(|id| vec![id]))
+            .collect::<Vec<_>>()
+        );
+
+        // Following direction, only the cycle holds together.
+        let strong = connected_components(&store, Direction::Outgoing, None).unwrap();
+        assert_eq!(strong.len(), 8);
+        assert_eq!(strong[0], sorted(c[0..4].to_vec()));
+        assert!(strong[1..].iter().all(|component| component.len() == 1));
+
+        // Without "cites", the chain splits in two.
+        let only_next = HashSet::from([RelationType::new("next")]);
+        let by_type = connected_components(&store, Direction::Both, Some(&only_next)).unwrap();
+        assert_eq!(by_type.len(), 6);
+        assert!(by_type.contains(&vec![c[6]]));
+    }
+
+    #[test]
+    fn test_reachability_follows_direction_and_types() {
+        let store = VersionStore::new();
+        let c = concepts(&store, 4);
+        relate(&store, c[0], "next", c[1]);
+        relate(&store, c[1], "cites", c[2]);
+        relate(&store, c[3], "next", c[0]);
+
+        let forward = reachable_from(&store, c[0], Direction::Outgoing, None).unwrap();
+        assert_eq!(forward, HashSet::from([c[0], c[1], c[2]]));
+        let backward = reachable_from(&store, c[0], Direction::Incoming, None).unwrap();
+        assert_eq!(backward, HashSet::from([c[0], c[3]]));
+        let only_next = HashSet::from([RelationType::new("next")]);
+        let typed = reachable_from(&store, c[0], Direction::Both, Some(&only_next)).unwrap();
+        assert_eq!(typed, HashSet::from([c[0], c[1], c[3]]));
+
+        assert!(matches!(
+            reachable_from(&store, Uuid::new_v4(), Direction::Both, None),
+            Err(MnemonicError::ConceptNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_cycles_and_topological_order_per_type() {
+        let store = VersionStore::new();
+        let c = sorted(concepts(&store, 5));
+        // c0 -> c1 -> c3, c0 -> c2 -> c3 as a diamond of "depends_on"; c4 is untouched.
+        relate(&store, c[0], "depends_on", c[1]);
+        relate(&store, c[0], "depends_on", c[2]);
+        relate(&store, c[1], "depends_on", c[3]);
+        relate(&store, c[2], "depends_on", c[3]);
+        // A cycle in another type doesn't count.
+        relate(&store, c[3], "mentions", c[0]);
+
+        let depends_on = RelationType::new("depends_on");
+        assert_eq!(find_cycle(&store, &depends_on).unwrap(), None);
+        assert_eq!(topological_order(&store, &depends_on).unwrap(), c[0..4].to_vec());
+        assert_eq!(find_cycle(&store, &RelationType::new("mentions")).unwrap(), None);
+        assert_eq!(
+            find_path(&store, c[0], c[3], &depends_on, &[], &HashSet::new()).unwrap().len(),
+            3
+        );
+
+        relate(&store, c[3], "depends_on", c[2]);
+        let cycle = find_cycle(&store, &depends_on).unwrap().unwrap();
+        assert_eq!(sorted(cycle.clone()), vec![c[2], c[3]]);
+        match topological_order(&store, &depends_on) {
+            Err(MnemonicError::CycleDetected { relationship_type, cycle: reported }) => {
+                assert_eq!(relationship_type, depends_on);
+                assert_eq!(sorted(reported), vec![c[2], c[3]]);
+            }
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_find_path_counts_pending_edges() {
+        let store = VersionStore::new();
+        let c = concepts(&store, 3);
+        relate(&store, c[0], "next", c[1]);
+        let next = RelationType::new("next");
+        let none_removed = HashSet::new();
+
+        assert_eq!(find_path(&store, c[0], c[2], &next, &[], &none_removed), None);
+        assert_eq!(
+            find_path(&store, c[0], c[2], &next, &[(c[1], c[2])], &none_removed),
+            Some(vec![c[0], c[1], c[2]])
+        );
+        let existing: HashSet<RelationshipId> = store
+            .outgoing_relationships(&c[0])
+            .unwrap()
+            .iter()
+            .map(|v| v.relationship_id)
+            .collect();
+        assert_eq!(find_path(&store, c[0], c[1], &next, &[], &existing), None);
+        assert_eq!(find_path(&store, c[1], c[1], &next, &[], &none_removed), Some(vec![c[1]]));
+    }
+
+    #[test]
+    fn test_neighborhood_dedupes_overlaps_and_reports_missing_roots() {
+        let store = VersionStore::new();
+        let c = concepts(&store, 6);
+        // c0 - c1 - c2 - c3 - c4 as a chain, with c5 off to the side.
+        relate(&store, c[0], "next", c[1]);
+        relate(&store, c[2], "next", c[1]);
+        relate(&store, c[2], "next", c[3]);
+        relate(&store, c[3], "cites", c[4]);
+        let ghost = Uuid::new_v4();
+
+        let (collected, missing) =
+            neighborhood(&store, &[c[0], c[2], ghost, c[0], ghost], 1, None).unwrap();
+        assert_eq!(sorted(collected.clone()), sorted(vec![c[0], c[1], c[2], c[3]]));
+        assert_eq!(missing, vec![ghost]);
+        // c3 -> c4 leaves the slice, so only the edges among collected concepts remain.
+        assert_eq!(relationships_among(&store, &collected, None).len(), 3);
+
+        let only_next = HashSet::from([RelationType::new("next")]);
+        let (typed, _) = neighborhood(&store, &[c[3]], 5, Some(&only_next)).unwrap();
+        assert_eq!(sorted(typed), sorted(vec![c[0], c[1], c[2], c[3]]));
+        let (roots_only, _) = neighborhood(&store, &[c[5]], 0, None).unwrap();
+        assert_eq!(roots_only, vec![c[5]]);
+    }
+
+    #[test]
+    fn test_shortest_path_and_bounded_neighborhood_respect_limits() {
+        let store = VersionStore::new();
+        let c = concepts(&store, 5);
+        // c0 -> c1 -> c2 -> c3, plus a shortcut c0 -> c3 of another type; c4 is alone.
+        relate(&store, c[0], "next", c[1]);
+        relate(&store, c[1], "next", c[2]);
+        relate(&store, c[2], "next", c[3]);
+        relate(&store, c[0], "skip", c[3]);
+
+        let unlimited = ScanMeter::unlimited();
+        let path = |to, max_depth, max_concepts, types| {
+            let limits = TraversalLimits { max_depth, max_concepts };
+            shortest_path(&store, c[0], to, limits, types, &Deadline::none(), &unlimited).unwrap()
+        };
+        let (found, truncated) = path(c[3], 10, usize::MAX, None);
+        let (concepts, relationships) = found.unwrap();
+        assert_eq!((concepts, relationships.len(), truncated), (vec![c[0], c[3]], 1, false));
+        let only_next = HashSet::from([RelationType::new("next")]);
+        let (found, _) = path(c[3], 10, usize::MAX, Some(&only_next));
+        assert_eq!(found.unwrap().0, c[0..4].to_vec());
+        assert_eq!(path(c[3], 2, usize::MAX, Some(&only_next)), (None, false));
+        assert_eq!(path(c[3], 10, 2, Some(&only_next)), (None, true));
+        // Edges are followed as drawn.
+        let any = TraversalLimits { max_depth: 10, max_concepts: usize::MAX };
+        assert!(shortest_path(&store, c[3], c[0], any, None, &Deadline::none(), &unlimited).unwrap().0.is_none());
+        assert_eq!(path(c[4], 10, usize::MAX, None), (None, false));
+        assert!(matches!(
+            shortest_path(&store, c[0], Uuid::new_v4(), any, None, &Deadline::none(), &unlimited),
+            Err(MnemonicError::ConceptNotFound(_))
+        ));
+
+        let neighborhood = |max_concepts, meter: &ScanMeter| {
+            bounded_neighborhood(&store, &[c[0]], 3, None, max_concepts, &Deadline::none(), meter).unwrap()
+        };
+        let (collected, _, truncated) = neighborhood(3, &unlimited);
+        assert_eq!((collected.len(), truncated), (3, true));
+        let (collected, _, truncated) = neighborhood(4, &unlimited);
+        assert_eq!((collected.len(), truncated), (4, false));
+
+        // Each concept expanded spends an item: c0 reaches c1 and c3, c1 reaches c2.
+        let (collected, _, truncated) = neighborhood(usize::MAX, &ScanBudget::items(2).start());
+        assert_eq!((collected.len(), truncated), (4, true));
+        let (found, truncated) =
+            shortest_path(&store, c[0], c[2], any, Some(&only_next), &Deadline::none(), &ScanBudget::items(1).start())
+                .unwrap();
+        assert_eq!((found, truncated), (None, true));
+    }
+
+    #[test]
+    fn test_long_chain_does_not_recurse() {
+        let store = VersionStore::new();
+        let c = concepts(&store, 50_000);
+        for pair in c.windows(2) {
+            relate(&store, pair[0], "next", pair[1]);
+        }
+
+        assert_eq!(reachable_from(&store, c[0], Direction::Outgoing, None).unwrap().len(), c.len());
+        assert_eq!(connected_components(&store, Direction::Both, None).unwrap().len(), 1);
+        assert_eq!(
+            connected_components(&store, Direction::Outgoing, None).unwrap().len(),
+            c.len()
+        );
+        assert_eq!(topological_order(&store, &RelationType::new("next")).unwrap(), c);
+        relate(&store, c[c.len() - 1], "next", c[0]);
+        assert_eq!(find_cycle(&store, &RelationType::new("next")).unwrap().unwrap().len(), c.len());
+    }
+}
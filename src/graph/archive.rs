@@ -0,0 +1,213 @@
+// Full-fidelity archives of a database in the `.mnar` format: every version still held, as it
+// was committed, with the version counters, transaction metadata and commit sequence that go
+// with them. A JSONL export carries only the graph as it stands; restoring an archive gives
+// back the whole history, so time-travel reads answer exactly as they did.
+//
+// An archive is JSON Lines of `ArchiveRecord`s, the header first.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+use uuid::Uuid;
+
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::{ConceptId, ConceptVersion};
+use crate::types::content::ContentHash;
+use crate::types::relationship::{RelationshipId, RelationshipVersion};
+use crate::types::transaction::TransactionMetadata;
+
+/// What the header of every `.mnar` archive calls its format.
+pub const ARCHIVE_FORMAT: &str = "mnar";
+
+/// The newest archive layout this build writes and reads.
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// One line of a `.mnar` archive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ArchiveRecord {
+    /// Always the first line.
+    Header {
+        format: String,
+        format_version: u32,
+        commit_sequence: u64,
+        exported_at: DateTime<Utc>,
+    },
+    ConceptVersion(ConceptVersion),
+    RelationshipVersion(RelationshipVersion),
+    /// The latest version number of a concept, which outlives pruned versions.
+    ConceptHead { concept_id: ConceptId, head: u64 },
+    RelationshipHead { relationship_id: RelationshipId, head: u64 },
+    /// Who committed a transaction and why, for transactions that said so.
+    Transaction { transaction_id: Uuid, metadata: TransactionMetadata },
+    /// A content hash claimed by a deduplicating store, in hex.
+    ContentHash { hash: String, concept_id: ConceptId },
+}
+
+/// Everything an archive holds, in the order it is written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Archive {
+    pub commit_sequence: u64,
+    pub concept_versions: Vec<ConceptVersion>,
+    pub relationship_versions: Vec<RelationshipVersion>,
+    pub concept_heads: Vec<(ConceptId, u64)>,
+    pub relationship_heads: Vec<(RelationshipId, u64)>,
+    pub transactions: Vec<(Uuid, TransactionMetadata)>,
+    pub content_hashes: Vec<(ContentHash, ConceptId)>,
+}
+
+/// What an archive export wrote, or an import restored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveReport {
+    pub concept_versions: usize,
+    pub relationship_versions: usize,
+    pub transactions: usize,
+    pub commit_sequence: u64,
+}
+
+impl Archive {
+    /// How much the archive holds.
+    pub fn report(&self) -> ArchiveReport {
+        ArchiveReport {
+            concept_versions: self.concept_versions.len(),
+            relationship_versions: self.relationship_versions.len(),
+            transactions: self.transactions.len(),
+            commit_sequence: self.commit_sequence,
+        }
+    }
+
+    /// The latest timestamp anywhere in the archive, if it holds any versions.
+    pub fn latest_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.concept_versions
+            .iter()
+            .flat_map(|v| [Some(v.created_at), v.deleted_at])
+            .chain(self.relationship_versions.iter().flat_map(|v| [Some(v.created_at), v.deleted_at]))
+            .flatten()
+            .max()
+    }
+
+    /// Writes the header, then one `ArchiveRecord` per line: versions before the counters
+    /// and metadata that refer to them.
+    pub fn write(&self, mut writer: impl Write) -> Result<ArchiveReport> {
+        let header = ArchiveRecord::Header {
+            format: ARCHIVE_FORMAT.to_string(),
+            format_version: ARCHIVE_FORMAT_VERSION,
+            commit_sequence: self.commit_sequence,
+            exported_at: Utc::now(),
+        };
+        let records = std::iter::once(header)
+            .chain(self.concept_versions.iter().cloned().map(ArchiveRecord::ConceptVersion))
+            .chain(self.relationship_versions.iter().cloned().map(ArchiveRecord::RelationshipVersion))
+            .chain(
+                self.concept_heads
+                    .iter()
+                    .map(|&(concept_id, head)| ArchiveRecord::ConceptHead { concept_id, head }),
+            )
+            .chain(
+                self.relationship_heads
+                    .iter()
+                    .map(|&(relationship_id, head)| ArchiveRecord::RelationshipHead { relationship_id, head }),
+            )
+            .chain(self.transactions.iter().map(|(transaction_id, metadata)| ArchiveRecord::Transaction {
+                transaction_id: *transaction_id,
+                metadata: metadata.clone(),
+            }))
+            .chain(self.content_hashes.iter().map(|&(hash, concept_id)| ArchiveRecord::ContentHash {
+                hash: format!("{:032x}", hash),
+                concept_id,
+            }));
+        for record in records {
+            serde_json::to_writer(&mut writer, &record).map_err(io::Error::from)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(self.report())
+    }
+
+    /// Reads back what `write` wrote. Blank lines are skipped; anything without a header this
+    /// build understands is refused before a single version is read.
+    pub fn read(reader: impl BufRead) -> Result<Self> {
+        let mut archive = Self::default();
+        let mut header_seen = false;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: ArchiveRecord = serde_json::from_str(&line).map_err(io::Error::from)?;
+            match (header_seen, record) {
+                (
+                    false,
+                    ArchiveRecord::Header {
+                        format,
+                        format_version,
+                        commit_sequence,
+                        ..
+                    },
+                ) => {
+                    if format != ARCHIVE_FORMAT || format_version > ARCHIVE_FORMAT_VERSION {
+                        return Err(MnemonicError::Archive(format!(
+                            "unsupported archive format {} version {}",
+                            format, format_version
+                        )));
+                    }
+                    archive.commit_sequence = commit_sequence;
+                    header_seen = true;
+                }
+                (false, _) => return Err(MnemonicError::Archive("the archive has no header".to_string())),
+                (true, ArchiveRecord::Header { .. }) => {
+                    return Err(MnemonicError::Archive("the archive has a second header".to_string()));
+                }
+                (true, ArchiveRecord::ConceptVersion(version)) => archive.concept_versions.push(version),
+                (true, ArchiveRecord::RelationshipVersion(version)) => archive.relationship_versions.push(version),
+                (true, ArchiveRecord::ConceptHead { concept_id, head }) => archive.concept_heads.push((concept_id, head)),
+                (true, ArchiveRecord::RelationshipHead { relationship_id, head }) => {
+                    archive.relationship_heads.push((relationship_id, head))
+                }
+                (true, ArchiveRecord::Transaction { transaction_id, metadata }) => {
+                    archive.transactions.push((transaction_id, metadata))
+                }
+                (true, ArchiveRecord::ContentHash { hash, concept_id }) => {
+                    let hash = ContentHash::from_str_radix(&hash, 16)
+                        .map_err(|_| MnemonicError::Archive(format!("invalid content hash {:?}", hash)))?;
+                    archive.content_hashes.push((hash, concept_id));
+                }
+            }
+        }
+        if !header_seen {
+            return Err(MnemonicError::Archive("the archive is empty".to_string()));
+        }
+        Ok(archive)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::concept::Concept;
+    use serde_json::json;
+
+    #[test]
+    fn test_an_archive_round_trips_and_refuses_what_it_cannot_read() {
+        let concept = Concept::new(json!({"name": "Ada"}));
+        let version = ConceptVersion::from_concept(&concept, Uuid::new_v4(), 1);
+        let archive = Archive {
+            commit_sequence: 7,
+            concept_versions: vec![version.clone()],
+            concept_heads: vec![(concept.id, 3)],
+            transactions: vec![(version.created_by, TransactionMetadata::default())],
+            content_hashes: vec![(u128::MAX - 1, concept.id)],
+            ..Archive::default()
+        };
+        let mut bytes = Vec::new();
+        let written = archive.write(&mut bytes).unwrap();
+        assert_eq!(written, ArchiveReport { concept_versions: 1, transactions: 1, commit_sequence: 7, ..Default::default() });
+        assert_eq!(Archive::read(bytes.as_slice()).unwrap(), archive);
+
+        let headless = bytes.split(|b| *b == b'\n').nth(1).unwrap();
+        assert!(matches!(Archive::read(headless), Err(MnemonicError::Archive(_))));
+        assert!(matches!(Archive::read(&b"\n"[..]), Err(MnemonicError::Archive(_))));
+        let future = br#"{"kind":"header","format":"mnar","format_version":99,"commit_sequence":0,"exported_at":"2026-01-01T00:00:00Z"}"#;
+        assert!(matches!(Archive::read(&future[..]), Err(MnemonicError::Archive(_))));
+    }
+}
@@ -0,0 +1,303 @@
+// The audit log: every committed version as a flat change record, in commit order.
+//
+// The VersionStore keeps an ordered index of (commit time, item, version) next to the
+// version chains, rebuilt with them on hydration, so a page of the log is a range scan
+// rather than a walk over every chain. Records are assembled from the versions on read.
+// Activity counts are the same range scan, tallied into fixed-width time buckets.
+
+use chrono::{DateTime, DurationRound, SecondsFormat, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::error::{self, MnemonicError};
+use crate::types::concept::{ConceptVersion, TransactionId};
+use crate::types::query::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::types::relationship::RelationshipVersion;
+
+/// Which kind of graph item a change record is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Concept,
+    Relationship,
+}
+
+/// What a version did to its item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// One committed version, as the audit log reports it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the change was committed.
+    pub timestamp: DateTime<Utc>,
+    pub item_kind: ItemKind,
+    pub item_id: Uuid,
+    pub version: u64,
+    pub change: ChangeKind,
+    pub transaction_id: TransactionId,
+    /// The actor the transaction recorded, if it recorded one.
+    pub actor: Option<String>,
+}
+
+impl AuditRecord {
+    pub(crate) fn from_concept_version(version: &ConceptVersion) -> Self {
+        Self {
+            timestamp: version.deleted_at.unwrap_or(version.created_at),
+            item_kind: ItemKind::Concept,
+            item_id: version.concept_id,
+            version: version.version,
+            change: change_kind(version.version, version.deleted_at.is_some()),
+            transaction_id: version.deleted_by.unwrap_or(version.created_by),
+            actor: None,
+        }
+    }
+
+    pub(crate) fn from_relationship_version(version: &RelationshipVersion) -> Self {
+        Self {
+            // A deletion copies the previous version, so it's stamped by its deletion time.
+            timestamp: version.deleted_at.unwrap_or(version.created_at),
+            item_kind: ItemKind::Relationship,
+            item_id: version.relationship_id,
+            version: version.version,
+            change: change_kind(version.version, version.deleted_at.is_some()),
+            transaction_id: version.deleted_by.unwrap_or(version.created_by),
+            actor: None,
+        }
+    }
+
+    /// Where this record sits in the log.
+    pub fn cursor(&self) -> AuditCursor {
+        AuditCursor {
+            timestamp: self.timestamp,
+            item_kind: self.item_kind,
+            item_id: self.item_id,
+            version: self.version,
+        }
+    }
+}
+
+fn change_kind(version: u64, deleted: bool) -> ChangeKind {
+    if deleted {
+        ChangeKind::Deleted
+    } else if version == 1 {
+        ChangeKind::Created
+    } else {
+        ChangeKind::Updated
+    }
+}
+
+/// A position in the audit log. The log is ordered by these fields, in this order.
+/// On the wire it's an opaque string such as `2026-01-01T00:00:00.000000000Z_concept_<id>_2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct AuditCursor {
+    pub timestamp: DateTime<Utc>,
+    pub item_kind: ItemKind,
+    pub item_id: Uuid,
+    pub version: u64,
+}
+
+impl fmt::Display for AuditCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.item_kind {
+            ItemKind::Concept => "concept",
+            ItemKind::Relationship => "relationship",
+        };
+        write!(
+            f,
+            "{}_{}_{}_{}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            kind,
+            self.item_id,
+            self.version
+        )
+    }
+}
+
+impl FromStr for AuditCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid audit cursor {:?}", s);
+        let mut parts = s.split('_');
+        let (Some(timestamp), Some(kind), Some(id), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .map_err(|_| invalid())?
+                .with_timezone(&Utc),
+            item_kind: match kind {
+                "concept" => ItemKind::Concept,
+                "relationship" => ItemKind::Relationship,
+                _ => return Err(invalid()),
+            },
+            item_id: id.parse().map_err(|_| invalid())?,
+            version: version.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl From<AuditCursor> for String {
+    fn from(cursor: AuditCursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+impl TryFrom<String> for AuditCursor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Which changes to list, and where to resume from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+    /// Only changes committed at or after this moment.
+    pub since: Option<DateTime<Utc>>,
+    /// Only changes committed before this moment.
+    pub until: Option<DateTime<Utc>>,
+    /// Only changes whose transaction recorded this actor.
+    pub actor: Option<String>,
+    /// At most this many records per page (capped at `MAX_PAGE_SIZE`).
+    pub limit: Option<usize>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<AuditCursor>,
+}
+
+impl AuditQuery {
+    /// The page size to actually use.
+    pub fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+/// A page of the audit log, oldest change first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub records: Vec<AuditRecord>,
+    /// Pass this as `cursor` to get the next page. `None` on the last page.
+    pub next_cursor: Option<AuditCursor>,
+}
+
+/// The most buckets one activity query may ask for.
+pub const MAX_ACTIVITY_BUCKETS: usize = 10_000;
+
+/// How wide each activity bucket is. Buckets start on the UTC hour or midnight.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketWidth {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl BucketWidth {
+    pub fn duration(self) -> TimeDelta {
+        match self {
+            Self::Hour => TimeDelta::hours(1),
+            Self::Day => TimeDelta::days(1),
+        }
+    }
+
+    /// The start of the bucket `timestamp` falls in.
+    pub fn floor(self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        // Epoch-aligned, and UTC has no daylight saving, so every bucket is the same width.
+        timestamp.duration_trunc(self.duration()).unwrap_or(timestamp)
+    }
+}
+
+/// What was committed in one activity bucket, counted from the version timestamps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityBucket {
+    /// Where the bucket starts; it runs for one `BucketWidth`.
+    pub start: DateTime<Utc>,
+    pub concepts_created: u64,
+    pub concepts_updated: u64,
+    pub concepts_deleted: u64,
+    pub relationships_created: u64,
+    pub relationships_deleted: u64,
+}
+
+impl ActivityBucket {
+    /// An empty bucket starting at `start`.
+    pub fn empty(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            concepts_created: 0,
+            concepts_updated: 0,
+            concepts_deleted: 0,
+            relationships_created: 0,
+            relationships_deleted: 0,
+        }
+    }
+
+    /// Counts one change record. Relationship updates aren't counted.
+    pub fn count(&mut self, item_kind: ItemKind, change: ChangeKind) {
+        let counter = match (item_kind, change) {
+            (ItemKind::Concept, ChangeKind::Created) => &mut self.concepts_created,
+            (ItemKind::Concept, ChangeKind::Updated) => &mut self.concepts_updated,
+            (ItemKind::Concept, ChangeKind::Deleted) => &mut self.concepts_deleted,
+            (ItemKind::Relationship, ChangeKind::Created) => &mut self.relationships_created,
+            (ItemKind::Relationship, ChangeKind::Deleted) => &mut self.relationships_deleted,
+            (ItemKind::Relationship, ChangeKind::Updated) => return,
+        };
+        *counter += 1;
+    }
+}
+
+/// Empty buckets of `width` covering `from` up to `to`, both rounded out to whole buckets.
+/// `InvalidData` if the window is empty or needs more than `MAX_ACTIVITY_BUCKETS`.
+pub fn activity_buckets(from: DateTime<Utc>, to: DateTime<Utc>, width: BucketWidth) -> error::Result<Vec<ActivityBucket>> {
+    if from >= to {
+        return Err(MnemonicError::InvalidData(format!("activity window {} to {} is empty", from, to)));
+    }
+    let first = width.floor(from);
+    // The bucket the window's last instant falls in, never before `first` as `to` is after `from`.
+    let last = width.floor(to - TimeDelta::nanoseconds(1));
+    let count = usize::try_from((last - first).num_seconds() / width.duration().num_seconds())
+        .map_or(usize::MAX, |n| n.saturating_add(1));
+    if count > MAX_ACTIVITY_BUCKETS {
+        return Err(MnemonicError::InvalidData(format!(
+            "activity window needs {} buckets; at most {} are allowed",
+            count, MAX_ACTIVITY_BUCKETS
+        )));
+    }
+    Ok((0..count as i32).map(|n| ActivityBucket::empty(first + width.duration() * n)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trips_through_its_string_form() {
+        let cursor = AuditCursor {
+            timestamp: Utc::now(),
+            item_kind: ItemKind::Relationship,
+            item_id: Uuid::new_v4(),
+            version: 12,
+        };
+        assert_eq!(cursor.to_string().parse::<AuditCursor>(), Ok(cursor));
+        let json = serde_json::to_value(cursor).unwrap();
+        assert!(json.is_string());
+        assert_eq!(serde_json::from_value::<AuditCursor>(json).unwrap(), cursor);
+
+        for bad in ["", "yesterday_concept_x_1", &format!("{}_widget_{}_1", "2026-01-01T00:00:00Z", Uuid::nil())] {
+            assert!(bad.parse::<AuditCursor>().is_err());
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Bounding how much of the store one read may scan. A ScanBudget caps how many items a scan
+// may visit and how long it may take; the scan spends it through a ScanMeter, one item at a
+// time, and stops once it is spent. Unlike a passed Deadline, a spent budget isn't an error:
+// the scan answers with what it saw and says it was truncated, or, where a partial answer
+// would mislead, fails with `MnemonicError::ScanBudgetExceeded`.
+
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use crate::error::{MnemonicError, Result};
+
+/// What a scan may spend unless configured otherwise.
+pub const DEFAULT_SCAN_BUDGET: ScanBudget =
+    ScanBudget { max_items: 100_000, max_duration: Duration::from_secs(10) };
+
+/// How many items a scan may visit, and for how long. The default never runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanBudget {
+    pub max_items: usize,
+    pub max_duration: Duration,
+}
+
+impl Default for ScanBudget {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+impl ScanBudget {
+    /// A budget that never runs out.
+    pub const fn unlimited() -> Self {
+        Self { max_items: usize::MAX, max_duration: Duration::MAX }
+    }
+
+    /// A budget of `max_items` items, however long they take.
+    pub const fn items(max_items: usize) -> Self {
+        Self { max_items, ..Self::unlimited() }
+    }
+
+    /// This budget, lowered to whatever a request asked for. A request can't raise it.
+    pub fn narrowed(self, max_items: Option<usize>, max_duration: Option<Duration>) -> Self {
+        Self {
+            max_items: max_items.map_or(self.max_items, |items| items.min(self.max_items)),
+            max_duration: max_duration.map_or(self.max_duration, |duration| duration.min(self.max_duration)),
+        }
+    }
+
+    /// Starts spending the budget; the clock runs from now.
+    pub fn start(self) -> ScanMeter {
+        ScanMeter { budget: self, started: Instant::now(), items: Cell::new(0), spent: Cell::new(false) }
+    }
+}
+
+/// One scan's spending of a ScanBudget. Takes `&self`, so the filters and callbacks the
+/// scan helpers take can spend it.
+#[derive(Debug)]
+pub struct ScanMeter {
+    budget: ScanBudget,
+    started: Instant,
+    items: Cell<usize>,
+    spent: Cell<bool>,
+}
+
+impl ScanMeter {
+    /// A meter that never runs out.
+    pub fn unlimited() -> Self {
+        ScanBudget::unlimited().start()
+    }
+
+    /// Spends one item, or says the budget is gone; once it is, it stays gone.
+    pub fn take(&self) -> bool {
+        if self.spent.get() {
+            return false;
+        }
+        if self.items.get() >= self.budget.max_items || self.started.elapsed() >= self.budget.max_duration {
+            self.spent.set(true);
+            return false;
+        }
+        self.items.set(self.items.get() + 1);
+        true
+    }
+
+    /// How many items have been spent.
+    pub fn items(&self) -> usize {
+        self.items.get()
+    }
+
+    /// Whether the scan was refused an item, so what it saw may be missing some.
+    pub fn is_spent(&self) -> bool {
+        self.spent.get()
+    }
+
+    /// `ScanBudgetExceeded` if the budget ran out, for scans only a whole answer will do for.
+    pub fn ensure_whole(&self) -> Result<()> {
+        if self.is_spent() {
+            return Err(MnemonicError::ScanBudgetExceeded { items: self.items() });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_meter_stops_at_either_limit_and_requests_only_narrow_the_budget() {
+        let meter = ScanBudget::items(2).start();
+        assert!(meter.take() && meter.take());
+        assert!(!meter.is_spent());
+        assert!(!meter.take());
+        assert!(meter.is_spent());
+        assert!(matches!(meter.ensure_whole(), Err(MnemonicError::ScanBudgetExceeded { items: 2 })));
+
+        let timed = ScanBudget { max_duration: Duration::ZERO, ..ScanBudget::unlimited() }.start();
+        assert!(!timed.take());
+        assert_eq!(timed.items(), 0);
+        assert!(ScanBudget::unlimited().start().ensure_whole().is_ok());
+
+        let budget = ScanBudget { max_items: 10, max_duration: Duration::from_secs(1) };
+        assert_eq!(budget.narrowed(Some(50), Some(Duration::from_millis(5))), ScanBudget {
+            max_items: 10,
+            max_duration: Duration::from_millis(5),
+        });
+        assert_eq!(budget.narrowed(None, None), budget);
+    }
+}
@@ -0,0 +1,63 @@
+// Deleting every concept that matches a filter, e.g. to clean up after a bad import.
+//
+// Matches are found with one scan and then tombstoned in batches, each its own transaction,
+// so a conflict only costs the batch it hit. A concept that changed between the scan and its
+// batch is judged again as of the batch, and left alone if it no longer matches.
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::budget::ScanBudget;
+use crate::types::concept::ConceptId;
+
+/// How `GraphEngine::delete_where` deletes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkDeleteOptions {
+    /// Only find the matches, deleting nothing. On by default.
+    pub dry_run: bool,
+    /// How many concepts each transaction tombstones.
+    pub batch_size: usize,
+    /// Also tombstone the live relationships touching each match, in the same transaction.
+    /// Otherwise they are left as `delete_concept` leaves them.
+    pub cascade: bool,
+    /// How much of the store a dry run may scan for matches. A real run always looks at
+    /// everything, however long it takes. Set by the caller, never by a request body.
+    #[serde(skip)]
+    pub scan_budget: ScanBudget,
+}
+
+impl Default for BulkDeleteOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: true,
+            batch_size: 500,
+            cascade: false,
+            scan_budget: ScanBudget::unlimited(),
+        }
+    }
+}
+
+/// A batch that failed to commit. Its concepts are still active.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkDeleteFailure {
+    pub concept_ids: Vec<ConceptId>,
+    pub error: String,
+}
+
+/// The outcome of `GraphEngine::delete_where`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BulkDeleteReport {
+    pub dry_run: bool,
+    /// Every active concept the filter matched, in ID order. On a dry run, what would be deleted.
+    pub matched: Vec<ConceptId>,
+    pub concepts_deleted: usize,
+    pub relationships_deleted: usize,
+    /// Matches that had been deleted or no longer matched by the time their batch ran.
+    pub skipped: usize,
+    pub batches_committed: usize,
+    pub batches_failed: usize,
+    pub failures: Vec<BulkDeleteFailure>,
+    /// True when a dry run's scan budget ran out, so `matched` may be missing some.
+    #[serde(default)]
+    pub truncated: bool,
+}
@@ -0,0 +1,241 @@
+// Tiered integrity checks across disk, memory and the indexes derived from them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::consistency::group_chains;
+use super::versioning::VersionStore;
+use crate::error::Result;
+use crate::storage::{keys, RocksBackend};
+use crate::types::concept::{ConceptId, TransactionId};
+use crate::types::relationship::{Relationship, RelationshipId};
+use crate::ConflictItem;
+use uuid::Uuid;
+
+/// How thorough `GraphEngine::check` is. Each level runs every check of the ones before it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckDepth {
+    /// Compare the number of entries in each keyspace with what memory holds.
+    Quick,
+    /// Also check that every item's latest version is both in memory and on disk.
+    #[default]
+    Standard,
+    /// Also resolve every index entry, and check every relationship's endpoints were
+    /// active concepts when it was created.
+    Deep,
+}
+
+/// One thing `GraphEngine::check` found wrong.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// A keyspace holds a different number of entries on disk than memory accounts for.
+    CountMismatch { keyspace: String, memory: usize, disk: usize },
+    /// Memory's latest version of an item has no record on disk.
+    MissingOnDisk { item: ConflictItem, version: u64, key: String },
+    /// Disk's latest version of an item isn't in memory.
+    MissingInMemory { item: ConflictItem, version: u64, key: String },
+    /// A source or target index entry names a relationship record that doesn't exist or
+    /// doesn't have that endpoint.
+    OrphanedIndexEntry { key: String, concept_id: ConceptId, relationship_id: RelationshipId },
+    /// A content hash entry names a concept with no history.
+    OrphanedContentHash { key: String, concept_id: ConceptId },
+    /// A relationship was created while one of its endpoints wasn't an active concept.
+    DanglingEndpoint {
+        relationship_id: RelationshipId,
+        version: u64,
+        concept_id: ConceptId,
+        created_at: DateTime<Utc>,
+    },
+}
+
+/// The outcome of `GraphEngine::check`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CheckReport {
+    pub depth: CheckDepth,
+    /// How many entries each keyspace holds on disk.
+    pub keyspaces: BTreeMap<String, usize>,
+    pub findings: Vec<Finding>,
+}
+
+impl CheckReport {
+    /// Whether nothing was found wrong.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Runs every check up to `depth`, changing nothing.
+/// Callers must hold the commit lock so no commit lands halfway through the check.
+pub(crate) fn check(version_store: &VersionStore, backend: &RocksBackend, depth: CheckDepth) -> Result<CheckReport> {
+    let mut report = CheckReport { depth, ..Default::default() };
+    let memory_concepts = version_store.concept_version_numbers()?;
+    let memory_relationships = version_store.relationship_version_numbers()?;
+    let disk_concepts = chains(backend.load_version_numbers(keys::CONCEPT_VERSION)?);
+    let disk_relationships = chains(backend.load_version_numbers(keys::RELATIONSHIP_VERSION)?);
+
+    // 1. Quick: entry counts per keyspace.
+    let versions = |chains: &HashMap<_, Vec<u64>>| chains.values().map(Vec::len).sum::<usize>();
+    for (keyspace, memory, disk) in [
+        ("concept_versions", versions(&memory_concepts), versions(&disk_concepts)),
+        ("relationship_versions", versions(&memory_relationships), versions(&disk_relationships)),
+        ("concept_heads", memory_concepts.len(), backend.load_all_concept_heads()?.len()),
+        ("relationship_heads", memory_relationships.len(), backend.load_all_relationship_heads()?.len()),
+    ] {
+        report.keyspaces.insert(keyspace.to_string(), disk);
+        if memory != disk {
+            report.findings.push(Finding::CountMismatch { keyspace: keyspace.to_string(), memory, disk });
+        }
+    }
+    if depth == CheckDepth::Quick {
+        return Ok(report);
+    }
+
+    // 2. Standard: each side's latest version is on the other.
+    compare_heads(&memory_concepts, &disk_concepts, keys::CONCEPT_VERSION, ConflictItem::Concept, &mut report.findings);
+    compare_heads(
+        &memory_relationships,
+        &disk_relationships,
+        keys::RELATIONSHIP_VERSION,
+        ConflictItem::Relationship,
+        &mut report.findings,
+    );
+    if depth == CheckDepth::Standard {
+        return Ok(report);
+    }
+
+    // 3. Deep: index entries resolve...
+    let records: HashMap<RelationshipId, Relationship> =
+        backend.load_all_relationships()?.into_iter().map(|r| (r.id, r)).collect();
+    for tag in [keys::INDEX_SOURCE, keys::INDEX_TARGET] {
+        for (concept_id, relationship_id) in backend.load_relationship_index(tag)? {
+            let endpoint = records
+                .get(&relationship_id)
+                .map(|r| if tag == keys::INDEX_SOURCE { r.source } else { r.target });
+            if endpoint != Some(concept_id) {
+                report.findings.push(Finding::OrphanedIndexEntry {
+                    key: keys::describe(&keys::index(tag, &concept_id, &relationship_id)),
+                    concept_id,
+                    relationship_id,
+                });
+            }
+        }
+    }
+    for (hash, concept_id) in backend.load_all_content_hashes()? {
+        if !memory_concepts.contains_key(&concept_id) {
+            report.findings.push(Finding::OrphanedContentHash {
+                key: keys::describe(&keys::item(keys::CONTENT_HASH, &Uuid::from_u128(hash))),
+                concept_id,
+            });
+        }
+    }
+
+    // ...and relationships were created between active concepts. Versions load in key
+    // order, so each chain is oldest first.
+    let relationships = group_chains(backend.load_all_relationship_versions()?, |v| v.relationship_id);
+    for (relationship_id, chain) in relationships {
+        let (Some(first), Some(last)) = (chain.first(), chain.last()) else {
+            continue;
+        };
+        for concept_id in [first.source, first.target] {
+            let active = endpoint_was_active(version_store, &concept_id, first.created_at, first.created_by)?
+                // A purge may keep a concept's inactive relationships, so a concept with no
+                // history at all only matters to a relationship that is still active.
+                .unwrap_or(last.deleted_at.is_some());
+            if !active {
+                report.findings.push(Finding::DanglingEndpoint {
+                    relationship_id,
+                    version: first.version,
+                    concept_id,
+                    created_at: first.created_at,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// The sorted version numbers of every item.
+fn chains(numbers: Vec<(Uuid, u64)>) -> HashMap<Uuid, Vec<u64>> {
+    let mut chains: HashMap<Uuid, Vec<u64>> = HashMap::new();
+    for (id, number) in numbers {
+        chains.entry(id).or_default().push(number);
+    }
+    for numbers in chains.values_mut() {
+        numbers.sort_unstable();
+    }
+    chains
+}
+
+/// Records every item whose latest version on one side is missing from the other.
+fn compare_heads(
+    memory: &HashMap<Uuid, Vec<u64>>,
+    disk: &HashMap<Uuid, Vec<u64>>,
+    tag: u8,
+    item: fn(Uuid) -> ConflictItem,
+    findings: &mut Vec<Finding>,
+) {
+    let ids: HashSet<&Uuid> = memory.keys().chain(disk.keys()).collect();
+    let mut ids: Vec<&Uuid> = ids.into_iter().collect();
+    ids.sort();
+    let has = |chains: &HashMap<Uuid, Vec<u64>>, id, version| {
+        chains.get(id).is_some_and(|numbers: &Vec<u64>| numbers.binary_search(&version).is_ok())
+    };
+    for id in ids {
+        if let Some(&version) = memory.get(id).and_then(|numbers| numbers.last())
+            && !has(disk, id, version)
+        {
+            let key = keys::describe(&keys::version(tag, id, version));
+            findings.push(Finding::MissingOnDisk { item: item(*id), version, key });
+        }
+        if let Some(&version) = disk.get(id).and_then(|numbers| numbers.last())
+            && !has(memory, id, version)
+        {
+            let key = keys::describe(&keys::version(tag, id, version));
+            findings.push(Finding::MissingInMemory { item: item(*id), version, key });
+        }
+    }
+}
+
+/// Whether `concept_id` was active at `at`, or was written by the same transaction as the
+/// relationship (whose timestamp may then come first). `None` when it has no history.
+fn endpoint_was_active(
+    version_store: &VersionStore,
+    concept_id: &ConceptId,
+    at: DateTime<Utc>,
+    created_by: TransactionId,
+) -> Result<Option<bool>> {
+    let history = version_store.concept_history(concept_id)?;
+    let Some(oldest) = history.first() else {
+        return Ok(None);
+    };
+    if history.iter().any(|v| v.created_by == created_by && v.deleted_at.is_none()) {
+        return Ok(Some(true));
+    }
+    Ok(Some(match history.iter().rev().find(|v| v.created_at <= at) {
+        Some(version) => version.is_active_at(at),
+        // Everything from back then was pruned, so there is nothing left to contradict.
+        None => oldest.version > 1,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_heads_reports_each_side() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let memory = HashMap::from([(a, vec![1, 2]), (b, vec![1])]);
+        let disk = HashMap::from([(a, vec![1]), (b, vec![1, 2])]);
+        let mut findings = Vec::new();
+        compare_heads(&memory, &disk, keys::CONCEPT_VERSION, ConflictItem::Concept, &mut findings);
+
+        let key = |id, version| keys::describe(&keys::version(keys::CONCEPT_VERSION, &id, version));
+        assert_eq!(findings.len(), 2);
+        assert!(findings.contains(&Finding::MissingOnDisk { item: ConflictItem::Concept(a), version: 2, key: key(a, 2) }));
+        assert!(findings.contains(&Finding::MissingInMemory { item: ConflictItem::Concept(b), version: 2, key: key(b, 2) }));
+    }
+}
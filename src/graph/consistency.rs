@@ -0,0 +1,182 @@
+// Cross-checks between the in-memory VersionStore and the 'versions' cabinet on disk.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use super::versioning::VersionStore;
+use crate::error::Result;
+use crate::storage::RocksBackend;
+use crate::ConflictItem;
+
+/// One way the in-memory history and the on-disk history disagree.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Discrepancy {
+    /// Memory holds a different set of versions for an item than disk does.
+    Mismatch {
+        item: ConflictItem,
+        memory_head: Option<u64>,
+        disk_head: Option<u64>,
+    },
+    /// An item's history on disk skips version numbers, so a write was lost.
+    BrokenChain { item: ConflictItem, versions: Vec<u64> },
+}
+
+/// The outcome of `GraphEngine::verify_consistency` or `GraphEngine::repair_consistency`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsistencyReport {
+    pub concepts_checked: usize,
+    pub relationships_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+    /// How many mismatched items had their in-memory history reloaded from disk.
+    pub repaired: usize,
+}
+
+impl ConsistencyReport {
+    /// Whether memory and disk agree completely.
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Compares the in-memory version heads against the 'versions' cabinet.
+/// Disk is the source of truth: with `repair`, every mismatched item is reloaded from it.
+/// Callers must hold the commit lock so no commit lands halfway through the check.
+pub(crate) fn verify(
+    version_store: &VersionStore,
+    backend: &RocksBackend,
+    repair: bool,
+) -> Result<ConsistencyReport> {
+    let mut report = ConsistencyReport::default();
+
+    // 1. Concepts.
+    let mut disk_concepts = group_chains(backend.load_all_concept_versions()?, |v| v.concept_id);
+    let disk_numbers = version_numbers(&disk_concepts, |v| v.version);
+    let mismatched = compare_chains(
+        &version_store.concept_version_numbers()?,
+        &disk_numbers,
+        ConflictItem::Concept,
+        &mut report.discrepancies,
+    );
+    report.concepts_checked = disk_numbers.len();
+    if repair {
+        for concept_id in mismatched {
+            let versions = disk_concepts.remove(&concept_id).unwrap_or_default();
+            version_store.replace_concept_versions(concept_id, versions)?;
+            report.repaired += 1;
+        }
+        for (concept_id, head) in backend.load_all_concept_heads()? {
+            version_store.restore_concept_head(concept_id, head)?;
+        }
+    }
+
+    // 2. Relationships.
+    let mut disk_relationships =
+        group_chains(backend.load_all_relationship_versions()?, |v| v.relationship_id);
+    let disk_numbers = version_numbers(&disk_relationships, |v| v.version);
+    let mismatched = compare_chains(
+        &version_store.relationship_version_numbers()?,
+        &disk_numbers,
+        ConflictItem::Relationship,
+        &mut report.discrepancies,
+    );
+    report.relationships_checked = disk_numbers.len();
+    if repair {
+        for rel_id in mismatched {
+            let versions = disk_relationships.remove(&rel_id).unwrap_or_default();
+            version_store.replace_relationship_versions(rel_id, versions)?;
+            report.repaired += 1;
+        }
+        for (rel_id, head) in backend.load_all_relationship_heads()? {
+            version_store.restore_relationship_head(rel_id, head)?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// The lightweight check run during hydration, when memory was just loaded from disk:
+/// only look for history chains that skip version numbers.
+pub(crate) fn check_chains(version_store: &VersionStore) -> Result<Vec<Discrepancy>> {
+    let mut discrepancies = Vec::new();
+    for (concept_id, versions) in version_store.concept_version_numbers()? {
+        if !is_contiguous(&versions) {
+            discrepancies.push(Discrepancy::BrokenChain {
+                item: ConflictItem::Concept(concept_id),
+                versions,
+            });
+        }
+    }
+    for (rel_id, versions) in version_store.relationship_version_numbers()? {
+        if !is_contiguous(&versions) {
+            discrepancies.push(Discrepancy::BrokenChain {
+                item: ConflictItem::Relationship(rel_id),
+                versions,
+            });
+        }
+    }
+    Ok(discrepancies)
+}
+
+/// Groups loaded versions into per-item chains.
+pub(crate) fn group_chains<V, Id: Eq + Hash>(versions: Vec<V>, id_of: impl Fn(&V) -> Id) -> HashMap<Id, Vec<V>> {
+    let mut chains: HashMap<Id, Vec<V>> = HashMap::new();
+    for version in versions {
+        chains.entry(id_of(&version)).or_default().push(version);
+    }
+    chains
+}
+
+/// The sorted version numbers of every chain.
+fn version_numbers<V, Id: Copy + Eq + Hash>(
+    chains: &HashMap<Id, Vec<V>>,
+    number_of: impl Fn(&V) -> u64,
+) -> HashMap<Id, Vec<u64>> {
+    chains
+        .iter()
+        .map(|(id, chain)| {
+            let mut numbers: Vec<u64> = chain.iter().map(&number_of).collect();
+            numbers.sort_unstable();
+            (*id, numbers)
+        })
+        .collect()
+}
+
+/// Records a discrepancy for every item whose chains differ, returning those items.
+fn compare_chains<Id: Copy + Eq + Hash>(
+    memory: &HashMap<Id, Vec<u64>>,
+    disk: &HashMap<Id, Vec<u64>>,
+    item: fn(Id) -> ConflictItem,
+    discrepancies: &mut Vec<Discrepancy>,
+) -> Vec<Id> {
+    let ids: HashSet<Id> = memory.keys().chain(disk.keys()).copied().collect();
+    let mut mismatched = Vec::new();
+    for id in ids {
+        let in_memory = memory.get(&id);
+        let on_disk = disk.get(&id);
+        if in_memory != on_disk {
+            discrepancies.push(Discrepancy::Mismatch {
+                item: item(id),
+                memory_head: in_memory.and_then(|v| v.last().copied()),
+                disk_head: on_disk.and_then(|v| v.last().copied()),
+            });
+            mismatched.push(id);
+        }
+        if let Some(versions) = on_disk
+            && !is_contiguous(versions)
+        {
+            discrepancies.push(Discrepancy::BrokenChain {
+                item: item(id),
+                versions: versions.clone(),
+            });
+        }
+    }
+    mismatched
+}
+
+/// Version numbers only ever go up by one, so a gap means a version went missing.
+/// Chains may start above 1 once old versions are pruned.
+fn is_contiguous(versions: &[u64]) -> bool {
+    versions.windows(2).all(|pair| pair[1] == pair[0] + 1)
+}
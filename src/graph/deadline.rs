@@ -0,0 +1,112 @@
+// Bounding how long an engine operation may run. Long walks and scans check their Deadline at
+// each loop boundary and stop with `MnemonicError::Timeout` once it has passed, or with
+// `MnemonicError::Cancelled` once someone has cancelled it, giving their worker back.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::{MnemonicError, Result};
+
+/// When an operation must give up, and a switch to make it give up sooner. Clones share the
+/// switch, so cancelling any of them stops every operation checking one. The default never
+/// passes and is only stopped by `cancel`.
+#[derive(Debug, Clone, Default)]
+pub struct Deadline {
+    budget: Option<(Duration, Instant)>,
+    cancelled: Arc<AtomicBool>,
+    // How long each check sleeps, standing in for slow work in tests.
+    #[cfg(test)]
+    delay: Duration,
+}
+
+impl Deadline {
+    /// A deadline that never passes.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// A deadline `budget` from now.
+    pub fn after(budget: Duration) -> Self {
+        Self { budget: Some((budget, Instant::now() + budget)), ..Self::default() }
+    }
+
+    /// `after(budget)` with a budget, `none()` without.
+    pub fn within(budget: Option<Duration>) -> Self {
+        budget.map_or_else(Self::none, Self::after)
+    }
+
+    /// Makes every operation checking this deadline, or a clone of it, stop at its next check.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Cancelled` once cancelled, `Timeout` once passed, and otherwise nothing.
+    pub fn check(&self) -> Result<()> {
+        #[cfg(test)]
+        std::thread::sleep(self.delay);
+        if self.is_cancelled() {
+            return Err(MnemonicError::Cancelled);
+        }
+        match self.budget {
+            Some((budget, at)) if Instant::now() >= at => Err(MnemonicError::Timeout(budget)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Cancels this deadline when the guard is dropped, unless it was disarmed first, so
+    /// work whose caller has gone away stops instead of running on unseen.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(Some(self.clone()))
+    }
+
+    /// This deadline, with every check taking `delay` first.
+    #[cfg(all(test, feature = "server"))]
+    pub(crate) fn slowed(self, delay: Duration) -> Self {
+        Self { delay, ..self }
+    }
+}
+
+/// See `Deadline::cancel_on_drop`.
+pub struct CancelOnDrop(Option<Deadline>);
+
+impl CancelOnDrop {
+    /// The work finished, so dropping the guard no longer cancels anything.
+    pub fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(deadline) = &self.0 {
+            deadline.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_deadline_times_out_once_passed_and_cancels_through_clones() {
+        assert!(Deadline::none().check().is_ok());
+        let passed = Deadline::after(Duration::ZERO);
+        assert!(matches!(passed.check(), Err(MnemonicError::Timeout(budget)) if budget == Duration::ZERO));
+
+        let deadline = Deadline::after(Duration::from_secs(60));
+        let clone = deadline.clone();
+        assert!(clone.check().is_ok());
+        drop(deadline.cancel_on_drop());
+        assert!(matches!(clone.check(), Err(MnemonicError::Cancelled)));
+
+        let finished = Deadline::none();
+        finished.cancel_on_drop().disarm();
+        assert!(finished.check().is_ok());
+    }
+}
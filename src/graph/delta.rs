@@ -0,0 +1,92 @@
+// Graph deltas: what changed in the live graph between two watermarks, for clients that
+// keep a copy of it up to date instead of fetching the whole graph again.
+//
+// A watermark is a commit timestamp. `TransactionManager::watermark` hands one out under the
+// commit lock, so every commit stamped at or before it is already in memory and every later
+// commit is stamped after it. A delta reports each item that changed in its window as it
+// stood at the window's end: a concept or relationship that is live then is added (which
+// replaces any older copy), anything else is removed. Applying a delta is idempotent, so a
+// snapshot that caught some of the window's commits still converges once it is applied.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::versioning::VersionStore;
+use crate::error::Result;
+use crate::types::concept::{ConceptId, ConceptVersion};
+use crate::types::relationship::{RelationshipId, RelationshipVersion};
+
+/// What changed in the live graph after `since` and up to `as_of`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphDelta {
+    /// Concepts created or updated in the window, as of its end.
+    pub added_concepts: Vec<ConceptVersion>,
+    /// Concepts deleted or expired in the window.
+    pub removed_concept_ids: Vec<ConceptId>,
+    /// Relationships created in the window.
+    pub added_relationships: Vec<RelationshipVersion>,
+    /// Relationships deleted in the window, or hidden by an endpoint's expiry.
+    pub removed_relationship_ids: Vec<RelationshipId>,
+    /// The watermark to ask for the next delta from.
+    pub as_of: DateTime<Utc>,
+}
+
+impl GraphDelta {
+    /// Whether nothing changed in the window.
+    pub fn is_empty(&self) -> bool {
+        self.added_concepts.is_empty()
+            && self.removed_concept_ids.is_empty()
+            && self.added_relationships.is_empty()
+            && self.removed_relationship_ids.is_empty()
+    }
+}
+
+/// The delta from `since` to `as_of`, which must be a watermark.
+pub(crate) fn delta(version_store: &VersionStore, since: DateTime<Utc>, as_of: DateTime<Utc>) -> Result<GraphDelta> {
+    let (mut concepts, mut relationships) = version_store.changed_between(since, as_of);
+    // Expiring takes no commit, so it only shows in the log once the sweeper catches up.
+    for concept_id in version_store.concepts_expiring_between(since, as_of) {
+        relationships.extend(version_store.relationship_ids_touching(&concept_id));
+        concepts.insert(concept_id);
+    }
+
+    let mut delta = GraphDelta { as_of, ..Default::default() };
+    for concept_id in concepts {
+        match version_store.get_concept_version_at_timestamp(&concept_id, as_of)? {
+            Some(version) => delta.added_concepts.push(version),
+            None => delta.removed_concept_ids.push(concept_id),
+        }
+    }
+    for relationship_id in relationships {
+        let live = version_store.get_relationship_version_at_timestamp(&relationship_id, as_of)?;
+        let live = match live {
+            Some(version) if ends_live(version_store, &version, as_of)? => Some(version),
+            _ => None,
+        };
+        match live {
+            Some(version) => delta.added_relationships.push(version),
+            None => delta.removed_relationship_ids.push(relationship_id),
+        }
+    }
+
+    // The same window gives the same delta.
+    delta.added_concepts.sort_by_key(|v| v.concept_id);
+    delta.removed_concept_ids.sort();
+    delta.added_relationships.sort_by_key(|v| v.relationship_id);
+    delta.removed_relationship_ids.sort();
+    Ok(delta)
+}
+
+/// Whether /graph would show `version` at `at`: it hides a relationship once an endpoint has
+/// expired, until the sweeper tombstones both. A deleted endpoint doesn't hide it.
+fn ends_live(version_store: &VersionStore, version: &RelationshipVersion, at: DateTime<Utc>) -> Result<bool> {
+    for concept_id in [version.source, version.target] {
+        let expired = version_store
+            .get_existing_concept_version_at_timestamp(&concept_id, at)?
+            .is_some_and(|endpoint| endpoint.is_expired_at(at));
+        if expired {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
@@ -1,69 +1,466 @@
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
 use serde_json;
-use serde_json::json;
-use std::path::Path;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::task;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
-use super::transaction::{IsolationLevel, Transaction, TransactionManager};
-use crate::error::{MnemonicError, Result};
-use crate::storage::RocksBackend;
+use super::analysis;
+use super::archive::{Archive, ArchiveReport};
+use super::audit::{ActivityBucket, AuditPage, AuditQuery, BucketWidth};
+use super::budget::{ScanBudget, ScanMeter};
+use super::bulk::{BulkDeleteOptions, BulkDeleteReport};
+use super::check::{CheckDepth, CheckReport};
+use super::consistency::ConsistencyReport;
+use super::deadline::Deadline;
+use super::delta::GraphDelta;
+use super::entity::{self, ConceptEntity};
+use super::events::CommitEvent;
+use super::export::{ExportReport, GraphSnapshot, ImportReport};
+use super::merge::{MergeOptions, MergeReport, MergeStrategy};
+use super::neo4j::{self, ImportStats};
+use super::observers::{ObserverFailures, VersionObserver};
+use super::query::{self as graph_query, GraphQuery, QueryResult};
+use super::pruning::{PruneReport, PurgeOptions, PurgeReport, RetentionPolicy};
+use super::rebuild::RebuildReport;
+use super::replication::ChangeSource;
+use super::seed::{SeedFixture, SeedReport};
+use super::snapshots::{ReadSnapshot, SnapshotToken};
+use super::versioning::{VersionStoreStats, concept_from_version, relationship_from_version};
+use super::worker_pool::{WorkerPool, WorkerPoolConfig};
+use super::transaction::{
+    spawn_expiry_sweeper, CommitReceipt, IsolationLevel, Transaction, TransactionConfig, TransactionManager,
+    TransactionSummary,
+};
+use crate::error::{BatchItemError, MnemonicError, Result};
+use crate::storage::{RocksBackend, StorageMetrics, StorageOptions, StorageStats};
 use crate::types::{
-    concept::{Concept, ConceptId, ConceptVersion},
-    relationship::{RelationType, Relationship, RelationshipId, RelationshipMetadata},
+    concept::{self, Concept, ConceptId, ConceptVersion, DataLimits},
+    content,
+    query::{self, ConceptListOptions, ConceptListing, ConceptPage, Direction, Filter},
+    relationship::{
+        Cardinality, RelationType, Relationship, RelationshipId, RelationshipTypeDefinition,
+        RelationshipTypeUsage, RelationshipVersion,
+    },
+    system,
+    transaction::TransactionMetadata,
 };
 
-/// High-level graph engine that provides the core Mnemoninc Computing primities
-#[derive(Debug)]
+/// A snapshot of whether the engine is running normally.
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineHealth {
+    /// False once any lock had to be recovered after a panic, or any version observer has
+    /// failed. The engine keeps serving, but `verify_consistency` should be run to check the
+    /// in-memory state, and a failed observer's index may be missing versions.
+    pub healthy: bool,
+    pub lock_recoveries: u64,
+    /// The observers registered with `register_observer` that have failed, by name.
+    pub observer_failures: Vec<ObserverFailures>,
+}
+
+/// Counts over the live graph, plus how much history sits behind it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub concepts: u64,
+    pub relationships: u64,
+    pub relationships_by_type: HashMap<RelationType, u64>,
+    pub versions: VersionStoreStats,
+    /// The concepts commits most often conflicted on lately, hottest first (see `hot_spots`).
+    #[serde(default)]
+    pub conflict_hot_spots: Vec<(ConceptId, f64)>,
+    /// False while the history is still being loaded in the background (see `warmed`).
+    #[serde(default = "warmed_by_default")]
+    pub warmed: bool,
+}
+
+fn warmed_by_default() -> bool {
+    true
+}
+
+/// How many conflict hot spots `GraphEngine::stats` reports.
+pub const STATS_HOT_SPOTS: usize = 5;
+
+/// How far `GraphEngine::shortest_path` and `GraphEngine::neighborhood` may walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraversalLimits {
+    /// The most hops from the starting concept.
+    pub max_depth: u32,
+    /// The most concepts the walk may visit.
+    pub max_concepts: usize,
+}
+
+/// What a bounded walk found: concepts in the order reached (along the path, for a path),
+/// and the relationships joining them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Traversal {
+    pub concepts: Vec<Concept>,
+    pub relationships: Vec<Relationship>,
+    /// True when `max_concepts` or a scan budget stopped the walk, so something it would have
+    /// found may be missing.
+    pub truncated: bool,
+}
+
+/// How `GraphEngine::store_with_options` stores a concept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoreOptions {
+    /// Return the existing concept instead of storing a second copy of the same data
+    /// (up to key order). Only concepts stored with this set are found again. An existing
+    /// concept is returned as it is, whatever its expiry.
+    pub dedupe: bool,
+    /// When the concept stops being active. Must be in the future.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// How `GraphEngine::relate_with_options` creates a relationship.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelateOptions {
+    /// `false` for a symmetric relationship, which reads the same from either end: it is
+    /// outgoing and incoming for both, and a `Unique` constraint sees (a, b) and (b, a) as
+    /// the same pair.
+    pub directed: bool,
+}
+
+impl Default for RelateOptions {
+    fn default() -> Self {
+        Self { directed: true }
+    }
+}
+
+/// What `GraphEngine::update_relationship` changes about a relationship; `None` keeps it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelationshipUpdate {
+    pub relationship_type: Option<RelationType>,
+    /// The concept the relationship points at from now on. Must be active.
+    pub target: Option<ConceptId>,
+}
+
+/// A relationship and the concepts at either end, as `GraphEngine::relationship_with_endpoints`
+/// read them at `as_of`. An endpoint deleted or expired by then is `None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipEndpoints {
+    pub relationship: RelationshipVersion,
+    pub source: Option<ConceptVersion>,
+    pub target: Option<ConceptVersion>,
+    pub as_of: DateTime<Utc>,
+}
+
+/// How `GraphEngine::delete_concept_with_options` deletes a concept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeleteOptions {
+    /// Also tombstone the live relationships touching the concept, in the same transaction.
+    pub cascade: bool,
+    /// Without `cascade`, refuse to delete a concept live relationships still touch, rather
+    /// than leaving them behind as `delete_concept` does.
+    pub require_unreferenced: bool,
+}
+
+/// The outcome of `GraphEngine::delete_concept_with_options`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeleteReport {
+    pub concept_id: ConceptId,
+    /// The relationships tombstoned with it, in ID order; empty unless cascading.
+    pub relationships_deleted: Vec<RelationshipId>,
+}
+
+/// The outcome of `GraphEngine::store_with_options`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoreReport {
+    pub concept_id: ConceptId,
+    /// True when `concept_id` is an existing concept and nothing was written.
+    pub deduplicated: bool,
+    /// What the commit wrote; `None` when deduplicated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<CommitReceipt>,
+}
+
+/// Everything that can be tuned when opening a GraphEngine.
+#[derive(Debug, Clone, Default)]
+pub struct EngineConfig {
+    pub storage: StorageOptions,
+    pub transactions: TransactionConfig,
+    pub worker_pool: WorkerPoolConfig,
+    /// What concept data the store paths accept.
+    pub data_limits: DataLimits,
+    /// How long a traversal, export, snapshot or listing may run before it fails with
+    /// `MnemonicError::Timeout`, unless it is given its own `Deadline`. `None` means no limit.
+    pub operation_timeout: Option<Duration>,
+}
+
+/// High-level graph engine that provides the core Mnemoninc Computing primities.
+///
+/// Cloning is cheap and every clone is a handle on the same engine: the same store, worker
+/// pool and database. Closing any of them closes them all.
+#[derive(Debug, Clone)]
 pub struct GraphEngine {
     // We hold the backend inside an Arc so we can share it safely
     // across multiple concurrent operations.
     transaction_manager: Arc<TransactionManager>,
     backend: Arc<RocksBackend>,
+    // All blocking work runs here, so a burst of requests can't spawn unbounded threads.
+    worker_pool: Arc<WorkerPool>,
+    data_limits: DataLimits,
+    operation_timeout: Option<Duration>,
+    // Set by `close`, so dropping the engine afterwards doesn't warn. Shared by every clone.
+    closed: Arc<AtomicBool>,
 }
 
+/// How long `GraphEngine::close` waits for background threads to let go of the database.
+const CLOSE_GRACE: Duration = Duration::from_secs(5);
+
 impl GraphEngine {
     /// Create a new GraphEngine instance with the specified storage path.
     pub fn new(storage_path: &Path) -> Result<Self> {
+        Self::with_transaction_config(storage_path, TransactionConfig::default())
+    }
+
+    /// Create a new GraphEngine whose transactions follow the given configuration
+    /// (e.g. a custom maximum transaction age).
+    pub fn with_transaction_config(storage_path: &Path, config: TransactionConfig) -> Result<Self> {
+        Self::with_config(
+            storage_path,
+            EngineConfig {
+                transactions: config,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Create a new GraphEngine with full control over its configuration.
+    pub fn with_config(storage_path: &Path, config: EngineConfig) -> Result<Self> {
         // Initialize the low-level backend.
-        let backend = Arc::new(RocksBackend::new(storage_path)?);
-        let transaction_manager = TransactionManager::new(Arc::clone(&backend))?;
+        let backend = Arc::new(RocksBackend::with_options(storage_path, &config.storage)?);
+        let sweep_interval = config.transactions.expiry_sweep_interval;
+        let transaction_manager =
+            Arc::new(TransactionManager::with_config(Arc::clone(&backend), config.transactions)?);
+        // A read-only engine can't write the tombstones, so its expired concepts stay hidden.
+        if let Some(interval) = sweep_interval.filter(|_| !config.storage.read_only) {
+            spawn_expiry_sweeper(Arc::downgrade(&transaction_manager), interval)?;
+        }
         // Wrap it in an Arc and store it.
         Ok(Self {
-            transaction_manager: Arc::new(transaction_manager),
+            transaction_manager,
             backend,
+            worker_pool: Arc::new(WorkerPool::new(config.worker_pool)),
+            data_limits: config.data_limits,
+            operation_timeout: config.operation_timeout,
+            closed: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Shuts the engine down cleanly: waits for the operations already running or queued,
+    /// turns away new ones with `MnemonicError::EngineClosed`, aborts the transactions still
+    /// active, flushes everything to disk and closes the database, so the path can be
+    /// opened again as soon as this returns. Other clones are turned away too, but they hold
+    /// the database until they are dropped: close fails if something still holds it once the
+    /// grace period is up, e.g. a live clone or a handle from `backend()`.
+    pub async fn close(self) -> Result<()> {
+        self.worker_pool.close().await;
+        let manager = Arc::clone(&self.transaction_manager);
+        let backend = Arc::clone(&self.backend);
+        let db = Arc::downgrade(&self.backend.db);
+        self.closed.store(true, Ordering::Release);
+        drop(self);
+        tokio::task::spawn_blocking(move || {
+            let aborted = manager.abort_all_transactions();
+            if aborted > 0 {
+                tracing::info!("Aborted {} transaction(s) still active at close", aborted);
+            }
+            backend.flush()?;
+            drop((manager, backend));
+            // Background threads only hold the database while they work, so give them a moment.
+            let deadline = Instant::now() + CLOSE_GRACE;
+            while db.strong_count() > 0 {
+                if Instant::now() >= deadline {
+                    return Err(MnemonicError::Internal(format!(
+                        "The database is still held by {} other handle(s) after close",
+                        db.strong_count()
+                    )));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Ok(())
         })
+        .await?
     }
 
     /// STORE primitive: Creates and commits a concept in a single, atomic transaction.
     pub async fn store(&self, data: serde_json::Value) -> Result<ConceptId> {
-        let manager = Arc::clone(&self.transaction_manager);
+        self.store_with_meta(data, TransactionMetadata::default()).await
+    }
 
-        task::spawn_blocking(move || {
-            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
-            let new_concept = Concept::new(data);
+    /// Like `store`, but records who made the change and why.
+    pub async fn store_with_meta(
+        &self,
+        data: serde_json::Value,
+        metadata: TransactionMetadata,
+    ) -> Result<ConceptId> {
+        // Checked before the transaction begins, so a refused payload costs no transaction.
+        let new_concept = Concept::new_within(data, &self.data_limits)?;
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata)?;
             let concept_id = new_concept.id;
-            txn.write_set.insert(concept_id);
-            txn.pending_writes.insert(concept_id, new_concept);
+            manager.stage_concept_write(&mut txn, new_concept);
             manager.commit_transaction(txn)?;
             Ok(concept_id)
         })
         .await
-        .unwrap()
+    }
+
+    /// Like `store`, with options. When deduplicating, the data's content hash is claimed in
+    /// the same commit as the concept; if a concurrent store of the same data commits first,
+    /// ours conflicts and the winner's ID is returned.
+    pub async fn store_with_options(
+        &self,
+        data: serde_json::Value,
+        options: StoreOptions,
+    ) -> Result<StoreReport> {
+        self.store_with_options_and_meta(data, options, TransactionMetadata::default())
+            .await
+    }
+
+    /// Like `store_with_options`, but records who made the change and why.
+    pub async fn store_with_options_and_meta(
+        &self,
+        data: serde_json::Value,
+        options: StoreOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<StoreReport> {
+        let new_concept = Concept::new_within(data, &self.data_limits)?;
+        self.store_concept(new_concept, options, metadata).await
+    }
+
+    /// Stores a concept with no data, for a purely structural node such as a group.
+    pub async fn store_empty(&self) -> Result<ConceptId> {
+        let report = self
+            .store_empty_with_options_and_meta(StoreOptions::default(), TransactionMetadata::default())
+            .await?;
+        Ok(report.concept_id)
+    }
+
+    /// Like `store_empty`, with options, recording who made the change and why. Empty
+    /// concepts all have the same content, so `dedupe` is ignored.
+    pub async fn store_empty_with_options_and_meta(
+        &self,
+        options: StoreOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<StoreReport> {
+        let options = StoreOptions { dedupe: false, ..options };
+        self.store_concept(Concept::empty(), options, metadata).await
+    }
+
+    async fn store_concept(
+        &self,
+        mut new_concept: Concept,
+        options: StoreOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<StoreReport> {
+        if options.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(MnemonicError::InvalidData("expires_at must be in the future".to_string()));
+        }
+        new_concept.expires_at = options.expires_at;
+        self.run_blocking(move |manager| {
+            let deduplicated = |concept_id| StoreReport { concept_id, deduplicated: true, receipt: None };
+            if options.dedupe
+                && let Some(existing) = manager.find_duplicate(&new_concept.data)?
+            {
+                return Ok(deduplicated(existing));
+            }
+
+            let mut txn = manager.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata)?;
+            let concept_id = new_concept.id;
+            if options.dedupe {
+                txn.content_hashes
+                    .insert(content::content_hash(&new_concept.data), concept_id);
+            }
+            manager.stage_concept_write(&mut txn, new_concept.clone());
+            match manager.commit_transaction_detailed(txn) {
+                Ok(receipt) => Ok(StoreReport { concept_id, deduplicated: false, receipt: Some(receipt) }),
+                Err(MnemonicError::TransactionConflict(conflicts)) if options.dedupe => {
+                    match manager.find_duplicate(&new_concept.data)? {
+                        Some(existing) => Ok(deduplicated(existing)),
+                        None => Err(MnemonicError::TransactionConflict(conflicts)),
+                    }
+                }
+                Err(e) => Err(e),
+            }
+        })
+        .await
     }
 
     /// RELATE primitive: Creates and commits a relationship in a single transaction.
     pub async fn relate(
         &self,
         source: ConceptId,
-        relationship_type: RelationType,
+        relationship_type: impl Into<RelationType>,
         target: ConceptId,
     ) -> Result<RelationshipId> {
-        // 1. Begin a new transaction for this single operation.
-        let manager = Arc::clone(&self.transaction_manager);
+        self.relate_with_meta(source, relationship_type, target, TransactionMetadata::default())
+            .await
+    }
 
-        task::spawn_blocking(move || {
-            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+    /// Like `relate`, but records who made the change and why.
+    pub async fn relate_with_meta(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+        metadata: TransactionMetadata,
+    ) -> Result<RelationshipId> {
+        self.relate_with_options_and_meta(source, relationship_type, target, RelateOptions::default(), metadata)
+            .await
+    }
+
+    /// Like `relate`, with the given options.
+    pub async fn relate_with_options(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+        options: RelateOptions,
+    ) -> Result<RelationshipId> {
+        self.relate_with_options_and_meta(source, relationship_type, target, options, TransactionMetadata::default())
+            .await
+    }
+
+    /// Like `relate_with_options`, but records who made the change and why.
+    pub async fn relate_with_options_and_meta(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+        options: RelateOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<RelationshipId> {
+        self.relate_with_receipt(source, relationship_type, target, options, metadata)
+            .await
+            .map(|(relationship_id, _)| relationship_id)
+    }
+
+    /// Like `relate_with_options_and_meta`, but also hands back what the commit wrote.
+    pub async fn relate_with_receipt(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+        options: RelateOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<(RelationshipId, CommitReceipt)> {
+        let relationship_type = relationship_type.into();
+        // 1. Begin a new transaction for this single operation.
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata)?;
 
             // For a 'relate', we should check that the source and target concepts exist
             // This is a 'read', so we should add them to our read_set.
@@ -74,37 +471,107 @@ impl GraphEngine {
                 .version_store()
                 .get_concept_version_at_timestamp(&target, txn.start_timestamp)?;
 
+            // Don't leave the transaction dangling in the active list if we bail out early.
             if source_version.is_none() {
+                manager.abort_transaction(txn.id)?;
                 return Err(MnemonicError::ConceptNotFound(source));
             }
             if target_version.is_none() {
+                manager.abort_transaction(txn.id)?;
                 return Err(MnemonicError::ConceptNotFound(target));
             }
-            txn.read_set.insert(source);
-            txn.read_set.insert(target);
+            manager.note_concept_read(&mut txn, source);
+            manager.note_concept_read(&mut txn, target);
 
             // 2. Perform the work inside the transaction.
-            let new_rel = Relationship::new(source, relationship_type, target);
+            let new_rel = Relationship { directed: options.directed, ..Relationship::new(source, relationship_type, target) };
             let rel_id = new_rel.id;
 
             // Add the new relationship to the transaction's "shopping cart".
-            txn.relationship_write_set.insert(rel_id);
-            txn.pending_relationship_writes.insert(rel_id, new_rel);
+            manager.stage_relationship_write(&mut txn, new_rel);
 
             // 3. Commit the transaction atomically.
+            let receipt = manager.commit_transaction_detailed(txn)?;
+
+            Ok((rel_id, receipt))
+        })
+        .await
+    }
+
+    /// Batch STORE: creates every concept in a single transaction, returning their IDs in order.
+    pub async fn store_batch(
+        &self,
+        items: Vec<serde_json::Value>,
+        metadata: TransactionMetadata,
+    ) -> Result<Vec<ConceptId>> {
+        let concepts = items
+            .into_iter()
+            .map(|data| Concept::new_within(data, &self.data_limits))
+            .collect::<Result<Vec<_>>>()?;
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata)?;
+            let mut ids = Vec::with_capacity(concepts.len());
+            for new_concept in concepts {
+                ids.push(new_concept.id);
+                manager.stage_concept_write(&mut txn, new_concept);
+            }
             manager.commit_transaction(txn)?;
+            Ok(ids)
+        })
+        .await
+    }
 
-            Ok(rel_id)
+    /// Batch RELATE: creates every relationship in a single transaction, returning their IDs
+    /// in order. If any item points at a missing concept, nothing is committed and every
+    /// offending item is reported.
+    pub async fn relate_batch(
+        &self,
+        items: Vec<(ConceptId, RelationType, ConceptId)>,
+        metadata: TransactionMetadata,
+    ) -> Result<Vec<RelationshipId>> {
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata)?;
+            let version_store = manager.version_store();
+
+            // Look every endpoint up at once: two per item, source then target.
+            let endpoints: Vec<ConceptId> = items
+                .iter()
+                .flat_map(|(source, _, target)| [*source, *target])
+                .collect();
+            let found = version_store.get_latest_active_concepts(&endpoints)?;
+
+            let mut errors = Vec::new();
+            let mut ids = Vec::with_capacity(items.len());
+            for (index, (source, relationship_type, target)) in items.into_iter().enumerate() {
+                for (endpoint, version) in [source, target].into_iter().zip(&found[index * 2..]) {
+                    if version.is_none() {
+                        errors.push(BatchItemError {
+                            index,
+                            error: MnemonicError::ConceptNotFound(endpoint).to_string(),
+                        });
+                    }
+                }
+                manager.note_concept_read(&mut txn, source);
+                manager.note_concept_read(&mut txn, target);
+
+                let new_rel = Relationship::new(source, relationship_type, target);
+                ids.push(new_rel.id);
+                manager.stage_relationship_write(&mut txn, new_rel);
+            }
+
+            if !errors.is_empty() {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::InvalidBatch(errors));
+            }
+            manager.commit_transaction(txn)?;
+            Ok(ids)
         })
         .await
-        .unwrap()
     }
 
     /// UNRELATE primitive: Remove a relationship from the graph.
     pub async fn unrelate(&self, rel_id: RelationshipId) -> Result<()> {
-        let manager = Arc::clone(&self.transaction_manager);
-
-        task::spawn_blocking(move || {
+        self.run_blocking(move |manager| {
             // 1. Begin a new transaction.
             let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
 
@@ -114,14 +581,13 @@ impl GraphEngine {
                 .get_relationship_version_at_timestamp(&rel_id, txn.start_timestamp)?
                 .is_none()
             {
+                manager.abort_transaction(txn.id)?;
                 return Err(MnemonicError::RelationshipNotFound(rel_id));
             }
 
-            // 3. Add the delete operation to our "shopping cart".
-            txn.pending_deletes.insert(rel_id);
-
-            // Mark this as a "write" operation for conflict detection purposes.
-            txn.relationship_write_set.insert(rel_id);
+            // 3. Add the delete operation to our "shopping cart", which also marks it as a
+            // "write" for conflict detection.
+            manager.stage_relationship_delete(&mut txn, rel_id);
 
             // 4. Commit the transaction.
             manager.commit_transaction(txn)?;
@@ -129,173 +595,1663 @@ impl GraphEngine {
             Ok(())
         })
         .await
-        .unwrap()
     }
 
-    /// Basic RETRIEVE: Get all relationships originating from a concept.
-    pub async fn retrieve_by_source(&self, source_id: ConceptId) -> Result<Vec<Relationship>> {
-        let manager = Arc::clone(&self.transaction_manager);
+    /// Re-types or re-targets a relationship in place, committing a new version under the same
+    /// ID so its history shows the old shape and the new. Like any write, a concurrent change to
+    /// the relationship is a `TransactionConflict`. An update that changes nothing commits nothing.
+    pub async fn update_relationship(&self, rel_id: RelationshipId, changes: RelationshipUpdate) -> Result<()> {
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            let Some(latest) = manager.read_relationship(&mut txn, &rel_id)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::RelationshipNotFound(rel_id));
+            };
+            let mut updated = relationship_from_version(&latest);
+            if let Some(relationship_type) = changes.relationship_type {
+                updated.relationship_type = relationship_type;
+            }
+            if let Some(target) = changes.target.filter(|target| *target != latest.target) {
+                if manager
+                    .version_store()
+                    .get_concept_version_at_timestamp(&target, txn.start_timestamp)?
+                    .is_none()
+                {
+                    manager.abort_transaction(txn.id)?;
+                    return Err(MnemonicError::ConceptNotFound(target));
+                }
+                manager.note_concept_read(&mut txn, target);
+                updated.target = target;
+            }
+            if updated.relationship_type == latest.relationship_type && updated.target == latest.target {
+                manager.abort_transaction(txn.id)?;
+                return Ok(());
+            }
 
-        task::spawn_blocking(move || {
-            let now = chrono::Utc::now();
-            let version_store = manager.version_store();
+            // The indexes follow the latest version, so they move when this one lands.
+            manager.stage_relationship_write(&mut txn, updated);
+            manager.commit_transaction(txn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// FORGET primitive: Tombstones a concept so it is no longer active.
+    /// Its history stays available to time-travel queries. System concepts can't be deleted.
+    pub async fn delete_concept(&self, id: ConceptId) -> Result<()> {
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
 
-            // 1. Get ALL relationships from the version store's memory.
-            let all_active_rels = version_store.get_all_active_relationships()?;
+            let Some(latest) = manager.version_store().get_concept_version_at_timestamp(&id, txn.start_timestamp)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptNotFound(id));
+            };
+            if system::is_system(&latest.tags) {
+                manager.abort_transaction(txn.id)?;
+                return Err(system::refused(id));
+            }
 
-            // 2. Filter them down to find the ones that match our source_id.
-            let matching_rels: Vec<Relationship> = all_active_rels
-                .into_iter()
-                .filter(|version| version.source == source_id)
-                // 3. Convert them back to the simple 'Relationship' type for the API.
-                .map(|version| Relationship {
-                    id: version.relationship_id,
-                    source: version.source,
-                    relationship_type: version.relationship_type.clone(),
-                    target: version.target,
-                    metadata: RelationshipMetadata {
-                        created_at: version.created_at,
-                        version: version.version,
-                        transaction_id: version.created_by,
-                    },
-                })
-                .collect();
+            manager.stage_concept_delete(&mut txn, id);
+            manager.commit_transaction(txn)?;
 
-            Ok(matching_rels)
+            Ok(())
         })
         .await
-        .unwrap()
     }
-    /// Begin a new transaction
-    pub async fn begin_transaction(&self, isolation_level: IsolationLevel) -> Result<Transaction> {
-        let manager = Arc::clone(&self.transaction_manager);
-        task::spawn_blocking(move || manager.begin_transaction(isolation_level))
+
+    /// Like `delete_concept`, with options: `cascade` tombstones the live relationships touching
+    /// the concept in the same transaction, and `require_unreferenced` refuses to leave them.
+    pub async fn delete_concept_with_options(&self, id: ConceptId, options: DeleteOptions) -> Result<DeleteReport> {
+        self.delete_concept_with_options_and_meta(id, options, TransactionMetadata::default())
             .await
-            .unwrap() // This unwrap can be improved later
     }
 
-    /// Commit a transaction
-    pub async fn commit_transaction(&self, transaction: Transaction) -> Result<()> {
-        let manager = Arc::clone(&self.transaction_manager);
-        task::spawn_blocking(move || manager.commit_transaction(transaction))
-            .await
-            .unwrap()
+    /// Like `delete_concept_with_options`, but records who made the change and why.
+    pub async fn delete_concept_with_options_and_meta(
+        &self,
+        id: ConceptId,
+        options: DeleteOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<DeleteReport> {
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata)?;
+            let version_store = manager.version_store();
+
+            let Some(latest) = version_store.get_concept_version_at_timestamp(&id, txn.start_timestamp)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptNotFound(id));
+            };
+            if system::is_system(&latest.tags) {
+                manager.abort_transaction(txn.id)?;
+                return Err(system::refused(id));
+            }
+            let touching = version_store.relationship_ids_touching(&id);
+            if !options.cascade && options.require_unreferenced && !touching.is_empty() {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptReferenced { concept_id: id, relationships: touching });
+            }
+
+            let mut report = DeleteReport { concept_id: id, relationships_deleted: Vec::new() };
+            if options.cascade {
+                for rel_id in &touching {
+                    manager.stage_relationship_delete(&mut txn, *rel_id);
+                }
+                report.relationships_deleted = touching;
+            }
+            manager.stage_concept_delete(&mut txn, id);
+            manager.commit_transaction(txn)?;
+            Ok(report)
+        })
+        .await
     }
 
-    /// Abort a transaction
-    pub async fn abort_transaction(&self, transaction_id: Uuid) -> Result<()> {
-        let manager = Arc::clone(&self.transaction_manager);
-        task::spawn_blocking(move || manager.abort_transaction(transaction_id))
-            .await
-            .unwrap()
+    /// Replaces the concept's data, committing a new version that keeps its tags and expiry.
+    /// A concurrent change to the concept is a `TransactionConflict`; data equal to what it
+    /// holds commits nothing. System concepts change only through `put_system`.
+    pub async fn update_concept(&self, id: ConceptId, data: serde_json::Value) -> Result<()> {
+        let data = Concept::new_within(data, &self.data_limits)?.data;
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            let Some(latest) = manager.version_store().get_concept_version_at_timestamp(&id, txn.start_timestamp)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptNotFound(id));
+            };
+            if system::is_system(&latest.tags) {
+                manager.abort_transaction(txn.id)?;
+                return Err(system::refused(id));
+            }
+            if latest.data == data {
+                manager.abort_transaction(txn.id)?;
+                return Ok(());
+            }
+            let mut concept = concept_from_version(&latest);
+            concept.data = data;
+            manager.stage_concept_write(&mut txn, concept);
+            manager.commit_transaction(txn)
+        })
+        .await
     }
 
-    /// Returns a thread-safe handle to the internal TransactionManager.
-    /// This is useful for advanced operations or for testing and debugging.
-    pub fn transaction_manager(&self) -> Arc<TransactionManager> {
-        Arc::clone(&self.transaction_manager)
+    /// Tags the concept, committing a new version that keeps its data, and returns its tags
+    /// afterwards. Tagging is a write like any other, so a concurrent change to the concept is
+    /// a `TransactionConflict`. Tags it already carries change nothing, and commit nothing.
+    /// System concepts can't be retagged.
+    pub async fn add_tags(
+        &self,
+        id: ConceptId,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<BTreeSet<String>> {
+        self.retag(id, tags.into_iter().map(Into::into).collect(), true).await
     }
 
-    /// Checks if the graph is empty and, if so, populates it with initial seed data.
-    /// This is an async function because our engine operations are async.
-    pub async fn seed_if_empty(&self) -> Result<()> {
-        let manager = self.transaction_manager();
-        let vs = manager.version_store();
+    /// Untags the concept, like `add_tags`. Tags it doesn't carry are ignored.
+    pub async fn remove_tags(
+        &self,
+        id: ConceptId,
+        tags: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<BTreeSet<String>> {
+        self.retag(id, tags.into_iter().map(Into::into).collect(), false).await
+    }
+
+    async fn retag(&self, id: ConceptId, tags: BTreeSet<String>, add: bool) -> Result<BTreeSet<String>> {
+        for tag in &tags {
+            concept::check_tag(tag)?;
+        }
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            let Some(latest) = manager.version_store().get_concept_version_at_timestamp(&id, txn.start_timestamp)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptNotFound(id));
+            };
+            if system::is_system(&latest.tags) {
+                manager.abort_transaction(txn.id)?;
+                return Err(system::refused(id));
+            }
+            let mut concept = concept_from_version(&latest);
+            if add {
+                concept.tags.extend(tags);
+            } else {
+                concept.tags.retain(|tag| !tags.contains(tag));
+            }
+            if concept.tags == latest.tags {
+                manager.abort_transaction(txn.id)?;
+                return Ok(latest.tags);
+            }
+            let retagged = concept.tags.clone();
+            manager.stage_concept_write(&mut txn, concept);
+            manager.commit_transaction(txn)?;
+            Ok(retagged)
+        })
+        .await
+    }
 
-        // This check is synchronous, so it doesn't need to be in a spawn_blocking task.
-        let all_concepts = vs.get_all_active_concepts()?;
+    /// MERGE: folds `absorb` into `keep` in one transaction. Every live relationship touching
+    /// `absorb` is tombstoned and re-created against `keep`, `keep` gets the data `strategy`
+    /// picks but keeps its own tags, and `absorb` is tombstoned. Edges between the two become
+    /// self-relationships, which are dropped. A concurrent change to either concept or to a rewired relationship
+    /// aborts the whole merge with a `TransactionConflict`. Neither concept may be a system concept.
+    pub async fn merge_concepts(
+        &self,
+        keep: ConceptId,
+        absorb: ConceptId,
+        strategy: MergeStrategy,
+    ) -> Result<MergeReport> {
+        self.merge_concepts_with(keep, absorb, strategy, MergeOptions::default()).await
+    }
 
-        // If the VersionStore is NOT empty, it means we've already seeded. Do nothing.
-        if !all_concepts.is_empty() {
-            tracing::info!("Graph already contains data. Skipping seed.");
-            return Ok(());
+    /// Like `merge_concepts`, but `options` can keep the resulting self-relationships.
+    pub async fn merge_concepts_with(
+        &self,
+        keep: ConceptId,
+        absorb: ConceptId,
+        strategy: MergeStrategy,
+        options: MergeOptions,
+    ) -> Result<MergeReport> {
+        if keep == absorb {
+            return Err(MnemonicError::Transaction(format!(
+                "Cannot merge concept {} into itself",
+                keep
+            )));
         }
+        let data_limits = self.data_limits.clone();
+        self.run_blocking(move |manager| {
+            // Serializable, so the edges and data we read can't change under us unnoticed.
+            let mut txn = manager.begin_transaction(IsolationLevel::Serializable)?;
 
-        tracing::info!("Graph is empty. Seeding with initial data...");
+            let Some(keep_version) = manager.read_concept(&mut txn, &keep)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptNotFound(keep));
+            };
+            let Some(absorb_version) = manager.read_concept(&mut txn, &absorb)? else {
+                manager.abort_transaction(txn.id)?;
+                return Err(MnemonicError::ConceptNotFound(absorb));
+            };
+            if let Some(system) = [&keep_version, &absorb_version].into_iter().find(|v| system::is_system(&v.tags)) {
+                manager.abort_transaction(txn.id)?;
+                return Err(system::refused(system.concept_id));
+            }
 
-        // --- Our Sample Data Story ---
+            if let Some(data) = strategy.merge(
+                &query::parse_data(&keep_version.data),
+                &query::parse_data(&absorb_version.data),
+            ) {
+                let mut merged = match Concept::new_within(data, &data_limits) {
+                    Ok(merged) => merged,
+                    Err(e) => {
+                        manager.abort_transaction(txn.id)?;
+                        return Err(e);
+                    }
+                };
+                merged.id = keep;
+                merged.tags = keep_version.tags.clone();
+                manager.stage_concept_write(&mut txn, merged);
+            }
+            manager.stage_concept_delete(&mut txn, absorb);
 
-        // 1. Create the core concepts.
-        let mnemonic = self
-            .store(json!({"name": "Mnemonic Computing", "type": "Project"}))
-            .await?;
-        let developer = self
-            .store(json!({"name": "4xmafole", "type": "Person"}))
-            .await?;
-        let ui = self
-            .store(json!({"name": "Explorer UI", "type": "Component"}))
-            .await?;
-        let backend = self
-            .store(json!({"name": "Core Engine", "type": "Component"}))
-            .await?;
+            // A self-relationship on `absorb` shows up in both lists.
+            let mut touching: Vec<RelationshipId> = Vec::new();
+            let store = manager.version_store();
+            let outgoing = store.outgoing_relationships(&absorb)?;
+            for rel in outgoing.into_iter().chain(store.incoming_relationships(&absorb)?) {
+                if !touching.contains(&rel.relationship_id) {
+                    touching.push(rel.relationship_id);
+                }
+            }
 
-        // 2. Create the relationships to tell the story.
-        self.relate(developer, "leads".to_string(), mnemonic)
-            .await?;
-        self.relate(mnemonic, "is_composed_of".to_string(), ui)
-            .await?;
-        self.relate(mnemonic, "is_composed_of".to_string(), backend)
-            .await?;
-        self.relate(
-            ui,
-            "is_written_in".to_string(),
-            self.store(json!({"name": "React", "type": "Technology"}))
-                .await?,
-        )
-        .await?;
-        self.relate(
-            backend,
-            "is_written_in".to_string(),
-            self.store(json!({"name": "Rust", "type": "Technology"}))
-                .await?,
-        )
-        .await?;
+            let mut report = MergeReport {
+                kept: keep,
+                absorbed: absorb,
+                relationships_rewired: HashMap::new(),
+                self_relationships_dropped: Vec::new(),
+            };
+            for rel_id in touching {
+                // Edges committed after we started aren't ours to move; the commit refuses
+                // to tombstone `absorb` while they point at it.
+                let Some(rel) = manager.read_relationship(&mut txn, &rel_id)? else {
+                    continue;
+                };
+                manager.stage_relationship_delete(&mut txn, rel_id);
 
-        tracing::info!("Seeding complete!");
+                let rewire = |endpoint: ConceptId| if endpoint == absorb { keep } else { endpoint };
+                let (source, target) = (rewire(rel.source), rewire(rel.target));
+                if source == target && !options.keep_self_relationships && rel.source != rel.target {
+                    report.self_relationships_dropped.push(rel_id);
+                    continue;
+                }
+                let replacement = Relationship { directed: rel.directed, ..Relationship::new(source, rel.relationship_type, target) };
+                report.relationships_rewired.insert(rel_id, replacement.id);
+                manager.stage_relationship_write(&mut txn, replacement);
+            }
 
-        Ok(())
+            manager.commit_transaction(txn)?;
+            Ok(report)
+        })
+        .await
     }
 
-    /// Retrieves the most recent active version of a single concept by its ID.
-    pub async fn get_concept(&self, id: ConceptId) -> Result<Option<Concept>> {
-        let manager = Arc::clone(&self.transaction_manager);
-
-        task::spawn_blocking(move || {
-            let now = chrono::Utc::now();
+    /// Basic RETRIEVE: Get all relationships originating from a concept.
+    pub async fn retrieve_by_source(&self, source_id: ConceptId) -> Result<Vec<Relationship>> {
+        self.run_blocking(move |manager| {
             let version_store = manager.version_store();
 
-            // Use the version store's time-travel ability.
-            if let Some(version) = version_store.get_concept_version_at_timestamp(&id, now)? {
-                // Convert the ConceptVersion back to a simple Concept for the API.
-                let concept = Concept {
-                    id: version.concept_id,
-                    data: version.data.clone(),
-                    metadata: crate::types::concept::ConceptMetadata {
-                        created_at: version.created_at,
-                        updated_at: version.created_at, // Simplification for this example
-                        version: version.version,
-                        transaction_id: version.created_by,
-                    },
-                };
-                Ok(Some(concept))
-            } else {
-                Ok(None)
-            }
+            // The adjacency index already knows which live relationships leave this concept.
+            let matching_rels: Vec<Relationship> = version_store
+                .outgoing_relationships(&source_id)?
+                .iter()
+                // Convert them back to the simple 'Relationship' type for the API.
+                .map(relationship_from_version)
+                .collect();
+
+            Ok(matching_rels)
+        })
+        .await
+    }
+    /// Get the active relationships of one type that leave a concept, straight from the
+    /// (concept, type) entries of the adjacency index.
+    pub async fn retrieve_by_source_and_type(
+        &self,
+        source_id: ConceptId,
+        relationship_type: impl Into<RelationType>,
+    ) -> Result<Vec<Relationship>> {
+        let relationship_type = relationship_type.into();
+        self.run_blocking(move |manager| {
+            Ok(manager
+                .version_store()
+                .outgoing_relationships_of_type(&source_id, &relationship_type)?
+                .iter()
+                .map(relationship_from_version)
+                .collect())
         })
         .await
-        .unwrap()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+    /// Get all active relationships pointing at a concept.
+    pub async fn retrieve_by_target(&self, target_id: ConceptId) -> Result<Vec<Relationship>> {
+        self.run_blocking(move |manager| {
+            Ok(manager
+                .version_store()
+                .incoming_relationships(&target_id)?
+                .iter()
+                .map(relationship_from_version)
+                .collect())
+        })
+        .await
+    }
 
-    #[tokio::test]
-    async fn test_engine_transactional_store_is_visible() {
-        let dir = tempdir().unwrap();
-        let engine = GraphEngine::new(dir.path()).unwrap();
+    /// Every version of a concept still held, oldest first, tombstones included.
+    pub async fn concept_history(&self, id: ConceptId) -> Result<Vec<ConceptVersion>> {
+        self.run_blocking(move |manager| {
+            let history = manager.version_store().concept_history(&id)?;
+            if history.is_empty() {
+                return Err(MnemonicError::ConceptNotFound(id));
+            }
+            Ok(history)
+        })
+        .await
+    }
+
+    /// Every version of a relationship still held, oldest first, including after it was unrelated.
+    pub async fn relationship_history(&self, id: RelationshipId) -> Result<Vec<RelationshipVersion>> {
+        self.run_blocking(move |manager| {
+            let history = manager.version_store().relationship_history(&id)?;
+            if history.is_empty() {
+                return Err(MnemonicError::RelationshipNotFound(id));
+            }
+            Ok(history)
+        })
+        .await
+    }
+
+    /// An active relationship with both its endpoints, all read at one watermark, so a commit
+    /// landing meanwhile can't show one end from before it and the other from after.
+    /// `RelationshipNotFound` if the relationship isn't active.
+    pub async fn relationship_with_endpoints(&self, id: RelationshipId) -> Result<RelationshipEndpoints> {
+        self.run_blocking(move |manager| {
+            let as_of = manager.watermark();
+            let version_store = manager.version_store();
+            let relationship = version_store
+                .get_relationship_version_at_timestamp(&id, as_of)?
+                .ok_or(MnemonicError::RelationshipNotFound(id))?;
+            let endpoint = |concept_id| -> Result<Option<ConceptVersion>> {
+                Ok(version_store
+                    .get_concept_version_at_timestamp(&concept_id, as_of)?
+                    .filter(|version| version.is_active_at(as_of)))
+            };
+            Ok(RelationshipEndpoints {
+                source: endpoint(relationship.source)?,
+                target: endpoint(relationship.target)?,
+                relationship,
+                as_of,
+            })
+        })
+        .await
+    }
+
+    /// Begin a new transaction
+    pub async fn begin_transaction(&self, isolation_level: IsolationLevel) -> Result<Transaction> {
+        self.run_blocking(move |manager| manager.begin_transaction(isolation_level)).await
+    }
+
+    /// Begin a new transaction that records who is making the change and why.
+    pub async fn begin_transaction_with_meta(
+        &self,
+        isolation_level: IsolationLevel,
+        metadata: TransactionMetadata,
+    ) -> Result<Transaction> {
+        self.run_blocking(move |manager| manager.begin_transaction_with_meta(isolation_level, metadata))
+            .await
+    }
+
+    /// Commit a transaction
+    pub async fn commit_transaction(&self, transaction: Transaction) -> Result<()> {
+        self.run_blocking(move |manager| manager.commit_transaction(transaction)).await
+    }
+
+    /// Like `commit_transaction`, but hands back what the commit wrote.
+    pub async fn commit_transaction_detailed(&self, transaction: Transaction) -> Result<CommitReceipt> {
+        self.run_blocking(move |manager| manager.commit_transaction_detailed(transaction)).await
+    }
+
+    /// Abort a transaction
+    pub async fn abort_transaction(&self, transaction_id: Uuid) -> Result<()> {
+        self.run_blocking(move |manager| manager.abort_transaction(transaction_id)).await
+    }
+
+    /// Looks up who committed a transaction and why, if that was recorded.
+    pub async fn transaction_metadata(
+        &self,
+        transaction_id: Uuid,
+    ) -> Result<Option<TransactionMetadata>> {
+        self.run_blocking(move |manager| {
+            manager
+                .version_store()
+                .get_transaction_metadata(&transaction_id)
+        })
+        .await
+    }
+
+    /// Lists all transactions that have begun but not yet committed or aborted.
+    pub async fn active_transactions(&self) -> Result<Vec<TransactionSummary>> {
+        self.run_blocking(move |manager| manager.list_active()).await
+    }
+
+    /// Looks up a single active transaction by its ID.
+    pub async fn active_transaction(&self, transaction_id: Uuid) -> Result<Option<TransactionSummary>> {
+        self.run_blocking(move |manager| manager.get_active(&transaction_id)).await
+    }
+
+    /// Cross-checks the in-memory version heads against what is on disk, without changing anything.
+    pub async fn verify_consistency(&self) -> Result<ConsistencyReport> {
+        self.run_blocking(move |manager| manager.check_consistency(false)).await
+    }
+
+    /// Like `verify_consistency`, but reloads every mismatched item's history from disk.
+    pub async fn repair_consistency(&self) -> Result<ConsistencyReport> {
+        self.run_blocking(move |manager| manager.check_consistency(true)).await
+    }
+
+    /// What changed in the live graph after `since`, a watermark from an earlier delta or
+    /// snapshot. Applying it, then the deltas after it in turn, keeps a copy of the graph
+    /// equal to the live one.
+    pub async fn graph_delta(&self, since: DateTime<Utc>) -> Result<GraphDelta> {
+        self.run_blocking(move |manager| manager.graph_delta(since)).await
+    }
+
+    /// Opens a read snapshot of the graph as it is now. Reads given its timestamp see the same
+    /// graph until it is released, and pruning and purging keep what it can see. One left
+    /// unused for `TransactionConfig::snapshot_idle_timeout` is released on its own.
+    pub async fn open_snapshot(&self) -> Result<ReadSnapshot> {
+        self.run_blocking(move |manager| Ok(manager.open_snapshot())).await
+    }
+
+    /// The timestamp an open snapshot reads at, or `SnapshotNotFound`. Each call counts as
+    /// using the snapshot.
+    pub async fn snapshot_timestamp(&self, token: SnapshotToken) -> Result<DateTime<Utc>> {
+        self.run_blocking(move |manager| manager.snapshot_timestamp(token)).await
+    }
+
+    /// Releases a read snapshot. `false` if it wasn't open.
+    pub async fn release_snapshot(&self, token: SnapshotToken) -> Result<bool> {
+        self.run_blocking(move |manager| Ok(manager.release_snapshot(token))).await
+    }
+
+    /// Checks disk, memory and the indexes against each other, as thoroughly as `depth` says,
+    /// and lists everything found wrong. Changes nothing; see `rebuild_derived_state`.
+    pub async fn check(&self, depth: CheckDepth) -> Result<CheckReport> {
+        self.run_blocking(move |manager| manager.check(depth)).await
+    }
+
+    /// Regenerates every index (adjacency, labels, tags, content hashes, the source and target
+    /// entries) from the version history, for when a bug or crash has left them stale.
+    pub async fn rebuild_derived_state(&self) -> Result<RebuildReport> {
+        self.run_blocking(move |manager| manager.rebuild_derived_state()).await
+    }
+
+    /// The value of the system concept `name` (see `types::system`), if one has been put.
+    pub async fn get_system(&self, name: &str) -> Result<Option<serde_json::Value>> {
+        let name = name.to_string();
+        self.run_blocking(move |manager| manager.get_system(&name)).await
+    }
+
+    /// Puts `value` in the system concept `name`, committing a new version of it like any
+    /// other write, so a concurrent put is a `TransactionConflict`. Putting the value it
+    /// already holds commits nothing.
+    pub async fn put_system(&self, name: &str, value: serde_json::Value) -> Result<()> {
+        system::check_name(name)?;
+        self.data_limits.check(&system::system_data(name, value.clone()))?;
+        let name = name.to_string();
+        self.run_blocking(move |manager| manager.put_system(&name, value)).await
+    }
+
+    /// Requires every concept labelled `label` to satisfy `schema` (see `types::schema` for
+    /// the supported keywords) from its next write on. The schema is persisted.
+    pub async fn register_schema(&self, label: &str, schema: serde_json::Value) -> Result<()> {
+        let label = label.to_string();
+        self.run_blocking(move |manager| manager.register_schema(&label, schema)).await
+    }
+
+    /// The schema registered for `label`, if any.
+    pub async fn schema(&self, label: &str) -> Result<Option<serde_json::Value>> {
+        let label = label.to_string();
+        self.run_blocking(move |manager| manager.schema(&label)).await
+    }
+
+    /// Limits how many active relationships of `relationship_type` a concept may take part in.
+    /// Later `relate` calls and commits that would exceed it fail with
+    /// `MnemonicError::CardinalityViolation`. The constraint is persisted; `Many` lifts it.
+    pub async fn set_relationship_constraint(
+        &self,
+        relationship_type: &str,
+        cardinality: Cardinality,
+    ) -> Result<()> {
+        let relationship_type = relationship_type.to_string();
+        self.run_blocking(move |manager| {
+            manager.set_relationship_constraint(&relationship_type, cardinality)
+        })
+        .await
+    }
+
+    /// The cardinality constraint on `relationship_type`.
+    pub async fn relationship_constraint(&self, relationship_type: &str) -> Result<Cardinality> {
+        let relationship_type = relationship_type.to_string();
+        self.run_blocking(move |manager| manager.relationship_constraint(&relationship_type))
+            .await
+    }
+
+    /// Registers a relationship type, or updates its description. The registration is
+    /// persisted. With `TransactionConfig::strict_relationship_types` set, relationships of
+    /// any other type fail with `MnemonicError::UnknownRelationshipType`.
+    pub async fn register_relationship_type(
+        &self,
+        name: &str,
+        description: &str,
+    ) -> Result<RelationshipTypeDefinition> {
+        let (name, description) = (name.to_string(), description.to_string());
+        self.run_blocking(move |manager| manager.register_relationship_type(&name, &description))
+            .await
+    }
+
+    /// Every registered relationship type, in name order, with how many active
+    /// relationships use it.
+    pub async fn relationship_types(&self) -> Result<Vec<RelationshipTypeUsage>> {
+        self.run_blocking(move |manager| {
+            let counts = manager.version_store().count_active_relationships_by_type()?;
+            Ok(manager
+                .relationship_types()?
+                .into_iter()
+                .map(|definition| RelationshipTypeUsage {
+                    active_relationships: counts.get(&definition.name).copied().unwrap_or(0),
+                    name: definition.name,
+                    description: definition.description,
+                    registered_at: definition.registered_at,
+                })
+                .collect())
+        })
+        .await
+    }
+
+    /// A page of the audit log: every version committed in the query's window, oldest first,
+    /// with the actor its transaction recorded. Pruned and purged versions are gone from it.
+    pub async fn audit_log(&self, query: AuditQuery) -> Result<AuditPage> {
+        self.run_blocking(move |manager| manager.version_store().audit_log(&query)).await
+    }
+
+    /// Counts what was committed in each `width` bucket from `from` up to `to`, for charting
+    /// the graph's growth; see `VersionStore::activity`.
+    pub async fn activity(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        width: BucketWidth,
+    ) -> Result<Vec<ActivityBucket>> {
+        self.run_blocking(move |manager| manager.version_store().activity(from, to, width)).await
+    }
+
+    /// Removes old versions from memory and disk as `policy` allows.
+    /// Fails with `MnemonicError::PruneBlocked` if an active transaction still reads one of them.
+    pub async fn prune_versions(&self, policy: RetentionPolicy) -> Result<PruneReport> {
+        self.run_blocking(move |manager| manager.prune_versions(&policy)).await
+    }
+
+    /// PURGE: erases a concept and every relationship that ever touched it, history included,
+    /// from memory and disk. Unlike `delete_concept`, which only tombstones the concept and
+    /// keeps it for time travel, afterwards even historical queries find nothing.
+    /// Fails with `MnemonicError::PurgeBlocked` while an active transaction could still read
+    /// the concept. Transaction metadata is shared between items and is left alone.
+    pub async fn purge_concept(&self, id: ConceptId) -> Result<PurgeReport> {
+        self.purge_concept_with(id, PurgeOptions::default()).await
+    }
+
+    /// Like `purge_concept`, but `options` can keep the relationships' history.
+    pub async fn purge_concept_with(&self, id: ConceptId, options: PurgeOptions) -> Result<PurgeReport> {
+        self.run_blocking(move |manager| manager.purge_concept(id, &options)).await
+    }
+
+    /// Tombstones every active concept whose data matches `filter`, in batched transactions.
+    /// `options` defaults to a dry run, which only lists the matches. A batch that conflicts
+    /// is reported and the rest still run.
+    pub async fn delete_where(&self, filter: Filter, options: BulkDeleteOptions) -> Result<BulkDeleteReport> {
+        self.delete_where_with_meta(filter, options, TransactionMetadata::default()).await
+    }
+
+    /// Like `delete_where`, but every batch records who made the change and why.
+    pub async fn delete_where_with_meta(
+        &self,
+        filter: Filter,
+        options: BulkDeleteOptions,
+        metadata: TransactionMetadata,
+    ) -> Result<BulkDeleteReport> {
+        self.run_blocking(move |manager| manager.delete_where(&filter, &options, &metadata)).await
+    }
+
+    /// Up to `limit` commits after `after` (from the first, if `None`), oldest first, with
+    /// every version each one wrote. What a follower replicates from.
+    pub async fn changes(&self, after: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<CommitEvent>> {
+        self.run_blocking(move |manager| manager.changes(after, limit)).await
+    }
+
+    /// Follows a leader: applies each commit `source` yields, with the leader's IDs, version
+    /// numbers and timestamps, until the source ends or fails. Returns how many were applied.
+    /// Local commits fail with `ReadOnlyReplica` meanwhile. Dropping the future stops it, and
+    /// the next call picks up after the last commit applied.
+    pub async fn replicate_from(&self, mut source: impl ChangeSource) -> Result<usize> {
+        let manager = self.transaction_manager();
+        let _replica = manager.begin_replication()?;
+        let mut applied = 0;
+        while let Some(commits) = source.next_commits(manager.replicated_through()).await? {
+            applied += self
+                .run_blocking(move |manager| {
+                    let mut applied = 0;
+                    for commit in &commits {
+                        if manager.apply_replicated_commit(commit)? {
+                            applied += 1;
+                        }
+                    }
+                    Ok(applied)
+                })
+                .await?;
+        }
+        Ok(applied)
+    }
+
+    /// Compacts the whole database, reclaiming the space of deleted and pruned records. With
+    /// `StorageOptions::version_compaction` on, the history of long-deleted items goes too.
+    pub async fn compact_storage(&self) -> Result<()> {
+        let backend = Arc::clone(&self.backend);
+        self.run_blocking(move |manager| {
+            manager.publish_compaction_horizon()?;
+            backend.compact_all()
+        })
+        .await
+    }
+
+    /// The storage backend's slow-operation count and latest statistics sample.
+    pub fn storage_metrics(&self) -> &StorageMetrics {
+        self.backend.metrics()
+    }
+
+    /// Reports disk usage per column family, with the latest statistics sample. `exact` also
+    /// counts every key, which is slow.
+    pub async fn storage_stats(&self, exact: bool) -> Result<StorageStats> {
+        let backend = Arc::clone(&self.backend);
+        self.run_blocking(move |_| backend.stats(exact)).await
+    }
+
+    /// Runs `f` on the worker pool with a handle to the TransactionManager.
+    /// If `f` panics (or the task is cancelled), the caller gets `MnemonicError::Internal`
+    /// back instead of the panic tearing down its own task.
+    async fn run_blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<TransactionManager>) -> Result<T> + Send + 'static,
+    {
+        let manager = Arc::clone(&self.transaction_manager);
+        self.worker_pool.run(move || f(manager)).await
+    }
+
+    /// `run_blocking` for work that checks `deadline`, first of all before it starts. The
+    /// deadline is cancelled if the caller stops waiting, so abandoned work gives its worker
+    /// back at its next check.
+    async fn run_blocking_within<T, F>(&self, deadline: Deadline, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<TransactionManager>, Deadline) -> Result<T> + Send + 'static,
+    {
+        let guard = deadline.cancel_on_drop();
+        let result = self
+            .run_blocking(move |manager| {
+                deadline.check()?;
+                f(manager, deadline)
+            })
+            .await;
+        guard.disarm();
+        result
+    }
+
+    /// A deadline `EngineConfig::operation_timeout` from now, the one long operations get
+    /// unless they are given their own.
+    pub fn deadline(&self) -> Deadline {
+        Deadline::within(self.operation_timeout)
+    }
+
+    /// Returns the pool all blocking engine work runs on.
+    pub fn worker_pool(&self) -> Arc<WorkerPool> {
+        Arc::clone(&self.worker_pool)
+    }
+
+    /// Reports whether any lock had to be recovered from a panicking thread.
+    pub fn health(&self) -> EngineHealth {
+        let lock_recoveries = self.transaction_manager.lock_health().recoveries();
+        let observer_failures = self.transaction_manager.version_store().observer_failures();
+        EngineHealth {
+            healthy: lock_recoveries == 0 && observer_failures.is_empty(),
+            lock_recoveries,
+            observer_failures,
+        }
+    }
+
+    /// Keeps `observer` up to date with every version: those already held, then every one
+    /// committed or loaded from disk. See `VersionStore::register_observer`.
+    pub fn register_observer(&self, observer: Box<dyn VersionObserver>) {
+        self.transaction_manager.version_store().register_observer(observer);
+    }
+
+    /// Whether the whole version history is in memory. Only false while an engine started
+    /// with `HydrationMode::Background` is still warming up.
+    pub fn warmed(&self) -> bool {
+        self.transaction_manager.version_store().is_warm()
+    }
+
+    /// The `top_n` concepts commits have most often conflicted on lately, hottest first, each
+    /// with a score that counts a conflict as one and halves every five minutes.
+    pub fn hot_spots(&self, top_n: usize) -> Vec<(ConceptId, f64)> {
+        self.transaction_manager.metrics().conflict_hot_spots.top(top_n)
+    }
+
+    /// Listens for every commit from now on, in commit order. A listener that falls too far
+    /// behind gets `RecvError::Lagged` and should catch up from the version history.
+    pub fn subscribe_commits(&self) -> broadcast::Receiver<Arc<CommitEvent>> {
+        self.transaction_manager.subscribe_commits()
+    }
+
+    /// What concept data the store paths accept.
+    pub fn data_limits(&self) -> &DataLimits {
+        &self.data_limits
+    }
+
+    /// Returns a thread-safe handle to the internal TransactionManager.
+    /// This is useful for advanced operations or for testing and debugging.
+    pub fn transaction_manager(&self) -> Arc<TransactionManager> {
+        Arc::clone(&self.transaction_manager)
+    }
+
+    /// The number of the latest commit: a cheap tag for the state of the whole graph, which
+    /// changes with every commit. Doesn't touch the worker pool. A purge erases without
+    /// committing, so pair it with `history_erased_at` to name the graph's state.
+    pub fn latest_commit_sequence(&self) -> u64 {
+        self.transaction_manager.latest_commit_sequence()
+    }
+
+    /// When a purge last erased history, if one ever has.
+    pub fn history_erased_at(&self) -> Option<DateTime<Utc>> {
+        self.transaction_manager.history_erased_at()
+    }
+
+    /// When the latest version still held was committed, if any is.
+    pub fn last_changed_at(&self) -> Option<DateTime<Utc>> {
+        self.transaction_manager.version_store().last_changed_at()
+    }
+
+    /// Returns a thread-safe handle to the underlying storage backend.
+    pub fn backend(&self) -> Arc<RocksBackend> {
+        Arc::clone(&self.backend)
+    }
+
+    /// Writes `fixture` in a single transaction if the graph has no active concepts other
+    /// than system concepts, and does nothing otherwise, so seeding on every start-up is safe.
+    pub async fn seed_if_empty(&self, fixture: SeedFixture) -> Result<SeedReport> {
+        fixture.validate()?;
+        self.run_blocking(move |manager| {
+            let mut seeded = false;
+            manager.version_store().scan_active_concepts(&ScanMeter::unlimited(), |version, _| {
+                seeded = !system::is_system(&version.tags);
+                if seeded { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            })?;
+            if seeded {
+                tracing::info!("Graph already contains data. Skipping seed.");
+                return Ok(SeedReport {
+                    skipped: true,
+                    ..Default::default()
+                });
+            }
+
+            tracing::info!("Graph is empty. Seeding with initial data...");
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            for seed in &fixture.concepts {
+                let mut concept = Concept::new(seed.data.clone());
+                concept.id = seed.id;
+                manager.stage_concept_write(&mut txn, concept);
+            }
+            for seed in &fixture.relationships {
+                let relationship =
+                    Relationship::new(seed.source, seed.relationship_type.clone(), seed.target);
+                manager.stage_relationship_write(&mut txn, relationship);
+            }
+            manager.commit_transaction(txn)?;
+            tracing::info!("Seeding complete!");
+
+            Ok(SeedReport {
+                concepts_created: fixture.concepts.len(),
+                relationships_created: fixture.relationships.len(),
+                skipped: false,
+            })
+        })
+        .await
+    }
+
+    /// Lists active concepts a page at a time, in ascending ID order.
+    /// Because pages resume after the last ID seen, concepts created between pages never cause
+    /// duplicates or gaps among the rest.
+    pub async fn list_concepts(&self, options: ConceptListOptions) -> Result<ConceptPage> {
+        self.list_concepts_within(options, None, self.deadline()).await
+    }
+
+    /// `list_concepts` as the graph stood at `timestamp`, e.g. a snapshot's, so that every
+    /// page of a listing comes from the same moment.
+    pub async fn list_concepts_at(&self, options: ConceptListOptions, timestamp: DateTime<Utc>) -> Result<ConceptPage> {
+        self.list_concepts_within(options, Some(timestamp), self.deadline()).await
+    }
+
+    /// `list_concepts`, or `list_concepts_at` when `at` is given, failing once `deadline`
+    /// passes, e.g. while a label filter skips past a long run of other concepts.
+    pub async fn list_concepts_within(
+        &self,
+        options: ConceptListOptions,
+        at: Option<DateTime<Utc>>,
+        deadline: Deadline,
+    ) -> Result<ConceptPage> {
+        self.list_concepts_budgeted(options, at, deadline, ScanBudget::unlimited()).await
+    }
+
+    /// `list_concepts_within`, looking at no more concepts than `budget` allows. A page the
+    /// budget cut short is marked truncated, and its cursor resumes after the last concept
+    /// looked at, so the next request carries on the search.
+    pub async fn list_concepts_budgeted(
+        &self,
+        options: ConceptListOptions,
+        at: Option<DateTime<Utc>>,
+        deadline: Deadline,
+        budget: ScanBudget,
+    ) -> Result<ConceptPage> {
+        self.run_blocking_within(deadline, move |manager, deadline| {
+            let meter = budget.start();
+            let limit = options.page_size();
+            let label = options.label;
+            let tag = options.tag;
+            let search = options.search.map(|text| text.to_lowercase());
+            let predicate = options.predicate;
+            let include_system = options.include_system;
+            let stopped = Cell::new(None);
+            let wanted = |version: &ConceptVersion, created_at| {
+                if let Err(e) = deadline.check() {
+                    stopped.set(Some(e));
+                    return false;
+                }
+                (include_system || !system::is_system(&version.tags))
+                    && tag.as_ref().is_none_or(|tag| version.tags.contains(tag))
+                    && label.as_ref().is_none_or(|label| {
+                        query::labels_of(&query::parse_data(&version.data)).contains(label)
+                    })
+                    && search.as_ref().is_none_or(|text| query::mentions(&query::parse_data(&version.data), text))
+                    && predicate.as_ref().is_none_or(|predicate| predicate.evaluate_created(version, created_at))
+            };
+            // Fetch one extra to find out whether there is another page.
+            let version_store = manager.version_store();
+            let cursor = options.cursor.as_ref();
+            let (mut page, last_seen) = match at {
+                Some(timestamp) => version_store.get_active_concepts_page_at(cursor, limit + 1, timestamp, &meter, wanted)?,
+                None => version_store.get_active_concepts_page(cursor, limit + 1, &meter, wanted)?,
+            };
+            if let Some(e) = stopped.take() {
+                return Err(e);
+            }
+
+            let has_more = page.len() > limit;
+            page.truncate(limit);
+            let concepts: Vec<ConceptListing> = page
+                .iter()
+                .map(|(version, created_at)| ConceptListing::new(version, *created_at))
+                .collect();
+            let next_cursor = if has_more {
+                concepts.last().map(|concept| concept.id)
+            } else if meter.is_spent() {
+                last_seen
+            } else {
+                None
+            };
+            Ok(ConceptPage {
+                concepts,
+                next_cursor,
+                truncated: meter.is_spent(),
+            })
+        })
+        .await
+    }
+
+    /// Retrieves the most recent active version of a single concept by its ID.
+    pub async fn get_concept(&self, id: ConceptId) -> Result<Option<Concept>> {
+        self.run_blocking(move |manager| {
+            let version_store = manager.version_store();
+
+            // We only care about "now", so skip the time-travel search.
+            Ok(version_store
+                .get_latest_active_concept_version(&id)?
+                .as_ref()
+                .map(concept_from_version))
+        })
+        .await
+    }
+
+    /// The version of a concept that was active at `timestamp`, e.g. a snapshot's.
+    pub async fn get_concept_at(&self, id: ConceptId, timestamp: DateTime<Utc>) -> Result<Option<Concept>> {
+        self.run_blocking(move |manager| {
+            Ok(manager
+                .version_store()
+                .get_concept_version_at_timestamp(&id, timestamp)?
+                .as_ref()
+                .map(concept_from_version))
+        })
+        .await
+    }
+
+    /// `get_concept` for many concepts under one read lock, for callers that would otherwise
+    /// look endpoints up one at a time. The result lines up with `ids`.
+    pub async fn get_concepts(&self, ids: Vec<ConceptId>) -> Result<Vec<Option<Concept>>> {
+        self.run_blocking(move |manager| {
+            Ok(manager
+                .version_store()
+                .get_latest_active_concepts(&ids)?
+                .iter()
+                .map(|version| version.as_ref().map(concept_from_version))
+                .collect())
+        })
+        .await
+    }
+
+    /// The active concepts carrying `tag`, in ID order.
+    pub async fn find_by_tag(&self, tag: &str) -> Result<Vec<Concept>> {
+        let tag = tag.to_string();
+        self.run_blocking(move |manager| {
+            let version_store = manager.version_store();
+            let mut ids = version_store.concepts_with_tag(&tag)?;
+            ids.sort();
+            Ok(version_store
+                .get_latest_active_concepts(&ids)?
+                .iter()
+                .flatten()
+                .map(concept_from_version)
+                .collect())
+        })
+        .await
+    }
+
+    /// How many concepts are active.
+    pub async fn count_concepts(&self) -> Result<u64> {
+        self.run_blocking(move |manager| manager.version_store().count_active_concepts())
+            .await
+    }
+
+    /// How many relationships are active.
+    pub async fn count_relationships(&self) -> Result<u64> {
+        self.run_blocking(move |manager| manager.version_store().count_active_relationships())
+            .await
+    }
+
+    /// How many active relationships touch a concept in the given direction.
+    pub async fn degree(&self, id: ConceptId, direction: Direction) -> Result<u64> {
+        self.run_blocking(move |manager| {
+            let version_store = manager.version_store();
+            if version_store.get_latest_active_concept_version(&id)?.is_none() {
+                return Err(MnemonicError::ConceptNotFound(id));
+            }
+            version_store.degree(&id, direction)
+        })
+        .await
+    }
+
+    /// How many active relationships there are of each type.
+    pub async fn count_by_relationship_type(&self) -> Result<HashMap<RelationType, u64>> {
+        self.run_blocking(move |manager| {
+            manager.version_store().count_active_relationships_by_type()
+        })
+        .await
+    }
+
+    /// All of the counts above in one go, with the version store's own numbers.
+    pub async fn stats(&self) -> Result<GraphStats> {
+        self.run_blocking(move |manager| {
+            let version_store = manager.version_store();
+            Ok(GraphStats {
+                concepts: version_store.count_active_concepts()?,
+                relationships: version_store.count_active_relationships()?,
+                relationships_by_type: version_store.count_active_relationships_by_type()?,
+                versions: version_store.stats()?,
+                conflict_hot_spots: manager.metrics().conflict_hot_spots.top(STATS_HOT_SPOTS),
+                warmed: version_store.is_warm(),
+            })
+        })
+        .await
+    }
+
+    /// Groups active concepts into connected components, largest first. `Direction::Both`
+    /// ignores edge direction (weak components); the others give strongly connected ones.
+    pub async fn connected_components(&self, direction: Direction) -> Result<Vec<Vec<ConceptId>>> {
+        self.run_blocking(move |manager| {
+            analysis::connected_components(&manager.version_store(), direction, None)
+        })
+        .await
+    }
+
+    /// Like `connected_components`, but only relationships of the given types join concepts.
+    pub async fn connected_components_with_types(
+        &self,
+        direction: Direction,
+        types: Vec<RelationType>,
+    ) -> Result<Vec<Vec<ConceptId>>> {
+        let types: HashSet<RelationType> = types.into_iter().collect();
+        self.run_blocking(move |manager| {
+            analysis::connected_components(&manager.version_store(), direction, Some(&types))
+        })
+        .await
+    }
+
+    /// Every active concept reachable from `id` in the given direction, `id` included.
+    pub async fn reachable_from(
+        &self,
+        id: ConceptId,
+        direction: Direction,
+    ) -> Result<HashSet<ConceptId>> {
+        self.run_blocking(move |manager| {
+            analysis::reachable_from(&manager.version_store(), id, direction, None)
+        })
+        .await
+    }
+
+    /// Like `reachable_from`, but only over relationships of the given types.
+    pub async fn reachable_from_with_types(
+        &self,
+        id: ConceptId,
+        direction: Direction,
+        types: Vec<RelationType>,
+    ) -> Result<HashSet<ConceptId>> {
+        let types: HashSet<RelationType> = types.into_iter().collect();
+        self.run_blocking(move |manager| {
+            analysis::reachable_from(&manager.version_store(), id, direction, Some(&types))
+        })
+        .await
+    }
+
+    /// One cycle formed by relationships of the given type, in edge order, if there is any.
+    pub async fn find_cycle(&self, relationship_type: &RelationType) -> Result<Option<Vec<ConceptId>>> {
+        let relationship_type = relationship_type.clone();
+        self.run_blocking(move |manager| {
+            analysis::find_cycle(&manager.version_store(), &relationship_type)
+        })
+        .await
+    }
+
+    /// Orders the concepts joined by relationships of the given type so that every source
+    /// comes before its targets. Fails with `CycleDetected` when no such order exists.
+    pub async fn topological_order(&self, relationship_type: &RelationType) -> Result<Vec<ConceptId>> {
+        let relationship_type = relationship_type.clone();
+        self.run_blocking(move |manager| {
+            analysis::topological_order(&manager.version_store(), &relationship_type)
+        })
+        .await
+    }
+
+    /// Counts active concepts by the value at `group_by` in their data, optionally only those
+    /// passing `filter`, leaving out system concepts. See `query::group_key` for how values
+    /// become group names.
+    /// Concepts are visited a chunk at a time rather than copied out all at once.
+    pub async fn aggregate(
+        &self,
+        group_by: &str,
+        filter: Option<Filter>,
+    ) -> Result<HashMap<String, u64>> {
+        self.aggregate_budgeted(group_by, filter, ScanBudget::unlimited()).await
+    }
+
+    /// `aggregate`, failing with `ScanBudgetExceeded` if counting every concept would take
+    /// more than `budget`; counts of part of the graph would look like counts of all of it.
+    pub async fn aggregate_budgeted(
+        &self,
+        group_by: &str,
+        filter: Option<Filter>,
+        budget: ScanBudget,
+    ) -> Result<HashMap<String, u64>> {
+        let group_by = group_by.to_string();
+        self.run_blocking(move |manager| {
+            let meter = budget.start();
+            let mut groups = HashMap::new();
+            manager.version_store().scan_active_concepts(&meter, |version, created_at| {
+                if system::is_system(&version.tags) {
+                    return ControlFlow::Continue(());
+                }
+                let data = query::parse_data(&version.data);
+                if filter.as_ref().is_none_or(|filter| filter.matches(version, &data, created_at)) {
+                    let key = query::group_key(query::lookup(&data, &group_by));
+                    *groups.entry(key).or_default() += 1;
+                }
+                ControlFlow::Continue(())
+            })?;
+            meter.ensure_whole()?;
+            Ok(groups)
+        })
+        .await
+    }
+
+    /// Stores `entity` as a new concept labelled `T::label()`.
+    pub async fn store_entity<T: ConceptEntity>(&self, entity: &T) -> Result<ConceptId> {
+        self.store(entity::entity_data(entity)?).await
+    }
+
+    /// The active concept `id` as a `T`; `EntityMismatch` if it isn't labelled `T::label()`
+    /// or its data doesn't deserialize as one.
+    pub async fn get_entity<T: ConceptEntity>(&self, id: ConceptId) -> Result<Option<T>> {
+        match self.get_concept(id).await? {
+            Some(concept) => entity::entity_from_data(id, query::parse_data(&concept.data)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Every active concept labelled `T::label()` whose data passes `filter`, in ID order.
+    /// One that doesn't deserialize as a `T` fails the whole call with `EntityMismatch`.
+    pub async fn find_entities<T: ConceptEntity>(&self, filter: Option<Filter>) -> Result<Vec<(ConceptId, T)>> {
+        let label = T::label();
+        let matches = self
+            .run_blocking(move |manager| {
+                let mut matches = Vec::new();
+                manager.version_store().scan_active_concepts(&ScanMeter::unlimited(), |version, created_at| {
+                    let data = query::parse_data(&version.data);
+                    if query::labels_of(&data).iter().any(|l| l == label)
+                        && filter.as_ref().is_none_or(|filter| filter.matches(version, &data, created_at))
+                    {
+                        matches.push((version.concept_id, data));
+                    }
+                    ControlFlow::Continue(())
+                })?;
+                Ok(matches)
+            })
+            .await?;
+        matches
+            .into_iter()
+            .map(|(id, data)| Ok((id, entity::entity_from_data(id, data)?)))
+            .collect()
+    }
+
+    /// Relates `source` to `target` once both are checked, inside the committing transaction,
+    /// to carry `S::label()` and `T::label()`; `EntityMismatch` names the labels found instead.
+    /// The endpoints are serializable reads, so relabelling one concurrently is a conflict.
+    pub async fn relate_entities<S: ConceptEntity, T: ConceptEntity>(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+    ) -> Result<RelationshipId> {
+        let relationship_type = relationship_type.into();
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Serializable)?;
+            let read_endpoint = |id: ConceptId| -> Result<serde_json::Value> {
+                match manager.version_store().get_concept_version_at_timestamp(&id, txn.start_timestamp)? {
+                    Some(version) => Ok(query::parse_data(&version.data)),
+                    None => Err(MnemonicError::ConceptNotFound(id)),
+                }
+            };
+            let checked = read_endpoint(source)
+                .and_then(|data| entity::require_label::<S>(source, &data))
+                .and_then(|()| read_endpoint(target))
+                .and_then(|data| entity::require_label::<T>(target, &data));
+            // Don't leave the transaction dangling in the active list if we bail out early.
+            if let Err(err) = checked {
+                manager.abort_transaction(txn.id)?;
+                return Err(err);
+            }
+            manager.note_concept_read(&mut txn, source);
+            manager.note_concept_read(&mut txn, target);
+
+            let new_rel = Relationship::new(source, relationship_type, target);
+            let rel_id = new_rel.id;
+            manager.stage_relationship_write(&mut txn, new_rel);
+            manager.commit_transaction(txn)?;
+            Ok(rel_id)
+        })
+        .await
+    }
+
+    /// The targets of `id`'s active `relationship_type` edges as `T`s, beside the edges' IDs.
+    /// A target that isn't a `T` fails the whole call with `EntityMismatch`.
+    pub async fn neighbors_of_type<T: ConceptEntity>(
+        &self,
+        id: ConceptId,
+        relationship_type: impl Into<RelationType>,
+    ) -> Result<Vec<(RelationshipId, T)>> {
+        let relationship_type = relationship_type.into();
+        let targets = self
+            .run_blocking(move |manager| {
+                let version_store = manager.version_store();
+                let mut targets = Vec::new();
+                for rel in version_store.outgoing_relationships_of_type(&id, &relationship_type)? {
+                    if let Some(target) = version_store.get_latest_active_concept_version(&rel.target)? {
+                        targets.push((rel.relationship_id, rel.target, query::parse_data(&target.data)));
+                    }
+                }
+                Ok(targets)
+            })
+            .await?;
+        targets
+            .into_iter()
+            .map(|(rel_id, target, data)| Ok((rel_id, entity::entity_from_data(target, data)?)))
+            .collect()
+    }
+
+    /// Collects every active concept within `depth` hops of any root, either way along
+    /// relationships of the given types (any type when `None`), plus the relationships among
+    /// them. Roots that aren't active concepts are listed in `missing_roots`.
+    pub async fn subgraph(
+        &self,
+        roots: Vec<ConceptId>,
+        depth: u32,
+        types: Option<Vec<RelationType>>,
+    ) -> Result<GraphSnapshot> {
+        let types: Option<HashSet<RelationType>> = types.map(|types| types.into_iter().collect());
+        self.run_blocking(move |manager| {
+            let version_store = manager.version_store();
+            let (collected, missing_roots) =
+                analysis::neighborhood(&version_store, &roots, depth, types.as_ref())?;
+            let relationships = analysis::relationships_among(&version_store, &collected, types.as_ref());
+            Ok(GraphSnapshot {
+                // A concept deleted since the walk simply drops out.
+                concepts: version_store
+                    .get_latest_active_concepts(&collected)?
+                    .iter()
+                    .flatten()
+                    .map(concept_from_version)
+                    .collect(),
+                relationships: relationships.iter().map(relationship_from_version).collect(),
+                missing_roots,
+            })
+        })
+        .await
+    }
+
+    /// A shortest path from `from` to `to` along outgoing relationships of the given types
+    /// (any type when `None`). Both ends must be active concepts; an empty `Traversal` means
+    /// no path was found within `limits`.
+    pub async fn shortest_path(
+        &self,
+        from: ConceptId,
+        to: ConceptId,
+        types: Option<Vec<RelationType>>,
+        limits: TraversalLimits,
+    ) -> Result<Traversal> {
+        self.shortest_path_within(from, to, types, limits, self.deadline()).await
+    }
+
+    /// `shortest_path`, failing once `deadline` passes.
+    pub async fn shortest_path_within(
+        &self,
+        from: ConceptId,
+        to: ConceptId,
+        types: Option<Vec<RelationType>>,
+        limits: TraversalLimits,
+        deadline: Deadline,
+    ) -> Result<Traversal> {
+        self.shortest_path_budgeted(from, to, types, limits, deadline, ScanBudget::unlimited()).await
+    }
+
+    /// `shortest_path_within`, expanding no more concepts than `budget` allows. Running out
+    /// is a truncated search, like reaching `limits.max_concepts`.
+    pub async fn shortest_path_budgeted(
+        &self,
+        from: ConceptId,
+        to: ConceptId,
+        types: Option<Vec<RelationType>>,
+        limits: TraversalLimits,
+        deadline: Deadline,
+        budget: ScanBudget,
+    ) -> Result<Traversal> {
+        let types: Option<HashSet<RelationType>> = types.map(|types| types.into_iter().collect());
+        self.run_blocking_within(deadline, move |manager, deadline| {
+            let version_store = manager.version_store();
+            let (path, truncated) = analysis::shortest_path(
+                &version_store,
+                from,
+                to,
+                limits,
+                types.as_ref(),
+                &deadline,
+                &budget.start(),
+            )?;
+            let Some((concepts, relationships)) = path else {
+                return Ok(Traversal { truncated, ..Default::default() });
+            };
+            Ok(Traversal {
+                concepts: version_store
+                    .get_latest_active_concepts(&concepts)?
+                    .iter()
+                    .flatten()
+                    .map(concept_from_version)
+                    .collect(),
+                relationships: relationships
+                    .iter()
+                    .map(|rel_id| version_store.get_latest_active_relationship_version(rel_id))
+                    .collect::<Result<Vec<_>>>()?
+                    .iter()
+                    .flatten()
+                    .map(relationship_from_version)
+                    .collect(),
+                truncated,
+            })
+        })
+        .await
+    }
+
+    /// The active concepts within `limits.max_depth` hops of `id`, either way along
+    /// relationships of the given types, nearest first, and the relationships among them.
+    pub async fn neighborhood(
+        &self,
+        id: ConceptId,
+        types: Option<Vec<RelationType>>,
+        limits: TraversalLimits,
+    ) -> Result<Traversal> {
+        self.neighborhood_within(id, types, limits, self.deadline()).await
+    }
+
+    /// `neighborhood`, failing once `deadline` passes.
+    pub async fn neighborhood_within(
+        &self,
+        id: ConceptId,
+        types: Option<Vec<RelationType>>,
+        limits: TraversalLimits,
+        deadline: Deadline,
+    ) -> Result<Traversal> {
+        self.neighborhood_budgeted(id, types, limits, deadline, ScanBudget::unlimited()).await
+    }
+
+    /// `neighborhood_within`, expanding no more concepts than `budget` allows. Running out
+    /// truncates the walk, like reaching `limits.max_concepts`.
+    pub async fn neighborhood_budgeted(
+        &self,
+        id: ConceptId,
+        types: Option<Vec<RelationType>>,
+        limits: TraversalLimits,
+        deadline: Deadline,
+        budget: ScanBudget,
+    ) -> Result<Traversal> {
+        let types: Option<HashSet<RelationType>> = types.map(|types| types.into_iter().collect());
+        self.run_blocking_within(deadline, move |manager, deadline| {
+            let version_store = manager.version_store();
+            let (collected, missing, truncated) = analysis::bounded_neighborhood(
+                &version_store,
+                &[id],
+                limits.max_depth,
+                types.as_ref(),
+                limits.max_concepts,
+                &deadline,
+                &budget.start(),
+            )?;
+            if !missing.is_empty() {
+                return Err(MnemonicError::ConceptNotFound(id));
+            }
+            let relationships = analysis::relationships_among(&version_store, &collected, types.as_ref());
+            Ok(Traversal {
+                concepts: version_store
+                    .get_latest_active_concepts(&collected)?
+                    .iter()
+                    .flatten()
+                    .map(concept_from_version)
+                    .collect(),
+                relationships: relationships.iter().map(relationship_from_version).collect(),
+                truncated,
+            })
+        })
+        .await
+    }
+
+    /// Runs a declarative query (see `graph::query`), examining no more concepts and edges
+    /// than `budget` allows before giving up with a truncated result.
+    pub async fn query(&self, query: GraphQuery, budget: ScanBudget) -> Result<QueryResult> {
+        self.run_blocking(move |manager| graph_query::execute(&manager.version_store(), &query, &budget.start()))
+            .await
+    }
+
+    /// The whole active graph. Relationships left pointing at deleted concepts are left out,
+    /// so the snapshot is self-contained.
+    pub async fn snapshot(&self) -> Result<GraphSnapshot> {
+        self.run_blocking(move |manager| {
+            let version_store = manager.version_store();
+            let concepts = version_store.get_all_active_concepts()?;
+            let ids: Vec<ConceptId> = concepts.iter().map(|v| v.concept_id).collect();
+            Ok(GraphSnapshot {
+                relationships: analysis::relationships_among(&version_store, &ids, None)
+                    .iter()
+                    .map(relationship_from_version)
+                    .collect(),
+                concepts: concepts.iter().map(concept_from_version).collect(),
+                missing_roots: Vec::new(),
+            })
+        })
+        .await
+    }
+
+    /// The graph as it stood at `timestamp`: every concept active then, and the relationships
+    /// active then between them.
+    pub async fn snapshot_at(&self, timestamp: DateTime<Utc>) -> Result<GraphSnapshot> {
+        self.snapshot_at_within(timestamp, self.deadline()).await
+    }
+
+    /// `snapshot_at`, failing once `deadline` passes.
+    pub async fn snapshot_at_within(&self, timestamp: DateTime<Utc>, deadline: Deadline) -> Result<GraphSnapshot> {
+        self.run_blocking_within(deadline, move |manager, deadline| {
+            GraphSnapshot::at_paced(&manager.version_store(), timestamp, || deadline.check())
+        })
+        .await
+    }
+
+    /// Writes the whole graph to `writer` as JSON Lines, as it stood when the export began
+    /// (the report's `as_of`), however much is committed while it runs.
+    pub async fn export_jsonl(&self, writer: impl Write) -> Result<ExportReport> {
+        self.export_jsonl_within(writer, self.deadline()).await
+    }
+
+    /// `export_jsonl`, failing once `deadline` passes while the graph is being read.
+    pub async fn export_jsonl_within(&self, writer: impl Write, deadline: Deadline) -> Result<ExportReport> {
+        self.export_jsonl_paced(writer, deadline, || {}).await
+    }
+
+    /// `export_jsonl_within`, calling `after_item` once each item has been read so tests
+    /// can slow the export down.
+    async fn export_jsonl_paced(
+        &self,
+        writer: impl Write,
+        deadline: Deadline,
+        mut after_item: impl FnMut() + Send + 'static,
+    ) -> Result<ExportReport> {
+        // A registered snapshot rather than a bare timestamp, so pruning keeps every version
+        // the export has yet to read. Touching it per item stops it going idle on big graphs.
+        let pinned = self.open_snapshot().await?;
+        let snapshot = self
+            .run_blocking_within(deadline, move |manager, deadline| {
+                GraphSnapshot::at_paced(&manager.version_store(), pinned.timestamp, || {
+                    after_item();
+                    deadline.check()?;
+                    manager.snapshot_timestamp(pinned.token).map(drop)
+                })
+            })
+            .await;
+        self.release_snapshot(pinned.token).await?;
+        let report = snapshot?.write_jsonl(writer)?;
+        Ok(ExportReport { as_of: Some(pinned.timestamp), ..report })
+    }
+
+    /// Reads a JSON Lines export and writes it in a single transaction, keeping every ID.
+    /// Items that already exist get a new version; everything else is created.
+    pub async fn import_jsonl(&self, reader: impl BufRead) -> Result<ImportReport> {
+        let snapshot = GraphSnapshot::read_jsonl(reader)?;
+        self.run_blocking(move |manager| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            let report = ImportReport {
+                concepts: snapshot.concepts.len(),
+                relationships: snapshot.relationships.len(),
+            };
+            for concept in snapshot.concepts {
+                manager.stage_concept_write(&mut txn, concept);
+            }
+            for mut relationship in snapshot.relationships {
+                // Like concepts, imported relationships come into being now, not when exported.
+                relationship.metadata.created_at = chrono::Utc::now();
+                manager.stage_relationship_write(&mut txn, relationship);
+            }
+            manager.commit_transaction(txn)?;
+            Ok(report)
+        })
+        .await
+    }
+
+    /// Writes a full-fidelity `.mnar` archive of the database to `writer` (see
+    /// `graph::archive`): every version still held, with the transaction metadata and commit
+    /// sequence, as they stood at one point in the history.
+    pub async fn export_archive(&self, writer: impl Write) -> Result<ArchiveReport> {
+        let archive = self.run_blocking(|manager| manager.export_archive()).await?;
+        archive.write(writer)
+    }
+
+    /// Restores an archive written by `export_archive` into this engine, which must be
+    /// empty. Every ID, version and timestamp comes back as it was, so reads at any point in
+    /// the archived history answer as they did on the original.
+    pub async fn import_archive(&self, reader: impl BufRead) -> Result<ArchiveReport> {
+        let archive = Archive::read(reader)?;
+        let report = archive.report();
+        self.run_blocking(move |manager| manager.import_archive(archive)).await?;
+        Ok(report)
+    }
+
+    /// Imports a Neo4j CSV export (see `graph::neo4j` for the header conventions): every node
+    /// file, then every relationship file, in a single transaction. Rows that can't be
+    /// imported are reported with their line numbers instead of failing the import.
+    pub async fn import_neo4j_csv(&self, nodes_files: Vec<PathBuf>, rels_files: Vec<PathBuf>) -> Result<ImportStats> {
+        let limits = self.data_limits.clone();
+        self.run_blocking(move |manager| {
+            let import = neo4j::read(&nodes_files, &rels_files, &limits)?;
+            let mut stats = import.stats;
+            if import.concepts.is_empty() && import.relationships.is_empty() {
+                return Ok(stats);
+            }
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            stats.concepts = import.concepts.len();
+            stats.relationships = import.relationships.len();
+            for concept in import.concepts {
+                manager.stage_concept_write(&mut txn, concept);
+            }
+            for relationship in import.relationships {
+                manager.stage_relationship_write(&mut txn, relationship);
+            }
+            manager.commit_transaction(txn)?;
+            Ok(stats)
+        })
+        .await
+    }
+
+    /// Writes the subgraph around `roots` to `writer` as JSON Lines, following every
+    /// relationship type. See `GraphSnapshot::write_jsonl` for the format.
+    pub async fn export_jsonl_subgraph(
+        &self,
+        roots: Vec<ConceptId>,
+        depth: u32,
+        writer: impl Write,
+    ) -> Result<ExportReport> {
+        self.subgraph(roots, depth, None).await?.write_jsonl(writer)
+    }
+
+    /// Streams every active concept in batches of at most `batch_size`, for consumers such as
+    /// exports that shouldn't hold the whole graph at once. The IDs to visit are taken when
+    /// the stream is first polled; each batch is then read separately, so concepts deleted
+    /// mid-scan are skipped and concepts created mid-scan are not seen.
+    pub fn scan_concepts(&self, batch_size: usize) -> impl Stream<Item = Result<Vec<Concept>>> + '_ {
+        self.scan_concepts_within(batch_size, self.deadline())
+    }
+
+    /// `scan_concepts`, ending with an error once `deadline` passes. The deadline covers the
+    /// whole scan, however slowly its batches are taken.
+    pub fn scan_concepts_within(
+        &self,
+        batch_size: usize,
+        deadline: Deadline,
+    ) -> impl Stream<Item = Result<Vec<Concept>>> + '_ {
+        let batch_size = batch_size.max(1);
+        stream::unfold(ScanState::Start, move |state| {
+            let deadline = deadline.clone();
+            async move {
+                let (ids, mut offset) = match state {
+                    ScanState::Start => {
+                        let listed = self
+                            .run_blocking_within(deadline.clone(), |manager, _| manager.version_store().concept_ids())
+                            .await;
+                        match listed {
+                            Ok(ids) => (ids, 0),
+                            Err(e) => return Some((Err(e), ScanState::Done)),
+                        }
+                    }
+                    ScanState::At(ids, offset) => (ids, offset),
+                    ScanState::Done => return None,
+                };
+
+                // Skip over chunks that turn out to hold only deleted concepts.
+                while offset < ids.len() {
+                    let chunk = ids[offset..(offset + batch_size).min(ids.len())].to_vec();
+                    offset += chunk.len();
+                    let versions = self
+                        .run_blocking_within(deadline.clone(), move |manager, _| {
+                            manager.version_store().get_latest_active_concepts(&chunk)
+                        })
+                        .await;
+                    match versions {
+                        Ok(versions) if versions.iter().all(Option::is_none) => continue,
+                        Ok(versions) => {
+                            let batch = versions.iter().flatten().map(concept_from_version).collect();
+                            return Some((Ok(batch), ScanState::At(ids, offset)));
+                        }
+                        Err(e) => return Some((Err(e), ScanState::Done)),
+                    }
+                }
+                None
+            }
+        })
+    }
+}
+
+impl Drop for GraphEngine {
+    /// Dropping works, but whatever a commit still running was doing is left to RocksDB's
+    /// recovery, so say so when the last handle goes.
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.closed) == 1 && !self.closed.load(Ordering::Acquire) {
+            tracing::warn!("GraphEngine dropped without close(); call close() to shut it down cleanly");
+        }
+    }
+}
+
+/// Where a `GraphEngine::scan_concepts` stream has got to.
+enum ScanState<Id> {
+    Start,
+    At(Vec<Id>, usize),
+    Done,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConflictItem;
+    use crate::graph::Discrepancy;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_get_concepts_lines_up_with_the_requested_ids() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let ada = engine.store(serde_json::json!({"name": "Ada"})).await.unwrap();
+        let gone = engine.store(serde_json::json!({"name": "Gone"})).await.unwrap();
+        engine.delete_concept(gone).await.unwrap();
+
+        let found = engine.get_concepts(vec![gone, ada, Uuid::new_v4(), ada]).await.unwrap();
+        let found: Vec<Option<ConceptId>> = found.iter().map(|c| c.as_ref().map(|c| c.id)).collect();
+        assert_eq!(found, vec![None, Some(ada), None, Some(ada)]);
+    }
+
+    #[tokio::test]
+    async fn test_engine_transactional_store_is_visible() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
 
         // Store a concept using the engine's public API
         let concept_id = engine
@@ -315,4 +2271,337 @@ mod tests {
         assert!(retrieved_version.is_some());
         assert_eq!(retrieved_version.unwrap().concept_id, concept_id);
     }
+
+    #[tokio::test]
+    async fn test_verify_consistency_detects_and_repairs_crash_during_commit() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        engine.store(serde_json::json!({"name": "Before"})).await.unwrap();
+        assert!(engine.verify_consistency().await.unwrap().is_consistent());
+
+        // Simulate dying right after the disk write: the commit never reaches memory.
+        let manager = engine.transaction_manager();
+        manager.set_skip_memory_apply(true);
+        let lost_id = engine.store(serde_json::json!({"name": "Lost"})).await.unwrap();
+        manager.set_skip_memory_apply(false);
+        assert!(engine.get_concept(lost_id).await.unwrap().is_none());
+
+        let report = engine.verify_consistency().await.unwrap();
+        assert_eq!(report.concepts_checked, 2);
+        assert_eq!(
+            report.discrepancies,
+            vec![Discrepancy::Mismatch {
+                item: ConflictItem::Concept(lost_id),
+                memory_head: None,
+                disk_head: Some(1),
+            }]
+        );
+        assert_eq!(report.repaired, 0);
+
+        let report = engine.repair_consistency().await.unwrap();
+        assert_eq!(report.repaired, 1);
+        assert!(engine.get_concept(lost_id).await.unwrap().is_some());
+        assert!(engine.verify_consistency().await.unwrap().is_consistent());
+    }
+
+    #[tokio::test]
+    async fn test_engine_keeps_serving_after_poisoned_locks() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let before = engine.store(serde_json::json!({"name": "Before"})).await.unwrap();
+        assert!(engine.health().healthy);
+
+        // Panic while holding write locks, as a buggy caller might.
+        let manager = engine.transaction_manager();
+        manager.version_store().poison_concept_versions_for_test();
+        manager.poison_active_transactions_for_test();
+
+        // Reads and writes still work...
+        assert!(engine.get_concept(before).await.unwrap().is_some());
+        let after = engine.store(serde_json::json!({"name": "After"})).await.unwrap();
+        assert!(engine.get_concept(after).await.unwrap().is_some());
+
+        // ...but the engine admits something went wrong.
+        let health = engine.health();
+        assert!(!health.healthy);
+        assert_eq!(health.lock_recoveries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_panic_in_blocking_task_is_returned_as_error() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+
+        let manager = engine.transaction_manager();
+        manager.set_panic_on_commit(true);
+        let result = engine.store(serde_json::json!({"name": "Doomed"})).await;
+        manager.set_panic_on_commit(false);
+
+        match result {
+            Err(MnemonicError::Internal(message)) => {
+                assert!(message.contains("simulated panic during commit"), "{}", message)
+            }
+            other => panic!("Expected an internal error, got {:?}", other),
+        }
+
+        // The panic poisoned the commit lock, which is recovered on the next commit.
+        engine.store(serde_json::json!({"name": "Fine"})).await.unwrap();
+        assert_eq!(engine.health().lock_recoveries, 1);
+    }
+
+    fn small_pool_engine(dir: &Path, max_concurrency: usize, max_queue: usize) -> GraphEngine {
+        let config = EngineConfig {
+            worker_pool: WorkerPoolConfig {
+                max_concurrency,
+                max_queue,
+            },
+            ..Default::default()
+        };
+        GraphEngine::with_config(dir, config).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_requests_stay_within_pool_size() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(small_pool_engine(dir.path(), 4, 1000));
+
+        let handles: Vec<_> = (0..200)
+            .map(|i| {
+                let engine = Arc::clone(&engine);
+                tokio::spawn(async move {
+                    let id = engine.store(serde_json::json!({ "n": i })).await?;
+                    engine.get_concept(id).await
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.await.unwrap().unwrap().is_some());
+        }
+
+        let metrics = engine.worker_pool().metrics();
+        assert!(metrics.running.peak() <= 4, "peak was {}", metrics.running.peak());
+        assert_eq!(metrics.completed.get(), 400);
+        assert_eq!(metrics.rejected.get(), 0);
+        assert_eq!(metrics.queued.get(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_close_waits_for_a_racing_commit_and_frees_the_path() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let second = GraphEngine::new(dir.path());
+        assert!(matches!(second, Err(MnemonicError::DatabaseLocked(ref path)) if path == dir.path()));
+
+        let metrics = engine.transaction_manager().metrics();
+        engine.transaction_manager().begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let pool = engine.worker_pool();
+
+        // The caller stops waiting, but the commit carries on on its worker.
+        let (stored, committed) = std::sync::mpsc::channel();
+        let commit = engine.run_blocking(move |manager| {
+            std::thread::sleep(Duration::from_millis(100));
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot)?;
+            let concept = Concept::new(serde_json::json!({"name": "late"}));
+            txn.write_set.insert(concept.id);
+            txn.pending_writes.insert(concept.id, concept.clone());
+            manager.commit_transaction(txn)?;
+            stored.send(concept.id).unwrap();
+            Ok(())
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(10), commit).await.is_err());
+
+        engine.close().await.unwrap();
+        let id = committed.try_recv().expect("close returned before the commit finished");
+        assert_eq!(metrics.aborted.get(), 1);
+        assert!(matches!(pool.run(|| Ok(())).await, Err(MnemonicError::EngineClosed)));
+
+        let reopened = GraphEngine::new(dir.path()).unwrap();
+        assert!(reopened.get_concept(id).await.unwrap().is_some());
+        reopened.close().await.unwrap();
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_the_engine_can_be_shared_across_threads() {
+        // A field that isn't Send + Sync would stop every handle being shared; fail here instead.
+        assert_send_sync::<GraphEngine>();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_clones_share_the_engine_and_closing_one_closes_them_all() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let handle = engine.clone();
+        let id = tokio::spawn(async move { handle.store(serde_json::json!({"name": "shared"})).await })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(engine.get_concept(id).await.unwrap().is_some());
+
+        let handle = engine.clone();
+        let closing = tokio::spawn(engine.close());
+        // The clone is turned away once the pool closes, and close finishes once it's dropped.
+        while !matches!(handle.get_concept(id).await, Err(MnemonicError::EngineClosed)) {
+            tokio::task::yield_now().await;
+        }
+        drop(handle);
+        closing.await.unwrap().unwrap();
+        let reopened = GraphEngine::new(dir.path()).unwrap();
+        assert!(reopened.get_concept(id).await.unwrap().is_some());
+        reopened.close().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_a_slowed_walk_times_out_and_gives_its_worker_back() {
+        let dir = tempdir().unwrap();
+        let engine = small_pool_engine(dir.path(), 1, 4);
+        let metrics = engine.worker_pool().metrics();
+        let root = engine.store(serde_json::json!({"n": 0})).await.unwrap();
+        let mut previous = root;
+        for n in 1..20 {
+            let next = engine.store(serde_json::json!({ "n": n })).await.unwrap();
+            engine.relate(previous, "next", next).await.unwrap();
+            previous = next;
+        }
+        let limits = TraversalLimits { max_depth: 20, max_concepts: 100 };
+
+        let slowed = Deadline::after(Duration::from_millis(50)).slowed(Duration::from_millis(10));
+        let walk = engine.neighborhood_within(root, None, limits, slowed).await;
+        assert!(matches!(walk, Err(MnemonicError::Timeout(budget)) if budget == Duration::from_millis(50)));
+        assert_eq!(metrics.running.get(), 0);
+
+        // A caller that stops waiting cancels the walk instead of leaving it on the only worker.
+        let slowed = Deadline::none().slowed(Duration::from_millis(10));
+        let abandoned = engine.neighborhood_within(root, None, limits, slowed.clone());
+        assert!(tokio::time::timeout(Duration::from_millis(30), abandoned).await.is_err());
+        assert!(slowed.is_cancelled());
+
+        let walk = engine.neighborhood(root, None, limits).await.unwrap();
+        assert_eq!(walk.concepts.len(), 20);
+        assert_eq!(metrics.running.get(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_full_queue_fails_fast_with_overloaded() {
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(small_pool_engine(dir.path(), 1, 2));
+        let metrics = engine.worker_pool().metrics();
+
+        // Occupy the only worker until we say so.
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let busy = {
+            let engine = Arc::clone(&engine);
+            tokio::spawn(async move {
+                engine
+                    .run_blocking(move |_| {
+                        blocked.recv().unwrap();
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+        while metrics.running.get() < 1 {
+            tokio::task::yield_now().await;
+        }
+
+        // Fill the queue.
+        let waiting: Vec<_> = (0..2)
+            .map(|_| {
+                let engine = Arc::clone(&engine);
+                tokio::spawn(async move { engine.active_transactions().await })
+            })
+            .collect();
+        while metrics.queued.get() < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        // The next caller is turned away immediately.
+        let rejected = engine.active_transactions().await;
+        assert!(matches!(rejected, Err(MnemonicError::Overloaded { queue_limit: 2 })));
+        assert_eq!(metrics.rejected.get(), 1);
+
+        // Once the worker frees up, everyone who waited gets served.
+        release.send(()).unwrap();
+        busy.await.unwrap().unwrap();
+        for handle in waiting {
+            handle.await.unwrap().unwrap();
+        }
+        assert_eq!(metrics.queued.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scan_concepts_streams_bounded_batches() {
+        use futures_util::TryStreamExt;
+
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let version_store = engine.transaction_manager().version_store();
+        let txn_id = Uuid::new_v4();
+        for n in 0..100_000u32 {
+            version_store
+                .add_concept_version(ConceptVersion {
+                    concept_id: Uuid::new_v4(),
+                    version: 1,
+                    data: crate::types::concept::ConceptData::Structured(serde_json::json!({ "n": n }).to_string().into()),
+                    created_at: chrono::Utc::now(),
+                    created_by: txn_id,
+                    deleted_at: None,
+                    deleted_by: None,
+                    expires_at: None,
+                    tags: Default::default(),
+                })
+                .unwrap();
+        }
+
+        let batches: Vec<Vec<Concept>> = engine.scan_concepts(1000).try_collect().await.unwrap();
+        assert!(batches.iter().all(|batch| !batch.is_empty() && batch.len() <= 1000));
+
+        let mut streamed: Vec<ConceptId> = batches.iter().flatten().map(|c| c.id).collect();
+        let mut expected: Vec<ConceptId> = version_store
+            .get_all_active_concepts()
+            .unwrap()
+            .iter()
+            .map(|v| v.concept_id)
+            .collect();
+        streamed.sort();
+        expected.sort();
+        assert_eq!(streamed.len(), 100_000);
+        assert_eq!(streamed, expected);
+    }
+
+    #[tokio::test]
+    async fn test_export_reads_the_graph_as_of_its_start_while_writes_continue() {
+        let dir = tempdir().unwrap();
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let mut ids = Vec::new();
+        for n in 0..5 {
+            ids.push(engine.store(serde_json::json!({ "n": n })).await.unwrap());
+        }
+        engine.relate(ids[0], "next", ids[1]).await.unwrap();
+
+        // Every item the export reads takes a while; commit edges and more while it crawls.
+        let started = Arc::new(tokio::sync::Notify::new());
+        let slow = {
+            let started = Arc::clone(&started);
+            move || {
+                started.notify_one();
+                std::thread::sleep(std::time::Duration::from_millis(40));
+            }
+        };
+        let mut out = Vec::new();
+        let (report, ()) = tokio::join!(engine.export_jsonl_paced(&mut out, Deadline::none(), slow), async {
+            started.notified().await;
+            engine.relate(ids[1], "next", ids[2]).await.unwrap();
+            engine.relate(ids[2], "next", ids[3]).await.unwrap();
+            engine.store(serde_json::json!({ "n": 5 })).await.unwrap();
+            engine.delete_concept(ids[4]).await.unwrap();
+        });
+        let report = report.unwrap();
+
+        let exported = GraphSnapshot::read_jsonl(out.as_slice()).unwrap();
+        assert_eq!(exported, engine.snapshot_at(report.as_of.unwrap()).await.unwrap());
+        assert_eq!((exported.concepts.len(), exported.relationships.len()), (5, 1));
+        assert_eq!(engine.snapshot().await.unwrap().relationships.len(), 3);
+    }
 }
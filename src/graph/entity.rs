@@ -0,0 +1,95 @@
+// Rust types stored as concepts. An entity's serde form becomes the concept's data, with the
+// type's label added to the `labels` array that listings and filters already understand; reads
+// take the label off again and deserialize what is left.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::ConceptId;
+use crate::types::query::labels_of;
+
+/// A type stored as concepts carrying `label()`, through `GraphEngine::store_entity`,
+/// `get_entity` and `find_entities`. It must serialize to a JSON object.
+pub trait ConceptEntity: Serialize + DeserializeOwned {
+    /// The label every concept of this type carries, e.g. `"Person"`.
+    fn label() -> &'static str;
+}
+
+/// `entity` as concept data: its fields, and `T::label()` among its labels.
+pub fn entity_data<T: ConceptEntity>(entity: &T) -> Result<Value> {
+    let mut data = serde_json::to_value(entity).map_err(|e| MnemonicError::InvalidData(e.to_string()))?;
+    let mut labels = labels_of(&data);
+    let Value::Object(fields) = &mut data else {
+        return Err(MnemonicError::InvalidData(format!("A {} must serialize to a JSON object", T::label())));
+    };
+    if !labels.iter().any(|label| label == T::label()) {
+        labels.push(T::label().to_string());
+    }
+    fields.insert("labels".to_string(), labels.into());
+    Ok(data)
+}
+
+/// `EntityMismatch` unless concept `id`'s data carries `T::label()`, naming the labels it has.
+pub fn require_label<T: ConceptEntity>(id: ConceptId, data: &Value) -> Result<()> {
+    let labels = labels_of(data);
+    if labels.iter().any(|label| label == T::label()) {
+        return Ok(());
+    }
+    Err(mismatch::<T>(id, format!("it is labelled {:?}", labels)))
+}
+
+/// Concept `id`'s data as a `T`: the data must carry `T::label()` and, without it,
+/// deserialize as one.
+pub fn entity_from_data<T: ConceptEntity>(id: ConceptId, mut data: Value) -> Result<T> {
+    require_label::<T>(id, &data)?;
+    let mut labels = labels_of(&data);
+    labels.retain(|label| label != T::label());
+    if let Value::Object(fields) = &mut data {
+        if labels.is_empty() {
+            fields.remove("labels");
+        } else {
+            fields.insert("labels".to_string(), labels.into());
+        }
+    }
+    serde_json::from_value(data).map_err(|e| mismatch::<T>(id, e.to_string()))
+}
+
+fn mismatch<T: ConceptEntity>(id: ConceptId, reason: String) -> MnemonicError {
+    MnemonicError::EntityMismatch { concept_id: id, label: T::label().to_string(), reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Tag {
+        name: String,
+    }
+
+    impl ConceptEntity for Tag {
+        fn label() -> &'static str {
+            "Tag"
+        }
+    }
+
+    #[test]
+    fn test_the_label_is_added_on_the_way_in_and_taken_off_on_the_way_out() {
+        let tag = Tag { name: "rust".to_string() };
+        let data = entity_data(&tag).unwrap();
+        assert_eq!(data, json!({"name": "rust", "labels": ["Tag"]}));
+        assert_eq!(entity_from_data::<Tag>(Uuid::new_v4(), data).unwrap(), tag);
+
+        let id = Uuid::new_v4();
+        let unlabelled = entity_from_data::<Tag>(id, json!({"name": "rust", "labels": ["Topic"]}));
+        assert!(matches!(unlabelled, Err(MnemonicError::EntityMismatch { concept_id, .. }) if concept_id == id));
+        let malformed = entity_from_data::<Tag>(id, json!({"name": 7, "labels": ["Tag"]})).unwrap_err();
+        assert!(malformed.to_string().starts_with(&format!("Concept {} is not a Tag: invalid type", id)));
+    }
+}
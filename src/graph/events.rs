@@ -0,0 +1,26 @@
+// Commit events: every commit, as it lands, for whoever is listening in-process.
+//
+// The TransactionManager publishes each commit on a tokio broadcast channel after it is on
+// disk and in memory. Events are shared behind an `Arc`, so listeners don't each copy the
+// versions. A listener that falls more than `COMMIT_CHANNEL_CAPACITY` commits behind gets
+// `RecvError::Lagged` and has to catch up from the version history.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::types::concept::{ConceptVersion, TransactionId};
+use crate::types::relationship::RelationshipVersion;
+use crate::types::transaction::TransactionMetadata;
+
+/// How many commits a listener may fall behind before it misses some.
+pub const COMMIT_CHANNEL_CAPACITY: usize = 1024;
+
+/// One committed transaction and every version it wrote.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitEvent {
+    pub transaction_id: TransactionId,
+    pub committed_at: DateTime<Utc>,
+    pub metadata: TransactionMetadata,
+    pub concept_versions: Vec<ConceptVersion>,
+    pub relationship_versions: Vec<RelationshipVersion>,
+}
@@ -0,0 +1,198 @@
+// Portable snapshots of the graph, and the JSON Lines and Graphviz formats they travel in.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+
+use super::versioning::{VersionStore, concept_from_version, relationship_from_version};
+use crate::error::Result;
+use crate::types::concept::{Concept, ConceptId};
+use crate::types::query::{DEFAULT_LABEL_FIELDS, label_for};
+use crate::types::relationship::Relationship;
+
+/// A self-contained slice of the graph: some concepts, and only the relationships between
+/// them, so nothing in it points outside it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphSnapshot {
+    pub concepts: Vec<Concept>,
+    pub relationships: Vec<Relationship>,
+    /// Roots that were asked for but aren't active concepts.
+    pub missing_roots: Vec<ConceptId>,
+}
+
+/// One line of a JSONL export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportRecord {
+    Concept(Concept),
+    Relationship(Relationship),
+}
+
+/// What an export wrote.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub concepts: usize,
+    pub relationships: usize,
+    pub missing_roots: Vec<ConceptId>,
+    /// The point in time a whole-graph export read the graph at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+/// What an import wrote.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub concepts: usize,
+    pub relationships: usize,
+}
+
+impl GraphSnapshot {
+    /// The graph as it stood at `timestamp`: every concept active then, and the relationships
+    /// active then between them.
+    pub(crate) fn at(version_store: &VersionStore, timestamp: DateTime<Utc>) -> Result<Self> {
+        Self::at_paced(version_store, timestamp, || Ok(()))
+    }
+
+    /// Like `at`, calling `after_item` once each concept or relationship has been looked up
+    /// and stopping at its first error. Every lookup is at `timestamp`, so commits landing
+    /// meanwhile don't show up however long the walk takes.
+    pub(crate) fn at_paced(
+        version_store: &VersionStore,
+        timestamp: DateTime<Utc>,
+        mut after_item: impl FnMut() -> Result<()>,
+    ) -> Result<Self> {
+        let mut snapshot = Self::default();
+        let mut ids = HashSet::new();
+        for concept_id in version_store.concept_ids()? {
+            if let Some(version) = version_store.get_concept_version_at_timestamp(&concept_id, timestamp)? {
+                ids.insert(concept_id);
+                snapshot.concepts.push(concept_from_version(&version));
+            }
+            after_item()?;
+        }
+        for relationship_id in version_store.relationship_ids()? {
+            if let Some(version) = version_store.get_relationship_version_at_timestamp(&relationship_id, timestamp)?
+                && ids.contains(&version.source)
+                && ids.contains(&version.target)
+            {
+                snapshot.relationships.push(relationship_from_version(&version));
+            }
+            after_item()?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes every concept and then every relationship, one `ExportRecord` per line.
+    /// Concepts come first so a reader can create them before the edges that need them.
+    pub fn write_jsonl(&self, mut writer: impl Write) -> Result<ExportReport> {
+        let records = self
+            .concepts
+            .iter()
+            .cloned()
+            .map(ExportRecord::Concept)
+            .chain(self.relationships.iter().cloned().map(ExportRecord::Relationship));
+        for record in records {
+            serde_json::to_writer(&mut writer, &record).map_err(io::Error::from)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(ExportReport {
+            concepts: self.concepts.len(),
+            relationships: self.relationships.len(),
+            missing_roots: self.missing_roots.clone(),
+            as_of: None,
+        })
+    }
+
+    /// Writes the snapshot as a Graphviz digraph, labelling concepts as `/graph` does.
+    pub fn write_dot(&self, mut writer: impl Write) -> Result<ExportReport> {
+        let fields: Vec<String> = DEFAULT_LABEL_FIELDS.iter().map(|f| f.to_string()).collect();
+        writeln!(writer, "digraph mnemonic {{")?;
+        for concept in &self.concepts {
+            let label = label_for(&concept.id, &concept.data, &fields);
+            writeln!(writer, "  \"{}\" [label=\"{}\"];", concept.id, escape_dot(&label))?;
+        }
+        for rel in &self.relationships {
+            writeln!(
+                writer,
+                "  \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                rel.source,
+                rel.target,
+                escape_dot(&rel.relationship_type),
+                if rel.directed { "" } else { ", dir=none" }
+            )?;
+        }
+        writeln!(writer, "}}")?;
+        writer.flush()?;
+        Ok(ExportReport {
+            concepts: self.concepts.len(),
+            relationships: self.relationships.len(),
+            missing_roots: self.missing_roots.clone(),
+            as_of: None,
+        })
+    }
+
+    /// Reads back what `write_jsonl` wrote. Blank lines are skipped.
+    pub fn read_jsonl(reader: impl BufRead) -> Result<Self> {
+        let mut snapshot = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line).map_err(io::Error::from)? {
+                ExportRecord::Concept(concept) => snapshot.concepts.push(concept),
+                ExportRecord::Relationship(relationship) => snapshot.relationships.push(relationship),
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let a = Concept::new(json!({"name": "a"}));
+        let b = Concept::new(json!({"name": "b"}));
+        let snapshot = GraphSnapshot {
+            relationships: vec![Relationship::new(a.id, "knows".to_string(), b.id)],
+            concepts: vec![a, b],
+            missing_roots: Vec::new(),
+        };
+
+        let mut out = Vec::new();
+        let report = snapshot.write_jsonl(&mut out).unwrap();
+        assert_eq!((report.concepts, report.relationships), (2, 1));
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.lines().next().unwrap().contains(r#""kind":"concept""#));
+
+        let read = GraphSnapshot::read_jsonl(format!("{}\n\n", text).as_bytes()).unwrap();
+        assert_eq!(read, snapshot);
+    }
+
+    #[test]
+    fn test_dot_output_escapes_labels() {
+        let a = Concept::new(json!({"name": "say \"hi\""}));
+        let snapshot = GraphSnapshot {
+            relationships: vec![Relationship::new(a.id, "loops".to_string(), a.id)],
+            concepts: vec![a.clone()],
+            missing_roots: Vec::new(),
+        };
+        let mut out = Vec::new();
+        snapshot.write_dot(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("digraph mnemonic {\n"));
+        assert!(text.contains(&format!("\"{}\" [label=\"say \\\"hi\\\"\"];", a.id)));
+        assert!(text.contains(&format!("\"{0}\" -> \"{0}\" [label=\"loops\"];", a.id)));
+    }
+}
@@ -0,0 +1,526 @@
+// Loading the version history from disk into the VersionStore at startup. The version keys
+// are cut into ranges by the first byte of the item ID, so every chain lies wholly inside one
+// range; worker threads take a range at a time, scan and decode it, group and sort its chains
+// off-lock, and hand them to the store in bulk. With `HydrationMode::Background` the same
+// ranges are loaded by a warmer thread after startup instead, while the store reads whatever
+// it hasn't got yet from disk one item at a time.
+
+use chrono::{DateTime, Utc};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use super::consistency;
+use super::versioning::{ColdHistory, VersionStore};
+use crate::error::{MnemonicError, Result};
+use crate::storage::RocksBackend;
+use crate::types::concept::{ConceptId, ConceptVersion};
+use crate::types::relationship::{RelationshipId, RelationshipVersion};
+
+/// How a `TransactionManager` loads the version history when it starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HydrationMode {
+    /// Load everything before the manager is returned.
+    #[default]
+    Eager,
+    /// Return at once and load the history on a background thread. Until it is done, an item
+    /// is read from disk the first time it is looked up, so reads and commits of any one item
+    /// are exact; the first lookup of a concept's relationships waits for all of them to
+    /// load. Whole-graph listings and label lookups see only what has been loaded so far, and
+    /// timestamps are not held back behind history still on disk if the clock stepped back
+    /// while we were down.
+    Background,
+}
+
+/// How many ranges the keyspace is cut into when hydrating on more than one thread. More
+/// ranges than threads, so a thread that drew a sparse one goes back for another.
+const RANGES: usize = 64;
+
+/// How often a hydration still running says how far it has got.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Loads every concept and relationship version on `backend` into `version_store` using up
+/// to `threads` threads, one meaning a single serial scan. Returns the latest timestamp
+/// among them, if there are any.
+pub(crate) fn hydrate(
+    backend: &RocksBackend,
+    version_store: &VersionStore,
+    threads: usize,
+) -> Result<Option<DateTime<Utc>>> {
+    let started = Instant::now();
+    let ranges = leading_byte_ranges(if threads > 1 { RANGES } else { 1 });
+    let next = AtomicUsize::new(0);
+    let (progress, loaded) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        let (ranges, next) = (&ranges, &next);
+        let workers: Vec<_> = (0..threads.clamp(1, ranges.len()))
+            .map(|_| {
+                let progress = progress.clone();
+                scope.spawn(move || -> Result<Option<DateTime<Utc>>> {
+                    let mut latest = None;
+                    while let Some(range) = ranges.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let concepts = backend.load_concept_versions_in(range.clone())?;
+                        let relationships = backend.load_relationship_versions_in(range.clone())?;
+                        // New timestamps must come after everything already committed, even
+                        // if the clock stepped back while we were down.
+                        let range_latest = concepts
+                            .iter()
+                            .flat_map(|v| [Some(v.created_at), v.deleted_at])
+                            .chain(relationships.iter().flat_map(|v| [Some(v.created_at), v.deleted_at]))
+                            .flatten()
+                            .max();
+                        latest = latest.max(range_latest);
+                        let counts = (concepts.len(), relationships.len());
+                        version_store.replace_concept_chains(consistency::group_chains(concepts, |v| v.concept_id))?;
+                        version_store
+                            .replace_relationship_chains(consistency::group_chains(relationships, |v| v.relationship_id))?;
+                        // Nobody listening just means the progress log has gone away.
+                        let _ = progress.send(counts);
+                    }
+                    Ok(latest)
+                })
+            })
+            .collect();
+        drop(progress);
+        log_progress(loaded, started);
+
+        let mut latest = None;
+        for worker in workers {
+            let worker_latest = worker
+                .join()
+                .map_err(|_| MnemonicError::Internal("A hydration thread panicked".to_string()))??;
+            latest = latest.max(worker_latest);
+        }
+        Ok(latest)
+    })
+}
+
+/// Raises every version counter to the one persisted: they may be ahead of the chains, e.g.
+/// if old versions were removed.
+pub(crate) fn restore_heads(backend: &RocksBackend, version_store: &VersionStore) -> Result<()> {
+    for (concept_id, head) in backend.load_all_concept_heads()? {
+        version_store.restore_concept_head(concept_id, head)?;
+    }
+    for (rel_id, head) in backend.load_all_relationship_heads()? {
+        version_store.restore_relationship_head(rel_id, head)?;
+    }
+    Ok(())
+}
+
+impl ColdHistory for RocksBackend {
+    fn concept_history(&self, concept_id: &ConceptId) -> Result<(Vec<ConceptVersion>, Option<u64>)> {
+        Ok((self.load_concept_versions_of(concept_id)?, self.load_concept_head(concept_id)?))
+    }
+
+    fn relationship_history(
+        &self,
+        relationship_id: &RelationshipId,
+    ) -> Result<(Vec<RelationshipVersion>, Option<u64>)> {
+        Ok((
+            self.load_relationship_versions_of(relationship_id)?,
+            self.load_relationship_head(relationship_id)?,
+        ))
+    }
+
+    fn all_relationship_versions(&self) -> Result<Vec<RelationshipVersion>> {
+        self.load_all_relationship_versions()
+    }
+
+    fn relationship_heads(&self) -> Result<Vec<(RelationshipId, u64)>> {
+        self.load_all_relationship_heads()
+    }
+}
+
+/// Starts the thread that warms `version_store` up from `backend`. It only holds weak
+/// references between ranges, and gives up if the manager is dropped first.
+pub(crate) fn spawn_warmer(backend: &Arc<RocksBackend>, version_store: &Arc<VersionStore>) -> Result<()> {
+    let handles = (Arc::downgrade(backend), Arc::downgrade(version_store));
+    std::thread::Builder::new()
+        .name("mnemonic-warmer".to_string())
+        .spawn(move || {
+            let upgrade = |(backend, store): &(Weak<RocksBackend>, Weak<VersionStore>)| {
+                Some((backend.upgrade()?, store.upgrade()?))
+            };
+            if let Err(e) = warm(|| upgrade(&handles)) {
+                tracing::error!("Warming the version store failed, reads keep going to disk: {}", e);
+            }
+        })
+        .map_err(|e| MnemonicError::Internal(format!("Failed to start the warmer: {}", e)))?;
+    Ok(())
+}
+
+/// Loads every chain not in memory yet, the relationships all at once and then the concepts
+/// a range at a time, then the version counters, and marks the store warm. `handles` is
+/// asked for the backend and store before each step; `None` stops early and returns `false`.
+pub(crate) fn warm(handles: impl Fn() -> Option<(Arc<RocksBackend>, Arc<VersionStore>)>) -> Result<bool> {
+    let started = Instant::now();
+    let ranges = leading_byte_ranges(RANGES);
+    let (mut concepts, mut relationships) = (0, 0);
+    let mut last_logged = started;
+    // Relationships first: until they are in, any lookup of a concept's relationships has to
+    // wait for them to load.
+    let Some((_, store)) = handles() else { return Ok(false) };
+    store.warm_adjacency()?;
+    relationships += store.stats()?.relationship_versions as usize;
+    drop(store);
+    for range in &ranges {
+        let Some((backend, store)) = handles() else { return Ok(false) };
+        let loaded = backend.load_concept_versions_in(range.clone())?;
+        concepts += loaded.len();
+        store.load_missing_concept_chains(consistency::group_chains(loaded, |v| v.concept_id))?;
+        log_warming(concepts, relationships, started, &mut last_logged);
+    }
+    let Some((backend, store)) = handles() else { return Ok(false) };
+    restore_heads(&backend, &store)?;
+    for discrepancy in consistency::check_chains(&store)? {
+        tracing::warn!("Inconsistent version history on disk: {:?}", discrepancy);
+    }
+    store.finish_warming();
+    tracing::info!(
+        "Warmed {} concept and {} relationship versions in {:.1?}",
+        concepts,
+        relationships,
+        started.elapsed()
+    );
+    Ok(true)
+}
+
+fn log_warming(concepts: usize, relationships: usize, started: Instant, last_logged: &mut Instant) {
+    if last_logged.elapsed() >= PROGRESS_INTERVAL {
+        tracing::info!(
+            "Warming: {} concept and {} relationship versions loaded in {:.1?}",
+            concepts,
+            relationships,
+            started.elapsed()
+        );
+        *last_logged = Instant::now();
+    }
+}
+
+/// `count` ranges of leading ID bytes, together covering every byte once, in order.
+fn leading_byte_ranges(count: usize) -> Vec<RangeInclusive<u8>> {
+    let width = 256 / count;
+    (0..count)
+        .map(|i| {
+            let end = if i + 1 == count { 255 } else { (i + 1) * width - 1 };
+            (i * width) as u8..=end as u8
+        })
+        .collect()
+}
+
+/// Logs the running totals every `PROGRESS_INTERVAL` until every worker has hung up, then
+/// once more with the final count.
+fn log_progress(loaded: Receiver<(usize, usize)>, started: Instant) {
+    let (mut concepts, mut relationships) = (0, 0);
+    let mut last_logged = started;
+    for (range_concepts, range_relationships) in loaded {
+        concepts += range_concepts;
+        relationships += range_relationships;
+        if last_logged.elapsed() >= PROGRESS_INTERVAL {
+            tracing::info!(
+                "Hydrating: {} concept and {} relationship versions loaded in {:.1?}",
+                concepts,
+                relationships,
+                started.elapsed()
+            );
+            last_logged = Instant::now();
+        }
+    }
+    tracing::info!(
+        "Hydrated {} concept and {} relationship versions in {:.1?}",
+        concepts,
+        relationships,
+        started.elapsed()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::audit::AuditQuery;
+    use crate::graph::observers::VersionObserver;
+    use crate::error::ConflictItem;
+    use crate::graph::transaction::{IsolationLevel, Transaction, TransactionConfig, TransactionManager};
+    use crate::types::concept::{Concept, ConceptData, ConceptVersion};
+    use crate::types::relationship::{Relationship, RelationshipVersion};
+    use serde_json::json;
+    use rocksdb::WriteBatch;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    const CONCEPTS: usize = 100_000;
+
+    /// Two versions of every concept, some tombstoned or expiring, and an edge from each
+    /// concept to the next: 300,000 versions in all.
+    fn write_history(backend: &RocksBackend) -> Vec<Uuid> {
+        let ids: Vec<Uuid> = (0..CONCEPTS).map(|_| Uuid::new_v4()).collect();
+        let txn_id = Uuid::new_v4();
+        let at = Utc::now() - chrono::Duration::hours(1);
+        let mut batch = WriteBatch::default();
+        for (n, id) in ids.iter().enumerate() {
+            let label = if n % 2 == 0 { "Even" } else { "Odd" };
+            for version in 1..=2 {
+                let stamp = at + chrono::Duration::milliseconds((n * 2 + version as usize) as i64);
+                let data = serde_json::json!({ "n": n, "v": version, "labels": [label] });
+                let concept = ConceptVersion {
+                    concept_id: *id,
+                    version,
+                    data: ConceptData::Structured(data.to_string().into()),
+                    created_at: stamp,
+                    created_by: txn_id,
+                    deleted_at: (version == 2 && n % 5 == 0).then_some(stamp),
+                    deleted_by: (version == 2 && n % 5 == 0).then_some(txn_id),
+                    expires_at: (n % 7 == 0).then(|| stamp + chrono::Duration::days(1)),
+                    tags: Default::default(),
+                };
+                backend.store_concept_version(&concept, &mut batch).unwrap();
+            }
+            let relationship = RelationshipVersion {
+                relationship_id: Uuid::new_v4(),
+                version: 1,
+                source: *id,
+                relationship_type: "next".into(),
+                target: ids[(n + 1) % CONCEPTS],
+                created_at: at,
+                created_by: txn_id,
+                deleted_at: None,
+                deleted_by: None,
+                directed: true,
+            };
+            backend.store_relationship_version(&relationship, &mut batch).unwrap();
+            if batch.len() >= 10_000 {
+                backend.db.write(std::mem::take(&mut batch)).unwrap();
+            }
+        }
+        backend.db.write(batch).unwrap();
+        ids
+    }
+
+    fn hydrated(backend: &Arc<RocksBackend>, hydration_threads: usize) -> Arc<VersionStore> {
+        let config = TransactionConfig {
+            max_transaction_age: None,
+            expiry_sweep_interval: None,
+            hydration_threads,
+            ..Default::default()
+        };
+        TransactionManager::with_config(Arc::clone(backend), config).unwrap().version_store()
+    }
+
+    fn sorted<T: Ord>(mut items: Vec<T>) -> Vec<T> {
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn test_parallel_hydration_loads_what_a_serial_scan_does() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let ids = write_history(&backend);
+
+        let serial = hydrated(&backend, 1);
+        let parallel = hydrated(&backend, 8);
+
+        assert_eq!(serial.stats().unwrap().concept_versions, 2 * CONCEPTS as u64);
+        assert_eq!(parallel.stats().unwrap(), serial.stats().unwrap());
+        assert_eq!(parallel.concept_version_numbers().unwrap(), serial.concept_version_numbers().unwrap());
+        assert_eq!(parallel.relationship_version_numbers().unwrap(), serial.relationship_version_numbers().unwrap());
+        let by_id = |mut versions: Vec<ConceptVersion>| {
+            versions.sort_by_key(|v| v.concept_id);
+            versions
+        };
+        assert_eq!(
+            by_id(parallel.get_all_active_concepts().unwrap()),
+            by_id(serial.get_all_active_concepts().unwrap())
+        );
+        assert_eq!(
+            sorted(parallel.concepts_with_label("Even").unwrap()),
+            sorted(serial.concepts_with_label("Even").unwrap())
+        );
+        let far_future = Utc::now() + chrono::Duration::days(30);
+        assert_eq!(sorted(parallel.expired_concepts(far_future).into_iter().collect()), sorted(serial.expired_concepts(far_future).into_iter().collect()));
+        for id in ids.iter().step_by(997) {
+            assert_eq!(parallel.concept_history(id).unwrap(), serial.concept_history(id).unwrap());
+            let targets = |store: &VersionStore| -> Vec<Uuid> {
+                sorted(store.outgoing_relationships(id).unwrap().iter().map(|r| r.target).collect())
+            };
+            assert_eq!(targets(&parallel), targets(&serial));
+        }
+        let everything = AuditQuery { limit: Some(500), ..Default::default() };
+        assert_eq!(parallel.audit_log(&everything).unwrap(), serial.audit_log(&everything).unwrap());
+    }
+
+    fn write(txn: &mut Transaction, id: Uuid, data: serde_json::Value) {
+        let concept = Concept {
+            id,
+            data: ConceptData::Structured(data.to_string().into()),
+            metadata: Default::default(),
+            expires_at: None,
+            tags: Default::default(),
+        };
+        txn.write_set.insert(id);
+        txn.pending_writes.insert(id, concept);
+    }
+
+    #[test]
+    fn test_a_cold_store_reads_and_commits_from_disk_until_warmed() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let (x, y) = (Uuid::new_v4(), Uuid::new_v4());
+        let edge = Relationship::new(x, "knows".to_string(), y);
+        {
+            let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            write(&mut txn, x, json!({"name": "X", "v": 1}));
+            write(&mut txn, y, json!({"name": "Y"}));
+            txn.relationship_write_set.insert(edge.id);
+            txn.pending_relationship_writes.insert(edge.id, edge.clone());
+            manager.commit_transaction(txn).unwrap();
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            write(&mut txn, x, json!({"name": "X", "v": 2}));
+            manager.commit_transaction(txn).unwrap();
+        }
+
+        let manager = TransactionManager::cold_for_test(Arc::clone(&backend), TransactionConfig::default()).unwrap();
+        let store = manager.version_store();
+        assert!(!store.is_warm());
+
+        // Nothing is in memory, so the read goes to disk and brings the edge along.
+        let latest = store.get_latest_concept_version(&x).unwrap().unwrap();
+        assert_eq!((latest.version, latest.data), (2, ConceptData::Structured(json!({"name": "X", "v": 2}).to_string().into())));
+        assert_eq!(store.concept_history(&x).unwrap().len(), 2);
+        let edges = store.outgoing_relationships(&x).unwrap();
+        assert_eq!(edges.iter().map(|r| r.relationship_id).collect::<Vec<_>>(), vec![edge.id]);
+
+        // Two writers race on a concept nobody has read since startup.
+        let mut first = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let mut second = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        write(&mut first, y, json!({"name": "Y", "by": "first"}));
+        write(&mut second, y, json!({"name": "Y", "by": "second"}));
+        manager.commit_transaction(first).unwrap();
+        match manager.commit_transaction(second) {
+            Err(MnemonicError::TransactionConflict(conflicts)) => {
+                assert_eq!(conflicts[0].item, ConflictItem::Concept(y));
+                assert_eq!(conflicts[0].committed_version, 2);
+            }
+            other => panic!("Expected a conflict on the cold concept, got {:?}", other),
+        }
+        assert_eq!(store.latest_concept_version_number(&y).unwrap(), 2);
+
+        // Warming leaves what commits added alone and fills in the rest.
+        let handles = (Arc::clone(&backend), Arc::clone(&store));
+        assert!(warm(|| Some(handles.clone())).unwrap());
+        assert!(store.is_warm());
+        assert_eq!(store.concept_history(&y).unwrap().len(), 2);
+        assert_eq!(store.get_all_active_concepts().unwrap().len(), 2);
+        assert_eq!(store.get_all_active_relationships().unwrap().len(), 1);
+    }
+
+    /// How many times each (item, version) has been seen.
+    #[derive(Default)]
+    struct Counting {
+        seen: std::sync::Mutex<std::collections::HashMap<(Uuid, u64), usize>>,
+    }
+
+    impl Counting {
+        fn see(&self, id: Uuid, version: u64) -> Result<()> {
+            *self.seen.lock().unwrap().entry((id, version)).or_default() += 1;
+            Ok(())
+        }
+
+        fn seen(&self) -> Vec<((Uuid, u64), usize)> {
+            sorted(self.seen.lock().unwrap().iter().map(|(key, count)| (*key, *count)).collect())
+        }
+    }
+
+    impl VersionObserver for Arc<Counting> {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn on_concept_version(&self, version: &ConceptVersion) -> Result<()> {
+            self.see(version.concept_id, version.version)
+        }
+
+        fn on_relationship_version(&self, version: &RelationshipVersion) -> Result<()> {
+            self.see(version.relationship_id, version.version)
+        }
+    }
+
+    struct Failing;
+
+    impl VersionObserver for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn on_concept_version(&self, _version: &ConceptVersion) -> Result<()> {
+            Err(MnemonicError::Internal("index full".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_an_observer_sees_each_hydrated_and_committed_version_once() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let (x, y) = (Uuid::new_v4(), Uuid::new_v4());
+        let edge = Relationship::new(x, "knows".to_string(), y);
+        {
+            let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            write(&mut txn, x, json!({"name": "X", "v": 1}));
+            write(&mut txn, y, json!({"name": "Y"}));
+            txn.relationship_write_set.insert(edge.id);
+            txn.pending_relationship_writes.insert(edge.id, edge.clone());
+            manager.commit_transaction(txn).unwrap();
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            write(&mut txn, x, json!({"name": "X", "v": 2}));
+            manager.commit_transaction(txn).unwrap();
+        }
+        let once = |items: &[(Uuid, u64)]| sorted(items.iter().map(|item| (*item, 1)).collect::<Vec<_>>());
+
+        // Registered on a cold store, it sees versions as they are loaded and as they are committed.
+        let manager = TransactionManager::cold_for_test(Arc::clone(&backend), TransactionConfig::default()).unwrap();
+        let store = manager.version_store();
+        let counting = Arc::new(Counting::default());
+        store.register_observer(Box::new(Arc::clone(&counting)));
+        store.register_observer(Box::new(Failing));
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        write(&mut txn, y, json!({"name": "Y", "v": 2}));
+        manager.commit_transaction(txn).unwrap();
+        assert_eq!(counting.seen(), once(&[(y, 1), (y, 2)]));
+        let handles = (Arc::clone(&backend), Arc::clone(&store));
+        assert!(warm(|| Some(handles.clone())).unwrap());
+        assert_eq!(counting.seen(), once(&[(x, 1), (x, 2), (y, 1), (y, 2), (edge.id, 1)]));
+
+        // The failing observer didn't fail the commit, or stop the other one hearing of it.
+        let failures = store.observer_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!((failures[0].observer.as_str(), failures[0].failures), ("failing", 4));
+        assert!(failures[0].last_error.contains("index full"));
+        drop(manager);
+
+        // Registered once a store has hydrated, it is told what was loaded, then what follows.
+        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+        let counting = Arc::new(Counting::default());
+        manager.version_store().register_observer(Box::new(Arc::clone(&counting)));
+        assert_eq!(counting.seen(), once(&[(x, 1), (x, 2), (y, 1), (y, 2), (edge.id, 1)]));
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        write(&mut txn, x, json!({"name": "X", "v": 3}));
+        manager.commit_transaction(txn).unwrap();
+        assert_eq!(counting.seen(), once(&[(x, 1), (x, 2), (x, 3), (y, 1), (y, 2), (edge.id, 1)]));
+    }
+
+    #[test]
+    fn test_the_ranges_cover_every_leading_byte_once() {
+        for count in [1, 3, 64, 256] {
+            let ranges = leading_byte_ranges(count);
+            assert_eq!(ranges.len(), count);
+            let bytes: Vec<u8> = ranges.into_iter().flatten().collect();
+            assert_eq!(bytes, (0..=u8::MAX).collect::<Vec<u8>>());
+        }
+    }
+}
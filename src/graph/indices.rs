@@ -1 +1,389 @@
 // Index management
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::types::concept::{ConceptId, ConceptVersion};
+use crate::types::query::{labels_of, parse_data};
+use crate::types::relationship::{RelationType, RelationshipId, RelationshipVersion};
+
+/// What rebuilding one index found: how many entries it now holds, and how many entries
+/// the old index had wrong (missing or stale).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexRebuild {
+    pub entries: usize,
+    pub repaired: usize,
+}
+
+impl IndexRebuild {
+    pub(crate) fn compare<T: Eq + Hash>(old: &HashSet<T>, new: &HashSet<T>) -> Self {
+        Self {
+            entries: new.len(),
+            repaired: old.symmetric_difference(new).count(),
+        }
+    }
+}
+
+/// An index kept from the latest version of each item. A `VersionStore` feeds its built-in
+/// indexes every version it takes in, in order, as it does the observers registered with it,
+/// so each index only has to remember what it needs to move an item when a newer version comes.
+pub trait VersionIndex {
+    fn concept_version(&mut self, _version: &ConceptVersion) {}
+
+    fn relationship_version(&mut self, _version: &RelationshipVersion) {}
+
+    /// The concept's whole history has been dropped.
+    fn forget_concept(&mut self, _concept_id: &ConceptId) {}
+
+    /// The relationship's whole history has been dropped.
+    fn forget_relationship(&mut self, _relationship_id: &RelationshipId) {}
+}
+
+/// Where a relationship is filed in `Adjacency`.
+#[derive(Debug)]
+struct Link {
+    source: ConceptId,
+    target: ConceptId,
+    relationship_type: RelationType,
+}
+
+/// The relationships leaving and entering each concept, as of each relationship's latest
+/// version. Tombstoned relationships are left out; history stays in the version chains.
+/// An undirected relationship is filed under its recorded source and target like any other,
+/// but counts as leaving and entering both.
+///
+/// The same edges are also filed by (concept, relationship type), so "the `member_of` edges
+/// leaving this concept" doesn't have to filter all of its edges.
+#[derive(Debug, Default)]
+pub struct Adjacency {
+    out_edges: HashMap<ConceptId, HashSet<RelationshipId>>,
+    in_edges: HashMap<ConceptId, HashSet<RelationshipId>>,
+    typed_out_edges: HashMap<(ConceptId, RelationType), HashSet<RelationshipId>>,
+    typed_in_edges: HashMap<(ConceptId, RelationType), HashSet<RelationshipId>>,
+    undirected: HashSet<RelationshipId>,
+    // Where each live relationship is filed above, to unfile it when its next version comes.
+    links: HashMap<RelationshipId, Link>,
+}
+
+impl VersionIndex for Adjacency {
+    /// Files the relationship under its new latest version, and unfiles it if that is a tombstone.
+    fn relationship_version(&mut self, latest: &RelationshipVersion) {
+        self.forget_relationship(&latest.relationship_id);
+        if latest.deleted_at.is_none() {
+            self.links.insert(
+                latest.relationship_id,
+                Link { source: latest.source, target: latest.target, relationship_type: latest.relationship_type.clone() },
+            );
+            self.out_edges
+                .entry(latest.source)
+                .or_default()
+                .insert(latest.relationship_id);
+            self.in_edges
+                .entry(latest.target)
+                .or_default()
+                .insert(latest.relationship_id);
+            self.typed_out_edges
+                .entry((latest.source, latest.relationship_type.clone()))
+                .or_default()
+                .insert(latest.relationship_id);
+            self.typed_in_edges
+                .entry((latest.target, latest.relationship_type.clone()))
+                .or_default()
+                .insert(latest.relationship_id);
+            if !latest.directed {
+                self.undirected.insert(latest.relationship_id);
+            }
+        }
+    }
+
+    fn forget_relationship(&mut self, relationship_id: &RelationshipId) {
+        if let Some(Link { source, target, relationship_type }) = self.links.remove(relationship_id) {
+            Self::unlink(&mut self.out_edges, source, *relationship_id);
+            Self::unlink(&mut self.in_edges, target, *relationship_id);
+            Self::unlink(&mut self.typed_out_edges, (source, relationship_type.clone()), *relationship_id);
+            Self::unlink(&mut self.typed_in_edges, (target, relationship_type), *relationship_id);
+            self.undirected.remove(relationship_id);
+        }
+    }
+}
+
+impl Adjacency {
+
+    /// Relationships leaving the given concept: those it is the source of, and undirected
+    /// ones it is the target of.
+    pub fn outgoing(&self, concept_id: &ConceptId) -> Vec<RelationshipId> {
+        self.edges_with_undirected(&self.out_edges, &self.in_edges, concept_id)
+    }
+
+    /// Relationships entering the given concept: those it is the target of, and undirected
+    /// ones it is the source of.
+    pub fn incoming(&self, concept_id: &ConceptId) -> Vec<RelationshipId> {
+        self.edges_with_undirected(&self.in_edges, &self.out_edges, concept_id)
+    }
+
+    /// Like `outgoing`, but only relationships of the given type.
+    pub fn outgoing_of_type(&self, concept_id: &ConceptId, relationship_type: &RelationType) -> Vec<RelationshipId> {
+        let key = (*concept_id, relationship_type.clone());
+        self.edges_with_undirected(&self.typed_out_edges, &self.typed_in_edges, &key)
+    }
+
+    /// Like `incoming`, but only relationships of the given type.
+    pub fn incoming_of_type(&self, concept_id: &ConceptId, relationship_type: &RelationType) -> Vec<RelationshipId> {
+        let key = (*concept_id, relationship_type.clone());
+        self.edges_with_undirected(&self.typed_in_edges, &self.typed_out_edges, &key)
+    }
+
+    /// Like `recorded_as_source` and `recorded_as_target` together, but only relationships
+    /// of the given type.
+    pub fn recorded_of_type(&self, concept_id: &ConceptId, relationship_type: &RelationType) -> Vec<RelationshipId> {
+        let key = (*concept_id, relationship_type.clone());
+        let mut ids = Self::edges(&self.typed_out_edges, &key);
+        ids.extend(Self::edges(&self.typed_in_edges, &key));
+        ids
+    }
+
+    /// Relationships recorded with the given concept as their source, whichever way they
+    /// point. Together with `recorded_as_target`, this lists every relationship touching the
+    /// concept once, or twice if it goes from the concept to itself.
+    pub fn recorded_as_source(&self, concept_id: &ConceptId) -> Vec<RelationshipId> {
+        Self::edges(&self.out_edges, concept_id)
+    }
+
+    /// Relationships recorded with the given concept as their target, whichever way they point.
+    pub fn recorded_as_target(&self, concept_id: &ConceptId) -> Vec<RelationshipId> {
+        Self::edges(&self.in_edges, concept_id)
+    }
+
+    /// How many relationships leave the given concept.
+    pub fn out_degree(&self, concept_id: &ConceptId) -> usize {
+        self.degree_with_undirected(&self.out_edges, &self.in_edges, concept_id)
+    }
+
+    /// How many relationships enter the given concept.
+    pub fn in_degree(&self, concept_id: &ConceptId) -> usize {
+        self.degree_with_undirected(&self.in_edges, &self.out_edges, concept_id)
+    }
+
+    /// How many relationships touch the given concept; one to itself counts twice.
+    pub fn degree(&self, concept_id: &ConceptId) -> usize {
+        self.out_edges.get(concept_id).map_or(0, HashSet::len) + self.in_edges.get(concept_id).map_or(0, HashSet::len)
+    }
+
+    /// How many live relationships there are in total.
+    pub fn edge_count(&self) -> usize {
+        self.out_edges.values().map(HashSet::len).sum()
+    }
+
+    /// Every entry, outgoing ones flagged `true`, for comparing two indexes.
+    pub(crate) fn entries(&self) -> HashSet<(bool, ConceptId, RelationshipId)> {
+        let outgoing = self.out_edges.iter().flat_map(|(concept, ids)| ids.iter().map(move |id| (true, *concept, *id)));
+        let incoming = self.in_edges.iter().flat_map(|(concept, ids)| ids.iter().map(move |id| (false, *concept, *id)));
+        outgoing.chain(incoming).collect()
+    }
+
+    /// `own` edges of the concept, plus the undirected ones among its `opposite` edges.
+    fn edges_with_undirected<K: Eq + Hash>(
+        &self,
+        own: &HashMap<K, HashSet<RelationshipId>>,
+        opposite: &HashMap<K, HashSet<RelationshipId>>,
+        concept_id: &K,
+    ) -> Vec<RelationshipId> {
+        let mut ids = Self::edges(own, concept_id);
+        ids.extend(self.undirected_only(own, opposite, concept_id));
+        ids
+    }
+
+    fn degree_with_undirected(
+        &self,
+        own: &HashMap<ConceptId, HashSet<RelationshipId>>,
+        opposite: &HashMap<ConceptId, HashSet<RelationshipId>>,
+        concept_id: &ConceptId,
+    ) -> usize {
+        own.get(concept_id).map_or(0, HashSet::len) + self.undirected_only(own, opposite, concept_id).count()
+    }
+
+    /// The undirected `opposite` edges of the concept that aren't among its `own` too, as
+    /// an undirected relationship from a concept to itself is.
+    fn undirected_only<'a, K: Eq + Hash>(
+        &'a self,
+        own: &'a HashMap<K, HashSet<RelationshipId>>,
+        opposite: &'a HashMap<K, HashSet<RelationshipId>>,
+        concept_id: &'a K,
+    ) -> impl Iterator<Item = RelationshipId> + 'a {
+        let own = own.get(concept_id);
+        opposite
+            .get(concept_id)
+            .into_iter()
+            .flatten()
+            .filter(move |id| self.undirected.contains(id) && own.is_none_or(|own| !own.contains(id)))
+            .copied()
+    }
+
+    fn edges<K: Eq + Hash>(
+        map: &HashMap<K, HashSet<RelationshipId>>,
+        concept_id: &K,
+    ) -> Vec<RelationshipId> {
+        map.get(concept_id)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn unlink<K: Eq + Hash>(
+        map: &mut HashMap<K, HashSet<RelationshipId>>,
+        concept_id: K,
+        relationship_id: RelationshipId,
+    ) {
+        if let Some(ids) = map.get_mut(&concept_id) {
+            ids.remove(&relationship_id);
+            // Don't keep an empty set around for every concept that ever had an edge.
+            if ids.is_empty() {
+                map.remove(&concept_id);
+            }
+        }
+    }
+}
+
+/// Live concepts filed under names, as `LabelIndex` and `TagIndex` file them.
+#[derive(Debug, Default)]
+struct Filing {
+    concepts: HashMap<String, HashSet<ConceptId>>,
+    // The names each live concept is filed under above.
+    names: HashMap<ConceptId, Vec<String>>,
+}
+
+impl Filing {
+    /// Files the concept under exactly `names`; a tombstone is filed under none.
+    fn file(&mut self, latest: &ConceptVersion, names: impl FnOnce() -> Vec<String>) {
+        self.forget(&latest.concept_id);
+        if latest.deleted_at.is_some() {
+            return;
+        }
+        let names = names();
+        if names.is_empty() {
+            return;
+        }
+        for name in &names {
+            self.concepts.entry(name.clone()).or_default().insert(latest.concept_id);
+        }
+        self.names.insert(latest.concept_id, names);
+    }
+
+    fn forget(&mut self, concept_id: &ConceptId) {
+        for name in self.names.remove(concept_id).into_iter().flatten() {
+            if let Some(ids) = self.concepts.get_mut(&name) {
+                ids.remove(concept_id);
+                if ids.is_empty() {
+                    self.concepts.remove(&name);
+                }
+            }
+        }
+    }
+
+    fn entries(&self) -> HashSet<(String, ConceptId)> {
+        self.concepts
+            .iter()
+            .flat_map(|(name, ids)| ids.iter().map(move |id| (name.clone(), *id)))
+            .collect()
+    }
+
+    fn concepts(&self, name: &str) -> Vec<ConceptId> {
+        self.concepts
+            .get(name)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The live concepts carrying each label, as of each concept's latest version.
+#[derive(Debug, Default)]
+pub struct LabelIndex {
+    filing: Filing,
+}
+
+impl VersionIndex for LabelIndex {
+    /// Moves the concept to its new latest version's labels; a tombstone carries none.
+    fn concept_version(&mut self, latest: &ConceptVersion) {
+        self.filing.file(latest, || labels_of(&parse_data(&latest.data)));
+    }
+
+    fn forget_concept(&mut self, concept_id: &ConceptId) {
+        self.filing.forget(concept_id);
+    }
+}
+
+impl LabelIndex {
+    /// Every (label, concept) entry, for comparing two indexes.
+    pub(crate) fn entries(&self) -> HashSet<(String, ConceptId)> {
+        self.filing.entries()
+    }
+
+    /// The concepts carrying the given label.
+    pub fn concepts(&self, label: &str) -> Vec<ConceptId> {
+        self.filing.concepts(label)
+    }
+}
+
+/// The live concepts carrying each tag, as of each concept's latest version.
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    filing: Filing,
+}
+
+impl VersionIndex for TagIndex {
+    /// Moves the concept to its new latest version's tags; a tombstone carries none.
+    fn concept_version(&mut self, latest: &ConceptVersion) {
+        self.filing.file(latest, || latest.tags.iter().cloned().collect());
+    }
+
+    fn forget_concept(&mut self, concept_id: &ConceptId) {
+        self.filing.forget(concept_id);
+    }
+}
+
+impl TagIndex {
+    /// Every (tag, concept) entry, for comparing two indexes.
+    pub(crate) fn entries(&self) -> HashSet<(String, ConceptId)> {
+        self.filing.entries()
+    }
+
+    /// The concepts carrying the given tag.
+    pub fn concepts(&self, tag: &str) -> Vec<ConceptId> {
+        self.filing.concepts(tag)
+    }
+}
+
+/// When each live concept with an expiry expires, as of each concept's latest version.
+#[derive(Debug, Default)]
+pub struct ExpiryIndex {
+    expirations: HashMap<ConceptId, DateTime<Utc>>,
+}
+
+impl VersionIndex for ExpiryIndex {
+    fn concept_version(&mut self, latest: &ConceptVersion) {
+        match latest.expires_at.filter(|_| latest.deleted_at.is_none()) {
+            Some(expires_at) => self.expirations.insert(latest.concept_id, expires_at),
+            None => self.expirations.remove(&latest.concept_id),
+        };
+    }
+
+    fn forget_concept(&mut self, concept_id: &ConceptId) {
+        self.expirations.remove(concept_id);
+    }
+}
+
+impl ExpiryIndex {
+    /// The concepts that have expired by `now`.
+    pub fn expired(&self, now: DateTime<Utc>) -> HashSet<ConceptId> {
+        self.expirations.iter().filter(|(_, expires_at)| **expires_at <= now).map(|(id, _)| *id).collect()
+    }
+
+    /// The concepts whose expiry falls after `since` and no later than `until`.
+    pub fn expiring_between(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Vec<ConceptId> {
+        self.expirations
+            .iter()
+            .filter(|(_, expires_at)| since < **expires_at && **expires_at <= until)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
@@ -0,0 +1,325 @@
+// GraphEngineLite: the engine's primitives over the in-memory version store alone.
+//
+// It needs neither RocksDB nor tokio, so it is what a build without the `server` feature
+// has, down to `wasm32-unknown-unknown` for a graph living in a browser. Every write is its
+// own commit with a strictly increasing timestamp, applied to the VersionStore straight
+// away; the history is kept just as the server keeps it, so time travel works the same.
+// Nothing is persisted: the graph travels as the JSON Lines `GraphEngine::export_jsonl`
+// writes, which is also how a local graph is synced with a server instance.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, PoisonError};
+use uuid::Uuid;
+
+use super::export::{GraphSnapshot, ImportReport};
+use super::versioning::{VersionStore, concept_from_version, relationship_from_version, tick};
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::{Concept, ConceptId, ConceptVersion, DataLimits, TransactionId};
+use crate::types::relationship::{RelationType, Relationship, RelationshipId, RelationshipVersion};
+
+/// An in-memory graph with the engine's store, relate, retrieve and time-travel primitives.
+#[derive(Debug, Default)]
+pub struct GraphEngineLite {
+    version_store: VersionStore,
+    // The last commit's timestamp. Held for the whole of a write, so writes are serialised.
+    clock: Mutex<DateTime<Utc>>,
+    data_limits: DataLimits,
+}
+
+impl GraphEngineLite {
+    /// An empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty graph that refuses concept data outside `data_limits`.
+    pub fn with_data_limits(data_limits: DataLimits) -> Self {
+        Self { data_limits, ..Self::default() }
+    }
+
+    /// STORE: creates a concept.
+    pub fn store(&self, data: Value) -> Result<ConceptId> {
+        let concept = Concept::new_within(data, &self.data_limits)?;
+        self.commit(|commit_time, transaction_id| {
+            let version = ConceptVersion { created_at: commit_time, ..ConceptVersion::from_concept(&concept, transaction_id, 1) };
+            Ok((vec![version], Vec::new()))
+        })?;
+        Ok(concept.id)
+    }
+
+    /// RELATE: creates a relationship between two active concepts.
+    pub fn relate(
+        &self,
+        source: ConceptId,
+        relationship_type: impl Into<RelationType>,
+        target: ConceptId,
+    ) -> Result<RelationshipId> {
+        let relationship = Relationship::new(source, relationship_type, target);
+        self.commit(|commit_time, transaction_id| {
+            for endpoint in [source, target] {
+                if self.version_store.get_concept_version_at_timestamp(&endpoint, commit_time)?.is_none() {
+                    return Err(MnemonicError::ConceptNotFound(endpoint));
+                }
+            }
+            let version = RelationshipVersion {
+                created_at: commit_time,
+                ..RelationshipVersion::from_relationship(&relationship, transaction_id)
+            };
+            Ok((Vec::new(), vec![version]))
+        })?;
+        Ok(relationship.id)
+    }
+
+    /// UNRELATE: removes a relationship, keeping its history.
+    pub fn unrelate(&self, relationship_id: RelationshipId) -> Result<()> {
+        self.commit(|commit_time, transaction_id| {
+            let latest = self
+                .version_store
+                .get_relationship_version_at_timestamp(&relationship_id, commit_time)?
+                .ok_or(MnemonicError::RelationshipNotFound(relationship_id))?;
+            let version = RelationshipVersion {
+                version: self.version_store.latest_relationship_version_number(&relationship_id)? + 1,
+                deleted_at: Some(commit_time),
+                deleted_by: Some(transaction_id),
+                ..latest
+            };
+            Ok((Vec::new(), vec![version]))
+        })
+    }
+
+    /// FORGET: tombstones a concept, keeping its history.
+    pub fn delete_concept(&self, id: ConceptId) -> Result<()> {
+        self.commit(|commit_time, transaction_id| {
+            let latest = self
+                .version_store
+                .get_concept_version_at_timestamp(&id, commit_time)?
+                .ok_or(MnemonicError::ConceptNotFound(id))?;
+            // Born deleted, as on the server, so reads before now still find the last version.
+            let version = ConceptVersion {
+                version: self.version_store.latest_concept_version_number(&id)? + 1,
+                created_at: commit_time,
+                created_by: transaction_id,
+                deleted_at: Some(commit_time),
+                deleted_by: Some(transaction_id),
+                expires_at: None,
+                ..latest
+            };
+            Ok((vec![version], Vec::new()))
+        })
+    }
+
+    /// The concept as it is now, if it is active.
+    pub fn get_concept(&self, id: ConceptId) -> Result<Option<Concept>> {
+        Ok(self
+            .version_store
+            .get_latest_active_concept_version(&id)?
+            .as_ref()
+            .map(concept_from_version))
+    }
+
+    /// The concept as it stood at `timestamp`, if it was active then.
+    pub fn get_concept_at(&self, id: ConceptId, timestamp: DateTime<Utc>) -> Result<Option<Concept>> {
+        Ok(self
+            .version_store
+            .get_concept_version_at_timestamp(&id, timestamp)?
+            .as_ref()
+            .map(concept_from_version))
+    }
+
+    /// RETRIEVE: the active relationships leaving a concept.
+    pub fn retrieve_by_source(&self, source_id: ConceptId) -> Result<Vec<Relationship>> {
+        Ok(self
+            .version_store
+            .outgoing_relationships(&source_id)?
+            .iter()
+            .map(relationship_from_version)
+            .collect())
+    }
+
+    /// The active relationships pointing at a concept.
+    pub fn retrieve_by_target(&self, target_id: ConceptId) -> Result<Vec<Relationship>> {
+        Ok(self
+            .version_store
+            .incoming_relationships(&target_id)?
+            .iter()
+            .map(relationship_from_version)
+            .collect())
+    }
+
+    /// Every version of a concept, oldest first, tombstones included.
+    pub fn concept_history(&self, id: ConceptId) -> Result<Vec<ConceptVersion>> {
+        self.version_store.concept_history(&id)
+    }
+
+    /// The graph as it stood at `timestamp`.
+    pub fn snapshot_at(&self, timestamp: DateTime<Utc>) -> Result<GraphSnapshot> {
+        GraphSnapshot::at(&self.version_store, timestamp)
+    }
+
+    /// The whole active graph as JSON Lines, in the format `GraphEngine::import_jsonl` reads.
+    pub fn export_jsonl(&self) -> Result<String> {
+        let mut out = Vec::new();
+        self.snapshot_at(Utc::now())?.write_jsonl(&mut out)?;
+        String::from_utf8(out).map_err(|e| MnemonicError::Internal(e.to_string()))
+    }
+
+    /// Imports what `export_jsonl` (or `GraphEngine::export_jsonl`) wrote, in one commit.
+    /// Items that already exist get a new version; a relationship must end at concepts that
+    /// are active or imported alongside it.
+    pub fn import_jsonl(&self, jsonl: &str) -> Result<ImportReport> {
+        let snapshot = GraphSnapshot::read_jsonl(jsonl.as_bytes())?;
+        let report = ImportReport {
+            concepts: snapshot.concepts.len(),
+            relationships: snapshot.relationships.len(),
+        };
+        self.commit(|commit_time, transaction_id| {
+            let imported: HashSet<ConceptId> = snapshot.concepts.iter().map(|concept| concept.id).collect();
+            let mut concept_heads = HashMap::new();
+            let mut concept_versions = Vec::new();
+            for concept in &snapshot.concepts {
+                let version = next_version(&mut concept_heads, concept.id, |id| {
+                    self.version_store.latest_concept_version_number(id)
+                })?;
+                concept_versions.push(ConceptVersion {
+                    created_at: commit_time,
+                    ..ConceptVersion::from_concept(concept, transaction_id, version)
+                });
+            }
+            let mut relationship_heads = HashMap::new();
+            let mut relationship_versions = Vec::new();
+            for relationship in &snapshot.relationships {
+                for endpoint in [relationship.source, relationship.target] {
+                    if !imported.contains(&endpoint)
+                        && self.version_store.get_concept_version_at_timestamp(&endpoint, commit_time)?.is_none()
+                    {
+                        return Err(MnemonicError::DanglingRelationship {
+                            relationship_id: relationship.id,
+                            concept_id: endpoint,
+                        });
+                    }
+                }
+                let version = next_version(&mut relationship_heads, relationship.id, |id| {
+                    self.version_store.latest_relationship_version_number(id)
+                })?;
+                relationship_versions.push(RelationshipVersion {
+                    version,
+                    created_at: commit_time,
+                    ..RelationshipVersion::from_relationship(relationship, transaction_id)
+                });
+            }
+            Ok((concept_versions, relationship_versions))
+        })?;
+        Ok(report)
+    }
+
+    /// Takes the next commit timestamp and has `build` make the commit's versions, then applies
+    /// them. Nothing is applied if `build` fails.
+    fn commit(
+        &self,
+        build: impl FnOnce(DateTime<Utc>, TransactionId) -> Result<(Vec<ConceptVersion>, Vec<RelationshipVersion>)>,
+    ) -> Result<()> {
+        let mut clock = self.clock.lock().unwrap_or_else(PoisonError::into_inner);
+        let (concept_versions, relationship_versions) = build(tick(&mut clock), Uuid::new_v4())?;
+        for version in concept_versions {
+            self.version_store.add_concept_version(version)?;
+        }
+        for version in relationship_versions {
+            self.version_store.add_relationship_version(version)?;
+        }
+        Ok(())
+    }
+}
+
+/// The next version number of `id`, counting the ones this commit has already given it.
+fn next_version(heads: &mut HashMap<Uuid, u64>, id: Uuid, latest: impl FnOnce(&Uuid) -> Result<u64>) -> Result<u64> {
+    let head = match heads.get(&id) {
+        Some(head) => *head,
+        None => latest(&id)?,
+    };
+    heads.insert(id, head + 1);
+    Ok(head + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::query::parse_data;
+    use serde_json::json;
+
+    fn name(concept: &Concept) -> Value {
+        parse_data(&concept.data)["name"].clone()
+    }
+
+    #[test]
+    fn test_store_relate_and_retrieve() {
+        let graph = GraphEngineLite::new();
+        let ada = graph.store(json!({"name": "Ada"})).unwrap();
+        let charles = graph.store(json!({"name": "Charles"})).unwrap();
+        let knows = graph.relate(ada, "knows", charles).unwrap();
+
+        assert_eq!(name(&graph.get_concept(ada).unwrap().unwrap()), json!("Ada"));
+        let outgoing = graph.retrieve_by_source(ada).unwrap();
+        assert_eq!(outgoing.iter().map(|r| (r.id, r.target)).collect::<Vec<_>>(), vec![(knows, charles)]);
+        assert_eq!(graph.retrieve_by_target(charles).unwrap(), outgoing);
+        assert!(matches!(
+            graph.relate(ada, "knows", Uuid::new_v4()),
+            Err(MnemonicError::ConceptNotFound(_))
+        ));
+
+        let strict = GraphEngineLite::with_data_limits(DataLimits { require_object: true, ..Default::default() });
+        assert!(matches!(strict.store(json!("Ada")), Err(MnemonicError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_time_travel_sees_what_was_live_then() {
+        let graph = GraphEngineLite::new();
+        let ada = graph.store(json!({"name": "Ada"})).unwrap();
+        let charles = graph.store(json!({"name": "Charles"})).unwrap();
+        let knows = graph.relate(ada, "knows", charles).unwrap();
+        // The moment of the last commit so far.
+        let before = graph.retrieve_by_source(ada).unwrap()[0].metadata.created_at;
+
+        graph.unrelate(knows).unwrap();
+        graph.delete_concept(charles).unwrap();
+        assert!(graph.get_concept(charles).unwrap().is_none());
+        assert!(graph.retrieve_by_source(ada).unwrap().is_empty());
+        assert_eq!(graph.concept_history(charles).unwrap().len(), 2);
+        assert!(matches!(graph.unrelate(knows), Err(MnemonicError::RelationshipNotFound(_))));
+
+        assert_eq!(name(&graph.get_concept_at(charles, before).unwrap().unwrap()), json!("Charles"));
+        let then = graph.snapshot_at(before).unwrap();
+        assert_eq!((then.concepts.len(), then.relationships.len()), (2, 1));
+        assert_eq!(graph.snapshot_at(Utc::now()).unwrap().concepts.len(), 1);
+    }
+
+    #[test]
+    fn test_jsonl_round_trips_between_graphs() {
+        let graph = GraphEngineLite::new();
+        let ada = graph.store(json!({"name": "Ada"})).unwrap();
+        let charles = graph.store(json!({"name": "Charles"})).unwrap();
+        let knows = graph.relate(ada, "knows", charles).unwrap();
+        let jsonl = graph.export_jsonl().unwrap();
+        assert_eq!(jsonl.lines().count(), 3);
+
+        let copy = GraphEngineLite::new();
+        let report = copy.import_jsonl(&jsonl).unwrap();
+        assert_eq!((report.concepts, report.relationships), (2, 1));
+        assert_eq!(name(&copy.get_concept(ada).unwrap().unwrap()), json!("Ada"));
+        assert_eq!(copy.retrieve_by_source(ada).unwrap()[0].id, knows);
+
+        // Importing again adds a version rather than a second copy.
+        copy.import_jsonl(&jsonl).unwrap();
+        assert_eq!(copy.concept_history(ada).unwrap().len(), 2);
+        assert_eq!(copy.snapshot_at(Utc::now()).unwrap().concepts.len(), 2);
+
+        let dangling = GraphEngineLite::new();
+        let edge_only = jsonl.lines().last().unwrap();
+        assert!(matches!(
+            dangling.import_jsonl(edge_only),
+            Err(MnemonicError::DanglingRelationship { .. })
+        ));
+        assert!(dangling.snapshot_at(Utc::now()).unwrap().relationships.is_empty());
+    }
+}
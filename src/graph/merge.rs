@@ -0,0 +1,91 @@
+// Merging two concepts that turn out to be the same thing.
+//
+// A merge is an ordinary transaction: the absorbed concept is tombstoned, every live edge
+// that touches it is tombstoned and re-created against the kept concept, and the kept
+// concept gets a new version holding the merged data. Nothing is rewritten in place, so
+// time-travel queries from before the merge still see both concepts and the old edges.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::types::concept::ConceptId;
+use crate::types::relationship::RelationshipId;
+
+/// What the kept concept holds after a merge.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// The kept concept's data, unchanged.
+    #[default]
+    KeepTarget,
+    /// The kept concept's fields, overwritten by the absorbed concept's top-level fields.
+    /// If either side isn't a JSON object, the absorbed concept's data wins outright.
+    PreferAbsorbFields,
+    /// Exactly this data.
+    Replace(Value),
+}
+
+impl MergeStrategy {
+    /// The merged data, or `None` when the kept concept keeps what it has.
+    pub(crate) fn merge(&self, keep: &Value, absorb: &Value) -> Option<Value> {
+        match self {
+            MergeStrategy::KeepTarget => None,
+            MergeStrategy::PreferAbsorbFields => match (keep, absorb) {
+                (Value::Object(keep), Value::Object(absorb)) => {
+                    let mut merged = keep.clone();
+                    merged.extend(absorb.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    Some(Value::Object(merged))
+                }
+                _ => Some(absorb.clone()),
+            },
+            MergeStrategy::Replace(data) => Some(data.clone()),
+        }
+    }
+}
+
+/// How `GraphEngine::merge_concepts_with` treats edges between the two concepts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MergeOptions {
+    /// Keep the self-relationships on the kept concept that edges between the two concepts
+    /// turn into. By default they are dropped.
+    pub keep_self_relationships: bool,
+}
+
+/// The outcome of a merge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub kept: ConceptId,
+    pub absorbed: ConceptId,
+    /// Each rewired relationship, mapped to the one that replaces it.
+    pub relationships_rewired: HashMap<RelationshipId, RelationshipId>,
+    /// Relationships that would have become self-relationships and were removed instead.
+    pub self_relationships_dropped: Vec<RelationshipId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_strategies_merge_data() {
+        let keep = json!({"name": "Ada Lovelace", "born": 1815});
+        let absorb = json!({"name": "Ada", "field": "maths"});
+
+        assert_eq!(MergeStrategy::KeepTarget.merge(&keep, &absorb), None);
+        assert_eq!(
+            MergeStrategy::PreferAbsorbFields.merge(&keep, &absorb),
+            Some(json!({"name": "Ada", "born": 1815, "field": "maths"}))
+        );
+        assert_eq!(
+            MergeStrategy::PreferAbsorbFields.merge(&keep, &json!("Ada")),
+            Some(json!("Ada"))
+        );
+        assert_eq!(
+            MergeStrategy::Replace(json!({"name": "A. Lovelace"})).merge(&keep, &absorb),
+            Some(json!({"name": "A. Lovelace"}))
+        );
+    }
+}
@@ -1,10 +1,97 @@
 // Graph engine module
 
+#[cfg(feature = "server")]
+pub mod analysis;
+pub mod archive;
+pub mod audit;
+pub mod budget;
+pub mod bulk;
+#[cfg(feature = "server")]
+pub mod check;
+#[cfg(feature = "server")]
+pub mod consistency;
+pub mod deadline;
+#[cfg(feature = "server")]
+pub mod delta;
+#[cfg(feature = "server")]
 pub mod engine;
+pub mod entity;
+pub mod events;
+pub mod export;
+#[cfg(feature = "server")]
+pub mod hydration;
+#[cfg(feature = "server")]
+pub mod merge;
+#[cfg(feature = "server")]
+pub mod neo4j;
+pub mod observers;
+#[cfg(feature = "server")]
+pub mod pruning;
+#[cfg(feature = "server")]
+pub mod query;
+#[cfg(feature = "server")]
+pub mod rebuild;
+#[cfg(feature = "server")]
+pub mod replication;
+pub mod seed;
+#[cfg(feature = "server")]
+pub mod snapshots;
+#[cfg(feature = "server")]
 pub mod storage;
 pub mod indices;
+pub mod lite;
 pub mod versioning;
+#[cfg(feature = "server")]
+pub mod worker_pool;
+#[cfg(feature = "server")]
 pub mod transaction;
 
-pub use engine::GraphEngine;
-pub use transaction::{Transaction, TransactionId, IsolationLevel};
\ No newline at end of file
+pub use archive::{Archive, ArchiveRecord, ArchiveReport, ARCHIVE_FORMAT, ARCHIVE_FORMAT_VERSION};
+pub use audit::{
+    ActivityBucket, AuditCursor, AuditPage, AuditQuery, AuditRecord, BucketWidth, ChangeKind, ItemKind, MAX_ACTIVITY_BUCKETS,
+};
+pub use budget::{ScanBudget, ScanMeter, DEFAULT_SCAN_BUDGET};
+pub use bulk::{BulkDeleteFailure, BulkDeleteOptions, BulkDeleteReport};
+#[cfg(feature = "server")]
+pub use check::{CheckDepth, CheckReport, Finding};
+#[cfg(feature = "server")]
+pub use consistency::{ConsistencyReport, Discrepancy};
+#[cfg(feature = "server")]
+pub use delta::GraphDelta;
+#[cfg(feature = "server")]
+pub use engine::{
+    DeleteOptions, DeleteReport, EngineConfig, EngineHealth, GraphEngine, GraphStats, RelateOptions, RelationshipEndpoints,
+    RelationshipUpdate, StoreOptions, StoreReport, Traversal, TraversalLimits,
+};
+pub use deadline::{CancelOnDrop, Deadline};
+pub use entity::ConceptEntity;
+pub use events::CommitEvent;
+pub use export::{ExportRecord, ExportReport, GraphSnapshot, ImportReport};
+#[cfg(feature = "server")]
+pub use merge::{MergeOptions, MergeReport, MergeStrategy};
+#[cfg(feature = "server")]
+pub use neo4j::{ImportRowError, ImportStats};
+pub use indices::IndexRebuild;
+pub use observers::{ObserverFailures, VersionObserver};
+pub use lite::GraphEngineLite;
+#[cfg(feature = "server")]
+pub use query::{GraphQuery, QueryResult, QueryRow};
+#[cfg(feature = "server")]
+pub use rebuild::RebuildReport;
+#[cfg(feature = "server")]
+pub use replication::{ChangeSource, CommitFeed};
+#[cfg(feature = "server")]
+pub use hydration::HydrationMode;
+#[cfg(feature = "server")]
+pub use pruning::{PruneReport, PurgeOptions, PurgeReport, RetentionPolicy};
+pub use seed::{SeedConcept, SeedFixture, SeedRelationship, SeedReport};
+#[cfg(feature = "server")]
+pub use snapshots::{ReadSnapshot, SnapshotToken};
+#[cfg(feature = "server")]
+pub use transaction::{
+    CommitReceipt, IsolationLevel, ReplicationGuard, Transaction, TransactionConfig, TransactionId, TransactionSummary,
+    EXPIRY_SWEEPER,
+};
+pub use versioning::VersionStoreStats;
+#[cfg(feature = "server")]
+pub use worker_pool::{WorkerPool, WorkerPoolConfig};
@@ -0,0 +1,443 @@
+// Importing the node and relationship CSV files Neo4j exports (the format
+// `neo4j-admin database import` reads), for moving a graph over from Neo4j.
+//
+// The first line of each file is its header. A node file has an ID column, `:ID` (or
+// `name:ID`, which also keeps the ID as the `name` property), and optionally a `:LABEL`
+// column of `;`-separated labels, which become the concept's `labels`. A relationship file
+// has `:START_ID`, `:END_ID` and `:TYPE`. IDs can be grouped into ID spaces, `:ID(Person)`,
+// which relationship columns name the same way. Every other column is a property written
+// `name:type`, such as `age:int` or `tags:string[]` (values split on `;`); untyped columns
+// are strings, `:IGNORE` columns are skipped, and empty fields are left out. Each concept
+// keeps its Neo4j ID in `neo4j_id`. Relationships here carry no properties, so those of
+// relationship files are counted and dropped.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::types::concept::{Concept, ConceptId, DataLimits};
+use crate::types::relationship::Relationship;
+
+/// The data field each imported concept keeps its Neo4j ID in.
+pub const NEO4J_ID_FIELD: &str = "neo4j_id";
+
+/// What `GraphEngine::import_neo4j_csv` imported, and the rows it couldn't.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportStats {
+    pub concepts: usize,
+    pub relationships: usize,
+    /// Property values on relationship rows, which had nowhere to go.
+    pub relationship_properties_dropped: usize,
+    /// Every row left out, in file order. A header that can't be read is reported at line 1
+    /// and its whole file is skipped.
+    pub errors: Vec<ImportRowError>,
+}
+
+/// A row `GraphEngine::import_neo4j_csv` left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportRowError {
+    pub file: PathBuf,
+    /// The line the row starts on; the header is line 1.
+    pub line: usize,
+    pub message: String,
+}
+
+/// What a set of files holds, ready to be written in one transaction.
+#[derive(Debug, Default)]
+pub(crate) struct Neo4jImport {
+    pub concepts: Vec<Concept>,
+    pub relationships: Vec<Relationship>,
+    pub stats: ImportStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PropertyType {
+    Int,
+    Float,
+    Boolean,
+    String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Column {
+    Id { space: Option<String>, property: Option<String> },
+    Label,
+    StartId(Option<String>),
+    EndId(Option<String>),
+    Type,
+    Ignore,
+    Property { name: String, kind: PropertyType, array: bool },
+}
+
+/// A node by its ID space and ID.
+type NodeKey = (Option<String>, String);
+
+/// Reads every node file, then every relationship file. Rows that can't be imported are
+/// collected into the stats with their line numbers; only failing to read a file is an error.
+pub(crate) fn read(nodes_files: &[PathBuf], rels_files: &[PathBuf], limits: &DataLimits) -> Result<Neo4jImport> {
+    let mut import = Neo4jImport::default();
+    let mut nodes: HashMap<NodeKey, ConceptId> = HashMap::new();
+    for path in nodes_files {
+        read_file(path, &mut import.stats, |columns, fields| {
+            let (key, data) = node_row(columns, fields)?;
+            if nodes.contains_key(&key) {
+                return Err(format!("duplicate node ID {:?}", key.1));
+            }
+            limits.check(&data).map_err(|e| e.to_string())?;
+            let concept = Concept::new(data);
+            nodes.insert(key, concept.id);
+            import.concepts.push(concept);
+            Ok(0)
+        })?;
+    }
+    for path in rels_files {
+        let mut relationships = Vec::new();
+        let dropped = read_file(path, &mut import.stats, |columns, fields| {
+            let (relationship, dropped) = relationship_row(columns, fields, &nodes)?;
+            relationships.push(relationship);
+            Ok(dropped)
+        })?;
+        import.relationships.extend(relationships);
+        import.stats.relationship_properties_dropped += dropped;
+    }
+    Ok(import)
+}
+
+/// Feeds each row of one file to `row`, recording the rows it refuses. Returns the sum of
+/// the counts it returned for the rows it took.
+fn read_file(
+    path: &Path,
+    stats: &mut ImportStats,
+    mut row: impl FnMut(&[Column], Vec<String>) -> std::result::Result<usize, String>,
+) -> Result<usize> {
+    let mut records = Records::new(BufReader::new(File::open(path)?));
+    let mut total = 0;
+    let mut refuse = |line: usize, message: String| {
+        stats.errors.push(ImportRowError { file: path.to_path_buf(), line, message });
+    };
+    let columns = match records.next().transpose()? {
+        Some((line, Ok(header))) => match parse_header(&header) {
+            Ok(columns) => columns,
+            Err(message) => {
+                refuse(line, message);
+                return Ok(total);
+            }
+        },
+        Some((line, Err(message))) => {
+            refuse(line, message);
+            return Ok(total);
+        }
+        None => return Ok(total),
+    };
+    for record in records {
+        let (line, fields) = record?;
+        let result = fields.and_then(|fields| {
+            if fields.len() != columns.len() {
+                return Err(format!("expected {} fields, found {}", columns.len(), fields.len()));
+            }
+            row(&columns, fields)
+        });
+        match result {
+            Ok(value) => total += value,
+            Err(message) => refuse(line, message),
+        }
+    }
+    Ok(total)
+}
+
+fn parse_header(fields: &[String]) -> std::result::Result<Vec<Column>, String> {
+    fields.iter().map(|field| parse_column(field.trim())).collect()
+}
+
+fn parse_column(field: &str) -> std::result::Result<Column, String> {
+    let (name, kind) = match field.rsplit_once(':') {
+        Some((name, kind)) => (name, kind),
+        None => (field, "string"),
+    };
+    // `:ID(Person)` and friends name an ID space.
+    let (kind, space) = match kind.split_once('(') {
+        Some((kind, rest)) => match rest.strip_suffix(')') {
+            Some(space) => (kind, Some(space.to_string())),
+            None => return Err(format!("unclosed ID space in header column {:?}", field)),
+        },
+        None => (kind, None),
+    };
+    let column = match kind.to_ascii_uppercase().as_str() {
+        "ID" => Column::Id { space, property: (!name.is_empty()).then(|| name.to_string()) },
+        "START_ID" => Column::StartId(space),
+        "END_ID" => Column::EndId(space),
+        "LABEL" => Column::Label,
+        "TYPE" => Column::Type,
+        "IGNORE" => Column::Ignore,
+        _ if space.is_some() => return Err(format!("only ID columns take an ID space, not {:?}", field)),
+        _ if name.is_empty() => return Err(format!("header column {:?} has no name", field)),
+        _ => {
+            let (kind, array) = match kind.strip_suffix("[]") {
+                Some(kind) => (kind, true),
+                None => (kind, false),
+            };
+            let kind = match kind.to_ascii_lowercase().as_str() {
+                "int" | "long" | "short" | "byte" => PropertyType::Int,
+                "float" | "double" => PropertyType::Float,
+                "boolean" => PropertyType::Boolean,
+                // Temporal and spatial values are kept as Neo4j wrote them.
+                "string" | "char" | "date" | "time" | "localtime" | "datetime" | "localdatetime" | "duration"
+                | "point" => PropertyType::String,
+                other => return Err(format!("unknown type {:?} in header column {:?}", other, field)),
+            };
+            Column::Property { name: name.to_string(), kind, array }
+        }
+    };
+    Ok(column)
+}
+
+/// A node row's key and the concept data it becomes.
+fn node_row(columns: &[Column], fields: Vec<String>) -> std::result::Result<(NodeKey, Value), String> {
+    let mut data = Map::new();
+    let mut key = None;
+    let mut labels = Vec::new();
+    for (column, field) in columns.iter().zip(fields) {
+        match column {
+            Column::Id { space, property } => {
+                if field.is_empty() {
+                    return Err("missing node ID".to_string());
+                }
+                if let Some(property) = property {
+                    data.insert(property.clone(), Value::String(field.clone()));
+                }
+                key = Some((space.clone(), field));
+            }
+            Column::Label => labels.extend(
+                field
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|label| !label.is_empty())
+                    .map(|label| Value::String(label.to_string())),
+            ),
+            Column::Property { name, kind, array } => {
+                if let Some(value) = property_value(name, *kind, *array, &field)? {
+                    data.insert(name.clone(), value);
+                }
+            }
+            Column::Ignore => {}
+            Column::StartId(_) | Column::EndId(_) | Column::Type => {
+                return Err("node files can't have :START_ID, :END_ID or :TYPE columns".to_string());
+            }
+        }
+    }
+    let Some(key) = key else {
+        return Err("node files need an :ID column".to_string());
+    };
+    if !labels.is_empty() {
+        data.insert("labels".to_string(), Value::Array(labels));
+    }
+    data.insert(NEO4J_ID_FIELD.to_string(), Value::String(key.1.clone()));
+    Ok((key, Value::Object(data)))
+}
+
+/// A relationship row's relationship, and how many property values it dropped.
+fn relationship_row(
+    columns: &[Column],
+    fields: Vec<String>,
+    nodes: &HashMap<NodeKey, ConceptId>,
+) -> std::result::Result<(Relationship, usize), String> {
+    let (mut source, mut target, mut relationship_type) = (None, None, None);
+    let mut dropped = 0;
+    for (column, field) in columns.iter().zip(fields) {
+        match column {
+            Column::StartId(space) => source = Some(endpoint("start", space, field, nodes)?),
+            Column::EndId(space) => target = Some(endpoint("end", space, field, nodes)?),
+            Column::Type if field.trim().is_empty() => return Err("missing relationship type".to_string()),
+            Column::Type => relationship_type = Some(field.trim().to_string()),
+            Column::Property { name, kind, array } => {
+                // Still checked, so a bad value is reported like it would be on a node.
+                if property_value(name, *kind, *array, &field)?.is_some() {
+                    dropped += 1;
+                }
+            }
+            Column::Ignore => {}
+            Column::Id { .. } | Column::Label => {
+                return Err("relationship files can't have :ID or :LABEL columns".to_string());
+            }
+        }
+    }
+    match (source, relationship_type, target) {
+        (Some(source), Some(relationship_type), Some(target)) => {
+            Ok((Relationship::new(source, relationship_type, target), dropped))
+        }
+        _ => Err("relationship files need :START_ID, :END_ID and :TYPE columns".to_string()),
+    }
+}
+
+fn endpoint(
+    end: &str,
+    space: &Option<String>,
+    id: String,
+    nodes: &HashMap<NodeKey, ConceptId>,
+) -> std::result::Result<ConceptId, String> {
+    let key = (space.clone(), id);
+    nodes.get(&key).copied().ok_or_else(|| match space {
+        Some(space) => format!("unknown {} node ID {:?} in ID space {:?}", end, key.1, space),
+        None => format!("unknown {} node ID {:?}", end, key.1),
+    })
+}
+
+/// A property's value, or `None` for an empty field.
+fn property_value(
+    name: &str,
+    kind: PropertyType,
+    array: bool,
+    field: &str,
+) -> std::result::Result<Option<Value>, String> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    if !array {
+        return scalar(name, kind, field).map(Some);
+    }
+    let values = field
+        .split(';')
+        .map(|item| scalar(name, kind, item))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(Some(Value::Array(values)))
+}
+
+fn scalar(name: &str, kind: PropertyType, text: &str) -> std::result::Result<Value, String> {
+    let invalid = |what: &str| format!("{}: {:?} is not {}", name, text, what);
+    match kind {
+        PropertyType::String => Ok(Value::String(text.to_string())),
+        PropertyType::Int => text.trim().parse::<i64>().map(Value::from).map_err(|_| invalid("an integer")),
+        PropertyType::Float => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| invalid("a finite number")),
+        PropertyType::Boolean => match text.trim().to_ascii_lowercase().as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(invalid("a boolean")),
+        },
+    }
+}
+
+/// The records of a CSV file, each with the line it starts on: fields are split on commas,
+/// and a field in double quotes may hold commas, newlines and doubled quotes. Blank lines are
+/// skipped. A record that can't be split comes back as the reason instead.
+struct Records<R> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    fn new(reader: R) -> Self {
+        Self { lines: reader.lines(), line: 0 }
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<(usize, std::result::Result<Vec<String>, String>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut text = String::new();
+        let mut start = None;
+        loop {
+            let Some(next) = self.lines.next() else {
+                return start.map(|start| Ok((start, Err("unterminated quoted field".to_string()))));
+            };
+            let next = match next {
+                Ok(next) => next,
+                Err(e) => return Some(Err(e)),
+            };
+            self.line += 1;
+            if start.is_none() && next.trim().is_empty() {
+                continue;
+            }
+            let start = *start.get_or_insert(self.line);
+            if !text.is_empty() || start != self.line {
+                text.push('\n');
+            }
+            text.push_str(next.trim_end_matches('\r'));
+            // Every quote opens or closes a quoted stretch; `""` does both.
+            if text.matches('"').count().is_multiple_of(2) {
+                return Some(Ok((start, split_record(&text))));
+            }
+        }
+    }
+}
+
+fn split_record(text: &str) -> std::result::Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => {
+                quoted = false;
+                if chars.peek().is_some_and(|next| *next != ',') {
+                    return Err("unexpected text after a closing quote".to_string());
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(text: &str) -> Vec<(usize, std::result::Result<Vec<String>, String>)> {
+        Records::new(text.as_bytes()).map(|record| record.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_quoted_fields_hold_commas_quotes_and_newlines() {
+        let text = "id:ID,bio\n\n1,\"says \"\"hi\"\", then\nleaves\"\r\n2,plain\n3,\"oops\"x\n";
+        assert_eq!(
+            records(text),
+            vec![
+                (1, Ok(vec!["id:ID".to_string(), "bio".to_string()])),
+                (3, Ok(vec!["1".to_string(), "says \"hi\", then\nleaves".to_string()])),
+                (5, Ok(vec!["2".to_string(), "plain".to_string()])),
+                (6, Err("unexpected text after a closing quote".to_string())),
+            ]
+        );
+        assert_eq!(records("a,\"b\n"), vec![(1, Err("unterminated quoted field".to_string()))]);
+    }
+
+    #[test]
+    fn test_header_columns_and_typed_values() {
+        let header: Vec<String> = ["name:ID(Person)", ":LABEL", "age:int", "tags:string[]", "skip:IGNORE", "note"]
+            .map(String::from)
+            .to_vec();
+        let columns = parse_header(&header).unwrap();
+        assert_eq!(columns[0], Column::Id { space: Some("Person".to_string()), property: Some("name".to_string()) });
+        assert_eq!(columns[5], Column::Property { name: "note".to_string(), kind: PropertyType::String, array: false });
+        assert!(parse_column("age:integer").unwrap_err().contains("unknown type"));
+
+        let row = ["ada", "Person;Admin", "36", "a;b", "x", ""].map(String::from).to_vec();
+        let (key, data) = node_row(&columns, row).unwrap();
+        assert_eq!(key, (Some("Person".to_string()), "ada".to_string()));
+        assert_eq!(
+            data,
+            serde_json::json!({"name": "ada", "labels": ["Person", "Admin"], "age": 36, "tags": ["a", "b"], "neo4j_id": "ada"})
+        );
+        let bad_age = ["lin", "", "old", "", "", ""].map(String::from).to_vec();
+        assert_eq!(node_row(&columns, bad_age).unwrap_err(), "age: \"old\" is not an integer");
+    }
+}
@@ -0,0 +1,169 @@
+// Keeping indexes of the graph up to date from the versions a VersionStore takes in. Every
+// version is handed to each observer once: as it is committed, or as it is loaded from disk
+// while the store hydrates. The store's own label, adjacency and expiry indexes are fed the
+// same way, before any observer registered with `VersionStore::register_observer`.
+//
+// Observers are called under the store's locks, so they must be quick and must not call back
+// into the store. A failing observer never fails the commit: its errors are counted and the
+// last one kept for `GraphEngine::health`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::error::Result;
+use crate::graph::indices::VersionIndex;
+use crate::types::concept::{ConceptId, ConceptVersion};
+use crate::types::relationship::{RelationshipId, RelationshipVersion};
+use crate::utils::locks::LockHealth;
+
+/// Something told about every version a `VersionStore` takes in. Versions of one item come
+/// in order; versions of different items come in no particular order.
+pub trait VersionObserver: Send + Sync {
+    /// Names the observer in `ObserverFailures`.
+    fn name(&self) -> &str;
+
+    fn on_concept_version(&self, _version: &ConceptVersion) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_relationship_version(&self, _version: &RelationshipVersion) -> Result<()> {
+        Ok(())
+    }
+
+    /// The concept's whole history has been dropped, by pruning or a repair.
+    fn on_concept_forgotten(&self, _concept_id: &ConceptId) -> Result<()> {
+        Ok(())
+    }
+
+    /// The relationship's whole history has been dropped.
+    fn on_relationship_forgotten(&self, _relationship_id: &RelationshipId) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// How often an observer has failed, and how it last did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObserverFailures {
+    pub observer: String,
+    pub failures: u64,
+    pub last_error: String,
+}
+
+/// One of a VersionStore's built-in indexes, behind its own lock, fed like an observer.
+#[derive(Debug)]
+pub(crate) struct Indexed<T> {
+    name: &'static str,
+    index: RwLock<T>,
+    lock_health: Arc<LockHealth>,
+}
+
+impl<T: Default> Indexed<T> {
+    pub(crate) fn new(name: &'static str, lock_health: &Arc<LockHealth>) -> Self {
+        Self { name, index: RwLock::default(), lock_health: Arc::clone(lock_health) }
+    }
+}
+
+impl<T> Indexed<T> {
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.lock_health.read(&self.index)
+    }
+
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.lock_health.write(&self.index)
+    }
+}
+
+impl<T: VersionIndex + Send + Sync> VersionObserver for Indexed<T> {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn on_concept_version(&self, version: &ConceptVersion) -> Result<()> {
+        self.write().concept_version(version);
+        Ok(())
+    }
+
+    fn on_relationship_version(&self, version: &RelationshipVersion) -> Result<()> {
+        self.write().relationship_version(version);
+        Ok(())
+    }
+
+    fn on_concept_forgotten(&self, concept_id: &ConceptId) -> Result<()> {
+        self.write().forget_concept(concept_id);
+        Ok(())
+    }
+
+    fn on_relationship_forgotten(&self, relationship_id: &RelationshipId) -> Result<()> {
+        self.write().forget_relationship(relationship_id);
+        Ok(())
+    }
+}
+
+/// The observers registered with a VersionStore, and how they have failed.
+pub(crate) struct Observers {
+    registered: RwLock<Vec<Box<dyn VersionObserver>>>,
+    failures: RwLock<BTreeMap<String, ObserverFailures>>,
+    lock_health: Arc<LockHealth>,
+}
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let registered = self.lock_health.read(&self.registered);
+        f.debug_list().entries(registered.iter().map(|observer| observer.name())).finish()
+    }
+}
+
+impl Observers {
+    pub(crate) fn new(lock_health: &Arc<LockHealth>) -> Self {
+        Self {
+            registered: RwLock::default(),
+            failures: RwLock::default(),
+            lock_health: Arc::clone(lock_health),
+        }
+    }
+
+    pub(crate) fn register(&self, observer: Box<dyn VersionObserver>) {
+        self.lock_health.write(&self.registered).push(observer);
+    }
+
+    /// Whether anything besides the built-in indexes is listening.
+    pub(crate) fn any(&self) -> bool {
+        !self.lock_health.read(&self.registered).is_empty()
+    }
+
+    /// Tells `builtin`, then every registered observer.
+    pub(crate) fn tell(&self, builtin: &[&dyn VersionObserver], event: impl Fn(&dyn VersionObserver) -> Result<()>) {
+        for observer in builtin {
+            self.tell_one(*observer, &event);
+        }
+        self.tell_registered(event);
+    }
+
+    /// Tells only the registered observers, for versions the built-in indexes don't need.
+    pub(crate) fn tell_registered(&self, event: impl Fn(&dyn VersionObserver) -> Result<()>) {
+        for observer in self.lock_health.read(&self.registered).iter() {
+            self.tell_one(observer.as_ref(), &event);
+        }
+    }
+
+    /// Tells one observer, recording the error if it fails.
+    pub(crate) fn tell_one(&self, observer: &dyn VersionObserver, event: impl Fn(&dyn VersionObserver) -> Result<()>) {
+        if let Err(e) = event(observer) {
+            tracing::warn!("Version observer {} failed: {}", observer.name(), e);
+            let mut failures = self.lock_health.write(&self.failures);
+            let entry = failures.entry(observer.name().to_string()).or_insert_with(|| ObserverFailures {
+                observer: observer.name().to_string(),
+                failures: 0,
+                last_error: String::new(),
+            });
+            entry.failures += 1;
+            entry.last_error = e.to_string();
+        }
+    }
+
+    /// Every observer that has failed, by name.
+    pub(crate) fn failures(&self) -> Vec<ObserverFailures> {
+        self.lock_health.read(&self.failures).values().cloned().collect()
+    }
+}
@@ -0,0 +1,285 @@
+// Garbage collection of old versions, in memory and in the 'versions' cabinet.
+
+use chrono::{DateTime, Duration, Utc};
+use rocksdb::WriteBatch;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use super::transaction::{IsolationLevel, Transaction};
+use super::versioning::{VersionStamp, VersionStore};
+use crate::error::{MnemonicError, Result};
+use crate::storage::{keys, RocksBackend};
+use crate::types::concept::{ConceptId, TransactionId};
+use crate::types::content::ContentHash;
+use crate::types::relationship::RelationshipVersion;
+
+/// Which old versions `GraphEngine::prune_versions` may remove.
+/// An item's latest version is never pruned, whatever the policy says.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep at least this many of each item's newest versions.
+    pub keep_latest: usize,
+    /// Only prune versions that were superseded at least this many seconds ago.
+    pub min_age_secs: Option<u64>,
+}
+
+/// The outcome of `GraphEngine::prune_versions`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub concept_versions_pruned: usize,
+    pub relationship_versions_pruned: usize,
+    /// How many concepts and relationships lost at least one version.
+    pub items_pruned: usize,
+    /// How many of those lost all their oldest versions at once, in one range delete.
+    #[serde(default)]
+    pub range_deletes: usize,
+    /// How many blobs no version referred to any more, and were deleted with them.
+    #[serde(default)]
+    pub blobs_collected: usize,
+}
+
+/// How `GraphEngine::purge_concept_with` treats the relationships of the purged concept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PurgeOptions {
+    /// Keep the history of the concept's relationships. The purge is then refused while any
+    /// of them is still active, since it would be left pointing at nothing.
+    pub keep_relationships: bool,
+}
+
+/// The outcome of `GraphEngine::purge_concept`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    pub concept_id: ConceptId,
+    pub concept_versions_purged: usize,
+    pub relationships_purged: usize,
+    pub relationship_versions_purged: usize,
+    #[serde(default)]
+    pub blobs_collected: usize,
+}
+
+/// Removes the versions `policy` allows from memory and disk, or nothing at all if an
+/// active transaction's snapshot still reads one of them.
+/// Callers must hold the commit lock so no commit lands halfway through.
+pub(crate) fn prune(
+    version_store: &VersionStore,
+    backend: &RocksBackend,
+    policy: &RetentionPolicy,
+    active: &[Transaction],
+) -> Result<PruneReport> {
+    let superseded_before = Utc::now() - Duration::seconds(policy.min_age_secs.unwrap_or(0) as i64);
+    let concepts = prunable(version_store.concept_version_stamps()?, policy, superseded_before);
+    let relationships =
+        prunable(version_store.relationship_version_stamps()?, policy, superseded_before);
+
+    // 1. Refuse if any snapshot would lose the version it reads.
+    let mut blocking: Vec<TransactionId> = active
+        .iter()
+        .filter(|txn| txn.isolation_level != IsolationLevel::ReadCommitted)
+        .filter(|txn| {
+            reads_any(&concepts, txn.start_timestamp)
+                || reads_any(&relationships, txn.start_timestamp)
+        })
+        .map(|txn| txn.id)
+        .collect();
+    if !blocking.is_empty() {
+        blocking.sort();
+        return Err(MnemonicError::PruneBlocked(blocking));
+    }
+
+    // 2. Disk first, so a crash leaves memory holding a superset of what's persisted.
+    // A chain that keeps only its newest versions loses the rest in one range delete;
+    // anything else is deleted key by key.
+    let mut batch = WriteBatch::default();
+    let mut report = PruneReport::default();
+    let mut concept_ranges = Vec::new();
+    for (concept_id, chain) in &concepts {
+        match chain.first_kept() {
+            Some(first_kept) => concept_ranges.push((*concept_id, first_kept)),
+            None => {
+                for &(version, _) in &chain.pruned {
+                    backend.delete_concept_version(concept_id, version, &mut batch)?;
+                }
+            }
+        }
+        report.concept_versions_pruned += chain.pruned.len();
+    }
+    let mut relationship_ranges = Vec::new();
+    for (rel_id, chain) in &relationships {
+        match chain.first_kept() {
+            Some(first_kept) => relationship_ranges.push((*rel_id, first_kept)),
+            None => {
+                for &(version, _) in &chain.pruned {
+                    backend.delete_relationship_version(rel_id, version, &mut batch)?;
+                }
+            }
+        }
+        report.relationship_versions_pruned += chain.pruned.len();
+    }
+    backend.delete_versions_before(keys::CONCEPT_VERSION, &concept_ranges, &mut batch);
+    backend.delete_versions_before(keys::RELATIONSHIP_VERSION, &relationship_ranges, &mut batch);
+    report.items_pruned = concepts.len() + relationships.len();
+    report.range_deletes = concept_ranges.len() + relationship_ranges.len();
+    backend.db.write(batch)?;
+    if report.concept_versions_pruned > 0 {
+        report.blobs_collected = backend.collect_blobs()?;
+    }
+
+    // 3. Memory.
+    for (concept_id, chain) in &concepts {
+        version_store.remove_concept_versions(concept_id, &chain.numbers())?;
+    }
+    for (rel_id, chain) in &relationships {
+        version_store.remove_relationship_versions(rel_id, &chain.numbers())?;
+    }
+
+    Ok(report)
+}
+
+/// Erases a concept from memory and disk as if it had never existed: every version, its
+/// version counter, the given content hash index entries and, unless `options` keeps them,
+/// every relationship that ever touched it.
+/// Refused while an active transaction could still read any of it.
+/// Callers must hold the commit lock so no commit lands halfway through.
+pub(crate) fn purge(
+    version_store: &VersionStore,
+    backend: &RocksBackend,
+    concept_id: ConceptId,
+    options: &PurgeOptions,
+    content_hashes: &[ContentHash],
+    active: &[Transaction],
+) -> Result<PurgeReport> {
+    let history = version_store.concept_history(&concept_id)?;
+    if history.is_empty() {
+        return Err(MnemonicError::ConceptNotFound(concept_id));
+    }
+    let relationships = if options.keep_relationships {
+        let now = Utc::now();
+        if let Some(active_rel) = version_store
+            .relationship_histories_touching(&concept_id)?
+            .iter()
+            .filter_map(|chain| chain.last())
+            .find(|latest| latest.is_active_at(now))
+        {
+            return Err(MnemonicError::DanglingRelationship {
+                relationship_id: active_rel.relationship_id,
+                concept_id,
+            });
+        }
+        Vec::new()
+    } else {
+        version_store.relationship_histories_touching(&concept_id)?
+    };
+
+    // 1. Refuse if any transaction could see any of it. Read-committed transactions read
+    // whatever is latest, and snapshots taken after the first version can see that version.
+    let first_written = history
+        .iter()
+        .map(|v| v.created_at)
+        .chain(relationships.iter().flatten().map(|v| v.created_at))
+        .min()
+        .unwrap_or_else(Utc::now);
+    let mut blocking: Vec<TransactionId> = active
+        .iter()
+        .filter(|txn| {
+            txn.isolation_level == IsolationLevel::ReadCommitted
+                || txn.start_timestamp >= first_written
+        })
+        .map(|txn| txn.id)
+        .collect();
+    if !blocking.is_empty() {
+        blocking.sort();
+        return Err(MnemonicError::PurgeBlocked(blocking));
+    }
+
+    // 2. Disk first, so a crash leaves memory holding a superset of what's persisted.
+    let mut batch = WriteBatch::default();
+    let concept_versions: Vec<u64> = history.iter().map(|v| v.version).collect();
+    backend.delete_concept_records(&concept_id, &concept_versions, &mut batch)?;
+    for hash in content_hashes {
+        backend.delete_content_hash(*hash, &mut batch)?;
+    }
+    let mut report = PurgeReport {
+        concept_id,
+        concept_versions_purged: concept_versions.len(),
+        relationships_purged: relationships.len(),
+        relationship_versions_purged: 0,
+        blobs_collected: 0,
+    };
+    for chain in &relationships {
+        let versions: Vec<u64> = chain.iter().map(|v| v.version).collect();
+        if let Some(latest) = chain.last() {
+            backend.delete_relationship_records(latest, &versions, &mut batch)?;
+        }
+        report.relationship_versions_purged += versions.len();
+    }
+    backend.db.write(batch)?;
+    report.blobs_collected = backend.collect_blobs()?;
+
+    // 3. Memory. Empty chains forget the items, version counters included.
+    version_store.replace_concept_versions(concept_id, Vec::new())?;
+    for chain in &relationships {
+        if let Some(RelationshipVersion { relationship_id, .. }) = chain.last() {
+            version_store.replace_relationship_versions(*relationship_id, Vec::new())?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// One chain's prunable versions, alongside the whole chain for snapshot checks.
+struct PrunableChain {
+    chain: Vec<VersionStamp>,
+    pruned: Vec<VersionStamp>,
+}
+
+impl PrunableChain {
+    fn numbers(&self) -> Vec<u64> {
+        self.pruned.iter().map(|(version, _)| *version).collect()
+    }
+
+    /// The oldest version kept, when every version before it goes. Only then can the pruned
+    /// ones go in one range delete: nothing kept sits between them.
+    fn first_kept(&self) -> Option<u64> {
+        if !self.chain.starts_with(&self.pruned) {
+            return None;
+        }
+        self.chain.get(self.pruned.len()).map(|(version, _)| *version)
+    }
+}
+
+/// The prunable versions of every chain, leaving out chains with none.
+fn prunable<Id: Eq + Hash>(
+    chains: HashMap<Id, Vec<VersionStamp>>,
+    policy: &RetentionPolicy,
+    superseded_before: DateTime<Utc>,
+) -> HashMap<Id, PrunableChain> {
+    let keep = policy.keep_latest.max(1);
+    chains
+        .into_iter()
+        .filter_map(|(id, chain)| {
+            let candidates = chain.len().saturating_sub(keep);
+            // A version is superseded when its successor is committed.
+            let pruned: Vec<VersionStamp> = (0..candidates)
+                .filter(|&i| chain[i + 1].1 <= superseded_before)
+                .map(|i| chain[i])
+                .collect();
+            (!pruned.is_empty()).then_some((id, PrunableChain { chain, pruned }))
+        })
+        .collect()
+}
+
+/// Whether a snapshot taken at `at` reads a version that is about to be pruned.
+fn reads_any<Id>(chains: &HashMap<Id, PrunableChain>, at: DateTime<Utc>) -> bool {
+    chains.values().any(|chain| {
+        let pruned: HashSet<u64> = chain.pruned.iter().map(|(version, _)| *version).collect();
+        chain
+            .chain
+            .iter()
+            .rev()
+            .find(|(_, created_at)| *created_at <= at)
+            .is_some_and(|(version, _)| pruned.contains(version))
+    })
+}
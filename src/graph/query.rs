@@ -0,0 +1,580 @@
+// A small declarative query language over the live graph, so new query shapes don't each
+// need an endpoint. A query matches one pattern, a source concept optionally joined to a
+// target by one relationship:
+//
+//     {"match": {"source": {"label": "person"},
+//                "relationship": {"type": "works_for"},
+//                "target": {"property": {"name": "Acme"}}},
+//      "return": ["source"], "limit": 100}
+//
+// The walk starts from the most selective end: a concept named by `id`, then the label
+// index, then a scan of every live concept. From there it follows the adjacency index and
+// checks `property` clauses against the data. A `predicate` clause takes a structured
+// predicate (see `query::filter`); the labels it requires of every match count as `label`s
+// when picking where to start. Every concept and edge it looks at spends an
+// item of a ScanBudget, so an unbounded match stops early and says so instead of walking the
+// whole graph.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use super::versioning::{concept_from_version, relationship_from_version};
+use super::versioning::VersionStore;
+use super::budget::ScanMeter;
+use crate::error::{MnemonicError, Result};
+use crate::query::filter::{IndexedPredicate, Predicate};
+use crate::types::concept::{Concept, ConceptId, ConceptVersion};
+use crate::types::query::{self as data_query, Direction, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::types::relationship::{RelationType, Relationship, RelationshipVersion};
+use crate::types::system;
+
+/// Constraints on one concept of the pattern. All of them must hold.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodePattern {
+    pub id: Option<ConceptId>,
+    /// A string in the concept's `labels` array.
+    pub label: Option<String>,
+    /// Field paths, as for `lookup`, and the JSON values they must equal.
+    pub properties: Vec<(String, Value)>,
+    pub predicate: Option<Predicate>,
+}
+
+impl NodePattern {
+    /// A label every match carries, for the label index to find candidates by.
+    fn indexed_label(&self) -> Option<String> {
+        if let Some(label) = &self.label {
+            return Some(label.clone());
+        }
+        let plan = self.predicate.as_ref()?.plan();
+        plan.indexed.into_iter().map(|IndexedPredicate::Label(label)| label).next()
+    }
+}
+
+/// Constraints on the relationship of the pattern.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelationshipPattern {
+    pub relationship_type: Option<RelationType>,
+}
+
+/// A part of the pattern a result can return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Source,
+    Relationship,
+    Target,
+}
+
+/// A parsed query. Without a relationship or target it matches single concepts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphQuery {
+    pub source: NodePattern,
+    pub relationship: Option<RelationshipPattern>,
+    pub target: Option<NodePattern>,
+    /// What each row holds; every bound part of the pattern by default.
+    pub returns: Vec<Binding>,
+    pub limit: usize,
+    /// Match system concepts too (see `types::system`). Not part of the query language; the
+    /// caller sets it.
+    pub include_system: bool,
+}
+
+/// One match, holding the parts the query returns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryRow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Concept>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<Relationship>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Concept>,
+}
+
+/// The matches found, at most `limit` of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub rows: Vec<QueryRow>,
+    /// How many concepts and edges were examined.
+    pub examined: usize,
+    /// True when the budget ran out before the query was answered, so matches may be missing.
+    pub truncated: bool,
+}
+
+impl GraphQuery {
+    /// Parses a query, failing with `MnemonicError::InvalidQuery` at the first offending path.
+    pub fn parse(query: &Value) -> Result<Self> {
+        let fields = object(query, "")?;
+        reject_unknown(fields, "", &["match", "return", "limit"])?;
+        let pattern = object(fields.get("match").ok_or_else(|| invalid("/match", "is required"))?, "/match")?;
+        reject_unknown(pattern, "/match", &["source", "relationship", "target"])?;
+
+        let source = match pattern.get("source") {
+            Some(source) => node_pattern(source, "/match/source")?,
+            None => NodePattern::default(),
+        };
+        let relationship = pattern
+            .get("relationship")
+            .map(|rel| relationship_pattern(rel, "/match/relationship"))
+            .transpose()?;
+        let target = pattern
+            .get("target")
+            .map(|target| node_pattern(target, "/match/target"))
+            .transpose()?;
+        // A target or a relationship implies the other.
+        let (relationship, target) = match (relationship, target) {
+            (None, None) => (None, None),
+            (relationship, target) => (
+                Some(relationship.unwrap_or_default()),
+                Some(target.unwrap_or_default()),
+            ),
+        };
+
+        let returns = match fields.get("return") {
+            None if relationship.is_some() => vec![Binding::Source, Binding::Relationship, Binding::Target],
+            None => vec![Binding::Source],
+            Some(Value::Array(names)) if !names.is_empty() => names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let path = format!("/return/{}", i);
+                    match (name.as_str(), relationship.is_some()) {
+                        (Some("source"), _) => Ok(Binding::Source),
+                        (Some("relationship"), true) => Ok(Binding::Relationship),
+                        (Some("target"), true) => Ok(Binding::Target),
+                        (Some("relationship" | "target"), false) => {
+                            Err(invalid(&path, "is not bound without a relationship or target"))
+                        }
+                        _ => Err(invalid(&path, "must be \"source\", \"relationship\" or \"target\"")),
+                    }
+                })
+                .collect::<Result<_>>()?,
+            Some(_) => return Err(invalid("/return", "must be a non-empty array of names")),
+        };
+
+        let limit = match fields.get("limit") {
+            None => DEFAULT_PAGE_SIZE,
+            Some(limit) => match limit.as_u64() {
+                Some(limit) if limit >= 1 && limit as usize <= MAX_PAGE_SIZE => limit as usize,
+                _ => return Err(invalid("/limit", &format!("must be between 1 and {}", MAX_PAGE_SIZE))),
+            },
+        };
+
+        Ok(Self { source, relationship, target, returns, limit, include_system: false })
+    }
+}
+
+fn invalid(path: &str, message: &str) -> MnemonicError {
+    MnemonicError::InvalidQuery {
+        path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+        message: message.to_string(),
+    }
+}
+
+fn object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>> {
+    value.as_object().ok_or_else(|| invalid(path, "must be an object"))
+}
+
+fn reject_unknown(fields: &Map<String, Value>, path: &str, known: &[&str]) -> Result<()> {
+    match fields.keys().find(|key| !known.contains(&key.as_str())) {
+        Some(key) => Err(invalid(&format!("{}/{}", path, key), "is not a known clause")),
+        None => Ok(()),
+    }
+}
+
+fn node_pattern(value: &Value, path: &str) -> Result<NodePattern> {
+    let fields = object(value, path)?;
+    reject_unknown(fields, path, &["id", "label", "property", "predicate"])?;
+    let id = fields
+        .get("id")
+        .map(|id| {
+            id.as_str()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| invalid(&format!("{}/id", path), "must be a UUID"))
+        })
+        .transpose()?;
+    let label = fields
+        .get("label")
+        .map(|label| {
+            label
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| invalid(&format!("{}/label", path), "must be a string"))
+        })
+        .transpose()?;
+    let properties = match fields.get("property") {
+        None => Vec::new(),
+        Some(properties) => object(properties, &format!("{}/property", path))?
+            .iter()
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect(),
+    };
+    let predicate = fields
+        .get("predicate")
+        .map(|predicate| {
+            serde_json::from_value(predicate.clone())
+                .map_err(|e| invalid(&format!("{}/predicate", path), &e.to_string()))
+        })
+        .transpose()?;
+    Ok(NodePattern { id, label, properties, predicate })
+}
+
+fn relationship_pattern(value: &Value, path: &str) -> Result<RelationshipPattern> {
+    let fields = object(value, path)?;
+    reject_unknown(fields, path, &["type"])?;
+    let relationship_type = fields
+        .get("type")
+        .map(|t| {
+            t.as_str()
+                .map(RelationType::new)
+                .ok_or_else(|| invalid(&format!("{}/type", path), "must be a string"))
+        })
+        .transpose()?;
+    Ok(RelationshipPattern { relationship_type })
+}
+
+/// Where a walk can start without scanning the whole graph.
+fn anchored(pattern: &NodePattern) -> bool {
+    pattern.id.is_some() || pattern.indexed_label().is_some()
+}
+
+/// Runs a query against the live graph, examining no more concepts and edges than `meter`
+/// allows.
+pub(crate) fn execute(version_store: &VersionStore, query: &GraphQuery, meter: &ScanMeter) -> Result<QueryResult> {
+    let mut run = Run { version_store, meter, include_system: query.include_system, seen: HashMap::new() };
+    let mut rows = Vec::new();
+
+    // Start from whichever end the indexes can find; the source unless only the target can.
+    let from_target = match &query.target {
+        Some(target) => !anchored(&query.source) && anchored(target),
+        None => false,
+    };
+    let (start, other) = match (&query.target, from_target) {
+        (Some(target), true) => (target, Some(&query.source)),
+        (target, _) => (&query.source, target.as_ref()),
+    };
+
+    'walk: for concept_id in candidates(version_store, start)? {
+        let Some(start_version) = run.matching(&concept_id, start)? else {
+            if run.exhausted() {
+                break;
+            }
+            continue;
+        };
+        let (Some(relationship), Some(other)) = (&query.relationship, other) else {
+            rows.push(row(query, Some(&start_version), None, None));
+            if rows.len() == query.limit {
+                break;
+            }
+            continue;
+        };
+
+        let types = relationship
+            .relationship_type
+            .as_ref()
+            .map(|t| std::iter::once(t.clone()).collect());
+        let direction = if from_target { Direction::Incoming } else { Direction::Outgoing };
+        let edges: Vec<RelationshipVersion> = version_store.with_neighbor_view(|view| {
+            view.edges(&concept_id, direction, types.as_ref())
+                .into_iter()
+                .filter_map(|(rel_id, _)| view.relationship(&rel_id).cloned())
+                .collect()
+        });
+        for edge in edges {
+            if !run.step() {
+                break 'walk;
+            }
+            let other_id = edge.other_end(&concept_id);
+            let Some(other_version) = run.matching(&other_id, other)? else {
+                if run.exhausted() {
+                    break 'walk;
+                }
+                continue;
+            };
+            let (source, target) = if from_target {
+                (&other_version, &start_version)
+            } else {
+                (&start_version, &other_version)
+            };
+            rows.push(row(query, Some(source), Some(&edge), Some(target)));
+            if rows.len() == query.limit {
+                break 'walk;
+            }
+        }
+    }
+
+    Ok(QueryResult { rows, examined: meter.items(), truncated: run.exhausted() })
+}
+
+/// The concepts a walk starting at `pattern` has to try.
+fn candidates(version_store: &VersionStore, pattern: &NodePattern) -> Result<Vec<ConceptId>> {
+    match (&pattern.id, pattern.indexed_label()) {
+        (Some(id), _) => Ok(vec![*id]),
+        (None, Some(label)) => {
+            let mut ids = version_store.concepts_with_label(&label)?;
+            // Sorted, so the same query over the same graph returns the same rows.
+            ids.sort();
+            Ok(ids)
+        }
+        (None, None) => {
+            let mut ids = version_store.active_concept_ids()?;
+            ids.sort();
+            Ok(ids)
+        }
+    }
+}
+
+/// The running state of one query: its budget, and the concepts it has already checked.
+struct Run<'a> {
+    version_store: &'a VersionStore,
+    meter: &'a ScanMeter,
+    include_system: bool,
+    /// Each concept's live version and parsed data, or `None` if it isn't live.
+    seen: HashMap<ConceptId, Option<(ConceptVersion, Value)>>,
+}
+
+impl Run<'_> {
+    /// Spends one step of the budget, or reports that it's gone.
+    fn step(&mut self) -> bool {
+        self.meter.take()
+    }
+
+    fn exhausted(&self) -> bool {
+        self.meter.is_spent()
+    }
+
+    /// The concept's live version if it matches `pattern`. Checking a concept for the first
+    /// time costs a step; once the budget is gone, nothing matches. Nor does a system concept,
+    /// unless the query includes them.
+    fn matching(&mut self, concept_id: &ConceptId, pattern: &NodePattern) -> Result<Option<ConceptVersion>> {
+        if !self.seen.contains_key(concept_id) {
+            if !self.step() {
+                return Ok(None);
+            }
+            let live = self
+                .version_store
+                .get_latest_active_concept_version(concept_id)?
+                .filter(|version| self.include_system || !system::is_system(&version.tags))
+                .map(|version| {
+                    let data = data_query::parse_data(&version.data);
+                    (version, data)
+                });
+            self.seen.insert(*concept_id, live);
+        }
+        let Some((version, data)) = &self.seen[concept_id] else {
+            return Ok(None);
+        };
+        let matches = pattern.id.is_none_or(|id| id == *concept_id)
+            && pattern
+                .label
+                .as_ref()
+                .is_none_or(|label| data_query::labels_of(data).contains(label))
+            && pattern
+                .properties
+                .iter()
+                .all(|(path, expected)| data_query::lookup(data, path) == Some(expected));
+        let matches = matches
+            && match &pattern.predicate {
+                None => true,
+                Some(predicate) => {
+                    let created_at = self.version_store.concept_created_at(concept_id)?.unwrap_or(version.created_at);
+                    predicate.evaluate_parsed(version, data, created_at)
+                }
+            };
+        Ok(matches.then(|| version.clone()))
+    }
+}
+
+fn row(
+    query: &GraphQuery,
+    source: Option<&ConceptVersion>,
+    relationship: Option<&RelationshipVersion>,
+    target: Option<&ConceptVersion>,
+) -> QueryRow {
+    let mut result = QueryRow::default();
+    for binding in &query.returns {
+        match binding {
+            Binding::Source => result.source = source.map(concept_from_version),
+            Binding::Relationship => result.relationship = relationship.map(relationship_from_version),
+            Binding::Target => result.target = target.map(concept_from_version),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::budget::ScanBudget;
+    use crate::types::concept::ConceptData;
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn concept(store: &VersionStore, data: Value) -> ConceptId {
+        let concept_id = Uuid::new_v4();
+        store
+            .add_concept_version(ConceptVersion {
+                concept_id,
+                version: 1,
+                data: ConceptData::Structured(data.to_string().into()),
+                created_at: Utc::now(),
+                created_by: Uuid::new_v4(),
+                deleted_at: None,
+                deleted_by: None,
+                expires_at: None,
+                tags: Default::default(),
+            })
+            .unwrap();
+        concept_id
+    }
+
+    fn relate(store: &VersionStore, source: ConceptId, relationship_type: &str, target: ConceptId) {
+        let rel = Relationship::new(source, relationship_type.to_string(), target);
+        store
+            .add_relationship_version(RelationshipVersion::from_relationship(&rel, Uuid::new_v4()))
+            .unwrap();
+    }
+
+    fn run(store: &VersionStore, query: Value) -> QueryResult {
+        execute(store, &GraphQuery::parse(&query).unwrap(), &ScanMeter::unlimited()).unwrap()
+    }
+
+    fn ids(concepts: impl Iterator<Item = Option<Concept>>) -> Vec<ConceptId> {
+        let mut ids: Vec<ConceptId> = concepts.flatten().map(|c| c.id).collect();
+        ids.sort();
+        ids
+    }
+
+    /// Ada and Grace work for Acme, Linus for Initech; Ada also knows Grace.
+    fn company(store: &VersionStore) -> [ConceptId; 5] {
+        let ada = concept(store, json!({"labels": ["person"], "name": "Ada"}));
+        let grace = concept(store, json!({"labels": ["person"], "name": "Grace"}));
+        let linus = concept(store, json!({"labels": ["person"], "name": "Linus"}));
+        let acme = concept(store, json!({"labels": ["company"], "name": "Acme", "hq": {"city": "Paris"}}));
+        let initech = concept(store, json!({"labels": ["company"], "name": "Initech"}));
+        relate(store, ada, "works_for", acme);
+        relate(store, grace, "works_for", acme);
+        relate(store, linus, "works_for", initech);
+        relate(store, ada, "knows", grace);
+        [ada, grace, linus, acme, initech]
+    }
+
+    fn sorted(mut ids: Vec<ConceptId>) -> Vec<ConceptId> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_each_clause_narrows_the_match() {
+        let store = VersionStore::new();
+        let [ada, grace, linus, acme, initech] = company(&store);
+
+        let by_label = run(&store, json!({"match": {"source": {"label": "company"}}}));
+        assert_eq!(ids(by_label.rows.into_iter().map(|r| r.source)), sorted(vec![acme, initech]));
+
+        let by_id = run(&store, json!({"match": {"source": {"id": ada.to_string()}}}));
+        assert_eq!(ids(by_id.rows.into_iter().map(|r| r.source)), vec![ada]);
+
+        let by_property = run(&store, json!({"match": {"source": {"property": {"hq.city": "Paris"}}}}));
+        assert_eq!(ids(by_property.rows.into_iter().map(|r| r.source)), vec![acme]);
+
+        let by_type = run(&store, json!({"match": {"relationship": {"type": "knows"}}}));
+        assert_eq!(by_type.rows.len(), 1);
+        let row = &by_type.rows[0];
+        assert_eq!(row.source.as_ref().unwrap().id, ada);
+        assert_eq!(row.relationship.as_ref().unwrap().relationship_type, "knows");
+        assert_eq!(row.target.as_ref().unwrap().id, grace);
+
+        // Only a target is named, so the walk starts there and follows edges backwards.
+        let into_initech = run(
+            &store,
+            json!({"match": {"target": {"id": initech.to_string()}}, "return": ["source"]}),
+        );
+        assert_eq!(into_initech.rows.len(), 1);
+        assert!(into_initech.rows[0].target.is_none());
+        assert_eq!(into_initech.rows[0].source.as_ref().unwrap().id, linus);
+    }
+
+    #[test]
+    fn test_combined_query_returns_the_requested_bindings() {
+        let store = VersionStore::new();
+        let [ada, grace, _, acme, _] = company(&store);
+
+        let result = run(
+            &store,
+            json!({
+                "match": {
+                    "source": {"label": "person"},
+                    "relationship": {"type": "works_for"},
+                    "target": {"property": {"name": "Acme"}}
+                },
+                "return": ["source", "target"],
+                "limit": 100
+            }),
+        );
+        assert!(!result.truncated);
+        assert!(result.rows.iter().all(|r| r.relationship.is_none()));
+        assert!(result.rows.iter().all(|r| r.target.as_ref().unwrap().id == acme));
+        assert_eq!(ids(result.rows.into_iter().map(|r| r.source)), sorted(vec![ada, grace]));
+
+        let limited = run(&store, json!({"match": {"source": {"label": "person"}}, "limit": 2}));
+        assert_eq!(limited.rows.len(), 2);
+    }
+
+    #[test]
+    fn test_a_predicate_clause_narrows_the_match_and_its_labels_anchor_the_walk() {
+        let store = VersionStore::new();
+        let [ada, _, linus, _, _] = company(&store);
+        for n in 0..20 {
+            concept(&store, json!({ "n": n }));
+        }
+
+        let predicate = json!({"and": [{"label": "person"}, {"not": {"eq": {"path": "name", "value": "Grace"}}}]});
+        let query = GraphQuery::parse(&json!({"match": {"source": {"predicate": predicate}}})).unwrap();
+        assert!(anchored(&query.source));
+        let result = execute(&store, &query, &ScanMeter::unlimited()).unwrap();
+        assert_eq!(ids(result.rows.into_iter().map(|r| r.source)), sorted(vec![ada, linus]));
+        // Only the three people were looked at, not every concept.
+        assert_eq!(result.examined, 3);
+
+        let either = json!({"or": [{"label": "person"}, {"label": "company"}]});
+        let query = GraphQuery::parse(&json!({"match": {"source": {"predicate": either}}})).unwrap();
+        assert!(!anchored(&query.source));
+        assert_eq!(execute(&store, &query, &ScanMeter::unlimited()).unwrap().rows.len(), 5);
+    }
+
+    #[test]
+    fn test_unbounded_match_stops_at_the_budget() {
+        let store = VersionStore::new();
+        for n in 0..50 {
+            concept(&store, json!({ "n": n }));
+        }
+        // Nothing matches, so without a budget this would look at every concept.
+        let query = GraphQuery::parse(&json!({"match": {"source": {"property": {"n": -1}}}})).unwrap();
+        let capped = execute(&store, &query, &ScanBudget::items(10).start()).unwrap();
+        assert!(capped.truncated);
+        assert_eq!(capped.examined, 10);
+        assert!(capped.rows.is_empty());
+
+        let full = execute(&store, &query, &ScanBudget::items(1000).start()).unwrap();
+        assert!(!full.truncated);
+        assert_eq!(full.examined, 50);
+    }
+
+    #[test]
+    fn test_invalid_queries_name_the_offending_path() {
+        let path_of = |query: Value| match GraphQuery::parse(&query) {
+            Err(MnemonicError::InvalidQuery { path, .. }) => path,
+            other => panic!("expected an invalid query, got {:?}", other),
+        };
+        assert_eq!(path_of(json!([])), "/");
+        assert_eq!(path_of(json!({})), "/match");
+        assert_eq!(path_of(json!({"match": {"source": {"lable": "x"}}})), "/match/source/lable");
+        assert_eq!(path_of(json!({"match": {"target": {"id": "nope"}}})), "/match/target/id");
+        assert_eq!(path_of(json!({"match": {"relationship": {"type": 3}}})), "/match/relationship/type");
+        assert_eq!(path_of(json!({"match": {"source": {"property": "x"}}})), "/match/source/property");
+        assert_eq!(path_of(json!({"match": {"target": {"predicate": {"gte": {}}}}})), "/match/target/predicate");
+        assert_eq!(path_of(json!({"match": {}, "return": ["source", "target"]})), "/return/1");
+        assert_eq!(path_of(json!({"match": {}, "limit": 0})), "/limit");
+    }
+}
@@ -0,0 +1,195 @@
+// Regenerating derived state (indexes on disk and in memory) from the version history.
+
+use chrono::Utc;
+use rocksdb::WriteBatch;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::consistency::ConsistencyReport;
+use super::versioning::concept_from_version;
+use super::indices::IndexRebuild;
+use super::versioning::VersionStore;
+use crate::error::Result;
+use crate::storage::{keys, RocksBackend};
+use crate::types::concept::{Concept, ConceptId};
+use crate::types::content::{self, ContentHash};
+use crate::types::relationship::{RelationType, RelationshipId};
+
+/// The outcome of `GraphEngine::rebuild_derived_state`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebuildReport {
+    /// The repair of in-memory history from the 'versions' cabinet that ran first.
+    pub consistency: ConsistencyReport,
+    pub adjacency: IndexRebuild,
+    pub labels: IndexRebuild,
+    pub tags: IndexRebuild,
+    pub content_hashes: IndexRebuild,
+    /// The source, target, type and composite source and type entries of the 'indices' cabinet.
+    pub relationship_index: IndexRebuild,
+    /// The head records of the 'concepts' cabinet.
+    pub concept_records: IndexRebuild,
+}
+
+impl RebuildReport {
+    /// How many entries were wrong across every index.
+    pub fn repaired(&self) -> usize {
+        self.consistency.repaired
+            + self.adjacency.repaired
+            + self.labels.repaired
+            + self.tags.repaired
+            + self.content_hashes.repaired
+            + self.relationship_index.repaired
+            + self.concept_records.repaired
+    }
+}
+
+/// Drops content hash entries that no longer point at an active concept with that content.
+/// Only deduplicating stores register a hash, so there is nothing to add back; what survives
+/// replaces the in-memory index. Callers must hold the commit lock.
+pub(crate) fn rebuild_content_hashes(
+    version_store: &VersionStore,
+    backend: &RocksBackend,
+) -> Result<(IndexRebuild, HashMap<ContentHash, ConceptId>)> {
+    let now = Utc::now();
+    let on_disk: HashSet<(ContentHash, ConceptId)> = backend.load_all_content_hashes()?.into_iter().collect();
+    let mut kept = HashSet::new();
+    for &(hash, concept_id) in &on_disk {
+        let current = version_store
+            .get_latest_concept_version(&concept_id)?
+            .is_some_and(|latest| latest.is_active_at(now) && content::content_hash(&latest.data) == hash);
+        if current {
+            kept.insert((hash, concept_id));
+        }
+    }
+
+    let mut batch = WriteBatch::default();
+    for (hash, _) in on_disk.difference(&kept) {
+        backend.delete_content_hash(*hash, &mut batch)?;
+    }
+    backend.db.write(batch)?;
+    Ok((IndexRebuild::compare(&on_disk, &kept), kept.into_iter().collect()))
+}
+
+/// Makes the source, target, type and composite index entries match the relationship records
+/// exactly. Databases written before the type indexes existed get theirs filled in here.
+/// Callers must hold the commit lock.
+pub(crate) fn rebuild_relationship_index(backend: &RocksBackend) -> Result<IndexRebuild> {
+    let relationships = backend.load_all_relationships()?;
+    let mut report = IndexRebuild::default();
+    let mut batch = WriteBatch::default();
+    for tag in [keys::INDEX_SOURCE, keys::INDEX_TARGET] {
+        let expected: HashSet<(ConceptId, RelationshipId)> = relationships
+            .iter()
+            .map(|r| (if tag == keys::INDEX_SOURCE { r.source } else { r.target }, r.id))
+            .collect();
+        let found: HashSet<(ConceptId, RelationshipId)> = backend.load_relationship_index(tag)?.into_iter().collect();
+        for (concept_id, relationship_id) in expected.difference(&found) {
+            backend.store_relationship_index_entry(tag, concept_id, relationship_id, &mut batch)?;
+        }
+        for (concept_id, relationship_id) in found.difference(&expected) {
+            backend.delete_relationship_index_entry(tag, concept_id, relationship_id, &mut batch);
+        }
+        let counts = IndexRebuild::compare(&found, &expected);
+        report.entries += counts.entries;
+        report.repaired += counts.repaired;
+    }
+
+    let expected: HashSet<(RelationType, RelationshipId)> =
+        relationships.iter().map(|r| (r.relationship_type.clone(), r.id)).collect();
+    let found: HashSet<(RelationType, RelationshipId)> = backend.load_type_index()?.into_iter().collect();
+    for (relationship_type, relationship_id) in expected.difference(&found) {
+        backend.store_type_index_entry(relationship_type, relationship_id, &mut batch)?;
+    }
+    for (relationship_type, relationship_id) in found.difference(&expected) {
+        backend.delete_type_index_entry(relationship_type, relationship_id, &mut batch);
+    }
+    let counts = IndexRebuild::compare(&found, &expected);
+    report.entries += counts.entries;
+    report.repaired += counts.repaired;
+
+    let expected: HashSet<(ConceptId, RelationType, RelationshipId)> =
+        relationships.iter().map(|r| (r.source, r.relationship_type.clone(), r.id)).collect();
+    let found: HashSet<(ConceptId, RelationType, RelationshipId)> =
+        backend.load_source_type_index()?.into_iter().collect();
+    for (source_id, relationship_type, relationship_id) in expected.difference(&found) {
+        backend.store_source_type_index_entry(source_id, relationship_type, relationship_id, &mut batch)?;
+    }
+    for (source_id, relationship_type, relationship_id) in found.difference(&expected) {
+        backend.delete_source_type_index_entry(source_id, relationship_type, relationship_id, &mut batch);
+    }
+    let counts = IndexRebuild::compare(&found, &expected);
+    report.entries += counts.entries;
+    report.repaired += counts.repaired;
+    backend.db.write(batch)?;
+    Ok(report)
+}
+
+/// Makes the 'concepts' cabinet hold exactly the latest version of every active concept,
+/// counting expired ones the sweeper hasn't tombstoned yet. Callers must hold the commit lock.
+pub(crate) fn rebuild_concept_records(version_store: &VersionStore, backend: &RocksBackend) -> Result<IndexRebuild> {
+    let mut latest = version_store.get_all_active_concepts()?;
+    for concept_id in version_store.expired_concepts(Utc::now()) {
+        latest.extend(version_store.get_latest_concept_version(&concept_id)?);
+    }
+    let expected: HashMap<ConceptId, Concept> = latest
+        .iter()
+        .map(|version| (version.concept_id, concept_from_version(version)))
+        .collect();
+    let found: HashMap<ConceptId, Concept> =
+        backend.load_all_concepts()?.into_iter().map(|concept| (concept.id, concept)).collect();
+
+    let mut report = IndexRebuild { entries: expected.len(), repaired: 0 };
+    let mut batch = WriteBatch::default();
+    for (concept_id, concept) in &expected {
+        if found.get(concept_id) != Some(concept) {
+            backend.store_concept_record(concept, &mut batch)?;
+            report.repaired += 1;
+        }
+    }
+    for concept_id in found.keys().filter(|id| !expected.contains_key(id)) {
+        backend.delete_concept_record(concept_id, &mut batch);
+        report.repaired += 1;
+    }
+    backend.db.write(batch)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::relationship::Relationship;
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_relationship_index_is_made_to_match_the_records() {
+        let dir = tempdir().unwrap();
+        let backend = RocksBackend::new(dir.path()).unwrap();
+        let relationship = Relationship::new(Uuid::new_v4(), "knows".to_string(), Uuid::new_v4());
+        backend.store_relationship(&relationship).unwrap();
+
+        let mut batch = WriteBatch::default();
+        backend.delete_relationship_index_entry(keys::INDEX_SOURCE, &relationship.source, &relationship.id, &mut batch);
+        let stray = Uuid::new_v4();
+        backend
+            .store_relationship_index_entry(keys::INDEX_TARGET, &stray, &relationship.id, &mut batch)
+            .unwrap();
+        // As if the relationship had been stored before there was a type index.
+        backend.delete_type_index_entry(&relationship.relationship_type, &relationship.id, &mut batch);
+        backend.db.write(batch).unwrap();
+        assert!(backend.get_relationships_by_source(&relationship.source).unwrap().is_empty());
+        assert!(backend.get_relationships_by_type(&relationship.relationship_type).unwrap().is_empty());
+
+        let report = rebuild_relationship_index(&backend).unwrap();
+        assert_eq!(report, IndexRebuild { entries: 4, repaired: 3 });
+        assert_eq!(backend.get_relationships_by_source(&relationship.source).unwrap(), vec![relationship.clone()]);
+        assert_eq!(backend.get_relationships_by_type(&relationship.relationship_type).unwrap(), vec![relationship.clone()]);
+        assert_eq!(
+            backend.load_relationship_index(keys::INDEX_TARGET).unwrap(),
+            vec![(relationship.target, relationship.id)]
+        );
+
+        // A second pass finds nothing left to fix.
+        assert_eq!(rebuild_relationship_index(&backend).unwrap().repaired, 0);
+    }
+}
@@ -0,0 +1,66 @@
+// Replication: a follower engine applying a leader's commit log as it grows.
+//
+// A commit is identified by its timestamp, which the leader hands out strictly increasing,
+// so the last one applied is all a follower needs to know where it stands. It asks its
+// `ChangeSource` for the commits after that, applies each with the leader's IDs, version
+// numbers and timestamps, and records how far it got in the same write. Replaying a commit
+// is a no-op, so a follower can stop at any point and pick up where it left off.
+
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::engine::GraphEngine;
+use super::events::CommitEvent;
+use crate::error::Result;
+use crate::types::query::MAX_PAGE_SIZE;
+
+/// Where a follower gets a leader's commits from.
+pub trait ChangeSource: Send {
+    /// The leader's next commits after `after` (from its first, if `None`), oldest first.
+    /// Waits until there is at least one; `None` once the source has ended.
+    fn next_commits(
+        &mut self,
+        after: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = Result<Option<Vec<CommitEvent>>>> + Send;
+}
+
+/// The commits of a leader in the same process: its history first, then each commit as it
+/// is published, going back to the history whenever the feed falls too far behind.
+#[derive(Debug)]
+pub struct CommitFeed {
+    leader: Arc<GraphEngine>,
+    commits: broadcast::Receiver<Arc<CommitEvent>>,
+    // Once the history has nothing more, the next commits come from the channel.
+    caught_up: bool,
+}
+
+impl CommitFeed {
+    pub fn new(leader: Arc<GraphEngine>) -> Self {
+        // Listening before reading the history, so no commit falls in between.
+        let commits = leader.subscribe_commits();
+        Self { leader, commits, caught_up: false }
+    }
+}
+
+impl ChangeSource for CommitFeed {
+    async fn next_commits(&mut self, after: Option<DateTime<Utc>>) -> Result<Option<Vec<CommitEvent>>> {
+        loop {
+            if !self.caught_up {
+                let commits = self.leader.changes(after, MAX_PAGE_SIZE).await?;
+                if !commits.is_empty() {
+                    return Ok(Some(commits));
+                }
+                self.caught_up = true;
+            }
+            match self.commits.recv().await {
+                // The history already had it.
+                Ok(commit) if after.is_some_and(|after| commit.committed_at <= after) => {}
+                Ok(commit) => return Ok(Some(vec![commit.as_ref().clone()])),
+                Err(RecvError::Lagged(_)) => self.caught_up = false,
+                Err(RecvError::Closed) => return Ok(None),
+            }
+        }
+    }
+}
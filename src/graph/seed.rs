@@ -0,0 +1,156 @@
+// Declarative seed data for fresh databases.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::error::{BatchItemError, MnemonicError, Result};
+use crate::types::concept::ConceptId;
+use crate::types::relationship::RelationType;
+
+/// A concept to seed. Its ID is fixed by the fixture, so the same fixture always yields the
+/// same concepts and other data can refer to them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeedConcept {
+    pub id: ConceptId,
+    pub data: Value,
+}
+
+/// A relationship to seed, between two of the fixture's concepts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeedRelationship {
+    pub source: ConceptId,
+    pub relationship_type: RelationType,
+    pub target: ConceptId,
+}
+
+/// Everything `GraphEngine::seed_if_empty` writes into an empty graph.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeedFixture {
+    pub concepts: Vec<SeedConcept>,
+    pub relationships: Vec<SeedRelationship>,
+}
+
+/// What `GraphEngine::seed_if_empty` did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeedReport {
+    pub concepts_created: usize,
+    pub relationships_created: usize,
+    /// True when the graph already held data, so nothing was written.
+    pub skipped: bool,
+}
+
+impl SeedFixture {
+    /// The small project graph the explorer UI shows out of the box.
+    pub fn demo() -> Self {
+        let id = |n: u128| Uuid::from_u128(0x6d6e_656d_6f6e_6963_0000_0000_0000_0000 | n);
+        let (mnemonic, developer, ui, backend, react, rust) =
+            (id(1), id(2), id(3), id(4), id(5), id(6));
+        let concept = |id, data| SeedConcept { id, data };
+        let relationship = |source, relationship_type: &str, target| SeedRelationship {
+            source,
+            relationship_type: relationship_type.into(),
+            target,
+        };
+
+        Self {
+            concepts: vec![
+                concept(mnemonic, json!({"name": "Mnemonic Computing", "type": "Project"})),
+                concept(developer, json!({"name": "4xmafole", "type": "Person"})),
+                concept(ui, json!({"name": "Explorer UI", "type": "Component"})),
+                concept(backend, json!({"name": "Core Engine", "type": "Component"})),
+                concept(react, json!({"name": "React", "type": "Technology"})),
+                concept(rust, json!({"name": "Rust", "type": "Technology"})),
+            ],
+            relationships: vec![
+                relationship(developer, "leads", mnemonic),
+                relationship(mnemonic, "is_composed_of", ui),
+                relationship(mnemonic, "is_composed_of", backend),
+                relationship(ui, "is_written_in", react),
+                relationship(backend, "is_written_in", rust),
+            ],
+        }
+    }
+
+    /// Loads a fixture from a JSON file shaped like this struct.
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file)).map_err(std::io::Error::from)?)
+    }
+
+    /// Checks that concept IDs are unique and every relationship joins two of the fixture's
+    /// concepts. Items are numbered concepts first, then relationships.
+    pub fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        let mut errors = Vec::new();
+        for (index, concept) in self.concepts.iter().enumerate() {
+            if !ids.insert(concept.id) {
+                errors.push(BatchItemError {
+                    index,
+                    error: format!("Concept {} appears more than once", concept.id),
+                });
+            }
+        }
+        for (offset, relationship) in self.relationships.iter().enumerate() {
+            for endpoint in [relationship.source, relationship.target] {
+                if !ids.contains(&endpoint) {
+                    errors.push(BatchItemError {
+                        index: self.concepts.len() + offset,
+                        error: format!("Concept {} is not part of the fixture", endpoint),
+                    });
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(MnemonicError::InvalidBatch(errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_demo_fixture_is_valid_and_stable() {
+        let demo = SeedFixture::demo();
+        demo.validate().unwrap();
+        assert_eq!(demo, SeedFixture::demo());
+        assert_eq!((demo.concepts.len(), demo.relationships.len()), (6, 5));
+    }
+
+    #[test]
+    fn test_fixture_loads_from_json_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("fixture.json");
+        std::fs::write(&path, serde_json::to_vec(&SeedFixture::demo()).unwrap()).unwrap();
+        assert_eq!(SeedFixture::from_json_file(&path).unwrap(), SeedFixture::demo());
+
+        std::fs::write(&path, "{\"concepts\": 3}").unwrap();
+        assert!(matches!(SeedFixture::from_json_file(&path), Err(MnemonicError::Io(_))));
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_endpoints_and_duplicate_ids() {
+        let mut fixture = SeedFixture::demo();
+        fixture.concepts.push(fixture.concepts[0].clone());
+        fixture.relationships.push(SeedRelationship {
+            source: fixture.concepts[0].id,
+            relationship_type: "knows".into(),
+            target: Uuid::new_v4(),
+        });
+        match fixture.validate() {
+            Err(MnemonicError::InvalidBatch(items)) => {
+                let indexes: Vec<usize> = items.iter().map(|item| item.index).collect();
+                assert_eq!(indexes, vec![6, 12]);
+            }
+            other => panic!("expected an invalid fixture, got {:?}", other),
+        }
+    }
+}
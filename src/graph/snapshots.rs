@@ -0,0 +1,105 @@
+// Read snapshots: points in time that reads can be pinned to across many requests, e.g. to
+// page through /concepts without rows appearing or vanishing between pages. Until one is
+// released or left idle too long, pruning and purging treat it like a snapshot transaction
+// that started at its timestamp.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::error::{MnemonicError, Result};
+use crate::utils::locks::LockHealth;
+
+/// Names an open snapshot.
+pub type SnapshotToken = Uuid;
+
+/// A point in time reads can be evaluated at, from `GraphEngine::open_snapshot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadSnapshot {
+    pub token: SnapshotToken,
+    /// Reads through the snapshot see every commit up to here and none after.
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Held {
+    timestamp: DateTime<Utc>,
+    last_used: Instant,
+}
+
+/// Every open snapshot. One left unused for `idle_timeout` is dropped the next time the
+/// registry is looked at, so it stops holding back pruning even if nobody releases it.
+#[derive(Debug)]
+pub(crate) struct SnapshotRegistry {
+    idle_timeout: Duration,
+    held: Mutex<HashMap<SnapshotToken, Held>>,
+    lock_health: Arc<LockHealth>,
+}
+
+impl SnapshotRegistry {
+    pub(crate) fn new(idle_timeout: Duration, lock_health: Arc<LockHealth>) -> Self {
+        Self {
+            idle_timeout,
+            held: Mutex::new(HashMap::new()),
+            lock_health,
+        }
+    }
+
+    /// Registers a snapshot at `timestamp`.
+    pub(crate) fn open(&self, timestamp: DateTime<Utc>) -> ReadSnapshot {
+        let token = Uuid::new_v4();
+        self.held().insert(token, Held { timestamp, last_used: Instant::now() });
+        ReadSnapshot { token, timestamp }
+    }
+
+    /// The timestamp of an open snapshot. Reading it counts as using the snapshot.
+    pub(crate) fn timestamp(&self, token: SnapshotToken) -> Result<DateTime<Utc>> {
+        let mut held = self.held();
+        let snapshot = held.get_mut(&token).ok_or(MnemonicError::SnapshotNotFound(token))?;
+        snapshot.last_used = Instant::now();
+        Ok(snapshot.timestamp)
+    }
+
+    /// Forgets a snapshot. `false` if it wasn't open.
+    pub(crate) fn release(&self, token: SnapshotToken) -> bool {
+        self.held().remove(&token).is_some()
+    }
+
+    /// Every open snapshot.
+    pub(crate) fn open_snapshots(&self) -> Vec<ReadSnapshot> {
+        self.held()
+            .iter()
+            .map(|(token, held)| ReadSnapshot { token: *token, timestamp: held.timestamp })
+            .collect()
+    }
+
+    /// The open snapshots, after dropping the idle ones.
+    fn held(&self) -> MutexGuard<'_, HashMap<SnapshotToken, Held>> {
+        let mut held = self.lock_health.lock(&self.held);
+        held.retain(|_, snapshot| snapshot.last_used.elapsed() < self.idle_timeout);
+        held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshots_expire_once_left_idle() {
+        let registry = SnapshotRegistry::new(Duration::from_millis(100), Arc::new(LockHealth::default()));
+        let (kept, idle) = (registry.open(Utc::now()), registry.open(Utc::now()));
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(40));
+            assert_eq!(registry.timestamp(kept.token).unwrap(), kept.timestamp);
+        }
+
+        assert_eq!(registry.open_snapshots(), vec![kept]);
+        assert!(matches!(registry.timestamp(idle.token), Err(MnemonicError::SnapshotNotFound(token)) if token == idle.token));
+        assert!(registry.release(kept.token));
+        assert!(!registry.release(kept.token));
+    }
+}
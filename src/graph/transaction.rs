@@ -1,21 +1,70 @@
-use super::versioning::VersionStore;
+use super::analysis;
+use super::archive::Archive;
+use super::budget::ScanBudget;
+use super::bulk::{BulkDeleteFailure, BulkDeleteOptions, BulkDeleteReport};
+use super::check::{self, CheckDepth, CheckReport};
+use super::consistency::{self, ConsistencyReport};
+use super::delta::{self, GraphDelta};
+use super::versioning::{concept_from_version, tick};
+use super::events::{CommitEvent, COMMIT_CHANNEL_CAPACITY};
+use super::hydration::{self, HydrationMode};
+use super::pruning::{self, PruneReport, PurgeOptions, PurgeReport, RetentionPolicy};
+use super::rebuild::{self, RebuildReport};
+use super::snapshots::{ReadSnapshot, SnapshotRegistry, SnapshotToken};
+use super::versioning::{ColdHistory, VersionStore};
 use crate::storage::RocksBackend;
-use crate::types::concept::{Concept, ConceptId, ConceptVersion};
-use crate::types::relationship::{Relationship, RelationshipId, RelationshipVersion};
-use crate::{MnemonicError, Result};
+use crate::types::concept::{Concept, ConceptData, ConceptId, ConceptVersion};
+use crate::types::content::{self, ContentHash};
+use crate::types::query::{labels_of, parse_data, Filter};
+use crate::types::relationship::{
+    Cardinality, RelationType, Relationship, RelationshipId, RelationshipTypeDefinition, RelationshipVersion,
+};
+use crate::types::schema::Schema;
+use crate::types::system;
+use crate::types::transaction::{RejectedWrite, TransactionMetadata, WriteKind, WriteMode};
+use crate::utils::locks::LockHealth;
+use crate::utils::metrics::TransactionMetrics;
+use crate::{ConflictInfo, ConflictItem, MnemonicError, Result};
 use chrono::{DateTime, Utc};
 use rocksdb::WriteBatch;
-use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
 /// A unique ID for a transaction.
 pub type TransactionId = Uuid;
 
+/// The actor recorded on the commits that tombstone expired concepts, and its thread's name.
+pub const EXPIRY_SWEEPER: &str = "mnemonic-expiry-sweeper";
+
+/// The system concept holding the registered schemas, by label.
+const SCHEMAS: &str = "schemas";
+/// The system concept holding the registered relationship types, by name.
+const RELATIONSHIP_TYPES: &str = "relationship-types";
+/// The system concept holding the cardinality constraints, by relationship type.
+const CARDINALITIES: &str = "cardinalities";
+
+/// A registry's entries, as its system concept holds them.
+type Registry = serde_json::Map<String, serde_json::Value>;
+
 /// Defines how much a transaciton is isolated from other concurrent transactions.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum IsolationLevel {
-    Snapshot, // For now, we will only implement the strongest level.
+    /// Reads see the graph as of the transaction's start; only the write set is validated.
+    /// Cheap, but allows write skew between transactions that write disjoint items.
+    Snapshot,
+    /// Like Snapshot, but commits also fail if anything in the read set changed since start.
+    Serializable,
+    /// Each read sees the latest committed version at the moment of that read, so two reads of
+    /// the same item may disagree. Writes are still validated against the write set, but reads
+    /// give no consistency guarantee across the transaction. Meant for cheap dashboard-style reads.
+    ReadCommitted,
 }
 
 /// A Transaction is a "workspace" for a set of atomic changes to the graph.
@@ -45,10 +94,40 @@ pub struct Transaction {
     /// A list of RelationshipIDs this transaction has written to. Used for conflict detection.
     pub relationship_write_set: HashSet<RelationshipId>,
 
+    /// The relationships this transaction creates or rewrites. The commit gives each one the
+    /// next number from its version counter.
     pub pending_relationship_writes: HashMap<RelationshipId, Relationship>,
 
     /// A list of relationships marked for deletion in this transaction.
     pub pending_deletes: HashSet<RelationshipId>,
+
+    /// A list of concepts marked for deletion in this transaction.
+    pub pending_concept_deletes: HashSet<ConceptId>,
+
+    /// Who is making these changes and why. Persisted with the commit when non-empty.
+    pub metadata: TransactionMetadata,
+
+    /// Content hashes claimed for concepts this transaction creates, so later deduplicating
+    /// stores find them. A claim on data another commit already holds is a conflict.
+    pub content_hashes: HashMap<ContentHash, ConceptId>,
+
+    /// Whether the commit checks that each pending write inserts or updates as it means to.
+    pub write_mode: WriteMode,
+
+    /// The set sizes its summary reports, shared with the copy in the active list.
+    progress: Arc<TransactionProgress>,
+}
+
+/// How big a transaction's sets are, as of its last recorded read or staged write. The
+/// caller's copy of a transaction and the active list's share one, so recording is a few
+/// atomic stores rather than a trip through the active list's lock.
+#[derive(Debug, Default)]
+struct TransactionProgress {
+    read_set: AtomicUsize,
+    write_set: AtomicUsize,
+    relationship_read_set: AtomicUsize,
+    relationship_write_set: AtomicUsize,
+    pending_deletes: AtomicUsize,
 }
 
 impl Transaction {
@@ -65,10 +144,126 @@ impl Transaction {
             pending_writes: HashMap::new(),
             pending_relationship_writes: HashMap::new(),
             pending_deletes: HashSet::new(),
+            pending_concept_deletes: HashSet::new(),
+            metadata: TransactionMetadata::default(),
+            content_hashes: HashMap::new(),
+            write_mode: WriteMode::default(),
+            progress: Arc::default(),
+        }
+    }
+
+    /// Publishes this transaction's set sizes to its summary.
+    fn record_progress(&self) {
+        let progress = &self.progress;
+        progress.read_set.store(self.read_set.len(), Ordering::Relaxed);
+        progress.write_set.store(self.write_set.len(), Ordering::Relaxed);
+        progress.relationship_read_set.store(self.relationship_read_set.len(), Ordering::Relaxed);
+        progress.relationship_write_set.store(self.relationship_write_set.len(), Ordering::Relaxed);
+        progress
+            .pending_deletes
+            .store(self.pending_deletes.len() + self.pending_concept_deletes.len(), Ordering::Relaxed);
+    }
+
+    /// How long ago this transaction started.
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.start_timestamp)
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+/// What a commit wrote.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitReceipt {
+    pub transaction_id: TransactionId,
+    pub committed_at: DateTime<Utc>,
+    /// The commit's place among every commit to the database, counting from 1.
+    pub sequence: u64,
+    /// The version number each written or tombstoned concept was given.
+    pub concept_versions: Vec<(ConceptId, u64)>,
+    /// The version number each written or tombstoned relationship was given.
+    pub relationship_versions: Vec<(RelationshipId, u64)>,
+    /// How many of those versions are tombstones.
+    pub deletes: usize,
+    /// Whether each pending concept write inserted a concept or updated one, as of the
+    /// transaction's snapshot. Deletes aren't listed.
+    pub concept_writes: HashMap<ConceptId, WriteKind>,
+}
+
+/// A lightweight, payload-free view of an active transaction, for debugging stuck clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionSummary {
+    pub id: TransactionId,
+    pub start_timestamp: DateTime<Utc>,
+    pub age_ms: u64,
+    pub isolation_level: IsolationLevel,
+    pub read_set_size: usize,
+    pub write_set_size: usize,
+    pub relationship_read_set_size: usize,
+    pub relationship_write_set_size: usize,
+    pub pending_delete_count: usize,
+}
+
+impl From<&Transaction> for TransactionSummary {
+    fn from(txn: &Transaction) -> Self {
+        let progress = &txn.progress;
+        Self {
+            id: txn.id,
+            start_timestamp: txn.start_timestamp,
+            age_ms: txn.age().as_millis() as u64,
+            isolation_level: txn.isolation_level,
+            read_set_size: progress.read_set.load(Ordering::Relaxed),
+            write_set_size: progress.write_set.load(Ordering::Relaxed),
+            relationship_read_set_size: progress.relationship_read_set.load(Ordering::Relaxed),
+            relationship_write_set_size: progress.relationship_write_set.load(Ordering::Relaxed),
+            pending_delete_count: progress.pending_deletes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Tunables for how the TransactionManager treats long-running transactions.
+#[derive(Debug, Clone)]
+pub struct TransactionConfig {
+    /// Transactions older than this are aborted by the reaper. `None` disables timeouts.
+    pub max_transaction_age: Option<Duration>,
+    /// How often the background reaper scans for expired transactions.
+    pub reap_interval: Duration,
+    /// Relationship types that must never form a cycle. A commit adding an edge of one of
+    /// these types that would close a cycle is rejected with the cycle.
+    pub acyclic_types: HashSet<RelationType>,
+    /// Refuse relationships whose type hasn't been registered with
+    /// `register_relationship_type`. Off by default, so any type is accepted.
+    pub strict_relationship_types: bool,
+    /// How often expired concepts are tombstoned. `None` leaves them in place, hidden from
+    /// reads but never deleted.
+    pub expiry_sweep_interval: Option<Duration>,
+    /// How long a read snapshot may go unused before it is released on its own.
+    pub snapshot_idle_timeout: Duration,
+    /// Whether the version history is loaded before the manager is returned or warmed up
+    /// in the background while reads fall back to disk.
+    pub hydration: HydrationMode,
+    /// How many threads load the version history from disk at startup. One scans it
+    /// serially; the default is one per CPU.
+    pub hydration_threads: usize,
+}
+
+impl Default for TransactionConfig {
+    fn default() -> Self {
+        Self {
+            max_transaction_age: Some(Duration::from_secs(300)),
+            reap_interval: Duration::from_secs(1),
+            acyclic_types: HashSet::new(),
+            strict_relationship_types: false,
+            expiry_sweep_interval: Some(Duration::from_secs(1)),
+            snapshot_idle_timeout: Duration::from_secs(300),
+            hydration: HydrationMode::Eager,
+            hydration_threads: num_cpus::get(),
         }
     }
 }
 
+type ActiveTransactions = RwLock<HashMap<TransactionId, Transaction>>;
+
 /// TransactionManager orchestrates all transactions and handles MVCC.
 #[derive(Debug)]
 pub struct TransactionManager {
@@ -77,64 +272,299 @@ pub struct TransactionManager {
     // Stores data to rocksdb
     backend: Arc<RocksBackend>,
     // A thread-safe map of all currently active, uncommitted transactions.
-    active_transactions: RwLock<HashMap<TransactionId, Transaction>>,
+    // Shared with the reaper thread, which only holds a weak reference to it.
+    active_transactions: Arc<ActiveTransactions>,
+    config: TransactionConfig,
+    metrics: Arc<TransactionMetrics>,
+    // Shared with the VersionStore and the reaper, so recoveries are counted in one place.
+    lock_health: Arc<LockHealth>,
+    // Commits are applied one at a time: validation, the disk write and the in-memory
+    // update all happen while holding this lock. It guards the last timestamp handed to a
+    // commit or a transaction start, so those are strictly ordered whatever the wall clock does.
+    commit_lock: Mutex<DateTime<Utc>>,
+    // How many commits the database has taken. Advanced only under the commit lock, in the
+    // same write as the commit it numbers.
+    commit_sequence: AtomicU64,
+    // The schema each label's concepts must satisfy, as the SCHEMAS system concept holds
+    // them. Changed only under the commit lock.
+    schemas: RwLock<HashMap<String, Schema>>,
+    // Relationship types limited to fewer than `Many` edges. Changed only under the commit lock.
+    cardinalities: RwLock<HashMap<RelationType, Cardinality>>,
+    // The registered relationship types, as the RELATIONSHIP_TYPES system concept holds
+    // them. Changed only under the commit lock.
+    relationship_types: RwLock<HashMap<RelationType, RelationshipTypeDefinition>>,
+    // Held while registering a schema or relationship type, so two registrations don't
+    // conflict over the system concept they both rewrite.
+    registry_lock: Mutex<()>,
+    // Which concept holds each claimed content hash. Changed only under the commit lock.
+    content_index: RwLock<HashMap<ContentHash, ConceptId>>,
+    // When a purge last erased history; deltas can't start before it. Changed only under
+    // the commit lock.
+    history_erased_at: RwLock<Option<DateTime<Utc>>>,
+    // The last commit replicated from a leader, if this is a follower. Changed only under the
+    // commit lock, in the same write as that commit.
+    replicated_through: RwLock<Option<DateTime<Utc>>>,
+    // Set while `GraphEngine::replicate_from` runs, so local commits are refused.
+    replicating: AtomicBool,
+    // The open read snapshots, which pruning and purging respect like snapshot transactions.
+    snapshots: SnapshotRegistry,
+    // Every commit is published here, in commit order, once it is applied.
+    commits: broadcast::Sender<Arc<CommitEvent>>,
+    // Test hook that simulates a crash between the disk write and the in-memory update.
+    #[cfg(test)]
+    skip_memory_apply: AtomicBool,
+    // Test hook that makes commits panic, as a bug deep inside one would.
+    #[cfg(test)]
+    panic_on_commit: AtomicBool,
+    // Test hook that makes the commit this many commits from now conflict, as if another
+    // writer had got there first. Zero leaves commits alone.
+    #[cfg(test)]
+    conflict_in_commits: AtomicUsize,
 }
 
 impl TransactionManager {
-    /// Creates a new, empty TransactionManager.
+    /// Creates a new TransactionManager with the default configuration.
     pub fn new(backend: Arc<RocksBackend>) -> Result<Self> {
-        // Note: It now returns a Result
+        Self::with_config(backend, TransactionConfig::default())
+    }
+
+    /// Creates a new TransactionManager, hydrating it from disk and starting the
+    /// transaction reaper if a maximum transaction age is configured.
+    pub fn with_config(backend: Arc<RocksBackend>, config: TransactionConfig) -> Result<Self> {
+        Self::open(backend, config, true)
+    }
+
+    /// A manager started with `HydrationMode::Background` whose store nothing warms, so
+    /// tests can look at it cold.
+    #[cfg(test)]
+    pub(crate) fn cold_for_test(backend: Arc<RocksBackend>, config: TransactionConfig) -> Result<Self> {
+        Self::open(backend, TransactionConfig { hydration: HydrationMode::Background, ..config }, false)
+    }
+
+    fn open(backend: Arc<RocksBackend>, config: TransactionConfig, spawn_warmer: bool) -> Result<Self> {
         // 1. Create a new, empty VersionStore.
         let version_store = VersionStore::new();
 
-        // 2. Load all historical versions from the disk.
-        let concept_versions = backend.load_all_concept_versions()?;
+        // 2. "Hydrate" it with every historical version on disk, whole chains at a time, or
+        // leave that to a background task and read from disk meanwhile.
+        // New timestamps must come after everything already committed, even if the clock
+        // stepped back while we were down; warming in the background gives that up.
+        let last_timestamp = match config.hydration {
+            HydrationMode::Eager => {
+                let latest = hydration::hydrate(&backend, &version_store, config.hydration_threads)?;
+                hydration::restore_heads(&backend, &version_store)?;
+                // Make sure none of the loaded chains skip a version.
+                for discrepancy in consistency::check_chains(&version_store)? {
+                    tracing::warn!("Inconsistent version history on disk: {:?}", discrepancy);
+                }
+                latest.unwrap_or(DateTime::<Utc>::MIN_UTC)
+            }
+            HydrationMode::Background => {
+                version_store.start_warming(Arc::clone(&backend) as Arc<dyn ColdHistory>);
+                DateTime::<Utc>::MIN_UTC
+            }
+        };
 
-        // 3. "Hydrate" the in-memory VersionStore by re-inserting all the historical data.
-        for version in concept_versions {
-            version_store.add_concept_version(version)?;
+        // Hydrate the "who and why" of past transactions.
+        for (transaction_id, metadata) in backend.load_all_transaction_metadata()? {
+            version_store.add_transaction_metadata(transaction_id, metadata)?;
         }
 
-        // Hydrate relationships
-        let relationship_versions = backend.load_all_relationship_versions()?;
-        for version in relationship_versions {
-            version_store.add_relationship_version(version)?;
-        }
+        let content_index: HashMap<ContentHash, ConceptId> =
+            backend.load_all_content_hashes()?.into_iter().collect();
+        let history_erased_at = backend.load_history_erased_at()?;
+        let replicated_through = backend.load_replicated_through()?;
+        let commit_sequence = backend.load_commit_sequence()?;
 
         // 4. Create the manager with the now-hydrated VersionStore.
-        Ok(Self {
+        let active_transactions = Arc::new(RwLock::new(HashMap::new()));
+        let metrics = Arc::new(TransactionMetrics::default());
+
+        // 5. Start the reaper so abandoned transactions can't pile up forever.
+        let lock_health = version_store.lock_health();
+        if let Some(max_age) = config.max_transaction_age {
+            spawn_reaper(
+                Arc::downgrade(&active_transactions),
+                Arc::clone(&metrics),
+                Arc::clone(&lock_health),
+                max_age,
+                config.reap_interval,
+            )?;
+        }
+
+        let snapshots = SnapshotRegistry::new(config.snapshot_idle_timeout, Arc::clone(&lock_health));
+        let config_hydration = config.hydration;
+        let manager = Self {
             version_store: Arc::new(version_store),
             backend,
-            active_transactions: RwLock::new(HashMap::new()),
-        })
+            active_transactions,
+            config,
+            metrics,
+            lock_health,
+            commit_lock: Mutex::new(last_timestamp),
+            commit_sequence: AtomicU64::new(commit_sequence),
+            schemas: RwLock::default(),
+            cardinalities: RwLock::default(),
+            relationship_types: RwLock::default(),
+            registry_lock: Mutex::new(()),
+            commits: broadcast::channel(COMMIT_CHANNEL_CAPACITY).0,
+            content_index: RwLock::new(content_index),
+            history_erased_at: RwLock::new(history_erased_at),
+            replicated_through: RwLock::new(replicated_through),
+            replicating: AtomicBool::new(false),
+            snapshots,
+            #[cfg(test)]
+            skip_memory_apply: AtomicBool::new(false),
+            #[cfg(test)]
+            panic_on_commit: AtomicBool::new(false),
+            #[cfg(test)]
+            conflict_in_commits: AtomicUsize::new(0),
+        };
+        if spawn_warmer && config_hydration == HydrationMode::Background {
+            hydration::spawn_warmer(&manager.backend, &manager.version_store)?;
+        }
+        // Schemas, relationship types and cardinalities are kept in system concepts; older
+        // databases had keyspaces of their own for them, moved over the first time they're
+        // opened writable.
+        manager.migrate_registries()?;
+        manager.reload_registries()?;
+        // Background compactions can start as soon as the database is open.
+        manager.publish_compaction_horizon()?;
+        Ok(manager)
     }
 
     /// Begins a new transaction and registers it as active.
     pub fn begin_transaction(&self, isolation_level: IsolationLevel) -> Result<Transaction> {
+        self.begin_transaction_with_meta(isolation_level, TransactionMetadata::default())
+    }
+
+    /// Begins a new transaction that records who is making the change and why.
+    pub fn begin_transaction_with_meta(
+        &self,
+        isolation_level: IsolationLevel,
+        metadata: TransactionMetadata,
+    ) -> Result<Transaction> {
         //1. Create a new transaction "shopping cart".
-        let transaction = Transaction::new(isolation_level);
+        let mut transaction = Transaction::new(isolation_level);
+        transaction.metadata = metadata;
 
-        //2. Lock the active transaction list for writing.
-        let mut active_txs = self
-            .active_transactions
-            .write()
-            .map_err(|e| MnemonicError::Transaction(format!("Lock failed: {}", e)))?;
+        //2. Take the start time under the commit lock, so every commit stamped before it is
+        // fully applied to memory and every commit after it is stamped later.
+        let mut clock = self.lock_health.lock(&self.commit_lock);
+        transaction.start_timestamp = tick(&mut clock);
 
-        //3. Add the new transaction to the list of active ones.
+        //3. Lock the active transaction list for writing.
+        let mut active_txs = self.lock_health.write(&self.active_transactions);
+
+        //4. Add the new transaction to the list of active ones.
         active_txs.insert(transaction.id, transaction.clone());
 
         Ok(transaction)
     }
 
+    /// Reads a concept as this transaction should see it, recording the read for validation.
+    /// Snapshot and Serializable transactions see the concept as of their start time;
+    /// ReadCommitted transactions see whatever was most recently committed.
+    pub fn read_concept(
+        &self,
+        transaction: &mut Transaction,
+        concept_id: &ConceptId,
+    ) -> Result<Option<ConceptVersion>> {
+        self.note_concept_read(transaction, *concept_id);
+        match transaction.isolation_level {
+            IsolationLevel::ReadCommitted => self
+                .version_store
+                .get_latest_active_concept_version(concept_id),
+            IsolationLevel::Snapshot | IsolationLevel::Serializable => self
+                .version_store
+                .get_concept_version_at_timestamp(concept_id, transaction.start_timestamp),
+        }
+    }
+
+    /// Reads a relationship as this transaction should see it, recording the read for validation.
+    pub fn read_relationship(
+        &self,
+        transaction: &mut Transaction,
+        relationship_id: &RelationshipId,
+    ) -> Result<Option<RelationshipVersion>> {
+        transaction.relationship_read_set.insert(*relationship_id);
+        transaction.record_progress();
+        match transaction.isolation_level {
+            IsolationLevel::ReadCommitted => self
+                .version_store
+                .get_latest_active_relationship_version(relationship_id),
+            IsolationLevel::Snapshot | IsolationLevel::Serializable => self
+                .version_store
+                .get_relationship_version_at_timestamp(relationship_id, transaction.start_timestamp),
+        }
+    }
+
+    /// Records that `transaction` read concept `id` some other way than `read_concept`, e.g.
+    /// to check it exists at the transaction's start.
+    pub fn note_concept_read(&self, transaction: &mut Transaction, id: ConceptId) {
+        transaction.read_set.insert(id);
+        transaction.record_progress();
+    }
+
+    /// Stages a new or updated concept in `transaction`.
+    pub fn stage_concept_write(&self, transaction: &mut Transaction, concept: Concept) {
+        let id = concept.id;
+        transaction.write_set.insert(id);
+        transaction.pending_writes.insert(id, concept);
+        transaction.record_progress();
+    }
+
+    /// Stages a concept's deletion in `transaction`.
+    pub fn stage_concept_delete(&self, transaction: &mut Transaction, id: ConceptId) {
+        transaction.write_set.insert(id);
+        transaction.pending_concept_deletes.insert(id);
+        transaction.record_progress();
+    }
+
+    /// Stages a new or rewritten relationship in `transaction`.
+    pub fn stage_relationship_write(&self, transaction: &mut Transaction, relationship: Relationship) {
+        let id = relationship.id;
+        transaction.relationship_write_set.insert(id);
+        transaction.pending_relationship_writes.insert(id, relationship);
+        transaction.record_progress();
+    }
+
+    /// Stages a relationship's deletion in `transaction`.
+    pub fn stage_relationship_delete(&self, transaction: &mut Transaction, id: RelationshipId) {
+        transaction.relationship_write_set.insert(id);
+        transaction.pending_deletes.insert(id);
+        transaction.record_progress();
+    }
+
+    /// Lists every active transaction, oldest first.
+    /// Only the set sizes each transaction last recorded are read, never its sets. It sees
+    /// reads made through `read_concept`, `read_relationship` and
+    /// `note_concept_read` and writes staged through the `stage_*` methods; sets a caller fills
+    /// in directly don't show.
+    pub fn list_active(&self) -> Result<Vec<TransactionSummary>> {
+        let mut summaries: Vec<TransactionSummary> = self
+            .lock_health
+            .read(&self.active_transactions)
+            .values()
+            .map(TransactionSummary::from)
+            .collect();
+        summaries.sort_by_key(|summary| summary.start_timestamp);
+        Ok(summaries)
+    }
+
+    /// Returns the summary of a single active transaction, if it is still active.
+    pub fn get_active(&self, transaction_id: &TransactionId) -> Result<Option<TransactionSummary>> {
+        let active_txs = self.lock_health.read(&self.active_transactions);
+        Ok(active_txs.get(transaction_id).map(TransactionSummary::from))
+    }
+
     /// Aborts a transaction, discarding all its changes.
     pub fn abort_transaction(&self, transaction_id: TransactionId) -> Result<()> {
-        let mut active_txs = self
-            .active_transactions
-            .write()
-            .map_err(|e| MnemonicError::Transaction(format!("Lock failed: {}", e)))?;
+        let mut active_txs = self.lock_health.write(&self.active_transactions);
 
         // Simply remove the transaction from the active list. Its changes are never saved.
         if active_txs.remove(&transaction_id).is_some() {
+            self.metrics.aborted.increment();
             Ok(())
         } else {
             Err(MnemonicError::Transaction(format!(
@@ -144,55 +574,98 @@ impl TransactionManager {
         }
     }
 
+    /// Aborts every active transaction, e.g. when the engine closes. Returns how many there were.
+    pub fn abort_all_transactions(&self) -> usize {
+        let aborted = std::mem::take(&mut *self.lock_health.write(&self.active_transactions));
+        self.metrics.aborted.add(aborted.len() as u64);
+        aborted.len()
+    }
+
     /// Commits a transaction, applying its changes if there are no conflicts.
     pub fn commit_transaction(&self, transaction: Transaction) -> Result<()> {
+        self.commit_transaction_detailed(transaction).map(|_| ())
+    }
+
+    /// Like `commit_transaction`, but hands back what the commit wrote.
+    pub fn commit_transaction_detailed(&self, transaction: Transaction) -> Result<CommitReceipt> {
+        // --- PHASE 0: CLAIM ---
+        // Take the transaction out of the active list. Whatever happens next, it is finished.
+        let was_active = self
+            .lock_health
+            .write(&self.active_transactions)
+            .remove(&transaction.id)
+            .is_some();
+
+        if self.is_expired(&transaction) {
+            // Either the reaper already removed it, or it expired before the reaper got to it.
+            if was_active {
+                self.metrics.expired.increment();
+            }
+            return Err(MnemonicError::TransactionExpired(transaction.id));
+        }
+        if !was_active {
+            return Err(MnemonicError::Transaction(format!(
+                "Transaction {} is not active",
+                transaction.id
+            )));
+        }
+        if self.is_replicating() {
+            return Err(MnemonicError::ReadOnlyReplica);
+        }
+
+        let mut clock = self.lock_health.lock(&self.commit_lock);
+        #[cfg(test)]
+        if self.panic_on_commit.load(Ordering::SeqCst) {
+            panic!("simulated panic during commit");
+        }
+        #[cfg(test)]
+        if self.conflict_in_commits.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)) == Ok(1) {
+            return Err(MnemonicError::TransactionConflict(Vec::new()));
+        }
+
         // --- PHASE 1: VALIDATION ---
         // Before we do anything, check for conflicts with other committed changes.
+        // Holding the commit lock means every earlier commit is fully visible here, and no
+        // conflicting commit can sneak in between this check and our write.
         self.validate_transaction(&transaction)?;
+        let concept_writes = self.classify_writes(&transaction)?;
 
-        // --- PHASE 2: PERSISTENCE & APPLY CHANGES ---
-        let mut batch = WriteBatch::default(); //1. Create a new atomic batch
+        // --- PHASE 2: BUILD THE NEW VERSIONS ---
+        // Everything this commit writes carries the same timestamp.
+        let commit_time = tick(&mut clock);
+        let mut concept_versions = Vec::new();
+        let mut relationship_versions = Vec::new();
+        // The latest version number of every item this commit touches, counting its own versions.
+        let mut concept_heads = HashMap::new();
+        let mut relationship_heads = HashMap::new();
 
         // Loop through all the "pending writes" in our transaction's shopping cart.
-        for (_concept_id, pending_concept) in &transaction.pending_writes {
-            // 1. Get the last known version from the in-memory store.
-            let last_version = self
-                .version_store
-                .get_concept_version_at_timestamp(_concept_id, transaction.start_timestamp)?;
-
-            // 2. Calculate the next version number
-            let next_version_num = last_version.map_or(1, |v| v.version + 1);
-
-            // 3. Create the new version with the correct number.
-            let new_version =
-                ConceptVersion::from_concept(&pending_concept, transaction.id, next_version_num);
-
-            // 4. Prepare for durable write and update in-memory store.
-            self.backend
-                .store_concept_version(&new_version, &mut batch)?;
-            self.version_store.add_concept_version(new_version)?;
+        for (concept_id, pending_concept) in &transaction.pending_writes {
+            // 1. Take the next number from the concept's version counter.
+            let next_version_num = self.next_concept_version(&mut concept_heads, concept_id)?;
+
+            // 2. Create the new version with the correct number.
+            concept_versions.push(ConceptVersion {
+                created_at: commit_time,
+                ..ConceptVersion::from_concept(pending_concept, transaction.id, next_version_num)
+            });
         }
 
         for (rel_id, pending_rel) in &transaction.pending_relationship_writes {
-            let last_version = self
-                .version_store
-                .get_relationship_version_at_timestamp(rel_id, transaction.start_timestamp)?;
-            let next_version_num = last_version.map_or(1, |v| v.version + 1);
+            let next_version_num = self.next_relationship_version(&mut relationship_heads, rel_id)?;
 
             let mut rel_for_version = pending_rel.clone();
             rel_for_version.metadata.version = next_version_num;
             rel_for_version.metadata.transaction_id = transaction.id;
 
-            let new_version =
-                RelationshipVersion::from_relationship(&rel_for_version, transaction.id);
-
-            // Now we call our new, correct function.
-            self.backend
-                .store_relationship_version(&new_version, &mut batch)?;
-            self.version_store.add_relationship_version(new_version)?;
+            // A rewritten relationship needs the number too; `from_relationship` makes a first version.
+            relationship_versions.push(RelationshipVersion {
+                version: next_version_num,
+                created_at: commit_time,
+                ..RelationshipVersion::from_relationship(&rel_for_version, transaction.id)
+            });
         }
 
-        let commit_time = Utc::now();
         for rel_id in &transaction.pending_deletes {
             // 1. Get the last active version of the relationship.
             if let Some(mut latest_version) = self
@@ -202,160 +675,1540 @@ impl TransactionManager {
                 // 2. Mark this version as "deleted".
                 latest_version.deleted_at = Some(commit_time);
                 latest_version.deleted_by = Some(transaction.id);
-                // We consider this a modification, so we increment the version.
-                latest_version.version += 1;
+                // We consider this a modification, so it gets a new version number.
+                latest_version.version =
+                    self.next_relationship_version(&mut relationship_heads, rel_id)?;
+                relationship_versions.push(latest_version);
+            }
+        }
 
-                // 3. Persist this new "deleted" version to disk and memory.
-                self.backend
-                    .store_relationship_version(&latest_version, &mut batch)?;
-                self.version_store
-                    .add_relationship_version(latest_version)?;
+        for concept_id in &transaction.pending_concept_deletes {
+            // An expired concept is no longer active, but still needs its tombstone.
+            if let Some(latest_version) = self
+                .version_store
+                .get_existing_concept_version_at_timestamp(concept_id, transaction.start_timestamp)?
+            {
+                // A concept tombstone is a new version that is born deleted at commit time,
+                // so time-travel queries before the commit still find the previous version.
+                concept_versions.push(ConceptVersion {
+                    version: self.next_concept_version(&mut concept_heads, concept_id)?,
+                    created_at: commit_time,
+                    created_by: transaction.id,
+                    deleted_at: Some(commit_time),
+                    deleted_by: Some(transaction.id),
+                    expires_at: None,
+                    ..latest_version
+                });
             }
         }
 
+        // --- PHASE 3: PERSISTENCE ---
+        // Disk first. If we die after this, hydration rebuilds memory from what was written;
+        // if the write fails, memory was never touched.
+        let mut batch = WriteBatch::default();
+        for version in &concept_versions {
+            self.backend.store_concept_version(version, &mut batch)?;
+            // Versions are in commit order, so a tombstone lands after any write it follows.
+            if version.deleted_at.is_some() {
+                self.backend.delete_concept_record(&version.concept_id, &mut batch);
+            } else {
+                self.backend.store_concept_record(&concept_from_version(version), &mut batch)?;
+            }
+        }
+        for version in &relationship_versions {
+            self.backend.store_relationship_version(version, &mut batch)?;
+        }
+        for (concept_id, head) in &concept_heads {
+            self.backend.store_concept_head(concept_id, *head, &mut batch)?;
+        }
+        for (rel_id, head) in &relationship_heads {
+            self.backend.store_relationship_head(rel_id, *head, &mut batch)?;
+        }
+        if !transaction.metadata.is_empty() {
+            self.backend
+                .store_transaction_metadata(&transaction.id, &transaction.metadata, &mut batch)?;
+        }
+        for (hash, concept_id) in &transaction.content_hashes {
+            self.backend.store_content_hash(*hash, concept_id, &mut batch)?;
+        }
+        let sequence = self.commit_sequence.load(Ordering::SeqCst) + 1;
+        self.backend.store_commit_sequence(sequence, &mut batch)?;
         // write the entire batch to disk, atomically.
-        self.backend.db.write(batch)?;
-
-        // --- PHASE 3: CLEANUP ---
-        // The commit was successful. Remove the transaction from the active list.
-        let mut active_txs = self
-            .active_transactions
-            .write()
-            .map_err(|e| MnemonicError::Transaction(format!("Lock failed: {}", e)))?;
-        active_txs.remove(&transaction.id);
+        self.backend.write(batch)?;
+        self.commit_sequence.store(sequence, Ordering::SeqCst);
+        let receipt = CommitReceipt {
+            transaction_id: transaction.id,
+            committed_at: commit_time,
+            sequence,
+            concept_versions: concept_versions.iter().map(|v| (v.concept_id, v.version)).collect(),
+            relationship_versions: relationship_versions.iter().map(|v| (v.relationship_id, v.version)).collect(),
+            deletes: concept_versions.iter().filter(|v| v.deleted_at.is_some()).count()
+                + relationship_versions.iter().filter(|v| v.deleted_at.is_some()).count(),
+            concept_writes,
+        };
+
+        // Published under the commit lock below, so listeners see commits in order.
+        let event = (self.commits.receiver_count() > 0).then(|| CommitEvent {
+            transaction_id: transaction.id,
+            committed_at: commit_time,
+            metadata: transaction.metadata.clone(),
+            concept_versions: concept_versions.clone(),
+            relationship_versions: relationship_versions.clone(),
+        });
+
+        // --- PHASE 4: APPLY TO MEMORY ---
+        if !self.skips_memory_apply() {
+            let registries_changed = touches_registries(&concept_versions);
+            for version in concept_versions {
+                self.version_store.add_concept_version(version)?;
+            }
+            for version in relationship_versions {
+                self.version_store.add_relationship_version(version)?;
+            }
+            if !transaction.metadata.is_empty() {
+                self.version_store
+                    .add_transaction_metadata(transaction.id, transaction.metadata)?;
+            }
+            self.lock_health
+                .write(&self.content_index)
+                .extend(transaction.content_hashes);
+            if registries_changed {
+                self.reload_registries()?;
+            }
+        }
+        self.metrics.committed.increment();
+        if let Some(event) = event {
+            // Only fails when every listener has gone away since we checked.
+            let _ = self.commits.send(Arc::new(event));
+        }
 
-        Ok(())
+        Ok(receipt)
     }
 
-    /// The "First Committer Wins" conflict detection logic.
-    fn validate_transaction(&self, transaction: &Transaction) -> Result<()> {
-        // Go through every concept ID that our transaction tried to change
-        for concept_id in &transaction.write_set {
-            // Ask the VersionStore: "Has this concept been modified by anyone else
-            // since our transaction started?"
-
-            //If YES, we have a conflict! Abort the commit.
-            if self
+    /// Whether each pending concept write inserts or updates, as of the transaction's snapshot.
+    /// Under `WriteMode::Strict`, fails with every write that doesn't do what it means to.
+    fn classify_writes(&self, transaction: &Transaction) -> Result<HashMap<ConceptId, WriteKind>> {
+        let mut kinds = HashMap::with_capacity(transaction.pending_writes.len());
+        let mut rejected = Vec::new();
+        for (concept_id, pending) in &transaction.pending_writes {
+            let existing = self
                 .version_store
-                .has_concept_been_modified_since(concept_id, transaction.start_timestamp)?
-            {
-                return Err(MnemonicError::TransactionConflict(format!(
-                    "Conflict detected on concept {}",
-                    concept_id
-                )));
+                .get_existing_concept_version_at_timestamp(concept_id, transaction.start_timestamp)?;
+            let kind = if existing.is_some() { WriteKind::Update } else { WriteKind::Insert };
+            kinds.insert(*concept_id, kind);
+            if transaction.write_mode == WriteMode::Lenient {
+                continue;
+            }
+            let intended = if pending.metadata.version == 0 { WriteKind::Insert } else { WriteKind::Update };
+            let reason = match (intended, kind) {
+                (WriteKind::Insert, WriteKind::Update) => Some("it already exists"),
+                (WriteKind::Insert, WriteKind::Insert)
+                    if self.version_store.latest_concept_version_number(concept_id)? > 0 =>
+                {
+                    Some("a deleted concept had this ID")
+                }
+                (WriteKind::Update, WriteKind::Insert)
+                    if self.version_store.latest_concept_version_number(concept_id)? > 0 =>
+                {
+                    Some("it was deleted")
+                }
+                (WriteKind::Update, WriteKind::Insert) => Some("it doesn't exist"),
+                _ => None,
+            };
+            if let Some(reason) = reason {
+                rejected.push(RejectedWrite { concept_id: *concept_id, intended, reason: reason.to_string() });
             }
         }
+        if !rejected.is_empty() {
+            rejected.sort_by_key(|rejected| rejected.concept_id);
+            return Err(MnemonicError::RejectedWrites(rejected));
+        }
+        Ok(kinds)
+    }
 
-        // If we get through the whole loop without finding any conflicts, we are safe.
-        Ok(())
+    /// Listens for commits from now on. See `graph::events` for what a slow listener misses.
+    pub fn subscribe_commits(&self) -> broadcast::Receiver<Arc<CommitEvent>> {
+        self.commits.subscribe()
     }
 
-    /// Returns a thread-safe handle to the internal VersionStore.
-    /// This is needed for the engine to perform read operations.
-    pub fn version_store(&self) -> Arc<VersionStore> {
-        Arc::clone(&self.version_store)
+    /// Hands out the next version number for a concept written by the commit in progress.
+    fn next_concept_version(
+        &self,
+        staged_heads: &mut HashMap<ConceptId, u64>,
+        concept_id: &ConceptId,
+    ) -> Result<u64> {
+        let latest = match staged_heads.get(concept_id) {
+            Some(head) => *head,
+            None => self.version_store.latest_concept_version_number(concept_id)?,
+        };
+        staged_heads.insert(*concept_id, latest + 1);
+        Ok(latest + 1)
     }
 
-    /// Retuns a thread-safe handle to the internal backend
-    pub fn backend(&self) -> Arc<RocksBackend> {
-        Arc::clone(&self.backend)
+    /// Hands out the next version number for a relationship written by the commit in progress.
+    fn next_relationship_version(
+        &self,
+        staged_heads: &mut HashMap<RelationshipId, u64>,
+        rel_id: &RelationshipId,
+    ) -> Result<u64> {
+        let latest = match staged_heads.get(rel_id) {
+            Some(head) => *head,
+            None => self.version_store.latest_relationship_version_number(rel_id)?,
+        };
+        staged_heads.insert(*rel_id, latest + 1);
+        Ok(latest + 1)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::types::concept::{ConceptData, ConceptMetadata};
-    use serde_json::json;
-    use std::thread;
-    use std::time::Duration;
-    use tempfile::tempdir;
+    /// Cross-checks the in-memory version heads against the 'versions' cabinet on disk,
+    /// reloading mismatched items from disk when `repair` is set.
+    /// Commits wait while the check runs.
+    pub fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport> {
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        consistency::verify(&self.version_store, &self.backend, repair)
+    }
 
-    #[test]
-    fn test_transaction_lifecycle() {
-        //1. Setup
-        let dir = tempdir().unwrap();
-        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
-        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+    /// Runs the integrity checks up to `depth` without changing anything.
+    /// Commits wait while the check runs.
+    pub fn check(&self, depth: CheckDepth) -> Result<CheckReport> {
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        check::check(&self.version_store, &self.backend, depth)
+    }
 
-        //2. Begin transaction
-        let txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+    /// Regenerates every index from the version history: first repairs memory from the
+    /// 'versions' cabinet, then rebuilds adjacency, labels and tags in memory, the content hash
+    /// and relationship entries of the 'indices' cabinet, and the concept head records.
+    /// Commits wait while it runs.
+    pub fn rebuild_derived_state(&self) -> Result<RebuildReport> {
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        let consistency = consistency::verify(&self.version_store, &self.backend, true)?;
+        let (adjacency, labels, tags) = self.version_store.rebuild_indexes()?;
+        let (content_hashes, kept) = rebuild::rebuild_content_hashes(&self.version_store, &self.backend)?;
+        *self.lock_health.write(&self.content_index) = kept;
+        let relationship_index = rebuild::rebuild_relationship_index(&self.backend)?;
+        let concept_records = rebuild::rebuild_concept_records(&self.version_store, &self.backend)?;
+        Ok(RebuildReport {
+            consistency,
+            adjacency,
+            labels,
+            tags,
+            content_hashes,
+            relationship_index,
+            concept_records,
+        })
+    }
 
-        //3. Verify begin
-        // We will do our check inside a separate block
-        // This ensures the read lock is released immediately after the check
+    /// Everything on disk an archive restores: every version still held, the version
+    /// counters, transaction metadata, claimed content hashes and the commit sequence. Read
+    /// under the commit lock, so it is one point in the history however busy the database is.
+    pub fn export_archive(&self) -> Result<Archive> {
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        Ok(Archive {
+            commit_sequence: self.commit_sequence.load(Ordering::SeqCst),
+            concept_versions: self.backend.load_all_concept_versions()?,
+            relationship_versions: self.backend.load_all_relationship_versions()?,
+            concept_heads: self.backend.load_all_concept_heads()?,
+            relationship_heads: self.backend.load_all_relationship_heads()?,
+            transactions: self.backend.load_all_transaction_metadata()?,
+            content_hashes: self.backend.load_all_content_hashes()?,
+        })
+    }
+
+    /// Writes `archive` into this database in one batch and loads it as hydration would,
+    /// keeping every ID, version number and timestamp. Refuses a database that has ever
+    /// committed anything, rather than mix two histories.
+    pub fn import_archive(&self, archive: Archive) -> Result<()> {
+        let mut clock = self.lock_health.lock(&self.commit_lock);
+        if self.is_replicating() {
+            return Err(MnemonicError::ReadOnlyReplica);
+        }
+        if self.commit_sequence.load(Ordering::SeqCst) > 0
+            || !self.backend.load_all_concept_heads()?.is_empty()
+            || !self.backend.load_all_relationship_heads()?.is_empty()
         {
-            let active_ids = manager.active_transactions.read().unwrap();
-            assert_eq!(active_ids.len(), 1);
-            assert!(active_ids.contains_key(&txn.id));
-        } // The read lock is automatically released here as `active_txs` is destroyed.
+            return Err(MnemonicError::Archive(
+                "an archive can only be imported into an empty database".to_string(),
+            ));
+        }
 
-        //4. Abort transaction
-        manager.abort_transaction(txn.id).unwrap();
+        let mut batch = WriteBatch::default();
+        for version in &archive.concept_versions {
+            self.backend.store_concept_version(version, &mut batch)?;
+        }
+        for version in &archive.relationship_versions {
+            self.backend.store_relationship_version(version, &mut batch)?;
+        }
+        for (concept_id, head) in &archive.concept_heads {
+            self.backend.store_concept_head(concept_id, *head, &mut batch)?;
+        }
+        for (rel_id, head) in &archive.relationship_heads {
+            self.backend.store_relationship_head(rel_id, *head, &mut batch)?;
+        }
+        for (transaction_id, metadata) in &archive.transactions {
+            self.backend.store_transaction_metadata(transaction_id, metadata, &mut batch)?;
+        }
+        for (hash, concept_id) in &archive.content_hashes {
+            self.backend.store_content_hash(*hash, concept_id, &mut batch)?;
+        }
+        self.backend.store_commit_sequence(archive.commit_sequence, &mut batch)?;
+        self.backend.write(batch)?;
+
+        // New timestamps must come after everything in the archive, as after a restart.
+        let latest = archive.latest_timestamp();
+        self.version_store
+            .replace_concept_chains(consistency::group_chains(archive.concept_versions, |v| v.concept_id))?;
+        self.version_store
+            .replace_relationship_chains(consistency::group_chains(archive.relationship_versions, |v| {
+                v.relationship_id
+            }))?;
+        for (concept_id, head) in archive.concept_heads {
+            self.version_store.restore_concept_head(concept_id, head)?;
+        }
+        for (rel_id, head) in archive.relationship_heads {
+            self.version_store.restore_relationship_head(rel_id, head)?;
+        }
+        for (transaction_id, metadata) in archive.transactions {
+            self.version_store.add_transaction_metadata(transaction_id, metadata)?;
+        }
+        self.lock_health.write(&self.content_index).extend(archive.content_hashes);
+        self.commit_sequence.store(archive.commit_sequence, Ordering::SeqCst);
+        if let Some(latest) = latest {
+            *clock = (*clock).max(latest);
+        }
+        // The concepts cabinet is derived from the versions, so it is filled in from them.
+        rebuild::rebuild_concept_records(&self.version_store, &self.backend)?;
+        // The archive's system concepts bring its schemas and relationship types along.
+        self.reload_registries()?;
+        Ok(())
+    }
 
-        //5. Verify Abort
-        // Perform our final check in another separate block.
-        {
-            let active_ids_after_abort = manager.active_transactions.read().unwrap();
-            assert!(active_ids_after_abort.is_empty());
-        } // The second read lock is released here.
+    /// Tombstones every concept whose expiry has passed, and the live relationships touching
+    /// them, in one commit. Returns how many concepts were tombstoned. If a concurrent commit
+    /// changes one of them, nothing is written and the next sweep tries again.
+    pub fn sweep_expired(&self) -> Result<usize> {
+        // A follower gets its tombstones from the leader's sweeper.
+        if self.is_replicating() {
+            return Ok(0);
+        }
+        let expired = self.version_store.expired_concepts(Utc::now());
+        if expired.is_empty() {
+            return Ok(0);
+        }
+        let mut txn = self.begin_transaction_with_meta(
+            IsolationLevel::Snapshot,
+            TransactionMetadata {
+                actor: Some(EXPIRY_SWEEPER.to_string()),
+                reason: Some("expired".to_string()),
+                ..Default::default()
+            },
+        )?;
+        for concept_id in &expired {
+            for rel_id in self.version_store.relationship_ids_touching(concept_id) {
+                self.stage_relationship_delete(&mut txn, rel_id);
+            }
+            self.stage_concept_delete(&mut txn, *concept_id);
+        }
+        match self.commit_transaction(txn) {
+            Ok(()) => Ok(expired.len()),
+            Err(MnemonicError::TransactionConflict(_)) => Ok(0),
+            Err(e) => Err(e),
+        }
     }
 
-    #[test]
-    fn test_first_committer_wins_conflict() {
-        // --- 1. SETUP ---
-        // Create a backend directly for our test, so we can peek into it.
-        let dir = tempdir().unwrap();
-        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
-        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+    /// Tombstones every active concept whose data matches `filter`, `options.batch_size` per
+    /// transaction, each recording `metadata`. A batch that fails is reported and the rest
+    /// carry on; every committed batch stays committed. System concepts never match, however
+    /// broad the filter.
+    pub fn delete_where(
+        &self,
+        filter: &Filter,
+        options: &BulkDeleteOptions,
+        metadata: &TransactionMetadata,
+    ) -> Result<BulkDeleteReport> {
+        if options.batch_size == 0 {
+            return Err(MnemonicError::InvalidData("batch_size must be at least 1".to_string()));
+        }
+        let matches = |version: &ConceptVersion, created_at| {
+            !system::is_system(&version.tags) && filter.matches(version, &parse_data(&version.data), created_at)
+        };
+        let mut report = BulkDeleteReport { dry_run: options.dry_run, ..Default::default() };
+        let budget = if options.dry_run { options.scan_budget } else { ScanBudget::unlimited() };
+        let meter = budget.start();
+        // When each match was created, which rechecking it later needs and no version records.
+        let mut created = HashMap::new();
+        self.version_store.scan_active_concepts(&meter, |version, created_at| {
+            if matches(version, created_at) {
+                report.matched.push(version.concept_id);
+                created.insert(version.concept_id, created_at);
+            }
+            ControlFlow::Continue(())
+        })?;
+        report.truncated = meter.is_spent();
+        report.matched.sort();
+        if options.dry_run {
+            return Ok(report);
+        }
 
-        // --- 2. CREATE INITIAL STATE (The Correct Way) ---
-        // Let's create our initial concept inside a transaction and commit it.
-        let concept_id;
-        {
-            let mut initial_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
-            let concept_to_create = Concept::new(json!({"value": "initial"}));
-            concept_id = concept_to_create.id; // Save the ID
+        for batch in report.matched.chunks(options.batch_size) {
+            let mut txn = self.begin_transaction_with_meta(IsolationLevel::Snapshot, metadata.clone())?;
+            let mut relationships = 0;
+            for concept_id in batch {
+                let current = self.version_store.get_concept_version_at_timestamp(concept_id, txn.start_timestamp)?;
+                if !current.as_ref().is_some_and(|current| matches(current, created[concept_id])) {
+                    report.skipped += 1;
+                    continue;
+                }
+                if options.cascade {
+                    for rel_id in self.version_store.relationship_ids_touching(concept_id) {
+                        relationships += usize::from(!txn.pending_deletes.contains(&rel_id));
+                        self.stage_relationship_delete(&mut txn, rel_id);
+                    }
+                }
+                self.stage_concept_delete(&mut txn, *concept_id);
+            }
+            let mut concept_ids: Vec<ConceptId> = txn.pending_concept_deletes.iter().copied().collect();
+            if concept_ids.is_empty() {
+                self.abort_transaction(txn.id)?;
+                continue;
+            }
+            match self.commit_transaction(txn) {
+                Ok(()) => {
+                    report.batches_committed += 1;
+                    report.concepts_deleted += concept_ids.len();
+                    report.relationships_deleted += relationships;
+                }
+                Err(e) => {
+                    concept_ids.sort();
+                    report.batches_failed += 1;
+                    report.failures.push(BulkDeleteFailure { concept_ids, error: e.to_string() });
+                }
+            }
+        }
+        Ok(report)
+    }
 
-            initial_txn.write_set.insert(concept_id);
-            initial_txn
-                .pending_writes
-                .insert(concept_id, concept_to_create);
+    /// Removes old versions allowed by `policy`, unless an active transaction still reads them.
+    /// Commits wait while pruning runs.
+    pub fn prune_versions(&self, policy: &RetentionPolicy) -> Result<PruneReport> {
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        let active = self.readers();
+        pruning::prune(&self.version_store, &self.backend, policy, &active)
+    }
 
-            // This commit writes the INITIAL version (version 1) to RocksDB and in-memory store.
-            manager.commit_transaction(initial_txn).unwrap();
+    /// Erases a concept and, unless `options` keeps them, its relationships, unless an active
+    /// transaction could still read them. Commits wait while the purge runs.
+    pub fn purge_concept(&self, concept_id: ConceptId, options: &PurgeOptions) -> Result<PurgeReport> {
+        let mut commit_guard = self.lock_health.lock(&self.commit_lock);
+        let active = self.readers();
+        let hashes: Vec<ContentHash> = self
+            .lock_health
+            .read(&self.content_index)
+            .iter()
+            .filter(|(_, holder)| **holder == concept_id)
+            .map(|(hash, _)| *hash)
+            .collect();
+        let report =
+            pruning::purge(&self.version_store, &self.backend, concept_id, options, &hashes, &active)?;
+        let mut content_index = self.lock_health.write(&self.content_index);
+        for hash in &hashes {
+            content_index.remove(hash);
         }
+        // Deltas from before now would miss what was erased.
+        let erased_at = tick(&mut commit_guard);
+        self.backend.store_history_erased_at(erased_at)?;
+        *self.lock_health.write(&self.history_erased_at) = Some(erased_at);
+        Ok(report)
+    }
 
-        // --- 2. THE RACE BEGINS ---
+    /// Works out which old versions the compaction filter may drop and hands that to the
+    /// backend, returning for how many items. Nothing happens unless `StorageOptions` turned
+    /// the filter on. The horizon moves back to the oldest open snapshot transaction or read
+    /// snapshot, so none of them loses a version it reads.
+    pub fn publish_compaction_horizon(&self) -> Result<usize> {
+        let Some((config, horizon)) = self.backend.version_compaction() else {
+            return Ok(0);
+        };
+        // Under the commit lock, so no commit lands between reading the readers and the chains.
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        let at = self
+            .readers()
+            .iter()
+            .filter(|txn| txn.isolation_level != IsolationLevel::ReadCommitted)
+            .map(|txn| txn.start_timestamp)
+            .fold(Utc::now() - chrono::Duration::seconds(config.horizon_secs as i64), DateTime::min);
+        let (concepts, relationships) = self.version_store.history_floors(at, config.drop_expired)?;
+        horizon.publish(concepts, relationships);
+        Ok(horizon.len())
+    }
 
-        // Alice starts her transaction.
-        let mut alice_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+    /// A timestamp that every commit applied so far comes at or before, and every later
+    /// commit comes after.
+    pub fn watermark(&self) -> DateTime<Utc> {
+        tick(&mut self.lock_health.lock(&self.commit_lock))
+    }
 
-        // Bob starts his transaction right after. His view of the world is the same as Alice's.
-        let mut bob_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+    /// How many commits the database has taken since it was created. Goes up by one with
+    /// every commit, so it names the graph's state for caches.
+    pub fn latest_commit_sequence(&self) -> u64 {
+        self.commit_sequence.load(Ordering::SeqCst)
+    }
 
-        // --- 4. ALICE WINS THE RACE ---
-        {
-            // Alice needs to read the concept first to modify it.
-            let concept_for_alice = manager
-                .version_store
-                .get_concept_version_at_timestamp(&concept_id, alice_txn.start_timestamp)
-                .unwrap()
-                .unwrap();
+    /// When a purge last erased history, if one ever has. A purge changes the graph without
+    /// a commit, so the commit sequence alone doesn't name the graph's state.
+    pub fn history_erased_at(&self) -> Option<DateTime<Utc>> {
+        *self.lock_health.read(&self.history_erased_at)
+    }
 
-            // Create the updated concept
-            let updated_concept = Concept {
-                id: concept_id,
-                data: ConceptData::Structured(json!({"value": "alice was here"}).to_string()),
-                metadata: ConceptMetadata {
-                    created_at: concept_for_alice.created_at,
-                    updated_at: Utc::now(),
-                    version: concept_for_alice.version + 1,
-                    transaction_id: alice_txn.id,
-                },
-            };
+    /// What changed in the live graph after `since`, up to a fresh watermark. Fails with
+    /// `HistoryErased` if a purge has erased history since then.
+    /// Commits wait while the delta is computed.
+    pub fn graph_delta(&self, since: DateTime<Utc>) -> Result<GraphDelta> {
+        let mut commit_guard = self.lock_health.lock(&self.commit_lock);
+        if let Some(erased_at) = *self.lock_health.read(&self.history_erased_at)
+            && since < erased_at
+        {
+            return Err(MnemonicError::HistoryErased { since, erased_at });
+        }
+        let as_of = tick(&mut commit_guard);
+        delta::delta(&self.version_store, since, as_of)
+    }
 
-            alice_txn.write_set.insert(concept_id);
+    /// Up to `limit` commits after `after` (from the first, if `None`), oldest first, for a
+    /// follower to replicate. Fails with `HistoryErased` if a purge has erased history since
+    /// `after`. Commits wait while the log is read.
+    pub fn changes(&self, after: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<CommitEvent>> {
+        let _commit_guard = self.lock_health.lock(&self.commit_lock);
+        if let Some(erased_at) = *self.lock_health.read(&self.history_erased_at) {
+            let since = after.unwrap_or(DateTime::<Utc>::MIN_UTC);
+            if since < erased_at {
+                return Err(MnemonicError::HistoryErased { since, erased_at });
+            }
+        }
+        self.version_store.commits_after(after, limit)
+    }
+
+    /// Applies a commit replicated from a leader as it was made there: same IDs, version
+    /// numbers and timestamps, without validating it again. A commit at or before the last
+    /// one replicated is skipped, so replaying one is harmless; returns whether it was applied.
+    /// Content hashes aren't replicated, so deduplicating stores only see the leader's claims
+    /// once writes move to this engine and `rebuild_derived_state` runs.
+    pub fn apply_replicated_commit(&self, commit: &CommitEvent) -> Result<bool> {
+        let mut clock = self.lock_health.lock(&self.commit_lock);
+        if self
+            .lock_health
+            .read(&self.replicated_through)
+            .is_some_and(|through| commit.committed_at <= through)
+        {
+            return Ok(false);
+        }
+
+        let mut concept_heads: HashMap<ConceptId, u64> = HashMap::new();
+        let mut relationship_heads: HashMap<RelationshipId, u64> = HashMap::new();
+        // A follower written to locally has versions of its own in the way; refuse before
+        // touching the disk rather than half-apply the commit.
+        for version in &commit.concept_versions {
+            let latest = match concept_heads.get(&version.concept_id) {
+                Some(head) => *head,
+                None => self.version_store.latest_concept_version_number(&version.concept_id)?,
+            };
+            if version.version <= latest {
+                return Err(MnemonicError::VersionOutOfSequence {
+                    id: version.concept_id,
+                    version: version.version,
+                    latest,
+                });
+            }
+            concept_heads.insert(version.concept_id, version.version);
+        }
+        for version in &commit.relationship_versions {
+            let latest = match relationship_heads.get(&version.relationship_id) {
+                Some(head) => *head,
+                None => self.version_store.latest_relationship_version_number(&version.relationship_id)?,
+            };
+            if version.version <= latest {
+                return Err(MnemonicError::VersionOutOfSequence {
+                    id: version.relationship_id,
+                    version: version.version,
+                    latest,
+                });
+            }
+            relationship_heads.insert(version.relationship_id, version.version);
+        }
+
+        let mut batch = WriteBatch::default();
+        for version in &commit.concept_versions {
+            self.backend.store_concept_version(version, &mut batch)?;
+            if version.deleted_at.is_some() {
+                self.backend.delete_concept_record(&version.concept_id, &mut batch);
+            } else {
+                self.backend.store_concept_record(&concept_from_version(version), &mut batch)?;
+            }
+        }
+        for version in &commit.relationship_versions {
+            self.backend.store_relationship_version(version, &mut batch)?;
+        }
+        for (concept_id, head) in &concept_heads {
+            self.backend.store_concept_head(concept_id, *head, &mut batch)?;
+        }
+        for (rel_id, head) in &relationship_heads {
+            self.backend.store_relationship_head(rel_id, *head, &mut batch)?;
+        }
+        if !commit.metadata.is_empty() {
+            self.backend
+                .store_transaction_metadata(&commit.transaction_id, &commit.metadata, &mut batch)?;
+        }
+        self.backend.store_replicated_through(commit.committed_at, &mut batch)?;
+        self.backend.write(batch)?;
+
+        // The leader may have pruned versions this follower never saw; the counters skip them.
+        for version in &commit.concept_versions {
+            let latest = self.version_store.latest_concept_version_number(&version.concept_id)?;
+            if version.version > latest + 1 {
+                self.version_store.restore_concept_head(version.concept_id, version.version - 1)?;
+            }
+            self.version_store.add_concept_version(version.clone())?;
+        }
+        for version in &commit.relationship_versions {
+            let latest = self.version_store.latest_relationship_version_number(&version.relationship_id)?;
+            if version.version > latest + 1 {
+                self.version_store
+                    .restore_relationship_head(version.relationship_id, version.version - 1)?;
+            }
+            self.version_store.add_relationship_version(version.clone())?;
+        }
+        if !commit.metadata.is_empty() {
+            self.version_store
+                .add_transaction_metadata(commit.transaction_id, commit.metadata.clone())?;
+        }
+        if touches_registries(&commit.concept_versions) {
+            self.reload_registries()?;
+        }
+        *self.lock_health.write(&self.replicated_through) = Some(commit.committed_at);
+        // Reads at a watermark must see this commit, so the clock can't stay behind it.
+        *clock = (*clock).max(commit.committed_at);
+        self.metrics.committed.increment();
+        if self.commits.receiver_count() > 0 {
+            let _ = self.commits.send(Arc::new(commit.clone()));
+        }
+        Ok(true)
+    }
+
+    /// The timestamp of the last commit replicated from a leader, if this is a follower.
+    pub fn replicated_through(&self) -> Option<DateTime<Utc>> {
+        *self.lock_health.read(&self.replicated_through)
+    }
+
+    /// Marks this engine as a follower until the guard is dropped; local commits are refused
+    /// meanwhile. Fails if it is already replicating.
+    pub fn begin_replication(&self) -> Result<ReplicationGuard<'_>> {
+        if self.replicating.swap(true, Ordering::SeqCst) {
+            return Err(MnemonicError::Replication("This engine is already replicating".to_string()));
+        }
+        Ok(ReplicationGuard { manager: self })
+    }
+
+    /// Whether a `ReplicationGuard` is held, making this engine a read-only follower.
+    pub fn is_replicating(&self) -> bool {
+        self.replicating.load(Ordering::SeqCst)
+    }
+
+    /// Opens a read snapshot at a fresh watermark, so it sees every commit applied so far.
+    pub fn open_snapshot(&self) -> ReadSnapshot {
+        self.snapshots.open(self.watermark())
+    }
+
+    /// The timestamp an open snapshot reads at; reading it keeps the snapshot from going idle.
+    pub fn snapshot_timestamp(&self, token: SnapshotToken) -> Result<DateTime<Utc>> {
+        self.snapshots.timestamp(token)
+    }
+
+    /// Releases a read snapshot. `false` if it wasn't open.
+    pub fn release_snapshot(&self, token: SnapshotToken) -> bool {
+        self.snapshots.release(token)
+    }
+
+    /// Every active transaction, and every open read snapshot posing as a snapshot
+    /// transaction that started at its timestamp, for the checks that must not take away
+    /// what one of them still reads.
+    fn readers(&self) -> Vec<Transaction> {
+        let mut readers: Vec<Transaction> = self
+            .lock_health
+            .read(&self.active_transactions)
+            .values()
+            .cloned()
+            .collect();
+        readers.extend(self.snapshots.open_snapshots().into_iter().map(|snapshot| Transaction {
+            id: snapshot.token,
+            start_timestamp: snapshot.timestamp,
+            ..Transaction::new(IsolationLevel::Snapshot)
+        }));
+        readers
+    }
+
+    #[cfg(test)]
+    fn skips_memory_apply(&self) -> bool {
+        self.skip_memory_apply.load(Ordering::SeqCst)
+    }
+
+    #[cfg(not(test))]
+    fn skips_memory_apply(&self) -> bool {
+        false
+    }
+
+    /// Makes commits write to disk but leave memory untouched, as if the process died in between.
+    #[cfg(test)]
+    pub(crate) fn set_skip_memory_apply(&self, skip: bool) {
+        self.skip_memory_apply.store(skip, Ordering::SeqCst);
+    }
+
+    /// Makes every commit panic while holding the commit lock.
+    #[cfg(test)]
+    pub(crate) fn set_panic_on_commit(&self, panic: bool) {
+        self.panic_on_commit.store(panic, Ordering::SeqCst);
+    }
+
+    /// Makes the `n`th commit from now fail with a conflict.
+    #[cfg(test)]
+    pub(crate) fn set_conflict_in_commits(&self, n: usize) {
+        self.conflict_in_commits.store(n, Ordering::SeqCst);
+    }
+
+    /// Whether a transaction has outlived the configured maximum age.
+    fn is_expired(&self, transaction: &Transaction) -> bool {
+        self.config
+            .max_transaction_age
+            .is_some_and(|max_age| transaction.age() > max_age)
+    }
+
+    /// The "First Committer Wins" conflict detection logic.
+    /// Every conflicting item is collected so callers can see the full picture at once.
+    fn validate_transaction(&self, transaction: &Transaction) -> Result<()> {
+        // Bad data is rejected whatever else is going on.
+        self.validate_schemas(transaction)?;
+
+        let mut conflicts = Vec::new();
+        let since = transaction.start_timestamp;
+
+        // Go through every item our transaction tried to change, and ask the VersionStore:
+        // "Has this been modified by anyone else since our transaction started?"
+        for concept_id in &transaction.write_set {
+            conflicts.extend(self.concept_conflict(concept_id, since)?);
+        }
+        for rel_id in &transaction.relationship_write_set {
+            conflicts.extend(self.relationship_conflict(rel_id, since)?);
+        }
+
+        // Serializable transactions must also not have based their writes on stale reads.
+        if transaction.isolation_level == IsolationLevel::Serializable {
+            for concept_id in transaction.read_set.difference(&transaction.write_set) {
+                if self.version_store.has_concept_been_modified_since(concept_id, since)? {
+                    conflicts.extend(self.concept_conflict(concept_id, since)?);
+                }
+            }
+            for rel_id in transaction
+                .relationship_read_set
+                .difference(&transaction.relationship_write_set)
+            {
+                if self.version_store.has_relationship_been_modified_since(rel_id, since)? {
+                    conflicts.extend(self.relationship_conflict(rel_id, since)?);
+                }
+            }
+        }
+
+        self.validate_referential_integrity(transaction, &mut conflicts)?;
+        self.validate_content_hashes(transaction, &mut conflicts)?;
+        self.validate_relationship_types(transaction)?;
+        self.validate_acyclic(transaction)?;
+        self.validate_cardinality(transaction)?;
+
+        // If we get through all the checks without finding any conflicts, we are safe.
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        for conflict in &conflicts {
+            if let ConflictItem::Concept(concept_id) = conflict.item {
+                self.metrics.conflict_hot_spots.record(concept_id);
+            }
+        }
+        Err(MnemonicError::TransactionConflict(conflicts))
+    }
+
+    /// Checks every concept the transaction writes against the schemas of its labels.
+    fn validate_schemas(&self, transaction: &Transaction) -> Result<()> {
+        let schemas = self.lock_health.read(&self.schemas);
+        if schemas.is_empty() {
+            return Ok(());
+        }
+        let mut concepts: Vec<&Concept> = transaction.pending_writes.values().collect();
+        concepts.sort_by_key(|concept| concept.id);
+
+        let mut violations = Vec::new();
+        for concept in concepts {
+            let data = parse_data(&concept.data);
+            for label in labels_of(&data) {
+                if let Some(schema) = schemas.get(&label) {
+                    violations.extend(schema.validate(concept.id, &label, &data));
+                }
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(MnemonicError::SchemaViolation(violations))
+        }
+    }
+
+    /// Describes the conflict if another transaction changed a concept after `since`.
+    fn concept_conflict(
+        &self,
+        concept_id: &ConceptId,
+        since: DateTime<Utc>,
+    ) -> Result<Option<ConflictInfo>> {
+        let Some(latest) = self.version_store.get_latest_concept_version(concept_id)? else {
+            return Ok(None);
+        };
+        if latest.created_at <= since {
+            return Ok(None);
+        }
+        let base = self
+            .version_store
+            .get_concept_version_at_timestamp(concept_id, since)?;
+        Ok(Some(ConflictInfo {
+            item: ConflictItem::Concept(*concept_id),
+            our_base_version: base.map(|v| v.version),
+            committed_version: latest.version,
+            committed_at: latest.created_at,
+            committed_by: latest.created_by,
+        }))
+    }
+
+    /// Describes the conflict if another transaction changed a relationship after `since`.
+    /// A deletion counts as a modification too.
+    fn relationship_conflict(
+        &self,
+        rel_id: &RelationshipId,
+        since: DateTime<Utc>,
+    ) -> Result<Option<ConflictInfo>> {
+        let Some(latest) = self.version_store.get_latest_relationship_version(rel_id)? else {
+            return Ok(None);
+        };
+        let committed_at = latest.deleted_at.unwrap_or(latest.created_at);
+        if committed_at <= since {
+            return Ok(None);
+        }
+        let base = self
+            .version_store
+            .get_relationship_version_at_timestamp(rel_id, since)?;
+        Ok(Some(ConflictInfo {
+            item: ConflictItem::Relationship(*rel_id),
+            our_base_version: base.map(|v| v.version),
+            committed_version: latest.version,
+            committed_at,
+            committed_by: latest.deleted_by.unwrap_or(latest.created_by),
+        }))
+    }
+
+    /// Makes sure the commit can't leave relationships pointing at concepts that no longer exist.
+    /// Concurrent changes are reported as conflicts; anything else is a dangling relationship.
+    fn validate_referential_integrity(
+        &self,
+        transaction: &Transaction,
+        conflicts: &mut Vec<ConflictInfo>,
+    ) -> Result<()> {
+        let now = Utc::now();
+
+        // 1. Every relationship we write must point at concepts that are still active.
+        for (rel_id, pending_rel) in &transaction.pending_relationship_writes {
+            for endpoint in [pending_rel.source, pending_rel.target] {
+                if transaction.pending_concept_deletes.contains(&endpoint) {
+                    return Err(MnemonicError::DanglingRelationship {
+                        relationship_id: *rel_id,
+                        concept_id: endpoint,
+                    });
+                }
+                // Concepts created in this same transaction are fine.
+                if transaction.pending_writes.contains_key(&endpoint) {
+                    continue;
+                }
+                match self.version_store.get_latest_concept_version(&endpoint)? {
+                    Some(latest) if latest.is_active_at(now) => {}
+                    Some(latest) if latest.created_at > transaction.start_timestamp => {
+                        conflicts.extend(
+                            self.concept_conflict(&endpoint, transaction.start_timestamp)?,
+                        );
+                    }
+                    _ => {
+                        return Err(MnemonicError::DanglingRelationship {
+                            relationship_id: *rel_id,
+                            concept_id: endpoint,
+                        });
+                    }
+                }
+            }
+        }
+
+        // 2. A concept we delete must not have gained relationships since we started. Only
+        //    the edges at each deleted concept are looked at, through the adjacency index.
+        let mut seen = HashSet::new();
+        for concept_id in &transaction.pending_concept_deletes {
+            let mut touching = self.version_store.outgoing_relationships(concept_id)?;
+            touching.extend(self.version_store.incoming_relationships(concept_id)?);
+            for rel in touching {
+                if rel.created_at > transaction.start_timestamp && seen.insert(rel.relationship_id) {
+                    conflicts.push(ConflictInfo {
+                        item: ConflictItem::Relationship(rel.relationship_id),
+                        our_base_version: None,
+                        committed_version: rel.version,
+                        committed_at: rel.created_at,
+                        committed_by: rel.created_by,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Refuses edges of an acyclic type that would close a cycle, counting the other edges
+    /// this transaction writes and leaving out the ones it deletes.
+    fn validate_acyclic(&self, transaction: &Transaction) -> Result<()> {
+        for relationship_type in &self.config.acyclic_types {
+            let added: Vec<(ConceptId, ConceptId)> = transaction
+                .pending_relationship_writes
+                .values()
+                .filter(|rel| &rel.relationship_type == relationship_type)
+                .map(|rel| (rel.source, rel.target))
+                .collect();
+            for &(source, target) in &added {
+                // The new edge closes a cycle exactly when its target already leads back to its source.
+                if let Some(cycle) = analysis::find_path(
+                    &self.version_store,
+                    target,
+                    source,
+                    relationship_type,
+                    &added,
+                    &transaction.pending_deletes,
+                ) {
+                    return Err(MnemonicError::CycleDetected {
+                        relationship_type: relationship_type.to_string(),
+                        cycle,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses content hash claims on data another concept already holds. Only one of two
+    /// concurrent deduplicating stores of the same data can commit; the other conflicts.
+    fn validate_content_hashes(
+        &self,
+        transaction: &Transaction,
+        conflicts: &mut Vec<ConflictInfo>,
+    ) -> Result<()> {
+        for (hash, concept_id) in &transaction.content_hashes {
+            let Some(pending) = transaction.pending_writes.get(concept_id) else {
+                return Err(MnemonicError::Transaction(format!(
+                    "Content hash claimed for concept {}, which the transaction doesn't write",
+                    concept_id
+                )));
+            };
+            let Some(existing) = self.find_duplicate_by_hash(*hash, &pending.data)? else {
+                continue;
+            };
+            if existing == *concept_id {
+                continue;
+            }
+            if let Some(latest) = self.version_store.get_latest_concept_version(&existing)? {
+                conflicts.push(ConflictInfo {
+                    item: ConflictItem::Concept(existing),
+                    our_base_version: None,
+                    committed_version: latest.version,
+                    committed_at: latest.created_at,
+                    committed_by: latest.created_by,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The active concept whose data is the same as `data`, if a deduplicating store put one there.
+    pub fn find_duplicate(&self, data: &ConceptData) -> Result<Option<ConceptId>> {
+        self.find_duplicate_by_hash(content::content_hash(data), data)
+    }
+
+    fn find_duplicate_by_hash(&self, hash: ContentHash, data: &ConceptData) -> Result<Option<ConceptId>> {
+        let Some(concept_id) = self.lock_health.read(&self.content_index).get(&hash).copied() else {
+            return Ok(None);
+        };
+        // The entry may be stale (the concept was since changed or deleted) or, rarely, a
+        // hash collision, so only data that still matches counts.
+        let matches = self
+            .version_store
+            .get_latest_concept_version(&concept_id)?
+            .is_some_and(|latest| {
+                latest.is_active_at(Utc::now()) && content::same_content(&latest.data, data)
+            });
+        Ok(matches.then_some(concept_id))
+    }
+
+    /// Refuses relationships that would give a concept more edges of a type than its
+    /// cardinality allows. Runs under the commit lock against the latest committed graph,
+    /// so of two concurrent transactions competing for the same slot only the first wins.
+    fn validate_cardinality(&self, transaction: &Transaction) -> Result<()> {
+        let cardinalities = self.lock_health.read(&self.cardinalities);
+        if cardinalities.is_empty() {
+            return Ok(());
+        }
+        let mut added: Vec<&Relationship> = transaction.pending_relationship_writes.values().collect();
+        added.sort_by_key(|rel| rel.id);
+
+        for (i, rel) in added.iter().enumerate() {
+            let Some(&cardinality) = cardinalities.get(&rel.relationship_type) else {
+                continue;
+            };
+            // An undirected relationship leaves and enters both of its ends.
+            let leaves = |source: ConceptId, target: ConceptId, directed: bool, end: ConceptId| {
+                source == end || (!directed && target == end)
+            };
+            let enters = |source, target, directed, end| leaves(target, source, directed, end);
+            let ends = [rel.source, rel.target];
+            let clashes = |other_id: RelationshipId,
+                           source: ConceptId,
+                           other_type: &RelationType,
+                           target: ConceptId,
+                           directed: bool| {
+                other_id != rel.id
+                    && other_type == &rel.relationship_type
+                    && !transaction.pending_deletes.contains(&other_id)
+                    && match cardinality {
+                        Cardinality::OneOutgoing => ends.iter().any(|&end| {
+                            leaves(rel.source, rel.target, rel.directed, end) && leaves(source, target, directed, end)
+                        }),
+                        Cardinality::OneIncoming => ends.iter().any(|&end| {
+                            enters(rel.source, rel.target, rel.directed, end) && enters(source, target, directed, end)
+                        }),
+                        // (a, b) and (b, a) are the same pair when either is undirected.
+                        Cardinality::Unique => {
+                            (source == rel.source && target == rel.target)
+                                || ((!directed || !rel.directed) && source == rel.target && target == rel.source)
+                        }
+                        Cardinality::Many => false,
+                    }
+            };
+            // Whatever clashes leaves (or, for a pair, enters) one of the two ends.
+            let mut committed = Vec::new();
+            for end in ends {
+                committed.extend(match cardinality {
+                    Cardinality::OneOutgoing => self.version_store.outgoing_relationships(&end)?,
+                    Cardinality::OneIncoming | Cardinality::Unique => self.version_store.incoming_relationships(&end)?,
+                    Cardinality::Many => continue,
+                });
+            }
+            // Edges committed by anyone, then the ones this transaction adds before this one.
+            let existing = committed
+                .iter()
+                .find(|v| clashes(v.relationship_id, v.source, &v.relationship_type, v.target, v.directed))
+                .map(|v| v.relationship_id)
+                .or_else(|| {
+                    added[..i]
+                        .iter()
+                        .find(|other| {
+                            clashes(other.id, other.source, &other.relationship_type, other.target, other.directed)
+                        })
+                        .map(|other| other.id)
+                });
+            if let Some(existing) = existing {
+                return Err(MnemonicError::CardinalityViolation {
+                    relationship_type: rel.relationship_type.to_string(),
+                    cardinality,
+                    relationship_id: rel.id,
+                    existing,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// In strict mode, refuses relationships of a type nobody registered, suggesting the
+    /// registered types it most resembles.
+    fn validate_relationship_types(&self, transaction: &Transaction) -> Result<()> {
+        if !self.config.strict_relationship_types {
+            return Ok(());
+        }
+        let registered = self.lock_health.read(&self.relationship_types);
+        for rel in transaction.pending_relationship_writes.values() {
+            if !registered.contains_key(&rel.relationship_type) {
+                return Err(MnemonicError::UnknownRelationshipType {
+                    relationship_type: rel.relationship_type.to_string(),
+                    suggestions: similar_names(&rel.relationship_type, registered.keys()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers `name` as a relationship type, or updates its description. Registering
+    /// matters only in strict mode, where unregistered types are refused.
+    pub fn register_relationship_type(&self, name: &str, description: &str) -> Result<RelationshipTypeDefinition> {
+        let _registry_guard = self.lock_health.lock(&self.registry_lock);
+        let definition = RelationshipTypeDefinition {
+            name: name.into(),
+            description: description.to_string(),
+            // Re-registering keeps the original date.
+            registered_at: self
+                .lock_health
+                .read(&self.relationship_types)
+                .get(name)
+                .map_or_else(Utc::now, |existing| existing.registered_at),
+        };
+        let mut registered = self.registry(RELATIONSHIP_TYPES, || Ok(Registry::new()))?;
+        registered.insert(name.to_string(), serde_json::to_value(&definition).map_err(std::io::Error::from)?);
+        // The commit updates the registry in memory.
+        self.put_system(RELATIONSHIP_TYPES, serde_json::Value::Object(registered))?;
+        Ok(definition)
+    }
+
+    /// Every registered relationship type, in name order.
+    pub fn relationship_types(&self) -> Result<Vec<RelationshipTypeDefinition>> {
+        let mut definitions: Vec<RelationshipTypeDefinition> =
+            self.lock_health.read(&self.relationship_types).values().cloned().collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(definitions)
+    }
+
+    /// Limits how many active relationships of `relationship_type` a concept may take part in,
+    /// from the next commit on. Relationships already stored aren't rechecked.
+    pub fn set_relationship_constraint(
+        &self,
+        relationship_type: &str,
+        cardinality: Cardinality,
+    ) -> Result<()> {
+        let _registry_guard = self.lock_health.lock(&self.registry_lock);
+        let mut constraints = self.registry(CARDINALITIES, || self.legacy_cardinalities())?;
+        if cardinality == Cardinality::Many {
+            constraints.remove(relationship_type);
+        } else {
+            let value = serde_json::to_value(cardinality).map_err(std::io::Error::from)?;
+            constraints.insert(relationship_type.to_string(), value);
+        }
+        // The commit swaps the constraints in under the commit lock, so each commit sees one
+        // consistent set.
+        self.put_system(CARDINALITIES, serde_json::Value::Object(constraints))
+    }
+
+    /// The cardinality constraint on `relationship_type`; `Many` when none was set.
+    pub fn relationship_constraint(&self, relationship_type: &str) -> Result<Cardinality> {
+        Ok(self
+            .lock_health
+            .read(&self.cardinalities)
+            .get(relationship_type)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    /// Requires every concept labelled `label` to satisfy `schema` from its next write on,
+    /// replacing any earlier schema for the label. Concepts already stored aren't rechecked.
+    pub fn register_schema(&self, label: &str, schema: serde_json::Value) -> Result<()> {
+        let schema = Schema::compile(schema)?;
+        let _registry_guard = self.lock_health.lock(&self.registry_lock);
+        let mut registered = self.registry(SCHEMAS, || Ok(Registry::new()))?;
+        registered.insert(label.to_string(), schema.as_value().clone());
+        // The commit swaps the schema in under the commit lock, so no commit is validated
+        // against a half-registered one.
+        self.put_system(SCHEMAS, serde_json::Value::Object(registered))
+    }
+
+    /// The schema registered for `label`, if any.
+    pub fn schema(&self, label: &str) -> Result<Option<serde_json::Value>> {
+        Ok(self
+            .lock_health
+            .read(&self.schemas)
+            .get(label)
+            .map(|schema| schema.as_value().clone()))
+    }
+
+    /// The value of the system concept `name` (see `types::system`), if one has been put.
+    pub fn get_system(&self, name: &str) -> Result<Option<serde_json::Value>> {
+        system::check_name(name)?;
+        Ok(self
+            .version_store
+            .get_latest_active_concept_version(&system::system_id(name))?
+            .filter(|version| system::is_system(&version.tags))
+            .and_then(|version| system::system_value(parse_data(&version.data))))
+    }
+
+    /// Puts `value` in the system concept `name`, committing a new version of it like any
+    /// other write, so a concurrent put is a `TransactionConflict`. Putting the value it
+    /// already holds commits nothing.
+    pub fn put_system(&self, name: &str, value: serde_json::Value) -> Result<()> {
+        system::check_name(name)?;
+        let data = system::system_data(name, value);
+        let id = system::system_id(name);
+        let mut txn = self.begin_transaction(IsolationLevel::Snapshot)?;
+        let latest = self.version_store.get_concept_version_at_timestamp(&id, txn.start_timestamp)?;
+        let mut concept = match latest {
+            Some(latest) if system::is_system(&latest.tags) && parse_data(&latest.data) == data => {
+                self.abort_transaction(txn.id)?;
+                return Ok(());
+            }
+            Some(latest) => concept_from_version(&latest),
+            None => Concept { id, ..Concept::empty() },
+        };
+        concept.data = ConceptData::Structured(data.to_string().into());
+        concept.tags = BTreeSet::from([system::SYSTEM_TAG.to_string()]);
+        self.stage_concept_write(&mut txn, concept);
+        self.commit_transaction(txn)
+    }
+
+    /// The entries of the registry kept in the system concept `name`, or what `legacy`
+    /// reads if there is no such concept yet.
+    fn registry(&self, name: &str, legacy: impl FnOnce() -> Result<Registry>) -> Result<Registry> {
+        match self.get_system(name)? {
+            Some(serde_json::Value::Object(entries)) => Ok(entries),
+            Some(_) => Ok(Registry::new()),
+            None => legacy(),
+        }
+    }
+
+    /// The schemas a database from before system concepts kept in a keyspace of their own.
+    fn legacy_schemas(&self) -> Result<Registry> {
+        Ok(self.backend.load_all_schemas()?.into_iter().collect())
+    }
+
+    /// The relationship types a database from before system concepts kept in a keyspace of
+    /// their own.
+    fn legacy_relationship_types(&self) -> Result<Registry> {
+        let mut registry = Registry::new();
+        for definition in self.backend.load_all_relationship_types()? {
+            let value = serde_json::to_value(&definition).map_err(std::io::Error::from)?;
+            registry.insert(definition.name.to_string(), value);
+        }
+        Ok(registry)
+    }
+
+    /// The cardinality constraints a database from before system concepts kept in a keyspace
+    /// of their own.
+    fn legacy_cardinalities(&self) -> Result<Registry> {
+        let mut registry = Registry::new();
+        for (relationship_type, cardinality) in self.backend.load_all_cardinalities()? {
+            let value = serde_json::to_value(cardinality).map_err(std::io::Error::from)?;
+            registry.insert(relationship_type.to_string(), value);
+        }
+        Ok(registry)
+    }
+
+    /// Moves the legacy registries into their system concepts and deletes the old keys. A
+    /// read-only database keeps reading the old keys, and a follower gets the system
+    /// concepts from its leader.
+    fn migrate_registries(&self) -> Result<()> {
+        if self.backend.is_read_only() || self.replicated_through().is_some() {
+            return Ok(());
+        }
+        let legacy = [
+            (SCHEMAS, self.legacy_schemas()?),
+            (RELATIONSHIP_TYPES, self.legacy_relationship_types()?),
+            (CARDINALITIES, self.legacy_cardinalities()?),
+        ];
+        for (name, legacy) in legacy {
+            if !legacy.is_empty() && self.get_system(name)?.is_none() {
+                self.put_system(name, serde_json::Value::Object(legacy))?;
+            }
+        }
+        let deleted = self.backend.delete_legacy_registries()?;
+        if deleted > 0 {
+            tracing::info!("Moved {} schemas, relationship types and cardinalities to system concepts", deleted);
+        }
+        Ok(())
+    }
+
+    /// Reads the schema, relationship-type and cardinality registries from their system concepts.
+    /// Entries that no longer compile or parse are skipped rather than blocking startup.
+    fn reload_registries(&self) -> Result<()> {
+        let mut schemas = HashMap::new();
+        for (label, schema) in self.registry(SCHEMAS, || self.legacy_schemas())? {
+            match Schema::compile(schema) {
+                Ok(schema) => {
+                    schemas.insert(label, schema);
+                }
+                Err(e) => tracing::warn!("Ignoring the stored schema for label {:?}: {}", label, e),
+            }
+        }
+        let mut relationship_types = HashMap::new();
+        for (name, definition) in self.registry(RELATIONSHIP_TYPES, || self.legacy_relationship_types())? {
+            match serde_json::from_value::<RelationshipTypeDefinition>(definition) {
+                Ok(definition) => {
+                    relationship_types.insert(definition.name.clone(), definition);
+                }
+                Err(e) => tracing::warn!("Ignoring the stored relationship type {:?}: {}", name, e),
+            }
+        }
+        let mut cardinalities = HashMap::new();
+        for (name, cardinality) in self.registry(CARDINALITIES, || self.legacy_cardinalities())? {
+            match serde_json::from_value::<Cardinality>(cardinality) {
+                Ok(Cardinality::Many) => {}
+                Ok(cardinality) => {
+                    cardinalities.insert(RelationType::new(&name), cardinality);
+                }
+                Err(e) => tracing::warn!("Ignoring the stored cardinality for relationship type {:?}: {}", name, e),
+            }
+        }
+        *self.lock_health.write(&self.schemas) = schemas;
+        *self.lock_health.write(&self.cardinalities) = cardinalities;
+        *self.lock_health.write(&self.relationship_types) = relationship_types;
+        Ok(())
+    }
+
+    /// Returns a thread-safe handle to the internal VersionStore.
+    /// This is needed for the engine to perform read operations.
+    pub fn version_store(&self) -> Arc<VersionStore> {
+        Arc::clone(&self.version_store)
+    }
+
+    /// Retuns a thread-safe handle to the internal backend
+    pub fn backend(&self) -> Arc<RocksBackend> {
+        Arc::clone(&self.backend)
+    }
+
+    /// Returns the lifecycle counters for this manager.
+    pub fn metrics(&self) -> Arc<TransactionMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Returns the counter of poisoned locks this manager and its VersionStore have recovered.
+    pub fn lock_health(&self) -> Arc<LockHealth> {
+        Arc::clone(&self.lock_health)
+    }
+
+    /// Panics while holding the active transaction list's write lock, poisoning it.
+    #[cfg(test)]
+    pub(crate) fn poison_active_transactions_for_test(&self) {
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = self.active_transactions.write();
+            panic!("simulated panic while holding the active transactions lock");
+        }));
+    }
+}
+
+/// Held while an engine replicates from a leader. Dropping it lets local commits in again.
+#[derive(Debug)]
+pub struct ReplicationGuard<'a> {
+    manager: &'a TransactionManager,
+}
+
+impl Drop for ReplicationGuard<'_> {
+    fn drop(&mut self) {
+        self.manager.replicating.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Starts a background thread that periodically aborts transactions older than `max_age`.
+/// The thread exits on its own once the TransactionManager (and its active list) is dropped.
+fn spawn_reaper(
+    active_transactions: Weak<ActiveTransactions>,
+    metrics: Arc<TransactionMetrics>,
+    lock_health: Arc<LockHealth>,
+    max_age: Duration,
+    interval: Duration,
+) -> Result<()> {
+    thread::Builder::new()
+        .name("mnemonic-txn-reaper".to_string())
+        .spawn(move || {
+            loop {
+                thread::sleep(interval);
+                let Some(active) = active_transactions.upgrade() else {
+                    break;
+                };
+                reap_expired(&active, max_age, &metrics, &lock_health);
+            }
+        })
+        .map_err(|e| MnemonicError::Transaction(format!("Failed to start reaper: {}", e)))?;
+    Ok(())
+}
+
+/// The most suggestions an `UnknownRelationshipType` error carries.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Whether any of `versions` is of the system concept holding a registry.
+fn touches_registries(versions: &[ConceptVersion]) -> bool {
+    if versions.is_empty() {
+        return false;
+    }
+    let registries =
+        [system::system_id(SCHEMAS), system::system_id(RELATIONSHIP_TYPES), system::system_id(CARDINALITIES)];
+    versions.iter().any(|version| registries.contains(&version.concept_id))
+}
+
+/// The `candidates` within a few edits of `name`, closest first. Case and the choice
+/// between `_`, `-` and nothing are ignored, so "worksFor" is a match for "works_for".
+fn similar_names<'a>(name: &str, candidates: impl Iterator<Item = &'a RelationType>) -> Vec<String> {
+    let normalize = |s: &str| -> Vec<char> {
+        s.chars().filter(|c| !matches!(c, '_' | '-')).flat_map(char::to_lowercase).collect()
+    };
+    let target = normalize(name);
+    let limit = (target.len() / 3).max(2);
+    let mut close: Vec<(usize, &RelationType)> = candidates
+        .map(|candidate| (edit_distance(&target, &normalize(candidate)), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    close.sort();
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| candidate.to_string()).collect()
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Starts a background thread that calls `sweep_expired` every `interval`. Like the reaper,
+/// it only holds a weak reference and exits once the TransactionManager is dropped.
+pub(crate) fn spawn_expiry_sweeper(manager: Weak<TransactionManager>, interval: Duration) -> Result<()> {
+    thread::Builder::new()
+        .name(EXPIRY_SWEEPER.to_string())
+        .spawn(move || {
+            loop {
+                thread::sleep(interval);
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                match manager.sweep_expired() {
+                    Ok(0) => {}
+                    Ok(swept) => tracing::info!("Tombstoned {} expired concept(s)", swept),
+                    Err(e) => tracing::warn!("Expiry sweep failed: {}", e),
+                }
+            }
+        })
+        .map_err(|e| MnemonicError::Transaction(format!("Failed to start expiry sweeper: {}", e)))?;
+    Ok(())
+}
+
+/// Removes every transaction older than `max_age` from the active list.
+fn reap_expired(
+    active: &ActiveTransactions,
+    max_age: Duration,
+    metrics: &TransactionMetrics,
+    lock_health: &LockHealth,
+) {
+    let mut active_txs = lock_health.write(active);
+    let before = active_txs.len();
+    active_txs.retain(|id, txn| {
+        let expired = txn.age() > max_age;
+        if expired {
+            tracing::warn!("Reaping transaction {} after {:?}", id, txn.age());
+        }
+        !expired
+    });
+    let reaped = (before - active_txs.len()) as u64;
+    if reaped > 0 {
+        metrics.expired.add(reaped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::concept::{ConceptData, ConceptMetadata};
+    use crate::storage::keys;
+    use serde_json::json;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_transaction_lifecycle() {
+        //1. Setup
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+
+        //2. Begin transaction
+        let txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+
+        //3. Verify begin
+        // We will do our check inside a separate block
+        // This ensures the read lock is released immediately after the check
+        {
+            let active_ids = manager.active_transactions.read().unwrap();
+            assert_eq!(active_ids.len(), 1);
+            assert!(active_ids.contains_key(&txn.id));
+        } // The read lock is automatically released here as `active_txs` is destroyed.
+
+        //4. Abort transaction
+        manager.abort_transaction(txn.id).unwrap();
+
+        //5. Verify Abort
+        // Perform our final check in another separate block.
+        {
+            let active_ids_after_abort = manager.active_transactions.read().unwrap();
+            assert!(active_ids_after_abort.is_empty());
+        } // The second read lock is released here.
+    }
+
+    #[test]
+    fn test_first_committer_wins_conflict() {
+        // --- 1. SETUP ---
+        // Create a backend directly for our test, so we can peek into it.
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+
+        // --- 2. CREATE INITIAL STATE (The Correct Way) ---
+        // Let's create our initial concept inside a transaction and commit it.
+        let concept_id;
+        {
+            let mut initial_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            let concept_to_create = Concept::new(json!({"value": "initial"}));
+            concept_id = concept_to_create.id; // Save the ID
+
+            initial_txn.write_set.insert(concept_id);
+            initial_txn
+                .pending_writes
+                .insert(concept_id, concept_to_create);
+
+            // This commit writes the INITIAL version (version 1) to RocksDB and in-memory store.
+            manager.commit_transaction(initial_txn).unwrap();
+        }
+
+        // --- 2. THE RACE BEGINS ---
+
+        // Alice starts her transaction.
+        let mut alice_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+
+        // Bob starts his transaction right after. His view of the world is the same as Alice's.
+        let mut bob_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+
+        // --- 4. ALICE WINS THE RACE ---
+        {
+            // Alice needs to read the concept first to modify it.
+            let concept_for_alice = manager
+                .version_store
+                .get_concept_version_at_timestamp(&concept_id, alice_txn.start_timestamp)
+                .unwrap()
+                .unwrap();
+
+            // Create the updated concept
+            let updated_concept = Concept {
+                id: concept_id,
+                data: ConceptData::Structured(json!({"value": "alice was here"}).to_string().into()),
+                metadata: ConceptMetadata {
+                    created_at: concept_for_alice.created_at,
+                    updated_at: Utc::now(),
+                    version: concept_for_alice.version + 1,
+                    transaction_id: alice_txn.id,
+                },
+                expires_at: None,
+                tags: Default::default(),
+            };
+
+            alice_txn.write_set.insert(concept_id);
             alice_txn.pending_writes.insert(concept_id, updated_concept);
 
             thread::sleep(Duration::from_millis(10));
@@ -366,8 +2219,10 @@ mod tests {
         {
             let updated_concept_bob = Concept {
                 id: concept_id,
-                data: ConceptData::Structured(json!({"value": "bob was here"}).to_string()),
+                data: ConceptData::Structured(json!({"value": "bob was here"}).to_string().into()),
                 metadata: Default::default(),
+                expires_at: None,
+                tags: Default::default(),
             };
             bob_txn.write_set.insert(concept_id);
             bob_txn
@@ -376,22 +2231,595 @@ mod tests {
 
             let bob_commit_result = manager.commit_transaction(bob_txn);
             assert!(bob_commit_result.is_err());
-            assert!(matches!(
-                bob_commit_result.unwrap_err(),
-                MnemonicError::TransactionConflict(_)
-            ));
+            match bob_commit_result.unwrap_err() {
+                MnemonicError::TransactionConflict(conflicts) => {
+                    assert_eq!(conflicts.len(), 1);
+                    assert_eq!(conflicts[0].item, ConflictItem::Concept(concept_id));
+                    assert_eq!(conflicts[0].our_base_version, Some(1));
+                    assert_eq!(conflicts[0].committed_version, 2);
+                }
+                other => panic!("Expected a conflict, got {:?}", other),
+            }
         }
 
         // --- 6. DURABILITY PROOF ---
         // Let's check that ALICE's commit (version 2) is on disk.
         let cf_versions = backend.db.cf_handle("versions").unwrap();
-        let expected_key_v2 = format!("cv:{}:2", concept_id);
+        let expected_key_v2 = keys::version(keys::CONCEPT_VERSION, &concept_id, 2);
         let version_data_v2 = backend.db.get_cf(&cf_versions, expected_key_v2).unwrap();
         assert!(version_data_v2.is_some());
 
         // And check that the INITIAL commit (version 1) is ALSO on disk.
-        let expected_key_v1 = format!("cv:{}:1", concept_id);
+        let expected_key_v1 = keys::version(keys::CONCEPT_VERSION, &concept_id, 1);
         let version_data_v1 = backend.db.get_cf(&cf_versions, expected_key_v1).unwrap();
         assert!(version_data_v1.is_some());
     }
+
+    #[test]
+    fn test_list_active_tracks_begin_commit_and_abort() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+
+        let first = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let second = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+
+        let listed: Vec<TransactionId> = manager
+            .list_active()
+            .unwrap()
+            .iter()
+            .map(|summary| summary.id)
+            .collect();
+        assert_eq!(listed.len(), 2);
+        assert!(listed.contains(&first.id) && listed.contains(&second.id));
+
+        let summary = manager.get_active(&first.id).unwrap().unwrap();
+        assert_eq!(summary.isolation_level, IsolationLevel::Snapshot);
+        assert_eq!(summary.write_set_size, 0);
+
+        // Staged writes and recorded reads show in the summary.
+        let mut first = first;
+        let concept = Concept::new(json!({"name": "Staged"}));
+        let concept_id = concept.id;
+        manager.stage_concept_write(&mut first, concept);
+        manager.read_concept(&mut first, &concept_id).unwrap();
+        let summary = manager.get_active(&first.id).unwrap().unwrap();
+        assert_eq!((summary.write_set_size, summary.read_set_size), (1, 1));
+        // Reads are counted once per item, however often they're made.
+        manager.note_concept_read(&mut first, concept_id);
+        manager.stage_relationship_delete(&mut first, Uuid::new_v4());
+        let summary = manager.get_active(&first.id).unwrap().unwrap();
+        assert_eq!(summary.read_set_size, 1);
+        assert_eq!((summary.relationship_write_set_size, summary.pending_delete_count), (1, 1));
+
+        manager.commit_transaction(first.clone()).unwrap();
+        manager.abort_transaction(second.id).unwrap();
+
+        assert!(manager.list_active().unwrap().is_empty());
+        assert!(manager.get_active(&first.id).unwrap().is_none());
+    }
+
+    /// Commits a single new concept and returns its ID.
+    fn commit_new_concept(manager: &TransactionManager, data: serde_json::Value) -> ConceptId {
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let concept = Concept::new(data);
+        let concept_id = concept.id;
+        txn.write_set.insert(concept_id);
+        txn.pending_writes.insert(concept_id, concept);
+        manager.commit_transaction(txn).unwrap();
+        concept_id
+    }
+
+    /// Begins two racing transactions: one deleting `x`, one relating `x -> y`.
+    fn begin_delete_and_relate(
+        manager: &TransactionManager,
+        x: ConceptId,
+        y: ConceptId,
+    ) -> (Transaction, Transaction, RelationshipId) {
+        let mut delete_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        manager.stage_concept_delete(&mut delete_txn, x);
+
+        let mut relate_txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let rel = Relationship::new(x, "knows".to_string(), y);
+        let rel_id = rel.id;
+        manager.stage_relationship_write(&mut relate_txn, rel);
+
+        (delete_txn, relate_txn, rel_id)
+    }
+
+    #[test]
+    fn test_relate_after_concurrent_delete_fails() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let y = commit_new_concept(&manager, json!({"name": "Y"}));
+
+        let (delete_txn, relate_txn, _) = begin_delete_and_relate(&manager, x, y);
+
+        manager.commit_transaction(delete_txn).unwrap();
+        match manager.commit_transaction(relate_txn) {
+            Err(MnemonicError::TransactionConflict(conflicts)) => {
+                assert_eq!(conflicts[0].item, ConflictItem::Concept(x));
+            }
+            other => panic!("Expected a conflict on the deleted concept, got {:?}", other),
+        }
+        assert!(manager.version_store().get_all_active_relationships().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delete_after_concurrent_relate_fails() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let y = commit_new_concept(&manager, json!({"name": "Y"}));
+
+        let (delete_txn, relate_txn, rel_id) = begin_delete_and_relate(&manager, x, y);
+
+        manager.commit_transaction(relate_txn).unwrap();
+        match manager.commit_transaction(delete_txn) {
+            Err(MnemonicError::TransactionConflict(conflicts)) => {
+                assert_eq!(conflicts[0].item, ConflictItem::Relationship(rel_id));
+            }
+            other => panic!("Expected a conflict on the new relationship, got {:?}", other),
+        }
+        assert!(manager
+            .version_store()
+            .get_concept_version_at_timestamp(&x, Utc::now())
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_deleting_both_ends_of_a_concurrent_relationship_conflicts_once() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let y = commit_new_concept(&manager, json!({"name": "Y"}));
+
+        let (mut delete_txn, relate_txn, rel_id) = begin_delete_and_relate(&manager, x, y);
+        manager.stage_concept_delete(&mut delete_txn, y);
+
+        manager.commit_transaction(relate_txn).unwrap();
+        match manager.commit_transaction(delete_txn) {
+            Err(MnemonicError::TransactionConflict(conflicts)) => {
+                let items: Vec<_> = conflicts.iter().map(|conflict| conflict.item).collect();
+                assert_eq!(items, vec![ConflictItem::Relationship(rel_id)]);
+            }
+            other => panic!("Expected one conflict on the new relationship, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_relationship_to_missing_concept_is_dangling() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let missing = Uuid::new_v4();
+
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let rel = Relationship::new(x, "knows".to_string(), missing);
+        txn.relationship_write_set.insert(rel.id);
+        txn.pending_relationship_writes.insert(rel.id, rel);
+
+        assert!(matches!(
+            manager.commit_transaction(txn),
+            Err(MnemonicError::DanglingRelationship { concept_id, .. }) if concept_id == missing
+        ));
+    }
+
+    /// Stages `rel` as a write in a new transaction.
+    fn begin_relate(manager: &TransactionManager, rel: Relationship) -> Transaction {
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        manager.stage_relationship_write(&mut txn, rel);
+        txn
+    }
+
+    #[test]
+    fn test_a_committed_relationship_is_versioned_in_memory_and_on_disk() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let y = commit_new_concept(&manager, json!({"name": "Y"}));
+
+        let rel = Relationship::new(x, "knows".to_string(), y);
+        manager.commit_transaction(begin_relate(&manager, rel.clone())).unwrap();
+        let latest = manager.version_store().get_latest_relationship_version(&rel.id).unwrap().unwrap();
+        assert_eq!((latest.version, latest.source, latest.target), (1, x, y));
+
+        let retyped = Relationship { relationship_type: "likes".into(), ..rel.clone() };
+        manager.commit_transaction(begin_relate(&manager, retyped)).unwrap();
+        let on_disk = backend.load_relationship_versions_of(&rel.id).unwrap();
+        assert_eq!(on_disk.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(on_disk[1].relationship_type, RelationType::new("likes"));
+        assert_eq!(manager.version_store().get_latest_relationship_version(&rel.id).unwrap(), on_disk.last().cloned());
+    }
+
+    #[test]
+    fn test_an_aborted_relationship_leaves_no_trace() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(Arc::clone(&backend)).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let y = commit_new_concept(&manager, json!({"name": "Y"}));
+
+        let rel = Relationship::new(x, "knows".to_string(), y);
+        let txn = begin_relate(&manager, rel.clone());
+        manager.abort_transaction(txn.id).unwrap();
+
+        assert!(manager.version_store().get_latest_relationship_version(&rel.id).unwrap().is_none());
+        assert!(backend.load_relationship_versions_of(&rel.id).unwrap().is_empty());
+        assert!(matches!(manager.commit_transaction(txn), Err(MnemonicError::Transaction(_))));
+    }
+
+    #[test]
+    fn test_concurrent_writes_to_one_relationship_have_a_single_winner() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let x = commit_new_concept(&manager, json!({"name": "X"}));
+        let y = commit_new_concept(&manager, json!({"name": "Y"}));
+        let rel = Relationship::new(x, "knows".to_string(), y);
+        manager.commit_transaction(begin_relate(&manager, rel.clone())).unwrap();
+
+        let alice = begin_relate(&manager, Relationship { relationship_type: "likes".into(), ..rel.clone() });
+        let bob = begin_relate(&manager, Relationship { relationship_type: "trusts".into(), ..rel.clone() });
+        thread::sleep(Duration::from_millis(10));
+        manager.commit_transaction(alice).unwrap();
+        match manager.commit_transaction(bob) {
+            Err(MnemonicError::TransactionConflict(conflicts)) => {
+                assert_eq!(conflicts.len(), 1);
+                assert_eq!(conflicts[0].item, ConflictItem::Relationship(rel.id));
+                assert_eq!(conflicts[0].our_base_version, Some(1));
+                assert_eq!(conflicts[0].committed_version, 2);
+            }
+            other => panic!("Expected a conflict on the relationship, got {:?}", other),
+        }
+        let latest = manager.version_store().get_latest_relationship_version(&rel.id).unwrap().unwrap();
+        assert_eq!(latest.relationship_type, RelationType::new("likes"));
+    }
+
+    /// Reads a counter concept inside a transaction, recording the read.
+    fn read_counter(manager: &TransactionManager, txn: &mut Transaction, id: ConceptId) -> i64 {
+        let version = manager.read_concept(txn, &id).unwrap().unwrap();
+        match version.data {
+            ConceptData::Structured(s) => serde_json::from_str::<serde_json::Value>(&s).unwrap()
+                ["value"]
+                .as_i64()
+                .unwrap(),
+            ConceptData::Empty => panic!("Counter concept has no data"),
+        }
+    }
+
+    /// Stages a new value for a counter concept inside a transaction.
+    fn write_counter(txn: &mut Transaction, id: ConceptId, value: i64) {
+        let mut concept = Concept::new(json!({ "value": value }));
+        concept.id = id;
+        txn.write_set.insert(id);
+        txn.pending_writes.insert(id, concept);
+    }
+
+    /// Two transactions each check "x + y >= 100" and then withdraw 100 from a different
+    /// counter. Returns whether each commit succeeded.
+    fn run_write_skew(isolation_level: IsolationLevel) -> (bool, bool) {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let x = commit_new_concept(&manager, json!({"value": 50}));
+        let y = commit_new_concept(&manager, json!({"value": 50}));
+
+        let mut first = manager.begin_transaction(isolation_level).unwrap();
+        let mut second = manager.begin_transaction(isolation_level).unwrap();
+
+        for (txn, target) in [(&mut first, x), (&mut second, y)] {
+            let total = read_counter(&manager, txn, x) + read_counter(&manager, txn, y);
+            assert!(total >= 100);
+            let current = read_counter(&manager, txn, target);
+            write_counter(txn, target, current - 100);
+        }
+
+        let first_ok = manager.commit_transaction(first).is_ok();
+        let second_result = manager.commit_transaction(second);
+        if let Err(err) = &second_result {
+            assert!(matches!(err, MnemonicError::TransactionConflict(_)));
+        }
+        (first_ok, second_result.is_ok())
+    }
+
+    #[test]
+    fn test_snapshot_allows_write_skew() {
+        assert_eq!(run_write_skew(IsolationLevel::Snapshot), (true, true));
+    }
+
+    #[test]
+    fn test_serializable_rejects_write_skew() {
+        assert_eq!(run_write_skew(IsolationLevel::Serializable), (true, false));
+    }
+
+    /// Reads counter `id` in a fresh transaction, lets another transaction change it, and
+    /// returns what the first transaction sees before and after that concurrent commit.
+    fn observe_concurrent_commit(isolation_level: IsolationLevel) -> (i64, i64) {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let id = commit_new_concept(&manager, json!({"value": 1}));
+
+        let mut reader = manager.begin_transaction(isolation_level).unwrap();
+        let before = read_counter(&manager, &mut reader, id);
+
+        let mut writer = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        write_counter(&mut writer, id, 2);
+        manager.commit_transaction(writer).unwrap();
+
+        let after = read_counter(&manager, &mut reader, id);
+        manager.abort_transaction(reader.id).unwrap();
+        (before, after)
+    }
+
+    #[test]
+    fn test_read_committed_sees_concurrent_commit() {
+        assert_eq!(observe_concurrent_commit(IsolationLevel::ReadCommitted), (1, 2));
+    }
+
+    #[test]
+    fn test_snapshot_does_not_see_concurrent_commit() {
+        assert_eq!(observe_concurrent_commit(IsolationLevel::Snapshot), (1, 1));
+    }
+
+    #[test]
+    fn test_similar_names_ignore_case_and_separators() {
+        let registered: Vec<RelationType> =
+            ["works_for", "worked_with", "knows", "owns"].into_iter().map(RelationType::new).collect();
+        assert_eq!(similar_names("worksFor", registered.iter()), vec!["works_for"]);
+        assert_eq!(similar_names("nows", registered.iter()), vec!["knows", "owns"]);
+        assert!(similar_names("manages", registered.iter()).is_empty());
+        assert_eq!(edit_distance(&['a', 'b'], &[]), 2);
+    }
+
+    fn short_timeout_config() -> TransactionConfig {
+        TransactionConfig {
+            max_transaction_age: Some(Duration::from_millis(100)),
+            reap_interval: Duration::from_millis(20),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_expired_transaction_is_reaped_and_cannot_commit() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::with_config(backend, short_timeout_config()).unwrap();
+
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let concept = Concept::new(json!({"value": "too late"}));
+        txn.write_set.insert(concept.id);
+        txn.pending_writes.insert(concept.id, concept);
+
+        // Outlive the timeout and give the reaper a chance to run.
+        thread::sleep(Duration::from_millis(250));
+
+        assert!(manager.active_transactions.read().unwrap().is_empty());
+        assert_eq!(manager.metrics().expired.get(), 1);
+
+        let result = manager.commit_transaction(txn.clone());
+        assert!(matches!(
+            result,
+            Err(MnemonicError::TransactionExpired(id)) if id == txn.id
+        ));
+    }
+
+    #[test]
+    fn test_commit_within_timeout_succeeds() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::with_config(backend, short_timeout_config()).unwrap();
+
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let concept = Concept::new(json!({"value": "just in time"}));
+        let concept_id = concept.id;
+        txn.write_set.insert(concept_id);
+        txn.pending_writes.insert(concept_id, concept);
+
+        manager.commit_transaction(txn).unwrap();
+
+        assert!(manager
+            .version_store()
+            .get_concept_version_at_timestamp(&concept_id, Utc::now())
+            .unwrap()
+            .is_some());
+        assert_eq!(manager.metrics().committed.get(), 1);
+        assert_eq!(manager.metrics().expired.get(), 0);
+    }
+
+    const STRESS_THREADS: usize = 16;
+
+    /// Every thread updates the same counter concept, all starting before any of them commits.
+    /// Returns how many commits succeeded and how many were rejected as conflicts.
+    fn race_updates(manager: &Arc<TransactionManager>, id: ConceptId) -> (usize, usize) {
+        let barrier = Arc::new(std::sync::Barrier::new(STRESS_THREADS));
+        let handles: Vec<_> = (0..STRESS_THREADS)
+            .map(|i| {
+                let manager = Arc::clone(manager);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+                    write_counter(&mut txn, id, i as i64);
+                    // Nobody commits until everybody has taken their snapshot.
+                    barrier.wait();
+                    manager.commit_transaction(txn)
+                })
+            })
+            .collect();
+
+        let mut committed = 0;
+        let mut conflicted = 0;
+        for handle in handles {
+            match handle.join().unwrap() {
+                Ok(()) => committed += 1,
+                Err(MnemonicError::TransactionConflict(_)) => conflicted += 1,
+                Err(other) => panic!("Unexpected commit error: {}", other),
+            }
+        }
+        (committed, conflicted)
+    }
+
+    #[test]
+    fn test_concurrent_commits_to_one_concept_have_a_single_winner() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = Arc::new(TransactionManager::new(backend).unwrap());
+        let id = commit_new_concept(&manager, json!({"value": 0}));
+
+        for round in 1..=5u64 {
+            let (committed, conflicted) = race_updates(&manager, id);
+            assert_eq!(committed, 1, "round {} should have exactly one winner", round);
+            assert_eq!(conflicted, STRESS_THREADS - 1);
+
+            // One new version per round, numbered without gaps or repeats.
+            let versions = manager.version_store.concept_version_numbers().unwrap()[&id].clone();
+            assert_eq!(versions, (1..=round + 1).collect::<Vec<_>>());
+        }
+        assert!(manager.check_consistency(false).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_delete_where_reports_a_conflicted_batch_and_keeps_the_others() {
+        let dir = tempdir().unwrap();
+        let backend = Arc::new(RocksBackend::new(dir.path()).unwrap());
+        let manager = TransactionManager::new(backend).unwrap();
+        let mut imported: Vec<ConceptId> =
+            (0..5).map(|n| commit_new_concept(&manager, json!({"n": n, "source": "import"}))).collect();
+        // Batches go in ID order.
+        imported.sort();
+        let kept = commit_new_concept(&manager, json!({"source": "manual"}));
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        let rel = Relationship::new(kept, "cites".to_string(), imported[0]);
+        txn.relationship_write_set.insert(rel.id);
+        txn.pending_relationship_writes.insert(rel.id, rel.clone());
+        manager.commit_transaction(txn).unwrap();
+
+        let filter = Filter { equals: HashMap::from([("source".to_string(), "import".to_string())]), ..Filter::default() };
+        let mut options = BulkDeleteOptions { batch_size: 2, cascade: true, ..Default::default() };
+        let dry_run = manager.delete_where(&filter, &options, &TransactionMetadata::default()).unwrap();
+        assert_eq!(dry_run.matched, imported);
+        assert_eq!(dry_run.concepts_deleted, 0);
+        assert_eq!(manager.version_store.count_active_concepts().unwrap(), 6);
+
+        // The second of three batches loses to a concurrent writer.
+        options.dry_run = false;
+        manager.set_conflict_in_commits(2);
+        let report = manager.delete_where(&filter, &options, &TransactionMetadata::default()).unwrap();
+        assert_eq!((report.batches_committed, report.batches_failed), (2, 1));
+        assert_eq!((report.concepts_deleted, report.relationships_deleted), (3, 1));
+        assert_eq!(report.failures[0].concept_ids, imported[2..4]);
+        let active = |id: &ConceptId| manager.version_store.get_latest_active_concept_version(id).unwrap().is_some();
+        assert!(imported.iter().enumerate().all(|(i, id)| active(id) == (2..4).contains(&i)));
+        assert!(active(&kept));
+        assert!(manager.version_store.get_latest_active_relationship_version(&rel.id).unwrap().is_none());
+
+        // Running it again finishes the job.
+        let retry = manager.delete_where(&filter, &options, &TransactionMetadata::default()).unwrap();
+        assert_eq!((retry.matched.len(), retry.concepts_deleted, retry.batches_failed), (2, 2, 0));
+    }
+
+    #[test]
+    fn test_strict_writes_must_insert_fresh_ids_and_update_live_concepts() {
+        let dir = tempdir().unwrap();
+        let manager = TransactionManager::new(Arc::new(RocksBackend::new(dir.path()).unwrap())).unwrap();
+        let insert = |id: ConceptId| Concept { id, ..Concept::new(json!({"n": 1})) };
+        let update = |id: ConceptId| Concept { metadata: ConceptMetadata::default(), ..insert(id) };
+        let commit = |mode: WriteMode, concept: Concept| {
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            txn.write_mode = mode;
+            txn.write_set.insert(concept.id);
+            txn.pending_writes.insert(concept.id, concept);
+            manager.commit_transaction_detailed(txn)
+        };
+        let live = Uuid::new_v4();
+        commit(WriteMode::Strict, insert(live)).unwrap();
+        let deleted = Uuid::new_v4();
+        commit(WriteMode::Strict, insert(deleted)).unwrap();
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        txn.write_set.insert(deleted);
+        txn.pending_concept_deletes.insert(deleted);
+        manager.commit_transaction(txn).unwrap();
+
+        // An insert of a fresh ID and an update of a live concept pass either way.
+        for mode in [WriteMode::Lenient, WriteMode::Strict] {
+            let fresh = Uuid::new_v4();
+            let report = commit(mode, insert(fresh)).unwrap();
+            assert_eq!(report.concept_writes, HashMap::from([(fresh, WriteKind::Insert)]));
+            let report = commit(mode, update(live)).unwrap();
+            assert_eq!(report.concept_writes, HashMap::from([(live, WriteKind::Update)]));
+        }
+
+        // The other two are applied when lenient, and rejected when strict.
+        let missing = Uuid::new_v4();
+        let rejected = |concept: Concept| match commit(WriteMode::Strict, concept) {
+            Err(MnemonicError::RejectedWrites(rejected)) => rejected,
+            other => panic!("Expected the write to be rejected, got {:?}", other),
+        };
+        let reasons = |concept: Concept| {
+            let rejected = rejected(concept);
+            assert_eq!(rejected.len(), 1);
+            (rejected[0].intended, rejected[0].reason.clone())
+        };
+        assert_eq!(reasons(insert(live)), (WriteKind::Insert, "it already exists".to_string()));
+        assert_eq!(reasons(insert(deleted)), (WriteKind::Insert, "a deleted concept had this ID".to_string()));
+        assert_eq!(reasons(update(missing)), (WriteKind::Update, "it doesn't exist".to_string()));
+        assert_eq!(reasons(update(deleted)), (WriteKind::Update, "it was deleted".to_string()));
+        assert_eq!(manager.version_store().latest_concept_version_number(&missing).unwrap(), 0);
+        assert_eq!(manager.version_store().latest_concept_version_number(&deleted).unwrap(), 2);
+
+        let report = commit(WriteMode::Lenient, insert(live)).unwrap();
+        assert_eq!(report.concept_writes, HashMap::from([(live, WriteKind::Update)]));
+        let report = commit(WriteMode::Lenient, update(missing)).unwrap();
+        assert_eq!(report.concept_writes, HashMap::from([(missing, WriteKind::Insert)]));
+        let report = commit(WriteMode::Lenient, update(deleted)).unwrap();
+        assert_eq!(report.concept_writes, HashMap::from([(deleted, WriteKind::Insert)]));
+        assert_eq!(manager.version_store().latest_concept_version_number(&deleted).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_a_receipt_gives_each_version_written_and_sequences_survive_a_reopen() {
+        let dir = tempdir().unwrap();
+        let kept = Uuid::new_v4();
+        let gone = Uuid::new_v4();
+        {
+            let manager = TransactionManager::new(Arc::new(RocksBackend::new(dir.path()).unwrap())).unwrap();
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            for id in [kept, gone] {
+                txn.write_set.insert(id);
+                txn.pending_writes.insert(id, Concept { id, ..Concept::new(json!({"n": 1})) });
+            }
+            let first = manager.commit_transaction_detailed(txn).unwrap();
+            assert_eq!(first.sequence, 1);
+            assert_eq!(first.deletes, 0);
+            let mut versions = first.concept_versions.clone();
+            versions.sort();
+            let mut expected = vec![(kept, 1), (gone, 1)];
+            expected.sort();
+            assert_eq!(versions, expected);
+
+            let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+            txn.write_set.insert(gone);
+            txn.pending_concept_deletes.insert(gone);
+            let second = manager.commit_transaction_detailed(txn).unwrap();
+            assert_eq!((second.sequence, second.deletes), (2, 1));
+            assert_eq!(second.concept_versions, vec![(gone, 2)]);
+            assert!(second.committed_at >= first.committed_at);
+        }
+
+        let manager = TransactionManager::new(Arc::new(RocksBackend::new(dir.path()).unwrap())).unwrap();
+        let mut txn = manager.begin_transaction(IsolationLevel::Snapshot).unwrap();
+        txn.write_set.insert(kept);
+        txn.pending_writes.insert(kept, Concept { id: kept, ..Concept::new(json!({"n": 2})) });
+        let third = manager.commit_transaction_detailed(txn).unwrap();
+        assert_eq!(third.sequence, 3);
+        assert_eq!(third.concept_versions, vec![(kept, 2)]);
+    }
 }
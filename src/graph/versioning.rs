@@ -1,20 +1,220 @@
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
-use std::sync::RwLock; // Read-Write Lock: Allows many readers or one writer at a time.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::{Bound, ControlFlow};
+use std::sync::{Arc, Mutex, RwLock}; // Read-Write Lock: Allows many readers or one writer at a time.
 
 use crate::error::{MnemonicError, Result};
-use crate::types::concept::{ConceptId, ConceptVersion};
-use crate::types::relationship::{RelationshipId, RelationshipVersion};
+use crate::graph::budget::ScanMeter;
+use crate::graph::audit::{self, ActivityBucket, AuditCursor, AuditPage, AuditQuery, AuditRecord, BucketWidth, ItemKind};
+use crate::graph::events::CommitEvent;
+use crate::graph::indices::{Adjacency, ExpiryIndex, IndexRebuild, LabelIndex, TagIndex, VersionIndex};
+use crate::graph::observers::{Indexed, ObserverFailures, Observers, VersionObserver};
+use crate::types::concept::{Concept, ConceptId, ConceptVersion, TransactionId};
+use crate::types::query::Direction;
+use crate::types::relationship::{
+    RelationType, Relationship, RelationshipId, RelationshipMetadata, RelationshipVersion,
+};
+use crate::types::system;
+use crate::types::transaction::TransactionMetadata;
+use crate::utils::locks::LockHealth;
+use uuid::Uuid;
+
+/// How many items a streaming scan visits per read-lock window.
+pub const SCAN_CHUNK_SIZE: usize = 1024;
+
+/// A version number and the moment that version was committed.
+pub type VersionStamp = (u64, DateTime<Utc>);
+
+/// An item and the version below which compaction may drop its history.
+pub type HistoryFloor = (Uuid, u64);
+
+/// A page of active concepts, each with when it was first created, and the last concept a
+/// budgeted scan looked at.
+pub type ActiveConceptsPage = (Vec<(ConceptVersion, DateTime<Utc>)>, Option<ConceptId>);
+
+/// Live relationships held under their read locks, for walks that look up many neighbours.
+/// Commits wait while a view is held, so keep it short.
+pub struct NeighborView<'a> {
+    relationship_versions: &'a BTreeMap<RelationshipId, Vec<RelationshipVersion>>,
+    adjacency: &'a Adjacency,
+    // Concepts past their expiry but not yet tombstoned, whose relationships are skipped.
+    expired: HashSet<ConceptId>,
+}
+
+impl NeighborView<'_> {
+    /// The concepts one active relationship away in the given direction, going only over
+    /// relationships of the given types when some are named. A concept appears once per edge.
+    pub fn neighbors(
+        &self,
+        concept_id: &ConceptId,
+        direction: Direction,
+        types: Option<&HashSet<RelationType>>,
+    ) -> Vec<ConceptId> {
+        self.edges(concept_id, direction, types)
+            .into_iter()
+            .map(|(_, neighbor)| neighbor)
+            .collect()
+    }
+
+    /// Like `neighbors`, but pairs each neighbour with the relationship leading to it.
+    pub fn edges(
+        &self,
+        concept_id: &ConceptId,
+        direction: Direction,
+        types: Option<&HashSet<RelationType>>,
+    ) -> Vec<(RelationshipId, ConceptId)> {
+        let mut edges = Vec::new();
+        if let Some(types) = types {
+            // Named types go straight to their (concept, type) entries instead of filtering
+            // every edge of the concept.
+            for relationship_type in types {
+                let ids = match direction {
+                    Direction::Outgoing => self.adjacency.outgoing_of_type(concept_id, relationship_type),
+                    Direction::Incoming => self.adjacency.incoming_of_type(concept_id, relationship_type),
+                    Direction::Both => self.adjacency.recorded_of_type(concept_id, relationship_type),
+                };
+                self.follow(concept_id, &ids, None, &mut edges);
+            }
+            return edges;
+        }
+        match direction {
+            Direction::Outgoing => self.follow(concept_id, &self.adjacency.outgoing(concept_id), types, &mut edges),
+            Direction::Incoming => self.follow(concept_id, &self.adjacency.incoming(concept_id), types, &mut edges),
+            // Leaving and entering would both list an undirected relationship.
+            Direction::Both => {
+                self.follow(concept_id, &self.adjacency.recorded_as_source(concept_id), types, &mut edges);
+                self.follow(concept_id, &self.adjacency.recorded_as_target(concept_id), types, &mut edges);
+            }
+        }
+        edges
+    }
+
+    /// The latest version of a relationship, deleted or not.
+    pub fn relationship(&self, relationship_id: &RelationshipId) -> Option<&RelationshipVersion> {
+        self.relationship_versions.get(relationship_id).and_then(|chain| chain.last())
+    }
+
+    fn follow(
+        &self,
+        concept_id: &ConceptId,
+        ids: &[RelationshipId],
+        types: Option<&HashSet<RelationType>>,
+        out: &mut Vec<(RelationshipId, ConceptId)>,
+    ) {
+        out.extend(
+            ids.iter()
+                .filter_map(|id| self.relationship_versions.get(id).and_then(|chain| chain.last()))
+                .filter(|version| types.is_none_or(|types| types.contains(&version.relationship_type)))
+                .filter(|version| !touches(version, &self.expired))
+                .map(|version| (version.relationship_id, version.other_end(concept_id))),
+        );
+    }
+}
+
+/// Whether a relationship starts or ends at one of `concepts`.
+fn touches(version: &RelationshipVersion, concepts: &HashSet<ConceptId>) -> bool {
+    !concepts.is_empty() && (concepts.contains(&version.source) || concepts.contains(&version.target))
+}
+
+/// How much history the store is holding in memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionStoreStats {
+    pub concepts: u64,
+    pub concept_versions: u64,
+    pub relationships: u64,
+    pub relationship_versions: u64,
+    pub transactions_with_metadata: u64,
+}
+
+/// Where a VersionStore that is still warming up reads the history it doesn't hold yet.
+pub trait ColdHistory: std::fmt::Debug + Send + Sync {
+    /// Every version of a concept, in any order, and its persisted latest version number.
+    fn concept_history(&self, concept_id: &ConceptId) -> Result<(Vec<ConceptVersion>, Option<u64>)>;
+
+    /// Every version of a relationship, in any order, and its persisted latest version number.
+    fn relationship_history(
+        &self,
+        relationship_id: &RelationshipId,
+    ) -> Result<(Vec<RelationshipVersion>, Option<u64>)>;
+
+    /// Every relationship version, in any order. Relationships aren't indexed by endpoint on
+    /// disk, so adjacency can only be had whole.
+    fn all_relationship_versions(&self) -> Result<Vec<RelationshipVersion>>;
+
+    /// Every relationship's persisted latest version number.
+    fn relationship_heads(&self) -> Result<Vec<(RelationshipId, u64)>>;
+}
 
 /// VersionStore manages all versions of concepts and relationships for MVCC.
-#[derive(Debug, Default)] // Default trait lets use create a new one easily.
+#[derive(Debug)]
 pub struct VersionStore {
     // A map form a Concept's ID to a list of all its historical versions.
-    // Wrapped in a RwLock to make it thread-safe.
-    concept_versions: RwLock<HashMap<ConceptId, Vec<ConceptVersion>>>,
+    // Wrapped in a RwLock to make it thread-safe. Ordered by ID, like the maps below, so
+    // every scan and page visits items in the same order, before and after a restart.
+    concept_versions: RwLock<BTreeMap<ConceptId, Vec<ConceptVersion>>>,
 
     // Same for relationships.
-    relationship_versions: RwLock<HashMap<RelationshipId, Vec<RelationshipVersion>>>,
+    relationship_versions: RwLock<BTreeMap<RelationshipId, Vec<RelationshipVersion>>>,
+
+    // The authoritative latest version number of every item. New versions must follow it.
+    concept_heads: RwLock<BTreeMap<ConceptId, u64>>,
+    relationship_heads: RwLock<BTreeMap<RelationshipId, u64>>,
+
+    // Which live relationships touch each concept. Always taken after `relationship_versions`.
+    adjacency: Indexed<Adjacency>,
+
+    // Which live concepts carry each label. Always taken after `concept_versions`.
+    labels: Indexed<LabelIndex>,
+
+    // Which live concepts carry each tag. Always taken after `concept_versions`.
+    tags: Indexed<TagIndex>,
+
+    // When each live concept with an expiry expires. Never held while taking another lock.
+    expirations: Indexed<ExpiryIndex>,
+
+    // Told about every version after the indexes above. Always taken after the version chains.
+    observers: Observers,
+
+    // Who committed each transaction and why, for transactions that said so.
+    transaction_metadata: RwLock<HashMap<TransactionId, TransactionMetadata>>,
+
+    // Every version held above, in commit order, for the audit log. Always taken after
+    // the version chains when both are written, and on its own when read.
+    change_log: RwLock<BTreeSet<AuditCursor>>,
+
+    // Where items not loaded yet are read from while the store warms up; `None` once the
+    // whole history is in memory. Never held while taking another lock.
+    cold: RwLock<Option<Arc<dyn ColdHistory>>>,
+
+    // Whether every relationship has been loaded while warming, so adjacency is complete.
+    // Held while they load, which happens once; taken before any other lock.
+    relationships_loaded: Mutex<bool>,
+
+    // Every lock above is taken through this, so a panic elsewhere can't wedge the store.
+    lock_health: Arc<LockHealth>,
+}
+
+impl Default for VersionStore {
+    fn default() -> Self {
+        let lock_health = Arc::new(LockHealth::new());
+        Self {
+            concept_versions: RwLock::default(),
+            relationship_versions: RwLock::default(),
+            concept_heads: RwLock::default(),
+            relationship_heads: RwLock::default(),
+            adjacency: Indexed::new("adjacency", &lock_health),
+            labels: Indexed::new("labels", &lock_health),
+            tags: Indexed::new("tags", &lock_health),
+            expirations: Indexed::new("expirations", &lock_health),
+            observers: Observers::new(&lock_health),
+            transaction_metadata: RwLock::default(),
+            change_log: RwLock::default(),
+            cold: RwLock::default(),
+            relationships_loaded: Mutex::default(),
+            lock_health,
+        }
+    }
 }
 
 impl VersionStore {
@@ -22,6 +222,128 @@ impl VersionStore {
         Self::default()
     }
 
+    /// Tells `observer` about every version held so far, then about every version added or
+    /// loaded from now on, each once. Nothing is added while the held versions are replayed.
+    pub fn register_observer(&self, observer: Box<dyn VersionObserver>) {
+        let _concept_heads = self.lock_health.write(&self.concept_heads);
+        let _relationship_heads = self.lock_health.write(&self.relationship_heads);
+        for chain in self.lock_health.read(&self.concept_versions).values() {
+            for version in chain {
+                self.observers.tell_one(observer.as_ref(), |o| o.on_concept_version(version));
+            }
+        }
+        for chain in self.lock_health.read(&self.relationship_versions).values() {
+            for version in chain {
+                self.observers.tell_one(observer.as_ref(), |o| o.on_relationship_version(version));
+            }
+        }
+        self.observers.register(observer);
+    }
+
+    /// Every registered observer that has failed, and how it last did.
+    pub fn observer_failures(&self) -> Vec<ObserverFailures> {
+        self.observers.failures()
+    }
+
+    fn concept_indexes(&self) -> [&dyn VersionObserver; 3] {
+        [&self.labels, &self.tags, &self.expirations]
+    }
+
+    fn relationship_indexes(&self) -> [&dyn VersionObserver; 1] {
+        [&self.adjacency]
+    }
+
+    /// Returns the counter of poisoned locks this store has recovered.
+    pub fn lock_health(&self) -> Arc<LockHealth> {
+        Arc::clone(&self.lock_health)
+    }
+
+    /// Panics while holding the concept history's write lock, poisoning it.
+    #[cfg(all(test, feature = "server"))]
+    pub(crate) fn poison_concept_versions_for_test(&self) {
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = self.concept_versions.write();
+            panic!("simulated panic while holding the concept versions lock");
+        }));
+    }
+
+    /// Reads items not in memory yet from `cold` when they are looked up, until
+    /// `finish_warming`. The first lookup of a concept's relationships loads every
+    /// relationship. Meanwhile whole-graph listings and label lookups see only what has been
+    /// loaded.
+    pub fn start_warming(&self, cold: Arc<dyn ColdHistory>) {
+        *self.lock_health.lock(&self.relationships_loaded) = false;
+        *self.lock_health.write(&self.cold) = Some(cold);
+    }
+
+    /// Stops reading from cold storage, once everything has been loaded.
+    pub fn finish_warming(&self) {
+        *self.lock_health.write(&self.cold) = None;
+    }
+
+    /// Whether the whole history is in memory, i.e. the store has finished warming up.
+    pub fn is_warm(&self) -> bool {
+        self.lock_health.read(&self.cold).is_none()
+    }
+
+    /// While warming, loads a concept from cold storage unless it is already in memory.
+    fn warm_concept(&self, concept_id: &ConceptId) -> Result<()> {
+        let Some(cold) = self.lock_health.read(&self.cold).clone() else {
+            return Ok(());
+        };
+        if self.lock_health.read(&self.concept_versions).contains_key(concept_id) {
+            return Ok(());
+        }
+        let (chain, head) = cold.concept_history(concept_id)?;
+        self.load_missing_concept_chains([(*concept_id, chain)])?;
+        if let Some(head) = head {
+            self.restore_concept_head(*concept_id, head)?;
+        }
+        Ok(())
+    }
+
+    /// While warming, loads a relationship from cold storage unless it is already in memory.
+    fn warm_relationship(&self, relationship_id: &RelationshipId) -> Result<()> {
+        match self.lock_health.read(&self.cold).clone() {
+            Some(cold) => self.warm_relationship_from(cold.as_ref(), relationship_id),
+            None => Ok(()),
+        }
+    }
+
+    /// While warming, loads every relationship not in memory yet, once, so the adjacency
+    /// of every concept is complete. Callers that get here at the same time wait for it.
+    pub fn warm_adjacency(&self) -> Result<()> {
+        let Some(cold) = self.lock_health.read(&self.cold).clone() else {
+            return Ok(());
+        };
+        let mut loaded = self.lock_health.lock(&self.relationships_loaded);
+        if *loaded {
+            return Ok(());
+        }
+        let mut chains: HashMap<RelationshipId, Vec<RelationshipVersion>> = HashMap::new();
+        for version in cold.all_relationship_versions()? {
+            chains.entry(version.relationship_id).or_default().push(version);
+        }
+        self.load_missing_relationship_chains(chains)?;
+        for (relationship_id, head) in cold.relationship_heads()? {
+            self.restore_relationship_head(relationship_id, head)?;
+        }
+        *loaded = true;
+        Ok(())
+    }
+
+    fn warm_relationship_from(&self, cold: &dyn ColdHistory, relationship_id: &RelationshipId) -> Result<()> {
+        if self.lock_health.read(&self.relationship_versions).contains_key(relationship_id) {
+            return Ok(());
+        }
+        let (chain, head) = cold.relationship_history(relationship_id)?;
+        self.load_missing_relationship_chains([(*relationship_id, chain)])?;
+        if let Some(head) = head {
+            self.restore_relationship_head(*relationship_id, head)?;
+        }
+        Ok(())
+    }
+
     /// The core of "Time Travel". Finds the correct version of the concept
     /// that was "live" at a specific timestamp.
     pub fn get_concept_version_at_timestamp(
@@ -29,11 +351,28 @@ impl VersionStore {
         concept_id: &ConceptId,
         timestamp: DateTime<Utc>,
     ) -> Result<Option<ConceptVersion>> {
+        self.concept_version_at(concept_id, timestamp, ConceptVersion::is_active_at)
+    }
+
+    /// Like `get_concept_version_at_timestamp`, but also finds a version whose expiry had
+    /// passed by then, as long as it hadn't been tombstoned.
+    pub fn get_existing_concept_version_at_timestamp(
+        &self,
+        concept_id: &ConceptId,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<ConceptVersion>> {
+        self.concept_version_at(concept_id, timestamp, ConceptVersion::exists_at)
+    }
+
+    fn concept_version_at(
+        &self,
+        concept_id: &ConceptId,
+        timestamp: DateTime<Utc>,
+        live: fn(&ConceptVersion, DateTime<Utc>) -> bool,
+    ) -> Result<Option<ConceptVersion>> {
+        self.warm_concept(concept_id)?;
         // We need to `read` the data, which requires a lock.
-        let versions_map = self
-            .concept_versions
-            .read()
-            .map_err(|e| MnemonicError::Transaction(format!("Read lock failed: {}", e)))?;
+        let versions_map = self.lock_health.read(&self.concept_versions);
 
         // Find the list of versions for this specific concept ID.
         if let Some(versions_vec) = versions_map.get(concept_id) {
@@ -44,7 +383,7 @@ impl VersionStore {
                     // NOW, check if `this specific version` was active at that time.
 
                     // Use our handy helper methhod to see if this version was active at the time.
-                    if version.is_active_at(timestamp) {
+                    if live(version, timestamp) {
                         return Ok(Some(version.clone()));
                     } else {
                         // We found the correct historical record, but it was inactive (deleted).
@@ -64,10 +403,8 @@ impl VersionStore {
         relationship_id: &RelationshipId,
         timestamp: DateTime<Utc>,
     ) -> Result<Option<RelationshipVersion>> {
-        let versions_map = self
-            .relationship_versions
-            .read()
-            .map_err(|e| MnemonicError::Transaction(format!("Read lock failed: {}", e)))?;
+        self.warm_relationship(relationship_id)?;
+        let versions_map = self.lock_health.read(&self.relationship_versions);
 
         if let Some(versions_vec) = versions_map.get(relationship_id) {
             // Search backwards from the newest version to the oldest.
@@ -86,48 +423,654 @@ impl VersionStore {
         Ok(None)
     }
 
-    /// Adds a new version to a concept's history chain
+    /// Returns the newest version of a concept, whether or not it is still active.
+    pub fn get_latest_concept_version(
+        &self,
+        concept_id: &ConceptId,
+    ) -> Result<Option<ConceptVersion>> {
+        self.warm_concept(concept_id)?;
+        let versions_map = self.lock_health.read(&self.concept_versions);
+
+        Ok(versions_map
+            .get(concept_id)
+            .and_then(|versions_vec| versions_vec.last().cloned()))
+    }
+
+    /// When a concept's first version was written, if it has any.
+    pub fn concept_created_at(&self, concept_id: &ConceptId) -> Result<Option<DateTime<Utc>>> {
+        self.warm_concept(concept_id)?;
+        let versions_map = self.lock_health.read(&self.concept_versions);
+        Ok(versions_map
+            .get(concept_id)
+            .and_then(|versions_vec| versions_vec.first())
+            .map(|first| first.created_at))
+    }
+
+    /// Returns the newest version of a relationship, including tombstones.
+    pub fn get_latest_relationship_version(
+        &self,
+        relationship_id: &RelationshipId,
+    ) -> Result<Option<RelationshipVersion>> {
+        self.warm_relationship(relationship_id)?;
+        let versions_map = self.lock_health.read(&self.relationship_versions);
+
+        Ok(versions_map
+            .get(relationship_id)
+            .and_then(|versions_vec| versions_vec.last().cloned()))
+    }
+
+    /// Fast path for "what is this concept right now?": the newest version, if it is active.
+    /// Unlike the timestamped lookup, this never has to walk the history chain.
+    pub fn get_latest_active_concept_version(
+        &self,
+        concept_id: &ConceptId,
+    ) -> Result<Option<ConceptVersion>> {
+        let now = Utc::now();
+        Ok(self
+            .get_latest_concept_version(concept_id)?
+            .filter(|version| version.is_active_at(now)))
+    }
+
+    /// `get_latest_active_concept_version` for many concepts under a single read lock.
+    /// The result lines up with `concept_ids`: unknown or deleted concepts are `None` in place.
+    pub fn get_latest_active_concepts(
+        &self,
+        concept_ids: &[ConceptId],
+    ) -> Result<Vec<Option<ConceptVersion>>> {
+        for concept_id in concept_ids {
+            self.warm_concept(concept_id)?;
+        }
+        let now = Utc::now();
+        let versions_map = self.lock_health.read(&self.concept_versions);
+
+        Ok(concept_ids
+            .iter()
+            .map(|concept_id| {
+                versions_map
+                    .get(concept_id)
+                    .and_then(|versions_vec| versions_vec.last())
+                    .filter(|version| version.is_active_at(now))
+                    .cloned()
+            })
+            .collect())
+    }
+
+    /// Fast path for the current state of a relationship: the newest version, if it is active.
+    pub fn get_latest_active_relationship_version(
+        &self,
+        relationship_id: &RelationshipId,
+    ) -> Result<Option<RelationshipVersion>> {
+        let now = Utc::now();
+        let expired = self.expired_concepts(now);
+        Ok(self
+            .get_latest_relationship_version(relationship_id)?
+            .filter(|version| version.is_active_at(now) && !touches(version, &expired)))
+    }
+
+    /// The newest version number ever committed for a concept, or 0 if it has none.
+    pub fn latest_concept_version_number(&self, concept_id: &ConceptId) -> Result<u64> {
+        self.warm_concept(concept_id)?;
+        let heads = self.lock_health.read(&self.concept_heads);
+        Ok(heads.get(concept_id).copied().unwrap_or(0))
+    }
+
+    /// The newest version number ever committed for a relationship, or 0 if it has none.
+    pub fn latest_relationship_version_number(&self, relationship_id: &RelationshipId) -> Result<u64> {
+        self.warm_relationship(relationship_id)?;
+        let heads = self.lock_health.read(&self.relationship_heads);
+        Ok(heads.get(relationship_id).copied().unwrap_or(0))
+    }
+
+    /// Adds a new version to a concept's history chain.
+    /// The version number must be exactly one past the concept's latest version.
     pub fn add_concept_version(&self, version: ConceptVersion) -> Result<()> {
+        self.warm_concept(&version.concept_id)?;
+        // Always take the head lock before the versions lock.
+        let mut heads = self.lock_health.write(&self.concept_heads);
+        let latest = heads.get(&version.concept_id).copied().unwrap_or(0);
+        if version.version != latest + 1 {
+            return Err(MnemonicError::VersionOutOfSequence {
+                id: version.concept_id,
+                version: version.version,
+                latest,
+            });
+        }
+
         // We need to `write` to the data, which requires a write lock.
-        let mut versions_map = self
-            .concept_versions
-            .write()
-            .map_err(|e| MnemonicError::Transaction(format!("Write lock failed: {}", e)))?;
+        let mut versions_map = self.lock_health.write(&self.concept_versions);
 
+        heads.insert(version.concept_id, version.version);
+        self.lock_health
+            .write(&self.change_log)
+            .insert(AuditRecord::from_concept_version(&version).cursor());
         // Find the vector for this concept ID, or create a new empty one if it's the first version.
-        versions_map
-            .entry(version.concept_id)
-            .or_default()
-            .push(version);
+        let chain = versions_map.entry(version.concept_id).or_default();
+        chain.push(version);
+        if let Some(version) = chain.last() {
+            self.observers.tell(&self.concept_indexes(), |o| o.on_concept_version(version));
+        }
         Ok(())
     }
 
     /// Adds a new version to a relationship's history chain.
+    /// The version number must be exactly one past the relationship's latest version.
     pub fn add_relationship_version(&self, version: RelationshipVersion) -> Result<()> {
-        let mut versions_map = self
-            .relationship_versions
-            .write()
-            .map_err(|e| MnemonicError::Transaction(format!("Write lock failed: {}", e)))?;
+        self.warm_relationship(&version.relationship_id)?;
+        let mut heads = self.lock_health.write(&self.relationship_heads);
+        let latest = heads.get(&version.relationship_id).copied().unwrap_or(0);
+        if version.version != latest + 1 {
+            return Err(MnemonicError::VersionOutOfSequence {
+                id: version.relationship_id,
+                version: version.version,
+                latest,
+            });
+        }
 
+        let mut versions_map = self.lock_health.write(&self.relationship_versions);
+
+        heads.insert(version.relationship_id, version.version);
+        self.lock_health
+            .write(&self.change_log)
+            .insert(AuditRecord::from_relationship_version(&version).cursor());
         // Find the vector for this relationship ID, or create a new empty one.
-        versions_map
-            .entry(version.relationship_id)
-            .or_default()
-            .push(version);
+        let chain = versions_map.entry(version.relationship_id).or_default();
+        chain.push(version);
+        if let Some(version) = chain.last() {
+            self.observers.tell(&self.relationship_indexes(), |o| o.on_relationship_version(version));
+        }
+
+        Ok(())
+    }
+
+    /// Replaces a concept's whole history chain, e.g. when loading it from disk.
+    /// The latest version number follows the new chain; an empty chain forgets the concept.
+    pub fn replace_concept_versions(
+        &self,
+        concept_id: ConceptId,
+        versions: Vec<ConceptVersion>,
+    ) -> Result<()> {
+        self.replace_concept_chains([(concept_id, versions)])
+    }
+
+    /// `replace_concept_versions` for many concepts, taking each lock once instead of once
+    /// per concept. The chains are sorted before any lock is taken.
+    pub fn replace_concept_chains(
+        &self,
+        chains: impl IntoIterator<Item = (ConceptId, Vec<ConceptVersion>)>,
+    ) -> Result<()> {
+        self.put_concept_chains(chains, false)
+    }
+
+    /// Adds the chains of concepts not in memory yet and leaves the rest alone, so a store
+    /// warming up never overwrites what commits have added since the chain was read.
+    pub fn load_missing_concept_chains(
+        &self,
+        chains: impl IntoIterator<Item = (ConceptId, Vec<ConceptVersion>)>,
+    ) -> Result<()> {
+        self.put_concept_chains(chains, true)
+    }
 
+    fn put_concept_chains(
+        &self,
+        chains: impl IntoIterator<Item = (ConceptId, Vec<ConceptVersion>)>,
+        only_missing: bool,
+    ) -> Result<()> {
+        let chains: Vec<_> = chains
+            .into_iter()
+            .map(|(concept_id, mut versions)| {
+                // Disk hands versions back in key order, where "10" sorts before "2".
+                versions.sort_by_key(|v| v.version);
+                let new_entries: Vec<AuditCursor> =
+                    versions.iter().map(|v| AuditRecord::from_concept_version(v).cursor()).collect();
+                (concept_id, versions, new_entries)
+            })
+            .collect();
+
+        let mut heads = self.lock_health.write(&self.concept_heads);
+        let mut versions_map = self.lock_health.write(&self.concept_versions);
+        let mut change_log = self.lock_health.write(&self.change_log);
+        for (concept_id, versions, new_entries) in chains {
+            if only_missing && (versions.is_empty() || versions_map.contains_key(&concept_id)) {
+                continue;
+            }
+            let previous = match versions.last() {
+                Some(last) => {
+                    heads.insert(concept_id, last.version);
+                    versions_map.insert(concept_id, versions)
+                }
+                None => {
+                    heads.remove(&concept_id);
+                    versions_map.remove(&concept_id)
+                }
+            };
+            for version in previous.iter().flatten() {
+                change_log.remove(&AuditRecord::from_concept_version(version).cursor());
+            }
+            change_log.extend(new_entries);
+            match versions_map.get(&concept_id) {
+                Some(chain) => self.replay_concept_chain(chain),
+                None if previous.is_some() => {
+                    self.observers.tell(&self.concept_indexes(), |o| o.on_concept_forgotten(&concept_id))
+                }
+                None => {}
+            }
+        }
         Ok(())
     }
 
+    /// Tells the indexes about a chain put in whole. They only follow latest versions, so
+    /// they are told about that one; registered observers are told about each.
+    fn replay_concept_chain(&self, chain: &[ConceptVersion]) {
+        for index in self.concept_indexes() {
+            if let Some(latest) = chain.last() {
+                self.observers.tell_one(index, |o| o.on_concept_version(latest));
+            }
+        }
+        if self.observers.any() {
+            for version in chain {
+                self.observers.tell_registered(|o| o.on_concept_version(version));
+            }
+        }
+    }
+
+    /// The concepts that have expired by `now` but haven't been tombstoned yet.
+    pub fn expired_concepts(&self, now: DateTime<Utc>) -> HashSet<ConceptId> {
+        self.expirations.read().expired(now)
+    }
+
+    /// The live concepts whose expiry falls after `since` and no later than `until`.
+    pub fn concepts_expiring_between(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Vec<ConceptId> {
+        self.expirations.read().expiring_between(since, until)
+    }
+
+    /// The concepts and relationships with a version committed after `since` and no later
+    /// than `until`, each once. The log is walked `SCAN_CHUNK_SIZE` entries at a time.
+    pub fn changed_between(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> (HashSet<ConceptId>, HashSet<RelationshipId>) {
+        // Every cursor at `since` sorts after this one, and none of them is wanted.
+        let mut start = Bound::Excluded(AuditCursor {
+            timestamp: since,
+            item_kind: ItemKind::Relationship,
+            item_id: Uuid::max(),
+            version: u64::MAX,
+        });
+        let (mut concepts, mut relationships) = (HashSet::new(), HashSet::new());
+        loop {
+            let chunk: Vec<AuditCursor> = self
+                .lock_health
+                .read(&self.change_log)
+                .range((start, Bound::Unbounded))
+                .take_while(|key| key.timestamp <= until)
+                .take(SCAN_CHUNK_SIZE)
+                .copied()
+                .collect();
+            let Some(last) = chunk.last() else {
+                break;
+            };
+            start = Bound::Excluded(*last);
+            for key in &chunk {
+                match key.item_kind {
+                    ItemKind::Concept => concepts.insert(key.item_id),
+                    ItemKind::Relationship => relationships.insert(key.item_id),
+                };
+            }
+            if chunk.len() < SCAN_CHUNK_SIZE {
+                break;
+            }
+        }
+        (concepts, relationships)
+    }
+
+    /// Up to `limit` commits after `after` (from the first one held, if `None`), oldest first,
+    /// rebuilt from the log: a commit's versions all carry its timestamp, so they sit together.
+    /// A commit whose older versions were pruned comes back without them. Callers must hold
+    /// the commit lock, or a commit still being applied could come back half-written.
+    pub fn commits_after(&self, after: Option<DateTime<Utc>>, limit: usize) -> Result<Vec<CommitEvent>> {
+        let mut start = match after {
+            // Every cursor at `after` sorts before this one.
+            Some(after) => Bound::Excluded(AuditCursor {
+                timestamp: after,
+                item_kind: ItemKind::Relationship,
+                item_id: Uuid::max(),
+                version: u64::MAX,
+            }),
+            None => Bound::Unbounded,
+        };
+        let mut commits: Vec<CommitEvent> = Vec::new();
+        'scan: loop {
+            let chunk: Vec<AuditCursor> = self
+                .lock_health
+                .read(&self.change_log)
+                .range((start, Bound::Unbounded))
+                .take(SCAN_CHUNK_SIZE)
+                .copied()
+                .collect();
+            let Some(last) = chunk.last() else {
+                break;
+            };
+            start = Bound::Excluded(*last);
+
+            let concepts = self.lock_health.read(&self.concept_versions);
+            let relationships = self.lock_health.read(&self.relationship_versions);
+            for key in &chunk {
+                if commits.last().is_none_or(|commit| commit.committed_at != key.timestamp) {
+                    if commits.len() == limit {
+                        break 'scan;
+                    }
+                    commits.push(CommitEvent {
+                        transaction_id: Uuid::nil(),
+                        committed_at: key.timestamp,
+                        metadata: TransactionMetadata::default(),
+                        concept_versions: Vec::new(),
+                        relationship_versions: Vec::new(),
+                    });
+                }
+                let Some(commit) = commits.last_mut() else {
+                    continue;
+                };
+                match key.item_kind {
+                    ItemKind::Concept => {
+                        if let Some(version) = concepts
+                            .get(&key.item_id)
+                            .and_then(|chain| chain.iter().find(|v| v.version == key.version))
+                        {
+                            commit.transaction_id = version.deleted_by.unwrap_or(version.created_by);
+                            commit.concept_versions.push(version.clone());
+                        }
+                    }
+                    ItemKind::Relationship => {
+                        if let Some(version) = relationships
+                            .get(&key.item_id)
+                            .and_then(|chain| chain.iter().find(|v| v.version == key.version))
+                        {
+                            commit.transaction_id = version.deleted_by.unwrap_or(version.created_by);
+                            commit.relationship_versions.push(version.clone());
+                        }
+                    }
+                }
+            }
+            if chunk.len() < SCAN_CHUNK_SIZE {
+                break;
+            }
+        }
+
+        let metadata = self.lock_health.read(&self.transaction_metadata);
+        for commit in &mut commits {
+            if let Some(recorded) = metadata.get(&commit.transaction_id) {
+                commit.metadata = recorded.clone();
+            }
+        }
+        Ok(commits)
+    }
+
+    /// Every live relationship touching a concept, expired endpoints or not.
+    pub fn relationship_ids_touching(&self, concept_id: &ConceptId) -> Vec<RelationshipId> {
+        if let Err(e) = self.warm_adjacency() {
+            tracing::warn!("Could not load the relationships of concept {} from disk: {}", concept_id, e);
+        }
+        let adjacency = self.adjacency.read();
+        let mut ids = adjacency.outgoing(concept_id);
+        ids.extend(adjacency.incoming(concept_id));
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Replaces a relationship's whole history chain, e.g. when loading it from disk.
+    /// The latest version number follows the new chain; an empty chain forgets the relationship.
+    pub fn replace_relationship_versions(
+        &self,
+        relationship_id: RelationshipId,
+        versions: Vec<RelationshipVersion>,
+    ) -> Result<()> {
+        self.replace_relationship_chains([(relationship_id, versions)])
+    }
+
+    /// `replace_relationship_versions` for many relationships, taking each lock once instead
+    /// of once per relationship. The chains are sorted before any lock is taken.
+    pub fn replace_relationship_chains(
+        &self,
+        chains: impl IntoIterator<Item = (RelationshipId, Vec<RelationshipVersion>)>,
+    ) -> Result<()> {
+        self.put_relationship_chains(chains, false)
+    }
+
+    /// Adds the chains of relationships not in memory yet and leaves the rest alone, like
+    /// `load_missing_concept_chains`.
+    pub fn load_missing_relationship_chains(
+        &self,
+        chains: impl IntoIterator<Item = (RelationshipId, Vec<RelationshipVersion>)>,
+    ) -> Result<()> {
+        self.put_relationship_chains(chains, true)
+    }
+
+    fn put_relationship_chains(
+        &self,
+        chains: impl IntoIterator<Item = (RelationshipId, Vec<RelationshipVersion>)>,
+        only_missing: bool,
+    ) -> Result<()> {
+        let chains: Vec<_> = chains
+            .into_iter()
+            .map(|(relationship_id, mut versions)| {
+                versions.sort_by_key(|v| v.version);
+                (relationship_id, versions)
+            })
+            .collect();
+
+        let mut heads = self.lock_health.write(&self.relationship_heads);
+        let mut versions_map = self.lock_health.write(&self.relationship_versions);
+        let mut change_log = self.lock_health.write(&self.change_log);
+        for (relationship_id, versions) in chains {
+            if only_missing && (versions.is_empty() || versions_map.contains_key(&relationship_id)) {
+                continue;
+            }
+            let previous = match versions.last() {
+                Some(last) => {
+                    heads.insert(relationship_id, last.version);
+                    versions_map.insert(relationship_id, versions)
+                }
+                None => {
+                    heads.remove(&relationship_id);
+                    versions_map.remove(&relationship_id)
+                }
+            };
+            let current = versions_map.get(&relationship_id);
+            match current {
+                Some(chain) => self.replay_relationship_chain(chain),
+                None if previous.is_some() => self
+                    .observers
+                    .tell(&self.relationship_indexes(), |o| o.on_relationship_forgotten(&relationship_id)),
+                None => {}
+            }
+            for version in previous.iter().flatten() {
+                change_log.remove(&AuditRecord::from_relationship_version(version).cursor());
+            }
+            change_log.extend(
+                current
+                    .into_iter()
+                    .flatten()
+                    .map(|v| AuditRecord::from_relationship_version(v).cursor()),
+            );
+        }
+        Ok(())
+    }
+
+    /// `replay_concept_chain` for a relationship's chain.
+    fn replay_relationship_chain(&self, chain: &[RelationshipVersion]) {
+        for index in self.relationship_indexes() {
+            if let Some(latest) = chain.last() {
+                self.observers.tell_one(index, |o| o.on_relationship_version(latest));
+            }
+        }
+        if self.observers.any() {
+            for version in chain {
+                self.observers.tell_registered(|o| o.on_relationship_version(version));
+            }
+        }
+    }
+
+    /// Raises a concept's latest version number to a persisted counter, so version numbers
+    /// are never reused even when older versions are no longer on disk.
+    pub fn restore_concept_head(&self, concept_id: ConceptId, head: u64) -> Result<()> {
+        let mut heads = self.lock_health.write(&self.concept_heads);
+        let latest = heads.entry(concept_id).or_default();
+        *latest = (*latest).max(head);
+        Ok(())
+    }
+
+    /// Raises a relationship's latest version number to a persisted counter.
+    pub fn restore_relationship_head(&self, relationship_id: RelationshipId, head: u64) -> Result<()> {
+        let mut heads = self.lock_health.write(&self.relationship_heads);
+        let latest = heads.entry(relationship_id).or_default();
+        *latest = (*latest).max(head);
+        Ok(())
+    }
+
+    /// Version numbers of every concept chain, oldest first.
+    pub fn concept_version_numbers(&self) -> Result<HashMap<ConceptId, Vec<u64>>> {
+        let versions_map = self.lock_health.read(&self.concept_versions);
+
+        Ok(versions_map
+            .iter()
+            .map(|(id, versions_vec)| (*id, versions_vec.iter().map(|v| v.version).collect()))
+            .collect())
+    }
+
+    /// Version numbers of every relationship chain, oldest first.
+    pub fn relationship_version_numbers(&self) -> Result<HashMap<RelationshipId, Vec<u64>>> {
+        let versions_map = self.lock_health.read(&self.relationship_versions);
+
+        Ok(versions_map
+            .iter()
+            .map(|(id, versions_vec)| (*id, versions_vec.iter().map(|v| v.version).collect()))
+            .collect())
+    }
+
+    /// The number and commit time of every version in every concept chain, oldest first.
+    pub fn concept_version_stamps(&self) -> Result<HashMap<ConceptId, Vec<VersionStamp>>> {
+        let versions_map = self.lock_health.read(&self.concept_versions);
+
+        Ok(versions_map
+            .iter()
+            .map(|(id, versions_vec)| {
+                (*id, versions_vec.iter().map(|v| (v.version, v.created_at)).collect())
+            })
+            .collect())
+    }
+
+    /// The number and commit time of every version in every relationship chain, oldest first.
+    pub fn relationship_version_stamps(
+        &self,
+    ) -> Result<HashMap<RelationshipId, Vec<VersionStamp>>> {
+        let versions_map = self.lock_health.read(&self.relationship_versions);
+
+        Ok(versions_map
+            .iter()
+            .map(|(id, versions_vec)| {
+                (*id, versions_vec.iter().map(|v| (v.version, v.created_at)).collect())
+            })
+            .collect())
+    }
+
+    /// For every item deleted at or before `horizon`, the number of its latest version, if it
+    /// has older ones: what the compaction filter may drop below. With `expired`, concepts
+    /// whose latest version expired by then count as deleted too.
+    pub fn history_floors(
+        &self,
+        horizon: DateTime<Utc>,
+        expired: bool,
+    ) -> Result<(Vec<HistoryFloor>, Vec<HistoryFloor>)> {
+        let gone_by = |deleted_at: Option<DateTime<Utc>>| deleted_at.is_some_and(|at| at <= horizon);
+        let concepts = self
+            .lock_health
+            .read(&self.concept_versions)
+            .iter()
+            .filter_map(|(id, chain)| Some((id, chain.first()?, chain.last()?)))
+            .filter(|(_, first, latest)| {
+                first.version < latest.version
+                    && (gone_by(latest.deleted_at) || (expired && gone_by(latest.expires_at)))
+            })
+            .map(|(id, _, latest)| (*id, latest.version))
+            .collect();
+        let relationships = self
+            .lock_health
+            .read(&self.relationship_versions)
+            .iter()
+            .filter_map(|(id, chain)| Some((id, chain.first()?, chain.last()?)))
+            .filter(|(_, first, latest)| first.version < latest.version && gone_by(latest.deleted_at))
+            .map(|(id, _, latest)| (*id, latest.version))
+            .collect();
+        Ok((concepts, relationships))
+    }
+
+    /// Drops the given versions from a concept's chain. The latest version number is kept,
+    /// so pruned numbers are never handed out again.
+    pub fn remove_concept_versions(&self, concept_id: &ConceptId, versions: &[u64]) -> Result<()> {
+        let mut versions_map = self.lock_health.write(&self.concept_versions);
+        if let Some(chain) = versions_map.get_mut(concept_id) {
+            let mut change_log = self.lock_health.write(&self.change_log);
+            chain.retain(|v| {
+                let removed = versions.contains(&v.version);
+                if removed {
+                    change_log.remove(&AuditRecord::from_concept_version(v).cursor());
+                }
+                !removed
+            });
+        }
+        Ok(())
+    }
+
+    /// Drops the given versions from a relationship's chain, keeping its latest version number.
+    pub fn remove_relationship_versions(
+        &self,
+        relationship_id: &RelationshipId,
+        versions: &[u64],
+    ) -> Result<()> {
+        let mut versions_map = self.lock_health.write(&self.relationship_versions);
+        if let Some(chain) = versions_map.get_mut(relationship_id) {
+            let mut change_log = self.lock_health.write(&self.change_log);
+            chain.retain(|v| {
+                let removed = versions.contains(&v.version);
+                if removed {
+                    change_log.remove(&AuditRecord::from_relationship_version(v).cursor());
+                }
+                !removed
+            });
+        }
+        Ok(())
+    }
+
+    /// Records the metadata of a committed transaction.
+    pub fn add_transaction_metadata(
+        &self,
+        transaction_id: TransactionId,
+        metadata: TransactionMetadata,
+    ) -> Result<()> {
+        let mut metadata_map = self.lock_health.write(&self.transaction_metadata);
+        metadata_map.insert(transaction_id, metadata);
+        Ok(())
+    }
+
+    /// Looks up the metadata recorded by a committed transaction, if it recorded any.
+    pub fn get_transaction_metadata(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<TransactionMetadata>> {
+        let metadata_map = self.lock_health.read(&self.transaction_metadata);
+        Ok(metadata_map.get(transaction_id).cloned())
+    }
+
     /// Adds a simple check for conflict detection
     pub fn has_concept_been_modified_since(
         &self,
         concept_id: &ConceptId,
         timestamp: DateTime<Utc>,
     ) -> Result<bool> {
-        let versions_map = self
-            .concept_versions
-            .read()
-            .map_err(|e| MnemonicError::Transaction(format!("Read lock failed: {}", e)))?;
+        self.warm_concept(concept_id)?;
+        let versions_map = self.lock_health.read(&self.concept_versions);
 
         if let Some(versions_vec) = versions_map.get(concept_id) {
             // If the latest version was created after our timestamp, there is a conflict.
@@ -144,10 +1087,8 @@ impl VersionStore {
         relationship_id: &RelationshipId,
         timestamp: DateTime<Utc>,
     ) -> Result<bool> {
-        let versions_map = self
-            .relationship_versions
-            .read()
-            .map_err(|e| MnemonicError::Transaction(format!("Read lock failed: {}", e)))?;
+        self.warm_relationship(relationship_id)?;
+        let versions_map = self.lock_health.read(&self.relationship_versions);
 
         if let Some(versions_vec) = versions_map.get(relationship_id) {
             // If the latest version's timestamp is after our check time, there is a conflict.
@@ -166,10 +1107,7 @@ impl VersionStore {
     /// Gets a snapshot of all active concepts at the current time.
     pub fn get_all_active_concepts(&self) -> Result<Vec<ConceptVersion>> {
         let now = Utc::now();
-        let versions_map = self
-            .concept_versions
-            .read()
-            .map_err(|e| MnemonicError::Transaction(format!("Read lock failed: {}", e)))?;
+        let versions_map = self.lock_health.read(&self.concept_versions);
 
         let mut active_concepts = Vec::new();
 
@@ -186,13 +1124,527 @@ impl VersionStore {
         Ok(active_concepts)
     }
 
+    /// The IDs of every concept whose latest version is active right now, in ID order.
+    pub fn active_concept_ids(&self) -> Result<Vec<ConceptId>> {
+        let now = Utc::now();
+        let versions_map = self.lock_health.read(&self.concept_versions);
+        Ok(versions_map
+            .iter()
+            .filter(|(_, chain)| chain.last().is_some_and(|latest| latest.is_active_at(now)))
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    /// The IDs of every concept with a history, deleted or not, in ID order.
+    pub fn concept_ids(&self) -> Result<Vec<ConceptId>> {
+        Ok(self.lock_health.read(&self.concept_versions).keys().copied().collect())
+    }
+
+    /// The IDs of every relationship with a history, deleted or not, in ID order.
+    pub fn relationship_ids(&self) -> Result<Vec<RelationshipId>> {
+        Ok(self.lock_health.read(&self.relationship_versions).keys().copied().collect())
+    }
+
+    /// Calls `f` with the current version of every active concept in ID order, until it breaks.
+    /// Unlike `get_all_active_concepts`, the read lock is only held for one chunk of
+    /// `SCAN_CHUNK_SIZE` concepts at a time, so writers get in between chunks.
+    /// Concepts created after the scan started are not visited. `f` runs under the lock,
+    /// so it must not call back into the VersionStore.
+    pub fn for_each_active_concept(
+        &self,
+        mut f: impl FnMut(&ConceptVersion) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.for_each_active_concept_created(|version, _| f(version))
+    }
+
+    /// `for_each_active_concept`, also passing when each concept was first created.
+    fn for_each_active_concept_created(
+        &self,
+        mut f: impl FnMut(&ConceptVersion, DateTime<Utc>) -> ControlFlow<()>,
+    ) -> Result<()> {
+        for chunk in self.concept_ids()?.chunks(SCAN_CHUNK_SIZE) {
+            let now = Utc::now();
+            let versions_map = self.lock_health.read(&self.concept_versions);
+            for concept_id in chunk {
+                let Some(versions_vec) = versions_map.get(concept_id) else {
+                    continue;
+                };
+                if let (Some(first), Some(latest)) = (versions_vec.first(), versions_vec.last())
+                    && latest.is_active_at(now)
+                    && f(latest, first.created_at).is_break()
+                {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `for_each_active_concept`, for relationships.
+    pub fn for_each_active_relationship(
+        &self,
+        mut f: impl FnMut(&RelationshipVersion) -> ControlFlow<()>,
+    ) -> Result<()> {
+        for chunk in self.relationship_ids()?.chunks(SCAN_CHUNK_SIZE) {
+            let now = Utc::now();
+            let expired = self.expired_concepts(now);
+            let versions_map = self.lock_health.read(&self.relationship_versions);
+            for rel_id in chunk {
+                let latest = versions_map.get(rel_id).and_then(|versions_vec| versions_vec.last());
+                if let Some(latest) = latest.filter(|version| version.is_active_at(now) && !touches(version, &expired))
+                    && f(latest).is_break()
+                {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `for_each_active_concept`, spending an item of `meter` on each concept and stopping
+    /// once it is spent. `f` is also told when each concept was first created.
+    pub fn scan_active_concepts(
+        &self,
+        meter: &ScanMeter,
+        mut f: impl FnMut(&ConceptVersion, DateTime<Utc>) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.for_each_active_concept_created(|version, created_at| {
+            if meter.take() { f(version, created_at) } else { ControlFlow::Break(()) }
+        })
+    }
+
+    /// `scan_active_concepts`, for relationships.
+    pub fn scan_active_relationships(
+        &self,
+        meter: &ScanMeter,
+        mut f: impl FnMut(&RelationshipVersion) -> ControlFlow<()>,
+    ) -> Result<()> {
+        self.for_each_active_relationship(|version| if meter.take() { f(version) } else { ControlFlow::Break(()) })
+    }
+
+    /// A page of active concepts in ascending ID order, starting after `after`.
+    /// Each entry is the current version together with when the concept was first created,
+    /// which `filter` is also told.
+    /// Every concept looked at spends an item of `meter`; once it is spent the page ends
+    /// early, and the second value is the last concept looked at, to resume after.
+    pub fn get_active_concepts_page(
+        &self,
+        after: Option<&ConceptId>,
+        limit: usize,
+        meter: &ScanMeter,
+        filter: impl Fn(&ConceptVersion, DateTime<Utc>) -> bool,
+    ) -> Result<ActiveConceptsPage> {
+        let now = Utc::now();
+        self.concepts_page(after, limit, meter, filter, |versions_vec| {
+            versions_vec.last().filter(|latest| latest.is_active_at(now))
+        })
+    }
+
+    /// `get_active_concepts_page` as the graph stood at `timestamp`: each entry is the version
+    /// live then.
+    pub fn get_active_concepts_page_at(
+        &self,
+        after: Option<&ConceptId>,
+        limit: usize,
+        timestamp: DateTime<Utc>,
+        meter: &ScanMeter,
+        filter: impl Fn(&ConceptVersion, DateTime<Utc>) -> bool,
+    ) -> Result<ActiveConceptsPage> {
+        self.concepts_page(after, limit, meter, filter, |versions_vec| {
+            versions_vec
+                .iter()
+                .rev()
+                .find(|version| version.created_at <= timestamp)
+                .filter(|version| version.is_active_at(timestamp))
+        })
+    }
+
+    fn concepts_page(
+        &self,
+        after: Option<&ConceptId>,
+        limit: usize,
+        meter: &ScanMeter,
+        filter: impl Fn(&ConceptVersion, DateTime<Utc>) -> bool,
+        live: impl Fn(&[ConceptVersion]) -> Option<&ConceptVersion>,
+    ) -> Result<ActiveConceptsPage> {
+        let versions_map = self.lock_health.read(&self.concept_versions);
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+
+        let mut last_seen = None;
+        let page = versions_map
+            .range((start, Bound::Unbounded))
+            .take_while(|_| meter.take())
+            .filter_map(|(concept_id, versions_vec)| {
+                last_seen = Some(*concept_id);
+                let version = live(versions_vec)?;
+                let first = versions_vec.first()?;
+                filter(version, first.created_at).then(|| (version.clone(), first.created_at))
+            })
+            .take(limit)
+            .collect();
+        Ok((page, last_seen))
+    }
+
+    /// The live relationships whose source is the given concept, found through the
+    /// adjacency index rather than a scan of every relationship.
+    pub fn outgoing_relationships(&self, concept_id: &ConceptId) -> Result<Vec<RelationshipVersion>> {
+        self.warm_adjacency()?;
+        let ids = self.adjacency.read().outgoing(concept_id);
+        self.latest_active_relationships(&ids)
+    }
+
+    /// The live relationships of one type whose source is the given concept, found through
+    /// the (concept, type) entries of the adjacency index.
+    pub fn outgoing_relationships_of_type(
+        &self,
+        concept_id: &ConceptId,
+        relationship_type: &RelationType,
+    ) -> Result<Vec<RelationshipVersion>> {
+        self.warm_adjacency()?;
+        let ids = self.adjacency.read().outgoing_of_type(concept_id, relationship_type);
+        self.latest_active_relationships(&ids)
+    }
+
+    /// Throws away the adjacency, label and tag indexes and regenerates them from the version
+    /// chains, reporting how much the old ones had wrong.
+    pub fn rebuild_indexes(&self) -> Result<(IndexRebuild, IndexRebuild, IndexRebuild)> {
+        let adjacency = {
+            let versions_map = self.lock_health.read(&self.relationship_versions);
+            let mut rebuilt = Adjacency::default();
+            for latest in versions_map.values().filter_map(|chain| chain.last()) {
+                rebuilt.relationship_version(latest);
+            }
+            let mut adjacency = self.adjacency.write();
+            let report = IndexRebuild::compare(&adjacency.entries(), &rebuilt.entries());
+            *adjacency = rebuilt;
+            report
+        };
+        let labels = {
+            let versions_map = self.lock_health.read(&self.concept_versions);
+            let mut rebuilt = LabelIndex::default();
+            for latest in versions_map.values().filter_map(|chain| chain.last()) {
+                rebuilt.concept_version(latest);
+            }
+            let mut labels = self.labels.write();
+            let report = IndexRebuild::compare(&labels.entries(), &rebuilt.entries());
+            *labels = rebuilt;
+            report
+        };
+        let tags = {
+            let versions_map = self.lock_health.read(&self.concept_versions);
+            let mut rebuilt = TagIndex::default();
+            for latest in versions_map.values().filter_map(|chain| chain.last()) {
+                rebuilt.concept_version(latest);
+            }
+            let mut tags = self.tags.write();
+            let report = IndexRebuild::compare(&tags.entries(), &rebuilt.entries());
+            *tags = rebuilt;
+            report
+        };
+        Ok((adjacency, labels, tags))
+    }
+
+    /// The IDs of the live concepts carrying the given label, in no particular order.
+    pub fn concepts_with_label(&self, label: &str) -> Result<Vec<ConceptId>> {
+        Ok(self.labels.read().concepts(label))
+    }
+
+    /// The IDs of the live concepts carrying the given tag, in no particular order.
+    pub fn concepts_with_tag(&self, tag: &str) -> Result<Vec<ConceptId>> {
+        Ok(self.tags.read().concepts(tag))
+    }
+
+    /// The live relationships whose target is the given concept.
+    pub fn incoming_relationships(&self, concept_id: &ConceptId) -> Result<Vec<RelationshipVersion>> {
+        self.warm_adjacency()?;
+        let ids = self.adjacency.read().incoming(concept_id);
+        self.latest_active_relationships(&ids)
+    }
+
+    fn latest_active_relationships(&self, ids: &[RelationshipId]) -> Result<Vec<RelationshipVersion>> {
+        let now = Utc::now();
+        let expired = self.expired_concepts(now);
+        let versions_map = self.lock_health.read(&self.relationship_versions);
+        Ok(ids
+            .iter()
+            .filter_map(|id| versions_map.get(id).and_then(|chain| chain.last()))
+            .filter(|version| version.is_active_at(now) && !touches(version, &expired))
+            .cloned()
+            .collect())
+    }
+
+    /// Runs `f` with a view of the live relationships, read-locked for its duration.
+    pub fn with_neighbor_view<T>(&self, f: impl FnOnce(&NeighborView<'_>) -> T) -> T {
+        let expired = self.expired_concepts(Utc::now());
+        let relationship_versions = self.lock_health.read(&self.relationship_versions);
+        let adjacency = self.adjacency.read();
+        f(&NeighborView {
+            relationship_versions: &relationship_versions,
+            adjacency: &adjacency,
+            expired,
+        })
+    }
+
+    /// Every version of a concept still held, oldest first. Empty if the concept is unknown.
+    pub fn concept_history(&self, concept_id: &ConceptId) -> Result<Vec<ConceptVersion>> {
+        self.warm_concept(concept_id)?;
+        Ok(self
+            .lock_health
+            .read(&self.concept_versions)
+            .get(concept_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Every version of a relationship still held, oldest first. Empty if it is unknown.
+    pub fn relationship_history(&self, relationship_id: &RelationshipId) -> Result<Vec<RelationshipVersion>> {
+        self.warm_relationship(relationship_id)?;
+        Ok(self
+            .lock_health
+            .read(&self.relationship_versions)
+            .get(relationship_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// The whole history of every relationship that ever started or ended at a concept.
+    pub fn relationship_histories_touching(
+        &self,
+        concept_id: &ConceptId,
+    ) -> Result<Vec<Vec<RelationshipVersion>>> {
+        Ok(self
+            .lock_health
+            .read(&self.relationship_versions)
+            .values()
+            .filter(|chain| {
+                chain
+                    .iter()
+                    .any(|v| &v.source == concept_id || &v.target == concept_id)
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// When the newest version in the change log was committed, if there is one.
+    pub fn last_changed_at(&self) -> Option<DateTime<Utc>> {
+        self.lock_health.read(&self.change_log).last().map(|key| key.timestamp)
+    }
+
+    /// A page of the audit log: the versions committed in the query's window, oldest first.
+    /// The log is walked `SCAN_CHUNK_SIZE` entries at a time, so commits get in between.
+    pub fn audit_log(&self, query: &AuditQuery) -> Result<AuditPage> {
+        let limit = query.page_size();
+        let first_since = query.since.map(|since| AuditCursor {
+            timestamp: since,
+            item_kind: ItemKind::Concept,
+            item_id: Uuid::nil(),
+            version: 0,
+        });
+        let mut start = match (query.cursor, first_since) {
+            (Some(cursor), Some(since)) if cursor < since => Bound::Included(since),
+            (Some(cursor), _) => Bound::Excluded(cursor),
+            (None, Some(since)) => Bound::Included(since),
+            (None, None) => Bound::Unbounded,
+        };
+
+        let mut records = Vec::new();
+        loop {
+            let chunk: Vec<AuditCursor> = self
+                .lock_health
+                .read(&self.change_log)
+                .range((start, Bound::Unbounded))
+                .take_while(|key| query.until.is_none_or(|until| key.timestamp < until))
+                .take(SCAN_CHUNK_SIZE)
+                .copied()
+                .collect();
+            let Some(last) = chunk.last() else {
+                break;
+            };
+            start = Bound::Excluded(*last);
+
+            let concepts = self.lock_health.read(&self.concept_versions);
+            let relationships = self.lock_health.read(&self.relationship_versions);
+            let metadata = self.lock_health.read(&self.transaction_metadata);
+            for key in &chunk {
+                // Versions pruned since the chunk was read are skipped.
+                let record = match key.item_kind {
+                    ItemKind::Concept => concepts
+                        .get(&key.item_id)
+                        .and_then(|chain| chain.iter().find(|v| v.version == key.version))
+                        .map(AuditRecord::from_concept_version),
+                    ItemKind::Relationship => relationships
+                        .get(&key.item_id)
+                        .and_then(|chain| chain.iter().find(|v| v.version == key.version))
+                        .map(AuditRecord::from_relationship_version),
+                };
+                let Some(mut record) = record else {
+                    continue;
+                };
+                record.actor = metadata
+                    .get(&record.transaction_id)
+                    .and_then(|metadata| metadata.actor.clone());
+                if query.actor.is_some() && record.actor != query.actor {
+                    continue;
+                }
+                records.push(record);
+                // One extra record says whether there's another page.
+                if records.len() > limit {
+                    break;
+                }
+            }
+            if records.len() > limit || chunk.len() < SCAN_CHUNK_SIZE {
+                break;
+            }
+        }
+
+        let next_cursor = if records.len() > limit {
+            records.truncate(limit);
+            records.last().map(AuditRecord::cursor)
+        } else {
+            None
+        };
+        Ok(AuditPage { records, next_cursor })
+    }
+
+    /// What was committed in each `width` bucket from `from` up to `to`, both rounded out to
+    /// whole buckets, with zeros for buckets nothing happened in. Read off the change log like
+    /// `audit_log`, so pruned versions aren't counted.
+    pub fn activity(&self, from: DateTime<Utc>, to: DateTime<Utc>, width: BucketWidth) -> Result<Vec<ActivityBucket>> {
+        let mut buckets = audit::activity_buckets(from, to, width)?;
+        let first = buckets[0].start;
+        let end = buckets[buckets.len() - 1].start + width.duration();
+        let step = width.duration().num_seconds();
+
+        let mut start = Bound::Included(AuditCursor {
+            timestamp: first,
+            item_kind: ItemKind::Concept,
+            item_id: Uuid::nil(),
+            version: 0,
+        });
+        loop {
+            let chunk: Vec<AuditCursor> = self
+                .lock_health
+                .read(&self.change_log)
+                .range((start, Bound::Unbounded))
+                .take_while(|key| key.timestamp < end)
+                .take(SCAN_CHUNK_SIZE)
+                .copied()
+                .collect();
+            let Some(last) = chunk.last() else {
+                break;
+            };
+            start = Bound::Excluded(*last);
+
+            let concepts = self.lock_health.read(&self.concept_versions);
+            let relationships = self.lock_health.read(&self.relationship_versions);
+            for key in &chunk {
+                let change = match key.item_kind {
+                    ItemKind::Concept => concepts
+                        .get(&key.item_id)
+                        .and_then(|chain| chain.iter().find(|v| v.version == key.version))
+                        .map(|v| AuditRecord::from_concept_version(v).change),
+                    ItemKind::Relationship => relationships
+                        .get(&key.item_id)
+                        .and_then(|chain| chain.iter().find(|v| v.version == key.version))
+                        .map(|v| AuditRecord::from_relationship_version(v).change),
+                };
+                if let Some(change) = change {
+                    let index = ((key.timestamp - first).num_seconds() / step) as usize;
+                    buckets[index].count(key.item_kind, change);
+                }
+            }
+            if chunk.len() < SCAN_CHUNK_SIZE {
+                break;
+            }
+        }
+        Ok(buckets)
+    }
+
+    /// How many concepts are active right now, leaving out system concepts as listings do.
+    pub fn count_active_concepts(&self) -> Result<u64> {
+        let now = Utc::now();
+        let versions_map = self.lock_health.read(&self.concept_versions);
+        Ok(versions_map
+            .values()
+            .filter(|chain| {
+                chain.last().is_some_and(|latest| latest.is_active_at(now) && !system::is_system(&latest.tags))
+            })
+            .count() as u64)
+    }
+
+    /// How many relationships are active right now, read off the adjacency index.
+    pub fn count_active_relationships(&self) -> Result<u64> {
+        let expired = self.expired_concepts(Utc::now());
+        let adjacency = self.adjacency.read();
+        // Until the sweeper catches up, edges of expired concepts are in the index but not live.
+        let mut hidden: Vec<RelationshipId> = expired
+            .iter()
+            .flat_map(|id| adjacency.outgoing(id).into_iter().chain(adjacency.incoming(id)))
+            .collect();
+        hidden.sort_unstable();
+        hidden.dedup();
+        Ok(adjacency.edge_count().saturating_sub(hidden.len()) as u64)
+    }
+
+    /// How many active relationships touch the given concept in the given direction.
+    pub fn degree(&self, concept_id: &ConceptId, direction: Direction) -> Result<u64> {
+        self.warm_adjacency()?;
+        let expired = self.expired_concepts(Utc::now());
+        if expired.is_empty() {
+            let adjacency = self.adjacency.read();
+            let degree = match direction {
+                Direction::Outgoing => adjacency.out_degree(concept_id),
+                Direction::Incoming => adjacency.in_degree(concept_id),
+                Direction::Both => adjacency.degree(concept_id),
+            };
+            return Ok(degree as u64);
+        }
+        if expired.contains(concept_id) {
+            return Ok(0);
+        }
+        Ok(self.with_neighbor_view(|view| view.edges(concept_id, direction, None).len()) as u64)
+    }
+
+    /// How many active relationships there are of each type.
+    pub fn count_active_relationships_by_type(&self) -> Result<HashMap<RelationType, u64>> {
+        let now = Utc::now();
+        let expired = self.expired_concepts(now);
+        let versions_map = self.lock_health.read(&self.relationship_versions);
+        let mut counts = HashMap::new();
+        for latest in versions_map.values().filter_map(|chain| chain.last()) {
+            if latest.is_active_at(now) && !touches(latest, &expired) {
+                *counts.entry(latest.relationship_type.clone()).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Counts the items and versions held in memory, deleted ones included.
+    /// Each map is read under its own lock, so the counts may straddle a commit.
+    pub fn stats(&self) -> Result<VersionStoreStats> {
+        let (concepts, concept_versions) = {
+            let versions_map = self.lock_health.read(&self.concept_versions);
+            (versions_map.len(), versions_map.values().map(Vec::len).sum::<usize>())
+        };
+        let (relationships, relationship_versions) = {
+            let versions_map = self.lock_health.read(&self.relationship_versions);
+            (versions_map.len(), versions_map.values().map(Vec::len).sum::<usize>())
+        };
+        let transactions_with_metadata = self.lock_health.read(&self.transaction_metadata).len();
+        Ok(VersionStoreStats {
+            concepts: concepts as u64,
+            concept_versions: concept_versions as u64,
+            relationships: relationships as u64,
+            relationship_versions: relationship_versions as u64,
+            transactions_with_metadata: transactions_with_metadata as u64,
+        })
+    }
+
     /// Gets a snapshot of all active relationships at the current time.
     pub fn get_all_active_relationships(&self) -> Result<Vec<RelationshipVersion>> {
         let now = Utc::now();
-        let versions_map = self
-            .relationship_versions
-            .read()
-            .map_err(|e| MnemonicError::Transaction(format!("Read lock failed: {}", e)))?;
+        let expired = self.expired_concepts(now);
+        let versions_map = self.lock_health.read(&self.relationship_versions);
 
         let mut active_relationships = Vec::new();
 
@@ -200,8 +1652,8 @@ impl VersionStore {
         for versions_vec in versions_map.values() {
             // Get the MOST RECENT version.
             if let Some(latest_version) = versions_vec.last() {
-                // Check if THIS LATEST version is active.
-                if latest_version.is_active_at(now) {
+                // Check if THIS LATEST version is active, and both its ends still are.
+                if latest_version.is_active_at(now) && !touches(latest_version, &expired) {
                     active_relationships.push(latest_version.clone());
                 }
             }
@@ -210,6 +1662,43 @@ impl VersionStore {
     }
 }
 
+/// The next commit timestamp after `last`: now, unless the clock hasn't moved past it.
+pub(crate) fn tick(last: &mut DateTime<Utc>) -> DateTime<Utc> {
+    *last = Utc::now().max(*last + chrono::Duration::nanoseconds(1));
+    *last
+}
+
+/// Converts a ConceptVersion back to a simple Concept for the API.
+pub(crate) fn relationship_from_version(version: &RelationshipVersion) -> Relationship {
+    Relationship {
+        id: version.relationship_id,
+        source: version.source,
+        relationship_type: version.relationship_type.clone(),
+        target: version.target,
+        metadata: RelationshipMetadata {
+            created_at: version.created_at,
+            version: version.version,
+            transaction_id: version.created_by,
+        },
+        directed: version.directed,
+    }
+}
+
+pub(crate) fn concept_from_version(version: &ConceptVersion) -> Concept {
+    Concept {
+        id: version.concept_id,
+        data: version.data.clone(),
+        metadata: crate::types::concept::ConceptMetadata {
+            created_at: version.created_at,
+            updated_at: version.created_at, // Simplification for this example
+            version: version.version,
+            transaction_id: version.created_by,
+        },
+        expires_at: version.expires_at,
+        tags: version.tags.clone(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,11 +1719,13 @@ mod tests {
         let version1 = ConceptVersion {
             concept_id,
             version: 1,
-            data: ConceptData::Structured("v1".to_string()),
+            data: ConceptData::Structured("v1".to_string().into()),
             created_at: t1,
             created_by: txn_id,
             deleted_at: None,
             deleted_by: None,
+            expires_at: None,
+            tags: Default::default(),
         };
         store.add_concept_version(version1.clone()).unwrap();
 
@@ -245,11 +1736,13 @@ mod tests {
         let version2 = ConceptVersion {
             concept_id,
             version: 2,
-            data: ConceptData::Structured("v2".to_string()),
+            data: ConceptData::Structured("v2".to_string().into()),
             created_at: t2,
             created_by: txn_id,
             deleted_at: None,
             deleted_by: None,
+            expires_at: None,
+            tags: Default::default(),
         };
         store.add_concept_version(version2.clone()).unwrap();
 
@@ -303,11 +1796,12 @@ mod tests {
             version: 2, // It's a new version
             source: source_id,
             target: target_id,
-            relationship_type: "knows".to_string(),
+            relationship_type: "knows".into(),
             created_at: t2,
             created_by: txn_id_2,
             deleted_at: Some(t2),
             deleted_by: Some(txn_id_2),
+            directed: true,
         };
         store.add_relationship_version(version2.clone()).unwrap();
 
@@ -327,4 +1821,235 @@ mod tests {
             .unwrap();
         assert!(retrieved_at_t2.is_none());
     }
+
+    #[test]
+    fn test_out_of_sequence_versions_are_rejected() {
+        let store = VersionStore::new();
+        let concept_id = Uuid::new_v4();
+        let make_version = |version| ConceptVersion {
+            concept_id,
+            version,
+            data: ConceptData::Structured(format!("v{}", version).into()),
+            created_at: Utc::now(),
+            created_by: Uuid::new_v4(),
+            deleted_at: None,
+            deleted_by: None,
+            expires_at: None,
+            tags: Default::default(),
+        };
+
+        store.add_concept_version(make_version(1)).unwrap();
+        store.add_concept_version(make_version(2)).unwrap();
+
+        // A second version 2 would silently overwrite the first one's key on disk.
+        let duplicate = store.add_concept_version(make_version(2));
+        assert!(matches!(
+            duplicate,
+            Err(MnemonicError::VersionOutOfSequence { version: 2, latest: 2, .. })
+        ));
+
+        // Skipping ahead is refused as well.
+        let gap = store.add_concept_version(make_version(4));
+        assert!(matches!(
+            gap,
+            Err(MnemonicError::VersionOutOfSequence { version: 4, latest: 2, .. })
+        ));
+
+        assert_eq!(store.latest_concept_version_number(&concept_id).unwrap(), 2);
+        assert_eq!(store.concept_version_numbers().unwrap()[&concept_id], vec![1, 2]);
+    }
+
+    #[test]
+    fn test_latest_active_concepts_line_up_with_ids() {
+        let store = VersionStore::new();
+        let txn_id = Uuid::new_v4();
+        let alive = Uuid::new_v4();
+        let deleted = Uuid::new_v4();
+        let unknown = Uuid::new_v4();
+        for id in [alive, deleted] {
+            store
+                .add_concept_version(ConceptVersion {
+                    concept_id: id,
+                    version: 1,
+                    data: ConceptData::Empty,
+                    created_at: Utc::now(),
+                    created_by: txn_id,
+                    deleted_at: None,
+                    deleted_by: None,
+                    expires_at: None,
+                    tags: Default::default(),
+                })
+                .unwrap();
+        }
+        store
+            .add_concept_version(ConceptVersion {
+                concept_id: deleted,
+                version: 2,
+                data: ConceptData::Empty,
+                created_at: Utc::now(),
+                created_by: txn_id,
+                deleted_at: Some(Utc::now()),
+                deleted_by: Some(txn_id),
+                expires_at: None,
+                tags: Default::default(),
+            })
+            .unwrap();
+
+        let found = store
+            .get_latest_active_concepts(&[unknown, alive, deleted, alive])
+            .unwrap();
+        let ids: Vec<Option<Uuid>> = found.iter().map(|v| v.as_ref().map(|v| v.concept_id)).collect();
+        assert_eq!(ids, vec![None, Some(alive), None, Some(alive)]);
+    }
+
+    #[test]
+    fn test_for_each_active_concept_matches_the_full_snapshot() {
+        let store = VersionStore::new();
+        let txn_id = Uuid::new_v4();
+        for n in 0..(SCAN_CHUNK_SIZE * 3 + 7) {
+            store
+                .add_concept_version(ConceptVersion {
+                    concept_id: Uuid::new_v4(),
+                    version: 1,
+                    data: ConceptData::Empty,
+                    created_at: Utc::now(),
+                    created_by: txn_id,
+                    // Every tenth concept is already deleted.
+                    deleted_at: (n % 10 == 0).then(Utc::now),
+                    deleted_by: (n % 10 == 0).then_some(txn_id),
+                    expires_at: None,
+                    tags: Default::default(),
+                })
+                .unwrap();
+        }
+
+        let mut visited = Vec::new();
+        store
+            .for_each_active_concept(|version| {
+                visited.push(version.concept_id);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        let mut expected: Vec<Uuid> = store
+            .get_all_active_concepts()
+            .unwrap()
+            .iter()
+            .map(|v| v.concept_id)
+            .collect();
+        visited.sort();
+        expected.sort();
+        assert_eq!(visited, expected);
+
+        // Breaking stops the walk.
+        let mut seen = 0;
+        store
+            .for_each_active_concept(|_| {
+                seen += 1;
+                if seen == 5 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            })
+            .unwrap();
+        assert_eq!(seen, 5);
+    }
+
+    #[test]
+    fn test_adjacency_follows_create_delete_recreate() {
+        let store = VersionStore::new();
+        let (source, target) = (Uuid::new_v4(), Uuid::new_v4());
+        let rel = Relationship::new(source, "knows".to_string(), target);
+        let ids = |versions: Vec<RelationshipVersion>| -> Vec<Uuid> {
+            versions.iter().map(|v| v.relationship_id).collect()
+        };
+
+        let created = RelationshipVersion::from_relationship(&rel, Uuid::new_v4());
+        store.add_relationship_version(created.clone()).unwrap();
+        assert_eq!(ids(store.outgoing_relationships(&source).unwrap()), vec![rel.id]);
+        assert_eq!(ids(store.incoming_relationships(&target).unwrap()), vec![rel.id]);
+        assert!(store.outgoing_relationships(&target).unwrap().is_empty());
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let deleted_at = Utc::now();
+        store
+            .add_relationship_version(RelationshipVersion {
+                version: 2,
+                deleted_at: Some(deleted_at),
+                deleted_by: Some(Uuid::new_v4()),
+                ..created.clone()
+            })
+            .unwrap();
+        assert!(store.outgoing_relationships(&source).unwrap().is_empty());
+        assert!(store.incoming_relationships(&target).unwrap().is_empty());
+        // The tombstone only hides the edge going forward; history still has it.
+        let historical = store
+            .get_relationship_version_at_timestamp(&rel.id, created.created_at)
+            .unwrap()
+            .expect("the edge was live before it was deleted");
+        assert_eq!((historical.source, historical.target), (source, target));
+
+        store
+            .add_relationship_version(RelationshipVersion {
+                version: 3,
+                created_at: Utc::now(),
+                ..created.clone()
+            })
+            .unwrap();
+        assert_eq!(ids(store.outgoing_relationships(&source).unwrap()), vec![rel.id]);
+        assert_eq!(ids(store.incoming_relationships(&target).unwrap()), vec![rel.id]);
+
+        // Reloading a chain that ends in a tombstone drops the edge; an empty chain too.
+        store
+            .replace_relationship_versions(
+                rel.id,
+                vec![created.clone(), RelationshipVersion { version: 2, deleted_at: Some(deleted_at), ..created.clone() }],
+            )
+            .unwrap();
+        assert!(store.outgoing_relationships(&source).unwrap().is_empty());
+        store.replace_relationship_versions(rel.id, vec![created.clone()]).unwrap();
+        assert_eq!(ids(store.outgoing_relationships(&source).unwrap()), vec![rel.id]);
+        store.replace_relationship_versions(rel.id, Vec::new()).unwrap();
+        assert!(store.incoming_relationships(&target).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_floors_cover_items_gone_by_the_horizon() {
+        let store = VersionStore::new();
+        let t0 = Utc::now() - chrono::Duration::hours(2);
+        let version = |concept_id, version, minutes: i64| ConceptVersion {
+            concept_id,
+            version,
+            data: ConceptData::Structured(version.to_string().into()),
+            created_at: t0 + chrono::Duration::minutes(minutes),
+            created_by: Uuid::new_v4(),
+            deleted_at: None,
+            deleted_by: None,
+            expires_at: None,
+            tags: Default::default(),
+        };
+        let (deleted, recent, expired, live, alone) =
+            (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for (id, deleted_at) in [(deleted, 10), (recent, 90)] {
+            store.add_concept_version(version(id, 1, 0)).unwrap();
+            let tombstone = version(id, 2, deleted_at);
+            store
+                .add_concept_version(ConceptVersion { deleted_at: Some(tombstone.created_at), ..tombstone })
+                .unwrap();
+        }
+        store.add_concept_version(version(expired, 1, 0)).unwrap();
+        store
+            .add_concept_version(ConceptVersion { expires_at: Some(t0 + chrono::Duration::minutes(20)), ..version(expired, 2, 5) })
+            .unwrap();
+        store.add_concept_version(version(live, 1, 0)).unwrap();
+        store.add_concept_version(version(live, 2, 5)).unwrap();
+        // Deleted, but with nothing older than the tombstone to drop.
+        let only = version(alone, 1, 0);
+        store.add_concept_version(ConceptVersion { deleted_at: Some(only.created_at), ..only }).unwrap();
+
+        let horizon = t0 + chrono::Duration::minutes(60);
+        let (concepts, relationships) = store.history_floors(horizon, false).unwrap();
+        assert_eq!((concepts, relationships), (vec![(deleted, 2)], vec![]));
+        let (mut concepts, _) = store.history_floors(horizon, true).unwrap();
+        concepts.sort();
+        let mut expected = vec![(deleted, 2), (expired, 2)];
+        expected.sort();
+        assert_eq!(concepts, expected);
+    }
 }
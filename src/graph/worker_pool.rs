@@ -0,0 +1,128 @@
+// Bounded pool for the engine's blocking work.
+
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{Semaphore, TryAcquireError};
+use tokio::task;
+
+use crate::error::{MnemonicError, Result};
+use crate::utils::metrics::{Gauge, WorkerPoolMetrics};
+
+/// Sizing for the engine's worker pool.
+#[derive(Debug, Clone)]
+pub struct WorkerPoolConfig {
+    /// How many operations may run on blocking threads at once.
+    pub max_concurrency: usize,
+    /// How many operations may wait for a free worker before new ones are rejected.
+    pub max_queue: usize,
+}
+
+impl Default for WorkerPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: std::thread::available_parallelism().map_or(4, |n| n.get()),
+            max_queue: 1024,
+        }
+    }
+}
+
+/// Runs blocking engine work on tokio's blocking threads, but never more than
+/// `max_concurrency` at a time. Work beyond that waits in a bounded queue, and once the queue
+/// is full new work fails fast with `MnemonicError::Overloaded`.
+#[derive(Debug)]
+pub struct WorkerPool {
+    permits: Arc<Semaphore>,
+    config: WorkerPoolConfig,
+    metrics: Arc<WorkerPoolMetrics>,
+}
+
+impl WorkerPool {
+    pub fn new(config: WorkerPoolConfig) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(config.max_concurrency.max(1))),
+            config,
+            metrics: Arc::new(WorkerPoolMetrics::default()),
+        }
+    }
+
+    /// Runs `f` on a worker, waiting for one to become free if necessary.
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let permit = match Arc::clone(&self.permits).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(TryAcquireError::Closed) => return Err(MnemonicError::EngineClosed),
+            Err(TryAcquireError::NoPermits) => {
+                // Every worker is busy, so get in line, unless the line is already full.
+                let queued = GaugeGuard::enter(&self.metrics.queued);
+                if queued.value > self.config.max_queue as u64 {
+                    self.metrics.rejected.increment();
+                    return Err(MnemonicError::Overloaded {
+                        queue_limit: self.config.max_queue,
+                    });
+                }
+                let waiting_since = Instant::now();
+                let permit = Arc::clone(&self.permits)
+                    .acquire_owned()
+                    .await
+                    .map_err(|_| MnemonicError::EngineClosed)?;
+                self.metrics
+                    .wait_micros
+                    .add(waiting_since.elapsed().as_micros() as u64);
+                permit
+            }
+        };
+
+        let metrics = Arc::clone(&self.metrics);
+        let result = task::spawn_blocking(move || {
+            // The permit is only released once the work is done, even if the caller gave up.
+            let _permit = permit;
+            let _running = GaugeGuard::enter(&metrics.running);
+            f()
+        })
+        .await;
+        self.metrics.completed.increment();
+        result?
+    }
+
+    /// Waits for the work already running or queued to finish, then turns away anything
+    /// new with `MnemonicError::EngineClosed`.
+    pub async fn close(&self) {
+        let workers = self.config.max_concurrency.max(1) as u32;
+        // The semaphore is fair, so this is granted only after every earlier waiter has run.
+        if let Ok(all) = self.permits.acquire_many(workers).await {
+            self.permits.close();
+            drop(all);
+        }
+    }
+
+    /// Returns the pool's load counters.
+    pub fn metrics(&self) -> Arc<WorkerPoolMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    pub fn config(&self) -> &WorkerPoolConfig {
+        &self.config
+    }
+}
+
+/// Holds a gauge up for as long as it lives, so cancelled waits are still counted down.
+struct GaugeGuard<'a> {
+    gauge: &'a Gauge,
+    value: u64,
+}
+
+impl<'a> GaugeGuard<'a> {
+    fn enter(gauge: &'a Gauge) -> Self {
+        let value = gauge.increment();
+        Self { gauge, value }
+    }
+}
+
+impl Drop for GaugeGuard<'_> {
+    fn drop(&mut self) {
+        self.gauge.decrement();
+    }
+}
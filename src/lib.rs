@@ -7,7 +7,15 @@ pub mod security;
 pub mod types;
 pub mod utils;
 pub mod error;
+#[cfg(feature = "server")]
 pub mod storage;
+#[cfg(feature = "server")]
 pub mod api;
+#[cfg(feature = "server")]
+pub mod cli;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(all(feature = "server", any(test, feature = "testing")))]
+pub mod testing;
 
-pub use error::{MnemonicError, Result};
\ No newline at end of file
+pub use error::{BatchItemError, ConflictInfo, ConflictItem, MnemonicError, Result};
\ No newline at end of file
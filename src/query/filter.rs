@@ -0,0 +1,325 @@
+// Matching concepts by predicates on their data, labels and timestamps, in one form every
+// endpoint that filters concepts can share: listings and aggregates take one as
+// `?predicate=<json>`, delete-where as the `predicate` of its filter, and /query as the
+// `predicate` of a node pattern. A predicate is plain JSON:
+//
+//     {"and": [{"label": "Task"},
+//              {"eq": {"path": "status", "value": "open"}},
+//              {"gt": {"path": "priority", "value": 2}},
+//              {"not": {"exists": {"path": "archived_at"}}},
+//              {"updated": {"since": "2026-01-01T00:00:00Z"}}]}
+//
+// Comparisons never coerce between types: a number only equals or orders against a number
+// (so `1` equals `1.0`), a string against a string, and the string "1" matches neither. A
+// comparison on a missing or null field is false, whatever the operator; wrap it in `not`
+// to match concepts lacking the field.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::types::concept::ConceptVersion;
+use crate::types::query::{labels_of, lookup, parse_data};
+
+/// A predicate on one concept version. Not to be confused with `types::query::Filter`, the
+/// older string-equality filter, which can carry one of these.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// The field equals the value.
+    Eq(FieldValue),
+    /// The field is present and doesn't equal the value.
+    Ne(FieldValue),
+    /// The field orders after the value: both numbers, or both strings.
+    Gt(FieldValue),
+    /// The field orders before the value: both numbers, or both strings.
+    Lt(FieldValue),
+    /// The field is a string containing the value as a substring, or an array holding an
+    /// element equal to it.
+    Contains(FieldValue),
+    /// The field is present and not null.
+    Exists(FieldPath),
+    /// A string in the concept's `labels` array.
+    Label(String),
+    /// When the concept was first written.
+    Created(TimeRange),
+    /// When this version of the concept was written.
+    Updated(TimeRange),
+    /// Every predicate holds; true when empty.
+    And(Vec<Predicate>),
+    /// Some predicate holds; false when empty.
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+/// A field path, as for `lookup`, and the value to compare it with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldValue {
+    pub path: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldPath {
+    pub path: String,
+}
+
+/// From `since`, inclusive, until `until`, exclusive. A missing end is unbounded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| since <= at) && self.until.is_none_or(|until| at < until)
+    }
+}
+
+/// A predicate an index answers without reading concept data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexedPredicate {
+    /// Answered by the label index.
+    Label(String),
+}
+
+/// How a predicate can be run: the concepts every `indexed` predicate names, narrowed by
+/// evaluating `residual` on each of them. With nothing indexed, every concept is a candidate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredicatePlan {
+    pub indexed: Vec<IndexedPredicate>,
+    /// What is left to evaluate; `None` when the indexes answer the whole predicate.
+    pub residual: Option<Predicate>,
+}
+
+impl Predicate {
+    /// Whether `version` passes. A version doesn't record when its concept was first written,
+    /// so `Created` is taken to mean this version's write time, which is right for a first
+    /// version; see `evaluate_created`.
+    pub fn evaluate(&self, version: &ConceptVersion) -> bool {
+        self.evaluate_created(version, version.created_at)
+    }
+
+    /// Whether `version` passes, for a concept first written at `created_at`.
+    pub fn evaluate_created(&self, version: &ConceptVersion, created_at: DateTime<Utc>) -> bool {
+        self.evaluate_parsed(version, &parse_data(&version.data), created_at)
+    }
+
+    /// `evaluate_created`, for a caller that has already parsed the version's data.
+    pub fn evaluate_parsed(&self, version: &ConceptVersion, data: &Value, created_at: DateTime<Utc>) -> bool {
+        self.matches(&Subject { version, data, created_at })
+    }
+
+    fn matches(&self, subject: &Subject<'_>) -> bool {
+        let field = |path: &str| lookup(subject.data, path).filter(|value| !value.is_null());
+        match self {
+            Predicate::Eq(f) => field(&f.path).is_some_and(|value| values_equal(value, &f.value)),
+            Predicate::Ne(f) => field(&f.path).is_some_and(|value| !values_equal(value, &f.value)),
+            Predicate::Gt(f) => field(&f.path).and_then(|value| compare(value, &f.value)) == Some(Ordering::Greater),
+            Predicate::Lt(f) => field(&f.path).and_then(|value| compare(value, &f.value)) == Some(Ordering::Less),
+            Predicate::Contains(f) => field(&f.path).is_some_and(|value| contains(value, &f.value)),
+            Predicate::Exists(f) => field(&f.path).is_some(),
+            Predicate::Label(label) => labels_of(subject.data).iter().any(|l| l == label),
+            Predicate::Created(range) => range.contains(subject.created_at),
+            Predicate::Updated(range) => range.contains(subject.version.created_at),
+            Predicate::And(predicates) => predicates.iter().all(|predicate| predicate.matches(subject)),
+            Predicate::Or(predicates) => predicates.iter().any(|predicate| predicate.matches(subject)),
+            Predicate::Not(predicate) => !predicate.matches(subject),
+        }
+    }
+
+    /// Splits the predicate into the predicates an index can answer and the rest. Only
+    /// predicates every match must satisfy, the top-level conjuncts, can narrow the
+    /// candidates; anything under `or` or `not` is left to evaluation.
+    pub fn plan(&self) -> PredicatePlan {
+        let mut conjuncts = Vec::new();
+        self.flatten_into(&mut conjuncts);
+        let mut indexed = Vec::new();
+        let mut rest = Vec::new();
+        for predicate in conjuncts {
+            match predicate {
+                Predicate::Label(label) => indexed.push(IndexedPredicate::Label(label.clone())),
+                other => rest.push(other.clone()),
+            }
+        }
+        let residual = match rest.len() {
+            0 => None,
+            1 => rest.pop(),
+            _ => Some(Predicate::And(rest)),
+        };
+        PredicatePlan { indexed, residual }
+    }
+
+    fn flatten_into<'a>(&'a self, conjuncts: &mut Vec<&'a Predicate>) {
+        match self {
+            Predicate::And(predicates) => predicates.iter().for_each(|predicate| predicate.flatten_into(conjuncts)),
+            other => conjuncts.push(other),
+        }
+    }
+}
+
+/// What a predicate is evaluated against: a version, its parsed data and its concept's creation.
+struct Subject<'a> {
+    version: &'a ConceptVersion,
+    data: &'a Value,
+    created_at: DateTime<Utc>,
+}
+
+/// JSON equality, except that numbers are equal by value whatever their representation.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b)),
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| values_equal(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+/// The order of two numbers or two strings; other pairs don't order.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn contains(field: &Value, value: &Value) -> bool {
+    match (field, value) {
+        (Value::String(field), Value::String(value)) => field.contains(value.as_str()),
+        (Value::Array(items), value) => items.iter().any(|item| values_equal(item, value)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::concept::ConceptData;
+    use chrono::TimeZone;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn version(data: Value) -> ConceptVersion {
+        ConceptVersion {
+            concept_id: Uuid::new_v4(),
+            version: 2,
+            data: ConceptData::Structured(data.to_string().into()),
+            created_at: Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap(),
+            created_by: Uuid::nil(),
+            deleted_at: None,
+            deleted_by: None,
+            expires_at: None,
+            tags: Default::default(),
+        }
+    }
+
+    fn parse(filter: Value) -> Predicate {
+        serde_json::from_value(filter).unwrap()
+    }
+
+    fn task() -> ConceptVersion {
+        version(json!({
+            "labels": ["Task"],
+            "title": "Write the filter",
+            "priority": 3,
+            "ratio": 0.5,
+            "tags": ["rust", 7],
+            "owner": {"name": "ada"},
+            "closed_at": null,
+        }))
+    }
+
+    #[test]
+    fn test_each_operator_on_present_fields() {
+        let passes = |filter: Value| parse(filter).evaluate(&task());
+        assert!(passes(json!({"eq": {"path": "owner.name", "value": "ada"}})));
+        assert!(!passes(json!({"eq": {"path": "owner.name", "value": "bob"}})));
+        assert!(passes(json!({"eq": {"path": "owner", "value": {"name": "ada"}}})));
+        assert!(passes(json!({"ne": {"path": "priority", "value": 4}})));
+        assert!(!passes(json!({"ne": {"path": "priority", "value": 3}})));
+        assert!(passes(json!({"gt": {"path": "priority", "value": 2}})));
+        assert!(!passes(json!({"gt": {"path": "priority", "value": 3}})));
+        assert!(passes(json!({"lt": {"path": "ratio", "value": 1}})));
+        assert!(passes(json!({"gt": {"path": "title", "value": "A"}})));
+        assert!(passes(json!({"contains": {"path": "title", "value": "filter"}})));
+        assert!(passes(json!({"contains": {"path": "tags", "value": "rust"}})));
+        assert!(passes(json!({"contains": {"path": "tags", "value": 7.0}})));
+        assert!(!passes(json!({"contains": {"path": "priority", "value": 3}})));
+        assert!(passes(json!({"exists": {"path": "tags.1"}})));
+        assert!(passes(json!({"label": "Task"})));
+        assert!(!passes(json!({"label": "Person"})));
+    }
+
+    #[test]
+    fn test_missing_and_null_fields_fail_every_comparison() {
+        let passes = |filter: Value| parse(filter).evaluate(&task());
+        for path in ["missing", "owner.missing", "tags.9", "closed_at"] {
+            for op in ["eq", "ne", "gt", "lt", "contains"] {
+                assert!(!passes(json!({op: {"path": path, "value": 1}})), "{} on {}", op, path);
+            }
+            assert!(!passes(json!({"exists": {"path": path}})));
+            assert!(passes(json!({"not": {"exists": {"path": path}}})));
+        }
+        assert!(!parse(json!({"label": "Task"})).evaluate(&version(Value::Null)));
+    }
+
+    #[test]
+    fn test_numbers_and_strings_never_compare_equal_or_ordered() {
+        let passes = |filter: Value| parse(filter).evaluate(&task());
+        assert!(passes(json!({"eq": {"path": "priority", "value": 3.0}})));
+        assert!(!passes(json!({"eq": {"path": "priority", "value": "3"}})));
+        assert!(passes(json!({"ne": {"path": "priority", "value": "3"}})));
+        assert!(!passes(json!({"gt": {"path": "priority", "value": "1"}})));
+        assert!(!passes(json!({"lt": {"path": "priority", "value": "9"}})));
+        assert!(!passes(json!({"contains": {"path": "tags", "value": "7"}})));
+        assert!(!passes(json!({"eq": {"path": "owner", "value": "ada"}})));
+    }
+
+    #[test]
+    fn test_nesting_and_time_ranges() {
+        let filter = parse(json!({"and": [
+            {"label": "Task"},
+            {"or": [{"eq": {"path": "priority", "value": 1}}, {"gt": {"path": "priority", "value": 2}}]},
+            {"not": {"contains": {"path": "tags", "value": "go"}}},
+            {"updated": {"since": "2026-02-01T00:00:00Z", "until": "2026-03-02T00:00:00Z"}},
+        ]}));
+        assert!(filter.evaluate(&task()));
+        assert!(!parse(json!({"or": []})).evaluate(&task()));
+        assert!(parse(json!({"and": []})).evaluate(&task()));
+
+        let march = Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap();
+        assert!(!parse(json!({"updated": {"until": march}})).evaluate(&task()));
+        assert!(parse(json!({"updated": {"since": march}})).evaluate(&task()));
+        let created = parse(json!({"created": {"until": "2026-02-01T00:00:00Z"}}));
+        assert!(!created.evaluate(&task()));
+        let january = Utc.with_ymd_and_hms(2026, 1, 15, 0, 0, 0).unwrap();
+        assert!(created.evaluate_created(&task(), january));
+    }
+
+    #[test]
+    fn test_the_plan_indexes_top_level_labels_only() {
+        let status = Predicate::Eq(FieldValue { path: "status".to_string(), value: json!("open") });
+        let plan = parse(json!({"and": [
+            {"label": "Task"},
+            {"and": [{"label": "Urgent"}, {"eq": {"path": "status", "value": "open"}}]},
+        ]}))
+        .plan();
+        assert_eq!(
+            plan.indexed,
+            vec![IndexedPredicate::Label("Task".to_string()), IndexedPredicate::Label("Urgent".to_string())]
+        );
+        assert_eq!(plan.residual, Some(status));
+
+        assert_eq!(parse(json!({"label": "Task"})).plan().residual, None);
+        let either = parse(json!({"or": [{"label": "Task"}, {"label": "Bug"}]}));
+        let plan = either.plan();
+        assert!(plan.indexed.is_empty());
+        assert_eq!(plan.residual, Some(either));
+    }
+}
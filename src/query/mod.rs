@@ -3,3 +3,4 @@
 pub mod parser;
 pub mod optimizer;
 pub mod executor;
+pub mod filter;
@@ -0,0 +1,1085 @@
+// How concept and relationship records, and their versions, are laid out on disk in every
+// format they have been written in. bincode is positional: bytes only decode into the exact
+// struct that encoded them. So each format's layout is frozen here, not borrowed from
+// `types`, and a field added to `Concept` changes nothing that is already stored.
+//
+// - Unversioned: raw bincode of the record, as written before formats had numbers. It starts
+//   with 0x10, the length of the record's id. Comes in two layouts, before and after
+//   `expires_at` (concepts) and `directed` (relationships).
+// - Sealed: 0xE5, then a concept whose data is an encryption envelope. Written by the first
+//   encryption-at-rest support.
+// - V1: `[1][bincode]`, with concept data either plain or sealed. Relationship head records
+//   are still written in it.
+// - V2: `[2][CBOR map]`, for concept records and for concept and relationship versions, which
+//   keep gaining fields.
+//   A map names its fields, so a field added at the end with `#[serde(default)]` needs no new
+//   format: older records decode with the default, and fields a newer build wrote that this
+//   one doesn't know are skipped. Never rename or reorder V2 fields; the golden tests below
+//   pin their bytes.
+// - Blobs: `[2][CBOR]` of V2 data, plain or sealed, in the 'blobs' cabinet. A concept or
+//   version record whose data is a blob holds only its content hash; see `BlobSource`.
+//
+// New writes use `latest_format`; reads take any of them. A change a V2 default can't cover
+// (or a bincode layout change) needs a new format number, with the old decoder kept.
+
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::{Concept, ConceptData, ConceptMetadata, ConceptVersion, TransactionId};
+use crate::types::content::ContentHash;
+use crate::types::relationship::{Relationship, RelationshipMetadata, RelationshipVersion};
+use super::encryption::{self, DataCipher};
+use super::keys;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+pub const V1: u8 = 1;
+pub const V2: u8 = 2;
+
+/// The format new records under the item key tag `tag` are written in.
+pub fn latest_format(tag: u8) -> u8 {
+    match tag {
+        keys::CONCEPT | keys::CONCEPT_VERSION | keys::RELATIONSHIP_VERSION => V2,
+        _ => V1,
+    }
+}
+
+/// The first byte of a record in the sealed format.
+const SEALED: u8 = 0xE5;
+/// The first byte of an unversioned record: bincode's length prefix of a 16-byte id.
+const UNVERSIONED: u8 = 0x10;
+
+/// The format a stored record is in, from its first byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Unversioned,
+    Sealed,
+    Version(u8),
+}
+
+pub fn format_of(value: &[u8]) -> RecordFormat {
+    match value.first() {
+        Some(&UNVERSIONED) => RecordFormat::Unversioned,
+        Some(&SEALED) => RecordFormat::Sealed,
+        Some(&version) => RecordFormat::Version(version),
+        None => RecordFormat::Version(0),
+    }
+}
+
+/// The outcome of `RocksBackend::migrate_records`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordMigrationReport {
+    /// Records rewritten in the latest format.
+    pub migrated: usize,
+    /// Records that were in it already.
+    pub current: usize,
+}
+
+/// Where the data of records that refer to a blob is read from.
+pub(crate) trait BlobSource {
+    /// The stored blob with this content hash.
+    fn blob(&self, hash: ContentHash) -> Result<Vec<u8>>;
+
+    /// The data the blob with this content hash holds. A source that keeps decoded blobs
+    /// can hand every record referring to one the same payload.
+    fn blob_data(&self, hash: ContentHash, cipher: Option<&DataCipher>) -> Result<ConceptData> {
+        decode_blob(&self.blob(hash)?, hash, cipher)
+    }
+}
+
+/// For reads with no 'blobs' cabinet behind them: a record that refers to a blob fails.
+pub(crate) struct NoBlobs;
+
+impl BlobSource for NoBlobs {
+    fn blob(&self, hash: ContentHash) -> Result<Vec<u8>> {
+        Err(MnemonicError::Internal(format!("Record refers to blob {:032x}, but no blobs were given", hash)))
+    }
+}
+
+pub(crate) fn encode_concept(concept: &Concept, cipher: Option<&DataCipher>) -> Result<Vec<u8>> {
+    encode_concept_with(concept, cipher, None)
+}
+
+/// Like `encode_concept`, but with `blob` set the record refers to that blob for its data.
+pub(crate) fn encode_concept_with(
+    concept: &Concept,
+    cipher: Option<&DataCipher>,
+    blob: Option<ContentHash>,
+) -> Result<Vec<u8>> {
+    let mut record = ConceptV2::from(concept);
+    if let Some(hash) = blob {
+        record.data = DataV2::blob(hash);
+    } else if let Some(cipher) = cipher {
+        record.data = record.data.seal(&concept.id, cipher)?;
+    }
+    map_envelope(V2, &record)
+}
+
+pub(crate) fn decode_concept(value: &[u8], cipher: Option<&DataCipher>) -> Result<Concept> {
+    decode_concept_with(value, cipher, &NoBlobs)
+}
+
+/// Like `decode_concept`, reading the blob a record refers to from `blobs`.
+pub(crate) fn decode_concept_with(value: &[u8], cipher: Option<&DataCipher>, blobs: &impl BlobSource) -> Result<Concept> {
+    match format_of(value) {
+        RecordFormat::Version(V2) => map_payload::<ConceptV2>(value)?.into_concept(cipher, blobs),
+        RecordFormat::Version(V1) => {
+            let record: ConceptV1 = bincode::deserialize(&value[1..])?;
+            Ok(Concept {
+                data: record.data.open(&record.id, cipher)?,
+                id: record.id,
+                metadata: record.metadata.into(),
+                expires_at: record.expires_at,
+                tags: BTreeSet::new(),
+            })
+        }
+        RecordFormat::Sealed => {
+            let record: SealedConcept = bincode::deserialize(&value[1..])?;
+            Ok(Concept {
+                data: open_data(&record.data, &record.id, cipher)?,
+                id: record.id,
+                metadata: record.metadata.into(),
+                expires_at: record.expires_at,
+                tags: BTreeSet::new(),
+            })
+        }
+        RecordFormat::Unversioned => match bincode::deserialize::<ConceptV0>(value) {
+            Ok(record) => Ok(Concept {
+                id: record.id,
+                data: record.data.into(),
+                metadata: record.metadata.into(),
+                expires_at: record.expires_at,
+                tags: BTreeSet::new(),
+            }),
+            Err(e) => match bincode::deserialize::<ConceptV0NoExpiry>(value) {
+                Ok(record) => Ok(Concept {
+                    id: record.id,
+                    data: record.data.into(),
+                    metadata: record.metadata.into(),
+                    expires_at: None,
+                    tags: BTreeSet::new(),
+                }),
+                Err(_) => Err(e.into()),
+            },
+        },
+        RecordFormat::Version(version) => Err(unknown_format(version)),
+    }
+}
+
+pub(crate) fn encode_concept_version(version: &ConceptVersion, cipher: Option<&DataCipher>) -> Result<Vec<u8>> {
+    encode_concept_version_with(version, cipher, None)
+}
+
+/// Like `encode_concept_version`, but with `blob` set the record refers to that blob for its data.
+pub(crate) fn encode_concept_version_with(
+    version: &ConceptVersion,
+    cipher: Option<&DataCipher>,
+    blob: Option<ContentHash>,
+) -> Result<Vec<u8>> {
+    let mut record = ConceptVersionV2::from(version);
+    if let Some(hash) = blob {
+        record.data = DataV2::blob(hash);
+    } else if let Some(cipher) = cipher {
+        record.data = record.data.seal(&version.concept_id, cipher)?;
+    }
+    map_envelope(V2, &record)
+}
+
+pub(crate) fn decode_concept_version(value: &[u8], cipher: Option<&DataCipher>) -> Result<ConceptVersion> {
+    decode_concept_version_with(value, cipher, &NoBlobs)
+}
+
+/// Like `decode_concept_version`, reading the blob a record refers to from `blobs`.
+pub(crate) fn decode_concept_version_with(
+    value: &[u8],
+    cipher: Option<&DataCipher>,
+    blobs: &impl BlobSource,
+) -> Result<ConceptVersion> {
+    match format_of(value) {
+        RecordFormat::Version(V2) => map_payload::<ConceptVersionV2>(value)?.into_version(cipher, blobs),
+        RecordFormat::Version(V1) => {
+            let record: ConceptVersionV1 = bincode::deserialize(&value[1..])?;
+            Ok(ConceptVersion {
+                data: record.data.open(&record.concept_id, cipher)?,
+                concept_id: record.concept_id,
+                version: record.version,
+                created_at: record.created_at,
+                created_by: record.created_by,
+                deleted_at: record.deleted_at,
+                deleted_by: record.deleted_by,
+                expires_at: record.expires_at,
+                tags: BTreeSet::new(),
+            })
+        }
+        RecordFormat::Sealed => {
+            let record: SealedConceptVersion = bincode::deserialize(&value[1..])?;
+            Ok(ConceptVersion {
+                data: open_data(&record.data, &record.concept_id, cipher)?,
+                concept_id: record.concept_id,
+                version: record.version,
+                created_at: record.created_at,
+                created_by: record.created_by,
+                deleted_at: record.deleted_at,
+                deleted_by: record.deleted_by,
+                expires_at: record.expires_at,
+                tags: BTreeSet::new(),
+            })
+        }
+        RecordFormat::Unversioned => match bincode::deserialize::<ConceptVersionV0>(value) {
+            Ok(record) => Ok(ConceptVersion {
+                concept_id: record.concept_id,
+                version: record.version,
+                data: record.data.into(),
+                created_at: record.created_at,
+                created_by: record.created_by,
+                deleted_at: record.deleted_at,
+                deleted_by: record.deleted_by,
+                expires_at: record.expires_at,
+                tags: BTreeSet::new(),
+            }),
+            Err(e) => match bincode::deserialize::<ConceptVersionV0NoExpiry>(value) {
+                Ok(record) => Ok(ConceptVersion {
+                    concept_id: record.concept_id,
+                    version: record.version,
+                    data: record.data.into(),
+                    created_at: record.created_at,
+                    created_by: record.created_by,
+                    deleted_at: record.deleted_at,
+                    deleted_by: record.deleted_by,
+                    expires_at: None,
+                    tags: BTreeSet::new(),
+                }),
+                Err(_) => Err(e.into()),
+            },
+        },
+        RecordFormat::Version(version) => Err(unknown_format(version)),
+    }
+}
+
+pub(crate) fn encode_relationship(relationship: &Relationship) -> Result<Vec<u8>> {
+    let record = RelationshipV1 {
+        id: relationship.id,
+        source: relationship.source,
+        relationship_type: relationship.relationship_type.to_string(),
+        target: relationship.target,
+        metadata: (&relationship.metadata).into(),
+        directed: relationship.directed,
+    };
+    envelope(V1, &record)
+}
+
+pub(crate) fn decode_relationship(value: &[u8]) -> Result<Relationship> {
+    // V1 and the unversioned layout happen to agree; only the framing differs.
+    let (value, fallback) = match format_of(value) {
+        RecordFormat::Version(V1) => (&value[1..], false),
+        RecordFormat::Unversioned => (value, true),
+        RecordFormat::Sealed => return Err(unknown_format(SEALED)),
+        RecordFormat::Version(version) => return Err(unknown_format(version)),
+    };
+    match bincode::deserialize::<RelationshipV1>(value) {
+        Ok(record) => Ok(Relationship {
+            id: record.id,
+            source: record.source,
+            relationship_type: record.relationship_type.into(),
+            target: record.target,
+            metadata: record.metadata.into(),
+            directed: record.directed,
+        }),
+        Err(e) if fallback => match bincode::deserialize::<RelationshipV0Undirected>(value) {
+            Ok(record) => Ok(Relationship {
+                id: record.id,
+                source: record.source,
+                relationship_type: record.relationship_type.into(),
+                target: record.target,
+                metadata: record.metadata.into(),
+                directed: true,
+            }),
+            Err(_) => Err(e.into()),
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) fn encode_relationship_version(version: &RelationshipVersion) -> Result<Vec<u8>> {
+    map_envelope(V2, &RelationshipVersionV2::from(version))
+}
+
+pub(crate) fn decode_relationship_version(value: &[u8]) -> Result<RelationshipVersion> {
+    let (value, fallback) = match format_of(value) {
+        RecordFormat::Version(V2) => return Ok(map_payload::<RelationshipVersionV2>(value)?.into()),
+        RecordFormat::Version(V1) => (&value[1..], false),
+        RecordFormat::Unversioned => (value, true),
+        RecordFormat::Sealed => return Err(unknown_format(SEALED)),
+        RecordFormat::Version(version) => return Err(unknown_format(version)),
+    };
+    match bincode::deserialize::<RelationshipVersionV1>(value) {
+        Ok(record) => Ok(RelationshipVersion {
+            relationship_id: record.relationship_id,
+            version: record.version,
+            source: record.source,
+            relationship_type: record.relationship_type.into(),
+            target: record.target,
+            created_at: record.created_at,
+            created_by: record.created_by,
+            deleted_at: record.deleted_at,
+            deleted_by: record.deleted_by,
+            directed: record.directed,
+        }),
+        Err(e) if fallback => match bincode::deserialize::<RelationshipVersionV0Undirected>(value) {
+            Ok(record) => Ok(RelationshipVersion {
+                relationship_id: record.relationship_id,
+                version: record.version,
+                source: record.source,
+                relationship_type: record.relationship_type.into(),
+                target: record.target,
+                created_at: record.created_at,
+                created_by: record.created_by,
+                deleted_at: record.deleted_at,
+                deleted_by: record.deleted_by,
+                directed: true,
+            }),
+            Err(_) => Err(e.into()),
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Re-encodes a record stored under the item key tag `tag` in the latest format.
+pub(crate) fn upgrade(tag: u8, value: &[u8], cipher: Option<&DataCipher>) -> Result<Vec<u8>> {
+    match tag {
+        keys::CONCEPT => encode_concept(&decode_concept(value, cipher)?, cipher),
+        keys::CONCEPT_VERSION => encode_concept_version(&decode_concept_version(value, cipher)?, cipher),
+        keys::RELATIONSHIP => encode_relationship(&decode_relationship(value)?),
+        keys::RELATIONSHIP_VERSION => encode_relationship_version(&decode_relationship_version(value)?),
+        other => Err(MnemonicError::Internal(format!("No record format for key tag {:#04x}", other))),
+    }
+}
+
+/// A blob holding `data`, sealed under its hash when there is a key. Blobs are shared by
+/// every record with the same data, so unlike record data they aren't tied to one concept.
+pub(crate) fn encode_blob(data: &ConceptData, hash: ContentHash, cipher: Option<&DataCipher>) -> Result<Vec<u8>> {
+    let mut blob = DataV2::Plain(data.into());
+    if let Some(cipher) = cipher {
+        blob = blob.seal(&Uuid::from_u128(hash), cipher)?;
+    }
+    map_envelope(V2, &blob)
+}
+
+pub(crate) fn decode_blob(value: &[u8], hash: ContentHash, cipher: Option<&DataCipher>) -> Result<ConceptData> {
+    match format_of(value) {
+        RecordFormat::Version(V2) => match map_payload::<DataV2>(value)? {
+            DataV2::Blob(_) => Err(MnemonicError::Internal(format!("Blob {:032x} refers to another blob", hash))),
+            data => data.open(&Uuid::from_u128(hash), cipher, &NoBlobs),
+        },
+        RecordFormat::Version(version) => Err(unknown_format(version)),
+        _ => Err(MnemonicError::Internal(format!("Blob {:032x} is not in a blob format", hash))),
+    }
+}
+
+/// The blob a V2 concept or version record refers to for its data, if it does.
+pub(crate) fn blob_of(value: &[u8]) -> Option<ContentHash> {
+    #[derive(Deserialize)]
+    struct DataOnly {
+        data: DataV2,
+    }
+    if format_of(value) != RecordFormat::Version(V2) {
+        return None;
+    }
+    match map_payload::<DataOnly>(value).ok()?.data {
+        DataV2::Blob(hash) => Some(ContentHash::from_be_bytes(hash.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn envelope(format: u8, record: &impl Serialize) -> Result<Vec<u8>> {
+    let mut value = vec![format];
+    bincode::serialize_into(&mut value, record)?;
+    Ok(value)
+}
+
+fn map_envelope(format: u8, record: &impl Serialize) -> Result<Vec<u8>> {
+    let mut value = vec![format];
+    ciborium::into_writer(record, &mut value)
+        .map_err(|e| MnemonicError::Internal(format!("Could not encode a record: {}", e)))?;
+    Ok(value)
+}
+
+fn map_payload<T: serde::de::DeserializeOwned>(value: &[u8]) -> Result<T> {
+    ciborium::from_reader(&value[1..]).map_err(|e| MnemonicError::Internal(format!("Unreadable record: {}", e)))
+}
+
+fn unknown_format(version: u8) -> MnemonicError {
+    MnemonicError::Internal(format!("Record is in format {:#04x}, which this build can't read", version))
+}
+
+fn open_data(envelope: &[u8], id: &Uuid, cipher: Option<&DataCipher>) -> Result<ConceptData> {
+    let cipher = cipher.ok_or_else(encryption::no_key)?;
+    let data: ConceptDataV1 = bincode::deserialize(&cipher.open(envelope, id.as_bytes())?)?;
+    Ok(data.into())
+}
+
+// --- V2 ---
+
+/// Like `DataV1`, but a sealed payload is a CBOR byte string rather than a list of numbers,
+/// and the data may be a blob stored apart, named by its big-endian content hash.
+#[derive(Serialize, Deserialize)]
+enum DataV2 {
+    Plain(ConceptDataV1),
+    Sealed(#[serde(with = "byte_string")] Vec<u8>),
+    Blob(#[serde(with = "byte_string")] Vec<u8>),
+}
+
+impl DataV2 {
+    fn blob(hash: ContentHash) -> Self {
+        DataV2::Blob(hash.to_be_bytes().to_vec())
+    }
+
+    fn seal(self, id: &Uuid, cipher: &DataCipher) -> Result<Self> {
+        Ok(match self {
+            DataV2::Plain(data) => DataV2::Sealed(cipher.seal(&bincode::serialize(&data)?, id.as_bytes())?),
+            stored => stored,
+        })
+    }
+
+    fn open(self, id: &Uuid, cipher: Option<&DataCipher>, blobs: &impl BlobSource) -> Result<ConceptData> {
+        match self {
+            DataV2::Plain(data) => Ok(data.into()),
+            DataV2::Sealed(envelope) => open_data(&envelope, id, cipher),
+            DataV2::Blob(hash) => {
+                let hash = <[u8; 16]>::try_from(hash.as_slice())
+                    .map(ContentHash::from_be_bytes)
+                    .map_err(|_| MnemonicError::Internal(format!("Record for {} has a malformed blob hash", id)))?;
+                blobs.blob_data(hash, cipher)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConceptVersionV2 {
+    concept_id: Uuid,
+    version: u64,
+    data: DataV2,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    #[serde(default)]
+    deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    deleted_by: Option<TransactionId>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    // Left out while empty, so untagged versions encode as they did before tags.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConceptV2 {
+    id: Uuid,
+    data: DataV2,
+    metadata: ConceptMetadataV1,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RelationshipVersionV2 {
+    relationship_id: Uuid,
+    version: u64,
+    source: Uuid,
+    relationship_type: String,
+    target: Uuid,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    #[serde(default)]
+    deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    deleted_by: Option<TransactionId>,
+    #[serde(default = "directed_by_default")]
+    directed: bool,
+}
+
+fn directed_by_default() -> bool {
+    true
+}
+
+/// Plain data; `encode_concept_version` seals it when there is a key.
+impl From<&ConceptVersion> for ConceptVersionV2 {
+    fn from(version: &ConceptVersion) -> Self {
+        Self {
+            concept_id: version.concept_id,
+            version: version.version,
+            data: DataV2::Plain((&version.data).into()),
+            created_at: version.created_at,
+            created_by: version.created_by,
+            deleted_at: version.deleted_at,
+            deleted_by: version.deleted_by,
+            expires_at: version.expires_at,
+            tags: version.tags.clone(),
+        }
+    }
+}
+
+/// Plain data; `encode_concept` seals it when there is a key.
+impl From<&Concept> for ConceptV2 {
+    fn from(concept: &Concept) -> Self {
+        Self {
+            id: concept.id,
+            data: DataV2::Plain((&concept.data).into()),
+            metadata: (&concept.metadata).into(),
+            expires_at: concept.expires_at,
+            tags: concept.tags.clone(),
+        }
+    }
+}
+
+impl ConceptV2 {
+    fn into_concept(self, cipher: Option<&DataCipher>, blobs: &impl BlobSource) -> Result<Concept> {
+        Ok(Concept {
+            data: self.data.open(&self.id, cipher, blobs)?,
+            id: self.id,
+            metadata: self.metadata.into(),
+            expires_at: self.expires_at,
+            tags: self.tags,
+        })
+    }
+}
+
+impl ConceptVersionV2 {
+    /// The other direction, which needs the key if the data is sealed.
+    fn into_version(self, cipher: Option<&DataCipher>, blobs: &impl BlobSource) -> Result<ConceptVersion> {
+        Ok(ConceptVersion {
+            data: self.data.open(&self.concept_id, cipher, blobs)?,
+            concept_id: self.concept_id,
+            version: self.version,
+            created_at: self.created_at,
+            created_by: self.created_by,
+            deleted_at: self.deleted_at,
+            deleted_by: self.deleted_by,
+            expires_at: self.expires_at,
+            tags: self.tags,
+        })
+    }
+}
+
+impl From<&RelationshipVersion> for RelationshipVersionV2 {
+    fn from(version: &RelationshipVersion) -> Self {
+        Self {
+            relationship_id: version.relationship_id,
+            version: version.version,
+            source: version.source,
+            relationship_type: version.relationship_type.to_string(),
+            target: version.target,
+            created_at: version.created_at,
+            created_by: version.created_by,
+            deleted_at: version.deleted_at,
+            deleted_by: version.deleted_by,
+            directed: version.directed,
+        }
+    }
+}
+
+impl From<RelationshipVersionV2> for RelationshipVersion {
+    fn from(version: RelationshipVersionV2) -> Self {
+        Self {
+            relationship_id: version.relationship_id,
+            version: version.version,
+            source: version.source,
+            relationship_type: version.relationship_type.into(),
+            target: version.target,
+            created_at: version.created_at,
+            created_by: version.created_by,
+            deleted_at: version.deleted_at,
+            deleted_by: version.deleted_by,
+            directed: version.directed,
+        }
+    }
+}
+
+/// `Vec<u8>` as a CBOR byte string.
+mod byte_string {
+    use serde::de::{Error, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        struct Bytes;
+
+        impl<'de> Visitor<'de> for Bytes {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte string")
+            }
+
+            fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(bytes.to_vec())
+            }
+
+            fn visit_byte_buf<E: Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(bytes)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+                let mut bytes = Vec::new();
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(bytes)
+            }
+        }
+
+        deserializer.deserialize_byte_buf(Bytes)
+    }
+}
+
+// --- V1 ---
+
+#[derive(Serialize, Deserialize)]
+enum ConceptDataV1 {
+    Empty,
+    Structured(String),
+}
+
+/// Concept data in the clear, or sealed by a `DataCipher` with the concept's id as associated
+/// data. A sealed payload is bincode of `ConceptDataV1`.
+#[derive(Serialize, Deserialize)]
+enum DataV1 {
+    Plain(ConceptDataV1),
+    Sealed(Vec<u8>),
+}
+
+impl DataV1 {
+    fn open(self, id: &Uuid, cipher: Option<&DataCipher>) -> Result<ConceptData> {
+        match self {
+            DataV1::Plain(data) => Ok(data.into()),
+            DataV1::Sealed(envelope) => open_data(&envelope, id, cipher),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConceptMetadataV1 {
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    version: u64,
+    transaction_id: TransactionId,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConceptV1 {
+    id: Uuid,
+    data: DataV1,
+    metadata: ConceptMetadataV1,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConceptVersionV1 {
+    concept_id: Uuid,
+    version: u64,
+    data: DataV1,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<TransactionId>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RelationshipMetadataV1 {
+    created_at: DateTime<Utc>,
+    version: u64,
+    transaction_id: TransactionId,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RelationshipV1 {
+    id: Uuid,
+    source: Uuid,
+    relationship_type: String,
+    target: Uuid,
+    metadata: RelationshipMetadataV1,
+    directed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RelationshipVersionV1 {
+    relationship_id: Uuid,
+    version: u64,
+    source: Uuid,
+    relationship_type: String,
+    target: Uuid,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<TransactionId>,
+    directed: bool,
+}
+
+// --- Sealed ---
+
+#[derive(Deserialize)]
+struct SealedConcept {
+    id: Uuid,
+    data: Vec<u8>,
+    metadata: ConceptMetadataV1,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct SealedConceptVersion {
+    concept_id: Uuid,
+    version: u64,
+    data: Vec<u8>,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<TransactionId>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+// --- Unversioned ---
+// Relationships after `directed` are laid out exactly as `RelationshipV1`.
+
+#[derive(Deserialize)]
+struct ConceptV0 {
+    id: Uuid,
+    data: ConceptDataV1,
+    metadata: ConceptMetadataV1,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct ConceptV0NoExpiry {
+    id: Uuid,
+    data: ConceptDataV1,
+    metadata: ConceptMetadataV1,
+}
+
+#[derive(Deserialize)]
+struct ConceptVersionV0 {
+    concept_id: Uuid,
+    version: u64,
+    data: ConceptDataV1,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<TransactionId>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct ConceptVersionV0NoExpiry {
+    concept_id: Uuid,
+    version: u64,
+    data: ConceptDataV1,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<TransactionId>,
+}
+
+#[derive(Deserialize)]
+struct RelationshipV0Undirected {
+    id: Uuid,
+    source: Uuid,
+    relationship_type: String,
+    target: Uuid,
+    metadata: RelationshipMetadataV1,
+}
+
+#[derive(Deserialize)]
+struct RelationshipVersionV0Undirected {
+    relationship_id: Uuid,
+    version: u64,
+    source: Uuid,
+    relationship_type: String,
+    target: Uuid,
+    created_at: DateTime<Utc>,
+    created_by: TransactionId,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<TransactionId>,
+}
+
+// --- Conversions between the frozen layouts and the live types ---
+
+impl From<&ConceptData> for ConceptDataV1 {
+    fn from(data: &ConceptData) -> Self {
+        match data {
+            ConceptData::Empty => ConceptDataV1::Empty,
+            ConceptData::Structured(json) => ConceptDataV1::Structured(json.to_string()),
+        }
+    }
+}
+
+impl From<ConceptDataV1> for ConceptData {
+    fn from(data: ConceptDataV1) -> Self {
+        match data {
+            ConceptDataV1::Empty => ConceptData::Empty,
+            ConceptDataV1::Structured(json) => ConceptData::Structured(json.into()),
+        }
+    }
+}
+
+impl From<&ConceptMetadata> for ConceptMetadataV1 {
+    fn from(metadata: &ConceptMetadata) -> Self {
+        Self {
+            created_at: metadata.created_at,
+            updated_at: metadata.updated_at,
+            version: metadata.version,
+            transaction_id: metadata.transaction_id,
+        }
+    }
+}
+
+impl From<ConceptMetadataV1> for ConceptMetadata {
+    fn from(metadata: ConceptMetadataV1) -> Self {
+        Self {
+            created_at: metadata.created_at,
+            updated_at: metadata.updated_at,
+            version: metadata.version,
+            transaction_id: metadata.transaction_id,
+        }
+    }
+}
+
+impl From<&RelationshipMetadata> for RelationshipMetadataV1 {
+    fn from(metadata: &RelationshipMetadata) -> Self {
+        Self {
+            created_at: metadata.created_at,
+            version: metadata.version,
+            transaction_id: metadata.transaction_id,
+        }
+    }
+}
+
+impl From<RelationshipMetadataV1> for RelationshipMetadata {
+    fn from(metadata: RelationshipMetadataV1) -> Self {
+        Self {
+            created_at: metadata.created_at,
+            version: metadata.version,
+            transaction_id: metadata.transaction_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::EncryptionKey;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn at() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    // Captured from databases written before records had a format byte, with ids 0xa1 (the
+    // concept and source), 0xb2 (the target), 0xc3 (the relationship) and 0xd4 (the
+    // transaction), every timestamp 2023-11-14T22:13:20Z.
+    const UNVERSIONED_CONCEPT: &str = "1000000000000000000000000000000000000000000000a1010000000e000000000000007b226e616d65223a22416461227d1400000000000000323032332d31312d31345432323a31333a32305a1400000000000000323032332d31312d31345432323a31333a32305a02000000000000001000000000000000000000000000000000000000000000d4011400000000000000323032332d31312d31345432323a31333a32305a";
+    const UNVERSIONED_CONCEPT_VERSION: &str = "1000000000000000000000000000000000000000000000a10200000000000000000000001400000000000000323032332d31312d31345432323a31333a32305a1000000000000000000000000000000000000000000000d4011400000000000000323032332d31312d31345432323a31333a32305a011000000000000000000000000000000000000000000000d400";
+    const UNVERSIONED_RELATIONSHIP: &str = "1000000000000000000000000000000000000000000000c31000000000000000000000000000000000000000000000a105000000000000006b6e6f77731000000000000000000000000000000000000000000000b21400000000000000323032332d31312d31345432323a31333a32305a01000000000000001000000000000000000000000000000000000000000000d400";
+    const UNVERSIONED_RELATIONSHIP_VERSION: &str = "1000000000000000000000000000000000000000000000c301000000000000001000000000000000000000000000000000000000000000a105000000000000006b6e6f77731000000000000000000000000000000000000000000000b21400000000000000323032332d31312d31345432323a31333a32305a1000000000000000000000000000000000000000000000d4000000";
+
+    // The V2 encodings of `concept_version()` and `relationship_version()`. If a change makes
+    // these tests fail, it changed the disk format: existing databases would no longer read
+    // back the same. Add a field with a default instead, or bump the format.
+    const GOLDEN_CONCEPT_VERSION: &str = "02a86a636f6e636570745f696450000000000000000000000000000000a16776657273696f6e026464617461a165506c61696e65456d7074796a637265617465645f617474323032332d31312d31345432323a31333a32305a6a637265617465645f627950000000000000000000000000000000d46a64656c657465645f617474323032332d31312d31345432323a31333a32305a6a64656c657465645f627950000000000000000000000000000000d46a657870697265735f6174f6";
+    const GOLDEN_RELATIONSHIP_VERSION: &str = "02aa6f72656c6174696f6e736869705f696450000000000000000000000000000000c36776657273696f6e0166736f7572636550000000000000000000000000000000a17172656c6174696f6e736869705f74797065656b6e6f77736674617267657450000000000000000000000000000000b26a637265617465645f617474323032332d31312d31345432323a31333a32305a6a637265617465645f627950000000000000000000000000000000d46a64656c657465645f6174f66a64656c657465645f6279f6686469726563746564f4";
+
+    fn concept() -> Concept {
+        Concept {
+            id: Uuid::from_u128(0xa1),
+            data: ConceptData::Structured(r#"{"name":"Ada"}"#.to_string().into()),
+            metadata: ConceptMetadata {
+                created_at: at(),
+                updated_at: at(),
+                version: 2,
+                transaction_id: Uuid::from_u128(0xd4),
+            },
+            expires_at: Some(at()),
+            tags: BTreeSet::new(),
+        }
+    }
+
+    fn concept_version() -> ConceptVersion {
+        ConceptVersion {
+            concept_id: Uuid::from_u128(0xa1),
+            version: 2,
+            data: ConceptData::Empty,
+            created_at: at(),
+            created_by: Uuid::from_u128(0xd4),
+            deleted_at: Some(at()),
+            deleted_by: Some(Uuid::from_u128(0xd4)),
+            expires_at: None,
+            tags: Default::default(),
+        }
+    }
+
+    fn relationship() -> Relationship {
+        Relationship {
+            id: Uuid::from_u128(0xc3),
+            source: Uuid::from_u128(0xa1),
+            relationship_type: "knows".into(),
+            target: Uuid::from_u128(0xb2),
+            metadata: RelationshipMetadata { created_at: at(), version: 1, transaction_id: Uuid::from_u128(0xd4) },
+            directed: false,
+        }
+    }
+
+    fn relationship_version() -> RelationshipVersion {
+        RelationshipVersion {
+            relationship_id: Uuid::from_u128(0xc3),
+            version: 1,
+            source: Uuid::from_u128(0xa1),
+            relationship_type: "knows".into(),
+            target: Uuid::from_u128(0xb2),
+            created_at: at(),
+            created_by: Uuid::from_u128(0xd4),
+            deleted_at: None,
+            deleted_by: None,
+            directed: false,
+        }
+    }
+
+    #[test]
+    fn test_unversioned_fixtures_still_decode() {
+        assert_eq!(decode_concept(&bytes(UNVERSIONED_CONCEPT), None).unwrap(), concept());
+        assert_eq!(decode_concept_version(&bytes(UNVERSIONED_CONCEPT_VERSION), None).unwrap(), concept_version());
+        assert_eq!(decode_relationship(&bytes(UNVERSIONED_RELATIONSHIP)).unwrap(), relationship());
+        assert_eq!(decode_relationship_version(&bytes(UNVERSIONED_RELATIONSHIP_VERSION)).unwrap(), relationship_version());
+    }
+
+    #[test]
+    fn test_version_records_match_their_golden_bytes() {
+        assert_eq!(encode_concept_version(&concept_version(), None).unwrap(), bytes(GOLDEN_CONCEPT_VERSION));
+        assert_eq!(decode_concept_version(&bytes(GOLDEN_CONCEPT_VERSION), None).unwrap(), concept_version());
+        assert_eq!(encode_relationship_version(&relationship_version()).unwrap(), bytes(GOLDEN_RELATIONSHIP_VERSION));
+        assert_eq!(decode_relationship_version(&bytes(GOLDEN_RELATIONSHIP_VERSION)).unwrap(), relationship_version());
+    }
+
+    #[test]
+    fn test_v2_versions_default_missing_fields_and_skip_unknown_ones() {
+        // What an older build wrote, without the optional fields, and what a newer one might:
+        // a field this build has never heard of.
+        #[derive(Serialize)]
+        struct Sparse {
+            relationship_id: Uuid,
+            version: u64,
+            source: Uuid,
+            relationship_type: String,
+            target: Uuid,
+            created_at: DateTime<Utc>,
+            created_by: TransactionId,
+            labels: Vec<String>,
+        }
+        let expected = relationship_version();
+        let record = Sparse {
+            relationship_id: expected.relationship_id,
+            version: expected.version,
+            source: expected.source,
+            relationship_type: expected.relationship_type.to_string(),
+            target: expected.target,
+            created_at: expected.created_at,
+            created_by: expected.created_by,
+            labels: vec!["reviewed".to_string()],
+        };
+        let decoded = decode_relationship_version(&map_envelope(V2, &record).unwrap()).unwrap();
+        assert_eq!(decoded, RelationshipVersion { directed: true, ..expected });
+    }
+
+    #[test]
+    fn test_new_records_are_enveloped_and_round_trip() {
+        let encoded = encode_concept(&concept(), None).unwrap();
+        assert_eq!(format_of(&encoded), RecordFormat::Version(latest_format(keys::CONCEPT)));
+        assert_eq!(decode_concept(&encoded, None).unwrap(), concept());
+
+        let encoded = encode_concept_version(&concept_version(), None).unwrap();
+        assert_eq!(decode_concept_version(&encoded, None).unwrap(), concept_version());
+        let encoded = encode_relationship(&relationship()).unwrap();
+        assert_eq!(decode_relationship(&encoded).unwrap(), relationship());
+        let encoded = encode_relationship_version(&relationship_version()).unwrap();
+        assert_eq!(decode_relationship_version(&encoded).unwrap(), relationship_version());
+
+        let cipher = DataCipher::new(&EncryptionKey::new(1, [7; 32]));
+        let sealed = encode_concept(&concept(), Some(&cipher)).unwrap();
+        assert!(!sealed.windows(3).any(|window| window == b"Ada"));
+        assert_eq!(decode_concept(&sealed, Some(&cipher)).unwrap(), concept());
+        assert!(matches!(decode_concept(&sealed, None), Err(MnemonicError::Encryption(_))));
+        let version = ConceptVersion { data: concept().data, ..concept_version() };
+        let sealed = encode_concept_version(&version, Some(&cipher)).unwrap();
+        assert!(!sealed.windows(3).any(|window| window == b"Ada"));
+        assert_eq!(decode_concept_version(&sealed, Some(&cipher)).unwrap(), version);
+    }
+
+    #[test]
+    fn test_records_refer_to_blobs_and_read_through_them() {
+        struct OneBlob(ContentHash, Vec<u8>);
+        impl BlobSource for OneBlob {
+            fn blob(&self, hash: ContentHash) -> Result<Vec<u8>> {
+                assert_eq!(hash, self.0);
+                Ok(self.1.clone())
+            }
+        }
+
+        let cipher = DataCipher::new(&EncryptionKey::new(1, [7; 32]));
+        let hash = 0xfeed;
+        for cipher in [None, Some(&cipher)] {
+            let blob = encode_blob(&concept().data, hash, cipher).unwrap();
+            assert_eq!(cipher.is_some(), !blob.windows(3).any(|window| window == b"Ada"));
+            let blobs = OneBlob(hash, blob);
+
+            let encoded = encode_concept_with(&concept(), cipher, Some(hash)).unwrap();
+            assert_eq!(blob_of(&encoded), Some(hash));
+            assert_eq!(decode_concept_with(&encoded, cipher, &blobs).unwrap(), concept());
+            assert!(decode_concept(&encoded, cipher).is_err());
+
+            let version = ConceptVersion { data: concept().data, ..concept_version() };
+            let encoded = encode_concept_version_with(&version, cipher, Some(hash)).unwrap();
+            assert_eq!(decode_concept_version_with(&encoded, cipher, &blobs).unwrap(), version);
+        }
+        assert_eq!(blob_of(&encode_concept(&concept(), None).unwrap()), None);
+    }
+
+    #[test]
+    fn test_v1_concepts_still_decode_and_tags_round_trip() {
+        let expected = concept();
+        let v1 = ConceptV1 {
+            id: expected.id,
+            data: DataV1::Plain((&expected.data).into()),
+            metadata: (&expected.metadata).into(),
+            expires_at: expected.expires_at,
+        };
+        let encoded = envelope(V1, &v1).unwrap();
+        assert_eq!(decode_concept(&encoded, None).unwrap(), expected);
+        assert_eq!(format_of(&upgrade(keys::CONCEPT, &encoded, None).unwrap()), RecordFormat::Version(V2));
+
+        let tags: BTreeSet<String> = ["needs-review".to_string(), "starred".to_string()].into();
+        let tagged = Concept { tags: tags.clone(), ..expected };
+        assert_eq!(decode_concept(&encode_concept(&tagged, None).unwrap(), None).unwrap(), tagged);
+        let version = ConceptVersion { tags, ..concept_version() };
+        assert_eq!(decode_concept_version(&encode_concept_version(&version, None).unwrap(), None).unwrap(), version);
+    }
+
+    #[test]
+    fn test_upgrading_rewrites_unversioned_records_in_the_latest_format() {
+        let upgraded = upgrade(keys::CONCEPT, &bytes(UNVERSIONED_CONCEPT), None).unwrap();
+        assert_eq!(upgraded, encode_concept(&concept(), None).unwrap());
+        let upgraded = upgrade(keys::RELATIONSHIP_VERSION, &bytes(UNVERSIONED_RELATIONSHIP_VERSION), None).unwrap();
+        assert_eq!(format_of(&upgraded), RecordFormat::Version(V2));
+
+        let mut future = encode_concept(&concept(), None).unwrap();
+        future[0] = V2 + 1;
+        assert!(decode_concept(&future, None).is_err());
+    }
+}
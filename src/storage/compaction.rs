@@ -0,0 +1,90 @@
+// The compaction filter that drops the history of long-deleted items from 'versions'.
+//
+// The filter sees one key at a time and can't read the database, so what it may drop is
+// worked out beforehand by the TransactionManager, which knows every chain and every open
+// reader, and published here as a floor per item.
+
+use rocksdb::compaction_filter::Decision;
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use uuid::Uuid;
+
+use super::keys;
+
+/// The name the filter is registered under, as RocksDB logs it.
+pub(crate) const FILTER_NAME: &str = "mnemonic_version_horizon";
+
+/// Per item, the version below which compaction may drop its versions. Shared between the
+/// backend's compaction filter and whoever publishes to it.
+#[derive(Debug, Default)]
+pub struct CompactionHorizon {
+    floors: RwLock<HashMap<(u8, Uuid), u64>>,
+}
+
+impl CompactionHorizon {
+    /// Replaces every floor. Each (item, version) is the latest version of a concept or
+    /// relationship that no reader can see past; the ones before it may go.
+    ///
+    /// A floor stays safe to apply after it is published: readers that start later only
+    /// ever see that version or newer ones.
+    pub fn publish(
+        &self,
+        concepts: impl IntoIterator<Item = (Uuid, u64)>,
+        relationships: impl IntoIterator<Item = (Uuid, u64)>,
+    ) {
+        let floors = concepts
+            .into_iter()
+            .map(|(id, version)| ((keys::CONCEPT_VERSION, id), version))
+            .chain(relationships.into_iter().map(|(id, version)| ((keys::RELATIONSHIP_VERSION, id), version)))
+            .collect();
+        *self.floors.write().unwrap_or_else(PoisonError::into_inner) = floors;
+    }
+
+    /// How many items currently have a floor.
+    pub fn len(&self) -> usize {
+        self.floors.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// What the filter does with one key of 'versions'. Anything but a version below its
+    /// item's floor is kept.
+    pub(crate) fn decide(&self, key: &[u8]) -> Decision {
+        let Some(&tag @ (keys::CONCEPT_VERSION | keys::RELATIONSHIP_VERSION)) = key.first() else {
+            return Decision::Keep;
+        };
+        let (Some(id), Some(Ok(number))) =
+            (keys::id_of(key), key.get(keys::ITEM_PREFIX_LEN..).map(<[u8; 8]>::try_from))
+        else {
+            return Decision::Keep;
+        };
+        let floors = self.floors.read().unwrap_or_else(PoisonError::into_inner);
+        match floors.get(&(tag, id)) {
+            Some(&floor) if u64::from_be_bytes(number) < floor => Decision::Remove,
+            _ => Decision::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_versions_below_their_items_floor_are_dropped() {
+        let (deleted, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let horizon = CompactionHorizon::default();
+        horizon.publish([(deleted, 3)], []);
+
+        let drops = |key: &[u8]| matches!(horizon.decide(key), Decision::Remove);
+        assert!(drops(&keys::version(keys::CONCEPT_VERSION, &deleted, 2)));
+        assert!(!drops(&keys::version(keys::CONCEPT_VERSION, &deleted, 3)));
+        // Same id, other kind of item; an item without a floor; keys that aren't versions.
+        assert!(!drops(&keys::version(keys::RELATIONSHIP_VERSION, &deleted, 1)));
+        assert!(!drops(&keys::version(keys::CONCEPT_VERSION, &other, 1)));
+        assert!(!drops(&keys::item(keys::CONCEPT_HEAD, &deleted)));
+        assert!(!drops(keys::FORMAT_MARKER));
+    }
+}
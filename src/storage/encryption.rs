@@ -0,0 +1,244 @@
+// Encryption at rest for concept payloads. Only `ConceptData` is sealed: ids, timestamps,
+// version numbers and every key stay in the clear, so indexes, scans and the version chain
+// work exactly as they do unencrypted. Relationships carry no payload and are stored as before.
+//
+// An envelope is `[key id][24-byte nonce][XChaCha20-Poly1305 ciphertext and tag]`. `codec`
+// decides what goes in one, and passes the item's id as associated data, so an envelope
+// copied onto another concept fails to open.
+
+use crate::error::{MnemonicError, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The environment variable `EncryptionKey::from_env` reads a key from, as 64 hex digits.
+pub const KEY_ENV: &str = "MNEMONIC_ENCRYPTION_KEY";
+/// The environment variable with that key's id; 1 when unset.
+pub const KEY_ID_ENV: &str = "MNEMONIC_ENCRYPTION_KEY_ID";
+
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = 1 + NONCE_LEN;
+/// What the encryption marker seals, so a wrong key is caught when the database opens.
+const CHECK_VALUE: &[u8] = b"mnemonic encryption check";
+
+/// A 32-byte data key, and the id written in front of everything it seals. Keys with
+/// different ids can be told apart without trying them, which is what rotation needs.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionKey {
+    pub id: u8,
+    /// In configuration files, 64 hex digits.
+    #[serde(with = "hex_key")]
+    key: [u8; 32],
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey").field("id", &self.id).field("key", &"<redacted>").finish()
+    }
+}
+
+impl EncryptionKey {
+    pub fn new(id: u8, key: [u8; 32]) -> Self {
+        Self { id, key }
+    }
+
+    /// A key written as 64 hex digits.
+    pub fn from_hex(id: u8, hex: &str) -> Result<Self> {
+        let key = hex_key::decode(hex.trim()).map_err(MnemonicError::Encryption)?;
+        Ok(Self { id, key })
+    }
+
+    /// The key in `MNEMONIC_ENCRYPTION_KEY`, if it is set, under the id in
+    /// `MNEMONIC_ENCRYPTION_KEY_ID`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(hex) = std::env::var(KEY_ENV) else {
+            return Ok(None);
+        };
+        let id = match std::env::var(KEY_ID_ENV) {
+            Ok(id) => id
+                .parse()
+                .map_err(|_| MnemonicError::Encryption(format!("{} must be a number from 0 to 255", KEY_ID_ENV)))?,
+            Err(_) => 1,
+        };
+        Self::from_hex(id, &hex).map(Some)
+    }
+
+    /// The key a key management service hands out for `id`, e.g. by unwrapping a data key
+    /// it keeps encrypted. `fetch` is only called once, here.
+    pub fn from_kms<E: fmt::Display>(
+        id: u8,
+        fetch: impl FnOnce(u8) -> std::result::Result<Vec<u8>, E>,
+    ) -> Result<Self> {
+        let bytes = fetch(id).map_err(|e| MnemonicError::Encryption(format!("Fetching key {}: {}", id, e)))?;
+        let key = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            MnemonicError::Encryption(format!("Key {} is {} bytes; it must be 32", id, bytes.len()))
+        })?;
+        Ok(Self { id, key })
+    }
+}
+
+/// Seals and opens concept payloads with one `EncryptionKey`.
+#[derive(Clone)]
+pub(crate) struct DataCipher {
+    key_id: u8,
+    cipher: XChaCha20Poly1305,
+}
+
+impl fmt::Debug for DataCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataCipher").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+impl DataCipher {
+    pub fn new(key: &EncryptionKey) -> Self {
+        Self { key_id: key.id, cipher: XChaCha20Poly1305::new(Key::from_slice(&key.key)) }
+    }
+
+    /// `[key id][nonce][ciphertext]`, with a fresh random nonce.
+    pub(crate) fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::fill(&mut nonce)
+            .map_err(|e| MnemonicError::Encryption(format!("No randomness for a nonce: {}", e)))?;
+        let ciphertext = self
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+            .map_err(|_| MnemonicError::Encryption("Encryption failed".to_string()))?;
+
+        let mut envelope = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        envelope.push(self.key_id);
+        envelope.extend_from_slice(&nonce);
+        envelope.extend_from_slice(&ciphertext);
+        Ok(envelope)
+    }
+
+    pub(crate) fn open(&self, envelope: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if envelope.len() < HEADER_LEN {
+            return Err(MnemonicError::Encryption("Encrypted value is truncated".to_string()));
+        }
+        let (header, ciphertext) = envelope.split_at(HEADER_LEN);
+        if header[0] != self.key_id {
+            return Err(MnemonicError::Encryption(format!(
+                "Value is encrypted with key {}, but key {} is configured",
+                header[0], self.key_id
+            )));
+        }
+        self.cipher
+            .decrypt(XNonce::from_slice(&header[1..]), Payload { msg: ciphertext, aad })
+            .map_err(|_| MnemonicError::Encryption("Decryption failed: wrong key or corrupted value".to_string()))
+    }
+
+    /// What the database's encryption marker holds for this key.
+    pub(crate) fn marker(&self) -> Result<Vec<u8>> {
+        self.seal(CHECK_VALUE, &[])
+    }
+
+    /// Whether this is the key a database's encryption marker was written with.
+    pub(crate) fn verify_marker(&self, marker: &[u8]) -> Result<()> {
+        if let Some(&id) = marker.first()
+            && id != self.key_id
+        {
+            return Err(MnemonicError::Encryption(format!(
+                "Database is encrypted with key {}, but key {} is configured",
+                id, self.key_id
+            )));
+        }
+        match self.open(marker, &[]) {
+            Ok(value) if value == CHECK_VALUE => Ok(()),
+            _ => Err(MnemonicError::Encryption(format!("Key {} does not open this database", self.key_id))),
+        }
+    }
+}
+
+/// The error for a sealed record read without a key.
+pub(crate) fn no_key() -> MnemonicError {
+    MnemonicError::Encryption("Record is encrypted, but no encryption key is configured".to_string())
+}
+
+/// The outcome of `RocksBackend::rotate_encryption_key`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyRotationReport {
+    /// Concept head records rewritten under the new key.
+    pub concepts: usize,
+    /// Concept versions rewritten under the new key.
+    pub concept_versions: usize,
+    /// Blobs rewritten under the new key.
+    #[serde(default)]
+    pub blobs: usize,
+}
+
+mod hex_key {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(key: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&key.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        decode(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+
+    pub fn decode(hex: &str) -> Result<[u8; 32], String> {
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err("An encryption key must be 64 hex digits".to_string());
+        }
+        let mut key = [0u8; 32];
+        for (byte, pair) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16)
+                .map_err(|_| "An encryption key must be 64 hex digits".to_string())?;
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_envelopes_round_trip_and_hide_their_plaintext() {
+        let cipher = DataCipher::new(&EncryptionKey::new(7, [1; 32]));
+        let id = Uuid::new_v4();
+        let envelope = cipher.seal(b"analytical engine", id.as_bytes()).unwrap();
+
+        assert_eq!(envelope[0], 7);
+        assert!(!envelope.windows(b"analytical".len()).any(|window| window == b"analytical"));
+        assert_eq!(cipher.open(&envelope, id.as_bytes()).unwrap(), b"analytical engine");
+        assert_ne!(cipher.seal(b"analytical engine", id.as_bytes()).unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_the_wrong_key_and_a_moved_envelope_fail_to_open() {
+        let cipher = DataCipher::new(&EncryptionKey::new(7, [1; 32]));
+        let id = Uuid::new_v4();
+        let envelope = cipher.seal(b"analytical engine", id.as_bytes()).unwrap();
+
+        let same_id = DataCipher::new(&EncryptionKey::new(7, [2; 32]));
+        assert!(matches!(same_id.open(&envelope, id.as_bytes()), Err(MnemonicError::Encryption(_))));
+        let other_id = DataCipher::new(&EncryptionKey::new(8, [1; 32]));
+        assert!(matches!(other_id.open(&envelope, id.as_bytes()), Err(MnemonicError::Encryption(m)) if m.contains("key 7")));
+        assert!(cipher.open(&envelope, Uuid::new_v4().as_bytes()).is_err());
+        assert!(cipher.open(&envelope[..10], id.as_bytes()).is_err());
+
+        assert!(cipher.verify_marker(&cipher.marker().unwrap()).is_ok());
+        assert!(same_id.verify_marker(&cipher.marker().unwrap()).is_err());
+    }
+    #[test]
+    fn test_keys_parse_from_hex_and_kms_and_stay_out_of_debug_output() {
+        let hex = "00ff".repeat(16);
+        let key = EncryptionKey::from_hex(3, &hex).unwrap();
+        assert_eq!(key.key[..2], [0x00, 0xff]);
+        assert!(!format!("{:?}", key).contains("ff"));
+        assert!(EncryptionKey::from_hex(3, "abc").is_err());
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(serde_json::from_str::<EncryptionKey>(&json).unwrap(), key);
+
+        let fetched = EncryptionKey::from_kms(3, |_| Ok::<_, String>(key.key.to_vec())).unwrap();
+        assert_eq!(fetched, key);
+        assert!(EncryptionKey::from_kms(3, |_| Ok::<_, String>(vec![0; 16])).is_err());
+        assert!(EncryptionKey::from_kms(3, |id| Err(format!("no key {}", id))).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+// Binary key layout for every cabinet: `[tag][16-byte uuid]`, followed by a second uuid
+// (index entries) or a big-endian version number (versions) where needed.
+//
+// Big-endian versions sort numerically, so an item's history scans back oldest first.
+// The type indexes are the exception: `[tag][type name][0x00][relationship id]`, see
+// `type_index`, and `[tag][source id][type name][0x00][relationship id]`, see `source_type_index`.
+// Tags sit below 0x60, so they can never be mistaken for the ASCII keys written before.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const CONCEPT: u8 = 0x01;
+pub const RELATIONSHIP: u8 = 0x02;
+pub const INDEX_SOURCE: u8 = 0x10;
+pub const INDEX_TARGET: u8 = 0x11;
+pub const CONTENT_HASH: u8 = 0x12;
+pub const INDEX_TYPE: u8 = 0x13;
+pub const INDEX_SOURCE_TYPE: u8 = 0x14;
+pub const CONCEPT_VERSION: u8 = 0x20;
+pub const RELATIONSHIP_VERSION: u8 = 0x21;
+pub const TRANSACTION_METADATA: u8 = 0x30;
+pub const CONCEPT_HEAD: u8 = 0x40;
+pub const RELATIONSHIP_HEAD: u8 = 0x41;
+/// Schemas, cardinalities and relationship types as databases kept them before system
+/// concepts; only read to move them over.
+pub const SCHEMA: u8 = 0x50;
+pub const CARDINALITY: u8 = 0x51;
+/// Webhook registrations. They stay out of system concepts because they carry secrets.
+pub const WEBHOOK: u8 = 0x52;
+pub const RELATIONSHIP_TYPE: u8 = 0x53;
+/// The one key recording when a purge last erased history.
+pub const HISTORY_ERASED_AT: u8 = 0x54;
+/// The one key recording the last commit a follower replicated.
+pub const REPLICATED_THROUGH: u8 = 0x55;
+/// The one key recording that concept data is encrypted, as a value sealed with the key.
+pub const ENCRYPTION_MARKER: u8 = 0x56;
+/// The one key recording how many commits the database has taken.
+pub const COMMIT_SEQUENCE: u8 = 0x57;
+/// A blob of concept data in the 'blobs' cabinet, keyed by its content hash.
+pub const BLOB: u8 = 0x58;
+/// A record that refers to a blob, in the 'blobs' cabinet; see `blob_ref`.
+pub const BLOB_REF: u8 = 0x59;
+
+/// The key, in the 'versions' cabinet, recording which key layout the database uses.
+pub const FORMAT_MARKER: &[u8] = &[0x00];
+/// The layout this module writes. Databases without a marker use the old string keys.
+pub const FORMAT_VERSION: u8 = 2;
+
+/// Length of `[tag][uuid]`: every key that belongs to one item starts with this many bytes.
+pub const ITEM_PREFIX_LEN: usize = 1 + 16;
+
+/// `[tag][id]`: a concept, a relationship, a head counter or a transaction's metadata.
+pub fn item(tag: u8, id: &Uuid) -> [u8; ITEM_PREFIX_LEN] {
+    let mut key = [0; ITEM_PREFIX_LEN];
+    key[0] = tag;
+    key[1..].copy_from_slice(id.as_bytes());
+    key
+}
+
+/// `[tag][concept id][relationship id]`: an index entry.
+pub fn index(tag: u8, concept_id: &Uuid, relationship_id: &Uuid) -> [u8; ITEM_PREFIX_LEN + 16] {
+    let mut key = [0; ITEM_PREFIX_LEN + 16];
+    key[..ITEM_PREFIX_LEN].copy_from_slice(&item(tag, concept_id));
+    key[ITEM_PREFIX_LEN..].copy_from_slice(relationship_id.as_bytes());
+    key
+}
+
+/// `[INDEX_TYPE][type name][0x00]`: the prefix every type index entry for one type shares.
+pub fn type_index_prefix(relationship_type: &str) -> Vec<u8> {
+    let mut key = named(INDEX_TYPE, relationship_type);
+    key.push(0x00);
+    key
+}
+
+/// `[INDEX_TYPE][type name][0x00][relationship id]`: a type index entry.
+///
+/// Type names are free text, so ":" is just another byte here and needs no escaping. The
+/// name ends at a 0x00 byte, which no sensible type contains; one that does would share
+/// its prefix with a shorter type's, so `type_index_id` also checks the key's length.
+pub fn type_index(relationship_type: &str, relationship_id: &Uuid) -> Vec<u8> {
+    let mut key = type_index_prefix(relationship_type);
+    key.extend_from_slice(relationship_id.as_bytes());
+    key
+}
+
+/// The relationship id of a type index entry, if `key` is one for exactly this type.
+pub fn type_index_id(relationship_type: &str, key: &[u8]) -> Option<Uuid> {
+    let prefix = type_index_prefix(relationship_type);
+    let id = key.strip_prefix(prefix.as_slice())?;
+    Uuid::from_slice(id).ok()
+}
+
+/// The type name and relationship id of any type index entry.
+pub fn split_type_index(key: &[u8]) -> Option<(&str, Uuid)> {
+    let (rest, id) = key.strip_prefix(&[INDEX_TYPE])?.split_at_checked(key.len().checked_sub(1 + 16)?)?;
+    Some((std::str::from_utf8(rest.strip_suffix(&[0x00])?).ok()?, Uuid::from_slice(id).ok()?))
+}
+
+/// `[INDEX_SOURCE_TYPE][source id][type name][0x00]`: the prefix every entry for one
+/// concept's relationships of one type shares. It starts with `item(INDEX_SOURCE_TYPE, source)`,
+/// so a prefix extractor of `ITEM_PREFIX_LEN` bytes can filter on it.
+pub fn source_type_index_prefix(source_id: &Uuid, relationship_type: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(ITEM_PREFIX_LEN + relationship_type.len() + 1 + 16);
+    key.extend_from_slice(&item(INDEX_SOURCE_TYPE, source_id));
+    key.extend_from_slice(relationship_type.as_bytes());
+    key.push(0x00);
+    key
+}
+
+/// `[INDEX_SOURCE_TYPE][source id][type name][0x00][relationship id]`: a composite index
+/// entry. The type name ends the same way as in `type_index`.
+pub fn source_type_index(source_id: &Uuid, relationship_type: &str, relationship_id: &Uuid) -> Vec<u8> {
+    let mut key = source_type_index_prefix(source_id, relationship_type);
+    key.extend_from_slice(relationship_id.as_bytes());
+    key
+}
+
+/// The source id, type name and relationship id of any composite index entry.
+pub fn split_source_type_index(key: &[u8]) -> Option<(Uuid, &str, Uuid)> {
+    if key.first() != Some(&INDEX_SOURCE_TYPE) {
+        return None;
+    }
+    let source_id = id_of(key)?;
+    let rest = key.get(ITEM_PREFIX_LEN..)?;
+    let (name, id) = rest.split_at_checked(rest.len().checked_sub(16)?)?;
+    Some((source_id, std::str::from_utf8(name.strip_suffix(&[0x00])?).ok()?, Uuid::from_slice(id).ok()?))
+}
+
+/// `[tag][id][version]`: one version of a concept or relationship.
+pub fn version(tag: u8, id: &Uuid, version: u64) -> [u8; ITEM_PREFIX_LEN + 8] {
+    let mut key = [0; ITEM_PREFIX_LEN + 8];
+    key[..ITEM_PREFIX_LEN].copy_from_slice(&item(tag, id));
+    key[ITEM_PREFIX_LEN..].copy_from_slice(&version.to_be_bytes());
+    key
+}
+
+/// `[tag][name]`: something keyed by name rather than ID, such as a label's schema or a
+/// relationship type's cardinality.
+pub fn named(tag: u8, name: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + name.len());
+    key.push(tag);
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// `[BLOB_REF][hash][record key]`: `record`, a concept or version key, refers to the blob
+/// with this content hash. The record's own tag says which cabinet it is in.
+pub fn blob_ref(hash: u128, record: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(ITEM_PREFIX_LEN + record.len());
+    key.extend_from_slice(&item(BLOB_REF, &Uuid::from_u128(hash)));
+    key.extend_from_slice(record);
+    key
+}
+
+/// The content hash and record key of a blob reference.
+pub fn split_blob_ref(key: &[u8]) -> Option<(u128, &[u8])> {
+    if key.first() != Some(&BLOB_REF) {
+        return None;
+    }
+    Some((id_of(key)?.as_u128(), key.get(ITEM_PREFIX_LEN..).filter(|record| !record.is_empty())?))
+}
+
+/// The uuid right after the tag byte.
+pub fn id_of(key: &[u8]) -> Option<Uuid> {
+    Uuid::from_slice(key.get(1..ITEM_PREFIX_LEN)?).ok()
+}
+
+/// Whether a key is in the binary layout (its tag is below any ASCII letter).
+pub fn is_binary(key: &[u8]) -> bool {
+    key.first().is_some_and(|tag| *tag < 0x60)
+}
+
+/// The outcome of `RocksBackend::migrate_keys`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyMigrationReport {
+    /// Keys rewritten from the string layout to the binary one.
+    pub migrated: usize,
+    /// Keys in neither layout, left untouched.
+    pub unrecognised: usize,
+}
+
+/// Rewrites a key in the old string layout ("concept:{id}", "cv:{id}:{version}", ...) into
+/// the binary one. `None` for keys this module doesn't recognise.
+pub fn from_legacy(key: &[u8]) -> Option<Vec<u8>> {
+    let key = std::str::from_utf8(key).ok()?;
+    let parse = |id: &str| Uuid::parse_str(id).ok();
+
+    // Longer prefixes first: "head:cv:" would otherwise never match.
+    if let Some(id) = key.strip_prefix("head:cv:") {
+        return Some(item(CONCEPT_HEAD, &parse(id)?).to_vec());
+    }
+    if let Some(id) = key.strip_prefix("head:rv:") {
+        return Some(item(RELATIONSHIP_HEAD, &parse(id)?).to_vec());
+    }
+    if let Some(id) = key.strip_prefix("txmeta:") {
+        return Some(item(TRANSACTION_METADATA, &parse(id)?).to_vec());
+    }
+    if let Some(id) = key.strip_prefix("concept:") {
+        return Some(item(CONCEPT, &parse(id)?).to_vec());
+    }
+    if let Some(id) = key.strip_prefix("rel:") {
+        return Some(item(RELATIONSHIP, &parse(id)?).to_vec());
+    }
+    if let Some(rest) = key.strip_prefix("idx_src:") {
+        let (concept_id, rel_id) = split_id(rest)?;
+        return Some(index(INDEX_SOURCE, &concept_id, &parse(rel_id)?).to_vec());
+    }
+    if let Some(rest) = key.strip_prefix("idx_tgt:") {
+        let (concept_id, rel_id) = split_id(rest)?;
+        return Some(index(INDEX_TARGET, &concept_id, &parse(rel_id)?).to_vec());
+    }
+    if let Some(rest) = key.strip_prefix("cv:") {
+        let (id, number) = split_id(rest)?;
+        return Some(version(CONCEPT_VERSION, &id, number.parse().ok()?).to_vec());
+    }
+    if let Some(rest) = key.strip_prefix("rv:") {
+        let (id, number) = split_id(rest)?;
+        return Some(version(RELATIONSHIP_VERSION, &id, number.parse().ok()?).to_vec());
+    }
+    None
+}
+
+/// Renders a binary key in the string layout, for reports a person acts on. Keys in neither
+/// layout come back as hex.
+pub fn describe(key: &[u8]) -> String {
+    let id = id_of(key);
+    let rest = key.get(ITEM_PREFIX_LEN..).unwrap_or_default();
+    let named = match (key.first(), id) {
+        // Checked first: the bytes after this tag are a type name, not a uuid.
+        (Some(&INDEX_TYPE), _) => describe_type_index(key),
+        (Some(&INDEX_SOURCE_TYPE), _) => split_source_type_index(key)
+            .map(|(source_id, name, rel_id)| format!("idx_st:{}:{}:{}", source_id, name, rel_id)),
+        (Some(&CONCEPT), Some(id)) if rest.is_empty() => Some(format!("concept:{}", id)),
+        (Some(&RELATIONSHIP), Some(id)) if rest.is_empty() => Some(format!("rel:{}", id)),
+        (Some(&CONTENT_HASH), Some(id)) if rest.is_empty() => Some(format!("content_hash:{}", id)),
+        (Some(&BLOB), Some(id)) if rest.is_empty() => Some(format!("blob:{:032x}", id.as_u128())),
+        (Some(&BLOB_REF), Some(id)) if !rest.is_empty() => {
+            Some(format!("blob_ref:{:032x}:{}", id.as_u128(), describe(rest)))
+        }
+        (Some(&TRANSACTION_METADATA), Some(id)) if rest.is_empty() => Some(format!("txmeta:{}", id)),
+        (Some(&CONCEPT_HEAD), Some(id)) if rest.is_empty() => Some(format!("head:cv:{}", id)),
+        (Some(&RELATIONSHIP_HEAD), Some(id)) if rest.is_empty() => Some(format!("head:rv:{}", id)),
+        (Some(&tag @ (INDEX_SOURCE | INDEX_TARGET)), Some(id)) => Uuid::from_slice(rest).ok().map(|rel_id| {
+            let name = if tag == INDEX_SOURCE { "idx_src" } else { "idx_tgt" };
+            format!("{}:{}:{}", name, id, rel_id)
+        }),
+        (Some(&tag @ (CONCEPT_VERSION | RELATIONSHIP_VERSION)), Some(id)) => {
+            <[u8; 8]>::try_from(rest).ok().map(|number| {
+                let name = if tag == CONCEPT_VERSION { "cv" } else { "rv" };
+                format!("{}:{}:{}", name, id, u64::from_be_bytes(number))
+            })
+        }
+        _ => None,
+    };
+    named.unwrap_or_else(|| key.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// "idx_type:{type}:{relationship id}", for a well-formed type index entry.
+fn describe_type_index(key: &[u8]) -> Option<String> {
+    let (name, id) = split_type_index(key)?;
+    Some(format!("idx_type:{}:{}", name, id))
+}
+
+/// Splits "{uuid}:{rest}".
+fn split_id(key: &str) -> Option<(Uuid, &str)> {
+    let (id, rest) = key.split_once(':')?;
+    Some((Uuid::parse_str(id).ok()?, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_sort_numerically() {
+        let id = Uuid::new_v4();
+        assert!(version(CONCEPT_VERSION, &id, 2) < version(CONCEPT_VERSION, &id, 10));
+        assert!(version(CONCEPT_VERSION, &id, u64::MAX).starts_with(&item(CONCEPT_VERSION, &id)));
+    }
+
+    #[test]
+    fn test_describe_renders_the_string_layout() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        for key in [format!("cv:{}:7", a), format!("idx_tgt:{}:{}", a, b), format!("head:rv:{}", a)] {
+            assert_eq!(describe(&from_legacy(key.as_bytes()).unwrap()), key);
+        }
+        assert_eq!(describe(&[0x7f, 0x01]), "7f01");
+        let record = version(CONCEPT_VERSION, &a, 3);
+        assert_eq!(describe(&blob_ref(0xff, &record)), format!("blob_ref:{:032x}:cv:{}:3", 0xff, a));
+        assert_eq!(split_blob_ref(&blob_ref(0xff, &record)), Some((0xff, &record[..])));
+        assert_eq!(describe(&type_index("schema:works_for", &b)), format!("idx_type:schema:works_for:{}", b));
+        assert_eq!(
+            describe(&source_type_index(&a, "org:member_of", &b)),
+            format!("idx_st:{}:org:member_of:{}", a, b)
+        );
+    }
+
+    #[test]
+    fn test_type_index_keys_keep_colons_and_only_match_their_own_type() {
+        let id = Uuid::new_v4();
+        let key = type_index("org:works_for", &id);
+        assert!(key.starts_with(&type_index_prefix("org:works_for")));
+        assert!(!key.starts_with(&type_index_prefix("org")));
+        assert_eq!(type_index_id("org:works_for", &key), Some(id));
+
+        // A type with a NUL in it shares the shorter type's prefix, but not its key length.
+        let odd = type_index("org\0x", &id);
+        assert!(odd.starts_with(&type_index_prefix("org")));
+        assert_eq!(type_index_id("org", &odd), None);
+        assert_eq!(type_index_id("org\0x", &odd), Some(id));
+    }
+
+    #[test]
+    fn test_source_type_index_keys_group_by_source_then_type() {
+        let (source, id) = (Uuid::new_v4(), Uuid::new_v4());
+        let key = source_type_index(&source, "member_of", &id);
+        assert!(key.starts_with(&item(INDEX_SOURCE_TYPE, &source)));
+        assert!(key.starts_with(&source_type_index_prefix(&source, "member_of")));
+        assert!(!key.starts_with(&source_type_index_prefix(&source, "member")));
+        assert_eq!(split_source_type_index(&key), Some((source, "member_of", id)));
+        assert_eq!(split_source_type_index(&type_index("member_of", &id)), None);
+    }
+
+    #[test]
+    fn test_legacy_keys_map_to_binary() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let cases: Vec<(String, Vec<u8>)> = vec![
+            (format!("concept:{}", a), item(CONCEPT, &a).to_vec()),
+            (format!("rel:{}", a), item(RELATIONSHIP, &a).to_vec()),
+            (format!("idx_src:{}:{}", a, b), index(INDEX_SOURCE, &a, &b).to_vec()),
+            (format!("idx_tgt:{}:{}", a, b), index(INDEX_TARGET, &a, &b).to_vec()),
+            (format!("cv:{}:12", a), version(CONCEPT_VERSION, &a, 12).to_vec()),
+            (format!("rv:{}:3", a), version(RELATIONSHIP_VERSION, &a, 3).to_vec()),
+            (format!("txmeta:{}", a), item(TRANSACTION_METADATA, &a).to_vec()),
+            (format!("head:cv:{}", a), item(CONCEPT_HEAD, &a).to_vec()),
+            (format!("head:rv:{}", a), item(RELATIONSHIP_HEAD, &a).to_vec()),
+        ];
+        for (legacy, binary) in cases {
+            assert_eq!(from_legacy(legacy.as_bytes()), Some(binary), "{}", legacy);
+        }
+        assert_eq!(from_legacy(b"concept:not-a-uuid"), None);
+        assert_eq!(from_legacy(&item(CONCEPT, &a)), None);
+        assert_eq!(id_of(&index(INDEX_SOURCE, &a, &b)), Some(a));
+    }
+}
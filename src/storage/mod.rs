@@ -1,3 +1,15 @@
+pub mod codec;
+pub mod compaction;
+pub mod encryption;
+pub mod keys;
+pub mod options;
 pub mod rocks_backend;
+pub mod stats;
 
-pub use rocks_backend::*;
\ No newline at end of file
+pub use codec::RecordMigrationReport;
+pub use compaction::CompactionHorizon;
+pub use encryption::{EncryptionKey, KeyRotationReport};
+pub use keys::KeyMigrationReport;
+pub use options::{ColumnFamilyTuning, Compression, StorageOptions, VersionCompaction};
+pub use rocks_backend::*;
+pub use stats::{ColumnFamilyStats, RocksDbSample, StorageMetrics, StorageStats};
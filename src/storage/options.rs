@@ -0,0 +1,121 @@
+// Tuning knobs for the RocksDB backend. The defaults leave every RocksDB default in place.
+
+use super::encryption::EncryptionKey;
+use rocksdb::{BlockBasedOptions, Cache, DBCompressionType, Options, SliceTransform};
+use serde::{Deserialize, Serialize};
+
+/// How a column family's SST files are compressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// Whatever RocksDB picks (Snappy, when it is compiled in).
+    #[default]
+    Default,
+    None,
+    Lz4,
+    Zstd,
+}
+
+/// Tuning for a single column family.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnFamilyTuning {
+    pub compression: Compression,
+    /// Memtable size in bytes. `None` keeps RocksDB's default.
+    pub write_buffer_size: Option<usize>,
+    /// Bits per key of a bloom filter. `None` means no filter.
+    pub bloom_filter_bits: Option<f64>,
+}
+
+/// Background cleanup of old history, by a compaction filter on 'versions'.
+///
+/// Off unless set: a version the filter drops is gone for time travel too, however far back
+/// `get_concept_at` asks. The latest version of every item is always kept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VersionCompaction {
+    /// The older versions of an item deleted at least this many seconds ago may go. Open
+    /// transactions and snapshots move the horizon back to when they started.
+    pub horizon_secs: u64,
+    /// Treat a concept whose latest version expired before the horizon as deleted, even
+    /// before the expiry sweeper has tombstoned it.
+    pub drop_expired: bool,
+}
+
+/// Everything that can be tuned when opening a `RocksBackend`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageOptions {
+    /// Size of an LRU block cache shared by every column family. `None` keeps RocksDB's default.
+    pub block_cache_bytes: Option<usize>,
+    /// Give 'versions' and 'indices' fixed-length prefix extractors matching their key layouts,
+    /// so per-item prefix scans can skip files via prefix bloom filters.
+    pub prefix_extractors: bool,
+    /// Open an existing database without write access, e.g. to inspect one a server has open.
+    /// Every write fails, and a database still in the string key layout can't be opened.
+    pub read_only: bool,
+    /// Drop the history of long-deleted items during compaction. See `VersionCompaction`.
+    pub version_compaction: Option<VersionCompaction>,
+    /// Encrypt concept data at rest with this key. A database that has been opened with a key
+    /// can't be opened without it; `RocksBackend::rotate_encryption_key` changes or removes it.
+    pub encryption: Option<EncryptionKey>,
+    /// Turn on RocksDB's statistics and sample them into `RocksBackend::metrics` this many
+    /// seconds apart. Statistics cost a little on every operation, so they are off by default.
+    pub statistics_interval_secs: Option<u64>,
+    /// Log a warning for every single read, write or scan that takes at least this many
+    /// milliseconds, naming the key or prefix. `None` times nothing.
+    pub slow_op_threshold_ms: Option<u64>,
+    /// Store concept data of at least this many bytes once, in 'blobs', with each version
+    /// and concept record holding only its content hash. Versions that share a payload then
+    /// share its storage; prunes and purges delete the blobs nothing refers to any more.
+    /// `None` keeps all data in its records. Only affects writes: reads take either.
+    pub blob_threshold_bytes: Option<usize>,
+    pub concepts: ColumnFamilyTuning,
+    pub relationships: ColumnFamilyTuning,
+    pub indices: ColumnFamilyTuning,
+    pub versions: ColumnFamilyTuning,
+    pub blobs: ColumnFamilyTuning,
+}
+
+impl StorageOptions {
+    /// Builds the RocksDB options of one column family. `prefix_len` is the length of the key
+    /// prefix that family is scanned by, if any.
+    pub(crate) fn column_family_options(
+        &self,
+        tuning: &ColumnFamilyTuning,
+        cache: Option<&Cache>,
+        prefix_len: Option<usize>,
+    ) -> Options {
+        let mut opts = Options::default();
+
+        match tuning.compression {
+            Compression::Default => {}
+            Compression::None => opts.set_compression_type(DBCompressionType::None),
+            Compression::Lz4 => opts.set_compression_type(DBCompressionType::Lz4),
+            Compression::Zstd => opts.set_compression_type(DBCompressionType::Zstd),
+        }
+        if let Some(size) = tuning.write_buffer_size {
+            opts.set_write_buffer_size(size);
+        }
+
+        if cache.is_some() || tuning.bloom_filter_bits.is_some() {
+            let mut table = BlockBasedOptions::default();
+            if let Some(cache) = cache {
+                table.set_block_cache(cache);
+            }
+            if let Some(bits) = tuning.bloom_filter_bits {
+                table.set_bloom_filter(bits, false);
+            }
+            opts.set_block_based_table_factory(&table);
+        }
+
+        if self.prefix_extractors
+            && let Some(len) = prefix_len
+        {
+            opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(len));
+            opts.set_memtable_prefix_bloom_ratio(0.1);
+        }
+
+        opts
+    }
+}
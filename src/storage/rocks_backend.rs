@@ -1,79 +1,430 @@
 use crate::error::{MnemonicError, Result};
 use crate::types::concept::ConceptVersion;
 use crate::types::concept::*; //Import everything from the concept file
+use crate::types::content::{self, ContentHash};
 use crate::types::relationship::*;
-use rocksdb::{ColumnFamilyDescriptor, DB, IteratorMode, Options, WriteBatch};
+use crate::types::transaction::TransactionMetadata;
+use super::compaction::{self, CompactionHorizon};
+use super::codec::{self, BlobSource, RecordFormat, RecordMigrationReport};
+use super::encryption::{DataCipher, EncryptionKey, KeyRotationReport};
+use super::keys::{self, KeyMigrationReport};
+use super::options::{StorageOptions, VersionCompaction};
+use super::stats::{self, ColumnFamilyStats, RocksDbSample, StorageMetrics, StorageStats};
+use rocksdb::{Cache, ColumnFamily, ColumnFamilyDescriptor, DB, IteratorMode, Options, ReadOptions, WriteBatch};
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::{Duration, Instant};
 use uuid::Uuid; //Import everything from relationship file
 
+/// A raw key/value pair as read back from RocksDB.
+type RawRecord = (Box<[u8]>, Box<[u8]>);
+
+/// `[tag][concept id][relationship id]`, as `keys::index` builds it.
+type IndexKey = [u8; keys::ITEM_PREFIX_LEN + 16];
+
+/// How many stale index keys may wait for the repair thread. Beyond that they are left
+/// for the next read or `scrub_indices` to find.
+const REPAIR_QUEUE_CAPACITY: usize = 1024;
+
 // These are the names of our "filing cabinets" inside the database.
 // This separates different kinds of data for better performance.
+//
+// 'versions' is the source of truth. 'concepts' holds a copy of each concept's latest
+// active version, which every commit keeps up to date in the same batch, so `get_concept`
+// is a point read that agrees with the engine. `rebuild_derived_state` regenerates it.
+// 'blobs' holds concept data too large to copy into every record that has it, once per
+// payload, with a reference entry per record that refers to it.
 
 const CF_CONCEPTS: &str = "concepts";
 const CF_RELATIONSHIPS: &str = "relationships";
 const CF_INDICES: &str = "indices";
 const CF_VERSIONS: &str = "versions";
+const CF_BLOBS: &str = "blobs";
+const ALL_CFS: [&str; 5] = [CF_CONCEPTS, CF_RELATIONSHIPS, CF_INDICES, CF_VERSIONS, CF_BLOBS];
 
 /// RocksDB-based storage backend for Mnemonic
 #[derive(Debug)]
 pub struct RocksBackend {
     pub db: Arc<DB>, // Arc stands for 'Atomically Reference Counted'.
                      // It's a safe way to share the database connection across many threads.
+    /// Stale index keys found by reads, deleted off the read path by a background thread.
+    /// Read-only databases have none.
+    repairs: Option<SyncSender<IndexKey>>,
+    /// What the compaction filter on 'versions' may drop, when `StorageOptions` turns it on.
+    compaction: Option<(VersionCompaction, Arc<CompactionHorizon>)>,
+    /// Seals concept data on the way to disk, when `StorageOptions` sets a key.
+    cipher: Option<DataCipher>,
+    /// Concept data this large goes to 'blobs', when `StorageOptions` sets a threshold.
+    blob_threshold: Option<usize>,
+    /// The payloads of blobs read or written so far, while anything still holds them, so
+    /// that every version of a concept referring to a blob shares one copy in memory.
+    blob_payloads: Mutex<HashMap<ContentHash, Weak<str>>>,
+    /// Slow operations, and the latest sample of RocksDB's statistics.
+    metrics: Arc<StorageMetrics>,
+    /// Reads, writes and scans at least this slow are logged.
+    slow_op_threshold: Option<Duration>,
+    /// The options the database was opened with, which hold its statistics, when
+    /// `StorageOptions` turns them on.
+    statistics: Option<Statistics>,
+}
+
+/// The database options, kept for `Options::get_statistics`.
+struct Statistics(Options);
+
+impl std::fmt::Debug for Statistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Statistics")
+    }
 }
 
 impl RocksBackend {
     /// Create a new or open an existing RocksDB database with optimized settings.
     pub fn new(path: &Path) -> Result<Self> {
+        Self::with_options(path, &StorageOptions::default())
+    }
+
+    /// Like `new`, but tuned by `options`.
+    pub fn with_options(path: &Path, options: &StorageOptions) -> Result<Self> {
         // --- General Settings ---
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.create_missing_column_families(true);
         opts.increase_parallelism(num_cpus::get() as i32); // Use all available CPU cores
+        if options.statistics_interval_secs.is_some() {
+            opts.enable_statistics();
+        }
+        let statistics = options.statistics_interval_secs.map(|_| Statistics(opts.clone()));
+        let slow_op_threshold = options.slow_op_threshold_ms.map(Duration::from_millis);
+        let metrics = Arc::<StorageMetrics>::default();
 
         // --- Our Filing Cabinets ---
+        let cache = options.block_cache_bytes.map(Cache::new_lru_cache);
+        let cf_opts = |tuning, prefix_len| {
+            options.column_family_options(tuning, cache.as_ref(), prefix_len)
+        };
+        let mut versions_opts = cf_opts(&options.versions, Some(keys::ITEM_PREFIX_LEN));
+        let compaction = options.version_compaction.clone().map(|config| {
+            let horizon = Arc::new(CompactionHorizon::default());
+            let filter = Arc::clone(&horizon);
+            versions_opts.set_compaction_filter(compaction::FILTER_NAME, move |_level, key, _value| filter.decide(key));
+            (config, horizon)
+        });
         let cfs = vec![
-            ColumnFamilyDescriptor::new(CF_CONCEPTS, Options::default()),
-            ColumnFamilyDescriptor::new(CF_RELATIONSHIPS, Options::default()),
-            ColumnFamilyDescriptor::new(CF_INDICES, Options::default()),
-            ColumnFamilyDescriptor::new(CF_VERSIONS, Options::default()),
+            ColumnFamilyDescriptor::new(CF_CONCEPTS, cf_opts(&options.concepts, None)),
+            ColumnFamilyDescriptor::new(CF_RELATIONSHIPS, cf_opts(&options.relationships, None)),
+            ColumnFamilyDescriptor::new(CF_INDICES, cf_opts(&options.indices, Some(keys::ITEM_PREFIX_LEN))),
+            ColumnFamilyDescriptor::new(CF_VERSIONS, versions_opts),
+            ColumnFamilyDescriptor::new(CF_BLOBS, cf_opts(&options.blobs, None)),
         ];
 
+        let cipher = options.encryption.as_ref().map(DataCipher::new);
+        let blob_threshold = options.blob_threshold_bytes;
+
         // --- Open the Database ---
-        let db = DB::open_cf_descriptors(&opts, path, cfs)?;
+        if options.read_only {
+            // A read-only open can't create the cabinets a newer build added.
+            if DB::list_cf(&opts, path).is_ok_and(|existing| !existing.iter().any(|name| name == CF_BLOBS)) {
+                return Err(MnemonicError::Internal(
+                    "Database predates the blobs cabinet; open it writable once to add it".to_string(),
+                ));
+            }
+            let db = DB::open_cf_descriptors_read_only(&opts, path, cfs, false)?;
+            let backend = Self {
+                db: Arc::new(db),
+                repairs: None,
+                compaction,
+                cipher,
+                blob_threshold,
+                blob_payloads: Mutex::default(),
+                metrics,
+                slow_op_threshold,
+                statistics,
+            };
+            if backend.key_format()? != Some(keys::FORMAT_VERSION) {
+                return Err(MnemonicError::Internal(
+                    "Database predates the binary key format; open it writable once to migrate it"
+                        .to_string(),
+                ));
+            }
+            backend.check_encryption(false)?;
+            backend.start_statistics_sampler(options)?;
+            return Ok(backend);
+        }
+        let db = Arc::new(DB::open_cf_descriptors(&opts, path, cfs).map_err(|e| locked_or(e, path))?);
+        let repairs = Some(spawn_index_repairs(&db)?);
+        let backend = Self {
+            db,
+            repairs,
+            compaction,
+            cipher,
+            blob_threshold,
+            blob_payloads: Mutex::default(),
+            metrics,
+            slow_op_threshold,
+            statistics,
+        };
+        backend.check_encryption(true)?;
+        backend.start_statistics_sampler(options)?;
+
+        // Databases written before binary keys existed are upgraded in place, once.
+        if backend.key_format()? != Some(keys::FORMAT_VERSION) {
+            let report = backend.migrate_keys()?;
+            if report.migrated > 0 {
+                tracing::info!("Migrated {} keys to the binary key format", report.migrated);
+            }
+        }
+
+        Ok(backend)
+    }
+
+    /// Writes the WAL and every memtable out to disk, so nothing depends on replaying the
+    /// log the next time the database opens.
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush_wal(true)?;
+        for name in ALL_CFS {
+            self.db.flush_cf(self.db.cf_handle(name).unwrap())?;
+        }
+        Ok(())
+    }
+
+    /// The compaction filter's settings and the floors it applies, if it is on.
+    pub fn version_compaction(&self) -> Option<(&VersionCompaction, &CompactionHorizon)> {
+        self.compaction.as_ref().map(|(config, horizon)| (config, &**horizon))
+    }
+
+    /// Fails unless the configured key, if any, is the one the database is encrypted with.
+    /// A database opened writable with a key for the first time records it, and from then on
+    /// can't be opened without it.
+    fn check_encryption(&self, record: bool) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let marker = self.db.get_cf(cf, [keys::ENCRYPTION_MARKER])?;
+        match (&self.cipher, marker) {
+            (Some(cipher), Some(marker)) => cipher.verify_marker(&marker),
+            (Some(cipher), None) if record => {
+                self.db.put_cf(cf, [keys::ENCRYPTION_MARKER], cipher.marker()?)?;
+                Ok(())
+            }
+            (None, Some(_)) => Err(MnemonicError::Encryption(
+                "Database is encrypted; set StorageOptions::encryption to its key to open it".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rewrites every concept record and concept version under `new`, offline: nothing else
+    /// may have the database open. `None` on either side means plaintext, so this also
+    /// encrypts a plaintext database or decrypts an encrypted one. Records already under `new`
+    /// are left alone, so an interrupted rotation can be run again with the same keys.
+    pub fn rotate_encryption_key(
+        path: &Path,
+        old: Option<&EncryptionKey>,
+        new: Option<&EncryptionKey>,
+    ) -> Result<KeyRotationReport> {
+        const BATCH_SIZE: usize = 10_000;
+
+        let options = StorageOptions { encryption: old.cloned(), ..Default::default() };
+        let backend = Self::with_options(path, &options)?;
+        let old = backend.cipher.as_ref();
+        let new = new.map(DataCipher::new);
+
+        let mut report = KeyRotationReport::default();
+        let mut batch = WriteBatch::default();
+        let cf = backend.db.cf_handle(CF_CONCEPTS).unwrap();
+        for (key, value) in backend.scan_prefix(CF_CONCEPTS, &[keys::CONCEPT])? {
+            // A record that refers to a blob holds nothing sealed; the blob is rotated below.
+            if codec::blob_of(&value).is_some() {
+                continue;
+            }
+            let concept = match codec::decode_concept(&value, old) {
+                Ok(concept) => concept,
+                Err(_) if codec::decode_concept(&value, new.as_ref()).is_ok() => continue,
+                Err(e) => return Err(e),
+            };
+            batch.put_cf(&cf, key, codec::encode_concept(&concept, new.as_ref())?);
+            report.concepts += 1;
+            if batch.len() >= BATCH_SIZE {
+                backend.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        let cf = backend.db.cf_handle(CF_VERSIONS).unwrap();
+        for (key, value) in backend.scan_versions_prefix(&[keys::CONCEPT_VERSION])? {
+            if codec::blob_of(&value).is_some() {
+                continue;
+            }
+            let version = match codec::decode_concept_version(&value, old) {
+                Ok(version) => version,
+                Err(_) if codec::decode_concept_version(&value, new.as_ref()).is_ok() => continue,
+                Err(e) => return Err(e),
+            };
+            batch.put_cf(&cf, key, codec::encode_concept_version(&version, new.as_ref())?);
+            report.concept_versions += 1;
+            if batch.len() >= BATCH_SIZE {
+                backend.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+        let cf_blobs = backend.db.cf_handle(CF_BLOBS).unwrap();
+        for (key, value) in backend.scan_prefix(CF_BLOBS, &[keys::BLOB])? {
+            let Some(hash) = keys::id_of(&key).map(|id| id.as_u128()) else { continue };
+            let data = match codec::decode_blob(&value, hash, old) {
+                Ok(data) => data,
+                Err(_) if codec::decode_blob(&value, hash, new.as_ref()).is_ok() => continue,
+                Err(e) => return Err(e),
+            };
+            batch.put_cf(&cf_blobs, key, codec::encode_blob(&data, hash, new.as_ref())?);
+            report.blobs += 1;
+            if batch.len() >= BATCH_SIZE {
+                backend.db.write(std::mem::take(&mut batch))?;
+            }
+        }
+
+        // The marker changes last, so until the rotation finishes the old key still opens it.
+        match &new {
+            Some(cipher) => batch.put_cf(&cf, [keys::ENCRYPTION_MARKER], cipher.marker()?),
+            None => batch.delete_cf(&cf, [keys::ENCRYPTION_MARKER]),
+        }
+        backend.db.write(batch)?;
+        Ok(report)
+    }
+
+    /// Which key layout the database was written with, if it says.
+    fn key_format(&self) -> Result<Option<u8>> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        Ok(self
+            .db
+            .get_cf(cf, keys::FORMAT_MARKER)?
+            .and_then(|value| value.first().copied()))
+    }
+
+    /// Rewrites every key still in the old string layout ("concept:{uuid}", ...) into the
+    /// binary one, then marks the database as migrated. Opening a database runs this when
+    /// needed, so callers rarely have to.
+    pub fn migrate_keys(&self) -> Result<KeyMigrationReport> {
+        const BATCH_SIZE: usize = 10_000;
+
+        let mut report = KeyMigrationReport::default();
+        for name in ALL_CFS {
+            let cf = self.db.cf_handle(name).unwrap();
+            let mut batch = WriteBatch::default();
+            for item in self.db.iterator_cf_opt(&cf, total_order(), IteratorMode::Start) {
+                let (key, value) = item.map_err(MnemonicError::Storage)?;
+                if name == CF_VERSIONS && &*key == keys::FORMAT_MARKER {
+                    continue;
+                }
+                match keys::from_legacy(&key) {
+                    Some(binary) => {
+                        batch.delete_cf(&cf, &key);
+                        batch.put_cf(&cf, binary, value);
+                        report.migrated += 1;
+                    }
+                    None if !keys::is_binary(&key) => report.unrecognised += 1,
+                    None => {}
+                }
+                if batch.len() >= BATCH_SIZE {
+                    self.db.write(std::mem::take(&mut batch))?;
+                }
+            }
+            self.db.write(batch)?;
+        }
 
-        Ok(Self { db: Arc::new(db) })
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        self.db.put_cf(cf, keys::FORMAT_MARKER, [keys::FORMAT_VERSION])?;
+        Ok(report)
     }
 
-    /// Saves a concept to the database.
+    /// Rewrites every concept, relationship and version record that isn't in the latest
+    /// format (see `codec`). Reads take every format, so this is never required; it spares
+    /// each later read the older decoder, and lets a release eventually drop that decoder.
+    /// Run it with no commits in flight, as `mnemonic-cli migrate` does: a record a commit
+    /// replaces while it is being rewritten would be put back.
+    pub fn migrate_records(&self) -> Result<RecordMigrationReport> {
+        const BATCH_SIZE: usize = 10_000;
+        const RECORDS: [(&str, u8); 4] = [
+            (CF_CONCEPTS, keys::CONCEPT),
+            (CF_RELATIONSHIPS, keys::RELATIONSHIP),
+            (CF_VERSIONS, keys::CONCEPT_VERSION),
+            (CF_VERSIONS, keys::RELATIONSHIP_VERSION),
+        ];
+
+        let mut report = RecordMigrationReport::default();
+        let mut batch = WriteBatch::default();
+        for (name, tag) in RECORDS {
+            let cf = self.db.cf_handle(name).unwrap();
+            for (key, value) in self.scan_prefix(name, &[tag])? {
+                if codec::format_of(&value) == RecordFormat::Version(codec::latest_format(tag)) {
+                    report.current += 1;
+                    continue;
+                }
+                batch.put_cf(&cf, key, codec::upgrade(tag, &value, self.cipher.as_ref())?);
+                report.migrated += 1;
+                if batch.len() >= BATCH_SIZE {
+                    self.db.write(std::mem::take(&mut batch))?;
+                }
+            }
+        }
+        self.db.write(batch)?;
+        Ok(report)
+    }
+
+    /// Writes a concept's head record directly, outside any transaction. The engine's
+    /// version history doesn't see it; commits maintain these records themselves.
     pub fn store_concept(&self, concept: &Concept) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        self.store_concept_record(concept, &mut batch)?;
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Adds a 'put' operation for a concept's head record to a WriteBatch.
+    pub fn store_concept_record(&self, concept: &Concept, batch: &mut WriteBatch) -> Result<()> {
         //1. Get a "handle" to the 'concepts' filing cabinet.
         let cf = self.db.cf_handle(CF_CONCEPTS).unwrap();
 
-        //2. Create a unique key for this concept. We'll use "concept:[UUID]".
-        let key = format!("concept:{}", concept.id);
+        //2. Create a unique key for this concept: [CONCEPT][id].
+        let key = keys::item(keys::CONCEPT, &concept.id);
 
-        //3. Convert our Rust struct into a sequence of bytes.
-        let value = bincode::serialize(concept)?;
+        //3. Convert our Rust struct into a sequence of bytes, sealing its data if configured.
+        let blob = self.offload(&concept.data, &key, batch)?;
+        let value = codec::encode_concept_with(concept, self.cipher.as_ref(), blob)?;
 
-        //4. Put the key and value into the database.
-        self.db.put_cf(cf, key, value)?;
+        //4. Put the key and value into the batch.
+        batch.put_cf(&cf, key, value);
         Ok(())
     }
 
-    /// Retrieves a concept from the database by its ID.
+    /// Adds a 'delete' operation for a concept's head record to a WriteBatch.
+    pub fn delete_concept_record(&self, id: &ConceptId, batch: &mut WriteBatch) {
+        let cf = self.db.cf_handle(CF_CONCEPTS).unwrap();
+        batch.delete_cf(&cf, keys::item(keys::CONCEPT, id));
+    }
+
+    /// Every concept head record.
+    pub fn load_all_concepts(&self) -> Result<Vec<Concept>> {
+        let mut concepts = Vec::new();
+        for (key, value) in self.scan_prefix(CF_CONCEPTS, &[keys::CONCEPT])? {
+            match codec::decode_concept_with(&value, self.cipher.as_ref(), self) {
+                Ok(concept) => concepts.push(concept),
+                Err(e) => tracing::warn!("Skipping unreadable concept {:?}: {}", keys::id_of(&key), e),
+            }
+        }
+        Ok(concepts)
+    }
+
+    /// Retrieves a concept's head record: its latest active version, as of the last commit.
     pub fn get_concept(&self, id: &ConceptId) -> Result<Option<Concept>> {
         let cf = self.db.cf_handle(CF_CONCEPTS).unwrap();
-        let key = format!("concept:{}", id);
+        let key = keys::item(keys::CONCEPT, id);
 
         //1. Ask the database for the value associated with our key.
-        let result = self.db.get_cf(cf, key)?;
+        let result = self.timed("get", &key, || self.db.get_cf(cf, key))?;
 
         //2. The result might be nothing (None) if the key wasn't found.
         match result {
             Some(data) => {
                 //3. If we found data, convert the bytes back into a Concept struct.
-                let concept = bincode::deserialize(&data)?;
+                let concept = codec::decode_concept_with(&data, self.cipher.as_ref(), self)?;
                 Ok(Some(concept))
             }
             None => {
@@ -83,13 +434,22 @@ impl RocksBackend {
         }
     }
 
+    /// Retrieves many concepts in one `multi_get`. The result lines up with `ids`:
+    /// a concept that doesn't exist is `None` in its place.
+    pub fn get_concepts(&self, ids: &[ConceptId]) -> Result<Vec<Option<Concept>>> {
+        let cf = self.db.cf_handle(CF_CONCEPTS).unwrap();
+        self.multi_get(cf, ids.iter().map(|id| keys::item(keys::CONCEPT, id)), |data| {
+            codec::decode_concept_with(data, self.cipher.as_ref(), self)
+        })
+    }
+
     /// Saves a relationship AND its index entries atomically.
     pub fn store_relationship(&self, relationship: &Relationship) -> Result<()> {
         let cf_rels = self.db.cf_handle(CF_RELATIONSHIPS).unwrap();
         let cf_indices = self.db.cf_handle(CF_INDICES).unwrap();
 
-        let key = format!("rel:{}", relationship.id);
-        let value = bincode::serialize(relationship)?;
+        let key = keys::item(keys::RELATIONSHIP, &relationship.id);
+        let value = codec::encode_relationship(relationship)?;
 
         //We use a WriteBatch to make sure everything saves at once, or nothing does.
         let mut batch = WriteBatch::default();
@@ -102,14 +462,23 @@ impl RocksBackend {
 
         //Now, put the index entries in the 'indices' cabinet.
 
-        // Index by source: key = "idx_src:[source_id]:[rel_id]" -> value = empty
-        let source_key = format!("idx_src:{}:{}", relationship.source, relationship.id);
+        // Index by source: key = [INDEX_SOURCE][source_id][rel_id] -> value = rel_id
+        let source_key = keys::index(keys::INDEX_SOURCE, &relationship.source, &relationship.id);
         batch.put_cf(&cf_indices, source_key, &rel_id_bytes);
 
-        //Index by target: key = "idx_tgt:[target_id]:[rel_id]" -> value = empty
-        let target_key = format!("idx_tgt:{}:{}", relationship.target, relationship.id);
+        //Index by target: key = [INDEX_TARGET][target_id][rel_id] -> value = rel_id
+        let target_key = keys::index(keys::INDEX_TARGET, &relationship.target, &relationship.id);
         batch.put_cf(&cf_indices, target_key, &rel_id_bytes);
 
+        //Index by type: key = [INDEX_TYPE][type][0x00][rel_id] -> value = rel_id
+        let type_key = keys::type_index(relationship.relationship_type.as_str(), &relationship.id);
+        batch.put_cf(&cf_indices, type_key, &rel_id_bytes);
+
+        //Index by source and type together: key = [INDEX_SOURCE_TYPE][source_id][type][0x00][rel_id] -> value = rel_id
+        let source_type_key =
+            keys::source_type_index(&relationship.source, relationship.relationship_type.as_str(), &relationship.id);
+        batch.put_cf(&cf_indices, source_type_key, &rel_id_bytes);
+
         //Now, write the entire batch to the database.
         self.db.write(batch)?;
 
@@ -119,30 +488,34 @@ impl RocksBackend {
     /// Retrieves a single relationship by its unique ID.
     pub fn get_relationship(&self, id: &RelationshipId) -> Result<Option<Relationship>> {
         let cf = self.db.cf_handle(CF_RELATIONSHIPS).unwrap();
-        let key = format!("rel:{}", id);
+        let key = keys::item(keys::RELATIONSHIP, id);
 
-        match self.db.get_cf(&cf, key)? {
-            Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+        match self.timed("get", &key, || self.db.get_cf(&cf, key))? {
+            Some(data) => Ok(Some(codec::decode_relationship(&data)?)),
             None => Ok(None),
         }
     }
 
-    /// Finds all relationships that start from a given concept ID.
-    // In src/storage/rocks_backend.rs
+    /// Retrieves many relationships in one `multi_get`, lined up with `ids` like `get_concepts`.
+    pub fn get_relationships(&self, ids: &[RelationshipId]) -> Result<Vec<Option<Relationship>>> {
+        let cf = self.db.cf_handle(CF_RELATIONSHIPS).unwrap();
+        self.multi_get(cf, ids.iter().map(|id| keys::item(keys::RELATIONSHIP, id)), codec::decode_relationship)
+    }
 
+    /// Finds all relationships that start from a given concept ID.
+    /// Index entries whose relationship is gone (or no longer starts here) are skipped and
+    /// queued for deletion.
     pub fn get_relationships_by_source(&self, source_id: &ConceptId) -> Result<Vec<Relationship>> {
         let cf_indices = self.db.cf_handle(CF_INDICES).unwrap();
-        let mut relationships = Vec::new();
+        let mut entries = Vec::new();
 
-        // The prefix to search for, e.g., "idx_src:[source_uuid]:"
-        let prefix_str = format!("idx_src:{}:", source_id);
-        let prefix_bytes = prefix_str.as_bytes();
+        // The prefix to search for: [INDEX_SOURCE][source_id]
+        let prefix = keys::item(keys::INDEX_SOURCE, source_id);
+        let prefix_bytes = &prefix[..];
 
-        // Start an iterator at the beginning of our key range.
-        let iter = self.db.iterator_cf(
-            &cf_indices,
-            rocksdb::IteratorMode::From(prefix_bytes, rocksdb::Direction::Forward),
-        );
+        // Start an iterator at the beginning of our key range. The prefix is exactly one
+        // concept's index entries, so a configured prefix extractor can filter on it.
+        let iter = self.db.prefix_iterator_cf(&cf_indices, prefix_bytes);
 
         for item in iter {
             let (key, value) = item?;
@@ -153,17 +526,113 @@ impl RocksBackend {
                 break;
             }
 
-            // The rest of the logic is the same: deserialize the value and fetch the full relationship.
-            if let Ok(rel_id) = bincode::deserialize::<Uuid>(&value) {
-                if let Some(rel) = self.get_relationship(&rel_id)? {
-                    relationships.push(rel);
+            // The value is the relationship's ID; the relationships are fetched together below.
+            if let (Ok(key), Ok(rel_id)) = (IndexKey::try_from(&*key), bincode::deserialize::<Uuid>(&value)) {
+                entries.push((key, rel_id));
+            }
+        }
+
+        let rel_ids: Vec<RelationshipId> = entries.iter().map(|(_, rel_id)| *rel_id).collect();
+        let mut relationships = Vec::with_capacity(entries.len());
+        for ((key, _), relationship) in entries.into_iter().zip(self.get_relationships(&rel_ids)?) {
+            match relationship {
+                Some(relationship) if relationship.source == *source_id => relationships.push(relationship),
+                _ => {
+                    tracing::warn!("Skipping stale index entry {}", keys::describe(&key));
+                    if let Some(repairs) = &self.repairs {
+                        // A full queue only delays the repair; the next read finds it again.
+                        let _ = repairs.try_send(key);
+                    }
+                }
+            }
+        }
+        Ok(relationships)
+    }
+
+    /// Finds all relationships of one type, like `get_relationships_by_source` but through the
+    /// type index. Entries whose relationship is gone or has changed type are skipped.
+    pub fn get_relationships_by_type(&self, relationship_type: &RelationType) -> Result<Vec<Relationship>> {
+        let name = relationship_type.as_str();
+        // The prefix is shorter or longer than the extractor's fixed length, so this scans in
+        // total order rather than through `prefix_iterator_cf`.
+        let rel_ids: Vec<RelationshipId> = self
+            .scan_prefix(CF_INDICES, &keys::type_index_prefix(name))?
+            .iter()
+            .filter_map(|(key, _)| keys::type_index_id(name, key))
+            .collect();
+
+        let mut relationships = Vec::with_capacity(rel_ids.len());
+        for (rel_id, relationship) in rel_ids.iter().zip(self.get_relationships(&rel_ids)?) {
+            match relationship {
+                Some(relationship) if relationship.relationship_type == *relationship_type => {
+                    relationships.push(relationship)
                 }
+                _ => tracing::warn!("Skipping stale index entry {}", keys::describe(&keys::type_index(name, rel_id))),
+            }
+        }
+        Ok(relationships)
+    }
+
+    /// Finds the relationships of one type that start from a given concept, from the composite
+    /// index alone rather than by intersecting the source and type indexes.
+    pub fn get_relationships_by_source_and_type(
+        &self,
+        source_id: &ConceptId,
+        relationship_type: &RelationType,
+    ) -> Result<Vec<Relationship>> {
+        let cf_indices = self.db.cf_handle(CF_INDICES).unwrap();
+        let prefix = keys::source_type_index_prefix(source_id, relationship_type.as_str());
+
+        // The prefix opens with [INDEX_SOURCE_TYPE][source_id], so the extractor can filter on it.
+        let mut rel_ids = Vec::new();
+        for item in self.db.prefix_iterator_cf(&cf_indices, &prefix) {
+            let (key, _) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            // Longer keys with this prefix belong to a type that continues past a 0x00.
+            if let Some(rel_id) = key.get(prefix.len()..).and_then(|id| Uuid::from_slice(id).ok()) {
+                rel_ids.push(rel_id);
             }
         }
 
+        let mut relationships = Vec::with_capacity(rel_ids.len());
+        for (rel_id, relationship) in rel_ids.iter().zip(self.get_relationships(&rel_ids)?) {
+            match relationship {
+                Some(relationship)
+                    if relationship.source == *source_id && relationship.relationship_type == *relationship_type =>
+                {
+                    relationships.push(relationship)
+                }
+                _ => tracing::warn!(
+                    "Skipping stale index entry {}",
+                    keys::describe(&keys::source_type_index(source_id, relationship_type.as_str(), rel_id))
+                ),
+            }
+        }
         Ok(relationships)
     }
 
+    /// Deletes every source and target index entry whose relationship record is missing or
+    /// has a different endpoint, returning how many there were.
+    pub fn scrub_indices(&self) -> Result<usize> {
+        let cf_indices = self.db.cf_handle(CF_INDICES).unwrap();
+        let mut batch = WriteBatch::default();
+        let mut scrubbed = 0;
+        for tag in [keys::INDEX_SOURCE, keys::INDEX_TARGET] {
+            let entries = self.load_relationship_index(tag)?;
+            let rel_ids: Vec<RelationshipId> = entries.iter().map(|(_, rel_id)| *rel_id).collect();
+            for ((concept_id, rel_id), relationship) in entries.iter().zip(self.get_relationships(&rel_ids)?) {
+                if !indexes(tag, concept_id, relationship.as_ref()) {
+                    batch.delete_cf(&cf_indices, keys::index(tag, concept_id, rel_id));
+                    scrubbed += 1;
+                }
+            }
+        }
+        self.db.write(batch)?;
+        Ok(scrubbed)
+    }
+
     /// Delete a relationship AND its index entries atomically.
     pub fn delete_ralationship(&self, id: &RelationshipId) -> Result<()> {
         let cf_rels = self.db.cf_handle(CF_RELATIONSHIPS).unwrap();
@@ -174,11 +643,13 @@ impl RocksBackend {
             let mut batch = WriteBatch::default();
 
             // Delete the main relationship data.
-            batch.delete_cf(&cf_rels, format!("rel:{}", id));
+            batch.delete_cf(&cf_rels, keys::item(keys::RELATIONSHIP, id));
 
             // Delete the index entries.
-            batch.delete_cf(&cf_indices, format!("idx_src::{}:{}", rel.source, rel.id));
-            batch.delete_cf(&cf_indices, format!("idx_tgt::{}:{}", rel.target, rel.id));
+            batch.delete_cf(&cf_indices, keys::index(keys::INDEX_SOURCE, &rel.source, &rel.id));
+            batch.delete_cf(&cf_indices, keys::index(keys::INDEX_TARGET, &rel.target, &rel.id));
+            batch.delete_cf(&cf_indices, keys::type_index(rel.relationship_type.as_str(), &rel.id));
+            batch.delete_cf(&cf_indices, keys::source_type_index(&rel.source, rel.relationship_type.as_str(), &rel.id));
 
             self.db.write(batch)?;
         }
@@ -195,10 +666,11 @@ impl RocksBackend {
     ) -> Result<()> {
         let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
 
-        // We'll create a key like: "cv:{concept_id}:{version_number}"
-        // This lets us easily look up all versions for a concept
-        let key = format!("cv:{}:{}", version.concept_id, version.version);
-        let value = bincode::serialize(version)?;
+        // Key: [CONCEPT_VERSION][concept_id][version_number]
+        // This lets us easily look up all versions for a concept, oldest first.
+        let key = keys::version(keys::CONCEPT_VERSION, &version.concept_id, version.version);
+        let blob = self.offload(&version.data, &key, batch)?;
+        let value = codec::encode_concept_version_with(version, self.cipher.as_ref(), blob)?;
 
         batch.put_cf(&cf, key, value);
         Ok(())
@@ -212,53 +684,1015 @@ impl RocksBackend {
     ) -> Result<()> {
         let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
 
-        // Key: "rv:{relationship_id}:{version_number}" (rv for Relationship Version)
-        let key = format!("rv:{}:{}", version.relationship_id, version.version);
-        let value = bincode::serialize(version)?;
+        // Key: [RELATIONSHIP_VERSION][relationship_id][version_number]
+        let key = keys::version(keys::RELATIONSHIP_VERSION, &version.relationship_id, version.version);
+        let value = codec::encode_relationship_version(version)?;
+
+        batch.put_cf(&cf, key, value);
+
+        Ok(())
+    }
+
+    /// Adds a 'delete' operation for one ConceptVersion to a WriteBatch.
+    pub fn delete_concept_version(
+        &self,
+        concept_id: &ConceptId,
+        version: u64,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        batch.delete_cf(&cf, keys::version(keys::CONCEPT_VERSION, concept_id, version));
+        Ok(())
+    }
+
+    /// Adds a 'delete' operation for one RelationshipVersion to a WriteBatch.
+    pub fn delete_relationship_version(
+        &self,
+        relationship_id: &RelationshipId,
+        version: u64,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        batch.delete_cf(&cf, keys::version(keys::RELATIONSHIP_VERSION, relationship_id, version));
+        Ok(())
+    }
+
+    /// Adds one 'delete range' operation for versions 0 through `upto_version` of a concept
+    /// (`keys::CONCEPT_VERSION`) or relationship (`keys::RELATIONSHIP_VERSION`) to a WriteBatch.
+    /// Big-endian version numbers make those keys one contiguous range, so however many
+    /// versions there are this leaves a single tombstone, which the next compaction drops.
+    pub fn delete_version_range(&self, tag: u8, id: &Uuid, upto_version: u64, batch: &mut WriteBatch) {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let from = keys::version(tag, id, 0);
+        match upto_version.checked_add(1) {
+            Some(end) => batch.delete_range_cf(&cf, from, keys::version(tag, id, end)),
+            // The range end is exclusive, and nothing sorts after the last version number.
+            None => {
+                batch.delete_range_cf(&cf, from, keys::version(tag, id, u64::MAX));
+                batch.delete_cf(&cf, keys::version(tag, id, u64::MAX));
+            }
+        }
+    }
+
+    /// Like `delete_version_range` for many items of one kind: each (item, first kept version)
+    /// in `plan` loses every version before the one it keeps.
+    pub fn delete_versions_before(&self, tag: u8, plan: &[(Uuid, u64)], batch: &mut WriteBatch) {
+        for (id, first_kept) in plan {
+            if let Some(upto_version) = first_kept.checked_sub(1) {
+                self.delete_version_range(tag, id, upto_version, batch);
+            }
+        }
+    }
+
+    /// Deletes the blobs no record refers to any more, and returns how many it deleted.
+    /// References go stale when their record is deleted or rewritten, so each one is checked
+    /// against its record first. Callers hold the commit lock, so no commit can add a
+    /// reference to a blob while this decides it has none.
+    pub fn collect_blobs(&self) -> Result<usize> {
+        let cf = self.db.cf_handle(CF_BLOBS).unwrap();
+        let mut batch = WriteBatch::default();
+        let mut referenced = HashSet::new();
+        for (key, _) in self.scan_prefix(CF_BLOBS, &[keys::BLOB_REF])? {
+            let Some((hash, record)) = keys::split_blob_ref(&key) else { continue };
+            let cabinet = match record.first() {
+                Some(&keys::CONCEPT) => CF_CONCEPTS,
+                _ => CF_VERSIONS,
+            };
+            let value = self.db.get_cf(self.db.cf_handle(cabinet).unwrap(), record)?;
+            if value.is_some_and(|value| codec::blob_of(&value) == Some(hash)) {
+                referenced.insert(hash);
+            } else {
+                batch.delete_cf(&cf, &key);
+            }
+        }
+        let mut collected = 0;
+        for item in self.db.iterator_cf_opt(&cf, total_order(), IteratorMode::From(&[keys::BLOB], rocksdb::Direction::Forward)) {
+            let (key, _) = item.map_err(MnemonicError::Storage)?;
+            if key.first() != Some(&keys::BLOB) {
+                break;
+            }
+            if keys::id_of(&key).is_some_and(|id| !referenced.contains(&id.as_u128())) {
+                batch.delete_cf(&cf, &key);
+                collected += 1;
+            }
+        }
+        self.db.write(batch)?;
+        Ok(collected)
+    }
+
+    /// Adds 'delete' operations for every trace of a concept to a WriteBatch: the given
+    /// versions, its version counter and its record in the 'concepts' cabinet.
+    pub fn delete_concept_records(
+        &self,
+        concept_id: &ConceptId,
+        versions: &[u64],
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        for &version in versions {
+            self.delete_concept_version(concept_id, version, batch)?;
+        }
+        let cf_versions = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let cf_concepts = self.db.cf_handle(CF_CONCEPTS).unwrap();
+        batch.delete_cf(&cf_versions, keys::item(keys::CONCEPT_HEAD, concept_id));
+        batch.delete_cf(&cf_concepts, keys::item(keys::CONCEPT, concept_id));
+        Ok(())
+    }
+
+    /// Adds 'delete' operations for every trace of a relationship to a WriteBatch: the given
+    /// versions, its version counter, its record and its index entries.
+    pub fn delete_relationship_records(
+        &self,
+        relationship: &RelationshipVersion,
+        versions: &[u64],
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let id = &relationship.relationship_id;
+        for &version in versions {
+            self.delete_relationship_version(id, version, batch)?;
+        }
+        let cf_versions = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let cf_rels = self.db.cf_handle(CF_RELATIONSHIPS).unwrap();
+        let cf_indices = self.db.cf_handle(CF_INDICES).unwrap();
+        batch.delete_cf(&cf_versions, keys::item(keys::RELATIONSHIP_HEAD, id));
+        batch.delete_cf(&cf_rels, keys::item(keys::RELATIONSHIP, id));
+        batch.delete_cf(&cf_indices, keys::index(keys::INDEX_SOURCE, &relationship.source, id));
+        batch.delete_cf(&cf_indices, keys::index(keys::INDEX_TARGET, &relationship.target, id));
+        batch.delete_cf(&cf_indices, keys::type_index(relationship.relationship_type.as_str(), id));
+        batch.delete_cf(
+            &cf_indices,
+            keys::source_type_index(&relationship.source, relationship.relationship_type.as_str(), id),
+        );
+        Ok(())
+    }
+
+    /// Loads every relationship saved with `store_relationship`.
+    pub fn load_all_relationships(&self) -> Result<Vec<Relationship>> {
+        let mut relationships = Vec::new();
+        for (key, value) in self.scan_prefix(CF_RELATIONSHIPS, &[keys::RELATIONSHIP])? {
+            match codec::decode_relationship(&value) {
+                Ok(relationship) => relationships.push(relationship),
+                Err(e) => tracing::warn!("Skipping unreadable relationship {:?}: {}", keys::id_of(&key), e),
+            }
+        }
+        Ok(relationships)
+    }
+
+    /// Every entry of the source (`keys::INDEX_SOURCE`) or target (`keys::INDEX_TARGET`)
+    /// index, as (concept, relationship) pairs.
+    pub fn load_relationship_index(&self, tag: u8) -> Result<Vec<(ConceptId, RelationshipId)>> {
+        Ok(self
+            .scan_prefix(CF_INDICES, &[tag])?
+            .iter()
+            .filter_map(|(key, _)| {
+                let relationship_id = Uuid::from_slice(key.get(keys::ITEM_PREFIX_LEN..)?).ok()?;
+                Some((keys::id_of(key)?, relationship_id))
+            })
+            .collect())
+    }
+
+    /// Adds a 'put' operation for one source or target index entry to a WriteBatch.
+    pub fn store_relationship_index_entry(
+        &self,
+        tag: u8,
+        concept_id: &ConceptId,
+        relationship_id: &RelationshipId,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        let value = bincode::serialize(relationship_id)?;
+        batch.put_cf(&cf, keys::index(tag, concept_id, relationship_id), value);
+        Ok(())
+    }
+
+    /// Adds a 'delete' operation for one source or target index entry to a WriteBatch.
+    pub fn delete_relationship_index_entry(
+        &self,
+        tag: u8,
+        concept_id: &ConceptId,
+        relationship_id: &RelationshipId,
+        batch: &mut WriteBatch,
+    ) {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        batch.delete_cf(&cf, keys::index(tag, concept_id, relationship_id));
+    }
+
+    /// Every entry of the type index, as (type, relationship) pairs.
+    pub fn load_type_index(&self) -> Result<Vec<(RelationType, RelationshipId)>> {
+        Ok(self
+            .scan_prefix(CF_INDICES, &[keys::INDEX_TYPE])?
+            .iter()
+            .filter_map(|(key, _)| keys::split_type_index(key))
+            .map(|(name, relationship_id)| (RelationType::new(name), relationship_id))
+            .collect())
+    }
+
+    /// Adds a 'put' operation for one type index entry to a WriteBatch.
+    pub fn store_type_index_entry(
+        &self,
+        relationship_type: &RelationType,
+        relationship_id: &RelationshipId,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        let value = bincode::serialize(relationship_id)?;
+        batch.put_cf(&cf, keys::type_index(relationship_type.as_str(), relationship_id), value);
+        Ok(())
+    }
+
+    /// Adds a 'delete' operation for one type index entry to a WriteBatch.
+    pub fn delete_type_index_entry(
+        &self,
+        relationship_type: &RelationType,
+        relationship_id: &RelationshipId,
+        batch: &mut WriteBatch,
+    ) {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        batch.delete_cf(&cf, keys::type_index(relationship_type.as_str(), relationship_id));
+    }
+
+    /// Every entry of the composite source and type index, as (source, type, relationship) triples.
+    pub fn load_source_type_index(&self) -> Result<Vec<(ConceptId, RelationType, RelationshipId)>> {
+        Ok(self
+            .scan_prefix(CF_INDICES, &[keys::INDEX_SOURCE_TYPE])?
+            .iter()
+            .filter_map(|(key, _)| keys::split_source_type_index(key))
+            .map(|(source_id, name, relationship_id)| (source_id, RelationType::new(name), relationship_id))
+            .collect())
+    }
+
+    /// Adds a 'put' operation for one composite source and type index entry to a WriteBatch.
+    pub fn store_source_type_index_entry(
+        &self,
+        source_id: &ConceptId,
+        relationship_type: &RelationType,
+        relationship_id: &RelationshipId,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        let value = bincode::serialize(relationship_id)?;
+        batch.put_cf(&cf, keys::source_type_index(source_id, relationship_type.as_str(), relationship_id), value);
+        Ok(())
+    }
+
+    /// Adds a 'delete' operation for one composite source and type index entry to a WriteBatch.
+    pub fn delete_source_type_index_entry(
+        &self,
+        source_id: &ConceptId,
+        relationship_type: &RelationType,
+        relationship_id: &RelationshipId,
+        batch: &mut WriteBatch,
+    ) {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        batch.delete_cf(&cf, keys::source_type_index(source_id, relationship_type.as_str(), relationship_id));
+    }
+
+    /// Adds a 'put' operation for a content hash index entry to a WriteBatch.
+    pub fn store_content_hash(
+        &self,
+        hash: ContentHash,
+        concept_id: &ConceptId,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+
+        // Key: [CONTENT_HASH][hash] -> value = concept_id. The hash is as wide as a UUID.
+        let key = keys::item(keys::CONTENT_HASH, &Uuid::from_u128(hash));
+        batch.put_cf(&cf, key, bincode::serialize(concept_id)?);
+        Ok(())
+    }
+
+    /// Adds a 'delete' operation for a content hash index entry to a WriteBatch.
+    pub fn delete_content_hash(&self, hash: ContentHash, batch: &mut WriteBatch) -> Result<()> {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        batch.delete_cf(&cf, keys::item(keys::CONTENT_HASH, &Uuid::from_u128(hash)));
+        Ok(())
+    }
+
+    /// Loads the whole content hash index.
+    pub fn load_all_content_hashes(&self) -> Result<Vec<(ContentHash, ConceptId)>> {
+        let cf = self.db.cf_handle(CF_INDICES).unwrap();
+        let prefix = [keys::CONTENT_HASH];
+        let iter = self.db.iterator_cf_opt(
+            &cf,
+            total_order(),
+            IteratorMode::From(&prefix, rocksdb::Direction::Forward),
+        );
+
+        let mut entries = Vec::new();
+        for item in iter {
+            let (key, value) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            if let (Some(hash), Ok(concept_id)) = (keys::id_of(&key), bincode::deserialize(&value)) {
+                entries.push((hash.as_u128(), concept_id));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Adds a 'put' operation for a transaction's metadata to a WriteBatch.
+    /// It lives next to the versions it describes, keyed by the transaction ID.
+    pub fn store_transaction_metadata(
+        &self,
+        transaction_id: &TransactionId,
+        metadata: &TransactionMetadata,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+
+        // Key: [TRANSACTION_METADATA][transaction_id]
+        let key = keys::item(keys::TRANSACTION_METADATA, transaction_id);
+        let value = bincode::serialize(metadata)?;
 
         batch.put_cf(&cf, key, value);
+        Ok(())
+    }
 
+    /// Adds a 'put' operation for a concept's latest version number to a WriteBatch.
+    /// This counter is what keeps version numbers unique, even after old versions are gone.
+    pub fn store_concept_head(
+        &self,
+        concept_id: &ConceptId,
+        head: u64,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+
+        // Key: [CONCEPT_HEAD][concept_id]
+        let key = keys::item(keys::CONCEPT_HEAD, concept_id);
+        batch.put_cf(&cf, key, bincode::serialize(&head)?);
+        Ok(())
+    }
+
+    /// Adds a 'put' operation for a relationship's latest version number to a WriteBatch.
+    pub fn store_relationship_head(
+        &self,
+        relationship_id: &RelationshipId,
+        head: u64,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+
+        // Key: [RELATIONSHIP_HEAD][relationship_id]
+        let key = keys::item(keys::RELATIONSHIP_HEAD, relationship_id);
+        batch.put_cf(&cf, key, bincode::serialize(&head)?);
         Ok(())
     }
 
+    /// Loads the latest version number of every concept.
+    pub fn load_all_concept_heads(&self) -> Result<Vec<(ConceptId, u64)>> {
+        self.load_heads(keys::CONCEPT_HEAD)
+    }
+
+    /// Loads the latest version number of every relationship.
+    pub fn load_all_relationship_heads(&self) -> Result<Vec<(RelationshipId, u64)>> {
+        self.load_heads(keys::RELATIONSHIP_HEAD)
+    }
+
+    /// The latest version number recorded for one concept, if any.
+    pub fn load_concept_head(&self, concept_id: &ConceptId) -> Result<Option<u64>> {
+        self.load_head(keys::CONCEPT_HEAD, concept_id)
+    }
+
+    /// The latest version number recorded for one relationship, if any.
+    pub fn load_relationship_head(&self, relationship_id: &RelationshipId) -> Result<Option<u64>> {
+        self.load_head(keys::RELATIONSHIP_HEAD, relationship_id)
+    }
+
+    /// Every stored version of one concept, in key order.
+    pub fn load_concept_versions_of(&self, concept_id: &ConceptId) -> Result<Vec<ConceptVersion>> {
+        let records = self.scan_versions_prefix(&keys::item(keys::CONCEPT_VERSION, concept_id))?;
+        Ok(self.decode_concept_versions(records))
+    }
+
+    /// Every stored version of one relationship, in key order.
+    pub fn load_relationship_versions_of(&self, relationship_id: &RelationshipId) -> Result<Vec<RelationshipVersion>> {
+        let records = self.scan_versions_prefix(&keys::item(keys::RELATIONSHIP_VERSION, relationship_id))?;
+        Ok(decode_relationship_versions(records))
+    }
+
     /// Loads all concept versions from the database.
     /// This is used to "hydrate" the in-memory VersionStore on startup.
     pub fn load_all_concept_versions(&self) -> Result<Vec<ConceptVersion>> {
+        self.load_concept_versions_in(0..=u8::MAX)
+    }
+
+    /// Loads the versions of the concepts whose IDs start with a byte in `leading`, so
+    /// hydration can read slices of the keyspace side by side.
+    pub fn load_concept_versions_in(&self, leading: RangeInclusive<u8>) -> Result<Vec<ConceptVersion>> {
+        let records = self.scan_versions_leading(keys::CONCEPT_VERSION, leading)?;
+        Ok(self.decode_concept_versions(records))
+    }
+
+    fn decode_concept_versions(&self, records: Vec<RawRecord>) -> Vec<ConceptVersion> {
+        // For each record found, deserialize the value back into a ConceptVersion.
+        // In real code, we'd log deserialization errors. For now, we just skip them.
+        records
+            .iter()
+            .filter_map(|(_key, value)| codec::decode_concept_version_with(value, self.cipher.as_ref(), self).ok())
+            .collect()
+    }
+
+    /// Loads all relationship versions from the database.
+    /// This is used to "hydrate" the in-memory VersionStore on startup.
+    pub fn load_all_relationship_versions(&self) -> Result<Vec<RelationshipVersion>> {
+        self.load_relationship_versions_in(0..=u8::MAX)
+    }
+
+    /// Loads the versions of the relationships whose IDs start with a byte in `leading`.
+    pub fn load_relationship_versions_in(&self, leading: RangeInclusive<u8>) -> Result<Vec<RelationshipVersion>> {
+        let records = self.scan_versions_leading(keys::RELATIONSHIP_VERSION, leading)?;
+        Ok(decode_relationship_versions(records))
+    }
+
+    /// The (item, version number) of every version key under `keys::CONCEPT_VERSION` or
+    /// `keys::RELATIONSHIP_VERSION`, read from the keys alone so unreadable values still count.
+    pub fn load_version_numbers(&self, tag: u8) -> Result<Vec<(Uuid, u64)>> {
+        Ok(self
+            .scan_versions_prefix(&[tag])?
+            .iter()
+            .filter_map(|(key, _)| {
+                let number = <[u8; 8]>::try_from(key.get(keys::ITEM_PREFIX_LEN..)?).ok()?;
+                Some((keys::id_of(key)?, u64::from_be_bytes(number)))
+            })
+            .collect())
+    }
+
+    /// Loads the metadata of every transaction that recorded some.
+    pub fn load_all_transaction_metadata(&self) -> Result<Vec<(TransactionId, TransactionMetadata)>> {
+        let mut records = Vec::new();
+        for (key, value) in self.scan_versions_prefix(&[keys::TRANSACTION_METADATA])? {
+            if let (Some(id), Ok(metadata)) = (keys::id_of(&key), bincode::deserialize(&value)) {
+                records.push((id, metadata));
+            }
+        }
+        Ok(records)
+    }
+
+    /// Saves the schema for concepts labelled `label`, replacing any earlier one, where
+    /// databases kept schemas before the `schemas` system concept. Schemas are kept as
+    /// JSON, next to the versions they govern.
+    pub fn store_schema(&self, label: &str, schema: &serde_json::Value) -> Result<()> {
         let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
 
-        // Create an iterator that scans the entire 'versions' column family.
-        let mut iter = self.db.iterator_cf(&cf, IteratorMode::Start);
-        let mut versions = Vec::new();
+        // Key: [SCHEMA][label]
+        let value = serde_json::to_vec(schema).map_err(std::io::Error::from)?;
+        self.db.put_cf(cf, keys::named(keys::SCHEMA, label), value)?;
+        Ok(())
+    }
 
-        while let Some(result) = iter.next() {
-            match result {
-                Ok((_key, value)) => {
-                    // For each record found, deserialize the value back into a ConceptVersion.
-                    if let Ok(version) = bincode::deserialize(&value) {
-                        versions.push(version);
-                    }
-                    // In real code, we'd log deserialization errors. For now, we just skip them.
+    /// Loads every label's schema.
+    pub fn load_all_schemas(&self) -> Result<Vec<(String, serde_json::Value)>> {
+        let mut schemas = Vec::new();
+        for (key, value) in self.scan_versions_prefix(&[keys::SCHEMA])? {
+            let label = String::from_utf8_lossy(&key[1..]).into_owned();
+            match serde_json::from_slice(&value) {
+                Ok(schema) => schemas.push((label, schema)),
+                Err(e) => tracing::warn!("Skipping unreadable schema for label {:?}: {}", label, e),
+            }
+        }
+        Ok(schemas)
+    }
+
+    /// Saves a relationship type's registration, replacing any earlier one, where databases
+    /// kept them before the `relationship-types` system concept. Registrations are kept as
+    /// JSON, like schemas.
+    pub fn store_relationship_type(&self, definition: &RelationshipTypeDefinition) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+
+        // Key: [RELATIONSHIP_TYPE][name]
+        let value = serde_json::to_vec(definition).map_err(std::io::Error::from)?;
+        self.db.put_cf(cf, keys::named(keys::RELATIONSHIP_TYPE, &definition.name), value)?;
+        Ok(())
+    }
+
+    /// Loads every registered relationship type.
+    pub fn load_all_relationship_types(&self) -> Result<Vec<RelationshipTypeDefinition>> {
+        let mut definitions = Vec::new();
+        for (key, value) in self.scan_versions_prefix(&[keys::RELATIONSHIP_TYPE])? {
+            match serde_json::from_slice(&value) {
+                Ok(definition) => definitions.push(definition),
+                Err(e) => tracing::warn!(
+                    "Skipping unreadable relationship type {:?}: {}",
+                    String::from_utf8_lossy(&key[1..]),
+                    e
+                ),
+            }
+        }
+        Ok(definitions)
+    }
+
+    /// Deletes the schemas, relationship types and cardinalities `store_schema`,
+    /// `store_relationship_type` and `store_cardinality` kept, once they have moved to system
+    /// concepts. Returns how many were deleted.
+    pub fn delete_legacy_registries(&self) -> Result<usize> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let mut batch = WriteBatch::default();
+        let mut deleted = 0;
+        for tag in [keys::SCHEMA, keys::RELATIONSHIP_TYPE, keys::CARDINALITY] {
+            for (key, _) in self.scan_versions_prefix(&[tag])? {
+                batch.delete_cf(cf, key);
+                deleted += 1;
+            }
+        }
+        if deleted > 0 {
+            self.write(batch)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Records that a purge erased history at `at`, so changes from before then can't be replayed.
+    pub fn store_history_erased_at(&self, at: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        self.db.put_cf(cf, [keys::HISTORY_ERASED_AT], bincode::serialize(&at)?)?;
+        Ok(())
+    }
+
+    /// When a purge last erased history, if one ever did.
+    pub fn load_history_erased_at(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        match self.db.get_cf(cf, [keys::HISTORY_ERASED_AT])? {
+            Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Adds recording `at` as the last replicated commit to a WriteBatch, so it lands with that
+    /// commit's versions.
+    pub fn store_replicated_through(
+        &self,
+        at: chrono::DateTime<chrono::Utc>,
+        batch: &mut WriteBatch,
+    ) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        batch.put_cf(&cf, [keys::REPLICATED_THROUGH], bincode::serialize(&at)?);
+        Ok(())
+    }
+
+    /// Adds recording `sequence` as the number of the latest commit to a WriteBatch, so it
+    /// lands with that commit's versions.
+    pub fn store_commit_sequence(&self, sequence: u64, batch: &mut WriteBatch) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        batch.put_cf(&cf, [keys::COMMIT_SEQUENCE], bincode::serialize(&sequence)?);
+        Ok(())
+    }
+
+    /// The number of the latest commit, or 0 before the first.
+    pub fn load_commit_sequence(&self) -> Result<u64> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        match self.db.get_cf(cf, [keys::COMMIT_SEQUENCE])? {
+            Some(data) => Ok(bincode::deserialize(&data)?),
+            None => Ok(0),
+        }
+    }
+
+    /// The timestamp of the last commit replicated from a leader, if this is a follower.
+    pub fn load_replicated_through(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        match self.db.get_cf(cf, [keys::REPLICATED_THROUGH])? {
+            Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Saves how many relationships of `relationship_type` a concept may take part in, where
+    /// databases kept constraints before the `cardinalities` system concept. `Many` is the
+    /// default, so it is stored as the absence of a constraint.
+    pub fn store_cardinality(&self, relationship_type: &str, cardinality: Cardinality) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+
+        // Key: [CARDINALITY][relationship type]
+        let key = keys::named(keys::CARDINALITY, relationship_type);
+        if cardinality == Cardinality::Many {
+            self.db.delete_cf(cf, key)?;
+        } else {
+            self.db.put_cf(cf, key, bincode::serialize(&cardinality)?)?;
+        }
+        Ok(())
+    }
+
+    /// Loads every relationship type's cardinality constraint.
+    pub fn load_all_cardinalities(&self) -> Result<Vec<(RelationType, Cardinality)>> {
+        let mut constraints = Vec::new();
+        for (key, value) in self.scan_versions_prefix(&[keys::CARDINALITY])? {
+            let relationship_type = RelationType::new(&String::from_utf8_lossy(&key[1..]));
+            match bincode::deserialize(&value) {
+                Ok(cardinality) => constraints.push((relationship_type, cardinality)),
+                Err(e) => tracing::warn!(
+                    "Skipping unreadable cardinality for relationship type {:?}: {}",
+                    relationship_type,
+                    e
+                ),
+            }
+        }
+        Ok(constraints)
+    }
+
+    /// Saves a webhook registration, replacing any earlier one with the same ID.
+    /// Registrations are kept as JSON, like schemas, but in a keyspace rather than a system
+    /// concept: they carry signing secrets, and system concepts can be read back by anyone
+    /// who can read the graph.
+    pub fn store_webhook(&self, id: &Uuid, webhook: &serde_json::Value) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+
+        // Key: [WEBHOOK][id]
+        let value = serde_json::to_vec(webhook).map_err(std::io::Error::from)?;
+        self.db.put_cf(cf, keys::item(keys::WEBHOOK, id), value)?;
+        Ok(())
+    }
+
+    /// Removes a webhook registration. Removing one that isn't there is not an error.
+    pub fn delete_webhook(&self, id: &Uuid) -> Result<()> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        self.db.delete_cf(cf, keys::item(keys::WEBHOOK, id))?;
+        Ok(())
+    }
+
+    /// Loads every webhook registration.
+    pub fn load_all_webhooks(&self) -> Result<Vec<(Uuid, serde_json::Value)>> {
+        let mut webhooks = Vec::new();
+        for (key, value) in self.scan_versions_prefix(&[keys::WEBHOOK])? {
+            let Some(id) = keys::id_of(&key) else { continue };
+            match serde_json::from_slice(&value) {
+                Ok(webhook) => webhooks.push((id, webhook)),
+                Err(e) => tracing::warn!("Skipping unreadable webhook {}: {}", id, e),
+            }
+        }
+        Ok(webhooks)
+    }
+
+    /// Compacts every cabinet, so space freed by deletes (e.g. pruned versions) is reclaimed.
+    pub fn compact_all(&self) -> Result<()> {
+        for name in ALL_CFS {
+            let cf = self.db.cf_handle(name).unwrap();
+            self.db.compact_range_cf(&cf, None::<&[u8]>, None::<&[u8]>);
+        }
+        Ok(())
+    }
+
+    /// Reports the size and key count of every cabinet. With `exact`, keys are also counted
+    /// one by one, which is accurate but reads the whole database.
+    pub fn stats(&self, exact: bool) -> Result<StorageStats> {
+        let mut column_families = Vec::new();
+        for name in ALL_CFS {
+            let cf = self.db.cf_handle(name).unwrap();
+            let property = |property: &str| -> Result<u64> {
+                Ok(self.db.property_int_value_cf(&cf, property)?.unwrap_or(0))
+            };
+            let exact_keys = if exact {
+                let mut count = 0;
+                for item in self.db.iterator_cf_opt(&cf, total_order(), IteratorMode::Start) {
+                    item.map_err(MnemonicError::Storage)?;
+                    count += 1;
+                }
+                Some(count)
+            } else {
+                None
+            };
+            column_families.push(ColumnFamilyStats {
+                name: name.to_string(),
+                estimated_keys: property("rocksdb.estimate-num-keys")?,
+                exact_keys,
+                live_data_bytes: property("rocksdb.estimate-live-data-size")?,
+                sst_bytes: property("rocksdb.total-sst-files-size")?,
+            });
+        }
+
+        // RocksDB has no property for the WAL, so add up its "*.log" files instead.
+        let wal_bytes = std::fs::read_dir(self.db.path())
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "log"))
+                    .filter_map(|entry| entry.metadata().ok())
+                    .map(|metadata| metadata.len())
+                    .sum()
+            })
+            .unwrap_or(0);
+
+        Ok(StorageStats {
+            total_sst_bytes: column_families.iter().map(|cf| cf.sst_bytes).sum(),
+            column_families,
+            wal_bytes,
+            rocksdb: self.metrics.latest_sample(),
+            slow_ops: self.metrics.slow_ops.get(),
+        })
+    }
+
+    /// Slow operations so far, and the latest statistics sample.
+    pub fn metrics(&self) -> &StorageMetrics {
+        &self.metrics
+    }
+
+    /// Samples RocksDB's statistics now, recording the sample in `metrics`; `None` unless
+    /// `StorageOptions` turned statistics on.
+    pub fn sample_statistics(&self) -> Result<Option<RocksDbSample>> {
+        let Some(Statistics(options)) = &self.statistics else {
+            return Ok(None);
+        };
+        let sample = sample_statistics(&self.db, options)?;
+        self.metrics.record_sample(sample.clone());
+        Ok(Some(sample))
+    }
+
+    /// Whether the database was opened read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.repairs.is_none()
+    }
+
+    /// Writes `batch` atomically, as one timed operation.
+    pub fn write(&self, batch: WriteBatch) -> Result<()> {
+        let entries = batch.len();
+        self.timed_as("write", || format!("a batch of {} entries", entries), || self.db.write(batch))?;
+        Ok(())
+    }
+
+    /// Runs one read or scan of `key`, logging it if it takes at least the slow-op threshold.
+    fn timed<T>(&self, op: &str, key: &[u8], f: impl FnOnce() -> T) -> T {
+        self.timed_as(op, || keys::describe(key), f)
+    }
+
+    /// `timed`, describing what the operation touched with `target`.
+    fn timed_as<T>(&self, op: &str, target: impl FnOnce() -> String, f: impl FnOnce() -> T) -> T {
+        let Some(threshold) = self.slow_op_threshold else {
+            return f();
+        };
+        let started = Instant::now();
+        let result = f();
+        let took = started.elapsed();
+        if took >= threshold {
+            self.metrics.slow_ops.increment();
+            tracing::warn!("Slow RocksDB {} of {} took {:?}", op, target(), took);
+        }
+        result
+    }
+
+    /// Starts sampling statistics in the background, if `options` turns them on.
+    fn start_statistics_sampler(&self, options: &StorageOptions) -> Result<()> {
+        if let (Some(secs), Some(Statistics(db_options))) = (options.statistics_interval_secs, &self.statistics) {
+            let interval = Duration::from_secs(secs.max(1));
+            spawn_statistics_sampler(&self.db, db_options.clone(), Arc::clone(&self.metrics), interval)?;
+        }
+        Ok(())
+    }
+
+    /// Reads and decodes many keys of one cabinet at once, keeping their order.
+    fn multi_get<T, K>(
+        &self,
+        cf: &ColumnFamily,
+        keys: impl Iterator<Item = K>,
+        decode: impl Fn(&[u8]) -> Result<T>,
+    ) -> Result<Vec<Option<T>>>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.db
+            .multi_get_cf(keys.map(|key| (cf, key)))
+            .into_iter()
+            .map(|value| match value? {
+                Some(data) => Ok(Some(decode(&data)?)),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Reads one [tag][id] version counter.
+    fn load_head(&self, tag: u8, id: &Uuid) -> Result<Option<u64>> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let key = keys::item(tag, id);
+        let value = self.timed("get", &key, || self.db.get_cf(cf, key))?;
+        Ok(value.and_then(|value| bincode::deserialize(&value).ok()))
+    }
+
+    /// Reads every [tag][id] version counter.
+    fn load_heads(&self, tag: u8) -> Result<Vec<(Uuid, u64)>> {
+        let mut heads = Vec::new();
+        for (key, value) in self.scan_versions_prefix(&[tag])? {
+            if let (Some(id), Ok(head)) = (keys::id_of(&key), bincode::deserialize(&value)) {
+                heads.push((id, head));
+            }
+        }
+        Ok(heads)
+    }
+
+    /// Moves data past the blob threshold into 'blobs', adding the blob (unless an identical
+    /// one is already there) and a reference from `record_key` to a WriteBatch. Returns the
+    /// hash to store in the record instead of the data, or None to keep the data inline:
+    /// small data, and data whose hash collides with a different blob's.
+    fn offload(&self, data: &ConceptData, record_key: &[u8], batch: &mut WriteBatch) -> Result<Option<ContentHash>> {
+        let ConceptData::Structured(raw) = data else { return Ok(None) };
+        if self.blob_threshold.is_none_or(|threshold| raw.len() < threshold) {
+            return Ok(None);
+        }
+        let cf = self.db.cf_handle(CF_BLOBS).unwrap();
+        let hash = content::exact_hash(raw.as_bytes());
+        let key = keys::item(keys::BLOB, &Uuid::from_u128(hash));
+        match self.timed("get", &key, || self.db.get_cf(cf, key))? {
+            Some(stored) if codec::decode_blob(&stored, hash, self.cipher.as_ref())? != *data => return Ok(None),
+            Some(_) => {}
+            None => {
+                batch.put_cf(&cf, key, codec::encode_blob(data, hash, self.cipher.as_ref())?);
+                self.share_blob(hash, raw);
+            }
+        }
+        batch.put_cf(&cf, keys::blob_ref(hash, record_key), []);
+        Ok(Some(hash))
+    }
+
+    /// Remembers `payload` as the data of blob `hash`, sweeping out payloads nothing holds
+    /// any more each time the map doubles.
+    fn share_blob(&self, hash: ContentHash, payload: &Arc<str>) {
+        let mut payloads = self.blob_payloads.lock().unwrap_or_else(PoisonError::into_inner);
+        payloads.insert(hash, Arc::downgrade(payload));
+        if payloads.len() >= 64 && payloads.len().is_power_of_two() {
+            payloads.retain(|_, payload| payload.strong_count() > 0);
+        }
+    }
+
+    /// Collects every record in the 'versions' cabinet whose key starts with `prefix`.
+    fn scan_versions_prefix(&self, prefix: &[u8]) -> Result<Vec<RawRecord>> {
+        self.scan_prefix(CF_VERSIONS, prefix)
+    }
+
+    /// Collects every record in the versions cabinet under `tag` whose item ID starts with a
+    /// byte in `leading`. The iterator is bounded, so it reads nothing past the range.
+    fn scan_versions_leading(&self, tag: u8, leading: RangeInclusive<u8>) -> Result<Vec<RawRecord>> {
+        let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
+        let start = [tag, *leading.start()];
+        let mut opts = total_order();
+        opts.set_iterate_upper_bound(match leading.end().checked_add(1) {
+            Some(next) => vec![tag, next],
+            None => vec![tag + 1],
+        });
+        self.timed("scan", &start, || {
+            let iter = self.db.iterator_cf_opt(&cf, opts, IteratorMode::From(&start, rocksdb::Direction::Forward));
+            let mut records = Vec::new();
+            for item in iter {
+                records.push(item.map_err(MnemonicError::Storage)?);
+            }
+            Ok(records)
+        })
+    }
+
+    /// Collects every record in the named cabinet whose key starts with `prefix`.
+    fn scan_prefix(&self, cf_name: &str, prefix: &[u8]) -> Result<Vec<RawRecord>> {
+        let cf = self.db.cf_handle(cf_name).unwrap();
+        let prefix_bytes = prefix;
+        self.timed("scan", prefix_bytes, || {
+            let iter = self.db.iterator_cf_opt(
+                &cf,
+                total_order(),
+                IteratorMode::From(prefix_bytes, rocksdb::Direction::Forward),
+            );
+
+            let mut records = Vec::new();
+            for item in iter {
+                let (key, value) = item.map_err(MnemonicError::Storage)?;
+                // Without a prefix extractor the iterator runs to the end of the cabinet,
+                // so stop as soon as we leave our key range.
+                if !key.starts_with(prefix_bytes) {
+                    break;
                 }
-                Err(e) => return Err(MnemonicError::Storage(e)),
+                records.push((key, value));
             }
+            Ok(records)
+        })
+    }
+}
+
+fn decode_relationship_versions(records: Vec<RawRecord>) -> Vec<RelationshipVersion> {
+    records
+        .iter()
+        .filter_map(|(_key, value)| codec::decode_relationship_version(value).ok())
+        .collect()
+}
+
+/// Whether a source or target index entry for `concept_id` rightly points at `relationship`.
+fn indexes(tag: u8, concept_id: &ConceptId, relationship: Option<&Relationship>) -> bool {
+    relationship.is_some_and(|r| {
+        let endpoint = if tag == keys::INDEX_SOURCE { r.source } else { r.target };
+        endpoint == *concept_id
+    })
+}
+
+/// Starts the thread that deletes stale index keys queued by reads. It holds only a weak
+/// reference, and stops once the backend (and with it the sending half) is dropped.
+/// `DatabaseLocked` when RocksDB couldn't take the database's LOCK file, which another open
+/// handle holds; the RocksDB error as it is otherwise.
+fn locked_or(err: rocksdb::Error, path: &Path) -> MnemonicError {
+    if err.to_string().contains("LOCK") {
+        return MnemonicError::DatabaseLocked(path.to_path_buf());
+    }
+    MnemonicError::Storage(err)
+}
+
+fn spawn_index_repairs(db: &Arc<DB>) -> Result<SyncSender<IndexKey>> {
+    let (sender, receiver) = mpsc::sync_channel::<IndexKey>(REPAIR_QUEUE_CAPACITY);
+    let db = Arc::downgrade(db);
+    std::thread::Builder::new().name("index-repair".to_string()).spawn(move || {
+        for key in receiver {
+            let Some(db) = db.upgrade() else {
+                break;
+            };
+            if let Err(e) = repair_index_entry(&db, &key) {
+                tracing::warn!("Could not repair index entry {}: {}", keys::describe(&key), e);
+            }
+        }
+    })?;
+    Ok(sender)
+}
+
+/// Samples the statistics into `metrics` every `interval`, until the database is dropped.
+fn spawn_statistics_sampler(
+    db: &Arc<DB>,
+    options: Options,
+    metrics: Arc<StorageMetrics>,
+    interval: Duration,
+) -> Result<()> {
+    let db = Arc::downgrade(db);
+    std::thread::Builder::new().name("rocksdb-statistics".to_string()).spawn(move || {
+        loop {
+            // Only held while sampling, so closing the database isn't kept waiting.
+            let Some(live) = db.upgrade() else {
+                break;
+            };
+            match sample_statistics(&live, &options) {
+                Ok(sample) => metrics.record_sample(sample),
+                Err(e) => tracing::warn!("Could not sample RocksDB statistics: {}", e),
+            }
+            drop(live);
+            std::thread::sleep(interval);
         }
+    })?;
+    Ok(())
+}
+
+/// Reads RocksDB's tickers and the per-family properties a sample sums.
+fn sample_statistics(db: &DB, options: &Options) -> Result<RocksDbSample> {
+    let tickers = options.get_statistics().map(|dump| stats::parse_tickers(&dump)).unwrap_or_default();
+    let ticker = |name: &str| tickers.get(name).copied().unwrap_or(0);
+    let summed = |property: &str| -> Result<u64> {
+        let mut total = 0;
+        for name in ALL_CFS {
+            total += db.property_int_value_cf(db.cf_handle(name).unwrap(), property)?.unwrap_or(0);
+        }
+        Ok(total)
+    };
+    let (hits, misses) = (ticker("rocksdb.block.cache.hit"), ticker("rocksdb.block.cache.miss"));
+    Ok(RocksDbSample {
+        sampled_at: chrono::Utc::now(),
+        stall_micros: ticker("rocksdb.stall.micros"),
+        pending_compaction_bytes: summed("rocksdb.estimate-pending-compaction-bytes")?,
+        memtable_bytes: summed("rocksdb.cur-size-all-mem-tables")?,
+        block_cache_hits: hits,
+        block_cache_misses: misses,
+        block_cache_hit_rate: (hits + misses > 0).then(|| hits as f64 / (hits + misses) as f64),
+    })
+}
 
-        Ok(versions)
+/// Deletes one queued index entry, unless a write since has made it valid again.
+fn repair_index_entry(db: &DB, key: &IndexKey) -> Result<()> {
+    let (Some(concept_id), Ok(rel_id)) = (keys::id_of(key), Uuid::from_slice(&key[keys::ITEM_PREFIX_LEN..])) else {
+        return Ok(());
+    };
+    let cf_rels = db.cf_handle(CF_RELATIONSHIPS).unwrap();
+    let relationship: Option<Relationship> = match db.get_cf(cf_rels, keys::item(keys::RELATIONSHIP, &rel_id))? {
+        Some(data) => Some(codec::decode_relationship(&data)?),
+        None => None,
+    };
+    if !indexes(key[0], &concept_id, relationship.as_ref()) {
+        db.delete_cf(db.cf_handle(CF_INDICES).unwrap(), key)?;
+        tracing::info!("Deleted stale index entry {}", keys::describe(key));
     }
-    /// Loads all relationship versions from the database.
-    /// This is used to "hydrate" the in-memory VersionStore on startup.
-pub fn load_all_relationship_versions(&self) -> Result<Vec<RelationshipVersion>> {
-    let cf = self.db.cf_handle(CF_VERSIONS).unwrap();
-    let mut versions = Vec::new();
-    // Use a prefix iterator to only scan for "rv:" (Relationship Version) keys
-    let iter = self.db.prefix_iterator_cf(&cf, "rv:");
+    Ok(())
+}
 
-    for item in iter {
-        let (_key, value) = item?;
-        if let Ok(version) = bincode::deserialize::<RelationshipVersion>(&value) {
-            versions.push(version);
+impl BlobSource for RocksBackend {
+    fn blob(&self, hash: ContentHash) -> Result<Vec<u8>> {
+        let key = keys::item(keys::BLOB, &Uuid::from_u128(hash));
+        let cf = self.db.cf_handle(CF_BLOBS).unwrap();
+        self.timed("get", &key, || self.db.get_cf(cf, key))?
+            .ok_or_else(|| MnemonicError::Internal(format!("Blob {:032x} is missing", hash)))
+    }
+
+    fn blob_data(&self, hash: ContentHash, cipher: Option<&DataCipher>) -> Result<ConceptData> {
+        let shared = self.blob_payloads.lock().unwrap_or_else(PoisonError::into_inner).get(&hash).and_then(Weak::upgrade);
+        if let Some(payload) = shared {
+            return Ok(ConceptData::Structured(payload));
+        }
+        let data = codec::decode_blob(&self.blob(hash)?, hash, cipher)?;
+        if let ConceptData::Structured(payload) = &data {
+            self.share_blob(hash, payload);
         }
+        Ok(data)
     }
-    Ok(versions)
 }
+
+/// Read options for scans that cross key prefixes. With a prefix extractor configured,
+/// iterators otherwise only promise correct results within the prefix they started in.
+fn total_order() -> ReadOptions {
+    let mut opts = ReadOptions::default();
+    opts.set_total_order_seek(true);
+    opts
 }
@@ -0,0 +1,110 @@
+// Disk usage figures, as reported by RocksDB, and what the backend samples of RocksDB's own
+// statistics while it runs.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use crate::utils::metrics::Counter;
+
+/// Size and key count of one column family.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnFamilyStats {
+    /// "concepts", "relationships", "indices" or "versions".
+    pub name: String,
+    /// RocksDB's estimate of the number of keys (`rocksdb.estimate-num-keys`).
+    pub estimated_keys: u64,
+    /// The exact number of keys, counted by iterating. Only filled in when asked for.
+    pub exact_keys: Option<u64>,
+    /// Estimated size of live data (`rocksdb.estimate-live-data-size`).
+    pub live_data_bytes: u64,
+    /// Size of all SST files (`rocksdb.total-sst-files-size`).
+    pub sst_bytes: u64,
+}
+
+/// The outcome of `RocksBackend::stats`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub column_families: Vec<ColumnFamilyStats>,
+    /// SST file size summed over every column family.
+    pub total_sst_bytes: u64,
+    /// Size of the write-ahead log files in the database directory.
+    pub wal_bytes: u64,
+    /// The latest sample of RocksDB's statistics, when `StorageOptions` turns them on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rocksdb: Option<RocksDbSample>,
+    /// Reads, writes and scans that took at least the slow-op threshold since the database opened.
+    #[serde(default)]
+    pub slow_ops: u64,
+}
+
+/// RocksDB's running figures at one moment. Stall time and cache lookups count up from the
+/// moment the database opened; the byte counts are current.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RocksDbSample {
+    pub sampled_at: DateTime<Utc>,
+    /// How long writes have been stalled or slowed down (`rocksdb.stall.micros`).
+    pub stall_micros: u64,
+    /// Bytes compaction has yet to rewrite, summed over every column family
+    /// (`rocksdb.estimate-pending-compaction-bytes`).
+    pub pending_compaction_bytes: u64,
+    /// Bytes held in memtables, summed over every column family (`rocksdb.cur-size-all-mem-tables`).
+    pub memtable_bytes: u64,
+    pub block_cache_hits: u64,
+    pub block_cache_misses: u64,
+    /// Hits over all lookups; `None` before the first lookup.
+    pub block_cache_hit_rate: Option<f64>,
+}
+
+/// What a backend has measured of itself, shared with its statistics sampler.
+#[derive(Debug, Default)]
+pub struct StorageMetrics {
+    pub slow_ops: Counter,
+    latest: Mutex<Option<RocksDbSample>>,
+}
+
+impl StorageMetrics {
+    /// The most recent statistics sample, if statistics are on and one has been taken.
+    pub fn latest_sample(&self) -> Option<RocksDbSample> {
+        self.latest.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    pub(crate) fn record_sample(&self, sample: RocksDbSample) {
+        *self.latest.lock().unwrap_or_else(PoisonError::into_inner) = Some(sample);
+    }
+}
+
+/// The tickers in RocksDB's statistics dump, whose lines read `rocksdb.block.cache.hit COUNT : 12`.
+/// Histogram lines, which have no `COUNT :`, are skipped.
+pub(crate) fn parse_tickers(dump: &str) -> HashMap<String, u64> {
+    dump.lines()
+        .filter_map(|line| {
+            let (name, count) = line.split_once(" COUNT : ")?;
+            Some((name.trim().to_string(), count.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+impl StorageStats {
+    /// The figures for one column family, by name.
+    pub fn column_family(&self, name: &str) -> Option<&ColumnFamilyStats> {
+        self.column_families.iter().find(|cf| cf.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tickers_are_read_from_a_statistics_dump() {
+        let dump = "rocksdb.block.cache.miss COUNT : 3\n\
+                    rocksdb.block.cache.hit COUNT : 12\n\
+                    rocksdb.db.get.micros P50 : 1.0 P95 : 2.0 P99 : 3.0 P100 : 4.0 COUNT : 9 SUM : 20\n";
+        let tickers = parse_tickers(dump);
+        assert_eq!(tickers.get("rocksdb.block.cache.hit"), Some(&12));
+        assert_eq!(tickers.get("rocksdb.block.cache.miss"), Some(&3));
+        assert_eq!(tickers.len(), 2);
+    }
+}
@@ -0,0 +1,454 @@
+// Test support: a deterministic simulation of concurrent clients driving a
+// TransactionManager, checked step by step against a single-threaded model of MVCC.
+//
+// Every choice the simulation makes comes from one seeded generator, so a failing seed
+// replays exactly. Failures carry that seed and the trace of operations that led up to them.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::error::{ConflictItem, MnemonicError};
+use crate::graph::transaction::TransactionManager;
+use crate::graph::{IsolationLevel, Transaction};
+use crate::types::concept::{Concept, ConceptData, ConceptId, ConceptVersion};
+
+/// How big a simulation to run.
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    /// How many logical clients interleave their transactions.
+    pub clients: usize,
+    /// How many concepts they all fight over. Small key spaces mean more conflicts.
+    pub keys: usize,
+    /// How many operations to run, across all clients.
+    pub steps: usize,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            clients: 4,
+            keys: 3,
+            steps: 200,
+        }
+    }
+}
+
+/// What a passing simulation did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationReport {
+    pub reads: usize,
+    pub writes: usize,
+    pub commits: usize,
+    /// Commits rejected by first-committer-wins (or, for Serializable, a stale read).
+    pub conflicts: usize,
+    pub aborts: usize,
+}
+
+/// The first point where the manager and the model disagreed.
+#[derive(Debug, Clone)]
+pub struct SimulationFailure {
+    pub seed: u64,
+    pub step: usize,
+    pub message: String,
+    /// Every operation up to and including the failing one.
+    pub trace: Vec<String>,
+}
+
+impl fmt::Display for SimulationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "simulation failed at step {} (seed {}): {}", self.step, self.seed, self.message)?;
+        writeln!(f, "trace:")?;
+        for line in &self.trace {
+            writeln!(f, "  {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SimulationFailure {}
+
+/// Runs `config.steps` random operations against `manager` and checks each result against the
+/// model: reads must match the snapshot (or, for ReadCommitted, the latest commit), a commit
+/// must fail exactly when an item it wrote was committed by someone else after it started,
+/// and the final state must hold every committed write and nothing else.
+///
+/// The keys are drawn from the seed, so several simulations can share one manager.
+pub fn simulate(
+    manager: &TransactionManager,
+    config: &SimulationConfig,
+) -> Result<SimulationReport, SimulationFailure> {
+    Simulation::new(manager, config).run()
+}
+
+/// A small, fast, deterministic generator (SplitMix64), so the simulation needs no RNG crate
+/// and a seed means the same thing on every platform.
+#[derive(Debug, Clone)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`. `bound` must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// The committed history of every key, in commit order.
+#[derive(Debug, Default)]
+struct Model {
+    /// Bumped by every commit that writes something.
+    commits: u64,
+    history: HashMap<ConceptId, Vec<(u64, String)>>,
+}
+
+impl Model {
+    fn value_at(&self, key: &ConceptId, commit: u64) -> Option<String> {
+        self.history
+            .get(key)?
+            .iter()
+            .rev()
+            .find(|(at, _)| *at <= commit)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn latest(&self, key: &ConceptId) -> Option<String> {
+        self.value_at(key, u64::MAX)
+    }
+
+    fn changed_since(&self, key: &ConceptId, commit: u64) -> bool {
+        self.history
+            .get(key)
+            .and_then(|versions| versions.last())
+            .is_some_and(|(at, _)| *at > commit)
+    }
+
+    fn apply(&mut self, writes: &HashMap<ConceptId, String>) {
+        if writes.is_empty() {
+            return;
+        }
+        self.commits += 1;
+        for (key, value) in writes {
+            self.history
+                .entry(*key)
+                .or_default()
+                .push((self.commits, value.clone()));
+        }
+    }
+}
+
+/// One client's open transaction, and what the model expects of it.
+struct Session {
+    transaction: Transaction,
+    /// The last commit the transaction's snapshot includes.
+    started_after: u64,
+    reads: HashSet<ConceptId>,
+    writes: HashMap<ConceptId, String>,
+}
+
+struct Simulation<'a> {
+    manager: &'a TransactionManager,
+    config: &'a SimulationConfig,
+    rng: SplitMix64,
+    keys: Vec<ConceptId>,
+    model: Model,
+    sessions: Vec<Option<Session>>,
+    report: SimulationReport,
+    trace: Vec<String>,
+    step: usize,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(manager: &'a TransactionManager, config: &'a SimulationConfig) -> Self {
+        let mut rng = SplitMix64::new(config.seed);
+        let keys = (0..config.keys)
+            .map(|_| Uuid::from_u64_pair(rng.next_u64(), rng.next_u64()))
+            .collect();
+        Self {
+            manager,
+            config,
+            rng,
+            keys,
+            model: Model::default(),
+            sessions: (0..config.clients).map(|_| None).collect(),
+            report: SimulationReport::default(),
+            trace: Vec::new(),
+            step: 0,
+        }
+    }
+
+    fn run(mut self) -> Result<SimulationReport, SimulationFailure> {
+        for step in 0..self.config.steps {
+            self.step = step;
+            let client = self.rng.below(self.config.clients);
+            match self.sessions[client].take() {
+                None => self.begin(client)?,
+                Some(session) => match self.rng.below(100) {
+                    0..40 => self.read(client, session)?,
+                    40..75 => self.write(client, session),
+                    75..92 => self.commit(client, session)?,
+                    _ => self.abort(client, session)?,
+                },
+            }
+        }
+
+        self.step = self.config.steps;
+        for client in 0..self.sessions.len() {
+            if let Some(session) = self.sessions[client].take() {
+                self.abort(client, session)?;
+            }
+        }
+        self.check_final_state()?;
+        Ok(self.report)
+    }
+
+    fn begin(&mut self, client: usize) -> Result<(), SimulationFailure> {
+        let isolation_level = [
+            IsolationLevel::Snapshot,
+            IsolationLevel::Serializable,
+            IsolationLevel::ReadCommitted,
+        ][self.rng.below(3)];
+        self.log(format!("c{} begin {:?}", client, isolation_level));
+        let transaction = self
+            .manager
+            .begin_transaction(isolation_level)
+            .map_err(|e| self.fail(format!("begin failed: {}", e)))?;
+        self.sessions[client] = Some(Session {
+            transaction,
+            started_after: self.model.commits,
+            reads: HashSet::new(),
+            writes: HashMap::new(),
+        });
+        Ok(())
+    }
+
+    fn read(&mut self, client: usize, mut session: Session) -> Result<(), SimulationFailure> {
+        let key = self.pick_key();
+        let read = self.manager.read_concept(&mut session.transaction, &self.keys[key]);
+        let expected = match session.transaction.isolation_level {
+            IsolationLevel::ReadCommitted => self.model.latest(&self.keys[key]),
+            IsolationLevel::Snapshot | IsolationLevel::Serializable => {
+                self.model.value_at(&self.keys[key], session.started_after)
+            }
+        };
+        let got = match read {
+            Ok(version) => version.as_ref().map(value_of),
+            Err(e) => {
+                self.log(format!("c{} read k{} -> err", client, key));
+                return Err(self.fail(format!("read failed: {}", e)));
+            }
+        };
+        self.log(format!("c{} read k{} -> {:?}", client, key, got));
+        if got != expected {
+            return Err(self.fail(format!("c{} read k{}: expected {:?}, got {:?}", client, key, expected, got)));
+        }
+        session.reads.insert(self.keys[key]);
+        self.report.reads += 1;
+        self.sessions[client] = Some(session);
+        Ok(())
+    }
+
+    fn write(&mut self, client: usize, mut session: Session) {
+        let key = self.pick_key();
+        let value = format!("c{}@{}", client, self.step);
+        self.log(format!("c{} write k{} = {}", client, key, value));
+        let id = self.keys[key];
+        let mut concept = Concept::new(serde_json::Value::String(value.clone()));
+        concept.id = id;
+        session.transaction.write_set.insert(id);
+        session.transaction.pending_writes.insert(id, concept);
+        session.writes.insert(id, value);
+        self.report.writes += 1;
+        self.sessions[client] = Some(session);
+    }
+
+    fn commit(&mut self, client: usize, session: Session) -> Result<(), SimulationFailure> {
+        // First committer wins on everything written; Serializable also loses on stale reads.
+        let mut expected: HashSet<ConceptId> = session
+            .writes
+            .keys()
+            .filter(|key| self.model.changed_since(key, session.started_after))
+            .copied()
+            .collect();
+        if session.transaction.isolation_level == IsolationLevel::Serializable {
+            expected.extend(
+                session
+                    .reads
+                    .iter()
+                    .filter(|key| self.model.changed_since(key, session.started_after)),
+            );
+        }
+
+        let result = self.manager.commit_transaction(session.transaction);
+        self.log(format!(
+            "c{} commit -> {}",
+            client,
+            match &result {
+                Ok(()) => "ok".to_string(),
+                Err(e) => format!("err: {}", e),
+            }
+        ));
+        match result {
+            Ok(()) if expected.is_empty() => {
+                self.model.apply(&session.writes);
+                self.report.commits += 1;
+                Ok(())
+            }
+            Ok(()) => Err(self.fail(format!(
+                "c{} committed over newer writes to {}",
+                client,
+                self.describe_keys(&expected)
+            ))),
+            Err(MnemonicError::TransactionConflict(conflicts)) => {
+                let reported: HashSet<ConceptId> = conflicts
+                    .iter()
+                    .filter_map(|conflict| match conflict.item {
+                        ConflictItem::Concept(id) => Some(id),
+                        _ => None,
+                    })
+                    .collect();
+                if reported != expected {
+                    return Err(self.fail(format!(
+                        "c{} conflicted on {}, expected {}",
+                        client,
+                        self.describe_keys(&reported),
+                        self.describe_keys(&expected)
+                    )));
+                }
+                self.report.conflicts += 1;
+                Ok(())
+            }
+            Err(e) => Err(self.fail(format!("c{} commit failed: {}", client, e))),
+        }
+    }
+
+    fn abort(&mut self, client: usize, session: Session) -> Result<(), SimulationFailure> {
+        self.log(format!("c{} abort", client));
+        self.manager
+            .abort_transaction(session.transaction.id)
+            .map_err(|e| self.fail(format!("abort failed: {}", e)))?;
+        self.report.aborts += 1;
+        Ok(())
+    }
+
+    /// Every committed write is the latest version, numbered by how many commits wrote it.
+    fn check_final_state(&mut self) -> Result<(), SimulationFailure> {
+        let store = self.manager.version_store();
+        for (index, key) in self.keys.iter().enumerate() {
+            let latest = store
+                .get_latest_concept_version(key)
+                .map_err(|e| self.fail(format!("final read failed: {}", e)))?;
+            let expected_versions = self.model.history.get(key).map_or(0, Vec::len) as u64;
+            let got = latest.as_ref().map(value_of);
+            let expected = self.model.latest(key);
+            if got != expected || latest.as_ref().map_or(0, |v| v.version) != expected_versions {
+                return Err(self.fail(format!(
+                    "k{} ends as {:?} (version {:?}), expected {:?} (version {})",
+                    index,
+                    got,
+                    latest.map(|v| v.version),
+                    expected,
+                    expected_versions
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn pick_key(&mut self) -> usize {
+        self.rng.below(self.keys.len())
+    }
+
+    fn describe_keys(&self, keys: &HashSet<ConceptId>) -> String {
+        let mut names: Vec<String> = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| keys.contains(key))
+            .map(|(index, _)| format!("k{}", index))
+            .collect();
+        names.sort();
+        format!("[{}]", names.join(", "))
+    }
+
+    fn log(&mut self, line: String) {
+        self.trace.push(format!("{:>4} {}", self.step, line));
+    }
+
+    fn fail(&self, message: String) -> SimulationFailure {
+        SimulationFailure {
+            seed: self.config.seed,
+            step: self.step,
+            message,
+            trace: self.trace.clone(),
+        }
+    }
+}
+
+fn value_of(version: &ConceptVersion) -> String {
+    match &version.data {
+        ConceptData::Structured(json) => serde_json::from_str(json).unwrap_or_else(|_| json.to_string()),
+        ConceptData::Empty => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::RocksBackend;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_many_seeds_uphold_snapshot_isolation() {
+        let dir = tempdir().unwrap();
+        let manager = TransactionManager::new(Arc::new(RocksBackend::new(dir.path()).unwrap())).unwrap();
+        let mut total = SimulationReport::default();
+        for seed in 0..200 {
+            let config = SimulationConfig { seed, ..Default::default() };
+            let report = simulate(&manager, &config).unwrap_or_else(|failure| panic!("{}", failure));
+            total.commits += report.commits;
+            total.conflicts += report.conflicts;
+        }
+        // The key space is small enough that both outcomes get plenty of coverage.
+        assert!(total.commits > 1000, "{:?}", total);
+        assert!(total.conflicts > 100, "{:?}", total);
+    }
+
+    #[test]
+    fn test_same_seed_replays_the_same_run() {
+        let run = |seed| {
+            let dir = tempdir().unwrap();
+            let manager = TransactionManager::new(Arc::new(RocksBackend::new(dir.path()).unwrap())).unwrap();
+            simulate(&manager, &SimulationConfig { seed, clients: 3, keys: 2, steps: 100 }).unwrap()
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn test_failure_reports_seed_and_trace() {
+        let failure = SimulationFailure {
+            seed: 42,
+            step: 3,
+            message: "c1 read k0: expected None, got Some(\"x\")".to_string(),
+            trace: vec!["   0 c1 begin Snapshot".to_string(), "   3 c1 read k0 -> Some(\"x\")".to_string()],
+        };
+        let text = failure.to_string();
+        assert!(text.starts_with("simulation failed at step 3 (seed 42): c1 read k0"));
+        assert!(text.contains("\n     0 c1 begin Snapshot\n"));
+    }
+}
@@ -1,7 +1,12 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::error::{MnemonicError, Result};
+use crate::types::system;
+
 /// Core concept identifier type. It's just a unique ID.
 pub type ConceptId = Uuid;
 pub type TransactionId = Uuid;
@@ -32,8 +37,27 @@ impl Default for ConceptMetadata {
 pub enum ConceptData {
     // For pure structural nodes, like a group.
     Empty,
-    // For storing structured info, like a user profile.
-    Structured(String),
+    // For storing structured info, like a user profile. Shared, so versions and copies
+    // holding the same JSON don't each keep their own.
+    Structured(Arc<str>),
+}
+
+/// Which kind of `ConceptData` a concept holds, as the API reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConceptKind {
+    #[default]
+    Structured,
+    Empty,
+}
+
+impl ConceptData {
+    pub fn kind(&self) -> ConceptKind {
+        match self {
+            ConceptData::Empty => ConceptKind::Empty,
+            ConceptData::Structured(_) => ConceptKind::Structured,
+        }
+    }
 }
 
 /// The complete Concept struct. This is a node in our graph.
@@ -42,6 +66,14 @@ pub struct Concept {
     pub id: ConceptId,
     pub data: ConceptData,
     pub metadata: ConceptMetadata,
+    /// When the concept stops being active on its own. The engine tombstones it soon after.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Free-form markers like `starred`, kept apart from the data so they can change without
+    /// rewriting it. Unlike labels they say nothing about what the concept is. Like the data,
+    /// a write replaces them: start from the concept's current tags to keep them.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
 }
 
 // These are "constructors" - easy ways to make a new Concept.
@@ -50,22 +82,102 @@ impl Concept {
     pub fn new(data: serde_json::Value) -> Self {
         Self {
             id: Uuid::new_v4(),
-            data: ConceptData::Structured(data.to_string()),
+            data: ConceptData::Structured(data.to_string().into()),
             // Set version to 0 to indicate it's `new` and has never been versioned.
             metadata: ConceptMetadata{version: 0, ..Default::default()},
+            expires_at: None,
+            tags: BTreeSet::new(),
         }
     }
 
+    /// Like `new`, but refuses data outside `limits`. Every version keeps its own copy of
+    /// the data, so an oversized payload costs its size again with each later version.
+    pub fn new_within(data: serde_json::Value, limits: &DataLimits) -> Result<Self> {
+        limits.check(&data)?;
+        Ok(Self::new(data))
+    }
+
     /// Create a new empty concept.
     pub fn empty() -> Self {
         Self {
             id: Uuid::new_v4(),
             data: ConceptData::Empty,
-            metadata: ConceptMetadata::default(),
+            metadata: ConceptMetadata{version: 0, ..Default::default()},
+            expires_at: None,
+            tags: BTreeSet::new(),
         }
     }
 }
 
+/// The longest tag `GraphEngine::add_tags` accepts, in bytes.
+pub const MAX_TAG_BYTES: usize = 128;
+
+/// Refuses a tag that is empty, starts or ends with whitespace, is longer than
+/// `MAX_TAG_BYTES`, or is the `system::SYSTEM_TAG` only system concepts carry.
+pub fn check_tag(tag: &str) -> Result<()> {
+    if tag == system::SYSTEM_TAG {
+        return Err(MnemonicError::InvalidData(format!("{:?} is reserved for system concepts", tag)));
+    }
+    if tag.trim().is_empty() || tag.trim() != tag {
+        return Err(MnemonicError::InvalidData(format!("{:?} is not a tag: tags can't be blank or padded", tag)));
+    }
+    if tag.len() > MAX_TAG_BYTES {
+        return Err(MnemonicError::InvalidData(format!("tags are at most {} bytes", MAX_TAG_BYTES)));
+    }
+    Ok(())
+}
+
+/// What concept data the engine accepts on its store paths.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataLimits {
+    /// The most bytes the data may take as JSON.
+    pub max_bytes: usize,
+    /// How deeply arrays and objects may nest. A flat object is at depth 1.
+    pub max_depth: usize,
+    /// Refuse data whose top level isn't a JSON object.
+    pub require_object: bool,
+}
+
+impl Default for DataLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024,
+            max_depth: 32,
+            require_object: false,
+        }
+    }
+}
+
+impl DataLimits {
+    pub fn check(&self, data: &serde_json::Value) -> Result<()> {
+        if self.require_object && !data.is_object() {
+            return Err(MnemonicError::InvalidData("data must be a JSON object".to_string()));
+        }
+        if nesting_exceeds(data, self.max_depth) {
+            return Err(MnemonicError::InvalidData(format!(
+                "data nests deeper than the limit of {}",
+                self.max_depth
+            )));
+        }
+        let size = serde_json::to_vec(data).map_err(std::io::Error::from)?.len();
+        if size > self.max_bytes {
+            return Err(MnemonicError::PayloadTooLarge { size, limit: self.max_bytes });
+        }
+        Ok(())
+    }
+}
+
+/// Whether arrays and objects in `value` nest more than `limit` deep. Stops descending at
+/// the limit, so it never walks further into a hostile payload than it has to.
+fn nesting_exceeds(value: &serde_json::Value, limit: usize) -> bool {
+    let deeper = |child: &serde_json::Value| nesting_exceeds(child, limit - 1);
+    match value {
+        serde_json::Value::Array(items) => limit == 0 || items.iter().any(deeper),
+        serde_json::Value::Object(fields) => limit == 0 || fields.values().any(deeper),
+        _ => false,
+    }
+}
+
 /// A versioned snapshot of a concept's state for MVCC.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ConceptVersion {
@@ -76,6 +188,12 @@ pub struct ConceptVersion {
     pub created_by: TransactionId,
     pub deleted_at: Option<DateTime<Utc>>,
     pub deleted_by: Option<TransactionId>,
+    /// Carried over from the concept: the version stops being active at this moment.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Carried over from the concept: its tags as of this version.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
 }
 
 impl ConceptVersion {
@@ -88,11 +206,43 @@ impl ConceptVersion {
             created_by: transaction_id,
             deleted_at: None,
             deleted_by: None,
+            expires_at: concept.expires_at,
+            tags: concept.tags.clone(),
         }
     }
 
     /// Checks if this version was "live" at a given timestamp
     pub fn is_active_at(&self, timestamp: DateTime<Utc>) -> bool {
-        self.created_at <= timestamp && self.deleted_at.map_or(true, |deleted| deleted > timestamp)
+        self.exists_at(timestamp) && !self.is_expired_at(timestamp)
+    }
+
+    /// Like `is_active_at`, but counts an expired version as live until it is tombstoned.
+    pub fn exists_at(&self, timestamp: DateTime<Utc>) -> bool {
+        self.created_at <= timestamp && self.deleted_at.is_none_or(|deleted| deleted > timestamp)
+    }
+
+    /// Whether the version's expiry had passed by `timestamp`.
+    pub fn is_expired_at(&self, timestamp: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires| expires <= timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_data_limits_check_size_depth_and_shape() {
+        let limits = DataLimits { max_bytes: 32, max_depth: 2, require_object: true };
+
+        assert!(limits.check(&json!({"a": {"b": [1, 2]}})).is_err());
+        assert!(limits.check(&json!({"a": {"b": 1}})).is_ok());
+        assert!(matches!(
+            limits.check(&json!({"name": "far too long for thirty-two bytes"})),
+            Err(MnemonicError::PayloadTooLarge { limit: 32, .. })
+        ));
+        assert!(matches!(limits.check(&json!([1])), Err(MnemonicError::InvalidData(_))));
+        assert!(DataLimits::default().check(&json!("a bare string")).is_ok());
     }
 }
@@ -0,0 +1,131 @@
+// Content hashes of concept data, for storing a payload once however often it is ingested.
+//
+// Structured data is hashed in a canonical form: object keys sorted, no whitespace, so two
+// payloads that differ only in key order or formatting share a hash. The hash is 128-bit
+// FNV-1a, which is stable across builds and platforms; it only narrows the search, and
+// callers compare the data itself before treating two concepts as the same.
+
+use serde_json::Value;
+
+use super::concept::ConceptData;
+
+/// The content hash of a concept's data.
+pub type ContentHash = u128;
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Hashes `data` in its canonical form. Structured data that isn't JSON is hashed as is.
+pub fn content_hash(data: &ConceptData) -> ContentHash {
+    let canonical = match data {
+        ConceptData::Empty => "empty:".to_string(),
+        ConceptData::Structured(raw) => match serde_json::from_str::<Value>(raw) {
+            Ok(value) => format!("json:{}", canonical_json(&value)),
+            Err(_) => format!("raw:{}", raw),
+        },
+    };
+    fnv1a(canonical.as_bytes())
+}
+
+/// Hashes `bytes` exactly as they are, for storage that must give them back byte for byte.
+pub fn exact_hash(bytes: &[u8]) -> ContentHash {
+    fnv1a(bytes)
+}
+
+/// Whether two concepts hold the same data, up to key order and formatting.
+pub fn same_content(a: &ConceptData, b: &ConceptData) -> bool {
+    match (a, b) {
+        (ConceptData::Empty, ConceptData::Empty) => true,
+        (ConceptData::Structured(a), ConceptData::Structured(b)) => {
+            match (serde_json::from_str::<Value>(a), serde_json::from_str::<Value>(b)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => a == b,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// `value` as compact JSON with every object's keys in sorted order.
+pub fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(item, out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u128).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn structured(raw: &str) -> ConceptData {
+        ConceptData::Structured(raw.to_string().into())
+    }
+
+    #[test]
+    fn test_key_order_and_whitespace_do_not_change_the_hash() {
+        let a = structured(r#"{"name":"Ada","tags":[{"b":2,"a":1}],"meta":{"y":true,"x":null}}"#);
+        let b = structured(r#"{ "meta": {"x": null, "y": true}, "tags": [{"a": 1, "b": 2}], "name": "Ada" }"#);
+        assert_eq!(content_hash(&a), content_hash(&b));
+        assert!(same_content(&a, &b));
+        assert_eq!(
+            canonical_json(&json!({"b": [2, {"d": 1, "c": 0}], "a": "x"})),
+            r#"{"a":"x","b":[2,{"c":0,"d":1}]}"#
+        );
+    }
+
+    #[test]
+    fn test_different_data_hashes_differently() {
+        let hashes = [
+            content_hash(&structured(r#"{"name":"Ada"}"#)),
+            content_hash(&structured(r#"{"name":"Grace"}"#)),
+            content_hash(&structured(r#"["name","Ada"]"#)),
+            content_hash(&structured(r#"{"name":["Ada"]}"#)),
+            content_hash(&ConceptData::Empty),
+        ];
+        for (i, a) in hashes.iter().enumerate() {
+            for b in &hashes[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        // The hash is fixed by the algorithm, not by the build.
+        assert_eq!(fnv1a(b""), FNV_OFFSET_BASIS);
+        assert_eq!(fnv1a(b"a"), 0xd228cb696f1a8caf78912b704e4a8964);
+    }
+}
@@ -1,5 +1,9 @@
 // This makes the contents of concepts.rs and relationship.rs public to the rest of the project.
 
 pub mod concept;
+pub mod content;
 pub mod relationship;
 pub mod query;
+pub mod schema;
+pub mod system;
+pub mod transaction;
@@ -1 +1,306 @@
 // Query types
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+use super::concept::{ConceptData, ConceptId, ConceptVersion};
+pub use crate::query::filter::Predicate;
+
+/// The data fields tried, in order, when picking a concept's display label.
+pub const DEFAULT_LABEL_FIELDS: [&str; 4] = ["name", "title", "handle", "id"];
+
+/// Page size used when a listing doesn't ask for one.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+/// The largest page a single listing request may ask for.
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// Which of a concept's relationships to follow or count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Relationships the concept is the source of.
+    Outgoing,
+    /// Relationships the concept is the target of.
+    Incoming,
+    /// Both; a relationship from a concept to itself counts twice.
+    #[default]
+    Both,
+}
+
+/// Which concepts to list, and where to resume from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConceptListOptions {
+    /// At most this many concepts per page (capped at `MAX_PAGE_SIZE`).
+    pub limit: Option<usize>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<ConceptId>,
+    /// Only list concepts carrying this label.
+    pub label: Option<String>,
+    /// Only list concepts carrying this tag.
+    pub tag: Option<String>,
+    /// Only list concepts with a string in their data containing this text, ignoring case.
+    pub search: Option<String>,
+    /// Only list concepts passing this predicate; `?predicate=<json>` in a query string.
+    #[serde(default, with = "predicate_param", skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<Predicate>,
+    /// List system concepts too (see `types::system`), which are left out otherwise.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_system: bool,
+}
+
+impl ConceptListOptions {
+    /// The page size to actually use.
+    pub fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+/// One concept as it appears in a listing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConceptListing {
+    pub id: ConceptId,
+    pub data: serde_json::Value,
+    pub labels: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    pub version: u64,
+    /// When the concept's first version was committed.
+    pub created_at: DateTime<Utc>,
+    /// When its current version was committed.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ConceptListing {
+    /// Builds a listing entry from a concept's current version and its creation time.
+    pub fn new(version: &ConceptVersion, created_at: DateTime<Utc>) -> Self {
+        let data = parse_data(&version.data);
+        Self {
+            id: version.concept_id,
+            labels: labels_of(&data),
+            tags: version.tags.clone(),
+            data,
+            version: version.version,
+            created_at,
+            updated_at: version.created_at,
+        }
+    }
+}
+
+/// A page of concepts, in ascending ID order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConceptPage {
+    pub concepts: Vec<ConceptListing>,
+    /// Pass this as `cursor` to get the next page. `None` on the last page.
+    pub next_cursor: Option<ConceptId>,
+    /// True when the scan budget ran out before the page filled. The page may be short or
+    /// empty even though more concepts match; `next_cursor` carries on from where it stopped.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Turns stored concept data back into JSON. Structural concepts have no data at all.
+pub fn parse_data(data: &ConceptData) -> serde_json::Value {
+    match data {
+        ConceptData::Structured(s) => serde_json::from_str(s).unwrap_or_default(),
+        ConceptData::Empty => serde_json::Value::Null,
+    }
+}
+
+/// A concept's labels: the strings in the `labels` array of its data.
+pub fn labels_of(data: &serde_json::Value) -> Vec<String> {
+    data.get("labels")
+        .and_then(|labels| labels.as_array())
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether a string anywhere in `data`, lowercased, contains `text`, which should already be.
+pub fn mentions(data: &serde_json::Value, text: &str) -> bool {
+    match data {
+        serde_json::Value::String(value) => value.to_lowercase().contains(text),
+        serde_json::Value::Array(values) => values.iter().any(|value| mentions(value, text)),
+        serde_json::Value::Object(fields) => fields.values().any(|value| mentions(value, text)),
+        _ => false,
+    }
+}
+
+/// A concept's display label: the first of `fields` its data holds as a non-empty string
+/// or a number, falling back to its first label and then to the first eight characters of
+/// its ID, which is all an empty concept has.
+pub fn label_for(concept_id: &ConceptId, data: &ConceptData, fields: &[String]) -> String {
+    let data = parse_data(data);
+    fields
+        .iter()
+        .find_map(|field| match data.get(field)? {
+            serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+        .or_else(|| labels_of(&data).into_iter().find(|label| !label.is_empty()))
+        .unwrap_or_else(|| concept_id.simple().to_string()[..8].to_string())
+}
+
+/// The group concepts fall into when the grouped field is missing or null.
+pub const MISSING_GROUP: &str = "(missing)";
+
+/// Equality predicates on concept data, all of which must hold. Keys are field paths as in
+/// `lookup`; a value matches a field when it is the group `group_key` would put that field in.
+/// A structured `predicate` must hold as well.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    #[serde(default)]
+    pub equals: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<Predicate>,
+}
+
+impl Filter {
+    /// A filter passing exactly the concepts `predicate` does.
+    pub fn predicate(predicate: Predicate) -> Self {
+        Self { predicate: Some(predicate), ..Self::default() }
+    }
+
+    /// Whether `version`, whose data parses to `data`, of a concept first written at
+    /// `created_at`, satisfies every predicate.
+    pub fn matches(&self, version: &ConceptVersion, data: &Value, created_at: DateTime<Utc>) -> bool {
+        self.equals
+            .iter()
+            .all(|(path, expected)| group_key(lookup(data, path)) == *expected)
+            && self
+                .predicate
+                .as_ref()
+                .is_none_or(|predicate| predicate.evaluate_parsed(version, data, created_at))
+    }
+}
+
+/// Carries an optional `Predicate` in a query string as JSON text,
+/// e.g. `?predicate={"gt":{"path":"priority","value":2}}`.
+pub mod predicate_param {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use super::Predicate;
+
+    pub fn serialize<S: Serializer>(predicate: &Option<Predicate>, serializer: S) -> Result<S::Ok, S::Error> {
+        match predicate {
+            Some(predicate) => {
+                serializer.serialize_str(&serde_json::to_string(predicate).map_err(serde::ser::Error::custom)?)
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Predicate>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|text| serde_json::from_str(&text).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Follows a dot-separated path into JSON data; numeric segments also index into arrays.
+pub fn lookup<'a>(data: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(data, |value, segment| match value {
+        Value::Object(fields) => fields.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// The group a field's value falls into: strings as they are, other values as JSON text
+/// (`3`, `true`, `["a"]`), and missing or null fields under `MISSING_GROUP`.
+pub fn group_key(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => MISSING_GROUP.to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn id() -> Uuid {
+        Uuid::parse_str("0123abcd-0000-0000-0000-000000000000").unwrap()
+    }
+
+    fn label(data: serde_json::Value) -> String {
+        let fields: Vec<String> = DEFAULT_LABEL_FIELDS.iter().map(|f| f.to_string()).collect();
+        label_for(&id(), &ConceptData::Structured(data.to_string().into()), &fields)
+    }
+
+    #[test]
+    fn test_label_falls_back_through_each_field() {
+        assert_eq!(label(serde_json::json!({"name": "Ada", "title": "Countess"})), "Ada");
+        assert_eq!(label(serde_json::json!({"name": "", "title": "Countess"})), "Countess");
+        assert_eq!(label(serde_json::json!({"handle": "ada"})), "ada");
+        assert_eq!(label(serde_json::json!({"id": 42})), "42");
+        assert_eq!(label(serde_json::json!({"other": "x"})), "0123abcd");
+        assert_eq!(label(serde_json::json!({"other": "x", "labels": ["Team"]})), "Team");
+        assert_eq!(label(serde_json::Value::Null), "0123abcd");
+        assert_eq!(label_for(&id(), &ConceptData::Empty, &[]), "0123abcd");
+    }
+
+    #[test]
+    fn test_group_keys_for_mixed_data_shapes() {
+        let data = serde_json::json!({
+            "category": "books",
+            "rating": 4,
+            "score": 4.5,
+            "draft": false,
+            "gone": null,
+            "tags": ["a", "b"],
+            "meta": {"region": "eu"},
+        });
+        let key = |path: &str| group_key(lookup(&data, path));
+        assert_eq!(key("category"), "books");
+        assert_eq!(key("rating"), "4");
+        assert_eq!(key("score"), "4.5");
+        assert_eq!(key("draft"), "false");
+        assert_eq!(key("gone"), MISSING_GROUP);
+        assert_eq!(key("absent"), MISSING_GROUP);
+        assert_eq!(key("tags"), r#"["a","b"]"#);
+        assert_eq!(key("tags.1"), "b");
+        assert_eq!(key("meta.region"), "eu");
+        assert_eq!(key("category.inner"), MISSING_GROUP);
+        assert_eq!(group_key(lookup(&Value::Null, "category")), MISSING_GROUP);
+    }
+
+    #[test]
+    fn test_filter_matches_on_group_keys() {
+        let data = serde_json::json!({"status": "open", "priority": 2});
+        let version = ConceptVersion {
+            concept_id: id(),
+            version: 1,
+            data: ConceptData::Structured(data.to_string().into()),
+            created_at: Utc::now(),
+            created_by: Uuid::nil(),
+            deleted_at: None,
+            deleted_by: None,
+            expires_at: None,
+            tags: Default::default(),
+        };
+        let filter = |pairs: &[(&str, &str)]| Filter {
+            equals: pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Filter::default()
+        };
+        let matches = |filter: Filter| filter.matches(&version, &data, version.created_at);
+        assert!(matches(filter(&[])));
+        assert!(matches(filter(&[("status", "open"), ("priority", "2")])));
+        assert!(!matches(filter(&[("status", "closed")])));
+        assert!(matches(filter(&[("owner", MISSING_GROUP)])));
+
+        // The equality predicates compare group keys; a predicate compares JSON values.
+        let predicate: Predicate = serde_json::from_value(serde_json::json!({"gt": {"path": "priority", "value": 1}})).unwrap();
+        assert!(matches(Filter { predicate: Some(predicate.clone()), ..filter(&[("status", "open")]) }));
+        assert!(!matches(Filter { predicate: Some(predicate), ..filter(&[("status", "closed")]) }));
+        assert!(!matches(filter(&[("priority", "1")])));
+    }
+}
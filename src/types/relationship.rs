@@ -1,5 +1,11 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
 use uuid::Uuid;
 
 use super::concept::{ConceptId, TransactionId}; // This means "import ConceptId & TransactionID from the concept.rs file in this same folder"
@@ -7,8 +13,194 @@ use super::concept::{ConceptId, TransactionId}; // This means "import ConceptId
 /// An ID for a relationship, which is an edge in our graph.
 pub type RelationshipId = Uuid;
 
-// For now, a relationship type is just a simple string, like "works_for".
-pub type RelationType = String;
+/// A relationship type, like "works_for". Names are interned, so every relationship of a
+/// type shares one copy of its name and cloning one only bumps a reference count. It
+/// serializes as a plain string, exactly as the `String` it used to be, so stored records
+/// and API payloads read back unchanged.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RelationType(Arc<str>);
+
+/// Interned names beyond this many are swept of ones nothing uses any more.
+const MIN_INTERNER_SWEEP: usize = 1024;
+
+#[derive(Default)]
+struct Interner {
+    names: HashSet<Arc<str>>,
+    sweep_at: usize,
+}
+
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(|| {
+    RwLock::new(Interner { names: HashSet::new(), sweep_at: MIN_INTERNER_SWEEP })
+});
+
+impl RelationType {
+    /// The interned copy of `name`.
+    pub fn new(name: &str) -> Self {
+        if let Some(shared) = INTERNER.read().unwrap_or_else(PoisonError::into_inner).names.get(name) {
+            return Self(Arc::clone(shared));
+        }
+        let mut interner = INTERNER.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(shared) = interner.names.get(name) {
+            return Self(Arc::clone(shared));
+        }
+        if interner.names.len() >= interner.sweep_at {
+            // A name only the interner holds has no relationships left to share it with.
+            interner.names.retain(|shared| Arc::strong_count(shared) > 1);
+            interner.sweep_at = (interner.names.len() * 2).max(MIN_INTERNER_SWEEP);
+        }
+        let shared: Arc<str> = Arc::from(name);
+        interner.names.insert(Arc::clone(&shared));
+        Self(shared)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether both are the same interned copy, not just equal names.
+    pub fn shares_name_with(&self, other: &RelationType) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Deref for RelationType {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for RelationType {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for RelationType {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for RelationType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for RelationType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for RelationType {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for RelationType {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl From<&String> for RelationType {
+    fn from(name: &String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<RelationType> for String {
+    fn from(relationship_type: RelationType) -> Self {
+        relationship_type.0.to_string()
+    }
+}
+
+impl PartialEq<str> for RelationType {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for RelationType {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for RelationType {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<RelationType> for &str {
+    fn eq(&self, other: &RelationType) -> bool {
+        *self == &*other.0
+    }
+}
+
+impl PartialEq<RelationType> for String {
+    fn eq(&self, other: &RelationType) -> bool {
+        **self == *other.0
+    }
+}
+
+impl Serialize for RelationType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for RelationType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct Name;
+        impl Visitor<'_> for Name {
+            type Value = RelationType;
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a relationship type name")
+            }
+            fn visit_str<E: de::Error>(self, name: &str) -> std::result::Result<RelationType, E> {
+                Ok(RelationType::new(name))
+            }
+        }
+        deserializer.deserialize_str(Name)
+    }
+}
+
+/// How many active relationships of one type a concept may take part in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cardinality {
+    /// A concept is the source of at most one, e.g. `reports_to`.
+    OneOutgoing,
+    /// A concept is the target of at most one, e.g. `married_to` seen from one side.
+    OneIncoming,
+    /// At most one joins any given source to any given target.
+    Unique,
+    /// No limit.
+    #[default]
+    Many,
+}
+
+/// A relationship type registered with `GraphEngine::register_relationship_type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipTypeDefinition {
+    pub name: RelationType,
+    pub description: String,
+    pub registered_at: DateTime<Utc>,
+}
+
+/// A registered relationship type and how many active relationships use it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipTypeUsage {
+    pub name: RelationType,
+    pub description: String,
+    pub registered_at: DateTime<Utc>,
+    pub active_relationships: u64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RelationshipMetadata {
@@ -35,19 +227,34 @@ pub struct Relationship {
     pub relationship_type: RelationType,
     pub target: ConceptId, // The ID of the concept where the edge ends.
     pub metadata: RelationshipMetadata,
+    /// `false` for a symmetric relationship, like "sibling_of", which reads the same from
+    /// either end. Source and target then only record the order it was created in.
+    #[serde(default = "directed_by_default")]
+    pub directed: bool,
 }
 
 impl Relationship {
     /// A constructor to easily create a new relationship.
-    pub fn new(source: ConceptId, relationship_type: RelationType, target: ConceptId) -> Self {
+    pub fn new(source: ConceptId, relationship_type: impl Into<RelationType>, target: ConceptId) -> Self {
         Self {
             id: Uuid::new_v4(),
             source,
-            relationship_type,
+            relationship_type: relationship_type.into(),
             target,
             metadata: RelationshipMetadata::default(),
+            directed: true,
         }
     }
+
+    /// Like `new`, but the relationship reads the same from either end.
+    pub fn undirected(a: ConceptId, relationship_type: impl Into<RelationType>, b: ConceptId) -> Self {
+        Self { directed: false, ..Self::new(a, relationship_type, b) }
+    }
+}
+
+/// Relationships point from source to target unless created undirected.
+pub(crate) fn directed_by_default() -> bool {
+    true
 }
 
 /// A versioned snapshot of a relationship's state for MVCC.
@@ -62,6 +269,8 @@ pub struct RelationshipVersion {
     pub created_by: TransactionId,
     pub deleted_at: Option<DateTime<Utc>>,
     pub deleted_by: Option<TransactionId>,
+    #[serde(default = "directed_by_default")]
+    pub directed: bool,
 }
 
 impl RelationshipVersion {
@@ -77,11 +286,42 @@ impl RelationshipVersion {
             created_by: transaction_id,
             deleted_at: None,
             deleted_by: None,
+            directed: relationship.directed,
         }
     }
 
     /// Checks if this version was "live" at a given timestamp.
     pub fn is_active_at(&self, timestamp: DateTime<Utc>) -> bool {
-        self.created_at <= timestamp && self.deleted_at.map_or(true, |deleted| deleted > timestamp)
+        self.created_at <= timestamp && self.deleted_at.is_none_or(|deleted| deleted > timestamp)
+    }
+
+    /// The end across from `concept_id`, which must be one of the two.
+    pub fn other_end(&self, concept_id: &ConceptId) -> ConceptId {
+        if self.source == *concept_id { self.target } else { self.source }
+    }
+
+    /// Whether the relationship joins `a` and `b`, in that order unless it is undirected.
+    pub fn joins(&self, a: &ConceptId, b: &ConceptId) -> bool {
+        (self.source == *a && self.target == *b) || (!self.directed && self.source == *b && self.target == *a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relation_types_are_interned_and_encode_as_plain_strings() {
+        let a = RelationType::new("works_for");
+        let b: RelationType = "works_for".to_string().into();
+        assert!(a.shares_name_with(&b));
+        assert_eq!(a, "works_for");
+
+        // Records written when the type was a `String` decode to the same shared name.
+        let bytes = bincode::serialize(&"works_for".to_string()).unwrap();
+        assert_eq!(bincode::serialize(&a).unwrap(), bytes);
+        let decoded: RelationType = bincode::deserialize(&bytes).unwrap();
+        assert!(decoded.shares_name_with(&a));
+        assert_eq!(serde_json::to_string(&a).unwrap(), "\"works_for\"");
     }
 }
@@ -0,0 +1,340 @@
+// Concept schemas: a practical subset of JSON Schema, checked against the data of every
+// concept that carries the schema's label.
+//
+// Supported keywords: type, enum, const, properties, required, additionalProperties, items,
+// minItems, maxItems, minLength, maxLength, minimum, maximum, exclusiveMinimum,
+// exclusiveMaximum and anyOf. Annotations ($schema, $id, $comment, title, description,
+// default, examples) are accepted and ignored. Anything else is refused when the schema is
+// registered, so a schema never silently checks less than it says.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::ConceptId;
+
+const TYPES: [&str; 7] = ["null", "boolean", "object", "array", "number", "integer", "string"];
+const ANNOTATIONS: [&str; 7] = ["$schema", "$id", "$comment", "title", "description", "default", "examples"];
+
+/// One way a concept's data breaks a schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    pub concept_id: ConceptId,
+    /// The label whose schema was broken.
+    pub label: String,
+    /// A JSON Pointer to the offending value; empty for the data as a whole.
+    pub path: String,
+    pub message: String,
+}
+
+/// A schema that has been checked to use only supported keywords.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema(Value);
+
+impl Schema {
+    /// Checks `schema` and wraps it, or explains what's wrong with it.
+    pub fn compile(schema: Value) -> Result<Self> {
+        check_schema(&schema, "")?;
+        Ok(Self(schema))
+    }
+
+    /// The schema as it was registered.
+    pub fn as_value(&self) -> &Value {
+        &self.0
+    }
+
+    /// Every violation in a concept's data, each attributed to `label`. Empty when the data
+    /// is valid.
+    pub fn validate(&self, concept_id: ConceptId, label: &str, data: &Value) -> Vec<SchemaViolation> {
+        let mut errors = Vec::new();
+        validate(&self.0, data, "", &mut errors);
+        errors
+            .into_iter()
+            .map(|(path, message)| SchemaViolation {
+                concept_id,
+                label: label.to_string(),
+                path,
+                message,
+            })
+            .collect()
+    }
+}
+
+fn invalid(path: &str, message: impl std::fmt::Display) -> MnemonicError {
+    let at = if path.is_empty() { "/" } else { path };
+    MnemonicError::InvalidSchema(format!("{} at {}", message, at))
+}
+
+fn check_schema(schema: &Value, path: &str) -> Result<()> {
+    let Value::Object(keywords) = schema else {
+        // `true` and `false` are schemas too: accept everything, or nothing.
+        return match schema {
+            Value::Bool(_) => Ok(()),
+            _ => Err(invalid(path, "A schema must be an object or a boolean")),
+        };
+    };
+    for (keyword, value) in keywords {
+        let here = format!("{}/{}", path, escape(keyword));
+        match keyword.as_str() {
+            "type" => {
+                let names: Vec<&Value> = match value {
+                    Value::Array(names) => names.iter().collect(),
+                    name => vec![name],
+                };
+                if names.is_empty() || !names.iter().all(|name| name.as_str().is_some_and(|n| TYPES.contains(&n))) {
+                    return Err(invalid(&here, format!("type must name one or more of {:?}", TYPES)));
+                }
+            }
+            "enum" => {
+                if !value.is_array() {
+                    return Err(invalid(&here, "enum must be an array"));
+                }
+            }
+            "const" => {}
+            "properties" => {
+                let Value::Object(properties) = value else {
+                    return Err(invalid(&here, "properties must be an object"));
+                };
+                for (name, property) in properties {
+                    check_schema(property, &format!("{}/{}", here, escape(name)))?;
+                }
+            }
+            "required" => {
+                if !value.as_array().is_some_and(|names| names.iter().all(Value::is_string)) {
+                    return Err(invalid(&here, "required must be an array of strings"));
+                }
+            }
+            "additionalProperties" | "items" => check_schema(value, &here)?,
+            "anyOf" => {
+                let Some(options) = value.as_array().filter(|options| !options.is_empty()) else {
+                    return Err(invalid(&here, "anyOf must be a non-empty array"));
+                };
+                for (index, option) in options.iter().enumerate() {
+                    check_schema(option, &format!("{}/{}", here, index))?;
+                }
+            }
+            "minItems" | "maxItems" | "minLength" | "maxLength" => {
+                if value.as_u64().is_none() {
+                    return Err(invalid(&here, format!("{} must be a non-negative integer", keyword)));
+                }
+            }
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" => {
+                if !value.is_number() {
+                    return Err(invalid(&here, format!("{} must be a number", keyword)));
+                }
+            }
+            keyword if ANNOTATIONS.contains(&keyword) => {}
+            keyword => return Err(invalid(&here, format!("Unsupported keyword {:?}", keyword))),
+        }
+    }
+    Ok(())
+}
+
+fn validate(schema: &Value, data: &Value, path: &str, errors: &mut Vec<(String, String)>) {
+    let keywords = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return errors.push((path.to_string(), "No value is allowed here".to_string())),
+        Value::Object(keywords) => keywords,
+        _ => return,
+    };
+    let mut fail = |message: String| errors.push((path.to_string(), message));
+
+    if let Some(expected) = keywords.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            name => name.as_str().into_iter().collect(),
+        };
+        if !names.iter().any(|name| has_type(data, name)) {
+            fail(format!("Expected {}, found {}", names.join(" or "), type_of(data)));
+            // The other keywords would only repeat the same complaint.
+            return;
+        }
+    }
+    if let Some(options) = keywords.get("enum").and_then(Value::as_array)
+        && !options.contains(data)
+    {
+        fail(format!("Expected one of {}", Value::Array(options.clone())));
+    }
+    if let Some(expected) = keywords.get("const")
+        && expected != data
+    {
+        fail(format!("Expected {}", expected));
+    }
+    if let Some(text) = data.as_str() {
+        let length = text.chars().count() as u64;
+        if let Some(min) = keywords.get("minLength").and_then(Value::as_u64).filter(|min| length < *min) {
+            fail(format!("Expected at least {} characters, found {}", min, length));
+        }
+        if let Some(max) = keywords.get("maxLength").and_then(Value::as_u64).filter(|max| length > *max) {
+            fail(format!("Expected at most {} characters, found {}", max, length));
+        }
+    }
+    if let Some(number) = data.as_f64() {
+        let bound = |keyword| keywords.get(keyword).and_then(Value::as_f64);
+        if let Some(min) = bound("minimum").filter(|min| number < *min) {
+            fail(format!("Expected at least {}, found {}", min, data));
+        }
+        if let Some(max) = bound("maximum").filter(|max| number > *max) {
+            fail(format!("Expected at most {}, found {}", max, data));
+        }
+        if let Some(min) = bound("exclusiveMinimum").filter(|min| number <= *min) {
+            fail(format!("Expected more than {}, found {}", min, data));
+        }
+        if let Some(max) = bound("exclusiveMaximum").filter(|max| number >= *max) {
+            fail(format!("Expected less than {}, found {}", max, data));
+        }
+    }
+    if let Some(items) = data.as_array() {
+        let count = items.len() as u64;
+        if let Some(min) = keywords.get("minItems").and_then(Value::as_u64).filter(|min| count < *min) {
+            fail(format!("Expected at least {} items, found {}", min, count));
+        }
+        if let Some(max) = keywords.get("maxItems").and_then(Value::as_u64).filter(|max| count > *max) {
+            fail(format!("Expected at most {} items, found {}", max, count));
+        }
+    }
+    if let Some(options) = keywords.get("anyOf").and_then(Value::as_array) {
+        let matches_one = options.iter().any(|option| {
+            let mut scratch = Vec::new();
+            validate(option, data, path, &mut scratch);
+            scratch.is_empty()
+        });
+        if !matches_one {
+            fail("Matches none of the anyOf schemas".to_string());
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (data, keywords.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate(item_schema, item, &format!("{}/{}", path, index), errors);
+        }
+    }
+    if let Value::Object(fields) = data {
+        validate_object(keywords, fields, path, errors);
+    }
+}
+
+fn validate_object(
+    keywords: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    path: &str,
+    errors: &mut Vec<(String, String)>,
+) {
+    let properties = keywords.get("properties").and_then(Value::as_object);
+    for name in keywords.get("required").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(name) = name.as_str().filter(|name| !fields.contains_key(*name)) {
+            errors.push((format!("{}/{}", path, escape(name)), "Required property is missing".to_string()));
+        }
+    }
+    for (name, value) in fields {
+        let here = format!("{}/{}", path, escape(name));
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(property) => validate(property, value, &here, errors),
+            None => {
+                if let Some(additional) = keywords.get("additionalProperties") {
+                    validate(additional, value, &here, errors);
+                }
+            }
+        }
+    }
+}
+
+fn has_type(data: &Value, name: &str) -> bool {
+    match name {
+        "null" => data.is_null(),
+        "boolean" => data.is_boolean(),
+        "object" => data.is_object(),
+        "array" => data.is_array(),
+        "number" => data.is_number(),
+        "integer" => data.is_i64() || data.is_u64() || data.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "string" => data.is_string(),
+        _ => false,
+    }
+}
+
+fn type_of(data: &Value) -> &'static str {
+    match data {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+    }
+}
+
+/// Escapes a property name for use in a JSON Pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn person() -> Schema {
+        Schema::compile(json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "email": {"type": "string"},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"enum": ["staff", "guest"]}}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn paths(schema: &Schema, data: Value) -> Vec<String> {
+        schema.validate(Uuid::nil(), "person", &data).into_iter().map(|v| v.path).collect()
+    }
+
+    #[test]
+    fn test_valid_data_passes() {
+        let schema = person();
+        assert!(paths(&schema, json!({"name": "Ada"})).is_empty());
+        assert!(paths(&schema, json!({"name": "Ada", "email": "ada@example.com", "age": 36, "x": 1})).is_empty());
+    }
+
+    #[test]
+    fn test_violations_name_their_paths() {
+        let schema = person();
+        assert_eq!(paths(&schema, json!({"email": 7})), vec!["/name", "/email"]);
+        assert_eq!(paths(&schema, json!({"name": "", "age": -1})), vec!["/age", "/name"]);
+        assert_eq!(paths(&schema, json!({"name": "Ada", "tags": ["staff", "boss"]})), vec!["/tags/1"]);
+        assert_eq!(paths(&schema, json!("Ada")), vec![""]);
+
+        let violation = &schema.validate(Uuid::nil(), "person", &json!({"name": 1}))[0];
+        assert_eq!(violation.label, "person");
+        assert_eq!(violation.message, "Expected string, found number");
+    }
+
+    #[test]
+    fn test_additional_properties_and_any_of() {
+        let schema = Schema::compile(json!({
+            "properties": {"id": {"anyOf": [{"type": "string"}, {"type": "integer"}]}},
+            "additionalProperties": false
+        }))
+        .unwrap();
+        assert!(paths(&schema, json!({"id": "a"})).is_empty());
+        assert!(paths(&schema, json!({"id": 3})).is_empty());
+        assert_eq!(paths(&schema, json!({"id": 1.5, "a/b": 1})), vec!["/a~1b", "/id"]);
+    }
+
+    #[test]
+    fn test_unsupported_or_malformed_schemas_are_refused() {
+        for schema in [
+            json!({"pattern": "^a"}),
+            json!({"type": "text"}),
+            json!({"properties": {"name": {"minLength": -1}}}),
+            json!({"required": "name"}),
+            json!(3),
+        ] {
+            assert!(matches!(Schema::compile(schema), Err(MnemonicError::InvalidSchema(_))));
+        }
+        assert!(Schema::compile(json!({"title": "Person", "description": "A human"})).is_ok());
+    }
+}
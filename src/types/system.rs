@@ -0,0 +1,80 @@
+// Engine metadata kept as concepts. A feature that needs durable state of its own (schemas,
+// the relationship-type registry, cardinality constraints, seed markers) can keep it in a
+// system concept instead of a keyspace of its own: an ordinary concept, written through the
+// same transactions and versioned like any other, whose ID comes from a stable name and which
+// carries SYSTEM_TAG so that listings, /graph and /query leave it out unless asked not to.
+// Anyone who can read the graph can still read it back, so secrets (webhook signing keys)
+// don't belong in one.
+
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+use crate::error::{MnemonicError, Result};
+use crate::types::concept::ConceptId;
+
+/// The namespace system concept IDs are derived in.
+pub const SYSTEM_NAMESPACE: Uuid = Uuid::from_u128(0x6d6e_656d_6f6e_6963_7379_7374_656d_0000);
+
+/// The tag every system concept carries, and no other concept may.
+pub const SYSTEM_TAG: &str = "mnemonic:system";
+
+/// The ID of the system concept `name`, e.g. `schema:person`: the UUIDv5 of
+/// `mnemonic:schema:person`, so it is the same in every database.
+pub fn system_id(name: &str) -> ConceptId {
+    Uuid::new_v5(&SYSTEM_NAMESPACE, format!("mnemonic:{}", name).as_bytes())
+}
+
+/// Whether a concept carrying `tags` is a system concept.
+pub fn is_system(tags: &BTreeSet<String>) -> bool {
+    tags.contains(SYSTEM_TAG)
+}
+
+/// Refuses a system concept name that is blank or padded.
+pub fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.trim() != name {
+        return Err(MnemonicError::InvalidData(format!(
+            "{:?} is not a system concept name: names can't be blank or padded",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// The error for changing the system concept `id` any way but through `put_system`: deleting,
+/// updating, retagging or merging it.
+pub fn refused(id: ConceptId) -> MnemonicError {
+    MnemonicError::InvalidData(format!("{} is a system concept; it changes only through put_system", id))
+}
+
+/// The data of the system concept `name` holding `value`.
+pub fn system_data(name: &str, value: Value) -> Value {
+    json!({"system": name, "value": value})
+}
+
+/// The value a system concept's data holds.
+pub fn system_value(mut data: Value) -> Option<Value> {
+    data.get_mut("value").map(Value::take)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_ids_are_stable_per_name_and_values_round_trip() {
+        let id = system_id("schema:person");
+        assert_eq!(id, system_id("schema:person"));
+        assert_ne!(id, system_id("schema:place"));
+        assert_eq!(id.get_version_num(), 5);
+
+        assert!(is_system(&BTreeSet::from([SYSTEM_TAG.to_string(), "starred".to_string()])));
+        assert!(!is_system(&BTreeSet::from(["starred".to_string()])));
+        assert!(check_name("seed:demo").is_ok());
+        assert!(matches!(check_name(" seed"), Err(MnemonicError::InvalidData(_))));
+
+        let data = system_data("seed:demo", json!({"at": 3}));
+        assert_eq!(data["system"], "seed:demo");
+        assert_eq!(system_value(data), Some(json!({"at": 3})));
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// Who made a change and why. Recorded once per committed transaction so every
+/// version it wrote can be traced back to an actor.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TransactionMetadata {
+    pub actor: Option<String>,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+impl TransactionMetadata {
+    /// Metadata naming just the actor.
+    pub fn for_actor(actor: impl Into<String>) -> Self {
+        Self {
+            actor: Some(actor.into()),
+            ..Default::default()
+        }
+    }
+
+    /// True when nothing has been recorded, in which case nothing needs persisting.
+    pub fn is_empty(&self) -> bool {
+        self.actor.is_none() && self.reason.is_none() && self.attributes.is_empty()
+    }
+}
+
+/// How a commit checks what its concept writes assume. A pending concept whose
+/// `metadata.version` is 0, as `Concept::new` makes it, means to insert; any other means to
+/// update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    /// Every write is applied as it comes: one to an unknown ID starts a chain at version 1,
+    /// and one to a deleted concept grafts a new version onto its tombstone.
+    #[default]
+    Lenient,
+    /// An insert must use an ID with no history, and an update must find its concept live in
+    /// the transaction's snapshot. Otherwise the commit fails with `RejectedWrites`.
+    Strict,
+}
+
+/// What a concept write did, judged against the transaction's snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteKind {
+    /// No concept with the ID existed.
+    Insert,
+    /// It wrote a new version of a concept that existed.
+    Update,
+}
+
+/// A concept write a `WriteMode::Strict` commit refused.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedWrite {
+    pub concept_id: Uuid,
+    /// What the write meant to do.
+    pub intended: WriteKind,
+    pub reason: String,
+}
+
+impl fmt::Display for RejectedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let intended = match self.intended {
+            WriteKind::Insert => "insert",
+            WriteKind::Update => "update",
+        };
+        write!(f, "{} of concept {}: {}", intended, self.concept_id, self.reason)
+    }
+}
@@ -0,0 +1,56 @@
+// Lock helpers that recover from poisoning instead of failing forever.
+
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::metrics::Counter;
+
+/// Hands out lock guards, recovering any lock a panicking thread left poisoned.
+/// The data behind a recovered lock may be mid-update, so every recovery is logged and
+/// counted; `GraphEngine::health()` reports the count.
+#[derive(Debug, Default)]
+pub struct LockHealth {
+    recoveries: Counter,
+}
+
+impl LockHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a read lock, recovering it if it was poisoned.
+    pub fn read<'a, T>(&self, lock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+        lock.read().unwrap_or_else(|poisoned| {
+            self.record_recovery();
+            lock.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// Takes a write lock, recovering it if it was poisoned.
+    pub fn write<'a, T>(&self, lock: &'a RwLock<T>) -> RwLockWriteGuard<'a, T> {
+        lock.write().unwrap_or_else(|poisoned| {
+            self.record_recovery();
+            lock.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// Locks a mutex, recovering it if it was poisoned.
+    pub fn lock<'a, T>(&self, mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+        mutex.lock().unwrap_or_else(|poisoned: PoisonError<_>| {
+            self.record_recovery();
+            mutex.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// How many poisoned locks have been recovered so far.
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries.get()
+    }
+
+    fn record_recovery(&self) {
+        tracing::warn!("Recovered a lock poisoned by a panicking thread; in-memory state may need repair");
+        self.recoveries.increment();
+    }
+}
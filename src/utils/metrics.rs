@@ -1 +1,196 @@
 // Performance metrics
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How many keys a `HotSpots` tracks by default.
+pub const HOT_SPOT_CAPACITY: usize = 256;
+
+/// How long it takes a default `HotSpots` score to halve once its key stops turning up.
+pub const HOT_SPOT_HALF_LIFE: Duration = Duration::from_secs(300);
+
+/// A monotonically increasing counter that can be shared across threads.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one to the counter.
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Adds `n` to the counter.
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Reads the current value.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters describing what happened to transactions over the lifetime of a TransactionManager.
+#[derive(Debug, Default)]
+pub struct TransactionMetrics {
+    pub committed: Counter,
+    pub aborted: Counter,
+    /// Transactions that outlived the configured maximum age and were aborted.
+    pub expired: Counter,
+    /// The concepts commits most often conflicted on lately.
+    pub conflict_hot_spots: HotSpots<Uuid>,
+}
+
+/// A decaying tally of the keys that keep turning up, held to a fixed number of keys.
+///
+/// Each sighting adds one to a key's score, and scores halve every `half_life`, so the tally
+/// follows what is hot now rather than what ever was. Once full, a new key takes the place of
+/// the coldest one and starts from its score (the Space-Saving scheme): a key that keeps
+/// coming back climbs past the ones that don't, however many keys pass through.
+#[derive(Debug)]
+pub struct HotSpots<K> {
+    capacity: usize,
+    half_life: Duration,
+    // Each key's score as of the moment it was last brought up to date.
+    scores: Mutex<HashMap<K, (f64, Instant)>>,
+}
+
+impl<K> Default for HotSpots<K> {
+    fn default() -> Self {
+        Self::new(HOT_SPOT_CAPACITY, HOT_SPOT_HALF_LIFE)
+    }
+}
+
+impl<K> HotSpots<K> {
+    pub fn new(capacity: usize, half_life: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            half_life,
+            scores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn decayed(&self, (score, at): (f64, Instant), now: Instant) -> f64 {
+        let half_lives = now.saturating_duration_since(at).as_secs_f64() / self.half_life.as_secs_f64();
+        score * 0.5f64.powf(half_lives)
+    }
+}
+
+impl<K: Copy + Eq + Hash> HotSpots<K> {
+    /// Counts one sighting of `key`.
+    pub fn record(&self, key: K) {
+        self.record_at(key, Instant::now());
+    }
+
+    fn record_at(&self, key: K, now: Instant) {
+        let mut scores = self.scores.lock().unwrap_or_else(PoisonError::into_inner);
+        let score = match scores.get(&key) {
+            Some(&entry) => self.decayed(entry, now),
+            None if scores.len() < self.capacity => 0.0,
+            None => {
+                let (coldest, score) = scores
+                    .iter()
+                    .map(|(key, &entry)| (*key, self.decayed(entry, now)))
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .expect("a full tally has keys");
+                scores.remove(&coldest);
+                score
+            }
+        };
+        scores.insert(key, (score + 1.0, now));
+    }
+
+    /// The `n` hottest keys with their current scores, hottest first.
+    pub fn top(&self, n: usize) -> Vec<(K, f64)> {
+        self.top_at(n, Instant::now())
+    }
+
+    fn top_at(&self, n: usize, now: Instant) -> Vec<(K, f64)> {
+        let scores = self.scores.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut top: Vec<(K, f64)> = scores.iter().map(|(key, &entry)| (*key, self.decayed(entry, now))).collect();
+        top.sort_by(|a, b| b.1.total_cmp(&a.1));
+        top.truncate(n);
+        top
+    }
+}
+
+/// A value that goes up and down, remembering the highest it has ever been.
+#[derive(Debug, Default)]
+pub struct Gauge {
+    value: AtomicU64,
+    peak: AtomicU64,
+}
+
+impl Gauge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one, returning the new value.
+    pub fn increment(&self) -> u64 {
+        let value = self.value.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak.fetch_max(value, Ordering::Relaxed);
+        value
+    }
+
+    /// Subtracts one.
+    pub fn decrement(&self) {
+        self.value.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Reads the current value.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// The highest value seen so far.
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// How the engine's worker pool has been coping with its load.
+#[derive(Debug, Default)]
+pub struct WorkerPoolMetrics {
+    /// Operations currently running on a worker.
+    pub running: Gauge,
+    /// Operations waiting for a free worker.
+    pub queued: Gauge,
+    pub completed: Counter,
+    /// Operations turned away because the queue was full.
+    pub rejected: Counter,
+    /// Total time operations spent waiting for a worker, in microseconds.
+    pub wait_micros: Counter,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_spots_decay_and_stay_within_capacity() {
+        let spots = HotSpots::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        for _ in 0..4 {
+            spots.record_at('a', start);
+        }
+        spots.record_at('b', start);
+        assert_eq!(spots.top_at(5, start), vec![('a', 4.0), ('b', 1.0)]);
+
+        // Ten seconds on, every score has halved.
+        let later = start + Duration::from_secs(10);
+        assert_eq!(spots.top_at(1, later), vec![('a', 2.0)]);
+
+        // A newcomer replaces the coldest key and inherits its score.
+        spots.record_at('c', later);
+        assert_eq!(spots.top_at(5, later), vec![('a', 2.0), ('c', 1.5)]);
+    }
+}
@@ -1,4 +1,6 @@
 // Utility modules
 
 pub mod uuid;
+pub mod locks;
 pub mod metrics;
+pub mod hmac;
@@ -0,0 +1,66 @@
+// Counts heap allocations around a read, to show relationship types are shared instead of
+// copied. This file has its own global allocator, so it is a test binary of its own.
+
+use mnemonic_core::graph::{EngineConfig, GraphEngine, TransactionConfig};
+use serde_json::json;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::tempdir;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[tokio::test]
+async fn test_retrieve_by_source_shares_relationship_types() {
+    const EDGES: usize = 5_000;
+    let dir = tempdir().unwrap();
+    let config = EngineConfig {
+        // No sweeper thread allocating while we count.
+        transactions: TransactionConfig { expiry_sweep_interval: None, ..Default::default() },
+        ..Default::default()
+    };
+    let engine = GraphEngine::with_config(dir.path(), config).unwrap();
+    let hub = engine.store(json!({"name": "hub"})).await.unwrap();
+    let mut spokes = Vec::with_capacity(EDGES);
+    for i in 0..EDGES {
+        spokes.push(engine.store(json!({"i": i})).await.unwrap());
+    }
+    let types = ["knows", "works_with", "reports_to"];
+    let items = spokes
+        .iter()
+        .enumerate()
+        .map(|(i, &spoke)| (hub, types[i % types.len()].into(), spoke))
+        .collect();
+    engine.relate_batch(items, Default::default()).await.unwrap();
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let outgoing = engine.retrieve_by_source(hub).await.unwrap();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    assert_eq!(outgoing.len(), EDGES);
+
+    // Copying each type's name would cost at least one allocation per relationship, twice
+    // over; sharing it leaves only the traversal's own buffers and the runtime's overhead.
+    assert!(
+        allocations < EDGES / 10,
+        "{} allocations to read {} relationships",
+        allocations,
+        EDGES
+    );
+    let knows: Vec<_> = outgoing.iter().filter(|r| r.relationship_type == "knows").collect();
+    assert!(knows.windows(2).all(|w| w[0].relationship_type.shares_name_with(&w[1].relationship_type)));
+}
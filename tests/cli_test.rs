@@ -0,0 +1,139 @@
+use mnemonic_core::{
+    cli::{self, CliError},
+    graph::GraphEngine,
+};
+use serde_json::{Value, json};
+use std::path::Path;
+use std::process::Command;
+use tempfile::tempdir;
+
+// The CLI is driven in-process through `cli::run`, so it sees the same storage as the
+// engine that wrote the fixture data.
+async fn run(db: &Path, args: &[&str]) -> Result<String, CliError> {
+    let mut argv = vec!["mnemonic-cli", "--db", db.to_str().unwrap()];
+    argv.extend_from_slice(args);
+    let mut out = Vec::new();
+    cli::run(argv, &mut out).await?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+async fn run_json(db: &Path, args: &[&str]) -> Value {
+    serde_json::from_str(&run(db, args).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_cli_inspects_a_database_read_only() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("db");
+    let (alice, project) = {
+        let engine = GraphEngine::new(&db).unwrap();
+        let alice = engine.store(json!({"name": "Alice"})).await.unwrap();
+        let project = engine.store(json!({"name": "Mnemonic"})).await.unwrap();
+        engine.relate(alice, "leads".to_string(), project).await.unwrap();
+        (alice, project)
+    };
+
+    let stats = run_json(&db, &["stats"]).await;
+    assert_eq!(stats["concepts"], 2);
+    assert_eq!(stats["relationships_by_type"]["leads"], 1);
+
+    let concept = run_json(&db, &["get-concept", &alice.to_string()]).await;
+    assert_eq!(concept["data"]["Structured"], r#"{"name":"Alice"}"#);
+    let history = run_json(&db, &["history", &alice.to_string()]).await;
+    assert_eq!(history.as_array().unwrap().len(), 1);
+
+    let outgoing = run_json(&db, &["neighbors", &alice.to_string(), "--direction", "outgoing"]).await;
+    assert_eq!(outgoing[0]["target"], project.to_string());
+    let incoming = run_json(&db, &["neighbors", &alice.to_string(), "--direction", "incoming"]).await;
+    assert!(incoming.as_array().unwrap().is_empty());
+
+    let verify = run_json(&db, &["verify"]).await;
+    assert!(verify["findings"].as_array().unwrap().is_empty());
+    let deep = run_json(&db, &["verify", "--depth", "deep"]).await;
+    assert_eq!(deep["depth"], "deep");
+    assert_eq!(deep["keyspaces"]["relationship_versions"], 1);
+
+    let dot = run(&db, &["export", "--format", "dot", "-"]).await.unwrap();
+    assert!(dot.starts_with("digraph mnemonic {"));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\"", alice, project)));
+
+    let pretty = run(&db, &["--pretty", "get-concept", &alice.to_string()]).await.unwrap();
+    assert!(pretty.contains("\n  \"id\""));
+
+    let missing = run(&db, &["get-concept", &uuid::Uuid::new_v4().to_string()]).await;
+    assert!(matches!(missing, Err(CliError::Engine(_))));
+}
+
+#[tokio::test]
+async fn test_cli_export_import_round_trip_needs_write() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source");
+    let copy = dir.path().join("copy");
+    let export = dir.path().join("graph.jsonl");
+    {
+        let engine = GraphEngine::new(&source).unwrap();
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        engine.relate(a, "knows".to_string(), b).await.unwrap();
+    }
+    drop(GraphEngine::new(&copy).unwrap());
+
+    let report = run_json(&source, &["export", export.to_str().unwrap()]).await;
+    assert_eq!((report["concepts"].clone(), report["relationships"].clone()), (json!(2), json!(1)));
+
+    let refused = run(&copy, &["import", export.to_str().unwrap()]).await;
+    assert!(matches!(refused, Err(CliError::Invalid(_))));
+    assert_eq!(run_json(&copy, &["stats"]).await["concepts"], 0);
+
+    let imported = run_json(&copy, &["--write", "import", export.to_str().unwrap()]).await;
+    assert_eq!(imported, json!({"concepts": 2, "relationships": 1}));
+    let stats = run_json(&copy, &["stats"]).await;
+    assert_eq!((stats["concepts"].clone(), stats["relationships"].clone()), (json!(2), json!(1)));
+
+    let pruned = run_json(&copy, &["--write", "prune", "--keep-days", "30"]).await;
+    assert_eq!(pruned["concept_versions_pruned"], 0);
+
+    assert!(matches!(run(&copy, &["rebuild"]).await, Err(CliError::Invalid(_))));
+    let rebuilt = run_json(&copy, &["--write", "rebuild"]).await;
+    assert_eq!(rebuilt["adjacency"], json!({"entries": 2, "repaired": 0}));
+
+    // Everything the import wrote is in the latest format already.
+    let migrated = run_json(&copy, &["--write", "migrate"]).await;
+    assert_eq!(migrated["migrated"], 0);
+}
+
+#[test]
+fn test_cli_binary_reports_errors_as_json() {
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("nothing-here");
+    let output = Command::new(env!("CARGO_BIN_EXE_mnemonic-cli"))
+        .args(["--db", missing.to_str().unwrap(), "stats"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert!(error["error"].is_string());
+
+    let help = Command::new(env!("CARGO_BIN_EXE_mnemonic-cli")).arg("--help").output().unwrap();
+    assert!(help.status.success());
+    assert!(String::from_utf8_lossy(&help.stdout).contains("get-concept"));
+}
+
+#[tokio::test]
+async fn test_cli_imports_a_neo4j_csv_export() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("db");
+    drop(GraphEngine::new(&db).unwrap());
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/neo4j");
+    let file = |name: &str| fixtures.join(name).to_str().unwrap().to_string();
+    let (persons, companies, works_at) = (file("persons.csv"), file("companies.csv"), file("works_at.csv"));
+    let args = ["import-neo4j", "--nodes", &persons, "--nodes", &companies, "--relationships", &works_at];
+
+    assert!(matches!(run(&db, &args).await, Err(CliError::Invalid(_))));
+    let mut writing = vec!["--write"];
+    writing.extend(args);
+    let stats = run_json(&db, &writing).await;
+    assert_eq!((stats["concepts"].clone(), stats["relationships"].clone()), (json!(4), json!(2)));
+    assert_eq!(stats["errors"][2], json!({"file": works_at, "line": 4, "message": "unknown start node ID \"p3\" in ID space \"Person\""}));
+    assert_eq!(run_json(&db, &["stats"]).await["relationships_by_type"]["WORKS_AT"], 2);
+}
@@ -0,0 +1,169 @@
+use axum_test::TestServer;
+use mnemonic_core::{
+    api::auth::{ApiKey, ApiKeys},
+    api::routes::{AppState, create_app},
+    client::{ClientError, MnemonicClient},
+    graph::{GraphEngine, IsolationLevel, RetentionPolicy},
+    types::concept::Concept,
+    types::query::{ConceptListOptions, Direction, Filter, Predicate},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::{TempDir, tempdir};
+
+// The client needs a real socket, so the app is served over HTTP on a random port.
+fn serve(state: impl FnOnce(Arc<GraphEngine>) -> AppState) -> (TestServer, String, Arc<GraphEngine>, TempDir) {
+    let dir = tempdir().unwrap();
+    let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+    let app = create_app(state(Arc::clone(&engine)), false);
+    let server = TestServer::builder().http_transport().build(app).unwrap();
+    let url = server.server_address().unwrap().to_string();
+    (server, url, engine, dir)
+}
+
+#[tokio::test]
+async fn test_client_round_trips_the_public_routes() {
+    let (_server, url, _engine, _dir) = serve(AppState::new);
+    let client = MnemonicClient::new(url, None).unwrap();
+    assert_eq!(client.ping().await.unwrap(), "pong");
+    assert_eq!(client.meta().await.unwrap().api_version, "v1");
+
+    let ada = client.create_concept(json!({"name": "Ada", "role": "dev"})).await.unwrap();
+    let team = client
+        .create_concepts(vec![json!({"name": "Lin", "role": "dev"}), json!({"name": "Core", "role": "team"})])
+        .await
+        .unwrap();
+    client.relate(ada, "member_of".to_string(), team[1]).await.unwrap();
+    client.relate_batch(vec![(team[0], "member_of".into(), team[1])]).await.unwrap();
+
+    let concept = client.get_concept(ada).await.unwrap();
+    assert_eq!(concept.id, ada);
+    assert_eq!(client.degree(team[1], Direction::Incoming).await.unwrap(), 2);
+    assert_eq!(client.degree(team[1], Direction::Outgoing).await.unwrap(), 0);
+
+    let page = client
+        .list_concepts(&ConceptListOptions { limit: Some(2), ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(page.concepts.len(), 2);
+
+    let graph = client.graph(true).await.unwrap();
+    assert_eq!((graph.nodes.len(), graph.edges.len()), (3, 2));
+    assert!(graph.nodes.iter().all(|node| node.data.is_some()));
+
+    let stats = client.stats().await.unwrap();
+    assert_eq!((stats.concepts, stats.relationships), (3, 2));
+
+    let filter = Filter { equals: HashMap::from([("role".to_string(), "dev".to_string())]), ..Filter::default() };
+    let buckets = client.aggregate("name", Some(&filter)).await.unwrap();
+    assert_eq!(buckets, HashMap::from([("Ada".to_string(), 1), ("Lin".to_string(), 1)]));
+    // A predicate travels as JSON text in the query string, for listings and aggregates alike.
+    let predicate: Predicate = serde_json::from_value(json!({"contains": {"path": "name", "value": "L"}})).unwrap();
+    let page = client
+        .list_concepts(&ConceptListOptions { predicate: Some(predicate.clone()), ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(page.concepts.iter().map(|concept| concept.id).collect::<Vec<_>>(), vec![team[0]]);
+    let buckets = client.aggregate("role", Some(&Filter::predicate(predicate))).await.unwrap();
+    assert_eq!(buckets, HashMap::from([("dev".to_string(), 1)]));
+
+    let missing = uuid::Uuid::new_v4();
+    assert!(matches!(client.get_concept(missing).await, Err(ClientError::NotFound(_))));
+    assert!(matches!(client.degree(missing, Direction::Both).await, Err(ClientError::NotFound(_))));
+
+    match client.delete_concept(team[1]).await {
+        Err(ClientError::ConceptReferenced { relationships, .. }) => assert_eq!(relationships.len(), 2),
+        other => panic!("expected ConceptReferenced, got {:?}", other),
+    }
+    let report = client.delete_concept_cascading(team[1]).await.unwrap();
+    assert_eq!((report.concept_id, report.relationships_deleted.len()), (team[1], 2));
+    client.delete_concept(team[0]).await.unwrap();
+    assert!(matches!(client.delete_concept(team[0]).await, Err(ClientError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_client_maps_auth_and_admin_errors() {
+    let keys = ApiKeys::from([
+        ("reader".to_string(), ApiKey { actor: "reader".to_string(), admin: false }),
+        ("root".to_string(), ApiKey { actor: "root".to_string(), admin: true }),
+    ]);
+    let (_server, url, engine, _dir) = serve(|engine| AppState::new(engine).with_api_keys(keys));
+
+    let anonymous = MnemonicClient::new(url.clone(), None).unwrap();
+    assert!(matches!(anonymous.create_concept(json!({})).await, Err(ClientError::Unauthorized(_))));
+    let reader = MnemonicClient::new(url.clone(), Some("reader".to_string())).unwrap();
+    reader.create_concept(json!({"name": "a"})).await.unwrap();
+    assert!(matches!(reader.storage_stats(false).await, Err(ClientError::Forbidden(_))));
+
+    let admin = MnemonicClient::new(url, Some("root".to_string())).unwrap();
+    assert!(admin.storage_stats(true).await.is_ok());
+    admin.compact_storage().await.unwrap();
+
+    // A transaction that still reads old versions blocks pruning with a structured 409.
+    let id = engine.store(json!({"name": "b"})).await.unwrap();
+    let txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    assert_eq!(admin.active_transaction(txn.id).await.unwrap().id, txn.id);
+    assert_eq!(admin.active_transactions().await.unwrap().len(), 1);
+    let mut update = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    let mut renamed = Concept::new(json!({"name": "b2"}));
+    renamed.id = id;
+    update.write_set.insert(id);
+    update.pending_writes.insert(id, renamed);
+    engine.commit_transaction(update).await.unwrap();
+    match admin.prune_versions(&RetentionPolicy::default()).await {
+        Err(ClientError::PruneBlocked { transactions, .. }) => assert_eq!(transactions, vec![txn.id]),
+        other => panic!("expected pruning to be blocked, got {:?}", other),
+    }
+
+    engine.abort_transaction(txn.id).await.unwrap();
+    assert!(matches!(admin.active_transaction(txn.id).await, Err(ClientError::NotFound(_))));
+    let report = admin.prune_versions(&RetentionPolicy::default()).await.unwrap();
+    assert_eq!(report.concept_versions_pruned, 1);
+}
+
+#[tokio::test]
+async fn test_a_follower_replicates_over_http_and_resumes_after_a_restart() {
+    let (_server, url, leader, _leader_dir) = serve(AppState::new);
+    let follower_dir = tempdir().unwrap();
+    let a = leader.store(json!({"name": "a"})).await.unwrap();
+    let b = leader.store(json!({"name": "b"})).await.unwrap();
+    let a_to_b = leader.relate(a, "knows", b).await.unwrap();
+
+    let follow = |through: usize| {
+        let follower = Arc::new(GraphEngine::new(follower_dir.path()).unwrap());
+        let client = MnemonicClient::new(url.clone(), None).unwrap();
+        let replication = tokio::spawn({
+            let follower = Arc::clone(&follower);
+            async move { follower.replicate_from(client).await }
+        });
+        let leader = Arc::clone(&leader);
+        async move {
+            let last = leader.changes(None, 100).await.unwrap()[through - 1].committed_at;
+            for _ in 0..100 {
+                if follower.transaction_manager().replicated_through() == Some(last) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert_eq!(follower.transaction_manager().replicated_through(), Some(last));
+            replication.abort();
+            let _ = replication.await;
+            follower
+        }
+    };
+
+    let follower = follow(3).await;
+    assert_eq!(follower.count_relationships().await.unwrap(), 1);
+    drop(follower);
+
+    // The restarted follower carries on from where it stopped, through the deletion.
+    leader.unrelate(a_to_b).await.unwrap();
+    leader.delete_concept(b).await.unwrap();
+    let follower = follow(5).await;
+    assert_eq!(follower.count_relationships().await.unwrap(), 0);
+    assert_eq!(follower.get_concept(b).await.unwrap(), None);
+    assert_eq!(follower.relationship_history(a_to_b).await.unwrap(), leader.relationship_history(a_to_b).await.unwrap());
+    assert_eq!(follower.get_concept(a).await.unwrap(), leader.get_concept(a).await.unwrap());
+}
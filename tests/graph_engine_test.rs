@@ -1,11 +1,19 @@
 use chrono::Utc;
 use mnemonic_core::{
-    graph::{GraphEngine, IsolationLevel},
-    types::concept::Concept,
+    MnemonicError,
+    graph::{ConceptEntity, GraphEngine, IsolationLevel, RelationshipUpdate},
+    storage::keys,
+    types::{
+        concept::{Concept, ConceptData},
+        query::{Filter, parse_data},
+        relationship::{RelationType, Relationship},
+        transaction::TransactionMetadata,
+    },
 };
 use serde_json::json;
 use tempfile::tempdir;
 
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::{task, time::sleep};
 
@@ -123,3 +131,2148 @@ async fn test_transaction_is_durable_across_restarts() {
         println!("SUCCESS: Transaction was durable and hydrated correctly!");
     }
 }
+
+#[tokio::test]
+async fn test_transaction_metadata_survives_restart() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().to_path_buf();
+
+    let metadata = TransactionMetadata {
+        actor: Some("importer".to_string()),
+        reason: Some("nightly sync".to_string()),
+        attributes: [("batch".to_string(), "42".to_string())].into(),
+    };
+
+    // --- FIRST SESSION: commit a change with metadata attached ---
+    let txn_id;
+    {
+        let engine = GraphEngine::new(&db_path).unwrap();
+        let mut txn = engine
+            .begin_transaction_with_meta(IsolationLevel::Snapshot, metadata.clone())
+            .await
+            .unwrap();
+        txn_id = txn.id;
+
+        let concept = Concept::new(json!({"name": "Synced"}));
+        txn.write_set.insert(concept.id);
+        txn.pending_writes.insert(concept.id, concept);
+        engine.commit_transaction(txn).await.unwrap();
+    }
+
+    sleep(Duration::from_millis(100)).await;
+
+    // --- SECOND SESSION: the metadata should be hydrated back ---
+    let engine = GraphEngine::new(&db_path).unwrap();
+    let restored = engine.transaction_metadata(txn_id).await.unwrap();
+    assert_eq!(restored, Some(metadata));
+}
+
+#[tokio::test]
+async fn test_long_history_hydrates_in_version_order() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().to_path_buf();
+
+    // Give one concept more than nine versions, so "cv:{id}:10" sorts before "cv:{id}:2" on disk.
+    let concept_id;
+    {
+        let engine = GraphEngine::new(&db_path).unwrap();
+        concept_id = engine.store(json!({"step": 1})).await.unwrap();
+        for step in 2..=12 {
+            let mut txn = engine
+                .begin_transaction(IsolationLevel::Snapshot)
+                .await
+                .unwrap();
+            let mut concept = Concept::new(json!({ "step": step }));
+            concept.id = concept_id;
+            txn.write_set.insert(concept_id);
+            txn.pending_writes.insert(concept_id, concept);
+            engine.commit_transaction(txn).await.unwrap();
+        }
+    }
+
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(&db_path).unwrap();
+    let latest = engine
+        .transaction_manager()
+        .version_store()
+        .get_latest_concept_version(&concept_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest.version, 12);
+    assert!(engine.verify_consistency().await.unwrap().is_consistent());
+}
+
+#[tokio::test]
+async fn test_version_counters_survive_hydration() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().to_path_buf();
+
+    let concept_id;
+    {
+        let engine = GraphEngine::new(&db_path).unwrap();
+        concept_id = engine.store(json!({"name": "Counted"})).await.unwrap();
+        engine.delete_concept(concept_id).await.unwrap();
+
+        // Remove the history itself, leaving only the persisted counter behind.
+        let backend = engine.backend();
+        let versions = backend.db.cf_handle("versions").unwrap();
+        for version in 1..=2 {
+            backend
+                .db
+                .delete_cf(&versions, keys::version(keys::CONCEPT_VERSION, &concept_id, version))
+                .unwrap();
+        }
+    }
+
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(&db_path).unwrap();
+    let version_store = engine.transaction_manager().version_store();
+    assert!(version_store.get_latest_concept_version(&concept_id).unwrap().is_none());
+    // The next version of this concept must be 3, never a reused 1.
+    assert_eq!(version_store.latest_concept_version_number(&concept_id).unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_engine_opens_with_tuned_storage() {
+    use mnemonic_core::graph::EngineConfig;
+    use mnemonic_core::storage::{ColumnFamilyTuning, Compression, StorageOptions};
+
+    let tuned = ColumnFamilyTuning {
+        compression: Compression::Lz4,
+        write_buffer_size: Some(8 << 20),
+        bloom_filter_bits: Some(10.0),
+    };
+    let config = EngineConfig {
+        storage: StorageOptions {
+            block_cache_bytes: Some(16 << 20),
+            prefix_extractors: true,
+            read_only: false,
+            version_compaction: None,
+            encryption: None,
+            statistics_interval_secs: Some(60),
+            slow_op_threshold_ms: Some(1000),
+            concepts: tuned.clone(),
+            relationships: tuned.clone(),
+            indices: ColumnFamilyTuning {
+                compression: Compression::None,
+                ..tuned.clone()
+            },
+            versions: ColumnFamilyTuning {
+                compression: Compression::Zstd,
+                ..tuned.clone()
+            },
+            blob_threshold_bytes: Some(64),
+            blobs: tuned,
+        },
+        ..Default::default()
+    };
+
+    let dir = tempdir().unwrap();
+    let (person_id, project_id) = {
+        let engine = GraphEngine::with_config(dir.path(), config.clone()).unwrap();
+        let person_id = engine.store(json!({"name": "Dana"})).await.unwrap();
+        let project_id = engine.store(json!({"name": "Atlas"})).await.unwrap();
+        engine.relate(person_id, "leads".to_string(), project_id).await.unwrap();
+        (person_id, project_id)
+    };
+
+    // Prefix scans and cross-prefix hydration both still see everything after a restart.
+    let engine = GraphEngine::with_config(dir.path(), config).unwrap();
+    let relationships = engine.retrieve_by_source(person_id).await.unwrap();
+    assert_eq!(relationships.len(), 1);
+    assert_eq!(relationships[0].target, project_id);
+    let version_store = engine.transaction_manager().version_store();
+    assert_eq!(version_store.get_all_active_concepts().unwrap().len(), 2);
+    assert!(engine.verify_consistency().await.unwrap().is_consistent());
+}
+
+#[tokio::test]
+async fn test_adjacency_is_rebuilt_on_restart() {
+    let dir = tempdir().unwrap();
+    let (alice, bob, kept, recreated) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let alice = engine.store(json!({"name": "Alice"})).await.unwrap();
+        let bob = engine.store(json!({"name": "Bob"})).await.unwrap();
+        let kept = engine.relate(alice, "knows".to_string(), bob).await.unwrap();
+        let dropped = engine.relate(alice, "follows".to_string(), bob).await.unwrap();
+        engine.unrelate(dropped).await.unwrap();
+        let recreated = engine.relate(alice, "follows".to_string(), bob).await.unwrap();
+        (alice, bob, kept, recreated)
+    };
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let mut outgoing: Vec<_> = engine
+        .retrieve_by_source(alice)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|rel| rel.id)
+        .collect();
+    let mut expected = vec![kept, recreated];
+    outgoing.sort();
+    expected.sort();
+    assert_eq!(outgoing, expected);
+
+    let tm = engine.transaction_manager();
+    let (incoming, bob_outgoing) = task::spawn_blocking(move || {
+        let version_store = tm.version_store();
+        (
+            version_store.incoming_relationships(&bob).unwrap().len(),
+            version_store.outgoing_relationships(&bob).unwrap().len(),
+        )
+    })
+    .await
+    .unwrap();
+    assert_eq!(incoming, 2);
+    assert_eq!(bob_outgoing, 0);
+}
+
+#[tokio::test]
+async fn test_counts_match_across_restart() {
+    use mnemonic_core::types::query::Direction;
+
+    let dir = tempdir().unwrap();
+    let (before, hub) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let hub = engine.store(json!({"name": "hub"})).await.unwrap();
+        for n in 0..5 {
+            let spoke = engine.store(json!({"name": format!("spoke {}", n)})).await.unwrap();
+            let rel = engine.relate(hub, "links".to_string(), spoke).await.unwrap();
+            if n % 2 == 0 {
+                engine.unrelate(rel).await.unwrap();
+            }
+        }
+        (engine.stats().await.unwrap(), hub)
+    };
+    assert_eq!((before.concepts, before.relationships), (6, 2));
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(engine.stats().await.unwrap(), before);
+    assert_eq!(engine.count_concepts().await.unwrap(), 6);
+    assert_eq!(engine.count_relationships().await.unwrap(), 2);
+    assert_eq!(engine.count_by_relationship_type().await.unwrap()["links"], 2);
+    assert_eq!(engine.degree(hub, Direction::Outgoing).await.unwrap(), 2);
+    assert_eq!(engine.degree(hub, Direction::Incoming).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_acyclic_relationship_type_rejects_closing_edge() {
+    use mnemonic_core::graph::{EngineConfig, TransactionConfig};
+    use mnemonic_core::MnemonicError;
+
+    let config = EngineConfig {
+        transactions: TransactionConfig {
+            acyclic_types: ["depends_on".into()].into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::with_config(dir.path(), config).unwrap();
+    let app = engine.store(json!({"name": "app"})).await.unwrap();
+    let lib = engine.store(json!({"name": "lib"})).await.unwrap();
+    let core = engine.store(json!({"name": "core"})).await.unwrap();
+
+    // A DAG, with a shortcut edge, is fine.
+    let depends_on: RelationType = "depends_on".into();
+    engine.relate(app, depends_on.clone(), lib).await.unwrap();
+    engine.relate(lib, depends_on.clone(), core).await.unwrap();
+    engine.relate(app, depends_on.clone(), core).await.unwrap();
+    assert_eq!(engine.find_cycle(&depends_on).await.unwrap(), None);
+    assert_eq!(engine.topological_order(&depends_on).await.unwrap(), vec![app, lib, core]);
+
+    match engine.relate(core, depends_on.clone(), app).await {
+        Err(MnemonicError::CycleDetected { relationship_type, cycle }) => {
+            assert_eq!(relationship_type, depends_on);
+            assert_eq!(cycle, vec![app, core]);
+        }
+        other => panic!("expected the closing edge to be rejected, got {:?}", other),
+    }
+    assert_eq!(engine.find_cycle(&depends_on).await.unwrap(), None);
+
+    // Other types are not constrained, even alongside the constrained edges.
+    engine.relate(core, "mentions".to_string(), app).await.unwrap();
+    engine.relate(app, "mentions".to_string(), core).await.unwrap();
+    assert!(engine.find_cycle(&"mentions".into()).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_subgraph_export_has_no_dangling_edges() {
+    use mnemonic_core::graph::GraphSnapshot;
+    use std::collections::HashSet;
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let mut ids = Vec::new();
+    for n in 0..6 {
+        ids.push(engine.store(json!({"name": format!("n{}", n)})).await.unwrap());
+    }
+    // n0 - n1 - n2 - n3 - n4 - n5, with n1 and n3 as overlapping roots.
+    for pair in ids.windows(2) {
+        engine.relate(pair[0], "next".to_string(), pair[1]).await.unwrap();
+    }
+    let ghost = uuid::Uuid::new_v4();
+
+    let snapshot = engine.subgraph(vec![ids[1], ids[3], ghost], 1, None).await.unwrap();
+    let collected: HashSet<_> = snapshot.concepts.iter().map(|c| c.id).collect();
+    assert_eq!(collected, ids[0..5].iter().copied().collect());
+    assert_eq!(snapshot.concepts.len(), 5);
+    assert_eq!(snapshot.relationships.len(), 4);
+    assert!(snapshot
+        .relationships
+        .iter()
+        .all(|rel| collected.contains(&rel.source) && collected.contains(&rel.target)));
+    assert_eq!(snapshot.missing_roots, vec![ghost]);
+
+    let filtered = engine
+        .subgraph(vec![ids[1]], 3, Some(vec!["other".into()]))
+        .await
+        .unwrap();
+    assert_eq!(filtered.concepts.len(), 1);
+    assert!(filtered.relationships.is_empty());
+
+    let mut out = Vec::new();
+    let report = engine
+        .export_jsonl_subgraph(vec![ids[1], ids[3], ghost], 1, &mut out)
+        .await
+        .unwrap();
+    assert_eq!((report.concepts, report.relationships), (5, 4));
+    assert_eq!(report.missing_roots, vec![ghost]);
+    let read = GraphSnapshot::read_jsonl(out.as_slice()).unwrap();
+    assert_eq!(read.concepts, snapshot.concepts);
+    assert_eq!(read.relationships.len(), 4);
+}
+
+#[tokio::test]
+async fn test_seeding_is_idempotent_across_restarts() {
+    use mnemonic_core::graph::SeedFixture;
+
+    let dir = tempdir().unwrap();
+    let demo = SeedFixture::demo();
+    {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let first = engine.seed_if_empty(demo.clone()).await.unwrap();
+        assert_eq!((first.concepts_created, first.relationships_created), (6, 5));
+        assert!(!first.skipped);
+
+        let second = engine.seed_if_empty(demo.clone()).await.unwrap();
+        assert!(second.skipped);
+        assert_eq!(second.concepts_created, 0);
+        assert_eq!(engine.count_concepts().await.unwrap(), 6);
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert!(engine.seed_if_empty(demo.clone()).await.unwrap().skipped);
+    assert_eq!(engine.count_concepts().await.unwrap(), 6);
+    assert_eq!(engine.count_relationships().await.unwrap(), 5);
+    // The fixture's IDs are the concepts' IDs.
+    let lead = engine.get_concept(demo.concepts[1].id).await.unwrap().unwrap();
+    assert_eq!(lead.id, demo.concepts[1].id);
+}
+
+#[tokio::test]
+async fn test_system_concepts_are_versioned_hidden_from_listings_and_survive_restart() {
+    use mnemonic_core::graph::SeedFixture;
+    use mnemonic_core::types::{query::ConceptListOptions, system};
+
+    let dir = tempdir().unwrap();
+    let id = system::system_id("seed:demo");
+    {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        assert_eq!(engine.get_system("seed:demo").await.unwrap(), None);
+        engine.put_system("seed:demo", json!({"version": 1})).await.unwrap();
+        engine.put_system("seed:demo", json!({"version": 1})).await.unwrap();
+        engine.put_system("seed:demo", json!({"version": 2})).await.unwrap();
+        assert_eq!(engine.get_system("seed:demo").await.unwrap(), Some(json!({"version": 2})));
+        // Putting the value it holds committed nothing.
+        let history = engine.concept_history(id).await.unwrap();
+        assert_eq!(history.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2]);
+
+        // A graph holding only system concepts is still empty enough to seed.
+        assert!(!engine.seed_if_empty(SeedFixture::demo()).await.unwrap().skipped);
+        let listed = engine.list_concepts(ConceptListOptions::default()).await.unwrap();
+        assert_eq!(listed.concepts.len(), 6);
+        assert!(listed.concepts.iter().all(|concept| concept.id != id));
+        let everything = ConceptListOptions { include_system: true, ..Default::default() };
+        let listed = engine.list_concepts(everything).await.unwrap();
+        assert_eq!(listed.concepts.len(), 7);
+
+        // Only system concepts carry the system tag.
+        let ordinary = listed.concepts.iter().find(|concept| concept.id != id).unwrap().id;
+        assert!(matches!(
+            engine.add_tags(ordinary, [system::SYSTEM_TAG]).await,
+            Err(MnemonicError::InvalidData(_))
+        ));
+        assert!(matches!(engine.put_system(" ", json!(1)).await, Err(MnemonicError::InvalidData(_))));
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(engine.get_system("seed:demo").await.unwrap(), Some(json!({"version": 2})));
+    assert_eq!(engine.concept_history(id).await.unwrap().len(), 2);
+    engine.put_system("seed:demo", json!({"version": 3})).await.unwrap();
+    assert_eq!(engine.get_system("seed:demo").await.unwrap(), Some(json!({"version": 3})));
+}
+
+#[tokio::test]
+async fn test_schemas_validate_labelled_concepts_and_survive_restart() {
+    use mnemonic_core::MnemonicError;
+
+    let dir = tempdir().unwrap();
+    let person = json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": {"name": {"type": "string"}, "email": {"type": "string"}}
+    });
+    {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        engine.register_schema("person", person.clone()).await.unwrap();
+
+        // Valid, with and without the optional field.
+        engine.store(json!({"labels": ["person"], "name": "Ada"})).await.unwrap();
+        engine
+            .store(json!({"labels": ["person"], "name": "Lin", "email": "lin@example.com"}))
+            .await
+            .unwrap();
+
+        // Invalid: every failing path is reported, and nothing is stored.
+        match engine.store(json!({"labels": ["person"], "email": 42})).await {
+            Err(MnemonicError::SchemaViolation(violations)) => {
+                let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+                assert_eq!(paths, vec!["/name", "/email"]);
+                assert!(violations.iter().all(|v| v.label == "person"));
+            }
+            other => panic!("expected a schema violation, got {:?}", other),
+        }
+        assert_eq!(engine.count_concepts().await.unwrap(), 2);
+
+        // Unlabelled concepts, and labels without a schema, aren't checked.
+        engine.store(json!({"email": 42})).await.unwrap();
+        engine.store(json!({"labels": ["robot"], "email": 42})).await.unwrap();
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(engine.schema("person").await.unwrap(), Some(person));
+    assert!(engine.store(json!({"labels": ["person"]})).await.is_err());
+
+    // Tightening the schema applies to the next write; stored concepts are left alone.
+    engine
+        .register_schema(
+            "person",
+            json!({"required": ["name", "email"], "properties": {"email": {"type": "string"}}}),
+        )
+        .await
+        .unwrap();
+    let without_email = engine.store(json!({"labels": ["person"], "name": "Bo"})).await;
+    assert!(matches!(without_email, Err(MnemonicError::SchemaViolation(v)) if v[0].path == "/email"));
+    assert_eq!(engine.count_concepts().await.unwrap(), 4);
+
+    let unsupported = engine.register_schema("person", json!({"pattern": ".*"})).await;
+    assert!(matches!(unsupported, Err(MnemonicError::InvalidSchema(_))));
+}
+
+#[tokio::test]
+async fn test_one_outgoing_cardinality_is_enforced_and_persisted() {
+    use mnemonic_core::MnemonicError;
+    use mnemonic_core::types::relationship::Cardinality;
+
+    let dir = tempdir().unwrap();
+    let (ada, grace) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        engine
+            .set_relationship_constraint("reports_to", Cardinality::OneOutgoing)
+            .await
+            .unwrap();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+        let linus = engine.store(json!({"name": "Linus"})).await.unwrap();
+
+        let first = engine.relate(ada, "reports_to".to_string(), grace).await.unwrap();
+        match engine.relate(ada, "reports_to".to_string(), linus).await {
+            Err(MnemonicError::CardinalityViolation { existing, cardinality, .. }) => {
+                assert_eq!(existing, first);
+                assert_eq!(cardinality, Cardinality::OneOutgoing);
+            }
+            other => panic!("expected a cardinality violation, got {:?}", other),
+        }
+        // Other sources and other types are unaffected.
+        engine.relate(linus, "reports_to".to_string(), grace).await.unwrap();
+        engine.relate(ada, "knows".to_string(), linus).await.unwrap();
+
+        // Once the first edge is gone the slot is free again.
+        engine.unrelate(first).await.unwrap();
+        engine.relate(ada, "reports_to".to_string(), linus).await.unwrap();
+        (ada, grace)
+    };
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(
+        engine.relationship_constraint("reports_to").await.unwrap(),
+        Cardinality::OneOutgoing
+    );
+    assert!(engine.relate(ada, "reports_to".to_string(), grace).await.is_err());
+
+    // Lifting the constraint is persisted too.
+    engine.set_relationship_constraint("reports_to", Cardinality::Many).await.unwrap();
+    engine.relate(ada, "reports_to".to_string(), grace).await.unwrap();
+    assert_eq!(engine.retrieve_by_source(ada).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_cardinality_race_lets_only_one_commit_win() {
+    use mnemonic_core::MnemonicError;
+    use mnemonic_core::types::relationship::{Cardinality, Relationship};
+
+    let dir = tempdir().unwrap();
+    let engine = std::sync::Arc::new(GraphEngine::new(dir.path()).unwrap());
+    engine
+        .set_relationship_constraint("reports_to", Cardinality::OneOutgoing)
+        .await
+        .unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+    let linus = engine.store(json!({"name": "Linus"})).await.unwrap();
+
+    // Both transactions start before either commits, so neither snapshot sees the other's edge.
+    let mut transactions = Vec::new();
+    for target in [grace, linus] {
+        let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let rel = Relationship::new(ada, "reports_to".to_string(), target);
+        txn.relationship_write_set.insert(rel.id);
+        txn.pending_relationship_writes.insert(rel.id, rel);
+        transactions.push(txn);
+    }
+    let handles: Vec<_> = transactions
+        .into_iter()
+        .map(|txn| {
+            let engine = std::sync::Arc::clone(&engine);
+            task::spawn(async move { engine.commit_transaction(txn).await })
+        })
+        .collect();
+    let mut outcomes = Vec::new();
+    for handle in handles {
+        outcomes.push(handle.await.unwrap());
+    }
+
+    assert_eq!(outcomes.iter().filter(|outcome| outcome.is_ok()).count(), 1);
+    assert!(
+        outcomes
+            .iter()
+            .any(|outcome| matches!(outcome, Err(MnemonicError::CardinalityViolation { .. })))
+    );
+    assert_eq!(engine.retrieve_by_source(ada).await.unwrap().len(), 1);
+
+    // Two edges added by one transaction are checked against each other as well.
+    engine
+        .set_relationship_constraint("mentors", Cardinality::Unique)
+        .await
+        .unwrap();
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    for _ in 0..2 {
+        let rel = Relationship::new(grace, "mentors".to_string(), linus);
+        txn.relationship_write_set.insert(rel.id);
+        txn.pending_relationship_writes.insert(rel.id, rel);
+    }
+    assert!(matches!(
+        engine.commit_transaction(txn).await,
+        Err(MnemonicError::CardinalityViolation { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_purge_erases_history_from_memory_and_disk() {
+    use mnemonic_core::MnemonicError;
+    use mnemonic_core::graph::PurgeOptions;
+
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().to_path_buf();
+    let (ada, grace, knows) = {
+        let engine = GraphEngine::new(&db_path).unwrap();
+        let ada = engine.store(json!({"name": "Ada", "email": "ada@example.com"})).await.unwrap();
+        let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+        let knows = engine.relate(ada, "knows".to_string(), grace).await.unwrap();
+        let before_delete = Utc::now();
+        engine.unrelate(knows).await.unwrap();
+        engine.delete_concept(ada).await.unwrap();
+
+        // A tombstoned concept is still there for time travel...
+        let version_store = engine.transaction_manager().version_store();
+        assert!(version_store.get_concept_version_at_timestamp(&ada, before_delete).unwrap().is_some());
+
+        // ...and a purge waits for any transaction that could read it.
+        let reader = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        match engine.purge_concept(ada).await {
+            Err(MnemonicError::PurgeBlocked(transactions)) => assert_eq!(transactions, vec![reader.id]),
+            other => panic!("expected the purge to be blocked, got {:?}", other),
+        }
+        engine.abort_transaction(reader.id).await.unwrap();
+
+        // Relationships can be kept, but not while they're still active.
+        let mentors = engine.relate(grace, "mentors".to_string(), grace).await.unwrap();
+        let keep = PurgeOptions { keep_relationships: true };
+        assert!(matches!(
+            engine.purge_concept_with(grace, keep).await,
+            Err(MnemonicError::DanglingRelationship { relationship_id, .. }) if relationship_id == mentors
+        ));
+
+        let report = engine.purge_concept(ada).await.unwrap();
+        assert_eq!(report.concept_versions_purged, 2);
+        assert_eq!(report.relationships_purged, 1);
+        assert_eq!(report.relationship_versions_purged, 2);
+
+        assert!(version_store.get_concept_version_at_timestamp(&ada, before_delete).unwrap().is_none());
+        assert!(version_store.get_relationship_version_at_timestamp(&knows, before_delete).unwrap().is_none());
+        assert!(matches!(engine.concept_history(ada).await, Err(MnemonicError::ConceptNotFound(_))));
+        assert!(matches!(engine.purge_concept(ada).await, Err(MnemonicError::ConceptNotFound(_))));
+        assert!(engine.verify_consistency().await.unwrap().is_consistent());
+        (ada, grace, knows)
+    };
+    sleep(Duration::from_millis(100)).await;
+
+    // Nothing comes back on restart, and no key anywhere mentions either ID.
+    let engine = GraphEngine::new(&db_path).unwrap();
+    assert!(engine.transaction_manager().version_store().concept_history(&ada).unwrap().is_empty());
+    assert!(engine.get_concept(grace).await.unwrap().is_some());
+    let backend = engine.backend();
+    for cf_name in ["concepts", "relationships", "indices", "versions"] {
+        let cf = backend.db.cf_handle(cf_name).unwrap();
+        for item in backend.db.iterator_cf(&cf, rocksdb::IteratorMode::Start) {
+            let (key, _) = item.unwrap();
+            for id in [ada, knows] {
+                assert!(
+                    !key.windows(16).any(|window| window == id.as_bytes()),
+                    "{} still holds a key for {}",
+                    cf_name,
+                    id
+                );
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_deduplicating_store_returns_the_existing_concept() {
+    use mnemonic_core::graph::StoreOptions;
+
+    let dir = tempdir().unwrap();
+    let dedupe = || StoreOptions { dedupe: true, ..Default::default() };
+    let first = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let first = engine
+            .store_with_options(json!({"title": "Report", "tags": ["a", "b"]}), dedupe())
+            .await
+            .unwrap();
+        assert!(!first.deduplicated);
+
+        // The same payload, and the same payload with its keys in another order.
+        let again = engine
+            .store_with_options(json!({"title": "Report", "tags": ["a", "b"]}), dedupe())
+            .await
+            .unwrap();
+        let reordered: serde_json::Value =
+            serde_json::from_str(r#"{ "tags": ["a", "b"], "title": "Report" }"#).unwrap();
+        let reordered = engine.store_with_options(reordered, dedupe()).await.unwrap();
+        for report in [&again, &reordered] {
+            assert!(report.deduplicated);
+            assert_eq!(report.concept_id, first.concept_id);
+        }
+
+        // Different data, including a different array order, is a different concept.
+        let other = engine
+            .store_with_options(json!({"title": "Report", "tags": ["b", "a"]}), dedupe())
+            .await
+            .unwrap();
+        assert!(!other.deduplicated);
+        assert_ne!(other.concept_id, first.concept_id);
+
+        // Without the option nothing is deduplicated.
+        let plain = engine
+            .store_with_options(json!({"title": "Report", "tags": ["a", "b"]}), StoreOptions::default())
+            .await
+            .unwrap();
+        assert!(!plain.deduplicated);
+        assert_eq!(engine.count_concepts().await.unwrap(), 3);
+        first.concept_id
+    };
+    sleep(Duration::from_millis(100)).await;
+
+    // The index survives a restart; a deleted concept is no longer a match.
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let payload = json!({"title": "Report", "tags": ["a", "b"]});
+    let report = engine.store_with_options(payload.clone(), dedupe()).await.unwrap();
+    assert_eq!(report.concept_id, first);
+    engine.delete_concept(first).await.unwrap();
+    let replacement = engine.store_with_options(payload.clone(), dedupe()).await.unwrap();
+    assert!(!replacement.deduplicated);
+    assert_eq!(engine.store_with_options(payload, dedupe()).await.unwrap().concept_id, replacement.concept_id);
+}
+
+#[tokio::test]
+async fn test_concurrent_deduplicating_stores_create_one_concept() {
+    use mnemonic_core::MnemonicError;
+    use mnemonic_core::graph::StoreOptions;
+    use mnemonic_core::types::content::content_hash;
+    use std::sync::Arc;
+
+    let dir = tempdir().unwrap();
+    let engine = Arc::new(GraphEngine::new(dir.path()).unwrap());
+
+    // Two transactions claim the same content; the second to commit conflicts.
+    let mut transactions = Vec::new();
+    for _ in 0..2 {
+        let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let concept = Concept::new(json!({"doc": "shared"}));
+        txn.content_hashes.insert(content_hash(&concept.data), concept.id);
+        txn.write_set.insert(concept.id);
+        txn.pending_writes.insert(concept.id, concept);
+        transactions.push(txn);
+    }
+    let mut transactions = transactions.into_iter();
+    engine.commit_transaction(transactions.next().unwrap()).await.unwrap();
+    assert!(matches!(
+        engine.commit_transaction(transactions.next().unwrap()).await,
+        Err(MnemonicError::TransactionConflict(_))
+    ));
+    assert_eq!(engine.count_concepts().await.unwrap(), 1);
+
+    // Racing stores all come back with the one concept that won.
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let engine = Arc::clone(&engine);
+            task::spawn(async move {
+                engine
+                    .store_with_options(json!({"doc": "raced"}), StoreOptions { dedupe: true, ..Default::default() })
+                    .await
+            })
+        })
+        .collect();
+    let mut ids = Vec::new();
+    for handle in handles {
+        ids.push(handle.await.unwrap().unwrap().concept_id);
+    }
+    ids.dedup();
+    assert_eq!(ids.len(), 1);
+    assert_eq!(engine.count_concepts().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_audit_log_window_spans_a_restart() {
+    use mnemonic_core::graph::{AuditQuery, ChangeKind, ItemKind};
+
+    let dir = tempdir().unwrap();
+    let (ada, knows, since) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        engine.store(json!({"name": "Before the window"})).await.unwrap();
+        let since = Utc::now();
+        let ada = engine
+            .store_with_meta(json!({"name": "Ada"}), TransactionMetadata::for_actor("alice"))
+            .await
+            .unwrap();
+        let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+        let knows = engine.relate(ada, "knows".to_string(), grace).await.unwrap();
+        (ada, knows, since)
+    };
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    engine.unrelate(knows).await.unwrap();
+    let until = Utc::now();
+    engine.store(json!({"name": "After the window"})).await.unwrap();
+
+    let page = engine
+        .audit_log(AuditQuery {
+            since: Some(since),
+            until: Some(until),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let changes: Vec<(ItemKind, ChangeKind)> =
+        page.records.iter().map(|r| (r.item_kind, r.change)).collect();
+    assert_eq!(
+        changes,
+        vec![
+            (ItemKind::Concept, ChangeKind::Created),
+            (ItemKind::Concept, ChangeKind::Created),
+            (ItemKind::Relationship, ChangeKind::Created),
+            (ItemKind::Relationship, ChangeKind::Deleted),
+        ]
+    );
+    assert_eq!(page.records[0].item_id, ada);
+    assert_eq!(page.records[0].actor.as_deref(), Some("alice"));
+    assert!(page.records[1..].iter().all(|r| r.actor.is_none()));
+    assert!(page.next_cursor.is_none());
+
+    let alice = engine
+        .audit_log(AuditQuery {
+            actor: Some("alice".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(alice.records.len(), 1);
+}
+
+#[tokio::test]
+async fn test_merge_rewires_edges_and_keeps_history() {
+    use mnemonic_core::graph::{MergeOptions, MergeStrategy};
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let keep = engine.store(json!({"name": "Ada Lovelace", "born": 1815})).await.unwrap();
+    let absorb = engine.store(json!({"name": "Ada", "field": "maths"})).await.unwrap();
+    let babbage = engine.store(json!({"name": "Babbage"})).await.unwrap();
+    let notes = engine.store(json!({"name": "Notes"})).await.unwrap();
+    let knows = engine.relate(babbage, "knows".to_string(), absorb).await.unwrap();
+    let wrote = engine.relate(absorb, "wrote".to_string(), notes).await.unwrap();
+    let same_as = engine.relate(keep, "same_as".to_string(), absorb).await.unwrap();
+    sleep(Duration::from_millis(5)).await;
+    let before = Utc::now();
+    sleep(Duration::from_millis(5)).await;
+
+    let report = engine
+        .merge_concepts(keep, absorb, MergeStrategy::PreferAbsorbFields)
+        .await
+        .unwrap();
+    assert_eq!((report.kept, report.absorbed), (keep, absorb));
+    assert_eq!(report.relationships_rewired.len(), 2);
+    assert_eq!(report.self_relationships_dropped, vec![same_as]);
+
+    // Both edges now hang off `keep`, under new IDs; the edge between the two is gone.
+    let outgoing = engine.retrieve_by_source(keep).await.unwrap();
+    assert_eq!(outgoing.len(), 1);
+    assert_eq!((outgoing[0].id, outgoing[0].target), (report.relationships_rewired[&wrote], notes));
+    let incoming = engine.retrieve_by_target(keep).await.unwrap();
+    assert_eq!(incoming.len(), 1);
+    assert_eq!((incoming[0].id, incoming[0].source), (report.relationships_rewired[&knows], babbage));
+    assert!(engine.retrieve_by_source(absorb).await.unwrap().is_empty());
+    assert!(engine.retrieve_by_target(absorb).await.unwrap().is_empty());
+
+    assert_eq!(engine.get_concept(absorb).await.unwrap(), None);
+    let merged = engine.get_concept(keep).await.unwrap().unwrap();
+    assert_eq!(
+        mnemonic_core::types::query::parse_data(&merged.data),
+        json!({"name": "Ada", "born": 1815, "field": "maths"})
+    );
+
+    // Before the merge, everything is as it was.
+    let version_store = engine.transaction_manager().version_store();
+    let old_absorb = version_store.get_concept_version_at_timestamp(&absorb, before).unwrap().unwrap();
+    assert!(old_absorb.deleted_at.is_none());
+    for rel_id in [knows, wrote, same_as] {
+        let old = version_store.get_relationship_version_at_timestamp(&rel_id, before).unwrap().unwrap();
+        assert!(old.source == absorb || old.target == absorb);
+        assert_eq!(version_store.get_relationship_version_at_timestamp(&rel_id, Utc::now()).unwrap(), None);
+    }
+    assert_eq!(engine.concept_history(keep).await.unwrap().len(), 2);
+
+    // Keeping self-relationships turns the edge between the two into a loop on `keep`.
+    let twin = engine.store(json!({"name": "Ada L."})).await.unwrap();
+    let loop_edge = engine.relate(twin, "same_as".to_string(), keep).await.unwrap();
+    let report = engine
+        .merge_concepts_with(keep, twin, MergeStrategy::KeepTarget, MergeOptions { keep_self_relationships: true })
+        .await
+        .unwrap();
+    assert!(report.self_relationships_dropped.is_empty());
+    let looped = engine.retrieve_by_target(keep).await.unwrap();
+    let new_loop = looped.iter().find(|r| r.id == report.relationships_rewired[&loop_edge]).unwrap();
+    assert_eq!((new_loop.source, new_loop.target), (keep, keep));
+    // KeepTarget leaves the kept concept's data, and its history, alone.
+    assert_eq!(engine.concept_history(keep).await.unwrap().len(), 2);
+
+    assert!(engine.merge_concepts(keep, keep, MergeStrategy::KeepTarget).await.is_err());
+    assert!(matches!(
+        engine.merge_concepts(keep, absorb, MergeStrategy::KeepTarget).await,
+        Err(mnemonic_core::error::MnemonicError::ConceptNotFound(id)) if id == absorb
+    ));
+}
+
+#[tokio::test]
+async fn test_commit_subscribers_see_each_commit_in_order() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    // Nobody is listening yet, so this commit is not delivered to anyone.
+    engine.store(json!({"name": "Before"})).await.unwrap();
+
+    let mut commits = engine.subscribe_commits();
+    let alice = engine.store(json!({"name": "Alice"})).await.unwrap();
+    let bob = engine.store(json!({"name": "Bob"})).await.unwrap();
+    let knows = engine.relate(alice, "knows".to_string(), bob).await.unwrap();
+
+    let first = commits.recv().await.unwrap();
+    assert_eq!(first.concept_versions.len(), 1);
+    assert_eq!(first.concept_versions[0].concept_id, alice);
+    assert_eq!(first.transaction_id, first.concept_versions[0].created_by);
+    assert_eq!(commits.recv().await.unwrap().concept_versions[0].concept_id, bob);
+    let third = commits.recv().await.unwrap();
+    assert!(third.concept_versions.is_empty());
+    assert_eq!(third.relationship_versions[0].relationship_id, knows);
+    assert!(first.committed_at <= third.committed_at);
+    assert!(commits.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_rebuild_derived_state_repairs_corrupted_indexes() {
+    use mnemonic_core::graph::StoreOptions;
+    use mnemonic_core::types::{content, relationship::Relationship};
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+    let unique = engine
+        .store_with_options(json!({"name": "Unique"}), StoreOptions { dedupe: true, ..Default::default() })
+        .await
+        .unwrap()
+        .concept_id;
+    let backend = engine.backend();
+    let relationship = Relationship::new(ada, "knows".to_string(), grace);
+    backend.store_relationship(&relationship).unwrap();
+
+    // Nothing to fix yet.
+    let report = engine.rebuild_derived_state().await.unwrap();
+    assert_eq!(report.repaired(), 0);
+    assert_eq!(report.relationship_index.entries, 4);
+    assert_eq!(report.content_hashes.entries, 1);
+
+    // Lose the source entry, invent a target entry, and point a content hash at the wrong concept.
+    let mut batch = rocksdb::WriteBatch::default();
+    backend.delete_relationship_index_entry(keys::INDEX_SOURCE, &ada, &relationship.id, &mut batch);
+    backend
+        .store_relationship_index_entry(keys::INDEX_TARGET, &ada, &relationship.id, &mut batch)
+        .unwrap();
+    let stale = content::content_hash(&Concept::new(json!({"name": "Nobody"})).data);
+    backend.store_content_hash(stale, &grace, &mut batch).unwrap();
+    backend.db.write(batch).unwrap();
+    assert!(backend.get_relationships_by_source(&ada).unwrap().is_empty());
+
+    let report = engine.rebuild_derived_state().await.unwrap();
+    assert!(report.consistency.is_consistent());
+    assert_eq!(report.relationship_index.repaired, 2);
+    assert_eq!(report.content_hashes.repaired, 1);
+    assert_eq!(report.repaired(), 3);
+    assert_eq!(backend.get_relationships_by_source(&ada).unwrap(), vec![relationship.clone()]);
+    assert_eq!(
+        backend.load_relationship_index(keys::INDEX_TARGET).unwrap(),
+        vec![(grace, relationship.id)]
+    );
+    let hashes = backend.load_all_content_hashes().unwrap();
+    assert_eq!(hashes.len(), 1);
+    assert_eq!(hashes[0].1, unique);
+
+    // Deduplication still finds the concept whose entry survived.
+    let again = engine
+        .store_with_options(json!({"name": "Unique"}), StoreOptions { dedupe: true, ..Default::default() })
+        .await
+        .unwrap();
+    assert!(again.deduplicated);
+    assert_eq!(again.concept_id, unique);
+}
+
+#[tokio::test]
+async fn test_check_finds_exactly_the_seeded_corruption() {
+    use mnemonic_core::ConflictItem;
+    use mnemonic_core::graph::{CheckDepth, Finding};
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    let mut renamed = Concept::new(json!({"name": "Ada L."}));
+    renamed.id = ada;
+    txn.write_set.insert(ada);
+    txn.pending_writes.insert(ada, renamed);
+    engine.commit_transaction(txn).await.unwrap();
+    engine.relate(ada, "knows".to_string(), grace).await.unwrap();
+    for depth in [CheckDepth::Quick, CheckDepth::Standard, CheckDepth::Deep] {
+        assert!(engine.check(depth).await.unwrap().is_clean(), "{:?}", depth);
+    }
+
+    // Lose Ada's latest version on disk and leave an index entry for a relationship that never was.
+    let backend = engine.backend();
+    let orphan = uuid::Uuid::new_v4();
+    let mut batch = rocksdb::WriteBatch::default();
+    backend.delete_concept_version(&ada, 2, &mut batch).unwrap();
+    backend
+        .store_relationship_index_entry(keys::INDEX_SOURCE, &grace, &orphan, &mut batch)
+        .unwrap();
+    backend.db.write(batch).unwrap();
+
+    let count = Finding::CountMismatch { keyspace: "concept_versions".to_string(), memory: 3, disk: 2 };
+    let missing = Finding::MissingOnDisk {
+        item: ConflictItem::Concept(ada),
+        version: 2,
+        key: format!("cv:{}:2", ada),
+    };
+    let orphaned = Finding::OrphanedIndexEntry {
+        key: format!("idx_src:{}:{}", grace, orphan),
+        concept_id: grace,
+        relationship_id: orphan,
+    };
+    let quick = engine.check(CheckDepth::Quick).await.unwrap();
+    assert_eq!(quick.findings, vec![count.clone()]);
+    assert_eq!(quick.keyspaces["concept_versions"], 2);
+    assert_eq!(engine.check(CheckDepth::Standard).await.unwrap().findings, vec![count.clone(), missing.clone()]);
+    assert_eq!(engine.check(CheckDepth::Deep).await.unwrap().findings, vec![count, missing, orphaned]);
+}
+
+#[tokio::test]
+async fn test_engine_stored_concepts_are_readable_through_every_read_path() {
+    use mnemonic_core::types::query::ConceptListOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let (ada, grace) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+        let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let mut renamed = Concept::new(json!({"name": "Ada L."}));
+        renamed.id = ada;
+        txn.write_set.insert(ada);
+        txn.pending_writes.insert(ada, renamed);
+        engine.commit_transaction(txn).await.unwrap();
+        engine.delete_concept(grace).await.unwrap();
+        (ada, grace)
+    };
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let backend = engine.backend();
+    let expected = engine.get_concept(ada).await.unwrap().unwrap();
+    assert_eq!(expected.data, Concept::new(json!({"name": "Ada L."})).data);
+    assert_eq!(expected.metadata.version, 2);
+
+    assert_eq!(engine.get_concepts(vec![ada, grace]).await.unwrap(), vec![Some(expected.clone()), None]);
+    assert_eq!(backend.get_concept(&ada).unwrap(), Some(expected.clone()));
+    assert_eq!(backend.get_concepts(&[ada, grace]).unwrap(), vec![Some(expected.clone()), None]);
+    let listed = engine.list_concepts(ConceptListOptions::default()).await.unwrap();
+    assert_eq!(listed.concepts.iter().map(|c| c.id).collect::<Vec<_>>(), vec![ada]);
+    assert_eq!(engine.snapshot().await.unwrap().concepts, vec![expected.clone()]);
+
+    // A lost head record is regenerated from the versions.
+    let mut batch = rocksdb::WriteBatch::default();
+    backend.delete_concept_record(&ada, &mut batch);
+    backend.db.write(batch).unwrap();
+    assert!(backend.get_concept(&ada).unwrap().is_none());
+    let report = engine.rebuild_derived_state().await.unwrap();
+    assert_eq!(report.concept_records.repaired, 1);
+    assert_eq!(backend.get_concept(&ada).unwrap(), Some(expected));
+}
+
+#[tokio::test]
+async fn test_expired_concepts_drop_out_of_reads_before_they_are_swept() {
+    use mnemonic_core::graph::{EngineConfig, StoreOptions, TransactionConfig};
+    use mnemonic_core::types::query::Direction;
+    use mnemonic_core::MnemonicError;
+
+    let config = EngineConfig {
+        transactions: TransactionConfig { expiry_sweep_interval: None, ..Default::default() },
+        ..Default::default()
+    };
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::with_config(dir.path(), config).unwrap();
+    let expiring = |ttl_ms| StoreOptions {
+        expires_at: Some(Utc::now() + chrono::Duration::milliseconds(ttl_ms)),
+        ..Default::default()
+    };
+    let session = engine.store_with_options(json!({"name": "Session"}), expiring(300)).await.unwrap().concept_id;
+    let user = engine.store(json!({"name": "User"})).await.unwrap();
+    engine.relate(session, "belongs_to".to_string(), user).await.unwrap();
+
+    assert!(engine.get_concept(session).await.unwrap().unwrap().expires_at.is_some());
+    assert_eq!(engine.retrieve_by_source(session).await.unwrap().len(), 1);
+    assert_eq!(engine.retrieve_by_target(user).await.unwrap().len(), 1);
+    assert_eq!(engine.count_relationships().await.unwrap(), 1);
+
+    sleep(Duration::from_millis(400)).await;
+    assert!(engine.get_concept(session).await.unwrap().is_none());
+    assert_eq!(engine.get_concepts(vec![session, user]).await.unwrap()[0], None);
+    assert!(engine.retrieve_by_source(session).await.unwrap().is_empty());
+    assert!(engine.retrieve_by_target(user).await.unwrap().is_empty());
+    assert_eq!(engine.count_concepts().await.unwrap(), 1);
+    assert_eq!(engine.count_relationships().await.unwrap(), 0);
+    assert_eq!(engine.degree(user, Direction::Both).await.unwrap(), 0);
+    assert!(engine.snapshot().await.unwrap().relationships.is_empty());
+    // Nothing has been written yet: the last version is still the live one.
+    assert!(engine.concept_history(session).await.unwrap().last().unwrap().deleted_at.is_none());
+    assert!(matches!(
+        engine.relate(user, "owns".to_string(), session).await,
+        Err(MnemonicError::ConceptNotFound(id)) if id == session
+    ));
+
+    // A sweep writes real tombstones for the concept and its relationship.
+    let manager = engine.transaction_manager();
+    assert_eq!(manager.sweep_expired().unwrap(), 1);
+    assert_eq!(manager.sweep_expired().unwrap(), 0);
+    let history = engine.concept_history(session).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(history[1].deleted_at.is_some());
+    // Time travel still finds the concept while it was live.
+    let live = manager
+        .version_store()
+        .get_concept_version_at_timestamp(&session, history[0].created_at)
+        .unwrap();
+    assert_eq!(live.map(|v| v.version), Some(1));
+    let relationships = manager.version_store().relationship_ids_touching(&user);
+    assert!(relationships.is_empty());
+    assert!(engine.check(mnemonic_core::graph::CheckDepth::Deep).await.unwrap().is_clean());
+
+    assert!(matches!(
+        engine.store_with_options(json!({"name": "Stale"}), expiring(-1000)).await,
+        Err(MnemonicError::InvalidData(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_expiry_sweeper_tombstones_without_a_restart() {
+    use mnemonic_core::graph::{EngineConfig, StoreOptions, TransactionConfig, EXPIRY_SWEEPER};
+
+    let config = EngineConfig {
+        transactions: TransactionConfig {
+            expiry_sweep_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::with_config(dir.path(), config).unwrap();
+    let options = StoreOptions {
+        expires_at: Some(Utc::now() + chrono::Duration::milliseconds(100)),
+        ..Default::default()
+    };
+    let token = engine.store_with_options(json!({"token": "abc"}), options).await.unwrap().concept_id;
+    assert!(engine.get_concept(token).await.unwrap().is_some());
+
+    let mut tombstone = None;
+    for _ in 0..100 {
+        sleep(Duration::from_millis(20)).await;
+        tombstone = engine.concept_history(token).await.unwrap().pop().filter(|v| v.deleted_at.is_some());
+        if tombstone.is_some() {
+            break;
+        }
+    }
+    let tombstone = tombstone.expect("the sweeper should have tombstoned the concept");
+    assert!(engine.get_concept(token).await.unwrap().is_none());
+    let metadata = engine.transaction_metadata(tombstone.created_by).await.unwrap().unwrap();
+    assert_eq!(metadata.actor.as_deref(), Some(EXPIRY_SWEEPER));
+    assert!(engine.backend().get_concept(&token).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_registries_are_system_concepts_and_move_there_from_their_old_keys() {
+    use mnemonic_core::storage::RocksBackend;
+    use mnemonic_core::types::relationship::{Cardinality, RelationshipTypeDefinition};
+
+    let dir = tempdir().unwrap();
+    let person = json!({"required": ["name"]});
+    {
+        // Where a database from before system concepts kept them.
+        let backend = RocksBackend::new(dir.path()).unwrap();
+        backend.store_schema("person", &person).unwrap();
+        backend
+            .store_relationship_type(&RelationshipTypeDefinition {
+                name: "works_for".into(),
+                description: "Employment".to_string(),
+                registered_at: Utc::now(),
+            })
+            .unwrap();
+        backend.store_cardinality("reports_to", Cardinality::OneOutgoing).unwrap();
+    }
+
+    {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        assert_eq!(engine.schema("person").await.unwrap(), Some(person.clone()));
+        assert_eq!(engine.relationship_types().await.unwrap()[0].description, "Employment");
+        assert_eq!(engine.get_system("schemas").await.unwrap(), Some(json!({"person": person})));
+        assert!(engine.backend().load_all_schemas().unwrap().is_empty());
+        assert!(engine.backend().load_all_relationship_types().unwrap().is_empty());
+        assert_eq!(engine.relationship_constraint("reports_to").await.unwrap(), Cardinality::OneOutgoing);
+        assert_eq!(engine.get_system("cardinalities").await.unwrap(), Some(json!({"reports_to": "one_outgoing"})));
+        assert!(engine.backend().load_all_cardinalities().unwrap().is_empty());
+
+        engine.register_schema("place", json!({"required": ["city"]})).await.unwrap();
+        engine.register_relationship_type("lives_in", "Home").await.unwrap();
+        let types = engine.get_system("relationship-types").await.unwrap().unwrap();
+        assert_eq!(types["lives_in"]["description"], "Home");
+        engine.set_relationship_constraint("reports_to", Cardinality::Many).await.unwrap();
+        engine.set_relationship_constraint("lives_in", Cardinality::OneOutgoing).await.unwrap();
+        // The system concepts don't count as concepts.
+        assert_eq!(engine.count_concepts().await.unwrap(), 0);
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert!(engine.schema("person").await.unwrap().is_some());
+    assert!(engine.store(json!({"labels": ["place"]})).await.is_err());
+    let types = engine.relationship_types().await.unwrap();
+    let names: Vec<String> = types.into_iter().map(|definition| definition.name.to_string()).collect();
+    assert_eq!(names, vec!["lives_in", "works_for"]);
+    assert_eq!(engine.relationship_constraint("reports_to").await.unwrap(), Cardinality::Many);
+    assert_eq!(engine.relationship_constraint("lives_in").await.unwrap(), Cardinality::OneOutgoing);
+}
+
+#[tokio::test]
+async fn test_scans_leave_system_concepts_alone() {
+    use mnemonic_core::graph::BulkDeleteOptions;
+    use mnemonic_core::types::query::ConceptListOptions;
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    engine.register_schema("person", json!({"required": ["name"]})).await.unwrap();
+    let ada = engine.store(json!({"labels": ["person"], "name": "Ada"})).await.unwrap();
+
+    // An empty filter matches every concept, but not the schema registry.
+    let everything = BulkDeleteOptions { dry_run: false, ..Default::default() };
+    let report = engine.delete_where(Filter::default(), everything).await.unwrap();
+    assert_eq!(report.matched, vec![ada]);
+    assert!(engine.store(json!({"labels": ["person"]})).await.is_err());
+    assert!(engine.get_system("schemas").await.unwrap().is_some());
+
+    engine.store(json!({"labels": ["person"], "name": "Lin"})).await.unwrap();
+    let groups = engine.aggregate("name", None).await.unwrap();
+    assert_eq!(groups, [("Lin".to_string(), 1)].into());
+    let page = engine.list_concepts(ConceptListOptions::default()).await.unwrap();
+    assert_eq!(page.concepts.len(), 1);
+}
+
+/// An engine holding the system concept `seed:demo`, whose ID it returns.
+async fn with_system_concept() -> (GraphEngine, tempfile::TempDir, uuid::Uuid) {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    engine.put_system("seed:demo", json!({"at": 1})).await.unwrap();
+    (engine, dir, mnemonic_core::types::system::system_id("seed:demo"))
+}
+
+#[tokio::test]
+async fn test_system_concepts_cannot_be_deleted() {
+    use mnemonic_core::graph::DeleteOptions;
+
+    let (engine, _dir, id) = with_system_concept().await;
+    let cascade = DeleteOptions { cascade: true, ..Default::default() };
+    let deleted = engine.delete_concept_with_options(id, cascade).await;
+    assert!(matches!(deleted, Err(MnemonicError::InvalidData(message)) if message.contains("system concept")));
+    assert!(matches!(engine.delete_concept(id).await, Err(MnemonicError::InvalidData(_))));
+    assert_eq!(engine.get_system("seed:demo").await.unwrap(), Some(json!({"at": 1})));
+}
+
+#[tokio::test]
+async fn test_system_concepts_cannot_be_retagged() {
+    let (engine, _dir, id) = with_system_concept().await;
+    assert!(matches!(engine.add_tags(id, ["starred"]).await, Err(MnemonicError::InvalidData(_))));
+    let untagged = engine.remove_tags(id, [mnemonic_core::types::system::SYSTEM_TAG]).await;
+    assert!(matches!(untagged, Err(MnemonicError::InvalidData(_))));
+    let concept = engine.get_concept(id).await.unwrap().unwrap();
+    assert_eq!(concept.metadata.version, 1);
+}
+
+#[tokio::test]
+async fn test_system_concepts_cannot_be_merged_either_way() {
+    use mnemonic_core::graph::MergeStrategy;
+
+    let (engine, _dir, id) = with_system_concept().await;
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    for (keep, absorb) in [(ada, id), (id, ada)] {
+        let merged = engine.merge_concepts(keep, absorb, MergeStrategy::PreferAbsorbFields).await;
+        assert!(matches!(merged, Err(MnemonicError::InvalidData(_))));
+    }
+    assert!(engine.get_concept(ada).await.unwrap().is_some());
+    assert_eq!(engine.get_system("seed:demo").await.unwrap(), Some(json!({"at": 1})));
+}
+
+#[tokio::test]
+async fn test_strict_mode_refuses_unregistered_relationship_types() {
+    use mnemonic_core::graph::{EngineConfig, TransactionConfig};
+    use mnemonic_core::MnemonicError;
+
+    let strict = || EngineConfig {
+        transactions: TransactionConfig { strict_relationship_types: true, ..Default::default() },
+        ..Default::default()
+    };
+    let dir = tempdir().unwrap();
+    let (ada, acme) = {
+        let engine = GraphEngine::with_config(dir.path(), strict()).unwrap();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let acme = engine.store(json!({"name": "Acme"})).await.unwrap();
+        engine.register_relationship_type("works_for", "Employment").await.unwrap();
+        engine.relate(ada, "works_for".to_string(), acme).await.unwrap();
+        (ada, acme)
+    };
+
+    // The registration survives a restart.
+    let engine = GraphEngine::with_config(dir.path(), strict()).unwrap();
+    let refused = engine.relate(ada, "worksFor".to_string(), acme).await.unwrap_err();
+    assert!(matches!(
+        &refused,
+        MnemonicError::UnknownRelationshipType { relationship_type, suggestions }
+            if relationship_type == "worksFor" && suggestions == &vec!["works_for".to_string()]
+    ));
+    assert_eq!(refused.to_string(), r#"Unknown relationship type "worksFor"; did you mean "works_for"?"#);
+    let unrelated = engine.relate(ada, "sponsors".to_string(), acme).await.unwrap_err();
+    assert_eq!(unrelated.to_string(), r#"Unknown relationship type "sponsors""#);
+    assert!(engine.relate_batch(vec![(ada, "sponsors".into(), acme)], Default::default()).await.is_err());
+    assert_eq!(engine.count_relationships().await.unwrap(), 1);
+
+    // Re-registering updates the description but keeps the date.
+    let first = engine.relationship_types().await.unwrap().remove(0);
+    let updated = engine.register_relationship_type("works_for", "Employed by").await.unwrap();
+    assert_eq!((updated.registered_at, updated.description.as_str()), (first.registered_at, "Employed by"));
+
+    // Without strict mode any type goes, registered or not.
+    drop(engine);
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    engine.relate(ada, "sponsors".to_string(), acme).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_undirected_relationships_read_the_same_from_either_end() {
+    use mnemonic_core::graph::{RelateOptions, TraversalLimits};
+    use mnemonic_core::types::query::Direction;
+
+    let dir = tempdir().unwrap();
+    let undirected = RelateOptions { directed: false };
+    let (a, b, c, siblings) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let a = engine.store(json!({"name": "a"})).await.unwrap();
+        let b = engine.store(json!({"name": "b"})).await.unwrap();
+        let c = engine.store(json!({"name": "c"})).await.unwrap();
+        let siblings = engine.relate_with_options(b, "sibling_of", a, undirected.clone()).await.unwrap();
+        engine.relate(b, "knows", c).await.unwrap();
+        (a, b, c, siblings)
+    };
+
+    // Reopened, so the flag has been through the disk as well.
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    for end in [a, b] {
+        let outgoing = engine.retrieve_by_source(end).await.unwrap();
+        assert!(outgoing.iter().any(|r| r.id == siblings && !r.directed));
+        let incoming = engine.retrieve_by_target(end).await.unwrap();
+        assert!(incoming.iter().any(|r| r.id == siblings));
+    }
+    assert_eq!(engine.degree(a, Direction::Outgoing).await.unwrap(), 1);
+    assert_eq!(engine.degree(a, Direction::Both).await.unwrap(), 1);
+    assert_eq!(engine.degree(b, Direction::Both).await.unwrap(), 2);
+    assert_eq!(engine.count_relationships().await.unwrap(), 2);
+
+    // A walk leaves `a` over the edge recorded as entering it.
+    let limits = TraversalLimits { max_depth: 5, max_concepts: 100 };
+    let path = engine.shortest_path(a, c, None, limits).await.unwrap();
+    let ids: Vec<_> = path.concepts.iter().map(|concept| concept.id).collect();
+    assert_eq!(ids, vec![a, b, c]);
+    let around_a = engine.neighborhood(a, None, limits).await.unwrap();
+    assert_eq!(around_a.concepts.len(), 3);
+}
+
+#[tokio::test]
+async fn test_unique_undirected_pairs_ignore_the_order_of_their_ends() {
+    use mnemonic_core::MnemonicError;
+    use mnemonic_core::graph::RelateOptions;
+    use mnemonic_core::types::relationship::{Cardinality, Relationship};
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let a = engine.store(json!({"name": "a"})).await.unwrap();
+    let b = engine.store(json!({"name": "b"})).await.unwrap();
+    for relationship_type in ["sibling_of", "follows"] {
+        engine.set_relationship_constraint(relationship_type, Cardinality::Unique).await.unwrap();
+    }
+    let undirected = || RelateOptions { directed: false };
+
+    engine.relate_with_options(a, "sibling_of", b, undirected()).await.unwrap();
+    for (source, target, options) in [(b, a, undirected()), (a, b, undirected()), (b, a, RelateOptions::default())] {
+        assert!(matches!(
+            engine.relate_with_options(source, "sibling_of", target, options).await,
+            Err(MnemonicError::CardinalityViolation { .. })
+        ));
+    }
+    // Directed pairs still tell the two orders apart.
+    engine.relate(a, "follows", b).await.unwrap();
+    engine.relate(b, "follows", a).await.unwrap();
+
+    // So does a single transaction adding both orders.
+    let c = engine.store(json!({"name": "c"})).await.unwrap();
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    for (source, target) in [(a, c), (c, a)] {
+        let rel = Relationship::undirected(source, "sibling_of", target);
+        txn.relationship_write_set.insert(rel.id);
+        txn.pending_relationship_writes.insert(rel.id, rel);
+    }
+    assert!(matches!(
+        engine.commit_transaction(txn).await,
+        Err(MnemonicError::CardinalityViolation { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_neo4j_csv_import_maps_labels_and_typed_properties() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/neo4j");
+    let stats = engine
+        .import_neo4j_csv(
+            vec![fixtures.join("persons.csv"), fixtures.join("companies.csv")],
+            vec![fixtures.join("works_at.csv"), fixtures.join("knows.csv")],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!((stats.concepts, stats.relationships, stats.relationship_properties_dropped), (4, 3, 1));
+    let errors: Vec<(String, usize)> = stats
+        .errors
+        .iter()
+        .map(|e| (e.file.file_name().unwrap().to_string_lossy().into_owned(), e.line))
+        .collect();
+    assert_eq!(
+        errors,
+        [("persons.csv", 4), ("persons.csv", 5), ("works_at.csv", 4), ("works_at.csv", 5), ("works_at.csv", 6), ("knows.csv", 3)]
+            .map(|(file, line)| (file.to_string(), line))
+    );
+    assert_eq!(stats.errors[0].message, "age: \"old\" is not an integer");
+    assert_eq!(stats.errors[1].message, "duplicate node ID \"p1\"");
+
+    let concepts = engine.snapshot().await.unwrap().concepts;
+    let by_neo4j_id = |id: &str| {
+        let concept = concepts
+            .iter()
+            .find(|c| parse_data(&c.data)["neo4j_id"] == id)
+            .unwrap_or_else(|| panic!("no concept imported for {}", id));
+        (concept.id, parse_data(&concept.data))
+    };
+    let (ada, ada_data) = by_neo4j_id("p1");
+    assert_eq!(
+        ada_data,
+        json!({
+            "personId": "p1",
+            "name": "Ada",
+            "age": 36,
+            "height": 1.7,
+            "active": true,
+            "nicknames": ["Countess", "Enchantress"],
+            "labels": ["Person", "Engineer"],
+            "neo4j_id": "p1",
+        })
+    );
+    assert_eq!(by_neo4j_id("p2").1, json!({"personId": "p2", "name": "Lin, Jr.", "age": 29, "active": false, "labels": ["Person"], "neo4j_id": "p2"}));
+    assert_eq!(by_neo4j_id("p4").1["labels"], json!(["Person", "Engineer", "Mathematician"]));
+    let (company, _) = by_neo4j_id("c1");
+
+    let mut edges: Vec<(String, uuid::Uuid)> = engine
+        .retrieve_by_source(ada)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.relationship_type.to_string(), r.target))
+        .collect();
+    edges.sort();
+    assert_eq!(edges, vec![("KNOWS".to_string(), by_neo4j_id("p4").0), ("WORKS_AT".to_string(), company)]);
+}
+
+#[tokio::test]
+async fn test_snapshot_at_returns_the_graph_as_it_stood_then() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+    let knows = engine.relate(ada, "knows".to_string(), grace).await.unwrap();
+    let before = Utc::now();
+    sleep(Duration::from_millis(10)).await;
+
+    let alan = engine.store(json!({"name": "Alan"})).await.unwrap();
+    engine.relate(ada, "knows".to_string(), alan).await.unwrap();
+    engine.unrelate(knows).await.unwrap();
+    engine.delete_concept(grace).await.unwrap();
+
+    let then = engine.snapshot_at(before).await.unwrap();
+    let mut names: Vec<_> = then.concepts.iter().map(|c| parse_data(&c.data)["name"].clone()).collect();
+    names.sort_by_key(|name| name.to_string());
+    assert_eq!(names, vec![json!("Ada"), json!("Grace")]);
+    assert_eq!(then.relationships.iter().map(|r| r.id).collect::<Vec<_>>(), vec![knows]);
+
+    let mut now = engine.snapshot_at(Utc::now()).await.unwrap();
+    let mut live = engine.snapshot().await.unwrap();
+    for snapshot in [&mut now, &mut live] {
+        snapshot.concepts.sort_by_key(|c| c.id);
+        snapshot.relationships.sort_by_key(|r| r.id);
+    }
+    assert_eq!(now, live);
+}
+
+#[tokio::test]
+async fn test_a_lite_graph_syncs_with_a_server_engine_over_jsonl() {
+    use mnemonic_core::graph::GraphEngineLite;
+
+    let local = GraphEngineLite::new();
+    let ada = local.store(json!({"name": "Ada"})).unwrap();
+    let charles = local.store(json!({"name": "Charles"})).unwrap();
+    let knows = local.relate(ada, "knows", charles).unwrap();
+
+    let dir = tempdir().unwrap();
+    let server = GraphEngine::new(dir.path()).unwrap();
+    server.import_jsonl(local.export_jsonl().unwrap().as_bytes()).await.unwrap();
+    assert_eq!(parse_data(&server.get_concept(ada).await.unwrap().unwrap().data), json!({"name": "Ada"}));
+    assert_eq!(server.retrieve_by_source(ada).await.unwrap()[0].id, knows);
+
+    let grace = server.store(json!({"name": "Grace"})).await.unwrap();
+    let mut exported = Vec::new();
+    server.export_jsonl(&mut exported).await.unwrap();
+    let report = GraphEngineLite::new().import_jsonl(std::str::from_utf8(&exported).unwrap()).unwrap();
+    assert_eq!((report.concepts, report.relationships), (3, 1));
+    local.import_jsonl(std::str::from_utf8(&exported).unwrap()).unwrap();
+    assert!(local.get_concept(grace).unwrap().is_some());
+    assert_eq!(local.retrieve_by_target(charles).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_hot_spots_rank_concepts_by_how_often_commits_conflict_on_them() {
+    use mnemonic_core::MnemonicError;
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let busy = engine.store(json!({"name": "busy"})).await.unwrap();
+    let quiet = engine.store(json!({"name": "quiet"})).await.unwrap();
+    engine.store(json!({"name": "calm"})).await.unwrap();
+
+    // Two writers race for the concept; the second to commit conflicts.
+    let collide = async |id| {
+        let mut writers = Vec::new();
+        for round in 0..2 {
+            let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+            let mut concept = Concept::new(json!({"round": round}));
+            concept.id = id;
+            txn.write_set.insert(id);
+            txn.pending_writes.insert(id, concept);
+            writers.push(txn);
+        }
+        let mut writers = writers.into_iter();
+        engine.commit_transaction(writers.next().unwrap()).await.unwrap();
+        assert!(matches!(
+            engine.commit_transaction(writers.next().unwrap()).await,
+            Err(MnemonicError::TransactionConflict(_))
+        ));
+    };
+    for _ in 0..6 {
+        collide(busy).await;
+    }
+    for _ in 0..2 {
+        collide(quiet).await;
+    }
+
+    let spots = engine.hot_spots(10);
+    assert_eq!(spots.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![busy, quiet]);
+    assert!(spots[0].1 > 5.9 && spots[0].1 <= 6.0);
+    assert!(spots[0].1 > spots[1].1 * 2.5);
+    assert_eq!(engine.hot_spots(1).len(), 1);
+    let in_stats = engine.stats().await.unwrap().conflict_hot_spots;
+    assert_eq!(in_stats.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![busy, quiet]);
+}
+
+#[tokio::test]
+async fn test_retrieve_by_source_and_type_returns_exactly_the_matching_edges() {
+    use mnemonic_core::graph::RelateOptions;
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let mut member_of = Vec::new();
+    for n in 0..12 {
+        let other = engine.store(json!({ "n": n })).await.unwrap();
+        let relationship_type = ["member_of", "knows", "member_of:past"][n % 3];
+        let id = engine.relate(ada, relationship_type, other).await.unwrap();
+        if relationship_type == "member_of" {
+            member_of.push(id);
+        }
+        // Edges of the type entering Ada don't leave her.
+        engine.relate(other, "member_of", ada).await.unwrap();
+    }
+    // An undirected edge leaves both ends, whichever one was recorded as its source.
+    let team = engine.store(json!({"name": "Team"})).await.unwrap();
+    let undirected = engine
+        .relate_with_options(team, "member_of", ada, RelateOptions { directed: false })
+        .await
+        .unwrap();
+    member_of.push(undirected);
+    engine.unrelate(member_of.remove(0)).await.unwrap();
+
+    let ids = |found: Vec<mnemonic_core::types::relationship::Relationship>| {
+        let mut ids: Vec<_> = found.into_iter().map(|r| r.id).collect();
+        ids.sort();
+        ids
+    };
+    member_of.sort();
+    assert_eq!(ids(engine.retrieve_by_source_and_type(ada, "member_of").await.unwrap()), member_of);
+    assert_eq!(engine.retrieve_by_source_and_type(ada, "knows").await.unwrap().len(), 4);
+    assert_eq!(engine.retrieve_by_source_and_type(ada, "member_of:past").await.unwrap().len(), 4);
+    assert!(engine.retrieve_by_source_and_type(ada, "works_for").await.unwrap().is_empty());
+
+    // Rebuilding the indexes from the version chains changes nothing.
+    let report = engine.rebuild_derived_state().await.unwrap();
+    assert_eq!(report.adjacency.repaired, 0);
+    assert_eq!(ids(engine.retrieve_by_source_and_type(ada, "member_of").await.unwrap()), member_of);
+}
+
+#[tokio::test]
+async fn test_pruning_a_long_history_range_deletes_all_but_the_kept_versions() {
+    use mnemonic_core::graph::RetentionPolicy;
+
+    const VERSIONS: u64 = 10_000;
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let concept_id = engine.store(json!({"step": 1})).await.unwrap();
+    for step in 2..=VERSIONS {
+        let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+        let mut concept = Concept::new(json!({ "step": step }));
+        concept.id = concept_id;
+        txn.write_set.insert(concept_id);
+        txn.pending_writes.insert(concept_id, concept);
+        engine.commit_transaction(txn).await.unwrap();
+    }
+
+    let policy = RetentionPolicy { keep_latest: 3, ..Default::default() };
+    let report = engine.prune_versions(policy).await.unwrap();
+    assert_eq!(report.concept_versions_pruned, VERSIONS as usize - 3);
+    assert_eq!((report.items_pruned, report.range_deletes), (1, 1));
+
+    // After compaction, a scan of the concept's versions meets only the ones kept.
+    engine.compact_storage().await.unwrap();
+    let backend = engine.backend();
+    let cf = backend.db.cf_handle("versions").unwrap();
+    let prefix = keys::item(keys::CONCEPT_VERSION, &concept_id);
+    let on_disk: Vec<u64> = backend
+        .db
+        .iterator_cf(cf, rocksdb::IteratorMode::From(&prefix, rocksdb::Direction::Forward))
+        .map(|item| item.unwrap().0)
+        .take_while(|key| key.starts_with(&prefix))
+        .map(|key| u64::from_be_bytes(key[keys::ITEM_PREFIX_LEN..].try_into().unwrap()))
+        .collect();
+    assert_eq!(on_disk, vec![VERSIONS - 2, VERSIONS - 1, VERSIONS]);
+    assert_eq!(
+        engine.transaction_manager().version_store().concept_version_numbers().unwrap()[&concept_id],
+        on_disk
+    );
+    assert!(engine.verify_consistency().await.unwrap().is_consistent());
+}
+
+/// Commits a new version of a concept with `data`.
+async fn rewrite(engine: &GraphEngine, concept_id: uuid::Uuid, data: serde_json::Value) {
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    let mut concept = Concept::new(data);
+    concept.id = concept_id;
+    txn.write_set.insert(concept_id);
+    txn.pending_writes.insert(concept_id, concept);
+    engine.commit_transaction(txn).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_compaction_filter_drops_the_history_of_long_deleted_concepts() {
+    use mnemonic_core::graph::EngineConfig;
+    use mnemonic_core::storage::{StorageOptions, VersionCompaction};
+
+    let config = |horizon_secs| EngineConfig {
+        storage: StorageOptions {
+            version_compaction: Some(VersionCompaction { horizon_secs, drop_expired: false }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let on_disk = |engine: &GraphEngine, id| -> Vec<u64> {
+        let mut versions: Vec<u64> = engine
+            .backend()
+            .load_version_numbers(keys::CONCEPT_VERSION)
+            .unwrap()
+            .into_iter()
+            .filter(|(concept_id, _)| *concept_id == id)
+            .map(|(_, version)| version)
+            .collect();
+        versions.sort_unstable();
+        versions
+    };
+
+    let dir = tempdir().unwrap();
+    let (gone, kept) = {
+        let engine = GraphEngine::with_config(dir.path(), config(3600)).unwrap();
+        let gone = engine.store(json!({"v": 1})).await.unwrap();
+        rewrite(&engine, gone, json!({"v": 2})).await;
+        engine.delete_concept(gone).await.unwrap();
+        let kept = engine.store(json!({"v": 1})).await.unwrap();
+        rewrite(&engine, kept, json!({"v": 2})).await;
+
+        // Deleted well inside the hour-long horizon, so nothing goes yet.
+        engine.compact_storage().await.unwrap();
+        assert_eq!(on_disk(&engine, gone), vec![1, 2, 3]);
+        (gone, kept)
+    };
+
+    // Reopened with no horizon at all, the deleted concept keeps only its tombstone.
+    let engine = GraphEngine::with_config(dir.path(), config(0)).unwrap();
+    let held = engine.store(json!({"v": 1})).await.unwrap();
+    rewrite(&engine, held, json!({"v": 2})).await;
+    let snapshot = engine.open_snapshot().await.unwrap();
+    engine.delete_concept(held).await.unwrap();
+
+    engine.compact_storage().await.unwrap();
+    assert_eq!(on_disk(&engine, gone), vec![3]);
+    assert_eq!(on_disk(&engine, kept), vec![1, 2]);
+    // The open snapshot still reads the concept as it was.
+    assert_eq!(on_disk(&engine, held), vec![1, 2, 3]);
+
+    engine.release_snapshot(snapshot.token).await.unwrap();
+    engine.compact_storage().await.unwrap();
+    assert_eq!(on_disk(&engine, held), vec![3]);
+    assert!(engine.get_concept(gone).await.unwrap().is_none());
+
+    // Memory catches up with disk on repair, and the deleted concepts stay deleted.
+    let report = engine.repair_consistency().await.unwrap();
+    assert_eq!(report.repaired, 2);
+    assert!(engine.verify_consistency().await.unwrap().is_consistent());
+    assert!(engine.get_concept(held).await.unwrap().is_none());
+    assert_eq!(engine.concept_history(kept).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_large_payloads_are_stored_once_as_blobs_and_collected_with_their_last_version() {
+    use mnemonic_core::graph::{EngineConfig, RetentionPolicy};
+    use mnemonic_core::storage::StorageOptions;
+
+    let config = EngineConfig {
+        storage: StorageOptions { blob_threshold_bytes: Some(1024), ..Default::default() },
+        ..Default::default()
+    };
+    let blobs = |engine: &GraphEngine| -> usize {
+        let db = &engine.backend().db;
+        let cf = db.cf_handle("blobs").unwrap();
+        db.prefix_iterator_cf(cf, [keys::BLOB])
+            .map(Result::unwrap)
+            .take_while(|(key, _)| key.first() == Some(&keys::BLOB))
+            .count()
+    };
+    let big = json!({"body": "x".repeat(4096)});
+
+    let dir = tempdir().unwrap();
+    let (id, before_shrinking) = {
+        let engine = GraphEngine::with_config(dir.path(), config.clone()).unwrap();
+        let id = engine.store(big.clone()).await.unwrap();
+        rewrite(&engine, id, big.clone()).await;
+        engine.store(json!({"small": true})).await.unwrap();
+        // Two versions with the same payload share one blob; small data stays inline.
+        assert_eq!(blobs(&engine), 1);
+
+        let before_shrinking = Utc::now();
+        sleep(Duration::from_millis(5)).await;
+        rewrite(&engine, id, json!({"body": "short"})).await;
+        (id, before_shrinking)
+    };
+
+    // Reads resolve the blob, after a restart too.
+    let engine = GraphEngine::with_config(dir.path(), config).unwrap();
+    let then = engine.get_concept_at(id, before_shrinking).await.unwrap().unwrap();
+    assert_eq!(parse_data(&then.data), big);
+    let history = engine.concept_history(id).await.unwrap();
+    assert_eq!(history.len(), 3);
+    // The two versions holding it share one copy in memory.
+    let payloads: Vec<_> = history
+        .iter()
+        .filter_map(|version| match &version.data {
+            ConceptData::Structured(payload) if payload.len() > 1024 => Some(payload),
+            _ => None,
+        })
+        .collect();
+    assert!(matches!(payloads[..], [first, second] if Arc::ptr_eq(first, second)));
+
+    let report = engine.prune_versions(RetentionPolicy { keep_latest: 1, ..Default::default() }).await.unwrap();
+    assert_eq!(report.concept_versions_pruned, 2);
+    assert_eq!(report.blobs_collected, 1);
+    assert_eq!(blobs(&engine), 0);
+    assert_eq!(parse_data(&engine.get_concept(id).await.unwrap().unwrap().data), json!({"body": "short"}));
+}
+
+#[tokio::test]
+async fn test_updates_replace_data_and_listings_search_it() {
+    use mnemonic_core::types::{query::ConceptListOptions, system};
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"labels": ["person"], "name": "Ada Lovelace"})).await.unwrap();
+    let grace = engine.store(json!({"labels": ["person"], "name": "Grace Hopper"})).await.unwrap();
+    engine.add_tags(ada, ["starred"]).await.unwrap();
+
+    // The data is replaced, the tags kept; the same data again commits nothing.
+    let married = json!({"labels": ["person"], "name": "Ada King", "titles": ["Countess"]});
+    engine.update_concept(ada, married.clone()).await.unwrap();
+    engine.update_concept(ada, married.clone()).await.unwrap();
+    let updated = engine.get_concept(ada).await.unwrap().unwrap();
+    assert_eq!((parse_data(&updated.data), updated.metadata.version), (married, 3));
+    assert!(updated.tags.contains("starred"));
+    assert!(matches!(
+        engine.update_concept(uuid::Uuid::new_v4(), json!({})).await,
+        Err(MnemonicError::ConceptNotFound(_))
+    ));
+    engine.put_system("seed:demo", json!(1)).await.unwrap();
+    let system = engine.update_concept(system::system_id("seed:demo"), json!({})).await;
+    assert!(matches!(system, Err(MnemonicError::InvalidData(_))));
+
+    // Search matches any string in the data, nested ones too, ignoring case.
+    let search = |text: &str| ConceptListOptions { search: Some(text.to_string()), ..Default::default() };
+    let found = |page: mnemonic_core::types::query::ConceptPage| -> Vec<_> {
+        page.concepts.into_iter().map(|concept| concept.id).collect()
+    };
+    assert_eq!(found(engine.list_concepts(search("KING")).await.unwrap()), vec![ada]);
+    assert_eq!(found(engine.list_concepts(search("countess")).await.unwrap()), vec![ada]);
+    assert_eq!(found(engine.list_concepts(search("hopper")).await.unwrap()), vec![grace]);
+    assert!(found(engine.list_concepts(search("lovelace")).await.unwrap()).is_empty());
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Person {
+    name: String,
+    email: Option<String>,
+}
+
+impl ConceptEntity for Person {
+    fn label() -> &'static str {
+        "Person"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Project {
+    name: String,
+    stars: u32,
+}
+
+impl ConceptEntity for Project {
+    fn label() -> &'static str {
+        "Project"
+    }
+}
+
+#[tokio::test]
+async fn test_entities_round_trip_under_their_labels() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = Person { name: "Ada".to_string(), email: Some("ada@example.com".to_string()) };
+    let lin = Person { name: "Lin".to_string(), email: None };
+    let engine_project = Project { name: "engine".to_string(), stars: 12 };
+
+    let ada_id = engine.store_entity(&ada).await.unwrap();
+    let lin_id = engine.store_entity(&lin).await.unwrap();
+    let project_id = engine.store_entity(&engine_project).await.unwrap();
+
+    assert_eq!(engine.get_entity::<Person>(ada_id).await.unwrap(), Some(ada.clone()));
+    assert_eq!(engine.get_entity::<Project>(project_id).await.unwrap(), Some(engine_project));
+    assert_eq!(engine.get_entity::<Person>(uuid::Uuid::new_v4()).await.unwrap(), None);
+    // The label is what listings already filter on.
+    let stored = engine.get_concept(ada_id).await.unwrap().unwrap();
+    assert_eq!(parse_data(&stored.data)["labels"], json!(["Person"]));
+
+    let mut people = engine.find_entities::<Person>(None).await.unwrap();
+    people.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    assert_eq!(people, vec![(ada_id, ada), (lin_id, lin.clone())]);
+    let filter = Filter { equals: [("name".to_string(), "Lin".to_string())].into(), ..Filter::default() };
+    assert_eq!(engine.find_entities::<Person>(Some(filter)).await.unwrap(), vec![(lin_id, lin)]);
+}
+
+#[tokio::test]
+async fn test_reading_a_concept_as_the_wrong_entity_is_a_typed_error() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let person_id = engine.store_entity(&Person { name: "Ada".to_string(), email: None }).await.unwrap();
+    // Labelled as a project, but without the fields of one.
+    let broken_id = engine.store(json!({"name": "engine", "labels": ["Project"]})).await.unwrap();
+
+    let err = engine.get_entity::<Project>(person_id).await.unwrap_err();
+    assert!(
+        matches!(&err, MnemonicError::EntityMismatch { concept_id, label, .. } if *concept_id == person_id && label == "Project"),
+        "{}",
+        err
+    );
+    let err = engine.get_entity::<Project>(broken_id).await.unwrap_err();
+    assert!(err.to_string().contains("missing field `stars`"), "{}", err);
+    assert!(matches!(engine.find_entities::<Project>(None).await, Err(MnemonicError::EntityMismatch { .. })));
+}
+
+#[tokio::test]
+async fn test_relating_entities_checks_the_endpoint_labels() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store_entity(&Person { name: "Ada".to_string(), email: None }).await.unwrap();
+    let grace = engine.store_entity(&Person { name: "Grace".to_string(), email: None }).await.unwrap();
+
+    let err = engine.relate_entities::<Person, Project>(ada, "works_on", grace).await.unwrap_err();
+    assert!(
+        matches!(&err, MnemonicError::EntityMismatch { concept_id, label, .. } if *concept_id == grace && label == "Project"),
+        "{}",
+        err
+    );
+    assert_eq!(err.to_string(), format!("Concept {} is not a Project: it is labelled [\"Person\"]", grace));
+    assert!(engine.retrieve_by_source(ada).await.unwrap().is_empty());
+    assert!(engine.active_transactions().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_typed_neighbors_follow_one_relationship_type() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store_entity(&Person { name: "Ada".to_string(), email: None }).await.unwrap();
+    let grace = engine.store_entity(&Person { name: "Grace".to_string(), email: None }).await.unwrap();
+    let engine_project = Project { name: "engine".to_string(), stars: 42 };
+    let project = engine.store_entity(&engine_project).await.unwrap();
+
+    let works_on = engine.relate_entities::<Person, Project>(ada, "works_on", project).await.unwrap();
+    engine.relate_entities::<Person, Person>(ada, "knows", grace).await.unwrap();
+
+    assert_eq!(engine.neighbors_of_type::<Project>(ada, "works_on").await.unwrap(), vec![(works_on, engine_project)]);
+    let known: Vec<Person> = engine
+        .neighbors_of_type::<Person>(ada, "knows")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(_, person)| person)
+        .collect();
+    assert_eq!(known, vec![Person { name: "Grace".to_string(), email: None }]);
+    assert!(matches!(
+        engine.neighbors_of_type::<Person>(ada, "works_on").await,
+        Err(MnemonicError::EntityMismatch { .. })
+    ));
+}
+
+/// Every page of `list_concepts` at size 7, concatenated, storing one more concept between
+/// pages when `write_between` is set.
+async fn list_in_pages(engine: &GraphEngine, write_between: bool) -> Vec<uuid::Uuid> {
+    use mnemonic_core::types::query::ConceptListOptions;
+
+    let mut listed = Vec::new();
+    let mut cursor = None;
+    loop {
+        let options = ConceptListOptions { limit: Some(7), cursor, ..Default::default() };
+        let page = engine.list_concepts(options).await.unwrap();
+        listed.extend(page.concepts.iter().map(|c| c.id));
+        if write_between {
+            engine.store(json!({"between": listed.len()})).await.unwrap();
+        }
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return listed,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_pages_and_scans_follow_id_order_across_writes_and_restarts() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let mut ids = Vec::new();
+    for n in 0..40 {
+        ids.push(engine.store(json!({"n": n})).await.unwrap());
+    }
+    // Random IDs, so the store order and the ID order interleave.
+    ids.sort();
+
+    assert_eq!(list_in_pages(&engine, false).await, ids);
+    let snapshot: Vec<_> = engine.snapshot().await.unwrap().concepts.iter().map(|c| c.id).collect();
+    assert_eq!(snapshot, ids);
+
+    // Concepts stored mid-listing land before or after the cursor; either way, none of the
+    // ones already there is listed twice or missed.
+    let listed = list_in_pages(&engine, true).await;
+    assert!(listed.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(ids.iter().all(|id| listed.contains(id)));
+
+    let all = list_in_pages(&engine, false).await;
+    assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+    engine.close().await.unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(list_in_pages(&engine, false).await, all);
+}
+
+#[tokio::test]
+async fn test_an_archive_restores_the_whole_history_into_an_empty_engine() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let mut points = vec![Utc::now()];
+    let alice = TransactionMetadata { actor: Some("alice".to_string()), ..Default::default() };
+    let ada = engine.store_with_meta(json!({"name": "Ada"}), alice.clone()).await.unwrap();
+    let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+    points.push(Utc::now());
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    let mut renamed = Concept::new(json!({"name": "Ada L."}));
+    renamed.id = ada;
+    txn.write_set.insert(ada);
+    txn.pending_writes.insert(ada, renamed);
+    engine.commit_transaction(txn).await.unwrap();
+    let knows = engine.relate(ada, "knows".to_string(), grace).await.unwrap();
+    points.push(Utc::now());
+    engine.unrelate(knows).await.unwrap();
+    engine.delete_concept(grace).await.unwrap();
+    points.push(Utc::now());
+
+    let mut archive = Vec::new();
+    let exported = engine.export_archive(&mut archive).await.unwrap();
+    assert_eq!((exported.concept_versions, exported.relationship_versions), (4, 2));
+
+    let restored_dir = tempdir().unwrap();
+    let restored = GraphEngine::new(restored_dir.path()).unwrap();
+    assert_eq!(restored.import_archive(archive.as_slice()).await.unwrap(), exported);
+    for &at in &points {
+        assert_eq!(restored.snapshot_at(at).await.unwrap(), engine.snapshot_at(at).await.unwrap(), "{}", at);
+    }
+    assert_eq!(restored.concept_history(ada).await.unwrap(), engine.concept_history(ada).await.unwrap());
+    let created_by = engine.concept_history(ada).await.unwrap()[0].created_by;
+    assert_eq!(restored.transaction_metadata(created_by).await.unwrap(), Some(alice));
+
+    // An engine with a history of its own is left alone.
+    assert!(matches!(
+        restored.import_archive(archive.as_slice()).await,
+        Err(MnemonicError::Archive(_))
+    ));
+    assert!(matches!(engine.import_archive(archive.as_slice()).await, Err(MnemonicError::Archive(_))));
+
+    // The restored engine numbers its commits and versions on from the archive's, and
+    // keeps all of it across a restart.
+    let mut txn = restored.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    let mut renamed = Concept::new(json!({"name": "Ada Lovelace"}));
+    renamed.id = ada;
+    txn.write_set.insert(ada);
+    txn.pending_writes.insert(ada, renamed);
+    let receipt = restored.commit_transaction_detailed(txn).await.unwrap();
+    assert_eq!(receipt.sequence, exported.commit_sequence + 1);
+    assert_eq!(receipt.concept_versions, vec![(ada, 3)]);
+    assert!(receipt.committed_at > *points.last().unwrap());
+    let latest = restored.get_concept(ada).await.unwrap();
+    assert!(latest.is_some());
+    restored.close().await.unwrap();
+    let restored = GraphEngine::new(restored_dir.path()).unwrap();
+    for &at in &points {
+        assert_eq!(restored.snapshot_at(at).await.unwrap(), engine.snapshot_at(at).await.unwrap(), "{}", at);
+    }
+    assert_eq!(restored.get_concept(ada).await.unwrap(), latest);
+}
+
+#[tokio::test]
+async fn test_tags_change_without_rewriting_data_and_keep_their_history() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada", "labels": ["person"]})).await.unwrap();
+    let bob = engine.store(json!({"name": "Bob"})).await.unwrap();
+    let ids = |concepts: Vec<Concept>| concepts.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+    let tags = engine.add_tags(ada, ["starred", "needs-review"]).await.unwrap();
+    assert_eq!(tags.iter().map(String::as_str).collect::<Vec<_>>(), ["needs-review", "starred"]);
+    let reviewing = Utc::now();
+    sleep(Duration::from_millis(5)).await;
+    engine.remove_tags(ada, ["needs-review", "never-had-it"]).await.unwrap();
+    // Tags it already carries commit nothing.
+    engine.add_tags(ada, ["starred"]).await.unwrap();
+    let history = engine.concept_history(ada).await.unwrap();
+    assert_eq!(history.len(), 3);
+    assert!(history.windows(2).all(|pair| pair[0].data == pair[1].data));
+
+    let now = engine.get_concept(ada).await.unwrap().unwrap();
+    assert_eq!(now.tags.iter().map(String::as_str).collect::<Vec<_>>(), ["starred"]);
+    let then = engine.get_concept_at(ada, reviewing).await.unwrap().unwrap();
+    assert!(then.tags.contains("needs-review") && then.tags.contains("starred"));
+
+    engine.add_tags(bob, ["starred"]).await.unwrap();
+    let mut both = vec![ada, bob];
+    both.sort();
+    assert_eq!(ids(engine.find_by_tag("starred").await.unwrap()), both);
+    assert!(engine.find_by_tag("needs-review").await.unwrap().is_empty());
+    engine.delete_concept(bob).await.unwrap();
+    assert_eq!(ids(engine.find_by_tag("starred").await.unwrap()), vec![ada]);
+
+    assert!(matches!(engine.add_tags(ada, [" padded"]).await, Err(MnemonicError::InvalidData(_))));
+    assert!(matches!(engine.add_tags(bob, ["starred"]).await, Err(MnemonicError::ConceptNotFound(_))));
+
+    // Tagging is a write: a transaction that read around it and writes the concept conflicts.
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    engine.add_tags(ada, ["contested"]).await.unwrap();
+    let mut renamed = Concept::new(json!({"name": "Ada Lovelace"}));
+    renamed.id = ada;
+    txn.write_set.insert(ada);
+    txn.pending_writes.insert(ada, renamed);
+    assert!(matches!(engine.commit_transaction(txn).await, Err(MnemonicError::TransactionConflict(_))));
+
+    // Tags are stored with each version, so they and their index come back after a restart.
+    engine.close().await.unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(ids(engine.find_by_tag("contested").await.unwrap()), vec![ada]);
+    assert!(engine.get_concept_at(ada, reviewing).await.unwrap().unwrap().tags.contains("needs-review"));
+    let rebuilt = engine.rebuild_derived_state().await.unwrap();
+    assert_eq!((rebuilt.tags.entries, rebuilt.tags.repaired), (2, 0));
+}
+
+#[tokio::test]
+async fn test_updating_a_relationship_keeps_its_id_and_moves_its_indexes() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let bob = engine.store(json!({"name": "Bob"})).await.unwrap();
+    let cy = engine.store(json!({"name": "Cy"})).await.unwrap();
+    let rel = engine.relate(ada, "nows", bob).await.unwrap();
+    let typo = Utc::now();
+    sleep(Duration::from_millis(5)).await;
+
+    let retyped = RelationshipUpdate { relationship_type: Some("knows".into()), ..Default::default() };
+    engine.update_relationship(rel, retyped.clone()).await.unwrap();
+    assert!(engine.retrieve_by_source_and_type(ada, "nows").await.unwrap().is_empty());
+    let knows = engine.retrieve_by_source_and_type(ada, "knows").await.unwrap();
+    assert_eq!((knows.len(), knows[0].id, knows[0].metadata.version), (1, rel, 2));
+    // The same change again commits nothing.
+    engine.update_relationship(rel, retyped).await.unwrap();
+
+    let retargeted = RelationshipUpdate { target: Some(cy), ..Default::default() };
+    engine.update_relationship(rel, retargeted).await.unwrap();
+    assert!(engine.retrieve_by_target(bob).await.unwrap().is_empty());
+    assert_eq!(engine.retrieve_by_target(cy).await.unwrap()[0].id, rel);
+
+    // The history shows every shape the relationship had, and time travel still finds the old one.
+    let history = engine.relationship_history(rel).await.unwrap();
+    let shapes: Vec<_> = history.iter().map(|v| (v.version, v.relationship_type.to_string(), v.target)).collect();
+    assert_eq!(shapes, [(1, "nows".to_string(), bob), (2, "knows".to_string(), bob), (3, "knows".to_string(), cy)]);
+    let then = engine.snapshot_at(typo).await.unwrap();
+    assert_eq!(then.relationships[0].relationship_type, "nows");
+    assert_eq!(then.relationships[0].target, bob);
+
+    let gone = uuid::Uuid::new_v4();
+    let dangling = RelationshipUpdate { target: Some(gone), ..Default::default() };
+    assert!(matches!(engine.update_relationship(rel, dangling).await, Err(MnemonicError::ConceptNotFound(id)) if id == gone));
+    assert!(matches!(
+        engine.update_relationship(gone, RelationshipUpdate::default()).await,
+        Err(MnemonicError::RelationshipNotFound(_))
+    ));
+
+    // The adjacency index is regenerated from the latest versions, so it agrees after a restart.
+    engine.close().await.unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(engine.retrieve_by_source_and_type(ada, "knows").await.unwrap()[0].target, cy);
+    assert!(engine.retrieve_by_target(bob).await.unwrap().is_empty());
+    assert_eq!(engine.rebuild_derived_state().await.unwrap().adjacency.repaired, 0);
+}
+
+#[tokio::test]
+async fn test_a_relationship_update_conflicts_with_a_concurrent_write_to_it() {
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let bob = engine.store(json!({"name": "Bob"})).await.unwrap();
+    let rel = engine.relate(ada, "knows", bob).await.unwrap();
+
+    let mut txn = engine.begin_transaction(IsolationLevel::Snapshot).await.unwrap();
+    let retyped = RelationshipUpdate { relationship_type: Some("mentors".into()), ..Default::default() };
+    engine.update_relationship(rel, retyped).await.unwrap();
+    let mut rewritten = Relationship::new(ada, "likes", bob);
+    rewritten.id = rel;
+    txn.relationship_write_set.insert(rel);
+    txn.pending_relationship_writes.insert(rel, rewritten);
+    assert!(matches!(engine.commit_transaction(txn).await, Err(MnemonicError::TransactionConflict(_))));
+
+    let history = engine.relationship_history(rel).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].relationship_type, "mentors");
+}
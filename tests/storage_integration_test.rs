@@ -85,3 +85,583 @@ fn test_store_and_get_relationship_by_source() {
     assert_eq!(found_rel.relationship_type, "works_for");
     println!("SUCCESS: Relationship indexing test passed!");
 }
+
+#[test]
+fn test_storage_stats_grow_with_data() {
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    let before = backend.stats(true).unwrap();
+
+    for n in 0..10 {
+        backend.store_concept(&Concept::new(json!({ "n": n }))).unwrap();
+    }
+
+    let after = backend.stats(true).unwrap();
+    let concepts_before = before.column_family("concepts").unwrap();
+    let concepts_after = after.column_family("concepts").unwrap();
+    assert_eq!(concepts_after.exact_keys, Some(concepts_before.exact_keys.unwrap() + 10));
+    assert!(concepts_after.estimated_keys > concepts_before.estimated_keys);
+    assert!(concepts_after.live_data_bytes > concepts_before.live_data_bytes);
+    assert_eq!(after.column_families.len(), 5);
+}
+
+/// Total size of every key in the database, in bytes.
+fn key_bytes(backend: &RocksBackend) -> usize {
+    ["concepts", "relationships", "indices", "versions"]
+        .iter()
+        .map(|name| {
+            let cf = backend.db.cf_handle(name).unwrap();
+            backend
+                .db
+                .iterator_cf(cf, rocksdb::IteratorMode::Start)
+                .map(|item| item.unwrap().0.len())
+                .sum::<usize>()
+        })
+        .sum()
+}
+
+#[tokio::test]
+async fn test_string_keyed_database_migrates_to_binary_keys() {
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::types::concept::{ConceptData, ConceptVersion};
+    use mnemonic_core::types::relationship::RelationshipVersion;
+    use uuid::Uuid;
+
+    const CONCEPTS: usize = 50;
+    const VERSIONS: u64 = 12;
+
+    // --- 1. SETUP: a database in the old layout, written key by key ---
+    let dir = tempdir().unwrap();
+    let mut concepts = Vec::new();
+    let legacy_bytes = {
+        let backend = RocksBackend::new(dir.path()).unwrap();
+        let db = &backend.db;
+        let cf = |name| db.cf_handle(name).unwrap();
+        db.delete_cf(cf("versions"), [0x00]).unwrap();
+
+        let txn = Uuid::new_v4();
+        for n in 0..CONCEPTS {
+            let concept = Concept::new(json!({ "n": n }));
+            db.put_cf(cf("concepts"), format!("concept:{}", concept.id), bincode::serialize(&concept).unwrap())
+                .unwrap();
+            for version in 1..=VERSIONS {
+                let record = ConceptVersion {
+                    concept_id: concept.id,
+                    version,
+                    data: ConceptData::Structured(json!({ "n": n, "v": version }).to_string().into()),
+                    created_at: chrono::Utc::now(),
+                    created_by: txn,
+                    deleted_at: None,
+                    deleted_by: None,
+                    expires_at: None,
+                    tags: Default::default(),
+                };
+                let key = format!("cv:{}:{}", concept.id, version);
+                db.put_cf(cf("versions"), key, bincode::serialize(&record).unwrap()).unwrap();
+            }
+            db.put_cf(cf("versions"), format!("head:cv:{}", concept.id), bincode::serialize(&VERSIONS).unwrap())
+                .unwrap();
+            concepts.push(concept);
+        }
+        for pair in concepts.windows(2) {
+            let rel = Relationship::new(pair[0].id, "next".to_string(), pair[1].id);
+            let record = RelationshipVersion::from_relationship(&rel, txn);
+            let rel_id = bincode::serialize(&rel.id).unwrap();
+            db.put_cf(cf("relationships"), format!("rel:{}", rel.id), bincode::serialize(&rel).unwrap())
+                .unwrap();
+            db.put_cf(cf("indices"), format!("idx_src:{}:{}", rel.source, rel.id), &rel_id).unwrap();
+            db.put_cf(cf("indices"), format!("idx_tgt:{}:{}", rel.target, rel.id), &rel_id).unwrap();
+            db.put_cf(cf("versions"), format!("rv:{}:1", rel.id), bincode::serialize(&record).unwrap())
+                .unwrap();
+            db.put_cf(cf("versions"), format!("head:rv:{}", rel.id), bincode::serialize(&1u64).unwrap())
+                .unwrap();
+        }
+        key_bytes(&backend)
+    };
+
+    // --- 2. ACTION: reopening migrates ---
+    let engine = GraphEngine::new(dir.path()).unwrap();
+
+    // --- 3. VERIFICATION ---
+    let binary_bytes = key_bytes(&engine.backend());
+    println!(
+        "Key space: {} bytes as strings, {} bytes binary ({:.0}% smaller)",
+        legacy_bytes,
+        binary_bytes,
+        100.0 * (1.0 - binary_bytes as f64 / legacy_bytes as f64)
+    );
+    // Version keys dominate: ~41 bytes as strings, 25 binary.
+    assert!(binary_bytes * 10 < legacy_bytes * 6);
+
+    let version_store = engine.transaction_manager().version_store();
+    let active = version_store.get_all_active_concepts().unwrap();
+    assert_eq!(active.len(), CONCEPTS);
+    assert!(active.iter().all(|v| v.version == VERSIONS));
+    let numbers = version_store.concept_version_numbers().unwrap();
+    assert_eq!(numbers[&concepts[0].id], (1..=VERSIONS).collect::<Vec<_>>());
+
+    let outgoing = engine.retrieve_by_source(concepts[0].id).await.unwrap();
+    assert_eq!(outgoing.len(), 1);
+    assert_eq!(outgoing[0].target, concepts[1].id);
+    assert!(engine.get_concept(concepts[3].id).await.unwrap().is_some());
+    assert!(engine.verify_consistency().await.unwrap().is_consistent());
+
+    // Already migrated: nothing left to do.
+    let report = engine.backend().migrate_keys().unwrap();
+    assert_eq!(report.migrated, 0);
+    assert_eq!(report.unrecognised, 0);
+}
+
+#[test]
+fn test_get_concepts_lines_up_with_ids() {
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    let first = Concept::new(json!({"name": "first"}));
+    let second = Concept::new(json!({"name": "second"}));
+    backend.store_concept(&first).unwrap();
+    backend.store_concept(&second).unwrap();
+    let missing = uuid::Uuid::new_v4();
+
+    let found = backend.get_concepts(&[second.id, missing, first.id, second.id]).unwrap();
+    let ids: Vec<Option<uuid::Uuid>> = found.iter().map(|c| c.as_ref().map(|c| c.id)).collect();
+    assert_eq!(ids, vec![Some(second.id), None, Some(first.id), Some(second.id)]);
+    assert_eq!(found[2].as_ref().unwrap().data, first.data);
+    assert!(backend.get_concepts(&[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_relationships_are_found_by_type_even_with_colons_in_the_name() {
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    let (ada, acme) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    let works_for = Relationship::new(ada, "works_for".to_string(), acme);
+    let namespaced = Relationship::new(ada, "org:works_for".to_string(), acme);
+    let parent = Relationship::new(acme, "org".to_string(), ada);
+    for relationship in [&works_for, &namespaced, &parent] {
+        backend.store_relationship(relationship).unwrap();
+    }
+
+    let by_type = |name: &str| {
+        let found = backend.get_relationships_by_type(&name.into()).unwrap();
+        found.into_iter().map(|r| r.id).collect::<Vec<_>>()
+    };
+    assert_eq!(by_type("works_for"), vec![works_for.id]);
+    assert_eq!(by_type("org:works_for"), vec![namespaced.id]);
+    assert_eq!(by_type("org"), vec![parent.id]);
+    assert!(by_type("org:").is_empty());
+
+    // Deleting a relationship removes its type entry with the others.
+    backend.delete_ralationship(&namespaced.id).unwrap();
+    assert!(by_type("org:works_for").is_empty());
+    assert_eq!(backend.load_type_index().unwrap(), {
+        let mut left = vec![(works_for.relationship_type.clone(), works_for.id), (parent.relationship_type.clone(), parent.id)];
+        left.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        left
+    });
+}
+
+#[test]
+fn test_composite_index_returns_exactly_one_sources_edges_of_one_type() {
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    let (ada, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    let mut member_of = Vec::new();
+    for i in 0..30 {
+        let relationship_type = ["member_of", "member", "knows", "member_of:past"][i % 4];
+        let relationship = Relationship::new(ada, relationship_type.to_string(), uuid::Uuid::new_v4());
+        backend.store_relationship(&relationship).unwrap();
+        if relationship_type == "member_of" {
+            member_of.push(relationship.id);
+        }
+    }
+    // Same type, different source.
+    backend.store_relationship(&Relationship::new(other, "member_of".to_string(), ada)).unwrap();
+
+    let by_source_and_type = |name: &str| {
+        let found = backend.get_relationships_by_source_and_type(&ada, &name.into()).unwrap();
+        let mut ids: Vec<uuid::Uuid> = found.into_iter().map(|r| r.id).collect();
+        ids.sort();
+        ids
+    };
+    member_of.sort();
+    assert_eq!(by_source_and_type("member_of"), member_of);
+    assert_eq!(by_source_and_type("member").len(), 8);
+    assert_eq!(by_source_and_type("member_of:past").len(), 7);
+    assert!(by_source_and_type("works_for").is_empty());
+
+    // Deleting a relationship removes its composite entry too.
+    backend.delete_ralationship(&member_of[0]).unwrap();
+    assert_eq!(by_source_and_type("member_of"), member_of[1..].to_vec());
+    assert_eq!(backend.load_source_type_index().unwrap().len(), 30);
+}
+
+/// Leaves source and target index entries behind for a relationship that was never stored,
+/// as a crash between the two writes of a delete would.
+fn orphan_entries(backend: &RocksBackend, source: uuid::Uuid) -> uuid::Uuid {
+    use mnemonic_core::storage::keys;
+
+    let ghost = uuid::Uuid::new_v4();
+    let mut batch = rocksdb::WriteBatch::default();
+    backend.store_relationship_index_entry(keys::INDEX_SOURCE, &source, &ghost, &mut batch).unwrap();
+    backend
+        .store_relationship_index_entry(keys::INDEX_TARGET, &uuid::Uuid::new_v4(), &ghost, &mut batch)
+        .unwrap();
+    backend.db.write(batch).unwrap();
+    ghost
+}
+
+#[test]
+fn test_reads_skip_orphaned_index_entries_and_repair_them_in_the_background() {
+    use mnemonic_core::storage::keys;
+
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    let real = Relationship::new(uuid::Uuid::new_v4(), "knows".to_string(), uuid::Uuid::new_v4());
+    backend.store_relationship(&real).unwrap();
+    let ghost = orphan_entries(&backend, real.source);
+
+    assert_eq!(backend.get_relationships_by_source(&real.source).unwrap(), vec![real.clone()]);
+    let mut waited = 0;
+    while backend.load_relationship_index(keys::INDEX_SOURCE).unwrap().len() > 1 {
+        assert!(waited < 200, "the orphaned entry was never repaired");
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        waited += 1;
+    }
+    assert_eq!(backend.load_relationship_index(keys::INDEX_SOURCE).unwrap(), vec![(real.source, real.id)]);
+
+    // Reads only see the source index, so the target entry waits for a scrub.
+    let targets = backend.load_relationship_index(keys::INDEX_TARGET).unwrap();
+    assert!(targets.iter().any(|(_, rel_id)| *rel_id == ghost));
+}
+
+#[test]
+fn test_scrub_indices_deletes_orphaned_entries_eagerly() {
+    use mnemonic_core::storage::keys;
+
+    let dir = tempdir().unwrap();
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    let real = Relationship::new(uuid::Uuid::new_v4(), "knows".to_string(), uuid::Uuid::new_v4());
+    backend.store_relationship(&real).unwrap();
+    orphan_entries(&backend, real.source);
+
+    assert_eq!(backend.scrub_indices().unwrap(), 2);
+    assert_eq!(backend.load_relationship_index(keys::INDEX_SOURCE).unwrap(), vec![(real.source, real.id)]);
+    assert_eq!(backend.load_relationship_index(keys::INDEX_TARGET).unwrap(), vec![(real.target, real.id)]);
+    assert_eq!(backend.scrub_indices().unwrap(), 0);
+    assert_eq!(backend.get_relationships_by_source(&real.source).unwrap(), vec![real]);
+}
+
+#[tokio::test]
+async fn test_concepts_written_before_expiry_existed_still_load() {
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::storage::keys;
+    use mnemonic_core::types::concept::{ConceptData, ConceptMetadata};
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    // The records as they were laid out before `expires_at`.
+    #[derive(Serialize)]
+    struct OldConcept {
+        id: Uuid,
+        data: ConceptData,
+        metadata: ConceptMetadata,
+    }
+    #[derive(Serialize)]
+    struct OldConceptVersion {
+        concept_id: Uuid,
+        version: u64,
+        data: ConceptData,
+        created_at: chrono::DateTime<chrono::Utc>,
+        created_by: Uuid,
+        deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+        deleted_by: Option<Uuid>,
+    }
+
+    let dir = tempdir().unwrap();
+    let id = Uuid::new_v4();
+    let data = ConceptData::Structured(json!({"name": "Ada"}).to_string().into());
+    {
+        let backend = RocksBackend::new(dir.path()).unwrap();
+        let db = &backend.db;
+        let cf = |name| db.cf_handle(name).unwrap();
+        let record = OldConcept { id, data: data.clone(), metadata: ConceptMetadata::default() };
+        db.put_cf(cf("concepts"), keys::item(keys::CONCEPT, &id), bincode::serialize(&record).unwrap())
+            .unwrap();
+        let version = OldConceptVersion {
+            concept_id: id,
+            version: 1,
+            data: data.clone(),
+            created_at: chrono::Utc::now() - chrono::Duration::seconds(1),
+            created_by: Uuid::new_v4(),
+            deleted_at: None,
+            deleted_by: None,
+        };
+        db.put_cf(cf("versions"), keys::version(keys::CONCEPT_VERSION, &id, 1), bincode::serialize(&version).unwrap())
+            .unwrap();
+        db.put_cf(cf("versions"), keys::item(keys::CONCEPT_HEAD, &id), bincode::serialize(&1u64).unwrap())
+            .unwrap();
+    }
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let concept = engine.get_concept(id).await.unwrap().unwrap();
+    assert_eq!((concept.data, concept.expires_at), (data.clone(), None));
+    let record = engine.backend().get_concept(&id).unwrap().unwrap();
+    assert_eq!((record.data, record.expires_at), (data, None));
+    assert_eq!(engine.backend().get_concepts(&[id]).unwrap()[0].as_ref().map(|c| c.id), Some(id));
+}
+
+#[tokio::test]
+async fn test_relationships_written_before_undirected_existed_still_load() {
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::storage::keys;
+    use mnemonic_core::types::relationship::RelationshipMetadata;
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    // The records as they were laid out before `directed`.
+    #[derive(Serialize)]
+    struct OldRelationship {
+        id: Uuid,
+        source: Uuid,
+        relationship_type: String,
+        target: Uuid,
+        metadata: RelationshipMetadata,
+    }
+    #[derive(Serialize)]
+    struct OldRelationshipVersion {
+        relationship_id: Uuid,
+        version: u64,
+        source: Uuid,
+        relationship_type: String,
+        target: Uuid,
+        created_at: chrono::DateTime<chrono::Utc>,
+        created_by: Uuid,
+        deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+        deleted_by: Option<Uuid>,
+    }
+
+    let dir = tempdir().unwrap();
+    let id = Uuid::new_v4();
+    let (ada, grace) = {
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        let ada = engine.store(json!({"name": "Ada"})).await.unwrap();
+        let grace = engine.store(json!({"name": "Grace"})).await.unwrap();
+        (ada, grace)
+    };
+    {
+        let backend = RocksBackend::new(dir.path()).unwrap();
+        let db = &backend.db;
+        let cf = |name| db.cf_handle(name).unwrap();
+        let record = OldRelationship {
+            id,
+            source: ada,
+            relationship_type: "knows".to_string(),
+            target: grace,
+            metadata: RelationshipMetadata::default(),
+        };
+        db.put_cf(cf("relationships"), keys::item(keys::RELATIONSHIP, &id), bincode::serialize(&record).unwrap())
+            .unwrap();
+        let version = OldRelationshipVersion {
+            relationship_id: id,
+            version: 1,
+            source: ada,
+            relationship_type: "knows".to_string(),
+            target: grace,
+            created_at: chrono::Utc::now(),
+            created_by: Uuid::new_v4(),
+            deleted_at: None,
+            deleted_by: None,
+        };
+        db.put_cf(
+            cf("versions"),
+            keys::version(keys::RELATIONSHIP_VERSION, &id, 1),
+            bincode::serialize(&version).unwrap(),
+        )
+        .unwrap();
+        db.put_cf(cf("versions"), keys::item(keys::RELATIONSHIP_HEAD, &id), bincode::serialize(&1u64).unwrap())
+            .unwrap();
+    }
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let outgoing = engine.retrieve_by_source(ada).await.unwrap();
+    assert_eq!(outgoing.iter().map(|r| (r.id, r.directed)).collect::<Vec<_>>(), vec![(id, true)]);
+    assert!(engine.retrieve_by_source(grace).await.unwrap().is_empty());
+    let record = engine.backend().get_relationship(&id).unwrap().unwrap();
+    assert_eq!((record.relationship_type.as_str(), record.directed), ("knows", true));
+}
+
+fn encrypted(key: Option<&mnemonic_core::storage::EncryptionKey>) -> mnemonic_core::graph::EngineConfig {
+    use mnemonic_core::storage::StorageOptions;
+    mnemonic_core::graph::EngineConfig {
+        storage: StorageOptions { encryption: key.cloned(), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_encrypted_concepts_round_trip_without_plaintext_on_disk() {
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::storage::EncryptionKey;
+    use mnemonic_core::types::concept::ConceptData;
+    use rocksdb::IteratorMode;
+
+    let dir = tempdir().unwrap();
+    let key = EncryptionKey::new(1, [7; 32]);
+    let (ada, lin) = {
+        let engine = GraphEngine::with_config(dir.path(), encrypted(Some(&key))).unwrap();
+        let ada = engine.store(json!({"name": "Ada", "secret": "analytical engine"})).await.unwrap();
+        let lin = engine.store(json!({"name": "Lin"})).await.unwrap();
+        engine.relate(ada, "knows", lin).await.unwrap();
+        (ada, lin)
+    };
+
+    // Neither head records nor versions hold the payload in the clear; ids and types do.
+    let backend = RocksBackend::with_options(dir.path(), &encrypted(Some(&key)).storage).unwrap();
+    for name in ["concepts", "versions", "relationships"] {
+        let cf = backend.db.cf_handle(name).unwrap();
+        for item in backend.db.iterator_cf(cf, IteratorMode::Start) {
+            let (_, value) = item.unwrap();
+            assert!(!value.windows(10).any(|window| window == b"analytical"), "plaintext in {}", name);
+        }
+    }
+    drop(backend);
+
+    let engine = GraphEngine::with_config(dir.path(), encrypted(Some(&key))).unwrap();
+    let concept = engine.get_concept(ada).await.unwrap().unwrap();
+    let data = json!({"name": "Ada", "secret": "analytical engine"}).to_string();
+    assert_eq!(concept.data, ConceptData::Structured(data.into()));
+    assert_eq!(engine.concept_history(ada).await.unwrap().len(), 1);
+    assert_eq!(engine.backend().get_concepts(&[lin]).unwrap()[0].as_ref().map(|c| c.id), Some(lin));
+}
+
+#[tokio::test]
+async fn test_an_encrypted_database_refuses_a_missing_or_wrong_key() {
+    use mnemonic_core::error::MnemonicError;
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::storage::EncryptionKey;
+
+    let dir = tempdir().unwrap();
+    let key = EncryptionKey::new(1, [7; 32]);
+    GraphEngine::with_config(dir.path(), encrypted(Some(&key)))
+        .unwrap()
+        .store(json!({"name": "Ada"}))
+        .await
+        .unwrap();
+
+    let refused = |config| match GraphEngine::with_config(dir.path(), config) {
+        Err(MnemonicError::Encryption(message)) => message,
+        Err(other) => panic!("expected an encryption error, got {}", other),
+        Ok(_) => panic!("opened an encrypted database with the wrong key"),
+    };
+    assert!(refused(encrypted(None)).contains("encrypted"));
+    assert!(refused(encrypted(Some(&EncryptionKey::new(1, [8; 32])))).contains("does not open"));
+    assert!(refused(encrypted(Some(&EncryptionKey::new(2, [7; 32])))).contains("key 1"));
+}
+
+#[tokio::test]
+async fn test_rotating_the_key_rewrites_every_concept_record() {
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::storage::EncryptionKey;
+    use mnemonic_core::types::concept::ConceptData;
+
+    let dir = tempdir().unwrap();
+    let (old, new) = (EncryptionKey::new(1, [7; 32]), EncryptionKey::new(2, [9; 32]));
+    let id = {
+        // Written in the clear first, then encrypted, so there are records of both kinds.
+        let engine = GraphEngine::new(dir.path()).unwrap();
+        engine.store(json!({"name": "Ada"})).await.unwrap()
+    };
+    let report = RocksBackend::rotate_encryption_key(dir.path(), None, Some(&old)).unwrap();
+    assert_eq!((report.concepts, report.concept_versions), (1, 1));
+    GraphEngine::with_config(dir.path(), encrypted(Some(&old)))
+        .unwrap()
+        .store(json!({"name": "Lin"}))
+        .await
+        .unwrap();
+
+    let report = RocksBackend::rotate_encryption_key(dir.path(), Some(&old), Some(&new)).unwrap();
+    assert_eq!((report.concepts, report.concept_versions), (2, 2));
+    assert!(GraphEngine::with_config(dir.path(), encrypted(Some(&old))).is_err());
+
+    let engine = GraphEngine::with_config(dir.path(), encrypted(Some(&new))).unwrap();
+    let ada = ConceptData::Structured(json!({"name": "Ada"}).to_string().into());
+    assert_eq!(engine.get_concept(id).await.unwrap().unwrap().data, ada);
+    assert_eq!(engine.count_concepts().await.unwrap(), 2);
+    drop(engine);
+
+    RocksBackend::rotate_encryption_key(dir.path(), Some(&new), None).unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(engine.count_concepts().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_migrating_records_rewrites_unversioned_ones_in_the_latest_format() {
+    use mnemonic_core::graph::GraphEngine;
+    use mnemonic_core::storage::codec::{self, RecordFormat};
+    use mnemonic_core::storage::keys;
+    use rocksdb::IteratorMode;
+
+    let dir = tempdir().unwrap();
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    let a = engine.store(json!({"name": "Ada"})).await.unwrap();
+    let b = engine.store(json!({"name": "Lin"})).await.unwrap();
+    engine.relate(a, "knows", b).await.unwrap();
+    drop(engine);
+
+    // Put the concepts back the way every record used to be written: raw bincode.
+    let backend = RocksBackend::new(dir.path()).unwrap();
+    for id in [a, b] {
+        let concept = backend.get_concept(&id).unwrap().unwrap();
+        let cf = backend.db.cf_handle("concepts").unwrap();
+        backend.db.put_cf(cf, keys::item(keys::CONCEPT, &id), bincode::serialize(&concept).unwrap()).unwrap();
+    }
+    let report = backend.migrate_records().unwrap();
+    assert_eq!(report.migrated, 2);
+    assert_eq!(backend.migrate_records().unwrap().migrated, 0);
+    for name in ["concepts", "versions"] {
+        let cf = backend.db.cf_handle(name).unwrap();
+        for item in backend.db.iterator_cf(cf, IteratorMode::Start) {
+            let (key, value) = item.unwrap();
+            if matches!(key[0], keys::CONCEPT | keys::CONCEPT_VERSION | keys::RELATIONSHIP_VERSION) {
+                assert_eq!(codec::format_of(&value), RecordFormat::Version(codec::latest_format(key[0])));
+            }
+        }
+    }
+    drop(backend);
+
+    let engine = GraphEngine::new(dir.path()).unwrap();
+    assert_eq!(engine.get_concept(a).await.unwrap().unwrap().id, a);
+    assert_eq!(engine.retrieve_by_source(a).await.unwrap().len(), 1);
+}
+
+#[test]
+fn test_statistics_are_sampled_and_slow_ops_counted_once_turned_on() {
+    use mnemonic_core::storage::StorageOptions;
+
+    let dir = tempdir().unwrap();
+    let plain = RocksBackend::new(dir.path()).unwrap();
+    let concept = Concept::new(json!({"name": "Ada"}));
+    plain.store_concept(&concept).unwrap();
+    plain.get_concept(&concept.id).unwrap();
+    assert_eq!(plain.sample_statistics().unwrap(), None);
+    let stats = plain.stats(false).unwrap();
+    assert_eq!((stats.rocksdb, stats.slow_ops), (None, 0));
+    drop(plain);
+
+    // A zero threshold makes every timed operation "slow".
+    let options = StorageOptions {
+        statistics_interval_secs: Some(60),
+        slow_op_threshold_ms: Some(0),
+        ..Default::default()
+    };
+    let backend = RocksBackend::with_options(dir.path(), &options).unwrap();
+    let before = backend.metrics().slow_ops.get();
+    assert!(backend.get_concept(&concept.id).unwrap().is_some());
+    assert!(backend.metrics().slow_ops.get() > before);
+
+    let sample = backend.sample_statistics().unwrap().unwrap();
+    assert!(sample.block_cache_hit_rate.is_none_or(|rate| (0.0..=1.0).contains(&rate)));
+    let stats = backend.stats(false).unwrap();
+    assert!(stats.rocksdb.is_some());
+    assert_eq!(stats.slow_ops, backend.metrics().slow_ops.get());
+}